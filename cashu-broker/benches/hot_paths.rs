@@ -0,0 +1,220 @@
+//! Benchmarks for the swap-critical path
+//!
+//! Covers adaptor signature operations, key tweaking, proof selection with
+//! each strategy and end-to-end quote creation. Mint I/O is replaced by
+//! synthetic proofs loaded straight into the liquidity manager.
+//!
+//! Run with: cargo bench --bench hot_paths
+
+use cashu_broker::adaptor::AdaptorContext;
+use cashu_broker::liquidity::LiquidityManager;
+use cashu_broker::selection::SelectionStrategy;
+use cashu_broker::swap::SwapCoordinator;
+use cashu_broker::{BrokerConfig, MintConfig, QuoteType, SwapRequest};
+use cdk::nuts::{Id, Proof, Proofs, SecretKey};
+use cdk::secret::Secret;
+use cdk::Amount;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::str::FromStr;
+
+const MINT_A: &str = "http://mint-a.bench";
+const MINT_B: &str = "http://mint-b.bench";
+
+fn mints() -> Vec<MintConfig> {
+    vec![
+        MintConfig {
+            mint_url: MINT_A.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        },
+        MintConfig {
+            mint_url: MINT_B.to_string(),
+            name: "Mint B".to_string(),
+            unit: "sat".to_string(),
+        },
+    ]
+}
+
+/// Build `count` unsigned proofs with power-of-two amounts
+fn synthetic_proofs(count: usize) -> Proofs {
+    let keyset_id = Id::from_str("009a1f293253e41e").expect("valid keyset id");
+
+    (0..count)
+        .map(|i| {
+            Proof::new(
+                Amount::from(1u64 << (i % 10)),
+                keyset_id,
+                Secret::generate(),
+                SecretKey::generate().public_key(),
+            )
+        })
+        .collect()
+}
+
+fn bench_adaptor(c: &mut Criterion) {
+    let ctx = AdaptorContext::new();
+    let signing_key = ctx.generate_adaptor_secret();
+    let public_key = ctx.adaptor_point_from_secret(&signing_key);
+    let adaptor_secret = ctx.generate_adaptor_secret();
+    let adaptor_point = ctx.adaptor_point_from_secret(&adaptor_secret);
+    let message = b"quote-id|mint-a|mint-b|1000";
+
+    let encrypted = ctx
+        .create_encrypted_signature(&signing_key, &adaptor_point, message)
+        .expect("encrypted signature");
+    let decrypted = ctx
+        .decrypt_signature(&adaptor_secret, encrypted.clone())
+        .expect("decrypted signature");
+
+    let mut group = c.benchmark_group("adaptor");
+
+    group.bench_function("create_encrypted_signature", |b| {
+        b.iter(|| {
+            ctx.create_encrypted_signature(
+                black_box(&signing_key),
+                black_box(&adaptor_point),
+                black_box(message),
+            )
+        })
+    });
+
+    group.bench_function("verify_encrypted_signature", |b| {
+        b.iter(|| {
+            ctx.verify_encrypted_signature(
+                black_box(&public_key),
+                black_box(&adaptor_point),
+                black_box(message),
+                black_box(&encrypted),
+            )
+        })
+    });
+
+    group.bench_function("decrypt_signature", |b| {
+        b.iter(|| ctx.decrypt_signature(black_box(&adaptor_secret), encrypted.clone()))
+    });
+
+    group.bench_function("recover_adaptor_secret", |b| {
+        b.iter(|| {
+            ctx.recover_adaptor_secret(
+                black_box(&adaptor_point),
+                black_box(&encrypted),
+                black_box(&decrypted),
+            )
+        })
+    });
+
+    // NUT-11 witness signatures, one per proof of a swap
+    let proof_secret = Secret::generate().to_string();
+    let witness = ctx
+        .create_encrypted_witness(&signing_key, &adaptor_point, &proof_secret)
+        .expect("encrypted witness");
+
+    group.bench_function("create_encrypted_witness", |b| {
+        b.iter(|| {
+            ctx.create_encrypted_witness(
+                black_box(&signing_key),
+                black_box(&adaptor_point),
+                black_box(&proof_secret),
+            )
+        })
+    });
+
+    group.bench_function("verify_encrypted_witness", |b| {
+        b.iter(|| {
+            ctx.verify_encrypted_witness(
+                black_box(&public_key),
+                black_box(&adaptor_point),
+                black_box(&proof_secret),
+                black_box(&witness),
+            )
+        })
+    });
+
+    group.bench_function("tweak_public_key", |b| {
+        b.iter(|| ctx.tweak_public_key(black_box(&public_key), black_box(&adaptor_point)))
+    });
+
+    group.bench_function("add_scalars", |b| {
+        b.iter(|| ctx.add_scalars(black_box(&signing_key), black_box(&adaptor_secret)))
+    });
+
+    group.finish();
+}
+
+fn bench_select_proofs(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let mut group = c.benchmark_group("select_proofs");
+
+    for size in [10usize, 100, 1_000, 5_000, 10_000] {
+        let manager = runtime.block_on(async {
+            let manager = LiquidityManager::new(mints()).await.expect("liquidity manager");
+            manager.set_require_dleq(false);
+            manager
+                .add_proofs(MINT_A, synthetic_proofs(size))
+                .await
+                .expect("add proofs");
+            manager
+        });
+
+        // Ask for roughly a third of the balance so selection has real work to do
+        let target = runtime.block_on(manager.get_balance(MINT_A)) / 3;
+
+        for strategy in [SelectionStrategy::LargestFirst, SelectionStrategy::BranchAndBound] {
+            manager.set_selection_strategy(strategy);
+            let id = BenchmarkId::new(strategy.to_string(), size);
+            group.bench_with_input(id, &target, |b, &target| {
+                b.to_async(&runtime)
+                    .iter(|| async { manager.select_proofs(MINT_A, black_box(target)).await })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_create_quote(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+
+    let config = BrokerConfig {
+        mints: mints(),
+        max_swap_amount: 100_000,
+        ..Default::default()
+    };
+
+    let manager = runtime.block_on(async {
+        let manager = LiquidityManager::new(config.mints.clone())
+            .await
+            .expect("liquidity manager");
+        manager.set_require_dleq(false);
+        manager
+            .add_proofs(MINT_B, synthetic_proofs(1_000))
+            .await
+            .expect("add proofs");
+        manager
+    });
+    let coordinator = SwapCoordinator::new(config);
+
+    c.bench_function("create_quote", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let request = SwapRequest {
+                client_id: None,
+                from_mint: MINT_A.to_string(),
+                to_mint: MINT_B.to_string(),
+                amount: 1_000,
+                quote_type: QuoteType::ExactIn,
+                client_public_key: None,
+                adaptor_point: None,
+            };
+            let quote = coordinator
+                .create_quote(black_box(request), &manager)
+                .await
+                .expect("quote");
+            // Free the reservation so liquidity doesn't run out across iterations
+            manager.release(&quote.quote_id).await;
+            quote
+        })
+    });
+}
+
+criterion_group!(benches, bench_adaptor, bench_select_proofs, bench_create_quote);
+criterion_main!(benches);
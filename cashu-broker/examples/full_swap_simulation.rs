@@ -0,0 +1,227 @@
+//! Example: Full atomic swap simulation with two wallets
+//!
+//! This example runs a complete swap between Charlie (the broker) and Bob:
+//! 1. Charlie starts with liquidity on two mints
+//! 2. Bob funds his Mint B wallet and requests a quote for Mint B → Mint A
+//! 3. Charlie locks the output on Mint A to Bob's tweaked key (P_bob + T)
+//! 4. Bob locks his input on Mint B to Charlie's tweaked key (P_charlie + T)
+//! 5. Charlie claims Bob's tokens with Bob's decrypted signature, from which
+//!    Bob recovers the adaptor secret t and checks that tG == T
+//! 6. Bob unlocks his Mint A tokens with sk_bob + t
+//!
+//! Both mints are in-process `testkit::MockMint`s, so the example runs on its
+//! own: cargo run --example full_swap_simulation
+
+use cashu_broker::adaptor::AdaptorContext;
+use cashu_broker::swap::swap_transcript;
+use cashu_broker::testkit::MockMint;
+use cashu_broker::{Broker, BrokerConfig, MintConfig, QuoteType, SwapRequest};
+use cdk::amount::SplitTarget;
+use cdk::nuts::{Proofs, PublicKey, SecretKey, SpendingConditions};
+use cdk::wallet::{ReceiveOptions, SendOptions, Wallet};
+use cdk::Amount;
+use schnorr_fun::fun::{Point, Scalar};
+
+/// Amount Bob swaps from Mint B to Mint A
+const SWAP_AMOUNT: u64 = 8;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // Initialize logging
+    tracing_subscriber::fmt::init();
+
+    println!("\n╔═══════════════════════════════════════════════════════════════╗");
+    println!("║          CASHU ATOMIC SWAP - FULL SIMULATION                 ║");
+    println!("╚═══════════════════════════════════════════════════════════════╝\n");
+
+    let adaptor_ctx = AdaptorContext::new();
+
+    // ===== Mints =====
+
+    let mint_a = MockMint::start().await?;
+    let mint_b = MockMint::start().await?;
+
+    // ===== Charlie =====
+
+    let config = BrokerConfig {
+        mints: vec![
+            MintConfig {
+                mint_url: mint_a.url().to_string(),
+                name: "Mint A".to_string(),
+                unit: "sat".to_string(),
+            },
+            MintConfig {
+                mint_url: mint_b.url().to_string(),
+                name: "Mint B".to_string(),
+                unit: "sat".to_string(),
+            },
+        ],
+        fee_rate: 0.005,        // 0.5% fee
+        min_swap_amount: 1,
+        max_swap_amount: 10_000,
+        quote_expiry_seconds: 300, // 5 minutes
+        ..Default::default()
+    };
+
+    println!("🚀 Initializing Charlie (the broker)...\n");
+    let broker = Broker::new(config).await?;
+    broker.initialize(100).await?;
+    broker.print_status().await;
+
+    // ===== Bob =====
+
+    println!("👤 Setting up Bob's wallets...\n");
+    let bob_wallet_a = mint_a.wallet().await?;
+    let bob_wallet_b = mint_b.wallet().await?;
+
+    // Mock mints pay the invoice automatically
+    let funding_quote = bob_wallet_b.mint_quote(Amount::from(20), None).await?;
+    bob_wallet_b
+        .mint(&funding_quote.id, SplitTarget::default(), None)
+        .await?;
+    println!(
+        "   Bob funded his Mint B wallet: {} sats",
+        bob_wallet_b.total_balance().await?
+    );
+
+    let bob_secret = SecretKey::generate();
+    let bob_pubkey = bob_secret.public_key().to_bytes().to_vec();
+
+    // Step 1: Bob requests a quote
+    println!("\n📨 Step 1: Bob requests {} sats Mint B → Mint A", SWAP_AMOUNT);
+    let quote = broker
+        .request_quote(SwapRequest {
+            client_id: Some("bob".to_string()),
+            from_mint: mint_b.url().to_string(),
+            to_mint: mint_a.url().to_string(),
+            amount: SWAP_AMOUNT,
+            quote_type: QuoteType::ExactIn,
+            client_public_key: Some(bob_pubkey.clone()),
+            adaptor_point: None,
+        })
+        .await?;
+
+    println!(
+        "   Quote {}: pay {} sats, receive {} sats (fee {})",
+        quote.quote_id, quote.input_amount, quote.output_amount, quote.fee
+    );
+    println!("   Adaptor point T: {}", hex::encode(&quote.adaptor_point));
+
+    // Step 2: Charlie locks the output to Bob's tweaked key
+    println!(
+        "\n🔒 Step 2: Charlie locks {} sats on Mint A to P_bob + T",
+        quote.output_amount
+    );
+    let prepared = broker.accept_quote(&quote.quote_id, &bob_pubkey).await?;
+
+    // Bob checks Charlie's adaptor signature over the swap transcript
+    let broker_point = Point::from_bytes(
+        quote
+            .broker_public_key
+            .clone()
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Broker pubkey has the wrong length"))?,
+    )
+    .ok_or_else(|| anyhow::anyhow!("Invalid broker pubkey"))?;
+    let adaptor_point = Point::from_bytes(
+        quote
+            .adaptor_point
+            .clone()
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Adaptor point has the wrong length"))?,
+    )
+    .ok_or_else(|| anyhow::anyhow!("Invalid adaptor point"))?;
+    adaptor_ctx.verify_encrypted_signature(
+        &broker_point,
+        &adaptor_point,
+        &swap_transcript(&quote, &bob_pubkey),
+        &prepared.encrypted_signature,
+    )?;
+    println!("   Encrypted signature verified: {}", prepared.encrypted_signature_hex());
+    let locked_for_bob = prepared.proofs;
+
+    // Step 3: Bob locks his input to Charlie's tweaked key
+    println!(
+        "\n🔒 Step 3: Bob locks {} sats on Mint B to P_charlie + T",
+        quote.input_amount
+    );
+    let broker_tweaked = quote
+        .tweaked_pubkey
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Quote is missing the broker's tweaked pubkey"))?;
+    let locked_for_charlie =
+        lock_to_pubkey(&bob_wallet_b, quote.input_amount, &broker_tweaked).await?;
+
+    // Step 4: Bob authorizes the swap with his own adaptor signature under T,
+    // and Charlie claims Bob's tokens once it decrypts under the adaptor point
+    println!("\n⚡ Step 4: Charlie claims Bob's tokens on Mint B");
+    let bob_scalar = Scalar::from_bytes(bob_secret.to_secret_bytes())
+        .and_then(|s| s.non_zero())
+        .ok_or_else(|| anyhow::anyhow!("Invalid client key"))?;
+    let bob_signature = adaptor_ctx.create_encrypted_signature(
+        &bob_scalar,
+        &adaptor_point,
+        &swap_transcript(&quote, &bob_pubkey),
+    )?;
+    let decrypted_signature = broker
+        .complete_swap(&quote.quote_id, locked_for_charlie, &bob_signature)
+        .await?;
+
+    // Step 5: Bob recovers the adaptor secret from his encrypted signature
+    // and the decrypted one Charlie had to reveal to claim
+    println!("\n🔑 Step 5: Bob recovers the adaptor secret");
+    let adaptor_secret =
+        adaptor_ctx.recover_adaptor_secret(&adaptor_point, &bob_signature, &decrypted_signature)?;
+
+    let recovered_point = adaptor_ctx.adaptor_point_from_secret(&adaptor_secret);
+    if recovered_point.to_bytes().to_vec() != quote.adaptor_point {
+        anyhow::bail!("Recovered secret does not match the quote's adaptor point");
+    }
+    println!("   tG == T ✓");
+
+    // Step 6: Bob unlocks his Mint A tokens with sk_bob + t
+    println!("\n🔓 Step 6: Bob unlocks his tokens on Mint A");
+    let unlock_scalar = adaptor_ctx.add_scalars(&bob_scalar, &adaptor_secret);
+    let unlock_key = SecretKey::from_slice(&unlock_scalar.to_bytes())?;
+
+    let received = bob_wallet_a
+        .receive_proofs(
+            locked_for_bob,
+            ReceiveOptions {
+                p2pk_signing_keys: vec![unlock_key],
+                ..Default::default()
+            },
+            None,
+        )
+        .await?;
+    println!("   Bob received {} sats on Mint A", received);
+
+    // Final balances
+    println!("\n📊 Final balances:");
+    println!("   Bob on Mint A: {} sats", bob_wallet_a.total_balance().await?);
+    println!("   Bob on Mint B: {} sats", bob_wallet_b.total_balance().await?);
+    broker.print_status().await;
+
+    println!("✅ Swap completed atomically! 🎉\n");
+
+    Ok(())
+}
+
+/// Send `amount` from a wallet as proofs locked to a compressed pubkey
+async fn lock_to_pubkey(wallet: &Wallet, amount: u64, pubkey: &[u8]) -> anyhow::Result<Proofs> {
+    let pubkey = PublicKey::from_slice(pubkey)?;
+
+    let prepared = wallet
+        .prepare_send(
+            Amount::from(amount),
+            SendOptions {
+                conditions: Some(SpendingConditions::new_p2pk(pubkey, None)),
+                ..Default::default()
+            },
+        )
+        .await?;
+    let token = prepared.confirm(None).await?;
+
+    let keysets = wallet.get_mint_keysets().await?;
+    Ok(token.proofs(&keysets)?)
+}
@@ -0,0 +1,10 @@
+-- Per-quote private keys, so accepted swaps survive broker restarts
+-- Secret columns hold ChaCha20-Poly1305 ciphertext (nonce || ciphertext)
+
+CREATE TABLE IF NOT EXISTS quote_keys (
+    quote_id TEXT PRIMARY KEY,
+    broker_swap_key BLOB NOT NULL,  -- Encrypted broker swap key scalar
+    adaptor_secret BLOB NOT NULL,  -- Encrypted adaptor secret scalar
+    refund_at INTEGER,  -- Unix time the broker's outputs become refundable (set on accept)
+    created_at TEXT NOT NULL  -- ISO 8601 timestamp
+);
@@ -0,0 +1,3 @@
+-- Exchange rate used for cross-unit swaps (target units per source unit, after spread)
+
+ALTER TABLE quotes ADD COLUMN exchange_rate REAL;
@@ -0,0 +1,11 @@
+-- API keys for privileged public routes (/quotes, /liquidity, /metrics)
+-- Only the SHA-256 hash of each key is stored; the key itself is shown once on creation
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,  -- Operator-chosen label
+    key_hash TEXT NOT NULL UNIQUE,  -- Hex SHA-256 of the key
+    created_at TEXT NOT NULL,  -- ISO 8601 timestamp
+    last_used_at TEXT,
+    revoked_at TEXT
+);
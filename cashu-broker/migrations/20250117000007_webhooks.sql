@@ -0,0 +1,22 @@
+-- Webhook callbacks on quote status changes
+-- Deliveries are queued when a quote with a callback_url changes status and
+-- retried with backoff until delivered or out of attempts
+
+ALTER TABLE quotes ADD COLUMN callback_url TEXT;
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id TEXT PRIMARY KEY,
+    quote_id TEXT NOT NULL,
+    event TEXT NOT NULL,  -- accepted, completed, failed, expired
+    url TEXT NOT NULL,
+    payload TEXT NOT NULL,  -- JSON body, signed as-is
+    status TEXT NOT NULL DEFAULT 'pending',  -- pending, delivered, failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL,  -- ISO 8601 timestamp
+    last_error TEXT,
+    created_at TEXT NOT NULL,
+    delivered_at TEXT,
+    FOREIGN KEY (quote_id) REFERENCES quotes(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at);
@@ -0,0 +1,3 @@
+-- Expected mint input fees (NUT-02) included in a quote, in the source mint's unit
+
+ALTER TABLE quotes ADD COLUMN mint_fee INTEGER NOT NULL DEFAULT 0;
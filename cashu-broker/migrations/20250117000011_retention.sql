@@ -0,0 +1,35 @@
+-- Data retention
+-- Settled quotes past the retention period are copied here without their
+-- proofs, keys or signatures when pruned in archive mode
+
+CREATE TABLE IF NOT EXISTS quotes_archive (
+    id TEXT PRIMARY KEY,
+    source_mint TEXT NOT NULL,
+    target_mint TEXT NOT NULL,
+    amount_in INTEGER NOT NULL,
+    amount_out INTEGER NOT NULL,
+    fee INTEGER NOT NULL,
+    fee_rate REAL NOT NULL,
+    mint_fee INTEGER NOT NULL,
+    exchange_rate REAL,
+    status TEXT NOT NULL,  -- completed, expired or failed
+    created_at TEXT NOT NULL,  -- ISO 8601 timestamp
+    accepted_at TEXT,
+    completed_at TEXT,
+    swap_completed_at TEXT,  -- When the swap record was settled
+    user_pubkey TEXT,
+    error_message TEXT,
+    archived_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_quotes_archive_created_at ON quotes_archive(created_at);
+
+-- Daily totals of pruned liquidity events, per mint and event type
+CREATE TABLE IF NOT EXISTS liquidity_event_summaries (
+    mint_url TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    day TEXT NOT NULL,  -- YYYY-MM-DD (UTC)
+    event_count INTEGER NOT NULL,
+    total_amount INTEGER NOT NULL,
+    PRIMARY KEY (mint_url, event_type, day)
+);
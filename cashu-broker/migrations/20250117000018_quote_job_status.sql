@@ -0,0 +1,4 @@
+-- Status of the latest swap step queued for a quote (accept or complete):
+-- queued, running, done or failed. NULL until the first step is queued.
+
+ALTER TABLE quotes ADD COLUMN job_status TEXT;
@@ -0,0 +1,11 @@
+-- What the broker needs to recover a swap interrupted between its own mint
+-- calls and the client hearing back. locked_proofs and encrypted_signature
+-- are written as soon as the broker's outputs are locked, before the accept
+-- is recorded, so outputs the client never got can be handed out again or
+-- refunded. client_signature is written once the client's adaptor signature
+-- verifies at complete, before the broker claims the client's proofs: from
+-- then on the claim is retried rather than the outputs refunded.
+
+ALTER TABLE quote_keys ADD COLUMN locked_proofs TEXT;  -- JSON, encrypted
+ALTER TABLE quote_keys ADD COLUMN encrypted_signature TEXT;  -- Broker's, hex
+ALTER TABLE quote_keys ADD COLUMN client_signature TEXT;  -- Client's encrypted, hex
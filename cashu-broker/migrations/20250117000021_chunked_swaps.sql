@@ -0,0 +1,29 @@
+-- Swaps split into chunks, each run as its own quote (see chunked.rs)
+-- Amounts are what the client pays (exact in); the planned chunk amounts
+-- are a JSON array.
+-- A chunked swap is stopped by its client, or finished once every chunk is
+-- completed.
+
+CREATE TABLE IF NOT EXISTS chunked_swaps (
+    id TEXT PRIMARY KEY,
+    source_mint TEXT NOT NULL,
+    target_mint TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    chunks TEXT NOT NULL,  -- JSON array of amounts
+    user_pubkey TEXT NOT NULL,
+    callback_url TEXT,  -- Given to every chunk's quote
+    created_at TEXT NOT NULL,  -- ISO 8601 timestamp
+    stopped_at TEXT  -- ISO 8601 timestamp
+);
+
+-- Quotes issued for each chunk, in order; a chunk whose quote expired or
+-- failed gets another one
+CREATE TABLE IF NOT EXISTS swap_chunks (
+    quote_id TEXT PRIMARY KEY,
+    chunked_swap_id TEXT NOT NULL,
+    chunk_index INTEGER NOT NULL,
+    created_at TEXT NOT NULL,  -- ISO 8601 timestamp
+    FOREIGN KEY (chunked_swap_id) REFERENCES chunked_swaps(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_swap_chunks_chunked_swap_id ON swap_chunks(chunked_swap_id);
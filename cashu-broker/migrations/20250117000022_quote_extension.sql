@@ -0,0 +1,4 @@
+-- When a client extended a pending quote's expiry. A quote is extended at
+-- most once, so a quote with extended_at set can't be extended again.
+
+ALTER TABLE quotes ADD COLUMN extended_at TEXT;
@@ -0,0 +1,11 @@
+-- Mint quotes the broker paid over Lightning while rebalancing but couldn't
+-- mint yet, e.g. because the mint was slow to see the payment. They are
+-- retried on every rebalance round until the ecash is minted.
+
+CREATE TABLE IF NOT EXISTS pending_mints (
+    quote_id TEXT PRIMARY KEY,    -- Mint quote on mint_url
+    mint_url TEXT NOT NULL,
+    amount INTEGER NOT NULL,      -- Amount in sats
+    from_mint TEXT NOT NULL,      -- Mint whose liquidity paid the invoice
+    created_at TEXT NOT NULL      -- ISO 8601 timestamp
+);
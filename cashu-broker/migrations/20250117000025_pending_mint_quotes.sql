@@ -0,0 +1,5 @@
+-- The wallet's copy of each pending mint quote (JSON, encrypted like the quote
+-- keys). Wallets are rebuilt empty on restart, so the quote is handed back to
+-- the wallet before minting it.
+
+ALTER TABLE pending_mints ADD COLUMN quote TEXT;
@@ -0,0 +1,10 @@
+-- Liquidity snapshots written on checkpoint (SIGHUP)
+-- Holds the latest known balance and proofs per mint
+
+CREATE TABLE IF NOT EXISTS liquidity_snapshots (
+    mint_url TEXT PRIMARY KEY,
+    balance BIGINT NOT NULL,  -- Balance in sats at snapshot time
+    proof_count BIGINT NOT NULL,  -- Number of proofs held
+    proofs TEXT NOT NULL,  -- JSON array of proofs
+    updated_at TEXT NOT NULL  -- ISO 8601 timestamp
+);
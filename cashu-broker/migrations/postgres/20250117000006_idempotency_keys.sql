@@ -0,0 +1,12 @@
+-- Idempotency-Key dedup records for POST /quote/:id/accept and /complete
+-- A row without status_code is a request still in flight
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    scope TEXT NOT NULL,  -- Method and path, e.g. "POST /quote/<id>/accept"
+    key TEXT NOT NULL,  -- Client-chosen Idempotency-Key header
+    request_hash TEXT NOT NULL,  -- Hex SHA-256 of the request body
+    status_code BIGINT,  -- Stored response, NULL while in flight
+    response_body TEXT,
+    created_at TEXT NOT NULL,  -- ISO 8601 timestamp
+    PRIMARY KEY (scope, key)
+);
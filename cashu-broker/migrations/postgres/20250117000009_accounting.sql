@@ -0,0 +1,17 @@
+-- Accounting ledger
+-- One row per amount the broker earned or paid: broker fees and spread on
+-- swaps, mint input fees, and Lightning fees on rebalancing and withdrawals
+
+CREATE TABLE IF NOT EXISTS accounting (
+    id BIGSERIAL PRIMARY KEY,
+    kind TEXT NOT NULL CHECK(kind IN ('swap_fee', 'spread', 'mint_fee', 'lightning_fee')),
+    mint_url TEXT NOT NULL,
+    unit TEXT NOT NULL,  -- Unit of the mint the amount is held on
+    amount BIGINT NOT NULL,  -- Never negative; the kind says whether it was earned or paid
+    quote_id TEXT,  -- Swap the entry belongs to (nullable for rebalancing and withdrawals)
+    created_at TEXT NOT NULL,  -- ISO 8601 timestamp
+
+    FOREIGN KEY (quote_id) REFERENCES quotes(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_accounting_created_at ON accounting(created_at);
@@ -0,0 +1,17 @@
+-- Audit log of administrative actions
+-- One row per change made through the admin API, with the affected values
+-- before and after as JSON
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    actor TEXT NOT NULL,  -- Name given in X-Admin-Actor, or 'admin'
+    action TEXT NOT NULL,  -- e.g. 'set_fee_rate', 'withdraw_ecash'
+    target TEXT,  -- Mint URL, quote ID or API key ID acted on (nullable)
+    before_value TEXT,  -- JSON (nullable)
+    after_value TEXT,  -- JSON (nullable)
+    request_id TEXT,  -- X-Request-Id of the admin request
+    created_at TEXT NOT NULL  -- ISO 8601 timestamp
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at);
+CREATE INDEX IF NOT EXISTS idx_audit_log_action ON audit_log(action);
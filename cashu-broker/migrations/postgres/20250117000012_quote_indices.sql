@@ -0,0 +1,11 @@
+-- Composite indices for quote listing
+-- Listings filter on status or user_pubkey and page newest first by
+-- (created_at, id), so both columns follow the filter. The single-column
+-- status and source_mint indices are prefixes of the new ones.
+
+CREATE INDEX IF NOT EXISTS idx_quotes_status_created_at ON quotes(status, created_at, id);
+CREATE INDEX IF NOT EXISTS idx_quotes_user_pubkey_created_at ON quotes(user_pubkey, created_at, id);
+CREATE INDEX IF NOT EXISTS idx_quotes_mints ON quotes(source_mint, target_mint);
+
+DROP INDEX IF EXISTS idx_quotes_status;
+DROP INDEX IF EXISTS idx_quotes_source_mint;
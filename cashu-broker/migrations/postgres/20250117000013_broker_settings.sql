@@ -0,0 +1,8 @@
+-- Broker settings changed at runtime that survive a restart
+-- One row per setting, e.g. 'maintenance' = 'true'
+
+CREATE TABLE IF NOT EXISTS broker_settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL  -- ISO 8601 timestamp
+);
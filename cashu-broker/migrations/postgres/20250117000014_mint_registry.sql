@@ -0,0 +1,11 @@
+-- Mints added or removed through the admin API
+-- Applied on top of MINTS at startup and on every reload, so changes made at
+-- runtime survive a restart. One row per mint, holding its latest change.
+
+CREATE TABLE IF NOT EXISTS mint_registry (
+    mint_url TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    unit TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('added', 'removed')),
+    updated_at TEXT NOT NULL  -- ISO 8601 timestamp
+);
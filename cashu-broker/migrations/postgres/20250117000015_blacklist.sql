@@ -0,0 +1,12 @@
+-- Public keys and proofs the broker refuses to deal with
+-- Managed through the admin API. Public keys are compressed secp256k1 keys
+-- and proofs are identified by Y = hash_to_curve(secret), both as lowercase
+-- hex, as in NUT-07 state checks.
+
+CREATE TABLE IF NOT EXISTS blacklist (
+    kind TEXT NOT NULL CHECK (kind IN ('pubkey', 'proof')),
+    value TEXT NOT NULL,
+    reason TEXT,
+    created_at TEXT NOT NULL,  -- ISO 8601 timestamp
+    PRIMARY KEY (kind, value)
+);
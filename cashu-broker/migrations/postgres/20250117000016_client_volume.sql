@@ -0,0 +1,10 @@
+-- Completed swap volume per client public key, for volume discounts
+-- One row per key and day; the input amounts of swaps completed that day
+-- are added up.
+
+CREATE TABLE IF NOT EXISTS client_volume (
+    pubkey TEXT NOT NULL,  -- Compressed public key as lowercase hex
+    day TEXT NOT NULL,  -- YYYY-MM-DD (UTC)
+    amount BIGINT NOT NULL,
+    PRIMARY KEY (pubkey, day)
+);
@@ -0,0 +1,15 @@
+-- Source proofs held by in-flight swaps
+-- A proof is claimed by the quote it is posted to on accept, and released
+-- once that quote is completed, failed or expired, or its accept falls
+-- through. The primary key keeps two quotes from taking the same proof at
+-- once. Proofs are identified by Y = hash_to_curve(secret), compressed, as
+-- lowercase hex.
+
+CREATE TABLE IF NOT EXISTS proof_claims (
+    y TEXT PRIMARY KEY,
+    quote_id TEXT NOT NULL,
+    created_at TEXT NOT NULL,  -- ISO 8601 timestamp
+    FOREIGN KEY (quote_id) REFERENCES quotes(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_proof_claims_quote_id ON proof_claims(quote_id);
@@ -0,0 +1,6 @@
+-- Who claims first in a swap: 'broker_first' (the broker holds the adaptor
+-- secret) or 'client_first' (the client does, and reveals it by redeeming
+-- the broker's outputs). For client-first quotes quote_keys.adaptor_secret
+-- is empty until the broker recovers the secret from the client's witness.
+
+ALTER TABLE quotes ADD COLUMN claim_order TEXT NOT NULL DEFAULT 'broker_first';
@@ -0,0 +1,11 @@
+-- Leases that let several broker instances share one database. The holder
+-- of a lease owns what it names, e.g. 'mint:<url>' (quoting out of that
+-- mint's liquidity) or 'sweep:quotes' (a background sweep), until it expires.
+-- Holders renew their leases well before then.
+
+CREATE TABLE IF NOT EXISTS leases (
+    name TEXT PRIMARY KEY,
+    holder TEXT NOT NULL,         -- Instance ID of the broker holding it
+    expires_at BIGINT NOT NULL,   -- Unix time in milliseconds
+    acquired_at TEXT NOT NULL     -- ISO 8601 timestamp, kept across renewals
+);
@@ -0,0 +1,454 @@
+//! # Cashu Swap Protocol
+//!
+//! The client's side of a cashu-broker atomic swap, without networking,
+//! wallets or std, so that it builds for `wasm32` and browser wallets can
+//! swap with a broker:
+//!
+//! 1. [`generate_secret_key`] makes the one-time swap key whose
+//!    [`public_key`] goes in the quote request
+//! 2. before locking its input to the quote's tweaked key, the client checks
+//!    it with [`check_tweaked_key`]: `P_broker + T`
+//! 3. [`verify_encrypted_signature`] checks the broker's adaptor signature
+//!    over the [`swap_transcript`] when it accepts, and
+//!    [`create_encrypted_signature`] makes the client's own for completing
+//! 4. [`unlock_key`] checks the revealed adaptor secret `t` against `T` and
+//!    derives `sk_client + t`, which unlocks the broker's outputs;
+//!    [`decrypt_signature`] and [`recover_adaptor_secret`] cover the other
+//!    direction
+//!
+//! Keys are 32-byte scalars, points 33-byte compressed keys, and encrypted
+//! signatures the 65 bytes of [`encode_encrypted_signature`], all as the
+//! broker's API sends them in hex. The `wasm` feature exports these functions
+//! to JavaScript (see [`wasm`]). `cashu-broker` builds its own client and
+//! transcripts on this crate, so both sides agree byte for byte.
+//! [`vectors`] has fixed swaps worked through for checking other
+//! implementations against.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod vectors;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use schnorr_fun;
+
+use alloc::{format, vec::Vec};
+use core::fmt;
+use rand_core::{CryptoRng, RngCore};
+use schnorr_fun::{
+    adaptor::{Adaptor, EncryptedSign, EncryptedSignature},
+    fun::{g, KeyPair, Point, Scalar, G},
+    Message, Schnorr, Signature,
+};
+use secp256kfun::{marker::*, nonce};
+use sha2::Sha256;
+
+/// Length of a secret key or adaptor secret
+pub const SECRET_KEY_LEN: usize = 32;
+
+/// Length of a compressed public key or point
+pub const PUBLIC_KEY_LEN: usize = 33;
+
+/// Length of a decrypted (BIP-340) signature
+pub const SIGNATURE_LEN: usize = 64;
+
+/// Length of an encoded encrypted signature: R (32) || s_hat (32) || needs_negation (1)
+pub const ENCRYPTED_SIGNATURE_LEN: usize = 65;
+
+/// Tag of the swap transcripts adaptor signatures are made over
+const MESSAGE_TAG: &str = "cashu-swap";
+
+/// Errors of the swap protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// A key, point or secret that isn't one; names which
+    InvalidKey(&'static str),
+    /// An encrypted signature of the wrong length
+    InvalidLength { expected: usize, got: usize },
+    /// A malformed signature; says what is wrong with it
+    InvalidSignature(&'static str),
+    /// A well-formed signature that doesn't verify
+    VerificationFailed,
+    /// The quote's tweaked key is not `P_broker + T`
+    TweakMismatch,
+    /// The revealed adaptor secret is not the discrete log of `T`
+    SecretMismatch,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::InvalidKey(what) => write!(f, "Invalid {}", what),
+            ProtocolError::InvalidLength { expected, got } => write!(
+                f,
+                "Invalid encrypted signature length: expected {}, got {}",
+                expected, got
+            ),
+            ProtocolError::InvalidSignature(what) => write!(f, "Invalid {}", what),
+            ProtocolError::VerificationFailed => {
+                f.write_str("Encrypted signature verification failed")
+            }
+            ProtocolError::TweakMismatch => {
+                f.write_str("Tweaked pubkey is not the broker pubkey plus the adaptor point")
+            }
+            ProtocolError::SecretMismatch => {
+                f.write_str("Revealed secret does not match the adaptor point")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProtocolError {}
+
+pub type Result<T> = core::result::Result<T, ProtocolError>;
+
+/// The terms of a quote that the swap transcript commits to
+#[derive(Debug, Clone, Copy)]
+pub struct SwapTerms<'a> {
+    pub quote_id: &'a str,
+    pub source_mint: &'a str,
+    pub target_mint: &'a str,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub adaptor_point: &'a [u8],
+}
+
+/// Canonical swap transcript signed by both adaptor signatures
+///
+/// Binds the quote terms to the client key the broker locked funds to:
+/// `quote_id|source_mint|target_mint|amount_in|amount_out|adaptor_point|client_pubkey`
+pub fn swap_transcript(terms: &SwapTerms<'_>, client_pubkey: &[u8]) -> Vec<u8> {
+    format!(
+        "{}|{}|{}|{}|{}|{}|{}",
+        terms.quote_id,
+        terms.source_mint,
+        terms.target_mint,
+        terms.amount_in,
+        terms.amount_out,
+        hex::encode(terms.adaptor_point),
+        hex::encode(client_pubkey),
+    )
+    .into_bytes()
+}
+
+/// Generate a one-time swap key
+pub fn generate_secret_key<R: RngCore + CryptoRng>(rng: &mut R) -> [u8; SECRET_KEY_LEN] {
+    Scalar::random(rng).to_bytes()
+}
+
+/// Compressed public key of `secret_key`
+pub fn public_key(secret_key: &[u8]) -> Result<[u8; PUBLIC_KEY_LEN]> {
+    let secret_key = scalar(secret_key, "secret key")?;
+    Ok(g!(secret_key * G).normalize().to_bytes())
+}
+
+/// Tweak a public key by a point: `P + T`
+pub fn tweak_public_key(public_key: &[u8], tweak: &[u8]) -> Result<[u8; PUBLIC_KEY_LEN]> {
+    let public_key = point(public_key, "public key")?;
+    let tweak = point(tweak, "adaptor point")?;
+    g!(public_key + tweak)
+        .normalize()
+        .non_zero()
+        .map(|tweaked| tweaked.to_bytes())
+        .ok_or(ProtocolError::InvalidKey("tweaked pubkey"))
+}
+
+/// Check that a quote's tweaked key is `P_broker + T` before locking to it
+pub fn check_tweaked_key(
+    broker_pubkey: &[u8],
+    adaptor_point: &[u8],
+    tweaked_pubkey: &[u8],
+) -> Result<()> {
+    if tweak_public_key(broker_pubkey, adaptor_point)?[..] == *tweaked_pubkey {
+        Ok(())
+    } else {
+        Err(ProtocolError::TweakMismatch)
+    }
+}
+
+/// Create an adaptor signature over `transcript`, encrypted under `encryption_point`
+pub fn create_encrypted_signature(
+    secret_key: &[u8],
+    encryption_point: &[u8],
+    transcript: &[u8],
+) -> Result<[u8; ENCRYPTED_SIGNATURE_LEN]> {
+    let keypair = KeyPair::<EvenY>::new_xonly(scalar(secret_key, "secret key")?);
+    let encryption_point = point(encryption_point, "adaptor point")?;
+    let msg = Message::<Public>::plain(MESSAGE_TAG, transcript);
+
+    let encrypted = schnorr().encrypted_sign(&keypair, &encryption_point, msg);
+    Ok(encode_encrypted_signature(&encrypted))
+}
+
+/// Verify an adaptor signature over `transcript` without decrypting it
+pub fn verify_encrypted_signature(
+    public_key: &[u8],
+    encryption_point: &[u8],
+    transcript: &[u8],
+    encrypted_signature: &[u8],
+) -> Result<()> {
+    let public_key = point(public_key, "public key")?;
+    let public_key = Point::<EvenY>::from_xonly_bytes(public_key.to_xonly_bytes())
+        .ok_or(ProtocolError::InvalidKey("public key"))?;
+    let encryption_point = point(encryption_point, "adaptor point")?;
+    let encrypted_signature = decode_encrypted_signature(encrypted_signature)?;
+    let msg = Message::<Public>::plain(MESSAGE_TAG, transcript);
+
+    if schnorr().verify_encrypted_signature(
+        &public_key,
+        &encryption_point,
+        msg,
+        &encrypted_signature,
+    ) {
+        Ok(())
+    } else {
+        Err(ProtocolError::VerificationFailed)
+    }
+}
+
+/// Decrypt an adaptor signature with the adaptor secret
+pub fn decrypt_signature(
+    adaptor_secret: &[u8],
+    encrypted_signature: &[u8],
+) -> Result<[u8; SIGNATURE_LEN]> {
+    let adaptor_secret = scalar(adaptor_secret, "adaptor secret")?;
+    let encrypted_signature = decode_encrypted_signature(encrypted_signature)?;
+    Ok(schnorr()
+        .decrypt_signature(adaptor_secret, encrypted_signature)
+        .to_bytes())
+}
+
+/// Recover the adaptor secret from an adaptor signature and its decryption
+pub fn recover_adaptor_secret(
+    encryption_point: &[u8],
+    encrypted_signature: &[u8],
+    signature: &[u8],
+) -> Result<[u8; SECRET_KEY_LEN]> {
+    let encryption_point = point(encryption_point, "adaptor point")?;
+    let encrypted_signature = decode_encrypted_signature(encrypted_signature)?;
+    let signature = <[u8; SIGNATURE_LEN]>::try_from(signature)
+        .ok()
+        .and_then(Signature::from_bytes)
+        .ok_or(ProtocolError::InvalidSignature("signature"))?;
+
+    schnorr()
+        .recover_decryption_key(&encryption_point, &encrypted_signature, &signature)
+        .map(|secret| secret.to_bytes())
+        .ok_or(ProtocolError::SecretMismatch)
+}
+
+/// Check the revealed adaptor secret against `T` and derive `sk_client + t`
+///
+/// The result is the secret key of `P_client + T`, which the broker's
+/// outputs are locked to.
+pub fn unlock_key(
+    secret_key: &[u8],
+    adaptor_secret: &[u8],
+    adaptor_point: &[u8],
+) -> Result<[u8; SECRET_KEY_LEN]> {
+    let secret_key = scalar(secret_key, "secret key")?;
+    let adaptor_secret = scalar(adaptor_secret, "adaptor secret")?;
+    if g!(adaptor_secret * G).normalize().to_bytes()[..] != *adaptor_point {
+        return Err(ProtocolError::SecretMismatch);
+    }
+
+    secp256kfun::op::scalar_add(&secret_key, &adaptor_secret)
+        .non_zero()
+        .map(|unlock| unlock.to_bytes())
+        .ok_or(ProtocolError::InvalidKey("unlock key"))
+}
+
+/// Encode an encrypted signature as `R (x-only) || s_hat || needs_negation`
+pub fn encode_encrypted_signature(
+    encrypted_sig: &EncryptedSignature,
+) -> [u8; ENCRYPTED_SIGNATURE_LEN] {
+    let mut bytes = [0u8; ENCRYPTED_SIGNATURE_LEN];
+    bytes[..32].copy_from_slice(&encrypted_sig.R.to_xonly_bytes());
+    bytes[32..64].copy_from_slice(&encrypted_sig.s_hat.to_bytes());
+    bytes[64] = encrypted_sig.needs_negation as u8;
+    bytes
+}
+
+/// Decode an encrypted signature produced by [`encode_encrypted_signature`]
+pub fn decode_encrypted_signature(bytes: &[u8]) -> Result<EncryptedSignature> {
+    if bytes.len() != ENCRYPTED_SIGNATURE_LEN {
+        return Err(ProtocolError::InvalidLength {
+            expected: ENCRYPTED_SIGNATURE_LEN,
+            got: bytes.len(),
+        });
+    }
+
+    let r_bytes: [u8; 32] = bytes[..32].try_into().expect("slice is 32 bytes");
+    let s_bytes: [u8; 32] = bytes[32..64].try_into().expect("slice is 32 bytes");
+
+    let r = Point::<EvenY>::from_xonly_bytes(r_bytes)
+        .ok_or(ProtocolError::InvalidSignature("encrypted signature nonce"))?;
+    let s_hat = Scalar::<Public, Zero>::from_bytes(s_bytes).ok_or(
+        ProtocolError::InvalidSignature("encrypted signature scalar"),
+    )?;
+    let needs_negation = match bytes[64] {
+        0 => false,
+        1 => true,
+        _ => {
+            return Err(ProtocolError::InvalidSignature(
+                "encrypted signature negation flag",
+            ))
+        }
+    };
+
+    Ok(EncryptedSignature {
+        R: r,
+        s_hat,
+        needs_negation,
+    })
+}
+
+fn schnorr() -> Schnorr<Sha256, nonce::Deterministic<Sha256>> {
+    Schnorr::<Sha256, _>::default()
+}
+
+fn point(bytes: &[u8], what: &'static str) -> Result<Point> {
+    <[u8; PUBLIC_KEY_LEN]>::try_from(bytes)
+        .ok()
+        .and_then(Point::from_bytes)
+        .ok_or(ProtocolError::InvalidKey(what))
+}
+
+fn scalar(bytes: &[u8], what: &'static str) -> Result<Scalar> {
+    <[u8; SECRET_KEY_LEN]>::try_from(bytes)
+        .ok()
+        .and_then(Scalar::from_bytes)
+        .and_then(|s| s.non_zero())
+        .ok_or(ProtocolError::InvalidKey(what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    struct Party {
+        secret_key: [u8; SECRET_KEY_LEN],
+        public_key: [u8; PUBLIC_KEY_LEN],
+    }
+
+    fn party() -> Party {
+        let secret_key = generate_secret_key(&mut rand::thread_rng());
+        Party {
+            public_key: public_key(&secret_key).unwrap(),
+            secret_key,
+        }
+    }
+
+    #[test]
+    fn test_swap() {
+        let broker = party();
+        let client = party();
+        let adaptor = party(); // t and T
+        let tweaked = tweak_public_key(&broker.public_key, &adaptor.public_key).unwrap();
+        check_tweaked_key(&broker.public_key, &adaptor.public_key, &tweaked).unwrap();
+
+        let terms = SwapTerms {
+            quote_id: "quote-1",
+            source_mint: "http://mint-a.test",
+            target_mint: "http://mint-b.test",
+            amount_in: 100,
+            amount_out: 99,
+            adaptor_point: &adaptor.public_key,
+        };
+        let transcript = swap_transcript(&terms, &client.public_key);
+
+        // Accept: the broker's adaptor signature checks out
+        let broker_sig =
+            create_encrypted_signature(&broker.secret_key, &adaptor.public_key, &transcript)
+                .unwrap();
+        verify_encrypted_signature(
+            &broker.public_key,
+            &adaptor.public_key,
+            &transcript,
+            &broker_sig,
+        )
+        .unwrap();
+        let other = swap_transcript(
+            &SwapTerms {
+                amount_out: 999,
+                ..terms
+            },
+            &client.public_key,
+        );
+        assert_eq!(
+            verify_encrypted_signature(
+                &broker.public_key,
+                &adaptor.public_key,
+                &other,
+                &broker_sig
+            ),
+            Err(ProtocolError::VerificationFailed)
+        );
+
+        // Complete: decrypting the client's signature reveals t
+        let client_sig =
+            create_encrypted_signature(&client.secret_key, &adaptor.public_key, &transcript)
+                .unwrap();
+        let decrypted = decrypt_signature(&adaptor.secret_key, &client_sig).unwrap();
+        let recovered =
+            recover_adaptor_secret(&adaptor.public_key, &client_sig, &decrypted).unwrap();
+        assert_eq!(recovered, adaptor.secret_key);
+
+        // sk_client + t is the key of P_client + T
+        let unlock = unlock_key(&client.secret_key, &recovered, &adaptor.public_key).unwrap();
+        assert_eq!(
+            public_key(&unlock).unwrap(),
+            tweak_public_key(&client.public_key, &adaptor.public_key).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rejects_mismatches() {
+        let broker = party();
+        let client = party();
+        let adaptor = party();
+
+        let wrong = tweak_public_key(&broker.public_key, &client.public_key).unwrap();
+        assert_eq!(
+            check_tweaked_key(&broker.public_key, &adaptor.public_key, &wrong),
+            Err(ProtocolError::TweakMismatch)
+        );
+        assert_eq!(
+            unlock_key(&client.secret_key, &broker.secret_key, &adaptor.public_key),
+            Err(ProtocolError::SecretMismatch)
+        );
+        assert_eq!(
+            unlock_key(&client.secret_key, &[0u8; 32], &adaptor.public_key),
+            Err(ProtocolError::InvalidKey("adaptor secret"))
+        );
+        assert_eq!(
+            public_key(&[1u8; 31]),
+            Err(ProtocolError::InvalidKey("secret key"))
+        );
+    }
+
+    #[test]
+    fn test_encrypted_signature_encoding() {
+        let signer = party();
+        let adaptor = party();
+        let encoded =
+            create_encrypted_signature(&signer.secret_key, &adaptor.public_key, b"transcript")
+                .unwrap();
+
+        let decoded = decode_encrypted_signature(&encoded).unwrap();
+        assert_eq!(encode_encrypted_signature(&decoded), encoded);
+
+        assert_eq!(
+            decode_encrypted_signature(&encoded[..10]).unwrap_err(),
+            ProtocolError::InvalidLength {
+                expected: ENCRYPTED_SIGNATURE_LEN,
+                got: 10
+            }
+        );
+        let mut bad_flag = encoded;
+        bad_flag[64] = 2;
+        assert!(decode_encrypted_signature(&bad_flag).is_err());
+    }
+}
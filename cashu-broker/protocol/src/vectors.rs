@@ -0,0 +1,308 @@
+//! Golden test vectors
+//!
+//! Fixed keys, transcripts and signatures for whole swaps, so that other
+//! implementations of the client can check they agree with the broker byte
+//! for byte. `vectors.json` at the root of this crate holds the same vectors
+//! for wallets not written in Rust. All values are hex, except the swap terms
+//! and the transcript.
+//!
+//! Each vector runs a swap through:
+//!
+//! - `*_public_key` and `adaptor_point` are the compressed points of the
+//!   secret keys and of the adaptor secret `t`
+//! - `broker_tweaked_pubkey` is `P_broker + T`, which the client locks its
+//!   input to, and `client_tweaked_pubkey` is `P_client + T`, which the broker
+//!   locks its outputs to
+//! - `transcript` is the [`swap_transcript`](crate::swap_transcript) of the
+//!   terms and the client key
+//! - `*_encrypted_signature` are adaptor signatures over the transcript,
+//!   encrypted under `T`, as [`encode_encrypted_signature`](crate::encode_encrypted_signature)
+//!   lays them out, and `*_signature` their decryptions with `t`. These are
+//!   BIP-340 signatures by the x-only key over `"cashu-swap"` zero-padded to
+//!   64 bytes followed by the transcript
+//! - `recovered_secret` is `t` as recovered from the client's signature pair,
+//!   and `unlock_key` is `sk_client + t`, the key of `client_tweaked_pubkey`
+//!
+//! The first vector has keys and nonce points with even y; in the second,
+//! every one has odd y, so keys are negated to sign and `needs_negation` is
+//! set. The signatures were made with fixed nonces: an implementation picks
+//! its own, so check signatures it creates by verifying them, not by
+//! comparing bytes.
+
+/// One swap worked through with fixed keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapVector {
+    pub description: &'static str,
+    pub broker_secret_key: &'static str,
+    pub broker_public_key: &'static str,
+    pub client_secret_key: &'static str,
+    pub client_public_key: &'static str,
+    pub adaptor_secret: &'static str,
+    pub adaptor_point: &'static str,
+    pub broker_tweaked_pubkey: &'static str,
+    pub client_tweaked_pubkey: &'static str,
+    pub quote_id: &'static str,
+    pub source_mint: &'static str,
+    pub target_mint: &'static str,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub transcript: &'static str,
+    pub broker_encrypted_signature: &'static str,
+    pub broker_signature: &'static str,
+    pub client_encrypted_signature: &'static str,
+    pub client_signature: &'static str,
+    pub recovered_secret: &'static str,
+    pub unlock_key: &'static str,
+}
+
+/// The published vectors
+pub const SWAP_VECTORS: [SwapVector; 2] = [
+    SwapVector {
+        description: "Keys with even y; no nonce negation",
+        broker_secret_key: "2d8d355f9d9a5acd86f9a08e163d4e31fc1c7de90be2397c4ecd0a4fd65a68ba",
+        broker_public_key: "022631c3f24f3bcb52d9c124bfb204cacfb3c8faa6993b161d6851d46db32dc4a0",
+        client_secret_key: "0f67964aa295781438d0991127f443b29fb24e0ce0b3f9ec4817656c68f516c1",
+        client_public_key: "02c3a899587f798cba09808a0462bc05b60468008afd5fc17e71e9bb7910339011",
+        adaptor_secret: "f053ca7dda3f9bda46998491dfcd1265af9d52f304e0dbba87bb47741e31fe6d",
+        adaptor_point: "0310894982a9fe1817b0cf14749ffc2642c56f6c7363d29cc12be360c8796d4f62",
+        broker_tweaked_pubkey: "037f4e851cbf1bab3426cc194716ea566afa83a42ee1396ba2c968abdf73646a44",
+        client_tweaked_pubkey: "0248626cd514d515634fa228e70824b31d155a424c61d922bd328c014ec68a6dbd",
+        quote_id: "00924a4f9bb8f0671fced38bb9557752",
+        source_mint: "https://mint-a.example",
+        target_mint: "https://mint-b.example",
+        amount_in: 1000,
+        amount_out: 990,
+        transcript: concat!(
+            "00924a4f9bb8f0671fced38bb9557752|",
+            "https://mint-a.example|",
+            "https://mint-b.example|",
+            "1000|",
+            "990|",
+            "0310894982a9fe1817b0cf14749ffc2642c56f6c7363d29cc12be360c8796d4f62|",
+            "02c3a899587f798cba09808a0462bc05b60468008afd5fc17e71e9bb7910339011",
+        ),
+        broker_encrypted_signature: concat!(
+            "1c252de7c8cf2dd66fbb6b9606c8ce14b740b096fd6ad02212c77c13505696c0",
+            "1adf631f6ad84156d4154e07c4d219a371a987d3b514199f2fb33e5574cfc0e6",
+            "00",
+        ),
+        broker_signature: concat!(
+            "1c252de7c8cf2dd66fbb6b9606c8ce14b740b096fd6ad02212c77c13505696c0",
+            "0b332d9d4517dd311aaed299a49f2c0a6697fde00aac551df79c273cc2cb7e12",
+        ),
+        client_encrypted_signature: concat!(
+            "22279c99c2ee6e37836f611e4f0f1ac9c5956bb70be78abdefb7fc74dcc12a34",
+            "38f1ef102309fa85cc3ecde0ea77c9c61b9447aeec5e31a5dfe133020051eed4",
+            "00",
+        ),
+        client_signature: concat!(
+            "22279c99c2ee6e37836f611e4f0f1ac9c5956bb70be78abdefb7fc74dcc12a34",
+            "2945b98dfd49966012d85272ca44dc2d1082bdbb41f66d24a7ca1be94e4dac00",
+        ),
+        recovered_secret: "f053ca7dda3f9bda46998491dfcd1265af9d52f304e0dbba87bb47741e31fe6d",
+        unlock_key: "ffbb60c87cd513ee7f6a1da307c156184f4fa0ffe594d5a6cfd2ace08727152e",
+    },
+    SwapVector {
+        description: "Keys with odd y, negated to sign; both nonces negated",
+        broker_secret_key: "8d026037975758e578c2ac0183e520e7065c2656f30861517bda0ee7d1147a9c",
+        broker_public_key: "036c47d945653649101fd49a36b544ceaef45d5bd17009e06fb0a192f8f0dcbbae",
+        client_secret_key: "60626fa8e1986919e3f0878d3248859be4c3824209c5af9a9c0d078257acb403",
+        client_public_key: "031631b7c8836244264ff42282e7f8c8fd5850ec95a7e2f3e8a167ec8bcee25237",
+        adaptor_secret: "1d119eec3b98a57d9d788acdb7babf605a5e72efde83c93577d0dd5dfee63be0",
+        adaptor_point: "02510358b08ca48b801a92af42cabd9cfdd084fa517e873b31130b1f517a23f6a4",
+        broker_tweaked_pubkey: "02aad9fa20f313cf525834aeecdbc55651e2618797bbd6c434ba72d8eb879a2761",
+        client_tweaked_pubkey: "0303c98afcd2f14a70483dc1a42a96f7bfa3cdf93de46d63abc62d15ed3ed8e588",
+        quote_id: "14ab8a2a12ea09595b7a20690cb5e122",
+        source_mint: "https://mint.example.com/cashu",
+        target_mint: "https://mint-b.example",
+        amount_in: 21,
+        amount_out: 20,
+        transcript: concat!(
+            "14ab8a2a12ea09595b7a20690cb5e122|",
+            "https://mint.example.com/cashu|",
+            "https://mint-b.example|",
+            "21|",
+            "20|",
+            "02510358b08ca48b801a92af42cabd9cfdd084fa517e873b31130b1f517a23f6a4|",
+            "031631b7c8836244264ff42282e7f8c8fd5850ec95a7e2f3e8a167ec8bcee25237",
+        ),
+        broker_encrypted_signature: concat!(
+            "4f0ca53986b4457ca3eb5c509e99ae269447bba7cf9e513dcd99264f7db77fcf",
+            "fd9030e322fe7f42c76c50818e4161e25e8677672a4f57d9d11306b4d7a2d49d",
+            "01",
+        ),
+        broker_signature: concat!(
+            "4f0ca53986b4457ca3eb5c509e99ae269447bba7cf9e513dcd99264f7db77fcf",
+            "e07e91f6e765d9c529f3c5b3d686a282042804774bcb8ea459422956d8bc98bd",
+        ),
+        client_encrypted_signature: concat!(
+            "0902f0ef937e4db49d8712dc290cddc5a8a04253966e7782cefd92139b6796fe",
+            "8bb24a537bcd51ed88cafbdff18c7cb3f3e1f0a523cb20f0fc4fa0ca8973f0f9",
+            "01",
+        ),
+        client_signature: concat!(
+            "0902f0ef937e4db49d8712dc290cddc5a8a04253966e7782cefd92139b6796fe",
+            "6ea0ab674034ac6feb52711239d1bd5399837db5454757bb847ec36c8a8db519",
+        ),
+        recovered_secret: "1d119eec3b98a57d9d788acdb7babf605a5e72efde83c93577d0dd5dfee63be0",
+        unlock_key: "7d740e951d310e978169125aea0344fc3f21f531e84978d013dde4e05692efe3",
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use alloc::vec::Vec;
+
+    fn bytes(value: &str) -> Vec<u8> {
+        hex::decode(value).unwrap()
+    }
+
+    #[test]
+    fn test_vectors() {
+        for vector in SWAP_VECTORS {
+            let adaptor_point = bytes(vector.adaptor_point);
+            let client_pubkey = bytes(vector.client_public_key);
+            for (secret_key, public) in [
+                (vector.broker_secret_key, vector.broker_public_key),
+                (vector.client_secret_key, vector.client_public_key),
+                (vector.adaptor_secret, vector.adaptor_point),
+            ] {
+                assert_eq!(
+                    public_key(&bytes(secret_key)).unwrap()[..],
+                    bytes(public)[..]
+                );
+            }
+
+            let broker_tweaked = bytes(vector.broker_tweaked_pubkey);
+            let broker_pubkey = bytes(vector.broker_public_key);
+            check_tweaked_key(&broker_pubkey, &adaptor_point, &broker_tweaked).unwrap();
+            assert_eq!(
+                tweak_public_key(&client_pubkey, &adaptor_point).unwrap()[..],
+                bytes(vector.client_tweaked_pubkey)[..]
+            );
+
+            let terms = SwapTerms {
+                quote_id: vector.quote_id,
+                source_mint: vector.source_mint,
+                target_mint: vector.target_mint,
+                amount_in: vector.amount_in,
+                amount_out: vector.amount_out,
+                adaptor_point: &adaptor_point,
+            };
+            let transcript = swap_transcript(&terms, &client_pubkey);
+            assert_eq!(transcript, vector.transcript.as_bytes());
+
+            let adaptor_secret = bytes(vector.adaptor_secret);
+            for (public, encrypted, signature) in [
+                (
+                    vector.broker_public_key,
+                    vector.broker_encrypted_signature,
+                    vector.broker_signature,
+                ),
+                (
+                    vector.client_public_key,
+                    vector.client_encrypted_signature,
+                    vector.client_signature,
+                ),
+            ] {
+                let public = bytes(public);
+                let encrypted = bytes(encrypted);
+                verify_encrypted_signature(&public, &adaptor_point, &transcript, &encrypted)
+                    .unwrap();
+                let decrypted = decrypt_signature(&adaptor_secret, &encrypted).unwrap();
+                assert_eq!(decrypted[..], bytes(signature)[..]);
+                assert_eq!(
+                    recover_adaptor_secret(&adaptor_point, &encrypted, &decrypted).unwrap()[..],
+                    bytes(vector.recovered_secret)[..]
+                );
+            }
+
+            let unlock = unlock_key(
+                &bytes(vector.client_secret_key),
+                &bytes(vector.recovered_secret),
+                &adaptor_point,
+            )
+            .unwrap();
+            assert_eq!(unlock[..], bytes(vector.unlock_key)[..]);
+        }
+    }
+
+    #[test]
+    fn test_decrypted_signatures_are_bip340() {
+        for vector in SWAP_VECTORS {
+            for (public, signature) in [
+                (vector.broker_public_key, vector.broker_signature),
+                (vector.client_public_key, vector.client_signature),
+            ] {
+                let msg = Message::<Public>::plain(MESSAGE_TAG, vector.transcript.as_bytes());
+                let public = point(&bytes(public), "public key").unwrap();
+                let public = Point::<EvenY>::from_xonly_bytes(public.to_xonly_bytes()).unwrap();
+                let signature = <[u8; SIGNATURE_LEN]>::try_from(bytes(signature))
+                    .ok()
+                    .and_then(Signature::from_bytes)
+                    .unwrap();
+                assert!(schnorr().verify(&public, msg, &signature));
+            }
+        }
+    }
+
+    #[test]
+    fn test_fresh_signatures_verify() {
+        // Nonces differ from the vectors', but the signatures must verify
+        for vector in SWAP_VECTORS {
+            let adaptor_point = bytes(vector.adaptor_point);
+            let transcript = vector.transcript.as_bytes();
+            let encrypted = create_encrypted_signature(
+                &bytes(vector.client_secret_key),
+                &adaptor_point,
+                transcript,
+            )
+            .unwrap();
+            verify_encrypted_signature(
+                &bytes(vector.client_public_key),
+                &adaptor_point,
+                transcript,
+                &encrypted,
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_json_matches() {
+        let json: serde_json::Value =
+            serde_json::from_str(include_str!("../vectors.json")).unwrap();
+        assert_eq!(json["message_tag"], MESSAGE_TAG);
+
+        let published = json["vectors"].as_array().unwrap();
+        assert_eq!(published.len(), SWAP_VECTORS.len());
+        for (vector, published) in SWAP_VECTORS.iter().zip(published) {
+            let expected = serde_json::json!({
+                "description": vector.description,
+                "broker_secret_key": vector.broker_secret_key,
+                "broker_public_key": vector.broker_public_key,
+                "client_secret_key": vector.client_secret_key,
+                "client_public_key": vector.client_public_key,
+                "adaptor_secret": vector.adaptor_secret,
+                "adaptor_point": vector.adaptor_point,
+                "broker_tweaked_pubkey": vector.broker_tweaked_pubkey,
+                "client_tweaked_pubkey": vector.client_tweaked_pubkey,
+                "quote_id": vector.quote_id,
+                "source_mint": vector.source_mint,
+                "target_mint": vector.target_mint,
+                "amount_in": vector.amount_in,
+                "amount_out": vector.amount_out,
+                "transcript": vector.transcript,
+                "broker_encrypted_signature": vector.broker_encrypted_signature,
+                "broker_signature": vector.broker_signature,
+                "client_encrypted_signature": vector.client_encrypted_signature,
+                "client_signature": vector.client_signature,
+                "recovered_secret": vector.recovered_secret,
+                "unlock_key": vector.unlock_key,
+            });
+            assert_eq!(published, &expected);
+        }
+    }
+}
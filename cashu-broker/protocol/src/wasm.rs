@@ -0,0 +1,157 @@
+//! JavaScript bindings
+//!
+//! Keys, points and signatures cross as hex strings, as the broker's API
+//! sends them, and transcripts as `Uint8Array`s. Failures are thrown as
+//! `Error`s carrying the [`ProtocolError`](crate::ProtocolError) message.
+
+use crate::{ProtocolError, SwapTerms};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use wasm_bindgen::prelude::*;
+
+/// Generate a one-time swap key from the browser's random source
+#[wasm_bindgen(js_name = generateSecretKey)]
+pub fn generate_secret_key() -> String {
+    hex::encode(crate::generate_secret_key(&mut rand_core::OsRng))
+}
+
+#[wasm_bindgen(js_name = publicKey)]
+pub fn public_key(secret_key: &str) -> Result<String, JsError> {
+    let public_key = crate::public_key(&decode(secret_key, "secret key")?).map_err(js)?;
+    Ok(hex::encode(public_key))
+}
+
+#[wasm_bindgen(js_name = tweakPublicKey)]
+pub fn tweak_public_key(public_key: &str, tweak: &str) -> Result<String, JsError> {
+    let tweaked = crate::tweak_public_key(
+        &decode(public_key, "public key")?,
+        &decode(tweak, "adaptor point")?,
+    )
+    .map_err(js)?;
+    Ok(hex::encode(tweaked))
+}
+
+#[wasm_bindgen(js_name = checkTweakedKey)]
+pub fn check_tweaked_key(
+    broker_pubkey: &str,
+    adaptor_point: &str,
+    tweaked_pubkey: &str,
+) -> Result<(), JsError> {
+    crate::check_tweaked_key(
+        &decode(broker_pubkey, "broker pubkey")?,
+        &decode(adaptor_point, "adaptor point")?,
+        &decode(tweaked_pubkey, "tweaked pubkey")?,
+    )
+    .map_err(js)
+}
+
+/// Transcript of a quote, from the fields of the broker's quote response
+#[wasm_bindgen(js_name = swapTranscript)]
+pub fn swap_transcript(
+    quote_id: &str,
+    source_mint: &str,
+    target_mint: &str,
+    amount_in: u64,
+    amount_out: u64,
+    adaptor_point: &str,
+    client_pubkey: &str,
+) -> Result<Vec<u8>, JsError> {
+    let adaptor_point = decode(adaptor_point, "adaptor point")?;
+    let terms = SwapTerms {
+        quote_id,
+        source_mint,
+        target_mint,
+        amount_in,
+        amount_out,
+        adaptor_point: &adaptor_point,
+    };
+    Ok(crate::swap_transcript(
+        &terms,
+        &decode(client_pubkey, "client pubkey")?,
+    ))
+}
+
+#[wasm_bindgen(js_name = createEncryptedSignature)]
+pub fn create_encrypted_signature(
+    secret_key: &str,
+    encryption_point: &str,
+    transcript: &[u8],
+) -> Result<String, JsError> {
+    let encrypted = crate::create_encrypted_signature(
+        &decode(secret_key, "secret key")?,
+        &decode(encryption_point, "adaptor point")?,
+        transcript,
+    )
+    .map_err(js)?;
+    Ok(hex::encode(encrypted))
+}
+
+#[wasm_bindgen(js_name = verifyEncryptedSignature)]
+pub fn verify_encrypted_signature(
+    public_key: &str,
+    encryption_point: &str,
+    transcript: &[u8],
+    encrypted_signature: &str,
+) -> Result<(), JsError> {
+    crate::verify_encrypted_signature(
+        &decode(public_key, "public key")?,
+        &decode(encryption_point, "adaptor point")?,
+        transcript,
+        &decode(encrypted_signature, "encrypted signature")?,
+    )
+    .map_err(js)
+}
+
+#[wasm_bindgen(js_name = decryptSignature)]
+pub fn decrypt_signature(
+    adaptor_secret: &str,
+    encrypted_signature: &str,
+) -> Result<String, JsError> {
+    let signature = crate::decrypt_signature(
+        &decode(adaptor_secret, "adaptor secret")?,
+        &decode(encrypted_signature, "encrypted signature")?,
+    )
+    .map_err(js)?;
+    Ok(hex::encode(signature))
+}
+
+#[wasm_bindgen(js_name = recoverAdaptorSecret)]
+pub fn recover_adaptor_secret(
+    encryption_point: &str,
+    encrypted_signature: &str,
+    signature: &str,
+) -> Result<String, JsError> {
+    let secret = crate::recover_adaptor_secret(
+        &decode(encryption_point, "adaptor point")?,
+        &decode(encrypted_signature, "encrypted signature")?,
+        &decode(signature, "signature")?,
+    )
+    .map_err(js)?;
+    Ok(hex::encode(secret))
+}
+
+/// `sk_client + t`, the key that redeems the broker's outputs
+#[wasm_bindgen(js_name = unlockKey)]
+pub fn unlock_key(
+    secret_key: &str,
+    adaptor_secret: &str,
+    adaptor_point: &str,
+) -> Result<String, JsError> {
+    let unlock = crate::unlock_key(
+        &decode(secret_key, "secret key")?,
+        &decode(adaptor_secret, "adaptor secret")?,
+        &decode(adaptor_point, "adaptor point")?,
+    )
+    .map_err(js)?;
+    Ok(hex::encode(unlock))
+}
+
+fn decode(value: &str, what: &'static str) -> Result<Vec<u8>, JsError> {
+    hex::decode(value).map_err(|_| js(ProtocolError::InvalidKey(what)))
+}
+
+fn js(error: ProtocolError) -> JsError {
+    JsError::new(&error.to_string())
+}
@@ -0,0 +1,261 @@
+//! Accounting ledger and profit and loss
+//!
+//! Quotes only say what a swap was priced at. The broker records what it
+//! actually earned and paid as [`LedgerEntry`](crate::db::LedgerEntry) rows in
+//! the `accounting` table: the broker fee and spread of each completed swap on
+//! the revenue side, and mint input fees and the Lightning fees of rebalancing
+//! and withdrawals on the cost side. Amounts stay in the unit of the mint they
+//! were earned or paid on and are never converted, so [`AccountingSummary`]
+//! reports profit and loss per mint and per unit.
+
+use crate::db::LedgerTotal;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What a ledger entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    SwapFee,      // Broker fee charged on a completed swap
+    Spread,       // Taken off the exchange rate of a cross-unit swap
+    MintFee,      // Input fees paid to a mint
+    LightningFee, // Routing fees paid on melts
+}
+
+impl EntryKind {
+    /// Whether the entry is earned rather than paid
+    pub fn is_revenue(self) -> bool {
+        matches!(self, EntryKind::SwapFee | EntryKind::Spread)
+    }
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryKind::SwapFee => write!(f, "swap_fee"),
+            EntryKind::Spread => write!(f, "spread"),
+            EntryKind::MintFee => write!(f, "mint_fee"),
+            EntryKind::LightningFee => write!(f, "lightning_fee"),
+        }
+    }
+}
+
+impl std::str::FromStr for EntryKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "swap_fee" => Ok(EntryKind::SwapFee),
+            "spread" => Ok(EntryKind::Spread),
+            "mint_fee" => Ok(EntryKind::MintFee),
+            "lightning_fee" => Ok(EntryKind::LightningFee),
+            _ => Err(format!("Invalid ledger entry kind: {}", s)),
+        }
+    }
+}
+
+/// Spread earned on a cross-unit swap, in the target mint's unit
+///
+/// Quotes pay out at the feed's rate less `spread`, so at the feed's rate the
+/// client would have received `output_amount / (1 - spread)`.
+pub fn realized_spread(output_amount: u64, spread: f64) -> u64 {
+    if !(0.0..1.0).contains(&spread) {
+        return 0;
+    }
+    (output_amount as f64 * spread / (1.0 - spread)).round() as u64
+}
+
+/// Revenue and costs in a single unit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfitAndLoss {
+    pub swap_fees: u64,
+    pub spread: u64,
+    pub mint_fees: u64,
+    pub lightning_fees: u64,
+    pub net: i64, // Revenue less costs
+}
+
+impl ProfitAndLoss {
+    fn add(&mut self, kind: EntryKind, amount: u64) {
+        match kind {
+            EntryKind::SwapFee => self.swap_fees += amount,
+            EntryKind::Spread => self.spread += amount,
+            EntryKind::MintFee => self.mint_fees += amount,
+            EntryKind::LightningFee => self.lightning_fees += amount,
+        }
+        self.net = (self.revenue() as i64) - (self.costs() as i64);
+    }
+
+    pub fn revenue(&self) -> u64 {
+        self.swap_fees + self.spread
+    }
+
+    pub fn costs(&self) -> u64 {
+        self.mint_fees + self.lightning_fees
+    }
+}
+
+/// Profit and loss on one mint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MintPnl {
+    pub mint_url: String,
+    pub unit: String,
+    #[serde(flatten)]
+    pub pnl: ProfitAndLoss,
+}
+
+/// Profit and loss over all mints of one unit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnitPnl {
+    pub unit: String,
+    #[serde(flatten)]
+    pub pnl: ProfitAndLoss,
+}
+
+/// Profit and loss report over an optional time window
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountingSummary {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<DateTime<Utc>>,
+    pub by_mint: Vec<MintPnl>, // Sorted by mint URL
+    pub by_unit: Vec<UnitPnl>, // Sorted by unit
+}
+
+impl AccountingSummary {
+    /// Build the report from ledger totals per mint, unit and kind
+    ///
+    /// Totals of an unknown kind are left out.
+    pub fn new(
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        totals: &[LedgerTotal],
+    ) -> Self {
+        let mut by_mint: BTreeMap<(&str, &str), ProfitAndLoss> = BTreeMap::new();
+        let mut by_unit: BTreeMap<&str, ProfitAndLoss> = BTreeMap::new();
+
+        for total in totals {
+            let Ok(kind) = total.kind.parse::<EntryKind>() else {
+                continue;
+            };
+            let amount = total.amount.max(0) as u64;
+            by_mint
+                .entry((&total.mint_url, &total.unit))
+                .or_default()
+                .add(kind, amount);
+            by_unit.entry(&total.unit).or_default().add(kind, amount);
+        }
+
+        Self {
+            from,
+            to,
+            by_mint: by_mint
+                .into_iter()
+                .map(|((mint_url, unit), pnl)| MintPnl {
+                    mint_url: mint_url.to_string(),
+                    unit: unit.to_string(),
+                    pnl,
+                })
+                .collect(),
+            by_unit: by_unit
+                .into_iter()
+                .map(|(unit, pnl)| UnitPnl {
+                    unit: unit.to_string(),
+                    pnl,
+                })
+                .collect(),
+        }
+    }
+
+    /// Totals for `unit`, zero if nothing was recorded in it
+    pub fn unit(&self, unit: &str) -> ProfitAndLoss {
+        self.by_unit
+            .iter()
+            .find(|u| u.unit == unit)
+            .map(|u| u.pnl)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total(mint_url: &str, unit: &str, kind: EntryKind, amount: i64) -> LedgerTotal {
+        LedgerTotal {
+            mint_url: mint_url.to_string(),
+            unit: unit.to_string(),
+            kind: kind.to_string(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_entry_kind_roundtrip() {
+        for kind in [
+            EntryKind::SwapFee,
+            EntryKind::Spread,
+            EntryKind::MintFee,
+            EntryKind::LightningFee,
+        ] {
+            assert_eq!(kind.to_string().parse::<EntryKind>(), Ok(kind));
+            assert_eq!(
+                serde_json::to_value(kind).unwrap(),
+                serde_json::json!(kind.to_string())
+            );
+        }
+        assert!("refund".parse::<EntryKind>().is_err());
+        assert!(EntryKind::Spread.is_revenue());
+        assert!(!EntryKind::MintFee.is_revenue());
+    }
+
+    #[test]
+    fn test_realized_spread() {
+        // 99 out at a 1% spread means 100 at the feed's rate
+        assert_eq!(realized_spread(99, 0.01), 1);
+        assert_eq!(realized_spread(9_900, 0.01), 100);
+        assert_eq!(realized_spread(1_000, 0.0), 0);
+        assert_eq!(realized_spread(1_000, 1.0), 0);
+    }
+
+    #[test]
+    fn test_summary() {
+        let totals = vec![
+            total("http://mint-a.test", "sat", EntryKind::SwapFee, 50),
+            total("http://mint-a.test", "sat", EntryKind::MintFee, 4),
+            total("http://mint-b.test", "sat", EntryKind::LightningFee, 10),
+            total("http://mint-b.test", "sat", EntryKind::MintFee, 2),
+            total("http://mint-c.test", "usd", EntryKind::Spread, 7),
+            LedgerTotal {
+                kind: "unknown".to_string(),
+                ..total("http://mint-c.test", "usd", EntryKind::Spread, 100)
+            },
+        ];
+
+        let summary = AccountingSummary::new(None, None, &totals);
+
+        assert_eq!(summary.by_mint.len(), 3);
+        assert_eq!(summary.by_mint[0].mint_url, "http://mint-a.test");
+        assert_eq!(summary.by_mint[0].pnl.net, 46);
+        assert_eq!(summary.by_mint[1].pnl.net, -12);
+
+        let sat = summary.unit("sat");
+        assert_eq!(sat.swap_fees, 50);
+        assert_eq!(sat.mint_fees, 6);
+        assert_eq!(sat.lightning_fees, 10);
+        assert_eq!(sat.net, 34);
+
+        // Units are never mixed
+        assert_eq!(summary.unit("usd").spread, 7);
+        assert_eq!(summary.unit("usd").net, 7);
+        assert_eq!(summary.unit("eur"), ProfitAndLoss::default());
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["by_unit"][0]["unit"], "sat");
+        assert_eq!(json["by_unit"][0]["net"], 34);
+        assert!(json.get("from").is_none());
+    }
+}
@@ -3,17 +3,21 @@
 //! Wrapper around schnorr_fun for atomic swap functionality
 
 use crate::error::{BrokerError, Result};
+use crate::rng::SwapRng;
 use schnorr_fun::{
     adaptor::{Adaptor, EncryptedSignature, EncryptedSign},
-    fun::{Scalar, Point, KeyPair, g, G},
+    fun::{Scalar, Point, KeyPair, g, s, G},
     Message, Schnorr,
 };
 use secp256kfun::{nonce, marker::*};
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 /// Adaptor signature context for atomic swaps
 pub struct AdaptorContext {
     schnorr: Schnorr<Sha256, nonce::Deterministic<Sha256>>,
+    rng: SwapRng,
 }
 
 impl AdaptorContext {
@@ -21,12 +25,19 @@ impl AdaptorContext {
     pub fn new() -> Self {
         Self {
             schnorr: Schnorr::<Sha256, _>::default(),
+            rng: SwapRng::os(),
         }
     }
 
+    /// Draw adaptor secrets and DLEQ nonces from `rng`
+    pub fn with_rng(mut self, rng: SwapRng) -> Self {
+        self.rng = rng;
+        self
+    }
+
     /// Generate a random adaptor secret
     pub fn generate_adaptor_secret(&self) -> Scalar {
-        Scalar::random(&mut rand::thread_rng())
+        self.rng.scalar()
     }
 
     /// Derive adaptor point from secret: T = t * G
@@ -83,6 +94,52 @@ impl AdaptorContext {
         }
     }
 
+    /// Create an encrypted NUT-11 witness signature for a proof's `secret`
+    ///
+    /// Once decrypted it is a plain BIP-340 signature over the SHA-256 of the
+    /// secret, which a mint accepts in a P2PK witness.
+    pub fn create_encrypted_witness(
+        &self,
+        signing_key: &Scalar,
+        encryption_point: &Point,
+        secret: &str,
+    ) -> Result<EncryptedSignature> {
+        let keypair = KeyPair::<EvenY>::new_xonly(*signing_key);
+        let digest = Sha256::digest(secret.as_bytes());
+        let msg = Message::<Public>::raw(&digest);
+
+        Ok(self.schnorr.encrypted_sign(&keypair, encryption_point, msg))
+    }
+
+    /// Verify an encrypted witness signature from [`Self::create_encrypted_witness`]
+    pub fn verify_encrypted_witness(
+        &self,
+        public_key: &Point,
+        encryption_point: &Point,
+        secret: &str,
+        encrypted_sig: &EncryptedSignature,
+    ) -> Result<()> {
+        let digest = Sha256::digest(secret.as_bytes());
+        let msg = Message::<Public>::raw(&digest);
+        let public_key_eveny = Point::<EvenY>::from_xonly_bytes(public_key.to_xonly_bytes())
+            .ok_or_else(|| BrokerError::AdaptorSignature(
+                "Failed to convert public key to EvenY".to_string(),
+            ))?;
+
+        if self.schnorr.verify_encrypted_signature(
+            &public_key_eveny,
+            encryption_point,
+            msg,
+            encrypted_sig,
+        ) {
+            Ok(())
+        } else {
+            Err(BrokerError::AdaptorSignature(
+                "Encrypted witness signature verification failed".to_string(),
+            ))
+        }
+    }
+
     /// Decrypt an encrypted signature using the adaptor secret
     pub fn decrypt_signature(
         &self,
@@ -121,8 +178,397 @@ impl AdaptorContext {
     }
 }
 
+/// Proof that the adaptor point is the tweak applied to the broker's key
+///
+/// Shows that `log_G(T) == log_G(P' - P)` where `P` is the broker pubkey and
+/// `P'` the tweaked pubkey, and that the broker knows this discrete log.
+/// Clients check it before locking funds to `P'`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DleqProof {
+    pub e: Scalar<Public, Zero>,
+    pub s: Scalar<Public, Zero>,
+}
+
+impl DleqProof {
+    /// Encode as `e || s` (64 bytes)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(64);
+        bytes.extend_from_slice(&self.e.to_bytes());
+        bytes.extend_from_slice(&self.s.to_bytes());
+        bytes
+    }
+
+    /// Decode from `e || s`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != 64 {
+            return Err(BrokerError::AdaptorSignature(format!(
+                "Invalid DLEQ proof length: expected 64, got {}",
+                bytes.len()
+            )));
+        }
+
+        let e_bytes: [u8; 32] = bytes[..32].try_into().expect("slice is 32 bytes");
+        let s_bytes: [u8; 32] = bytes[32..].try_into().expect("slice is 32 bytes");
+
+        let e = Scalar::from_bytes(e_bytes)
+            .ok_or_else(|| BrokerError::AdaptorSignature("Invalid DLEQ challenge".to_string()))?;
+        let s = Scalar::from_bytes(s_bytes)
+            .ok_or_else(|| BrokerError::AdaptorSignature("Invalid DLEQ response".to_string()))?;
+
+        Ok(Self { e, s })
+    }
+}
+
+/// Fiat-Shamir challenge for [`DleqProof`]
+fn dleq_challenge(
+    broker_pubkey: &Point,
+    tweaked_pubkey: &Point,
+    adaptor_point: &Point,
+    nonce_point: &Point,
+    context: &[u8],
+) -> Scalar<Public, Zero> {
+    let hash = Sha256::new()
+        .chain_update(b"cashu-swap/dleq")
+        .chain_update(broker_pubkey.to_bytes())
+        .chain_update(tweaked_pubkey.to_bytes())
+        .chain_update(adaptor_point.to_bytes())
+        .chain_update(nonce_point.to_bytes())
+        .chain_update(context)
+        .finalize();
+
+    Scalar::<Public, Zero>::from_bytes_mod_order(hash.into())
+}
+
+impl AdaptorContext {
+    /// Prove that `T = tG` is the tweak between the broker pubkey and the tweaked pubkey
+    ///
+    /// `context` binds the proof to a quote (e.g. the quote ID).
+    pub fn create_dleq_proof(
+        &self,
+        adaptor_secret: &Scalar,
+        broker_pubkey: &Point,
+        tweaked_pubkey: &Point,
+        context: &[u8],
+    ) -> DleqProof {
+        let adaptor_point = self.adaptor_point_from_secret(adaptor_secret);
+        let nonce = self.rng.scalar();
+        let nonce_point = g!(nonce * G).normalize();
+
+        let e = dleq_challenge(broker_pubkey, tweaked_pubkey, &adaptor_point, &nonce_point, context);
+        let s = s!(nonce + e * adaptor_secret).public();
+
+        DleqProof { e, s }
+    }
+
+    /// Verify a [`DleqProof`] against the points published in a quote
+    pub fn verify_dleq_proof(
+        &self,
+        proof: &DleqProof,
+        broker_pubkey: &Point,
+        tweaked_pubkey: &Point,
+        adaptor_point: &Point,
+        context: &[u8],
+    ) -> Result<()> {
+        // The tweak must be exactly T: P' - P == T
+        let tweak = g!(tweaked_pubkey - broker_pubkey).normalize().non_zero();
+        if tweak != Some(*adaptor_point) {
+            return Err(BrokerError::AdaptorSignature(
+                "Tweaked pubkey is not broker pubkey + adaptor point".to_string(),
+            ));
+        }
+
+        // Recompute the nonce point: R = sG - eT
+        let (proof_s, proof_e) = (&proof.s, &proof.e);
+        let nonce_point = g!(proof_s * G - proof_e * adaptor_point)
+            .normalize()
+            .non_zero()
+            .ok_or_else(|| BrokerError::AdaptorSignature("Invalid DLEQ proof".to_string()))?;
+
+        let e = dleq_challenge(broker_pubkey, tweaked_pubkey, adaptor_point, &nonce_point, context);
+        if e == proof.e {
+            Ok(())
+        } else {
+            Err(BrokerError::AdaptorSignature(
+                "DLEQ proof verification failed".to_string(),
+            ))
+        }
+    }
+}
+
 impl Default for AdaptorContext {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// A secret scalar that is wiped from memory when dropped
+///
+/// `Scalar` is `Copy` and never cleared, so long-lived keys are kept as bytes
+/// in this instead; take the `Scalar` out only for the operation at hand.
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct SecretScalar([u8; 32]);
+
+impl SecretScalar {
+    pub fn new(scalar: &Scalar) -> Self {
+        Self(scalar.to_bytes())
+    }
+
+    /// Parse a 32-byte non-zero scalar
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| {
+            BrokerError::AdaptorSignature("Invalid scalar bytes length".to_string())
+        })?;
+        let secret = Self(bytes);
+        Scalar::<Secret, Zero>::from_bytes(secret.0)
+            .and_then(|s| s.non_zero())
+            .ok_or_else(|| BrokerError::AdaptorSignature("Invalid scalar bytes".to_string()))?;
+        Ok(secret)
+    }
+
+    pub fn scalar(&self) -> Scalar {
+        Scalar::from_bytes(self.0)
+            .and_then(|s| s.non_zero())
+            .expect("checked when created")
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_vec()
+    }
+}
+
+impl fmt::Debug for SecretScalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretScalar(..)")
+    }
+}
+
+pub use cashu_swap_protocol::ENCRYPTED_SIGNATURE_LEN;
+
+/// Encode an encrypted signature as `R (x-only) || s_hat || needs_negation`
+pub fn encode_encrypted_signature(encrypted_sig: &EncryptedSignature) -> Vec<u8> {
+    cashu_swap_protocol::encode_encrypted_signature(encrypted_sig).to_vec()
+}
+
+/// Decode an encrypted signature produced by [`encode_encrypted_signature`]
+pub fn decode_encrypted_signature(bytes: &[u8]) -> Result<EncryptedSignature> {
+    cashu_swap_protocol::decode_encrypted_signature(bytes)
+        .map_err(|e| BrokerError::AdaptorSignature(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_signature_roundtrip() {
+        let ctx = AdaptorContext::new();
+        let signing_key = ctx.generate_adaptor_secret();
+        let public_key = ctx.adaptor_point_from_secret(&signing_key);
+        let adaptor_secret = ctx.generate_adaptor_secret();
+        let adaptor_point = ctx.adaptor_point_from_secret(&adaptor_secret);
+
+        let encrypted = ctx
+            .create_encrypted_signature(&signing_key, &adaptor_point, b"transcript")
+            .unwrap();
+
+        let encoded = encode_encrypted_signature(&encrypted);
+        assert_eq!(encoded.len(), ENCRYPTED_SIGNATURE_LEN);
+
+        let decoded = decode_encrypted_signature(&encoded).unwrap();
+        ctx.verify_encrypted_signature(&public_key, &adaptor_point, b"transcript", &decoded)
+            .unwrap();
+
+        let decrypted = ctx.decrypt_signature(&adaptor_secret, decoded.clone()).unwrap();
+        let recovered = ctx
+            .recover_adaptor_secret(&adaptor_point, &decoded, &decrypted)
+            .unwrap();
+        assert_eq!(recovered, adaptor_secret);
+    }
+
+    #[test]
+    fn test_protocol_crate_signatures() {
+        let ctx = AdaptorContext::new();
+        let broker_key = ctx.generate_adaptor_secret();
+        let broker_pubkey = ctx.adaptor_point_from_secret(&broker_key);
+        let client_key = ctx.generate_adaptor_secret();
+        let client_pubkey = ctx.adaptor_point_from_secret(&client_key);
+        let adaptor_point = ctx.adaptor_point_from_secret(&ctx.generate_adaptor_secret());
+
+        // The broker's signatures verify in the client's crate...
+        let encrypted = ctx
+            .create_encrypted_signature(&broker_key, &adaptor_point, b"transcript")
+            .unwrap();
+        cashu_swap_protocol::verify_encrypted_signature(
+            &broker_pubkey.to_bytes(),
+            &adaptor_point.to_bytes(),
+            b"transcript",
+            &encode_encrypted_signature(&encrypted),
+        )
+        .unwrap();
+
+        // ...and the client's in the broker
+        let encoded = cashu_swap_protocol::create_encrypted_signature(
+            &client_key.to_bytes(),
+            &adaptor_point.to_bytes(),
+            b"transcript",
+        )
+        .unwrap();
+        ctx.verify_encrypted_signature(
+            &client_pubkey,
+            &adaptor_point,
+            b"transcript",
+            &decode_encrypted_signature(&encoded).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_golden_vectors() {
+        let ctx = AdaptorContext::new();
+        let point = |hex: &str| -> Point {
+            Point::from_bytes(hex::decode(hex).unwrap().try_into().unwrap()).unwrap()
+        };
+        let scalar = |hex: &str| -> Scalar {
+            Scalar::from_bytes(hex::decode(hex).unwrap().try_into().unwrap())
+                .unwrap()
+                .non_zero()
+                .unwrap()
+        };
+
+        for vector in cashu_swap_protocol::vectors::SWAP_VECTORS {
+            let adaptor_secret = scalar(vector.adaptor_secret);
+            let adaptor_point = ctx.adaptor_point_from_secret(&adaptor_secret);
+            assert_eq!(adaptor_point, point(vector.adaptor_point));
+            assert_eq!(
+                ctx.tweak_public_key(&point(vector.broker_public_key), &adaptor_point),
+                point(vector.broker_tweaked_pubkey)
+            );
+
+            // The broker checks and decrypts the client's signature as the
+            // vectors have it
+            let encrypted =
+                decode_encrypted_signature(&hex::decode(vector.client_encrypted_signature).unwrap())
+                    .unwrap();
+            ctx.verify_encrypted_signature(
+                &point(vector.client_public_key),
+                &adaptor_point,
+                vector.transcript.as_bytes(),
+                &encrypted,
+            )
+            .unwrap();
+            let decrypted = ctx.decrypt_signature(&adaptor_secret, encrypted).unwrap();
+            assert_eq!(hex::encode(decrypted.to_bytes()), vector.client_signature);
+
+            let encrypted =
+                decode_encrypted_signature(&hex::decode(vector.broker_encrypted_signature).unwrap())
+                    .unwrap();
+            ctx.verify_encrypted_signature(
+                &point(vector.broker_public_key),
+                &adaptor_point,
+                vector.transcript.as_bytes(),
+                &encrypted,
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_encrypted_witness() {
+        let ctx = AdaptorContext::new();
+        let signing_key = ctx.generate_adaptor_secret();
+        let public_key = ctx.adaptor_point_from_secret(&signing_key);
+        let adaptor_secret = ctx.generate_adaptor_secret();
+        let adaptor_point = ctx.adaptor_point_from_secret(&adaptor_secret);
+        let secret = r#"["P2PK",{"nonce":"00","data":"02ab"}]"#;
+
+        let encrypted = ctx
+            .create_encrypted_witness(&signing_key, &adaptor_point, secret)
+            .unwrap();
+        ctx.verify_encrypted_witness(&public_key, &adaptor_point, secret, &encrypted)
+            .unwrap();
+        assert!(ctx
+            .verify_encrypted_witness(&public_key, &adaptor_point, "other", &encrypted)
+            .is_err());
+
+        // Decrypted, it's the BIP-340 signature a mint checks
+        let decrypted = ctx.decrypt_signature(&adaptor_secret, encrypted.clone()).unwrap();
+        let xonly = Point::<EvenY>::from_xonly_bytes(public_key.to_xonly_bytes()).unwrap();
+        let digest = Sha256::digest(secret.as_bytes());
+        assert!(ctx.schnorr.verify(&xonly, Message::<Public>::raw(&digest), &decrypted));
+
+        let recovered = ctx
+            .recover_adaptor_secret(&adaptor_point, &encrypted, &decrypted)
+            .unwrap();
+        assert_eq!(recovered, adaptor_secret);
+    }
+
+    #[test]
+    fn test_dleq_proof() {
+        let ctx = AdaptorContext::new();
+        let broker_key = ctx.generate_adaptor_secret();
+        let broker_pubkey = ctx.adaptor_point_from_secret(&broker_key);
+        let adaptor_secret = ctx.generate_adaptor_secret();
+        let adaptor_point = ctx.adaptor_point_from_secret(&adaptor_secret);
+        let tweaked = ctx.tweak_public_key(&broker_pubkey, &adaptor_point);
+
+        let proof = ctx.create_dleq_proof(&adaptor_secret, &broker_pubkey, &tweaked, b"quote-1");
+        let decoded = DleqProof::from_bytes(&proof.to_bytes()).unwrap();
+
+        ctx.verify_dleq_proof(&decoded, &broker_pubkey, &tweaked, &adaptor_point, b"quote-1")
+            .unwrap();
+
+        // Bound to the quote it was issued for
+        assert!(ctx
+            .verify_dleq_proof(&decoded, &broker_pubkey, &tweaked, &adaptor_point, b"quote-2")
+            .is_err());
+
+        // Rejects a tweaked key that does not use T
+        let other_point = ctx.adaptor_point_from_secret(&ctx.generate_adaptor_secret());
+        let other_tweaked = ctx.tweak_public_key(&broker_pubkey, &other_point);
+        assert!(ctx
+            .verify_dleq_proof(&decoded, &broker_pubkey, &other_tweaked, &adaptor_point, b"quote-1")
+            .is_err());
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        let a = AdaptorContext::new().with_rng(SwapRng::seeded(1));
+        let b = AdaptorContext::new().with_rng(SwapRng::seeded(1));
+        let secret = a.generate_adaptor_secret();
+        assert_eq!(secret, b.generate_adaptor_secret());
+
+        let broker_key = a.generate_adaptor_secret();
+        b.generate_adaptor_secret();
+        let broker_pubkey = a.adaptor_point_from_secret(&broker_key);
+        let tweaked =
+            a.tweak_public_key(&broker_pubkey, &a.adaptor_point_from_secret(&secret));
+        let proof = |ctx: &AdaptorContext| {
+            ctx.create_dleq_proof(&secret, &broker_pubkey, &tweaked, b"quote-1")
+                .to_bytes()
+        };
+        assert_eq!(proof(&a), proof(&b));
+    }
+
+    #[test]
+    fn test_secret_scalar() {
+        let ctx = AdaptorContext::new();
+        let scalar = ctx.generate_adaptor_secret();
+        let secret = SecretScalar::new(&scalar);
+
+        assert_eq!(secret.scalar(), scalar);
+        assert_eq!(SecretScalar::from_bytes(&secret.to_bytes()).unwrap(), secret);
+        assert_eq!(format!("{:?}", secret), "SecretScalar(..)");
+
+        assert!(SecretScalar::from_bytes(&[0u8; 32]).is_err());
+        assert!(SecretScalar::from_bytes(&[1u8; 31]).is_err());
+
+        let mut wiped = secret.clone();
+        wiped.zeroize();
+        assert_eq!(wiped.0, [0u8; 32]);
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_length() {
+        assert!(decode_encrypted_signature(&[0u8; 10]).is_err());
+    }
+}
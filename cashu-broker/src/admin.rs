@@ -0,0 +1,972 @@
+//! Admin API for managing the broker at runtime
+//!
+//! Every route requires `Authorization: Bearer <ADMIN_TOKEN>`. The router is
+//! only mounted when an admin token is configured. Changes made through it,
+//! and quote exports, are recorded in the audit log under the name the caller
+//! gives in `X-Admin-Actor`.
+
+use crate::accounting::AccountingSummary;
+use crate::api::{ApiError, AppState, MetricsQuery};
+use crate::api_keys::generate_api_key;
+use crate::blacklist::{self, BlacklistKind};
+use crate::db::{ApiKeyRecord, AuditEntry, BlacklistEntry, WebhookDelivery};
+use crate::error::BrokerError;
+use crate::export::{self, ExportFormat};
+use crate::quarantine::{QuarantineReason, QuarantineRetry, QuarantinedProof};
+use crate::request_id::RequestId;
+use crate::types::{BrokerConfig, MintConfig};
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Extension, Json, Router,
+};
+use cdk::nuts::PublicKey;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+/// Export rows buffered ahead of a slow client
+const EXPORT_BUFFER: usize = 64;
+
+/// Header naming the operator behind an admin request
+pub const ADMIN_ACTOR_HEADER: &str = "x-admin-actor";
+
+/// Actor recorded when a request doesn't name one
+const DEFAULT_ACTOR: &str = "admin";
+
+/// Longest actor name that is kept
+const MAX_ACTOR_LEN: usize = 64;
+
+/// Audit entries returned when no limit is given, and the most returned at once
+const DEFAULT_AUDIT_LIMIT: i64 = 100;
+const MAX_AUDIT_LIMIT: i64 = 1000;
+
+/// Create the admin router, guarded by a bearer token
+pub fn router(admin_token: String) -> Router<AppState> {
+    let admin_token: Arc<str> = admin_token.into();
+
+    Router::new()
+        .route("/config", get(get_config))
+        .route("/fee-rate", put(set_fee_rate))
+        .route("/maintenance", get(get_maintenance).put(set_maintenance))
+        .route("/mints", post(add_mint))
+        .route("/mints/:mint_url", delete(remove_mint))
+        .route("/quotes/:id/expire", post(expire_quote))
+        .route("/quotes/:id/webhooks", get(list_webhook_deliveries))
+        .route("/liquidity/:mint_url/deposit", post(deposit_ecash))
+        .route("/liquidity/:mint_url/invoice", post(create_deposit_invoice))
+        .route("/liquidity/:mint_url/withdraw", post(withdraw_ecash))
+        .route("/liquidity/:mint_url/melt", post(withdraw_lightning))
+        .route("/quarantine", get(list_quarantine))
+        .route("/quarantine/:mint_url/retry", post(retry_quarantine))
+        .route("/quarantine/:mint_url/discard", post(discard_quarantine))
+        .route("/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api-keys/:id", delete(revoke_api_key))
+        .route("/blacklist", get(list_blacklist).post(add_blacklist_entry))
+        .route("/blacklist/:kind/:value", delete(remove_blacklist_entry))
+        .route("/accounting/summary", get(get_accounting_summary))
+        .route("/export/quotes", get(export_quotes))
+        .route("/audit-log", get(list_audit_log))
+        .layer(middleware::from_fn(move |req: Request, next: Next| {
+            let admin_token = admin_token.clone();
+            async move { require_token(&admin_token, req, next).await }
+        }))
+}
+
+/// Reject requests without the admin bearer token
+async fn require_token(
+    admin_token: &str,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| ApiError::Unauthorized("Missing admin token".to_string()))?;
+
+    if !tokens_match(provided, admin_token) {
+        return Err(ApiError::Unauthorized("Invalid admin token".to_string()));
+    }
+
+    let actor = AdminActor::from_request(&req);
+    req.extensions_mut().insert(actor);
+
+    Ok(next.run(req).await)
+}
+
+/// Who made an admin request, as recorded in the audit log
+///
+/// All operators share the admin token, so the name is whatever the caller
+/// sends in `X-Admin-Actor`; it is a label for the log, not an identity.
+#[derive(Debug, Clone)]
+pub struct AdminActor {
+    pub name: String,
+    pub request_id: Option<String>,
+}
+
+impl AdminActor {
+    fn from_request(req: &Request) -> Self {
+        let name = req
+            .headers()
+            .get(ADMIN_ACTOR_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|name| {
+                !name.is_empty()
+                    && name.len() <= MAX_ACTOR_LEN
+                    && !name.chars().any(char::is_control)
+            })
+            .unwrap_or(DEFAULT_ACTOR)
+            .to_string();
+        let request_id = req.extensions().get::<RequestId>().map(|id| id.0.clone());
+
+        Self { name, request_id }
+    }
+
+    /// Record an action in the audit log
+    ///
+    /// The action has already been carried out, so failing to record it is
+    /// logged rather than returned to the caller.
+    async fn audit(
+        &self,
+        state: &AppState,
+        action: &str,
+        target: Option<&str>,
+        before: Option<Value>,
+        after: Option<Value>,
+    ) {
+        let entry = AuditEntry {
+            id: None,
+            actor: self.name.clone(),
+            action: action.to_string(),
+            target: target.map(str::to_string),
+            before: before.map(|value| value.to_string()),
+            after: after.map(|value| value.to_string()),
+            request_id: self.request_id.clone(),
+            created_at: Utc::now().to_rfc3339(),
+        };
+
+        if let Err(e) = state.db.record_audit_entry(&entry).await {
+            error!("Failed to audit {} by {}: {}", action, self.name, e);
+        }
+    }
+}
+
+/// Compare tokens in constant time (over their hashes, so length doesn't leak)
+pub(crate) fn tokens_match(provided: &str, expected: &str) -> bool {
+    let provided = Sha256::digest(provided.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+
+    provided
+        .iter()
+        .zip(expected.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Map broker errors caused by the admin's input to 400s
+fn admin_error(err: BrokerError) -> ApiError {
+    match err {
+        BrokerError::InvalidSwapRequest(_) | BrokerError::UnsupportedMint(_) => {
+            ApiError::BadRequest(err.to_string())
+        }
+        _ => ApiError::from(err),
+    }
+}
+
+// ===== Request/Response Types =====
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeeRateRequest {
+    pub fee_rate: f64,
+}
+
+/// Maintenance mode, as set and reported by `/admin/maintenance`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaintenanceMode {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AmountRequest {
+    pub amount: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepositRequest {
+    pub token: String, // Cashu token (cashuA/cashuB) for the mint
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepositResponse {
+    pub amount: u64,
+    pub balance: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepositInvoiceResponse {
+    pub quote_id: String,
+    pub invoice: String, // BOLT11 invoice to pay
+    pub amount: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WithdrawResponse {
+    pub token: String, // Cashu token holding the withdrawn proofs
+    pub amount: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MeltRequest {
+    pub invoice: String, // BOLT11 invoice to pay
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MeltResponse {
+    pub amount: u64,
+    pub fee_paid: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preimage: Option<String>,
+}
+
+/// Filter for `GET /admin/quarantine`
+#[derive(Debug, Deserialize)]
+pub struct QuarantineQuery {
+    pub mint_url: Option<String>,
+}
+
+/// A quarantined proof, without its secret
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+    pub mint_url: String,
+    pub y: String, // Hex, as the mint knows the proof
+    pub amount: u64,
+    pub keyset_id: String,
+    pub reason: QuarantineReason,
+    pub detail: String,
+    pub quarantined_at: String, // RFC 3339
+}
+
+impl QuarantineEntry {
+    fn new(mint_url: String, entry: &QuarantinedProof) -> Self {
+        Self {
+            mint_url,
+            y: entry.y().map(|y| y.to_hex()).unwrap_or_default(),
+            amount: entry.amount(),
+            keyset_id: entry.proof.keyset_id.to_string(),
+            reason: entry.reason,
+            detail: entry.detail.clone(),
+            quarantined_at: DateTime::<Utc>::from(entry.since).to_rfc3339(),
+        }
+    }
+}
+
+/// Quarantined proofs to act on, by Y (hex)
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QuarantineRequest {
+    #[serde(default)]
+    pub ys: Vec<String>, // A retry with none takes every proof of the mint
+}
+
+impl QuarantineRequest {
+    fn parse_ys(&self) -> Result<Vec<PublicKey>, ApiError> {
+        self.ys
+            .iter()
+            .map(|y| {
+                PublicKey::from_hex(y.trim())
+                    .map_err(|e| ApiError::BadRequest(format!("Invalid proof Y {}: {}", y, e)))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiscardResponse {
+    pub discarded: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+}
+
+/// Public key or proof Y to ban, as hex
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlacklistRequest {
+    pub kind: BlacklistKind,
+    pub value: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    pub key: String, // Only returned once; the broker stores a hash
+}
+
+/// Format and time window for `GET /admin/export/quotes`
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat, // csv (default) or jsonl
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>, // RFC 3339, inclusive
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>, // RFC 3339, exclusive
+}
+
+/// Filters for `GET /admin/audit-log`
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub action: Option<String>,
+    pub limit: Option<i64>, // Default 100, at most 1000
+}
+
+/// An audit log entry, with the before and after values as JSON
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub id: Option<i64>,
+    pub actor: String,
+    pub action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub created_at: String,
+}
+
+impl From<AuditEntry> for AuditLogEntry {
+    fn from(entry: AuditEntry) -> Self {
+        // Values are written as JSON; anything else is passed through as a string
+        let parse = |text: String| serde_json::from_str(&text).unwrap_or(Value::String(text));
+
+        Self {
+            id: entry.id,
+            actor: entry.actor,
+            action: entry.action,
+            target: entry.target,
+            before: entry.before.map(parse),
+            after: entry.after.map(parse),
+            request_id: entry.request_id,
+            created_at: entry.created_at,
+        }
+    }
+}
+
+// ===== Handlers =====
+
+/// Get the current broker configuration
+async fn get_config(State(state): State<AppState>) -> Json<BrokerConfig> {
+    Json(state.broker.get_config())
+}
+
+/// Change the fee rate for new quotes
+async fn set_fee_rate(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Json(req): Json<FeeRateRequest>,
+) -> Result<Json<BrokerConfig>, ApiError> {
+    let before = state.broker.get_config().fee_rate;
+    state
+        .broker
+        .set_fee_rate(req.fee_rate)
+        .map_err(admin_error)?;
+
+    actor
+        .audit(
+            &state,
+            "set_fee_rate",
+            None,
+            Some(json!({ "fee_rate": before })),
+            Some(json!({ "fee_rate": req.fee_rate })),
+        )
+        .await;
+
+    Ok(Json(state.broker.get_config()))
+}
+
+/// Whether the broker is in maintenance mode
+async fn get_maintenance(State(state): State<AppState>) -> Json<MaintenanceMode> {
+    Json(MaintenanceMode {
+        enabled: state.broker.in_maintenance(),
+    })
+}
+
+/// Turn maintenance mode on or off
+///
+/// New quotes are refused while it is on; open quotes can still be accepted
+/// and completed.
+async fn set_maintenance(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Json(req): Json<MaintenanceMode>,
+) -> Result<Json<MaintenanceMode>, ApiError> {
+    let before = state.broker.in_maintenance();
+    state
+        .broker
+        .set_maintenance(req.enabled)
+        .await
+        .map_err(ApiError::from)?;
+
+    actor
+        .audit(
+            &state,
+            "set_maintenance",
+            None,
+            Some(json!({ "enabled": before })),
+            Some(json!({ "enabled": req.enabled })),
+        )
+        .await;
+
+    Ok(Json(MaintenanceMode {
+        enabled: req.enabled,
+    }))
+}
+
+/// Add a supported mint
+async fn add_mint(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Json(mint): Json<MintConfig>,
+) -> Result<(StatusCode, Json<BrokerConfig>), ApiError> {
+    info!("Admin: adding mint {}", mint.mint_url);
+
+    let after = json!(mint);
+    let mint_url = mint.mint_url.clone();
+    state.broker.add_mint(mint).await.map_err(admin_error)?;
+
+    actor
+        .audit(&state, "add_mint", Some(&mint_url), None, Some(after))
+        .await;
+
+    Ok((StatusCode::CREATED, Json(state.broker.get_config())))
+}
+
+/// Remove a supported mint
+async fn remove_mint(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path(mint_url): Path<String>,
+) -> Result<Json<BrokerConfig>, ApiError> {
+    info!("Admin: removing mint {}", mint_url);
+
+    let before = state
+        .broker
+        .get_config()
+        .mints
+        .into_iter()
+        .find(|mint| mint.mint_url == mint_url)
+        .map(|mint| json!(mint));
+    state
+        .broker
+        .remove_mint(&mint_url)
+        .await
+        .map_err(admin_error)?;
+
+    actor
+        .audit(&state, "remove_mint", Some(&mint_url), before, None)
+        .await;
+
+    Ok(Json(state.broker.get_config()))
+}
+
+/// Force a pending quote to expire
+async fn expire_quote(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    state.broker.expire_quote(&id).await.map_err(admin_error)?;
+
+    actor
+        .audit(
+            &state,
+            "expire_quote",
+            Some(&id),
+            Some(json!({ "status": "pending" })),
+            Some(json!({ "status": "expired" })),
+        )
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the webhook deliveries of a quote
+async fn list_webhook_deliveries(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<WebhookDelivery>>, ApiError> {
+    let deliveries = state
+        .db
+        .list_webhook_deliveries(&id)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(deliveries))
+}
+
+/// Deposit liquidity on a mint from a cashu token
+async fn deposit_ecash(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path(mint_url): Path<String>,
+    Json(req): Json<DepositRequest>,
+) -> Result<Json<DepositResponse>, ApiError> {
+    let before = state.broker.get_mint_balance(&mint_url).await;
+    let amount = state
+        .broker
+        .deposit_ecash(&mint_url, &req.token)
+        .await
+        .map_err(admin_error)?;
+
+    let balance = state.broker.get_mint_balance(&mint_url).await;
+    actor
+        .audit(
+            &state,
+            "deposit_ecash",
+            Some(&mint_url),
+            Some(json!({ "balance": before })),
+            Some(json!({ "balance": balance, "amount": amount })),
+        )
+        .await;
+
+    Ok(Json(DepositResponse { amount, balance }))
+}
+
+/// Create a Lightning invoice that deposits liquidity on a mint once paid
+async fn create_deposit_invoice(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path(mint_url): Path<String>,
+    Json(req): Json<AmountRequest>,
+) -> Result<Json<DepositInvoiceResponse>, ApiError> {
+    let quote = state
+        .broker
+        .create_deposit_invoice(&mint_url, req.amount)
+        .await
+        .map_err(admin_error)?;
+
+    actor
+        .audit(
+            &state,
+            "create_deposit_invoice",
+            Some(&mint_url),
+            None,
+            Some(json!({ "amount": req.amount, "mint_quote_id": quote.id })),
+        )
+        .await;
+
+    let response = DepositInvoiceResponse {
+        quote_id: quote.id.clone(),
+        invoice: quote.request.clone(),
+        amount: req.amount,
+    };
+
+    // Mint the ecash in the background once the invoice is paid
+    let broker = state.broker.clone();
+    tokio::spawn(async move {
+        match broker.claim_deposit(&mint_url, quote).await {
+            Ok(amount) => info!("Deposit of {} sats on {} completed", amount, mint_url),
+            Err(e) => error!("Deposit on {} failed: {}", mint_url, e),
+        }
+    });
+
+    Ok(Json(response))
+}
+
+/// Withdraw liquidity from a mint as a cashu token
+async fn withdraw_ecash(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path(mint_url): Path<String>,
+    Json(req): Json<AmountRequest>,
+) -> Result<Json<WithdrawResponse>, ApiError> {
+    info!("Admin: withdrawing {} sats from {}", req.amount, mint_url);
+
+    let before = state.broker.get_mint_balance(&mint_url).await;
+    let (token, amount) = state
+        .broker
+        .withdraw_ecash(&mint_url, req.amount)
+        .await
+        .map_err(admin_error)?;
+
+    let balance = state.broker.get_mint_balance(&mint_url).await;
+    actor
+        .audit(
+            &state,
+            "withdraw_ecash",
+            Some(&mint_url),
+            Some(json!({ "balance": before })),
+            Some(json!({ "balance": balance, "amount": amount })),
+        )
+        .await;
+
+    Ok(Json(WithdrawResponse { token, amount }))
+}
+
+/// Pay a Lightning invoice from a mint's liquidity
+async fn withdraw_lightning(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path(mint_url): Path<String>,
+    Json(req): Json<MeltRequest>,
+) -> Result<Json<MeltResponse>, ApiError> {
+    info!("Admin: paying invoice from {}", mint_url);
+
+    let before = state.broker.get_mint_balance(&mint_url).await;
+    let payment = state
+        .broker
+        .withdraw_lightning(&mint_url, &req.invoice)
+        .await
+        .map_err(admin_error)?;
+
+    let balance = state.broker.get_mint_balance(&mint_url).await;
+    actor
+        .audit(
+            &state,
+            "withdraw_lightning",
+            Some(&mint_url),
+            Some(json!({ "balance": before })),
+            Some(json!({
+                "balance": balance,
+                "amount": payment.amount,
+                "fee_paid": payment.fee_paid,
+            })),
+        )
+        .await;
+
+    Ok(Json(MeltResponse {
+        amount: payment.amount,
+        fee_paid: payment.fee_paid,
+        preimage: payment.preimage,
+    }))
+}
+
+/// List quarantined proofs, on every mint or one
+async fn list_quarantine(
+    State(state): State<AppState>,
+    Query(query): Query<QuarantineQuery>,
+) -> Json<Vec<QuarantineEntry>> {
+    let entries = state
+        .broker
+        .quarantined_proofs(query.mint_url.as_deref())
+        .await
+        .iter()
+        .map(|(mint_url, entry)| QuarantineEntry::new(mint_url.clone(), entry))
+        .collect();
+
+    Json(entries)
+}
+
+/// Ask the mint about quarantined proofs, releasing those still unspent
+async fn retry_quarantine(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path(mint_url): Path<String>,
+    req: Option<Json<QuarantineRequest>>,
+) -> Result<Json<QuarantineRetry>, ApiError> {
+    let Json(req) = req.unwrap_or_default();
+    let ys = req.parse_ys()?;
+    info!("Admin: retrying quarantined proofs on {}", mint_url);
+
+    let outcome = state
+        .broker
+        .retry_quarantined(&mint_url, &ys)
+        .await
+        .map_err(admin_error)?;
+
+    actor
+        .audit(
+            &state,
+            "retry_quarantine",
+            Some(&mint_url),
+            Some(json!({ "ys": req.ys })),
+            Some(json!(outcome)),
+        )
+        .await;
+
+    Ok(Json(outcome))
+}
+
+/// Drop quarantined proofs for good
+async fn discard_quarantine(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path(mint_url): Path<String>,
+    Json(req): Json<QuarantineRequest>,
+) -> Result<Json<DiscardResponse>, ApiError> {
+    let ys = req.parse_ys()?;
+    info!("Admin: discarding {} quarantined proofs on {}", ys.len(), mint_url);
+
+    let discarded = state
+        .broker
+        .discard_quarantined(&mint_url, &ys)
+        .await
+        .map_err(admin_error)?;
+
+    actor
+        .audit(
+            &state,
+            "discard_quarantine",
+            Some(&mint_url),
+            Some(json!({ "ys": req.ys })),
+            Some(json!({ "discarded": discarded })),
+        )
+        .await;
+
+    Ok(Json(DiscardResponse { discarded }))
+}
+
+/// Issue a new API key
+async fn create_api_key(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Json(req): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<CreateApiKeyResponse>), ApiError> {
+    if req.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("API key name is required".to_string()));
+    }
+
+    let (key, record) = generate_api_key(req.name.trim());
+    state.db.create_api_key(&record).await.map_err(ApiError::from)?;
+
+    info!("Admin: issued API key {} ({})", record.id, record.name);
+    actor
+        .audit(
+            &state,
+            "create_api_key",
+            Some(&record.id),
+            None,
+            Some(json!({ "name": record.name })),
+        )
+        .await;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateApiKeyResponse {
+            id: record.id,
+            name: record.name,
+            key,
+        }),
+    ))
+}
+
+/// List issued API keys (without the keys themselves)
+async fn list_api_keys(State(state): State<AppState>) -> Result<Json<Vec<ApiKeyRecord>>, ApiError> {
+    let keys = state.db.list_api_keys().await.map_err(ApiError::from)?;
+
+    Ok(Json(keys))
+}
+
+/// Revoke an API key
+async fn revoke_api_key(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !state.db.revoke_api_key(&id).await.map_err(ApiError::from)? {
+        return Err(ApiError::NotFound(format!("API key {} not found", id)));
+    }
+
+    info!("Admin: revoked API key {}", id);
+    actor
+        .audit(&state, "revoke_api_key", Some(&id), None, None)
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List banned public keys and proofs, newest first
+async fn list_blacklist(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<BlacklistEntry>>, ApiError> {
+    let entries = state.db.list_blacklist().await.map_err(ApiError::from)?;
+
+    Ok(Json(entries))
+}
+
+/// Ban a public key or proof
+///
+/// Takes effect on the next quote request or accept; quotes already accepted
+/// can still complete.
+async fn add_blacklist_entry(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Json(req): Json<BlacklistRequest>,
+) -> Result<(StatusCode, Json<BlacklistEntry>), ApiError> {
+    let value = blacklist::normalize(req.kind, &req.value).map_err(admin_error)?;
+    let reason = req
+        .reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    let entry = BlacklistEntry::new(req.kind, &value, reason);
+    state
+        .db
+        .add_blacklist_entry(&entry)
+        .await
+        .map_err(ApiError::from)?;
+
+    info!("Admin: blacklisted {} {}", req.kind, value);
+    actor
+        .audit(
+            &state,
+            "add_blacklist_entry",
+            Some(&value),
+            None,
+            Some(json!({ "kind": entry.kind, "reason": entry.reason })),
+        )
+        .await;
+
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+/// Lift a ban
+async fn remove_blacklist_entry(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Path((kind, value)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    let kind: BlacklistKind = kind.parse().map_err(ApiError::BadRequest)?;
+    let value = blacklist::normalize(kind, &value).map_err(admin_error)?;
+    if !state
+        .db
+        .remove_blacklist_entry(kind, &value)
+        .await
+        .map_err(ApiError::from)?
+    {
+        return Err(ApiError::NotFound(format!("{} {} is not blacklisted", kind, value)));
+    }
+
+    info!("Admin: removed {} {} from the blacklist", kind, value);
+    actor
+        .audit(
+            &state,
+            "remove_blacklist_entry",
+            Some(&value),
+            Some(json!({ "kind": kind })),
+            None,
+        )
+        .await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Profit and loss per mint and unit, over an optional time window
+async fn get_accounting_summary(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<AccountingSummary>, ApiError> {
+    let totals = state
+        .db
+        .get_accounting_totals(query.from, query.to)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(AccountingSummary::new(query.from, query.to, &totals)))
+}
+
+/// Export quotes as CSV or JSON lines
+///
+/// Rows are read in a background task and streamed out as they arrive; the
+/// task stops when the client goes away. A database error midway cuts the
+/// response short.
+async fn export_quotes(
+    State(state): State<AppState>,
+    Extension(actor): Extension<AdminActor>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let format = query.format;
+    actor
+        .audit(
+            &state,
+            "export_quotes",
+            None,
+            None,
+            Some(json!({ "format": format.extension(), "from": query.from, "to": query.to })),
+        )
+        .await;
+
+    let (tx, rx) = mpsc::channel::<Result<String, BrokerError>>(EXPORT_BUFFER);
+
+    tokio::spawn(async move {
+        if let Some(header) = format.header() {
+            if tx.send(Ok(header)).await.is_err() {
+                return;
+            }
+        }
+
+        let mut rows = state.db.export_quotes(query.from, query.to);
+        while let Some(row) = rows.next().await {
+            let line = row.map(|quote| format.row(&quote));
+            if let Err(e) = &line {
+                error!("Quote export failed: {}", e);
+            }
+            let failed = line.is_err();
+            if tx.send(line).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    let lines = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|line| (line, rx))
+    });
+    let disposition = format!(
+        "attachment; filename=\"{}\"",
+        export::filename(format, Utc::now())
+    );
+
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
+
+/// List audit log entries, newest first
+async fn list_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<Vec<AuditLogEntry>>, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_AUDIT_LIMIT)
+        .clamp(1, MAX_AUDIT_LIMIT);
+    let entries = state
+        .db
+        .list_audit_entries(query.action.as_deref(), limit)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(entries.into_iter().map(AuditLogEntry::from).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("secret-token", "secret-token"));
+        assert!(!tokens_match("secret-token", "secret-tokem"));
+        assert!(!tokens_match("", "secret-token"));
+    }
+}
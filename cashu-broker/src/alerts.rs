@@ -0,0 +1,524 @@
+//! Operator alerts
+//!
+//! The [`Alerter`] follows the broker's event bus and raises an [`Alert`] when
+//! a mint's balance drops below a threshold, when swaps fail several times in
+//! a row, when a mint starts failing health checks, or when proofs are
+//! quarantined. Alerts go to every configured [`AlertSink`]: a generic JSON
+//! webhook or a Telegram bot.
+//!
+//! Each condition alerts once and only again after it has cleared, so a
+//! balance hovering around the threshold does not flood the channel.
+//! Quarantined proofs alert every time.
+
+use crate::error::{BrokerError, Result};
+use crate::events::BrokerEvent;
+use crate::quarantine::QuarantineReason;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+/// Timeout for a single alert delivery
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Telegram Bot API endpoint
+const TELEGRAM_API: &str = "https://api.telegram.org";
+
+/// Something an operator should look at
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Alert {
+    LowBalance {
+        mint_url: String,
+        balance: u64,
+        threshold: u64,
+    },
+    FailedSwaps {
+        count: u32,
+        quote_id: String, // The latest failure
+        error: String,
+    },
+    MintUnhealthy {
+        mint_url: String,
+    },
+    MintRecovered {
+        mint_url: String,
+    },
+    ProofsQuarantined {
+        mint_url: String,
+        amount: u64,
+        reason: QuarantineReason,
+    },
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alert::LowBalance {
+                mint_url,
+                balance,
+                threshold,
+            } => write!(
+                f,
+                "Low liquidity on {}: {} sats (threshold {})",
+                mint_url, balance, threshold
+            ),
+            Alert::FailedSwaps {
+                count,
+                quote_id,
+                error,
+            } => write!(
+                f,
+                "{} swaps failed in a row, latest {}: {}",
+                count, quote_id, error
+            ),
+            Alert::MintUnhealthy { mint_url } => {
+                write!(f, "Mint {} is failing health checks", mint_url)
+            }
+            Alert::MintRecovered { mint_url } => write!(f, "Mint {} is healthy again", mint_url),
+            Alert::ProofsQuarantined {
+                mint_url,
+                amount,
+                reason,
+            } => write!(
+                f,
+                "Quarantined {} sats of proofs on {} ({})",
+                amount, mint_url, reason
+            ),
+        }
+    }
+}
+
+/// Somewhere alerts are delivered
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &str;
+
+    async fn send(&self, alert: &Alert) -> Result<()>;
+}
+
+/// Body posted by the [`WebhookAlertSink`]
+#[derive(Serialize)]
+struct AlertPayload<'a> {
+    #[serde(flatten)]
+    alert: &'a Alert,
+    message: String,
+    timestamp: String,
+}
+
+/// Posts each alert as JSON to a URL
+///
+/// The body is the alert's fields with a `kind` tag, plus a human-readable
+/// `message` and a `timestamp`.
+pub struct WebhookAlertSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookAlertSink {
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+            url: url.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl AlertSink for WebhookAlertSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let payload = AlertPayload {
+            alert,
+            message: alert.to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+        };
+
+        self.client
+            .post(&self.url)
+            .json(&payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| BrokerError::Alert(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+/// Sends each alert as a message from a Telegram bot
+pub struct TelegramAlertSink {
+    client: reqwest::Client,
+    bot_token: String,
+    chat_id: String,
+}
+
+impl TelegramAlertSink {
+    pub fn new(bot_token: &str, chat_id: &str) -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+            bot_token: bot_token.to_string(),
+            chat_id: chat_id.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl AlertSink for TelegramAlertSink {
+    fn name(&self) -> &str {
+        "telegram"
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let url = format!("{}/bot{}/sendMessage", TELEGRAM_API, self.bot_token);
+        let body = serde_json::json!({
+            "chat_id": self.chat_id,
+            "text": format!("⚠️ {}", alert),
+        });
+
+        // reqwest errors include the URL, which holds the bot token
+        let response = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| BrokerError::Alert(e.without_url().to_string()))?;
+        if !response.status().is_success() {
+            return Err(BrokerError::Alert(format!(
+                "Telegram returned {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| BrokerError::Alert(e.to_string()))
+}
+
+/// When alerts are raised
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlertThresholds {
+    /// Alert when a mint's balance drops below this many sats (0 = off)
+    pub low_balance: u64,
+    /// Alert after this many swaps fail in a row (0 = off)
+    pub failed_swaps: u32,
+}
+
+/// Turns broker events into alerts
+pub struct Alerter {
+    thresholds: AlertThresholds,
+    sinks: Vec<Arc<dyn AlertSink>>,
+    low_mints: HashSet<String>,
+    unhealthy_mints: HashSet<String>,
+    consecutive_failures: u32,
+}
+
+impl Alerter {
+    pub fn new(thresholds: AlertThresholds) -> Self {
+        Self {
+            thresholds,
+            sinks: Vec::new(),
+            low_mints: HashSet::new(),
+            unhealthy_mints: HashSet::new(),
+            consecutive_failures: 0,
+        }
+    }
+
+    /// Also deliver alerts to `sink`
+    pub fn sink(mut self, sink: Arc<dyn AlertSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Deliver alerts for `events` until the bus closes
+    pub async fn run(mut self, mut events: broadcast::Receiver<BrokerEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Some(alert) = self.check(&event) {
+                        self.send(&alert).await;
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Alerter fell behind, skipped {} events", missed)
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    async fn send(&self, alert: &Alert) {
+        warn!("Alert: {}", alert);
+        for sink in &self.sinks {
+            if let Err(e) = sink.send(alert).await {
+                warn!("Failed to send alert to {}: {}", sink.name(), e);
+            }
+        }
+    }
+
+    /// Update the tracked conditions with `event`, returning the alert it raises
+    fn check(&mut self, event: &BrokerEvent) -> Option<Alert> {
+        match event {
+            BrokerEvent::BalanceChanged { mint_url, balance } => {
+                let threshold = self.thresholds.low_balance;
+                if threshold == 0 {
+                    return None;
+                }
+                if *balance >= threshold {
+                    self.low_mints.remove(mint_url);
+                    return None;
+                }
+                self.low_mints
+                    .insert(mint_url.clone())
+                    .then(|| Alert::LowBalance {
+                        mint_url: mint_url.clone(),
+                        balance: *balance,
+                        threshold,
+                    })
+            }
+            BrokerEvent::SwapCompleted { .. } => {
+                self.consecutive_failures = 0;
+                None
+            }
+            BrokerEvent::SwapFailed { quote_id, error } => {
+                self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+                (self.consecutive_failures == self.thresholds.failed_swaps).then(|| {
+                    Alert::FailedSwaps {
+                        count: self.consecutive_failures,
+                        quote_id: quote_id.clone(),
+                        error: error.clone(),
+                    }
+                })
+            }
+            BrokerEvent::MintHealthChanged { mint_url, healthy } => {
+                if *healthy {
+                    self.unhealthy_mints
+                        .remove(mint_url)
+                        .then(|| Alert::MintRecovered {
+                            mint_url: mint_url.clone(),
+                        })
+                } else {
+                    self.unhealthy_mints
+                        .insert(mint_url.clone())
+                        .then(|| Alert::MintUnhealthy {
+                            mint_url: mint_url.clone(),
+                        })
+                }
+            }
+            BrokerEvent::ProofsQuarantined {
+                mint_url,
+                amount,
+                reason,
+            } => Some(Alert::ProofsQuarantined {
+                mint_url: mint_url.clone(),
+                amount: *amount,
+                reason: *reason,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventBus;
+    use std::sync::Mutex;
+
+    const MINT: &str = "http://mint-a.test";
+
+    fn balance(balance: u64) -> BrokerEvent {
+        BrokerEvent::BalanceChanged {
+            mint_url: MINT.to_string(),
+            balance,
+        }
+    }
+
+    fn failed(quote_id: &str) -> BrokerEvent {
+        BrokerEvent::SwapFailed {
+            quote_id: quote_id.to_string(),
+            error: "mint offline".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_low_balance_alerts_once() {
+        let mut alerter = Alerter::new(AlertThresholds {
+            low_balance: 1000,
+            failed_swaps: 0,
+        });
+
+        assert_eq!(alerter.check(&balance(5000)), None);
+        assert_eq!(
+            alerter.check(&balance(900)),
+            Some(Alert::LowBalance {
+                mint_url: MINT.to_string(),
+                balance: 900,
+                threshold: 1000,
+            })
+        );
+        // Still low: no repeat until the balance recovers
+        assert_eq!(alerter.check(&balance(800)), None);
+        assert_eq!(alerter.check(&balance(1000)), None);
+        assert!(alerter.check(&balance(999)).is_some());
+
+        // Disabled with a zero threshold
+        let mut off = Alerter::new(AlertThresholds {
+            low_balance: 0,
+            failed_swaps: 0,
+        });
+        assert_eq!(off.check(&balance(0)), None);
+    }
+
+    #[test]
+    fn test_failed_swaps_alert_on_streak() {
+        let mut alerter = Alerter::new(AlertThresholds {
+            low_balance: 0,
+            failed_swaps: 2,
+        });
+
+        assert_eq!(alerter.check(&failed("q1")), None);
+        assert_eq!(
+            alerter.check(&failed("q2")),
+            Some(Alert::FailedSwaps {
+                count: 2,
+                quote_id: "q2".to_string(),
+                error: "mint offline".to_string(),
+            })
+        );
+        assert_eq!(alerter.check(&failed("q3")), None);
+
+        // A completed swap ends the streak
+        let completed = BrokerEvent::SwapCompleted {
+            quote_id: "q4".to_string(),
+            amount_received: 99,
+        };
+        assert_eq!(alerter.check(&completed), None);
+        assert_eq!(alerter.check(&failed("q5")), None);
+        assert!(alerter.check(&failed("q6")).is_some());
+    }
+
+    #[test]
+    fn test_mint_health_alerts() {
+        let mut alerter = Alerter::new(AlertThresholds {
+            low_balance: 0,
+            failed_swaps: 0,
+        });
+        let health = |healthy| BrokerEvent::MintHealthChanged {
+            mint_url: MINT.to_string(),
+            healthy,
+        };
+
+        // Recovery is only announced for a mint that was reported down
+        assert_eq!(alerter.check(&health(true)), None);
+        assert_eq!(
+            alerter.check(&health(false)),
+            Some(Alert::MintUnhealthy {
+                mint_url: MINT.to_string()
+            })
+        );
+        assert_eq!(
+            alerter.check(&health(true)),
+            Some(Alert::MintRecovered {
+                mint_url: MINT.to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_quarantine_alert() {
+        let mut alerter = Alerter::new(AlertThresholds {
+            low_balance: 0,
+            failed_swaps: 0,
+        });
+        let quarantined = BrokerEvent::ProofsQuarantined {
+            mint_url: MINT.to_string(),
+            amount: 64,
+            reason: QuarantineReason::InvalidDleq,
+        };
+
+        // Every batch is its own incident
+        for _ in 0..2 {
+            assert_eq!(
+                alerter.check(&quarantined),
+                Some(Alert::ProofsQuarantined {
+                    mint_url: MINT.to_string(),
+                    amount: 64,
+                    reason: QuarantineReason::InvalidDleq,
+                })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_delivers_to_sinks() {
+        struct Collect(Mutex<Vec<Alert>>);
+
+        #[async_trait]
+        impl AlertSink for Collect {
+            fn name(&self) -> &str {
+                "collect"
+            }
+
+            async fn send(&self, alert: &Alert) -> Result<()> {
+                self.0.lock().unwrap().push(alert.clone());
+                Ok(())
+            }
+        }
+
+        let sink = Arc::new(Collect(Mutex::new(Vec::new())));
+        let alerter = Alerter::new(AlertThresholds {
+            low_balance: 100,
+            failed_swaps: 1,
+        })
+        .sink(sink.clone());
+
+        let bus = EventBus::new(8);
+        let events = bus.subscribe();
+        bus.emit(balance(50));
+        bus.emit(failed("q1"));
+        drop(bus);
+
+        alerter.run(events).await;
+        assert_eq!(sink.0.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_webhook_payload() {
+        let alert = Alert::MintUnhealthy {
+            mint_url: MINT.to_string(),
+        };
+        let payload = serde_json::to_value(AlertPayload {
+            alert: &alert,
+            message: alert.to_string(),
+            timestamp: "2025-01-01T00:00:00+00:00".to_string(),
+        })
+        .unwrap();
+
+        assert_eq!(
+            payload,
+            serde_json::json!({
+                "kind": "mint_unhealthy",
+                "mint_url": MINT,
+                "message": "Mint http://mint-a.test is failing health checks",
+                "timestamp": "2025-01-01T00:00:00+00:00",
+            })
+        );
+    }
+}
@@ -1,18 +1,46 @@
+use crate::adaptor::{decode_encrypted_signature, encode_encrypted_signature};
+use crate::admin;
+use crate::api_keys;
+use crate::binding;
+use crate::blacklist;
 use crate::broker::Broker;
-use crate::db::{Database, LiquidityEvent, QuoteRecord};
-use crate::error::BrokerError;
-use crate::types::{SwapQuote, SwapRequest, SwapStatus};
+use crate::chunked::{self, ChunkedProgress, NextChunk};
+use crate::circuit_breaker::{CircuitState, CircuitStatus};
+use crate::cors;
+use crate::dashboard;
+use crate::db::{
+    ChunkedSwapRecord, Database, LiquidityEvent, QuoteCursor, QuoteFilter, QuoteRecord,
+    SwapChunk,
+};
+use crate::error::{BrokerError, ErrorCode};
+use crate::etag;
+use crate::fees::FeeSchedule;
+use crate::idempotency;
+use crate::limits::{self, RequestLimits};
+use crate::nip98;
+use crate::rate_limit::{self, RateLimitConfig, RateLimiter};
+use crate::request_id;
+use crate::risk::RiskViolation;
+use crate::types::{
+    ClaimOrder, MintConfig, QuoteType, Sensitive, SwapQuote, SwapRequest, SwapStatus,
+};
+use crate::validation::{FieldError, Validator};
+use crate::versioning::{self, ApiVersion};
+use crate::webhooks;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    extract::{DefaultBodyLimit, Path, Query, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tower_http::cors::CorsLayer;
+use std::time::Duration;
+use tower_http::compression::CompressionLayer;
 use tower_http::trace::TraceLayer;
 use uuid::Uuid;
 
@@ -24,28 +52,142 @@ pub struct AppState {
 }
 
 /// Create the API router
-pub fn create_router(state: AppState, cors_origins: Vec<String>) -> Router {
-    let cors = if cors_origins.contains(&"*".to_string()) {
-        CorsLayer::permissive()
-    } else {
-        CorsLayer::new()
-    };
-
-    Router::new()
-        // Swap endpoints
-        .route("/quote", post(request_quote))
-        .route("/quote/:id/accept", post(accept_quote))
-        .route("/quote/:id/complete", post(complete_quote))
-        .route("/quote/:id", get(get_quote_status))
+///
+/// The `/admin` routes and `/dashboard` are only mounted when an admin token
+/// is given. With a rate limit, every public route except the `/health`
+/// probes is limited. With `require_api_key`, the quote listing, liquidity and
+/// metrics routes need an `X-API-Key` header while the swap routes stay open.
+/// With `client_history_auth`, a client's quote history is only listed for a
+/// request signed by its key. Every route, `/health` and `/admin` included,
+/// is held to `limits`. `GET` responses carry an ETag, and responses are
+/// compressed for clients that accept it.
+///
+/// Routes are served under `/v1`, and at their unversioned paths as
+/// deprecated aliases (see [`crate::versioning`]); the `/health` probes and
+/// `/dashboard` only at the root.
+pub fn create_router(
+    state: AppState,
+    cors_origins: Vec<String>,
+    admin_token: Option<String>,
+    rate_limit: Option<RateLimitConfig>,
+    require_api_key: bool,
+    client_history_auth: bool,
+    limits: RequestLimits,
+) -> Router {
+    let cors = cors::cors_layer(&cors_origins);
+
+    let mut privileged = Router::new()
         .route("/quotes", get(list_quotes))
         // Liquidity endpoints
         .route("/liquidity", get(get_liquidity))
         .route("/liquidity/:mint_url/events", get(get_liquidity_events))
-        // Health & metrics
+        // Metrics
+        .route("/metrics", get(get_metrics));
+
+    if require_api_key {
+        privileged = privileged.route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            api_keys::require_api_key,
+        ));
+    }
+
+    // Swap steps that wallets may retry with an Idempotency-Key, and that
+    // only the quote's owner may take
+    let steps = Router::new()
+        .route("/quote/:id/accept", post(accept_quote))
+        .route("/quote/:id/complete", post(complete_quote))
+        .route("/quote/:id/extend", post(extend_quote))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            idempotency::idempotent,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            nip98::require_quote_owner,
+        ));
+
+    // Continuing or stopping a chunked swap is up to its owner too
+    let chunk_steps = Router::new()
+        .route("/chunked/:id/next", post(next_chunk))
+        .route("/chunked/:id/stop", post(stop_chunked_swap))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            nip98::require_quote_owner,
+        ));
+
+    let mut history = Router::new().route("/client/:pubkey/quotes", get(client_quotes));
+    if client_history_auth {
+        history = history.route_layer(middleware::from_fn(nip98::require_key_holder));
+    }
+
+    let mut router = Router::new()
+        // Swap endpoints
+        .route("/quote", post(request_quote))
+        .route("/quote/:id", get(get_quote_status))
+        .route("/quote/:id/secret", get(get_quote_secret))
+        .route("/chunked", post(request_chunked_swap))
+        .route("/chunked/:id", get(get_chunked_swap))
+        .route("/info", get(get_info))
+        .route("/mints/health", get(get_mints_health))
+        .merge(steps)
+        .merge(chunk_steps)
+        .merge(history)
+        .merge(privileged);
+
+    if let Some(config) = rate_limit {
+        let limiter = Arc::new(RateLimiter::new(config));
+        router = router.route_layer(middleware::from_fn(move |req: Request, next: Next| {
+            let limiter = limiter.clone();
+            async move { rate_limit::limit(limiter, req, next).await }
+        }));
+    }
+
+    if let Some(token) = &admin_token {
+        router = router.nest("/admin", admin::router(token.clone()));
+    }
+
+    // The unversioned paths stay as deprecated aliases of /v1
+    let legacy = router
+        .clone()
+        .route_layer(middleware::from_fn(versioning::legacy));
+    let v1 = router.route_layer(middleware::from_fn(|req: Request, next: Next| {
+        versioning::versioned(ApiVersion::V1, req, next)
+    }));
+
+    // Health checks stay reachable for load balancers and monitoring
+    router = Router::new()
+        .nest(ApiVersion::V1.prefix(), v1)
+        .merge(legacy)
         .route("/health", get(health_check))
-        .route("/metrics", get(get_metrics))
+        .route("/health/live", get(liveness))
+        .route("/health/ready", get(readiness));
+
+    // A page for browsers rather than part of the API, so not versioned
+    if let Some(token) = admin_token {
+        router = router.merge(dashboard::router(token));
+    }
+
+    let RequestLimits {
+        max_body_bytes,
+        timeout,
+    } = limits;
+    router = router
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(middleware::from_fn(move |req: Request, next: Next| {
+            limits::body_limit(max_body_bytes, req, next)
+        }))
+        .layer(middleware::from_fn(move |req: Request, next: Next| {
+            limits::timeout(timeout, req, next)
+        }))
+        // Tagged before compression, so the ETag is the same either way
+        .layer(middleware::from_fn(etag::etag))
+        .layer(CompressionLayer::new());
+
+    // Request IDs are assigned outermost so every log line of a request carries one
+    router
         .layer(cors)
         .layer(TraceLayer::new_for_http())
+        .layer(middleware::from_fn(request_id::request_id))
         .with_state(state)
 }
 
@@ -56,8 +198,34 @@ pub struct QuoteRequest {
     pub source_mint: String,
     pub target_mint: String,
     pub amount: u64,
+    #[serde(default)]
+    pub quote_type: QuoteType, // exact_in (default) or exact_out
+    #[serde(default)]
+    pub callback_url: Option<String>, // Webhook for status changes (needs WEBHOOK_SECRET)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user_pubkey: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptor_point: Option<String>, // Hex; asks for a client-first swap (needs user_pubkey)
+}
+
+impl QuoteRequest {
+    /// Check the form of the request's URLs and keys
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let mut validator = Validator::new();
+        validator
+            .url("source_mint", &self.source_mint)
+            .url("target_mint", &self.target_mint);
+        if let Some(url) = &self.callback_url {
+            validator.url("callback_url", url);
+        }
+        if let Some(pubkey) = &self.user_pubkey {
+            validator.point("user_pubkey", pubkey);
+        }
+        if let Some(point) = &self.adaptor_point {
+            validator.point("adaptor_point", point);
+        }
+        validator.finish().map_err(ApiError::Validation)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,26 +233,125 @@ pub struct QuoteResponse {
     pub quote: SwapQuote,
 }
 
+/// Request for a swap larger than the pair's maximum, run in chunks (see
+/// [`crate::chunked`])
 #[derive(Debug, Serialize, Deserialize)]
-pub struct AcceptQuoteRequest {
-    pub source_proofs: String,  // JSON serialized proofs
+pub struct ChunkedSwapRequest {
+    pub source_mint: String,
+    pub target_mint: String,
+    pub amount: u64, // Total to pay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk_amount: Option<u64>, // Largest chunk; the pair's max_swap_amount by default
+    #[serde(default)]
+    pub callback_url: Option<String>, // Given to every chunk's quote
+    pub user_pubkey: String, // Owns the chunked swap and every chunk's quote
+}
+
+impl ChunkedSwapRequest {
+    /// Check the form of the request's URLs and key
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let mut validator = Validator::new();
+        validator
+            .url("source_mint", &self.source_mint)
+            .url("target_mint", &self.target_mint)
+            .point("user_pubkey", &self.user_pubkey);
+        if let Some(url) = &self.callback_url {
+            validator.url("callback_url", url);
+        }
+        validator.finish().map_err(ApiError::Validation)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkedSwapResponse {
+    pub chunked: ChunkedProgress,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<SwapQuote>, // The chunk to swap now; none once every chunk completed
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AcceptQuoteRequest {
+    pub source_proofs: String,  // cashuA/cashuB token or JSON serialized proofs
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct AcceptQuoteResponse {
-    pub encrypted_signature: String,
+    pub encrypted_signature: String,  // Hex: R (x-only) || s_hat || needs_negation
     pub target_proofs: String,  // JSON serialized proofs
+    pub target_token: String,  // The same proofs as a cashuB token for the target mint
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_signatures: Vec<String>, // Client-first: hex, encrypted under T, per target proof
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refund_at: Option<u64>, // Unix time after which the broker can refund the target proofs
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct CompleteQuoteRequest {
-    pub decrypted_signature: String,
+    pub decrypted_signature: String,  // Token or JSON proofs locked to the broker's tweaked key
+    pub client_signature: String,  // Hex: client's adaptor signature over the swap transcript, encrypted under T
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct CompleteQuoteResponse {
     pub adaptor_secret: String,
-    pub status: String,
+    pub status: SwapStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QuoteSecretResponse {
+    pub quote_id: String,
+    pub adaptor_point: String,  // T, as given in the quote
+    pub adaptor_secret: String, // t, with t·G = T
+}
+
+// Proofs and secrets in swap bodies are redacted from Debug output
+
+impl std::fmt::Debug for AcceptQuoteRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AcceptQuoteRequest")
+            .field("source_proofs", &Sensitive(&self.source_proofs))
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for AcceptQuoteResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AcceptQuoteResponse")
+            .field("encrypted_signature", &self.encrypted_signature)
+            .field("target_proofs", &Sensitive(&self.target_proofs))
+            .field("target_token", &Sensitive(&self.target_token))
+            .field("output_signatures", &self.output_signatures)
+            .field("refund_at", &self.refund_at)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for CompleteQuoteRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompleteQuoteRequest")
+            .field("decrypted_signature", &Sensitive(&self.decrypted_signature))
+            .field("client_signature", &self.client_signature)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for CompleteQuoteResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompleteQuoteResponse")
+            .field("adaptor_secret", &Sensitive(&self.adaptor_secret))
+            .field("status", &self.status)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for QuoteSecretResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuoteSecretResponse")
+            .field("quote_id", &self.quote_id)
+            .field("adaptor_point", &self.adaptor_point)
+            .field("adaptor_secret", &Sensitive(&self.adaptor_secret))
+            .finish()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -92,6 +359,8 @@ pub struct QuoteStatusResponse {
     pub quote: QuoteRecord,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub swap: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunked: Option<ChunkedProgress>, // Progress of the chunked swap this quote is a chunk of
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -100,16 +369,50 @@ pub struct ListQuotesQuery {
     pub status: Option<String>,
     #[serde(default = "default_limit")]
     pub limit: i64,
+    #[serde(default)]
+    pub source_mint: Option<String>,
+    #[serde(default)]
+    pub target_mint: Option<String>,
+    #[serde(default)]
+    pub user_pubkey: Option<String>,
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>, // RFC 3339, inclusive
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>, // RFC 3339, exclusive
+    #[serde(default)]
+    pub cursor: Option<String>, // From the X-Next-Cursor header of the previous page
 }
 
 fn default_limit() -> i64 {
     50
 }
 
+/// Paging through one client's quotes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientQuotesQuery {
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub cursor: Option<String>, // From the X-Next-Cursor header of the previous page
+}
+
+/// Largest page served by `GET /quotes`
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Total number of quotes matching a `GET /quotes` query
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Cursor for the next page of `GET /quotes`, absent on the last page
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LiquidityResponse {
     pub mints: Vec<MintLiquidity>,
     pub total_balance: u64,
+    pub total_available: u64,
+    pub total_locked: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -117,7 +420,32 @@ pub struct MintLiquidity {
     pub mint_url: String,
     pub name: String,
     pub balance: u64,
+    pub reserved: u64,  // Held for pending quotes
+    pub locked: u64,    // Committed to accepted swaps that haven't completed
+    pub available: u64, // Free for new quotes
+    #[serde(default)]
+    pub quarantined: u64, // Held out of the balance until retried or discarded
     pub unit: String,
+    pub healthy: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MintsHealthResponse {
+    pub mints: Vec<MintHealthInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MintHealthInfo {
+    pub mint_url: String,
+    pub name: String,
+    pub available: bool, // Healthy and circuit not open, so new quotes are accepted
+    pub healthy: bool,   // Passing background health checks
+    pub consecutive_failures: u32,
+    pub last_checked: Option<String>,
+    pub last_error: Option<String>,
+    pub circuit: CircuitStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>, // Moving average over successful calls to the mint
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -125,6 +453,73 @@ pub struct LiquidityEventsResponse {
     pub events: Vec<LiquidityEvent>,
 }
 
+/// Version of the HTTP API described by `GET /info`
+pub const API_VERSION: &str = ApiVersion::CURRENT.as_str();
+
+/// Broker capabilities, for wallets to configure themselves
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InfoResponse {
+    pub name: String,
+    pub version: String,     // Broker software version
+    pub api_version: String, // See API_VERSION
+    #[serde(default)]
+    pub api_versions: Vec<String>, // Every version served, each under /v<version>
+    pub pubkey: String,      // Long-term identity key (compressed, hex)
+    pub mints: Vec<MintConfig>,
+    pub pairs: Vec<PairInfo>, // Every supported direction with its effective terms
+    pub fee_rate: f64,
+    #[serde(default)]
+    pub fees: FeeSchedule, // Tiers, minimum fee and volume discounts on top of the rates
+    pub min_swap_amount: u64,
+    pub max_swap_amount: u64,
+    pub quote_expiry_seconds: u64,
+    #[serde(default)]
+    pub quote_extension_seconds: u64, // How much longer a quote can be kept, once (0 = off)
+    pub refund_locktime_seconds: u64,
+    pub quote_types: Vec<QuoteType>,
+    pub features: InfoFeatures,
+    #[serde(default)]
+    pub maintenance: bool, // New quotes are refused until it is turned off
+    #[serde(default)]
+    pub error_codes: Vec<ErrorCodeInfo>, // Every code an error response may carry
+}
+
+/// Swap direction between two mints
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PairInfo {
+    pub source_mint: String,
+    pub target_mint: String,
+    pub fee_rate: f64,
+    pub min_swap_amount: u64,
+    pub max_swap_amount: u64,
+}
+
+/// An error code as listed in `GET /info`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorCodeInfo {
+    pub code: String,
+    pub retryable: bool,
+    pub description: String,
+}
+
+impl From<ErrorCode> for ErrorCodeInfo {
+    fn from(code: ErrorCode) -> Self {
+        Self {
+            code: code.to_string(),
+            retryable: code.retryable(),
+            description: code.description().to_string(),
+        }
+    }
+}
+
+/// Optional features enabled on this broker
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InfoFeatures {
+    pub cross_unit: bool,  // Swaps between mints of different units
+    pub webhooks: bool,    // callback_url on quote requests
+    pub idempotency: bool, // Idempotency-Key on accept and complete
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HealthResponse {
     pub status: String,
@@ -132,6 +527,24 @@ pub struct HealthResponse {
     pub database: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LivenessResponse {
+    pub status: String,
+    pub timestamp: String,
+}
+
+/// Result of `GET /health/ready`, served with 503 unless `ready`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub timestamp: String,
+    pub database: String,   // "ok" or the connection error
+    pub migrations: String, // "ok", how many are pending, or the error
+    pub mints: usize,       // Configured
+    pub wallets_loaded: usize,
+    pub healthy_mints: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MetricsResponse {
     pub total_quotes: u64,
@@ -139,12 +552,28 @@ pub struct MetricsResponse {
     pub failed_swaps: u64,
     pub total_volume: u64,
     pub total_fees: u64,
+    pub quotes_by_status: BTreeMap<String, u64>,
+}
+
+/// Optional time window for `GET /metrics` and `GET /admin/accounting/summary`
+#[derive(Debug, Deserialize)]
+pub struct MetricsQuery {
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>, // RFC 3339, inclusive
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>, // RFC 3339, exclusive
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
-    pub code: String,
+    pub code: String, // One of the `ErrorCode`s, listed in `GET /info`
+    #[serde(default)]
+    pub retryable: bool, // Whether the same request may succeed later
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>, // Seconds to wait before retrying, when known
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>, // What is wrong with each invalid field
 }
 
 // ===== Handlers =====
@@ -154,13 +583,31 @@ async fn request_quote(
     State(state): State<AppState>,
     Json(req): Json<QuoteRequest>,
 ) -> Result<Json<QuoteResponse>, ApiError> {
+    req.validate()?;
+    let quote = issue_quote(&state, req).await?;
+    Ok(Json(QuoteResponse { quote }))
+}
+
+/// Have the broker quote a request and save the quote
+async fn issue_quote(state: &AppState, req: QuoteRequest) -> Result<SwapQuote, ApiError> {
+    if let Some(url) = &req.callback_url {
+        if !state.db.webhooks_enabled() {
+            return Err(ApiError::BadRequest(
+                "Webhooks are not enabled on this broker".to_string(),
+            ));
+        }
+        webhooks::validate_callback_url(url).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    }
+
     // Create swap request
     let swap_request = SwapRequest {
         client_id: None,  // Anonymous for HTTP API
         from_mint: req.source_mint.clone(),
         to_mint: req.target_mint.clone(),
         amount: req.amount,
+        quote_type: req.quote_type,
         client_public_key: req.user_pubkey.as_ref().and_then(|hex_str| hex::decode(hex_str).ok()),
+        adaptor_point: client_adaptor_point(&req)?,
     };
 
     // Request quote from broker
@@ -171,7 +618,21 @@ async fn request_quote(
         .map_err(ApiError::from)?;
 
     // Save quote to database
-    let quote_record = QuoteRecord {
+    let mut quote_record = quote_record(&quote, req.user_pubkey);
+    quote_record.callback_url = req.callback_url;
+
+    state
+        .db
+        .create_quote(&quote_record)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(quote)
+}
+
+/// Database record for a freshly issued quote
+pub(crate) fn quote_record(quote: &SwapQuote, user_pubkey: Option<String>) -> QuoteRecord {
+    QuoteRecord {
         id: quote.quote_id.clone(),
         source_mint: quote.from_mint.clone(),
         target_mint: quote.to_mint.clone(),
@@ -179,10 +640,12 @@ async fn request_quote(
         amount_out: quote.output_amount as i64,
         fee: quote.fee as i64,
         fee_rate: quote.fee_rate,
+        mint_fee: quote.mint_fee as i64,
+        exchange_rate: quote.exchange_rate,
         broker_pubkey: hex::encode(&quote.broker_public_key),
         adaptor_point: hex::encode(&quote.adaptor_point),
         tweaked_pubkey: quote.tweaked_pubkey.as_ref().map(hex::encode).unwrap_or_default(),
-        status: SwapStatus::Pending.to_string(),
+        status: SwapStatus::Pending,
         created_at: Utc::now().to_rfc3339(),
         expires_at: Utc::now()
             .checked_add_signed(chrono::Duration::seconds(quote.expires_in as i64))
@@ -190,17 +653,24 @@ async fn request_quote(
             .to_rfc3339(),
         accepted_at: None,
         completed_at: None,
-        user_pubkey: req.user_pubkey,
+        user_pubkey,
         error_message: None,
-    };
-
-    state
-        .db
-        .create_quote(&quote_record)
-        .await
-        .map_err(ApiError::from)?;
+        callback_url: None,
+        job_status: None,
+        claim_order: quote.claim_order,
+        extended_at: None,
+    }
+}
 
-    Ok(Json(QuoteResponse { quote }))
+/// The adaptor point of a client-first quote request, decoded
+pub(crate) fn client_adaptor_point(req: &QuoteRequest) -> Result<Option<Vec<u8>>, ApiError> {
+    req.adaptor_point
+        .as_ref()
+        .map(|point| {
+            hex::decode(point)
+                .map_err(|e| ApiError::BadRequest(format!("Invalid adaptor_point hex: {}", e)))
+        })
+        .transpose()
 }
 
 /// Accept a quote and lock source proofs
@@ -217,48 +687,104 @@ async fn accept_quote(
         .map_err(ApiError::from)?
         .ok_or_else(|| ApiError::NotFound(format!("Quote {} not found", id)))?;
 
+    // A retry of an accept that already went through gets the same response,
+    // rather than a second batch of locked tokens
+    if quote.status == SwapStatus::Accepted {
+        let swap = state.db.get_swap_by_quote(&id).await.map_err(ApiError::from)?;
+        if let Some(swap) = swap.filter(|s| s.source_proofs == req.source_proofs) {
+            if let (Some(encrypted_signature), Some(target_proofs)) =
+                (swap.encrypted_signature, swap.target_proofs)
+            {
+                let proofs = serde_json::from_str(&target_proofs).map_err(|e| {
+                    ApiError::Internal(format!("Invalid stored target proofs: {}", e))
+                })?;
+                let output_signatures = output_signatures(&state, &id, &proofs).await?;
+                let target_token = locked_token(&state, &quote, proofs)?;
+                let refund_at = state.broker.refund_at(&id).await.map_err(ApiError::from)?;
+
+                return Ok(Json(AcceptQuoteResponse {
+                    encrypted_signature,
+                    target_proofs,
+                    target_token,
+                    output_signatures,
+                    refund_at,
+                }));
+            }
+        }
+    }
+
     // Check quote status
-    if quote.status != SwapStatus::Pending.to_string() {
+    if quote.status != SwapStatus::Pending {
         return Err(ApiError::BadRequest(format!(
             "Quote {} is not pending (status: {})",
             id, quote.status
         )));
     }
 
-    // Parse source proofs from JSON
-    let _source_proofs: cdk::nuts::Proofs = serde_json::from_str(&req.source_proofs)
-        .map_err(|e| ApiError::BadRequest(format!("Invalid source_proofs JSON: {}", e)))?;
-
-    // Get client pubkey - either from quote record or extract from proofs
+    // Get client pubkey from the quote record
     let client_pubkey_hex = quote.user_pubkey.as_ref()
         .ok_or_else(|| ApiError::BadRequest("No user_pubkey provided in quote".to_string()))?;
 
     let client_pubkey = hex::decode(client_pubkey_hex)
         .map_err(|e| ApiError::BadRequest(format!("Invalid client pubkey hex: {}", e)))?;
 
-    // Prepare broker's side of swap (mint P2PK locked tokens for client)
-    let target_proofs_data = state
+    // Parse source proofs from a token or JSON
+    let source_proofs = state
         .broker
-        .accept_quote(&id, &client_pubkey)
+        .parse_proofs(&quote.source_mint, &req.source_proofs)
         .await
-        .map_err(ApiError::from)?;
+        .map_err(proofs_error)?;
 
-    // Serialize target proofs to JSON
-    let target_proofs = serde_json::to_string(&target_proofs_data)
-        .map_err(|e| ApiError::Internal(format!("Failed to serialize target proofs: {}", e)))?;
+    // Refuse banned clients and known-stolen proofs before asking the mint
+    state
+        .broker
+        .check_blacklist(Some(&client_pubkey), &source_proofs)
+        .await
+        .map_err(ApiError::from)?;
 
-    // For encrypted signature, we'll use the adaptor point (in a full implementation,
-    // this would be an actual encrypted adaptor signature)
-    let encrypted_signature = quote.adaptor_point.clone();
+    // Only take proofs locked to this quote alone and signed by its client
+    let tweaked_pubkey = hex::decode(&quote.tweaked_pubkey)
+        .map_err(|e| ApiError::Internal(format!("Invalid stored tweaked pubkey: {}", e)))?;
+    binding::check_bound(&source_proofs, &tweaked_pubkey, &client_pubkey)
+        .map_err(ApiError::from)?;
 
-    // Update quote status
+    // Don't lock broker liquidity against proofs the client can't hand over
     state
-        .db
-        .update_quote_status(&id, SwapStatus::Accepted, None)
+        .broker
+        .check_proofs_unspent(&quote.source_mint, &source_proofs)
         .await
         .map_err(ApiError::from)?;
 
-    // Create swap record
+    // Hold the proofs for this quote, so a concurrent quote can't take them too
+    let ys = blacklist::proof_ys(&source_proofs).map_err(ApiError::from)?;
+    let claimed = state.db.claim_proofs(&id, &ys).await.map_err(ApiError::from)?;
+
+    // Prepare broker's side of swap (mint P2PK locked tokens for client)
+    let prepared = match state.broker.accept_quote(&id, &client_pubkey).await {
+        Ok(prepared) => prepared,
+        Err(e) => {
+            // Nothing was locked: give back the proofs this request claimed, so
+            // the client can try again. Claims of a concurrent accept of the
+            // same quote stay put.
+            if let Err(e) = state.db.release_proofs(&id, &claimed).await {
+                tracing::warn!("Could not release the proofs of quote {}: {}", id, e);
+            }
+            return Err(ApiError::from(e));
+        }
+    };
+    // Outputs are locked against these proofs from here on, so they stay
+    // claimed even if recording the accept fails; accepting again hands out
+    // the same outputs
+
+    // Serialize target proofs to JSON and as a token
+    let target_proofs = serde_json::to_string(&prepared.proofs)
+        .map_err(|e| ApiError::Internal(format!("Failed to serialize target proofs: {}", e)))?;
+    let target_token = locked_token(&state, &quote, prepared.proofs.clone())?;
+
+    // Broker's adaptor signature over the swap transcript, encrypted under T
+    let encrypted_signature = prepared.encrypted_signature_hex();
+
+    // Mark the quote accepted and record the swap together
     let swap_record = crate::db::SwapRecord {
         id: Uuid::new_v4().to_string(),
         quote_id: id.clone(),
@@ -273,16 +799,58 @@ async fn accept_quote(
 
     state
         .db
-        .create_swap(&swap_record)
+        .accept_quote(&swap_record)
         .await
         .map_err(ApiError::from)?;
+    let refund_at = state.broker.refund_at(&id).await.map_err(ApiError::from)?;
 
     Ok(Json(AcceptQuoteResponse {
         encrypted_signature,
         target_proofs,
+        target_token,
+        output_signatures: prepared.output_signatures_hex(),
+        refund_at,
     }))
 }
 
+/// The broker's locked outputs for a quote as a token on its target mint
+fn locked_token(
+    state: &AppState,
+    quote: &QuoteRecord,
+    proofs: cdk::nuts::Proofs,
+) -> Result<String, ApiError> {
+    let memo = format!("Atomic swap {}", quote.id);
+    state
+        .broker
+        .encode_token(&quote.target_mint, proofs, Some(memo))
+        .map_err(ApiError::from)
+}
+
+/// The broker's encrypted witness signatures for a client-first quote's outputs, as hex
+async fn output_signatures(
+    state: &AppState,
+    quote_id: &str,
+    proofs: &cdk::nuts::Proofs,
+) -> Result<Vec<String>, ApiError> {
+    let signatures = state
+        .broker
+        .output_signatures(quote_id, proofs)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(signatures
+        .iter()
+        .map(|sig| hex::encode(encode_encrypted_signature(sig)))
+        .collect())
+}
+
+/// Map errors from parsing client proofs, which are the client's fault unless the mint failed
+fn proofs_error(err: BrokerError) -> ApiError {
+    match err {
+        BrokerError::InvalidSwapRequest(msg) => ApiError::BadRequest(msg),
+        _ => ApiError::from(err),
+    }
+}
+
 /// Complete a quote after receiving decrypted signature
 async fn complete_quote(
     State(state): State<AppState>,
@@ -297,60 +865,73 @@ async fn complete_quote(
         .map_err(ApiError::from)?
         .ok_or_else(|| ApiError::NotFound(format!("Quote {} not found", id)))?;
 
+    // Completing twice has no further effect
+    if quote.status == SwapStatus::Completed {
+        let adaptor_secret = state
+            .broker
+            .revealed_adaptor_secret(&id)
+            .await
+            .map_err(ApiError::from)?;
+        return Ok(Json(CompleteQuoteResponse {
+            adaptor_secret: hex::encode(adaptor_secret),
+            status: quote.status,
+        }));
+    }
+
     // Check quote status
-    if quote.status != SwapStatus::Accepted.to_string() {
+    if quote.status != SwapStatus::Accepted {
         return Err(ApiError::BadRequest(format!(
             "Quote {} is not accepted (status: {})",
             id, quote.status
         )));
     }
+    if quote.claim_order == ClaimOrder::ClientFirst {
+        return Err(ApiError::BadRequest(format!(
+            "Quote {} is client-first: it completes once the client redeems the outputs",
+            id
+        )));
+    }
 
-    // Parse decrypted signature as client proofs with witness
-    let client_proofs_with_witness: cdk::nuts::Proofs = serde_json::from_str(&req.decrypted_signature)
-        .map_err(|e| ApiError::BadRequest(format!("Invalid decrypted_signature JSON (expected Proofs): {}", e)))?;
-
-    // Complete the swap - broker claims client's tokens
-    state
+    // Parse decrypted signature as client proofs with witness, from a token or JSON
+    let client_proofs_with_witness = state
         .broker
-        .complete_swap(&id, client_proofs_with_witness)
+        .parse_proofs(&quote.source_mint, &req.decrypted_signature)
         .await
-        .map_err(ApiError::from)?;
+        .map_err(proofs_error)?;
 
-    // Get adaptor secret from quote record (hex encoded)
-    let adaptor_secret = quote.adaptor_point.clone();
+    // Parse the client's encrypted adaptor signature
+    let client_signature = hex::decode(&req.client_signature)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid client_signature hex: {}", e)))
+        .and_then(|bytes| {
+            decode_encrypted_signature(&bytes).map_err(|e| ApiError::BadRequest(e.to_string()))
+        })?;
 
-    // Update quote status
-    state
-        .db
-        .update_quote_status(&id, SwapStatus::Completed, None)
+    // Complete the swap - broker verifies the client's signature, then claims client's tokens
+    let decrypted_signature = state
+        .broker
+        .complete_swap(&id, client_proofs_with_witness, &client_signature)
         .await
         .map_err(ApiError::from)?;
+    let decrypted_signature = hex::encode(decrypted_signature.to_bytes());
 
-    // Get swap record
-    let swap = state
-        .db
-        .get_swap_by_quote(&id)
+    // The claim went through, so the adaptor secret can be revealed
+    let adaptor_secret = state
+        .broker
+        .revealed_adaptor_secret(&id)
         .await
-        .map_err(ApiError::from)?
-        .ok_or_else(|| ApiError::NotFound(format!("Swap for quote {} not found", id)))?;
-
-    // Complete swap record in database
-    let target_proofs_str = swap.target_proofs.as_deref().unwrap_or("");
+        .map_err(ApiError::from)?;
+    let adaptor_secret = hex::encode(adaptor_secret);
 
+    // Mark the quote completed and settle the swap record together
     state
         .db
-        .complete_swap(
-            &swap.id,
-            target_proofs_str,
-            Some(&req.decrypted_signature),
-            Some(&adaptor_secret),
-        )
+        .complete_quote(&id, Some(&decrypted_signature), Some(&adaptor_secret))
         .await
         .map_err(ApiError::from)?;
 
     Ok(Json(CompleteQuoteResponse {
         adaptor_secret,
-        status: SwapStatus::Completed.to_string(),
+        status: SwapStatus::Completed,
     }))
 }
 
@@ -374,23 +955,333 @@ async fn get_quote_status(
         .map_err(ApiError::from)?
         .and_then(|s| serde_json::to_value(s).ok());
 
-    Ok(Json(QuoteStatusResponse { quote, swap }))
+    let chunked = match state.db.chunked_swap_of(&id).await.map_err(ApiError::from)? {
+        Some(chunked_id) => Some(chunked_progress(&state, &chunked_id).await?),
+        None => None,
+    };
+
+    Ok(Json(QuoteStatusResponse {
+        quote,
+        swap,
+        chunked,
+    }))
+}
+
+/// Start a chunked swap and quote its first chunk
+async fn request_chunked_swap(
+    State(state): State<AppState>,
+    Json(req): Json<ChunkedSwapRequest>,
+) -> Result<Json<ChunkedSwapResponse>, ApiError> {
+    req.validate()?;
+    let terms = state
+        .broker
+        .get_config()
+        .pair_terms(&req.source_mint, &req.target_mint);
+    let chunk_amount = req.chunk_amount.unwrap_or(terms.max_swap_amount);
+    if chunk_amount > terms.max_swap_amount {
+        return Err(ApiError::BadRequest(format!(
+            "chunk_amount {} is above the maximum swap amount {}",
+            chunk_amount, terms.max_swap_amount
+        )));
+    }
+    let chunks = chunked::plan(req.amount, chunk_amount, terms.min_swap_amount)
+        .map_err(ApiError::BadRequest)?;
+
+    let swap = ChunkedSwapRecord {
+        id: Uuid::new_v4().to_string(),
+        source_mint: req.source_mint,
+        target_mint: req.target_mint,
+        amount: req.amount as i64,
+        chunks,
+        user_pubkey: req.user_pubkey,
+        callback_url: req.callback_url,
+        created_at: Utc::now().to_rfc3339(),
+        stopped_at: None,
+    };
+
+    // Quoting the first chunk checks the pair, key and callback URL before anything is saved
+    let quote = issue_quote(&state, chunk_request(&swap, 0)).await?;
+    state
+        .db
+        .create_chunked_swap(&swap)
+        .await
+        .map_err(ApiError::from)?;
+    link_chunk(&state, &swap.id, 0, &quote).await?;
+
+    Ok(Json(ChunkedSwapResponse {
+        chunked: chunked_progress(&state, &swap.id).await?,
+        quote: Some(quote),
+    }))
+}
+
+/// Quote request for chunk `index` of a chunked swap
+fn chunk_request(swap: &ChunkedSwapRecord, index: usize) -> QuoteRequest {
+    QuoteRequest {
+        source_mint: swap.source_mint.clone(),
+        target_mint: swap.target_mint.clone(),
+        amount: swap.chunks[index],
+        quote_type: QuoteType::ExactIn,
+        callback_url: swap.callback_url.clone(),
+        user_pubkey: Some(swap.user_pubkey.clone()),
+        adaptor_point: None,
+    }
+}
+
+/// Record `quote` as chunk `index` of a chunked swap
+///
+/// If the swap was stopped or another chunk got quoted meanwhile, the quote
+/// is expired again.
+async fn link_chunk(
+    state: &AppState,
+    chunked_id: &str,
+    index: usize,
+    quote: &SwapQuote,
+) -> Result<(), ApiError> {
+    let linked = state
+        .db
+        .add_swap_chunk(chunked_id, index, &quote.quote_id)
+        .await
+        .map_err(ApiError::from)?;
+    if linked {
+        return Ok(());
+    }
+
+    if let Err(e) = state.broker.expire_quote(&quote.quote_id).await {
+        tracing::warn!("Could not expire unused chunk quote {}: {}", quote.quote_id, e);
+    }
+    Err(ApiError::Conflict(format!(
+        "Chunked swap {} is stopped or has a chunk in progress",
+        chunked_id
+    )))
+}
+
+/// A chunked swap with the quotes of its chunks
+async fn chunked_progress(state: &AppState, id: &str) -> Result<ChunkedProgress, ApiError> {
+    let (swap, quotes) = chunked_swap(state, id).await?;
+    Ok(ChunkedProgress::new(swap, quotes))
+}
+
+async fn chunked_swap(
+    state: &AppState,
+    id: &str,
+) -> Result<(ChunkedSwapRecord, Vec<SwapChunk>), ApiError> {
+    let swap = state
+        .db
+        .get_chunked_swap(id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ApiError::NotFound(format!("Chunked swap {} not found", id)))?;
+    let quotes = state.db.list_swap_chunks(id).await.map_err(ApiError::from)?;
+
+    Ok((swap, quotes))
+}
+
+/// Get the progress of a chunked swap
+async fn get_chunked_swap(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ChunkedProgress>, ApiError> {
+    chunked_progress(&state, &id).await.map(Json)
+}
+
+/// Quote the next chunk of a chunked swap
+///
+/// The previous chunk must have completed; one whose quote expired or failed
+/// is quoted again. Once every chunk completed there is no quote to give.
+async fn next_chunk(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ChunkedSwapResponse>, ApiError> {
+    let (swap, quotes) = chunked_swap(&state, &id).await?;
+
+    let index = match chunked::next_chunk(swap.chunks.len(), &quotes) {
+        NextChunk::Finished => {
+            return Ok(Json(ChunkedSwapResponse {
+                chunked: ChunkedProgress::new(swap, quotes),
+                quote: None,
+            }))
+        }
+        _ if swap.stopped_at.is_some() => {
+            return Err(ApiError::Conflict(format!("Chunked swap {} is stopped", id)))
+        }
+        NextChunk::InProgress(quote_id) => {
+            return Err(ApiError::Conflict(format!(
+                "Quote {} of chunked swap {} is still in progress",
+                quote_id, id
+            )))
+        }
+        NextChunk::Quote(index) => index,
+    };
+
+    let quote = issue_quote(&state, chunk_request(&swap, index)).await?;
+    link_chunk(&state, &id, index, &quote).await?;
+
+    Ok(Json(ChunkedSwapResponse {
+        chunked: chunked_progress(&state, &id).await?,
+        quote: Some(quote),
+    }))
+}
+
+/// Stop a chunked swap after the chunks swapped so far
+///
+/// A chunk quoted but not accepted yet is expired. One already accepted runs
+/// to its end as usual, completing or refunding.
+async fn stop_chunked_swap(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ChunkedProgress>, ApiError> {
+    let progress = chunked_progress(&state, &id).await?;
+    state.db.stop_chunked_swap(&id).await.map_err(ApiError::from)?;
+
+    let pending = progress
+        .quotes
+        .iter()
+        .filter(|chunk| chunk.status == SwapStatus::Pending);
+    for chunk in pending {
+        if let Err(e) = state.broker.expire_quote(&chunk.quote_id).await {
+            tracing::warn!("Could not expire chunk quote {}: {}", chunk.quote_id, e);
+        }
+    }
+
+    chunked_progress(&state, &id).await.map(Json)
+}
+
+/// Push a pending quote's expiry out by the configured extension, once
+///
+/// Returns the quote with its new expiry, signed again. A quote that isn't
+/// pending, was extended before or lost the liquidity held for it gets `409`.
+async fn extend_quote(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<QuoteResponse>, ApiError> {
+    if state.broker.get_config().quote_extension_seconds == 0 {
+        return Err(ApiError::BadRequest(
+            "Quote extension is not enabled on this broker".to_string(),
+        ));
+    }
+
+    state
+        .broker
+        .extend_quote(&id)
+        .await
+        .map(|quote| Json(QuoteResponse { quote }))
+        .map_err(|e| match e {
+            BrokerError::InvalidSwapRequest(msg) => ApiError::Conflict(msg),
+            e => ApiError::from(e),
+        })
+}
+
+/// Reveal the adaptor secret of a completed swap
+///
+/// The client needs `t` to unlock the broker's outputs, which are locked to
+/// `client_pubkey + T`. It is only given out once the quote is completed,
+/// i.e. after the broker's claim of the client's tokens went through.
+async fn get_quote_secret(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<QuoteSecretResponse>, ApiError> {
+    let quote = state
+        .db
+        .get_quote(&id)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ApiError::NotFound(format!("Quote {} not found", id)))?;
+
+    if quote.status != SwapStatus::Completed {
+        return Err(ApiError::Conflict(format!(
+            "Quote {} is not completed (status: {})",
+            id, quote.status
+        )));
+    }
+
+    let adaptor_secret = state
+        .broker
+        .revealed_adaptor_secret(&id)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Json(QuoteSecretResponse {
+        quote_id: id,
+        adaptor_point: quote.adaptor_point,
+        adaptor_secret: hex::encode(adaptor_secret),
+    }))
 }
 
 /// List quotes
 async fn list_quotes(
     State(state): State<AppState>,
     Query(query): Query<ListQuotesQuery>,
-) -> Result<Json<Vec<QuoteRecord>>, ApiError> {
-    let status = query.status.and_then(|s| s.parse::<SwapStatus>().ok());
+) -> Result<Response, ApiError> {
+    let filter = QuoteFilter {
+        status: query.status.and_then(|s| s.parse::<SwapStatus>().ok()),
+        source_mint: query.source_mint,
+        target_mint: query.target_mint,
+        user_pubkey: query.user_pubkey,
+        from: query.from,
+        to: query.to,
+    };
+    quotes_page(&state, &filter, query.limit, query.cursor.as_deref()).await
+}
+
+/// Quote history of the client holding `pubkey`, newest first
+///
+/// Pages like `GET /quotes`, so a wallet restored from seed can rebuild its
+/// list of swaps from the broker.
+async fn client_quotes(
+    State(state): State<AppState>,
+    Path(pubkey): Path<String>,
+    Query(query): Query<ClientQuotesQuery>,
+) -> Result<Response, ApiError> {
+    let valid = hex::decode(&pubkey).is_ok_and(|bytes| bytes.len() == 33);
+    if !valid {
+        return Err(ApiError::BadRequest(format!(
+            "Invalid pubkey {}: expected a compressed key in hex",
+            pubkey
+        )));
+    }
 
-    let quotes = state
+    let filter = QuoteFilter {
+        status: query.status.and_then(|s| s.parse::<SwapStatus>().ok()),
+        user_pubkey: Some(pubkey.to_lowercase()),
+        ..Default::default()
+    };
+    quotes_page(&state, &filter, query.limit, query.cursor.as_deref()).await
+}
+
+/// A page of quotes matching `filter`, with the total count and the cursor
+/// of the next page in headers
+async fn quotes_page(
+    state: &AppState,
+    filter: &QuoteFilter,
+    limit: i64,
+    cursor: Option<&str>,
+) -> Result<Response, ApiError> {
+    let cursor = match cursor {
+        Some(cursor) => Some(
+            QuoteCursor::decode(cursor)
+                .ok_or_else(|| ApiError::BadRequest("Invalid cursor".to_string()))?,
+        ),
+        None => None,
+    };
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+
+    let page = state
         .db
-        .list_quotes(status, query.limit)
+        .list_quotes_page(filter, limit, cursor.as_ref())
         .await
         .map_err(ApiError::from)?;
 
-    Ok(Json(quotes))
+    // Paging metadata goes in headers so the body stays a plain array
+    let mut response = Json(page.quotes).into_response();
+    let headers = response.headers_mut();
+    headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(page.total));
+    if let Some(next) = page.next_cursor {
+        if let Ok(value) = HeaderValue::from_str(&next.encode()) {
+            headers.insert(NEXT_CURSOR_HEADER, value);
+        }
+    }
+
+    Ok(response)
 }
 
 /// Get liquidity status
@@ -406,18 +1297,52 @@ async fn get_liquidity(
             mint_url: mb.mint_url.clone(),
             name: mb.name,
             balance: mb.balance,
+            reserved: mb.reserved,
+            locked: mb.locked,
+            available: mb.available,
+            quarantined: mb.quarantined,
             unit: "sat".to_string(),
+            healthy: mb.healthy,
         })
         .collect();
 
     let total_balance = mints.iter().map(|m| m.balance).sum();
+    let total_available = mints.iter().map(|m| m.available).sum();
+    let total_locked = mints.iter().map(|m| m.locked).sum();
 
     Ok(Json(LiquidityResponse {
         mints,
         total_balance,
+        total_available,
+        total_locked,
     }))
 }
 
+/// Get health check and circuit breaker state for each mint
+async fn get_mints_health(State(state): State<AppState>) -> Json<MintsHealthResponse> {
+    let mints = state
+        .broker
+        .get_mint_health()
+        .into_iter()
+        .map(|mint| MintHealthInfo {
+            available: mint.health.is_healthy() && mint.circuit.state != CircuitState::Open,
+            healthy: mint.health.is_healthy(),
+            consecutive_failures: mint.health.consecutive_failures,
+            last_checked: mint
+                .health
+                .last_checked
+                .map(|t| DateTime::<Utc>::from(t).to_rfc3339()),
+            last_error: mint.health.last_error,
+            latency_ms: mint.health.latency.map(|latency| latency.as_millis() as u64),
+            circuit: mint.circuit,
+            mint_url: mint.mint_url,
+            name: mint.name,
+        })
+        .collect();
+
+    Json(MintsHealthResponse { mints })
+}
+
 /// Get liquidity events for a mint
 async fn get_liquidity_events(
     State(state): State<AppState>,
@@ -447,42 +1372,127 @@ async fn health_check(State(state): State<AppState>) -> Result<Json<HealthRespon
     }))
 }
 
+/// Liveness probe: the process is up and serving requests
+async fn liveness() -> Json<LivenessResponse> {
+    Json(LivenessResponse {
+        status: "ok".to_string(),
+        timestamp: Utc::now().to_rfc3339(),
+    })
+}
+
+/// Readiness probe: the broker can actually swap
+///
+/// Ready when the database is reachable with every migration applied, every
+/// configured mint has a wallet loaded and at least one of them is healthy.
+async fn readiness(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let database = match state.db.pool().acquire().await {
+        Ok(_) => "ok".to_string(),
+        Err(e) => format!("error: {}", e),
+    };
+    let migrations = match state.db.pending_migrations().await {
+        Ok(0) => "ok".to_string(),
+        Ok(pending) => format!("{} pending", pending),
+        Err(e) => format!("error: {}", e),
+    };
+    let mints = state.broker.mint_readiness();
+
+    let ready = database == "ok"
+        && migrations == "ok"
+        && mints.wallets_loaded == mints.configured
+        && mints.healthy > 0;
+    let code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        code,
+        Json(ReadinessResponse {
+            ready,
+            timestamp: Utc::now().to_rfc3339(),
+            database,
+            migrations,
+            mints: mints.configured,
+            wallets_loaded: mints.wallets_loaded,
+            healthy_mints: mints.healthy,
+        }),
+    )
+}
+
+/// Describe the broker's keys, pairs and terms
+async fn get_info(State(state): State<AppState>) -> Json<InfoResponse> {
+    let config = state.broker.get_config();
+    let cross_unit = state.broker.supports_cross_unit();
+
+    let pairs = config
+        .swap_pairs(cross_unit)
+        .into_iter()
+        .map(|(source, target)| {
+            let terms = config.pair_terms(&source.mint_url, &target.mint_url);
+            PairInfo {
+                source_mint: source.mint_url.clone(),
+                target_mint: target.mint_url.clone(),
+                fee_rate: terms.fee_rate,
+                min_swap_amount: terms.min_swap_amount,
+                max_swap_amount: terms.max_swap_amount,
+            }
+        })
+        .collect();
+
+    Json(InfoResponse {
+        name: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        api_version: API_VERSION.to_string(),
+        api_versions: ApiVersion::SUPPORTED
+            .iter()
+            .map(|version| version.to_string())
+            .collect(),
+        pubkey: state.broker.identity().public_key_hex(),
+        mints: config.mints,
+        pairs,
+        fee_rate: config.fee_rate,
+        fees: config.fees,
+        min_swap_amount: config.min_swap_amount,
+        max_swap_amount: config.max_swap_amount,
+        quote_expiry_seconds: config.quote_expiry_seconds,
+        quote_extension_seconds: config.quote_extension_seconds,
+        refund_locktime_seconds: config.refund_locktime_seconds,
+        quote_types: vec![QuoteType::ExactIn, QuoteType::ExactOut],
+        features: InfoFeatures {
+            cross_unit,
+            webhooks: state.db.webhooks_enabled(),
+            idempotency: true,
+        },
+        maintenance: state.broker.in_maintenance(),
+        error_codes: ErrorCode::ALL.into_iter().map(ErrorCodeInfo::from).collect(),
+    })
+}
+
 /// Get metrics
-async fn get_metrics(State(state): State<AppState>) -> Result<Json<MetricsResponse>, ApiError> {
-    let all_quotes = state
+async fn get_metrics(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<MetricsResponse>, ApiError> {
+    let summary = state
         .db
-        .list_quotes(None, 10000)
+        .get_metrics_summary(query.from, query.to)
         .await
         .map_err(ApiError::from)?;
 
-    let total_quotes = all_quotes.len() as u64;
-    let completed_swaps = all_quotes
-        .iter()
-        .filter(|q| q.status == SwapStatus::Completed.to_string())
-        .count() as u64;
-    let failed_swaps = all_quotes
-        .iter()
-        .filter(|q| q.status == SwapStatus::Failed.to_string())
-        .count() as u64;
-
-    let total_volume: i64 = all_quotes
-        .iter()
-        .filter(|q| q.status == SwapStatus::Completed.to_string())
-        .map(|q| q.amount_in)
-        .sum();
-
-    let total_fees: i64 = all_quotes
-        .iter()
-        .filter(|q| q.status == SwapStatus::Completed.to_string())
-        .map(|q| q.fee)
-        .sum();
+    let completed = summary.status(SwapStatus::Completed);
 
     Ok(Json(MetricsResponse {
-        total_quotes,
-        completed_swaps,
-        failed_swaps,
-        total_volume: total_volume as u64,
-        total_fees: total_fees as u64,
+        total_quotes: summary.total_quotes() as u64,
+        completed_swaps: completed.count as u64,
+        failed_swaps: summary.status(SwapStatus::Failed).count as u64,
+        total_volume: completed.volume as u64,
+        total_fees: completed.fees as u64,
+        quotes_by_status: summary
+            .by_status
+            .into_iter()
+            .map(|m| (m.status.to_string(), m.count as u64))
+            .collect(),
     }))
 }
 
@@ -492,7 +1502,13 @@ async fn get_metrics(State(state): State<AppState>) -> Result<Json<MetricsRespon
 pub enum ApiError {
     Internal(String),
     BadRequest(String),
+    Unauthorized(String),
     NotFound(String),
+    Conflict(String),
+    RateLimited(u64), // Seconds until the client may retry
+    Validation(Vec<FieldError>),
+    PayloadTooLarge(String),
+    Timeout(Duration), // How long the request ran before the client was answered
     Broker(BrokerError),
 }
 
@@ -502,35 +1518,117 @@ impl From<BrokerError> for ApiError {
     }
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        let (status, code, message) = match self {
-            ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", msg),
-            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "BAD_REQUEST", msg),
-            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, "NOT_FOUND", msg),
-            ApiError::Broker(err) => match err {
-                BrokerError::QuoteNotFound(msg) => (StatusCode::NOT_FOUND, "QUOTE_NOT_FOUND", msg),
-                BrokerError::QuoteExpired(msg) => {
-                    (StatusCode::BAD_REQUEST, "QUOTE_EXPIRED", msg)
-                }
-                BrokerError::InsufficientLiquidity { .. } => (
-                    StatusCode::SERVICE_UNAVAILABLE,
-                    "INSUFFICIENT_LIQUIDITY",
-                    err.to_string(),
+impl ApiError {
+    /// HTTP status, machine-readable code and message for this error
+    pub fn into_parts(self) -> (StatusCode, ErrorCode, String) {
+        let (code, message) = match self {
+            ApiError::Internal(msg) => (ErrorCode::InternalError, msg),
+            ApiError::BadRequest(msg) => (ErrorCode::BadRequest, msg),
+            ApiError::Unauthorized(msg) => (ErrorCode::Unauthorized, msg),
+            ApiError::NotFound(msg) => (ErrorCode::NotFound, msg),
+            ApiError::Conflict(msg) => (ErrorCode::Conflict, msg),
+            ApiError::RateLimited(retry_after) => (
+                ErrorCode::RateLimited,
+                format!("Too many requests, retry in {}s", retry_after),
+            ),
+            ApiError::Validation(fields) => (
+                ErrorCode::ValidationFailed,
+                format!(
+                    "Invalid {}",
+                    fields
+                        .iter()
+                        .map(|f| f.field.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
                 ),
-                _ => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "BROKER_ERROR",
-                    err.to_string(),
-                ),
-            },
+            ),
+            ApiError::PayloadTooLarge(msg) => (ErrorCode::PayloadTooLarge, msg),
+            ApiError::Timeout(after) => (
+                ErrorCode::RequestTimeout,
+                format!("Request still running after {:?}; retry for its result", after),
+            ),
+            // A daily volume limit won't clear up soon, unlike open quotes
+            ApiError::Broker(
+                err @ BrokerError::RiskLimitExceeded(RiskViolation::DailyVolume { .. }),
+            ) => return (StatusCode::FORBIDDEN, err.code(), err.to_string()),
+            ApiError::Broker(err) => {
+                let code = err.code();
+                match err {
+                    BrokerError::QuoteNotFound(msg) | BrokerError::QuoteExpired(msg) => (code, msg),
+                    err => (code, err.to_string()),
+                }
+            }
         };
+        (error_status(code), code, message)
+    }
 
-        let body = Json(ErrorResponse {
+    /// HTTP status and JSON body of this error
+    pub fn into_body(self) -> (StatusCode, ErrorResponse) {
+        let retry_after = match &self {
+            ApiError::RateLimited(secs) => Some(*secs),
+            _ => None,
+        };
+        let fields = match &self {
+            ApiError::Validation(fields) => fields.clone(),
+            _ => Vec::new(),
+        };
+        let (status, code, message) = self.into_parts();
+
+        let body = ErrorResponse {
             error: message,
             code: code.to_string(),
-        });
+            retryable: code.retryable(),
+            retry_after,
+            fields,
+        };
+        (status, body)
+    }
+}
 
-        (status, body).into_response()
+/// HTTP status of responses with an error code
+fn error_status(code: ErrorCode) -> StatusCode {
+    match code {
+        ErrorCode::BadRequest
+        | ErrorCode::InvalidSwapRequest
+        | ErrorCode::AmountTooLow
+        | ErrorCode::AmountTooHigh
+        | ErrorCode::UnsupportedMint
+        | ErrorCode::SameMint
+        | ErrorCode::QuoteExpired
+        | ErrorCode::InvalidSignature
+        | ErrorCode::ProofsNotSpendable
+        | ErrorCode::ProofsNotBound => StatusCode::BAD_REQUEST,
+        ErrorCode::ValidationFailed => StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+        ErrorCode::Blacklisted => StatusCode::FORBIDDEN,
+        ErrorCode::NotFound | ErrorCode::QuoteNotFound => StatusCode::NOT_FOUND,
+        ErrorCode::Conflict | ErrorCode::DuplicateProofs => StatusCode::CONFLICT,
+        ErrorCode::RateLimited | ErrorCode::RiskLimitExceeded => StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::MintTimeout => StatusCode::GATEWAY_TIMEOUT,
+        ErrorCode::MintError | ErrorCode::UpstreamError => StatusCode::BAD_GATEWAY,
+        ErrorCode::InsufficientLiquidity
+        | ErrorCode::Maintenance
+        | ErrorCode::QueueFull
+        | ErrorCode::MintUnhealthy
+        | ErrorCode::MintUnavailable
+        | ErrorCode::PriceUnavailable
+        | ErrorCode::RequestTimeout => StatusCode::SERVICE_UNAVAILABLE,
+        ErrorCode::DatabaseError | ErrorCode::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, body) = self.into_body();
+        let retry_after = body.retry_after;
+
+        let mut response = (status, Json(body)).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
     }
 }
@@ -0,0 +1,63 @@
+//! broker-top: live view of a running broker in the terminal
+//!
+//! Usage: broker-top [--interval SECONDS] [--api-key KEY] [URL]
+//!
+//! URL defaults to `BROKER_URL`, then http://localhost:3000; the API key to
+//! `BROKER_API_KEY`. Press Ctrl-C to quit.
+
+use cashu_broker::top::{render, Monitor, CLEAR_SCREEN};
+use std::io::Write;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: broker-top [--interval SECONDS] [--api-key KEY] [URL]";
+
+/// Seconds between polls unless --interval is given
+const DEFAULT_INTERVAL: u64 = 2;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut url =
+        std::env::var("BROKER_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    let mut api_key = std::env::var("BROKER_API_KEY").ok();
+    let mut interval = DEFAULT_INTERVAL;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interval" | "-n" => {
+                interval = args
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&seconds| seconds > 0)
+                    .ok_or("--interval takes a positive number of seconds")?;
+            }
+            "--api-key" => api_key = Some(args.next().ok_or("--api-key takes a key")?),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ if arg.starts_with('-') => return Err(format!("{}\n{}", arg, USAGE).into()),
+            _ => url = arg,
+        }
+    }
+
+    let monitor = Monitor::new(&url, api_key)?;
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => break,
+        }
+
+        // A broker that is down or restarting is shown, not fatal
+        let screen = match monitor.snapshot().await {
+            Ok(snapshot) => render(&snapshot, &url),
+            Err(e) => format!("broker-top  {}\n\nCan't reach the broker: {}\n", url, e),
+        };
+        let mut stdout = std::io::stdout().lock();
+        write!(stdout, "{}{}", CLEAR_SCREEN, screen)?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+//! Binding source proofs to the quote's client
+//!
+//! The proofs posted to `POST /quote/:id/accept` must be ones the broker can
+//! claim for this quote, and that the quote's client vouches for. Each must be
+//! P2PK-locked to the quote's tweaked key with no other way to spend it: no
+//! further keys, no locktime or refund keys, one signature and `SIG_INPUTS`.
+//! And each must carry a witness signature by the quote's `user_pubkey` over
+//! its secret, made the NUT-11 way, so nobody can hand in proofs on behalf of
+//! a client that didn't lock them. The broker checks both before it locks
+//! anything, and drops the client's signatures before claiming the proofs;
+//! the mint only needs the broker's.
+
+use crate::error::{BrokerError, Result};
+use cdk::nuts::{Proofs, SecretKey, SigFlag, SpendingConditions};
+use schnorr_fun::fun::{marker::*, Point};
+use schnorr_fun::{Message, Schnorr, Signature};
+use secp256kfun::nonce;
+use sha2::{Digest, Sha256};
+
+/// Sign each proof's secret with the client's key, as the broker expects at accept
+pub fn sign_proofs(mut proofs: Proofs, client_key: &SecretKey) -> Result<Proofs> {
+    for proof in proofs.iter_mut() {
+        proof
+            .sign_p2pk(client_key.clone())
+            .map_err(|e| BrokerError::Cdk(format!("Failed to sign proof: {:?}", e)))?;
+    }
+    Ok(proofs)
+}
+
+/// Check every proof is locked to `tweaked_pubkey` alone and signed by `client_pubkey`
+///
+/// Both keys are compressed (33 bytes).
+pub fn check_bound(proofs: &Proofs, tweaked_pubkey: &[u8], client_pubkey: &[u8]) -> Result<()> {
+    let unbound = |msg: String| BrokerError::ProofsNotBound(msg);
+    let client_key = client_pubkey
+        .get(1..)
+        .and_then(|xonly| <[u8; 32]>::try_from(xonly).ok())
+        .and_then(Point::<EvenY>::from_xonly_bytes)
+        .ok_or_else(|| unbound("invalid client pubkey".to_string()))?;
+    let schnorr = Schnorr::<Sha256, nonce::Deterministic<Sha256>>::default();
+
+    if proofs.is_empty() {
+        return Err(unbound("no proofs".to_string()));
+    }
+    for proof in proofs {
+        let conditions = match SpendingConditions::try_from(&proof.secret) {
+            Ok(SpendingConditions::P2PKConditions { data, conditions }) => {
+                if data.to_bytes().as_slice() != tweaked_pubkey {
+                    return Err(unbound(format!(
+                        "proof of {} is not locked to the quote's tweaked key",
+                        proof.amount
+                    )));
+                }
+                conditions
+            }
+            _ => {
+                return Err(unbound(format!(
+                    "proof of {} is not P2PK-locked",
+                    proof.amount
+                )))
+            }
+        };
+        if let Some(conditions) = conditions {
+            let other_keys = conditions.pubkeys.is_some_and(|keys| !keys.is_empty())
+                || conditions.refund_keys.is_some_and(|keys| !keys.is_empty());
+            if other_keys
+                || conditions.locktime.is_some()
+                || conditions.num_sigs.is_some_and(|n| n > 1)
+                || conditions.sig_flag != SigFlag::SigInputs
+            {
+                return Err(unbound(format!(
+                    "proof of {} has spending conditions beyond the tweaked key",
+                    proof.amount
+                )));
+            }
+        }
+
+        // NUT-11 signs the SHA-256 of the secret
+        let digest = Sha256::digest(proof.secret.to_string().as_bytes());
+        let signed = proof
+            .witness
+            .as_ref()
+            .and_then(|witness| witness.signatures())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|sig| hex::decode(sig).ok())
+            .filter_map(|bytes| <[u8; 64]>::try_from(bytes).ok())
+            .filter_map(Signature::<Public>::from_bytes)
+            .any(|sig| schnorr.verify(&client_key, Message::<Public>::raw(&digest), &sig));
+        if !signed {
+            return Err(unbound(format!(
+                "proof of {} is not signed by the quote's client",
+                proof.amount
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Drop the client's signatures, leaving the proofs for the broker to sign
+pub fn strip_witnesses(proofs: &mut Proofs) {
+    for proof in proofs.iter_mut() {
+        proof.witness = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::nuts::{Conditions, Id, Nut10Secret, Proof};
+    use cdk::secret::Secret;
+    use cdk::Amount;
+    use std::str::FromStr;
+
+    fn proof(conditions: SpendingConditions) -> Proof {
+        Proof::new(
+            Amount::from(8),
+            Id::from_str("009a1f293253e41e").unwrap(),
+            Secret::try_from(Nut10Secret::from(conditions)).unwrap(),
+            SecretKey::generate().public_key(),
+        )
+    }
+
+    #[test]
+    fn test_check_bound() {
+        let client = SecretKey::generate();
+        let tweaked = SecretKey::generate().public_key();
+        let locked = || proof(SpendingConditions::new_p2pk(tweaked, None));
+        let check = |proofs: &Proofs| {
+            check_bound(proofs, &tweaked.to_bytes(), &client.public_key().to_bytes())
+        };
+
+        let signed = sign_proofs(vec![locked(), locked()], &client).unwrap();
+        check(&signed).unwrap();
+
+        // Unsigned, or signed by someone else
+        assert!(check(&vec![locked()]).is_err());
+        let other = sign_proofs(vec![locked()], &SecretKey::generate()).unwrap();
+        assert!(check(&other).is_err());
+        assert!(check(&Vec::new()).is_err());
+
+        // Locked to another key
+        let elsewhere = proof(SpendingConditions::new_p2pk(
+            SecretKey::generate().public_key(),
+            None,
+        ));
+        assert!(check(&sign_proofs(vec![elsewhere], &client).unwrap()).is_err());
+
+        // A refund path lets the client take the proofs back under the broker
+        let refundable = proof(SpendingConditions::new_p2pk(
+            tweaked,
+            Some(Conditions {
+                locktime: Some(1),
+                refund_keys: Some(vec![client.public_key()]),
+                ..Default::default()
+            }),
+        ));
+        assert!(check(&sign_proofs(vec![refundable], &client).unwrap()).is_err());
+
+        let mut stripped = signed;
+        strip_witnesses(&mut stripped);
+        assert!(stripped.iter().all(|p| p.witness.is_none()));
+        assert!(check(&stripped).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Banned public keys and proofs
+//!
+//! An emergency brake against abusive clients and known-stolen ecash. The
+//! admin API lists public keys the broker won't quote to or swap with, and
+//! proofs it won't take in. Quote requests are checked against the client's
+//! `user_pubkey`, and accepts against the quote's public key and every source
+//! proof. Proofs are identified by `Y = hash_to_curve(secret)` as in NUT-07,
+//! so an entry can be made from a mint's spent-proof list without knowing the
+//! secret.
+
+use crate::error::BrokerError;
+use cdk::nuts::{Proofs, PublicKey};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// What a blacklist entry bans
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlacklistKind {
+    Pubkey, // Client public key, compressed, as hex
+    Proof,  // Proof Y, compressed, as hex
+}
+
+impl fmt::Display for BlacklistKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pubkey => write!(f, "pubkey"),
+            Self::Proof => write!(f, "proof"),
+        }
+    }
+}
+
+impl FromStr for BlacklistKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pubkey" => Ok(Self::Pubkey),
+            "proof" => Ok(Self::Proof),
+            _ => Err(format!("Invalid blacklist kind: {}", s)),
+        }
+    }
+}
+
+/// Canonical form of a blacklisted value: a compressed point as lowercase hex
+pub fn normalize(kind: BlacklistKind, value: &str) -> Result<String, BrokerError> {
+    PublicKey::from_hex(value.trim())
+        .map(|key| key.to_hex())
+        .map_err(|e| BrokerError::InvalidSwapRequest(format!("Invalid {} {}: {}", kind, value, e)))
+}
+
+/// The Y of each proof, as stored in the blacklist
+pub fn proof_ys(proofs: &Proofs) -> Result<Vec<String>, BrokerError> {
+    proofs
+        .iter()
+        .map(|proof| {
+            proof
+                .y()
+                .map(|y| y.to_hex())
+                .map_err(|e| BrokerError::InvalidSwapRequest(format!("Invalid proof: {}", e)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "02a9acc1e48c25eeeb9289b5031cc57da9fe72f3fe2861d264bdc074209b107ba2";
+
+    #[test]
+    fn test_kind_roundtrip() {
+        for kind in [BlacklistKind::Pubkey, BlacklistKind::Proof] {
+            assert_eq!(kind.to_string().parse::<BlacklistKind>(), Ok(kind));
+            assert_eq!(
+                serde_json::to_value(kind).unwrap(),
+                serde_json::json!(kind.to_string())
+            );
+        }
+        assert!("mint".parse::<BlacklistKind>().is_err());
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(BlacklistKind::Pubkey, KEY).unwrap(), KEY);
+        assert_eq!(
+            normalize(BlacklistKind::Proof, &format!(" {} ", KEY.to_uppercase())).unwrap(),
+            KEY
+        );
+        assert!(normalize(BlacklistKind::Pubkey, "02abcd").is_err());
+        assert!(normalize(BlacklistKind::Proof, "not hex").is_err());
+    }
+}
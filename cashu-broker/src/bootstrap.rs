@@ -0,0 +1,156 @@
+//! Initial liquidity
+//!
+//! `INITIAL_LIQUIDITY` lists the balance the broker should hold on each mint
+//! when it starts and how to fund a shortfall: by printing a Lightning invoice
+//! and waiting for it to be paid, by depositing a cashu token read from a
+//! file, or not at all. [`bootstrap`] runs once at startup, before the server
+//! binds, so the broker starts taking quotes with its liquidity in place.
+
+use crate::broker::Broker;
+use crate::error::{BrokerError, Result};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// How a shortfall in a mint's starting balance is funded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FundingMode {
+    /// Print a Lightning invoice for the shortfall and wait for it to be paid
+    LightningInvoicePrint,
+    /// Deposit the cashu token in `token_file`
+    EcashTokenFile,
+    /// Leave the mint as it is, e.g. to fund it through the admin API
+    Skip,
+}
+
+impl fmt::Display for FundingMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LightningInvoicePrint => write!(f, "lightning-invoice-print"),
+            Self::EcashTokenFile => write!(f, "ecash-token-file"),
+            Self::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+/// Starting balance of one mint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InitialLiquidity {
+    pub mint_url: String,
+    pub amount: u64, // Balance to top up to
+    pub funding: FundingMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<PathBuf>, // Required with ecash-token-file
+}
+
+/// What bootstrapping did on a mint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Funded(u64),    // Amount deposited
+    AlreadyFunded,  // The balance was already at the target
+    Skipped,        // Funding mode `skip`
+    Failed(String), // Startup carries on without the liquidity
+}
+
+/// Top up each mint in `plan` to its starting balance
+///
+/// Token files are deposited first. Then an invoice for every remaining
+/// shortfall is printed, and the call waits until all of them are paid or
+/// time out. A mint that can't be funded doesn't stop the others.
+pub async fn bootstrap(broker: &Broker, plan: &[InitialLiquidity]) -> Vec<(String, Outcome)> {
+    let status = broker.get_liquidity_status().await;
+    let shortfall = |mint_url: &str, amount: u64| {
+        let balance = status
+            .mints
+            .iter()
+            .find(|m| m.mint_url == mint_url)
+            .map_or(0, |m| m.balance);
+        amount.saturating_sub(balance)
+    };
+
+    let mut outcomes = Vec::new();
+    let mut invoices = Vec::new();
+
+    for entry in plan {
+        let outcome = match entry.funding {
+            FundingMode::Skip => Outcome::Skipped,
+            _ if shortfall(&entry.mint_url, entry.amount) == 0 => Outcome::AlreadyFunded,
+            FundingMode::EcashTokenFile => match deposit_token_file(broker, entry).await {
+                Ok(amount) => Outcome::Funded(amount),
+                Err(e) => Outcome::Failed(e.to_string()),
+            },
+            FundingMode::LightningInvoicePrint => {
+                let amount = shortfall(&entry.mint_url, entry.amount);
+                match broker.create_deposit_invoice(&entry.mint_url, amount).await {
+                    Ok(quote) => {
+                        println!("\n⚡ Pay to fund {} with {} sats:", entry.mint_url, amount);
+                        println!("   {}\n", quote.request);
+                        info!("Waiting for a {} sat deposit on {}", amount, entry.mint_url);
+                        invoices.push((entry.mint_url.clone(), quote));
+                        continue;
+                    }
+                    Err(e) => Outcome::Failed(e.to_string()),
+                }
+            }
+        };
+        outcomes.push((entry.mint_url.clone(), outcome));
+    }
+
+    let claims = invoices.into_iter().map(|(mint_url, quote)| async move {
+        let outcome = match broker.claim_deposit(&mint_url, quote).await {
+            Ok(amount) => Outcome::Funded(amount),
+            Err(e) => Outcome::Failed(e.to_string()),
+        };
+        (mint_url, outcome)
+    });
+    outcomes.extend(join_all(claims).await);
+
+    for (mint_url, outcome) in &outcomes {
+        match outcome {
+            Outcome::Funded(amount) => info!("Funded {} with {} sats", mint_url, amount),
+            Outcome::AlreadyFunded => info!("{} already holds its starting balance", mint_url),
+            Outcome::Skipped => info!("Not funding {} at startup", mint_url),
+            Outcome::Failed(e) => warn!("Could not fund {}: {}", mint_url, e),
+        }
+    }
+
+    outcomes
+}
+
+async fn deposit_token_file(broker: &Broker, entry: &InitialLiquidity) -> Result<u64> {
+    let path = entry.token_file.as_ref().ok_or_else(|| {
+        BrokerError::InvalidSwapRequest(format!("No token file for {}", entry.mint_url))
+    })?;
+    let token = tokio::fs::read_to_string(path).await?;
+    broker.deposit_ecash(&entry.mint_url, &token).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initial_liquidity_json() {
+        let plan: Vec<InitialLiquidity> = serde_json::from_str(
+            r#"[
+                {"mint_url":"http://mint-a.test","amount":100000,"funding":"lightning-invoice-print"},
+                {"mint_url":"http://mint-b.test","amount":5000,"funding":"ecash-token-file",
+                 "token_file":"/run/secrets/mint-b.token"},
+                {"mint_url":"http://mint-c.test","amount":0,"funding":"skip"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(plan[0].funding, FundingMode::LightningInvoicePrint);
+        assert_eq!(plan[0].token_file, None);
+        assert_eq!(
+            plan[1].token_file.as_deref(),
+            Some(std::path::Path::new("/run/secrets/mint-b.token"))
+        );
+        assert_eq!(plan[2].funding.to_string(), "skip");
+        assert!(serde_json::from_str::<FundingMode>(r#""lightning""#).is_err());
+    }
+}
@@ -2,114 +2,1982 @@
 //!
 //! Facilitates atomic swaps between different Cashu mints for a fee
 
-use crate::error::Result;
-use crate::liquidity::LiquidityManager;
-use crate::swap::SwapCoordinator;
-use crate::types::{BrokerConfig, SwapQuote, SwapRequest};
-use cdk::nuts::Proofs;
+use crate::accounting::{self, EntryKind};
+use crate::adaptor::{decode_encrypted_signature, encode_encrypted_signature};
+use crate::blacklist::{self, BlacklistKind};
+use crate::circuit_breaker::{CircuitBreakerConfig, CircuitState, CircuitStatus};
+use crate::clock::{Clock, SystemClock};
+use crate::db::{
+    Database, LedgerEntry, LiquidityEvent, LiquiditySnapshot, MintChange, PendingMint, QuoteKeys,
+    QuoteRecord, SwapRecord,
+};
+use crate::error::{BrokerError, Result};
+use crate::events::{self, BrokerEvent, EventBus, EventSink};
+#[cfg(feature = "faults")]
+use crate::faults::FaultInjector;
+use crate::fees::VOLUME_WINDOW_DAYS;
+use crate::identity::BrokerIdentity;
+use crate::jobs::{JobQueue, JobQueueConfig, JobStatus};
+use crate::leases::{self, leased_mint, mint_lease, Leases, QUOTE_SWEEP};
+use crate::liquidity::{
+    Consolidation, InvoicePayment, LiquidityManager, MintHealth, MintLiquidity, RebalanceTransfer,
+};
+use crate::price::{self, PriceFeed};
+use crate::pricing::{InventorySkew, PricingStrategy};
+use crate::quarantine::{QuarantineReason, QuarantineRetry, QuarantinedProof};
+use crate::reconcile::{reconcile, ReconcileReport, Reconciliation};
+use crate::retry::RetryPolicy;
+use crate::rng::SwapRng;
+use crate::selection::SelectionStrategy;
+use crate::store::{LedgerStore, LiquidityStore, QuoteStore};
+use crate::swap::{PreparedSwap, QuoteSecrets, SwapCoordinator};
+use crate::timeouts::{MintOp, MintTimeouts};
+use crate::types::{
+    mint_key, mint_url_of, BrokerConfig, ClaimOrder, MintConfig, SwapQuote, SwapRequest,
+    SwapStatus,
+};
+use crate::watcher::OutputWatch;
+use cdk::amount::SplitTarget;
+use cdk::mint_url::MintUrl;
+use cdk::nuts::{CurrencyUnit, Proof, Proofs, PublicKey, State, Token};
+use cdk::wallet::MintQuote;
+use cdk::Amount;
+use chrono::{DateTime, Utc};
+use schnorr_fun::adaptor::EncryptedSignature;
+use schnorr_fun::Signature;
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tracing::info;
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tracing::{info, instrument, warn, Span};
+
+/// How often expired swap locks are checked for refunds
+const REFUND_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often claims of client proofs that failed at complete are retried
+const CLAIM_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long each watch of client-first swap outputs runs before checking them
+/// all again and resubscribing to those still waiting
+const CLIENT_CLAIM_WATCH_PERIOD: Duration = Duration::from_secs(15);
+
+/// How often expired quotes are swept
+const QUOTE_SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often each mint's info endpoint is pinged
+const MINT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long each watch for spent proofs runs before resubscribing to the current ones
+const SPENT_PROOFS_WATCH_PERIOD: Duration = Duration::from_secs(300);
+
+/// How often mint keysets and info are fetched again ahead of their cache expiry
+const MINT_CACHE_REFRESH_INTERVAL: Duration = Duration::from_secs(900);
+
+/// How often mint balances are checked for rebalancing
+const REBALANCE_CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// How long to wait for a pending rebalance mint, paid long ago, to be minted
+const PENDING_MINT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often proof counts are checked for consolidation
+const CONSOLIDATION_CHECK_INTERVAL: Duration = Duration::from_secs(600);
+
+/// How long to wait for a deposit invoice to be paid
+const DEPOSIT_PAYMENT_TIMEOUT: Duration = Duration::from_secs(3600);
 
 /// The main broker service ("Charlie")
 ///
 /// Coordinates liquidity management and swap execution across multiple Cashu mints
 pub struct Broker {
-    config: BrokerConfig,
     liquidity: Arc<LiquidityManager>,
     swap_coordinator: Arc<SwapCoordinator>,
+    store: Option<Arc<dyn QuoteStore>>,
+    liquidity_store: Option<Arc<dyn LiquidityStore>>,
+    ledger: Option<Arc<dyn LedgerStore>>,
+    price_feed: Option<Arc<dyn PriceFeed>>,
+    identity: Arc<BrokerIdentity>,
+    leases: Option<Leases>, // Set when other instances share the database
+    events: EventBus,
+    maintenance: AtomicBool, // New quotes are refused while set
+    jobs: JobQueue,          // Accepts and completes, which call the mints
+}
+
+/// Assembles a [`Broker`] from its configuration and optional parts
+///
+/// Without stores the broker keeps its state in memory only, and without an
+/// identity it generates a key that lasts as long as the process.
+pub struct BrokerBuilder {
+    config: BrokerConfig,
+    store: Option<Arc<dyn QuoteStore>>,
+    liquidity_store: Option<Arc<dyn LiquidityStore>>,
+    ledger: Option<Arc<dyn LedgerStore>>,
+    leases: Option<Leases>,
+    event_sinks: Vec<Arc<dyn EventSink>>,
+    price_feed: Option<Arc<dyn PriceFeed>>,
+    pricing: Arc<dyn PricingStrategy>,
+    identity: Option<BrokerIdentity>,
+    retry_policy: RetryPolicy,
+    selection_strategy: SelectionStrategy,
+    require_dleq: bool,
+    circuit_breaker: CircuitBreakerConfig,
+    mint_timeouts: MintTimeouts,
+    job_queue: JobQueueConfig,
+    rng: SwapRng,
+    clock: Arc<dyn Clock>,
+    #[cfg(feature = "faults")]
+    faults: FaultInjector,
+}
+
+impl BrokerBuilder {
+    pub fn new(config: BrokerConfig) -> Self {
+        Self {
+            config,
+            store: None,
+            liquidity_store: None,
+            ledger: None,
+            leases: None,
+            event_sinks: Vec::new(),
+            price_feed: None,
+            pricing: Arc::new(InventorySkew::default()),
+            identity: None,
+            retry_policy: RetryPolicy::default(),
+            selection_strategy: SelectionStrategy::default(),
+            require_dleq: true,
+            circuit_breaker: CircuitBreakerConfig::default(),
+            mint_timeouts: MintTimeouts::default(),
+            job_queue: JobQueueConfig::default(),
+            rng: SwapRng::os(),
+            clock: Arc::new(SystemClock),
+            #[cfg(feature = "faults")]
+            faults: FaultInjector::default(),
+        }
+    }
+
+    /// Persist quotes, liquidity history and the accounting ledger in the database
+    pub fn database(self, db: Database) -> Self {
+        let db = Arc::new(db);
+        self.quote_store(db.clone())
+            .liquidity_store(db.clone())
+            .ledger_store(db)
+    }
+
+    /// Persist quote status, keys and swap records in `store`
+    pub fn quote_store(mut self, store: Arc<dyn QuoteStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Record liquidity events and checkpoints in `store`
+    pub fn liquidity_store(mut self, store: Arc<dyn LiquidityStore>) -> Self {
+        self.liquidity_store = Some(store);
+        self
+    }
+
+    /// Record fees earned and paid in `store`
+    pub fn ledger_store(mut self, store: Arc<dyn LedgerStore>) -> Self {
+        self.ledger = Some(store);
+        self
+    }
+
+    /// Share the stores with other broker instances, taking `leases` on the
+    /// mints this one quotes out of and on sweeps of the shared tables
+    ///
+    /// Keep them from lapsing by running [`Leases::run`] alongside.
+    pub fn leases(mut self, leases: Leases) -> Self {
+        self.leases = Some(leases);
+        self
+    }
+
+    /// Hand every broker event to `sink`, in addition to subscribers
+    pub fn event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.event_sinks.push(sink);
+        self
+    }
+
+    /// Price quotes between mints of different units with `price_feed`
+    pub fn price_feed(mut self, price_feed: Arc<dyn PriceFeed>) -> Self {
+        self.price_feed = Some(price_feed);
+        self
+    }
+
+    /// Set the fee rate of new quotes with `pricing` instead of the flat
+    /// configured rates
+    pub fn pricing_strategy(mut self, pricing: Arc<dyn PricingStrategy>) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Use a persistent identity key instead of a random one
+    pub fn identity(mut self, identity: BrokerIdentity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Retry transient failures of wallet calls to mints according to `policy`
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Pick proofs for payments and withdrawals with `strategy`
+    pub fn selection_strategy(mut self, strategy: SelectionStrategy) -> Self {
+        self.selection_strategy = strategy;
+        self
+    }
+
+    /// Whether received proofs without a DLEQ are quarantined, as by default
+    pub fn require_dleq(mut self, required: bool) -> Self {
+        self.require_dleq = required;
+        self
+    }
+
+    /// Set when circuit breakers open for failing mints and how long they stay open
+    pub fn circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = config;
+        self
+    }
+
+    /// Give up on wallet calls to mints that take longer than `timeouts`
+    pub fn mint_timeouts(mut self, timeouts: MintTimeouts) -> Self {
+        self.mint_timeouts = timeouts;
+        self
+    }
+
+    /// Set how many accepts and completes run and wait at once
+    pub fn job_queue(mut self, config: JobQueueConfig) -> Self {
+        self.job_queue = config;
+        self
+    }
+
+    /// Draw keys, secrets and quote IDs from `rng`, a seeded one for
+    /// reproducible tests and simulations
+    pub fn rng(mut self, rng: SwapRng) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// Stamp and expire quotes by `clock` instead of the system clock
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Make wallet calls to mints fail, stall or lose their answer as the
+    /// rules of `faults` say; keep a clone to change them while it runs
+    #[cfg(feature = "faults")]
+    pub fn faults(mut self, faults: FaultInjector) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    /// Create the broker's wallets and start it up
+    pub async fn build(self) -> Result<Broker> {
+        let mut config = self.config;
+
+        // Mints added or removed at runtime outlive the configuration
+        if let Some(store) = &self.store {
+            apply_mint_changes(&mut config.mints, &store.list_mint_changes().await?);
+        }
+
+        println!("\n{}", "=".repeat(70));
+        println!("🤖 CHARLIE BROKER SERVICE");
+        println!("{}", "=".repeat(70));
+        println!("Fee Rate: {:.2}%", config.fee_rate * 100.0);
+        println!("Min Swap: {} sats", config.min_swap_amount);
+        println!("Max Swap: {} sats", config.max_swap_amount);
+        println!("Supported Mints: {}", config.mints.len());
+
+        for mint in &config.mints {
+            println!("  - {} ({})", mint.name, mint.mint_url);
+        }
+
+        println!("{}\n", "=".repeat(70));
+
+        let events = EventBus::with_sinks(events::CHANNEL_CAPACITY, self.event_sinks);
+        let liquidity = Arc::new(
+            LiquidityManager::new(config.mints.clone())
+                .await?
+                .with_events(events.clone()),
+        );
+        liquidity.set_retry_policy(self.retry_policy);
+        liquidity.set_selection_strategy(self.selection_strategy);
+        liquidity.set_require_dleq(self.require_dleq);
+        liquidity.circuit_breaker().set_config(self.circuit_breaker);
+        liquidity.set_timeouts(self.mint_timeouts);
+        #[cfg(feature = "faults")]
+        liquidity.set_faults(self.faults);
+        let swap_coordinator = Arc::new(
+            SwapCoordinator::new(config)
+                .with_events(events.clone())
+                .with_pricing(self.pricing)
+                .with_rng(self.rng.clone())
+                .with_clock(self.clock),
+        );
+
+        // Stay in maintenance mode across a restart
+        let maintenance = match &self.store {
+            Some(store) => store.maintenance_mode().await?,
+            None => false,
+        };
+        if maintenance {
+            warn!("Starting in maintenance mode, no new quotes until it is turned off");
+        }
+
+        Ok(Broker {
+            liquidity,
+            swap_coordinator,
+            store: self.store,
+            liquidity_store: self.liquidity_store,
+            ledger: self.ledger,
+            leases: self.leases,
+            price_feed: self.price_feed,
+            identity: Arc::new(
+                self.identity
+                    .unwrap_or_else(|| BrokerIdentity::new(self.rng.scalar())),
+            ),
+            events,
+            maintenance: AtomicBool::new(maintenance),
+            jobs: JobQueue::new(self.job_queue),
+        })
+    }
 }
 
-impl Broker {
-    /// Create a new broker instance
-    pub async fn new(config: BrokerConfig) -> Result<Self> {
-        println!("\n{}", "=".repeat(70));
-        println!("🤖 CHARLIE BROKER SERVICE");
-        println!("{}", "=".repeat(70));
-        println!("Fee Rate: {:.2}%", config.fee_rate * 100.0);
-        println!("Min Swap: {} sats", config.min_swap_amount);
-        println!("Max Swap: {} sats", config.max_swap_amount);
-        println!("Supported Mints: {}", config.mints.len());
+impl Broker {
+    /// Create a broker that keeps its state in memory
+    ///
+    /// Use [`Broker::builder`] to attach storage, an identity key or a price feed.
+    pub async fn new(config: BrokerConfig) -> Result<Self> {
+        Self::builder(config).build().await
+    }
+
+    pub fn builder(config: BrokerConfig) -> BrokerBuilder {
+        BrokerBuilder::new(config)
+    }
+
+    /// Receive quote, swap, balance and mint health events from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<BrokerEvent> {
+        self.events.subscribe()
+    }
+
+    /// The broker's long-term identity key
+    pub fn identity(&self) -> &BrokerIdentity {
+        &self.identity
+    }
+
+    /// Whether quotes between mints of different units can be priced
+    pub fn supports_cross_unit(&self) -> bool {
+        self.price_feed.is_some()
+    }
+
+    /// Whether the broker is refusing new quotes
+    pub fn in_maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::SeqCst)
+    }
+
+    /// Turn maintenance mode on or off
+    ///
+    /// In maintenance mode new quotes are refused with
+    /// [`BrokerError::Maintenance`], while quotes already issued can still be
+    /// accepted, completed and refunded, so the broker can be drained before
+    /// it is stopped. The setting is saved to the store, if any, and restored
+    /// on startup.
+    pub async fn set_maintenance(&self, enabled: bool) -> Result<()> {
+        if let Some(store) = &self.store {
+            store.set_maintenance_mode(enabled).await?;
+        }
+        self.maintenance.store(enabled, Ordering::SeqCst);
+
+        if enabled {
+            info!("Maintenance mode on, refusing new quotes");
+        } else {
+            info!("Maintenance mode off");
+        }
+        Ok(())
+    }
+
+    /// Initialize broker liquidity on all mints
+    ///
+    /// Only works against mints with a fake Lightning backend; use
+    /// [`Broker::deposit_ecash`] or a deposit invoice in production.
+    ///
+    /// In production, the broker would:
+    /// - Receive liquidity from users depositing ecash
+    /// - Mint via Lightning deposits
+    /// - Bootstrap with initial capital
+    pub async fn initialize(&self, amount_per_mint: u64) -> Result<()> {
+        self.liquidity.initialize_liquidity(amount_per_mint).await
+    }
+
+    /// Request a swap quote from the broker
+    #[instrument(name = "quote", skip_all, fields(quote_id = tracing::field::Empty))]
+    pub async fn request_quote(&self, request: SwapRequest) -> Result<SwapQuote> {
+        if self.in_maintenance() {
+            return Err(BrokerError::Maintenance);
+        }
+
+        self.check_blacklist(request.client_public_key.as_deref(), &Proofs::new())
+            .await?;
+        self.lease_mint(&request.to_mint).await?;
+
+        let client_id = request.client_id.as_deref().unwrap_or("anonymous");
+        println!("\n📨 Swap request from {}", client_id);
+        println!("   {} → {}", request.from_mint, request.to_mint);
+        println!("   Amount: {} sats\n", request.amount);
+
+        let exchange_rate = self
+            .exchange_rate(&request.from_mint, &request.to_mint)
+            .await?;
+        let volume = self.client_volume(request.client_public_key.as_deref()).await;
+
+        let mut quote = self
+            .swap_coordinator
+            .create_quote_at_rate(request, exchange_rate, volume, &self.liquidity)
+            .await?;
+        Span::current().record("quote_id", quote.quote_id.as_str());
+
+        // Commit to the terms with the long-term key, so a client can prove what it was offered
+        quote.quote_signature = Some(self.identity.sign_quote(&quote));
+
+        // Persist the quote's private keys so the swap survives a restart
+        if let (Some(store), Some(secrets)) = (
+            &self.store,
+            self.swap_coordinator.quote_secrets(&quote.quote_id).await,
+        ) {
+            store
+                .save_quote_keys(&QuoteKeys {
+                    quote_id: quote.quote_id.clone(),
+                    broker_swap_key: secrets.broker_swap_key.clone(),
+                    adaptor_secret: secrets.adaptor_secret.clone(),
+                    refund_at: None,
+                    locked_proofs: None,
+                    encrypted_signature: None,
+                    client_signature: None,
+                    created_at: Utc::now().to_rfc3339(),
+                })
+                .await?;
+        }
+
+        Ok(quote)
+    }
+
+    /// Take or renew this instance's lease on quoting out of `mint_url`
+    ///
+    /// Fails if another instance sharing the database holds it. Without
+    /// leases every mint is this instance's to quote out of.
+    async fn lease_mint(&self, mint_url: &str) -> Result<()> {
+        let Some(leases) = &self.leases else {
+            return Ok(());
+        };
+        // Unknown mints are rejected when the quote is validated
+        if self.swap_coordinator.config().unit_of(mint_url).is_none() {
+            return Ok(());
+        }
+
+        let lease = leases.acquire(&mint_lease(mint_url)).await?;
+        if lease.holder != leases.holder() {
+            return Err(BrokerError::MintLeased {
+                mint_url: mint_url.to_string(),
+                holder: lease.holder,
+            });
+        }
+        Ok(())
+    }
+
+    /// Stop serving quotes out of `mint_url` once another instance took its lease
+    ///
+    /// Pending quotes paying out of the mint are expired, releasing their
+    /// reservations: the new holder reserves that liquidity now, and accepts
+    /// are refused by [`Broker::accept_quote`] anyway. Accepted quotes keep
+    /// running, as their outputs are locked already. Returns how many quotes
+    /// were expired.
+    pub async fn mint_lease_lost(&self, mint_url: &str) -> usize {
+        let pending: Vec<String> = self
+            .swap_coordinator
+            .list_quotes()
+            .await
+            .into_iter()
+            .filter(|q| q.status == SwapStatus::Pending && q.to_mint == mint_url)
+            .map(|q| q.quote_id)
+            .collect();
+
+        let mut expired = 0;
+        for quote_id in pending {
+            if let Err(e) = self
+                .swap_coordinator
+                .expire_quote(&quote_id, &self.liquidity)
+                .await
+            {
+                warn!("Could not expire quote {}: {}", quote_id, e);
+                continue;
+            }
+            expired += 1;
+            if let Some(store) = &self.store {
+                let reason = Some("Mint lease lost to another instance".to_string());
+                if let Err(e) = store.close_quote(&quote_id, SwapStatus::Expired, reason).await {
+                    warn!("Failed to record expiry of quote {}: {}", quote_id, e);
+                }
+            }
+        }
+
+        if expired > 0 {
+            info!("Expired {} quotes paying out of {} after losing its lease", expired, mint_url);
+        }
+        expired
+    }
+
+    /// Whether this instance looks after a stored quote: it does if it holds
+    /// the lease on the mint the quote pays out of
+    async fn owns_quote(&self, record: &QuoteRecord) -> Result<bool> {
+        match &self.leases {
+            Some(leases) => leases.hold(&mint_lease(&record.target_mint)).await,
+            None => Ok(true),
+        }
+    }
+
+    /// Rate for a swap between two mints, or `None` if they share a unit
+    ///
+    /// The configured spread is taken off the feed's rate.
+    pub(crate) async fn exchange_rate(
+        &self,
+        from_mint: &str,
+        to_mint: &str,
+    ) -> Result<Option<f64>> {
+        let config = self.swap_coordinator.config();
+
+        // Unknown mints are rejected when the quote is validated
+        let (Some(from_unit), Some(to_unit)) = (config.unit_of(from_mint), config.unit_of(to_mint))
+        else {
+            return Ok(None);
+        };
+        if from_unit == to_unit {
+            return Ok(None);
+        }
+
+        let feed = self.price_feed.as_ref().ok_or_else(|| {
+            BrokerError::InvalidSwapRequest(format!(
+                "No price feed configured for {} to {} swaps",
+                from_unit, to_unit
+            ))
+        })?;
+
+        let rate = price::exchange_rate(feed.as_ref(), &from_unit, &to_unit).await?;
+        let rate = rate * (1.0 - config.price_spread);
+        info!(
+            "Exchange rate {:.6} {}/{} (via {})",
+            rate,
+            to_unit,
+            from_unit,
+            feed.name()
+        );
+
+        Ok(Some(rate))
+    }
+
+    /// Check with the mint that a client's proofs are neither spent nor pending
+    pub async fn check_proofs_unspent(&self, mint_url: &str, proofs: &Proofs) -> Result<()> {
+        self.liquidity.check_unspent(mint_url, proofs).await
+    }
+
+    /// Refuse a banned client public key, or proofs of which any is banned
+    ///
+    /// Without a store nothing is banned.
+    pub async fn check_blacklist(
+        &self,
+        client_pubkey: Option<&[u8]>,
+        proofs: &Proofs,
+    ) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+
+        let checks = [
+            (
+                BlacklistKind::Pubkey,
+                client_pubkey.map(hex::encode).into_iter().collect(),
+            ),
+            (BlacklistKind::Proof, blacklist::proof_ys(proofs)?),
+        ];
+        for (kind, values) in checks {
+            if let Some(entry) = store.find_blacklisted(kind, &values).await? {
+                warn!(
+                    "Refusing blacklisted {} {} ({})",
+                    kind,
+                    entry.value,
+                    entry.reason.as_deref().unwrap_or("no reason given")
+                );
+                return Err(BrokerError::Blacklisted {
+                    kind,
+                    value: entry.value,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// The broker's encrypted witness signatures for the outputs of a
+    /// client-first quote, as handed out at accept; empty for other quotes
+    pub async fn output_signatures(
+        &self,
+        quote_id: &str,
+        proofs: &Proofs,
+    ) -> Result<Vec<EncryptedSignature>> {
+        self.swap_coordinator
+            .output_signatures(quote_id, proofs)
+            .await
+    }
+
+    /// Unix time after which the broker can refund an accepted quote's
+    /// locked outputs, so the client has until then to redeem them
+    pub async fn refund_at(&self, quote_id: &str) -> Result<Option<u64>> {
+        self.swap_coordinator.refund_at(quote_id).await
+    }
+
+    /// Accept a quote and prepare the broker's side of the swap
+    ///
+    /// Returns the P2PK locked tokens that the broker creates for the client,
+    /// together with the broker's encrypted adaptor signature. The outputs
+    /// are stored before they are returned; a client accepting again after
+    /// an accept it never heard back about gets the same ones.
+    #[instrument(name = "quote", skip_all, fields(quote_id = %quote_id))]
+    pub async fn accept_quote(&self, quote_id: &str, client_pubkey: &[u8]) -> Result<PreparedSwap> {
+        println!("\n✅ Client accepted quote {}", quote_id);
+
+        if let Some(prepared) = self
+            .swap_coordinator
+            .prepared_swap(quote_id, client_pubkey)
+            .await
+        {
+            info!("Handing out the locked outputs of quote {} again", quote_id);
+            self.persist_locked_outputs(quote_id, &prepared).await?;
+            return Ok(prepared);
+        }
+
+        let quote = self
+            .swap_coordinator
+            .get_quote(quote_id)
+            .await
+            .ok_or_else(|| BrokerError::QuoteNotFound(quote_id.to_string()))?;
+        // The lease may have gone to another instance since the quote was made
+        self.lease_mint(&quote.to_mint).await?;
+        let prepared = self
+            .run_job(
+                quote_id,
+                &[&quote.to_mint],
+                self.swap_coordinator.prepare_swap(quote_id, client_pubkey, &self.liquidity),
+            )
+            .await?;
+
+        if let Some(quote) = self.swap_coordinator.get_quote(quote_id).await {
+            self.record_ledger_entry(
+                EntryKind::MintFee,
+                &quote.to_mint,
+                prepared.mint_fee,
+                Some(quote_id),
+            )
+            .await;
+        }
+
+        self.persist_locked_outputs(quote_id, &prepared).await?;
+
+        Ok(prepared)
+    }
+
+    /// Keep the outputs of an accepted quote where a restart finds them,
+    /// whether or not the client hears back: they are handed out again or
+    /// refunded
+    ///
+    /// Fails if they can't be stored, as a quote restored without its refund
+    /// locktime is never refunded; accepting again retries the write.
+    async fn persist_locked_outputs(&self, quote_id: &str, prepared: &PreparedSwap) -> Result<()> {
+        let (Some(store), Some(refund_at)) = (
+            &self.store,
+            self.swap_coordinator
+                .quote_secrets(quote_id)
+                .await
+                .and_then(|s| s.refund_at),
+        ) else {
+            return Ok(());
+        };
+        let locked_proofs = serde_json::to_string(&prepared.proofs)?;
+        store
+            .record_locked_outputs(
+                quote_id,
+                refund_at as i64,
+                &locked_proofs,
+                &prepared.encrypted_signature_hex(),
+            )
+            .await
+    }
+
+    /// Settle quotes left open by an unclean shutdown
+    ///
+    /// Called once at startup, before `restore_quotes`. Pending quotes past
+    /// their expiry are marked expired. For every accepted quote the mints are
+    /// asked about the client's proofs and the broker's locked outputs, and
+    /// the quote is marked completed, failed or expired as
+    /// [`reconcile`](crate::reconcile::reconcile) decides. Quotes still in
+    /// flight or due a refund stay accepted, and quotes whose mints can't be
+    /// reached are left for the next start.
+    pub async fn reconcile_quotes(&self) -> Result<ReconcileReport> {
+        let mut report = ReconcileReport::default();
+        let Some(store) = &self.store else {
+            return Ok(report);
+        };
+
+        let expired = store.expire_stale_quotes().await?;
+        for quote_id in &expired {
+            store.delete_quote_keys(quote_id).await?;
+        }
+        report.expired = expired.len();
+
+        for record in store.list_open_quotes().await? {
+            if record.status != SwapStatus::Accepted || !self.owns_quote(&record).await? {
+                continue;
+            }
+
+            let swap = store.get_swap_by_quote(&record.id).await?;
+            let keys = store.get_quote_keys(&record.id).await?;
+            let locked_proofs = keys.as_ref().and_then(|keys| keys.locked_proofs.as_deref());
+            let (source, target) = match self
+                .swap_proof_states(&record, swap.as_ref(), locked_proofs)
+                .await
+            {
+                Ok(states) => states,
+                Err(e) => {
+                    warn!("Could not reconcile quote {}: {}", record.id, e);
+                    report.skipped.push(record.id);
+                    continue;
+                }
+            };
+            let now = self.swap_coordinator.clock().unix_now();
+            let refund_due = keys
+                .as_ref()
+                .and_then(|keys| keys.refund_at)
+                .is_some_and(|at| at as u64 <= now);
+
+            let mut outcome = reconcile(source.as_deref(), target.as_deref(), refund_due);
+            // The client's claim spends a client-first swap's outputs too; once
+            // restored, the claim watcher tells it from a refund by the witnesses
+            if outcome == Reconciliation::Refunded
+                && record.claim_order == ClaimOrder::ClientFirst
+            {
+                outcome = Reconciliation::InFlight;
+            }
+            match outcome {
+                Reconciliation::Completed => {
+                    let adaptor_secret = keys
+                        .filter(|keys| !keys.adaptor_secret.is_empty())
+                        .map(|keys| hex::encode(&keys.adaptor_secret));
+                    store
+                        .complete_quote(&record.id, None, adaptor_secret.as_deref())
+                        .await?;
+                }
+                Reconciliation::Failed => {
+                    store
+                        .close_quote(
+                            &record.id,
+                            SwapStatus::Failed,
+                            Some("Broker restarted before the swap was recorded".to_string()),
+                        )
+                        .await?;
+                }
+                Reconciliation::Refunded => {
+                    store
+                        .close_quote(
+                            &record.id,
+                            SwapStatus::Expired,
+                            Some("Broker outputs refunded after locktime".to_string()),
+                        )
+                        .await?;
+                }
+                Reconciliation::RefundPending | Reconciliation::InFlight => {}
+            }
+
+            info!("Reconciled accepted quote {}: {}", record.id, outcome);
+            report.outcomes.push((record.id, outcome));
+        }
+
+        info!(
+            "Reconciliation: {} expired, {} completed, {} failed, {} refunded, {} awaiting refund, {} skipped",
+            report.expired,
+            report.count(Reconciliation::Completed),
+            report.count(Reconciliation::Failed),
+            report.count(Reconciliation::Refunded),
+            report.count(Reconciliation::RefundPending),
+            report.skipped.len()
+        );
+
+        Ok(report)
+    }
+
+    /// States of the client's proofs and the broker's locked outputs of a swap
+    ///
+    /// The outputs are taken from the swap record, or from `locked_proofs`
+    /// stored with the quote's keys when the record lacks them; without a
+    /// swap record there are no client proofs. Both are `None` when no locked
+    /// outputs were recorded.
+    async fn swap_proof_states(
+        &self,
+        record: &QuoteRecord,
+        swap: Option<&SwapRecord>,
+        locked_proofs: Option<&str>,
+    ) -> Result<(Option<Vec<State>>, Option<Vec<State>>)> {
+        let Some(target_proofs) = swap
+            .and_then(|swap| swap.target_proofs.as_deref())
+            .or(locked_proofs)
+        else {
+            return Ok((None, None));
+        };
+        let target_proofs: Proofs = serde_json::from_str(target_proofs)?;
+        let source = match swap {
+            Some(swap) => {
+                let source_proofs = self
+                    .parse_proofs(&record.source_mint, &swap.source_proofs)
+                    .await?;
+                Some(
+                    self.liquidity
+                        .proof_states(&record.source_mint, &source_proofs)
+                        .await?,
+                )
+            }
+            None => None,
+        };
+        let target = self
+            .liquidity
+            .proof_states(&record.target_mint, &target_proofs)
+            .await?;
+
+        Ok((source, Some(target)))
+    }
+
+    /// Reload pending and accepted quotes from the database
+    ///
+    /// Called once at startup, before serving requests. Pending quotes that
+    /// already expired are skipped; accepted quotes are always restored so
+    /// they can still complete or be refunded. So are pending quotes the
+    /// broker locked outputs for without the accept being recorded, which are
+    /// restored as accepted. With leases, quotes paying out of a mint another
+    /// instance holds are left to it. Quotes whose records are missing keys
+    /// or malformed are logged and skipped. Returns the number restored.
+    pub async fn restore_quotes(&self) -> Result<usize> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+
+        let mut restored = 0;
+        let mut leased = 0;
+        let mut skipped = 0;
+
+        for record in store.list_open_quotes().await? {
+            if !self.owns_quote(&record).await? {
+                leased += 1;
+                continue;
+            }
+
+            let Some(keys) = store.get_quote_keys(&record.id).await? else {
+                warn!("No stored keys for quote {}, cannot restore it", record.id);
+                skipped += 1;
+                continue;
+            };
+            // The swap record has the outputs once the accept is recorded,
+            // the quote's keys from the moment they are locked
+            let swap = store.get_swap_by_quote(&record.id).await?;
+
+            match self.restore_quote(&record, &keys, swap.as_ref()).await {
+                Ok(true) => restored += 1,
+                Ok(false) => {}
+                Err(e) => {
+                    warn!("Could not restore quote {}: {}", record.id, e);
+                    skipped += 1;
+                }
+            }
+        }
+
+        info!("Restored {} open quotes from the database", restored);
+        if leased > 0 {
+            info!("Left {} open quotes to the instances holding their mints", leased);
+        }
+        if skipped > 0 {
+            warn!("Skipped {} open quotes that could not be restored", skipped);
+        }
+
+        Ok(restored)
+    }
+
+    /// Restore one stored quote into the coordinator, returning whether it
+    /// was: pending quotes that already expired are not
+    async fn restore_quote(
+        &self,
+        record: &QuoteRecord,
+        keys: &QuoteKeys,
+        swap: Option<&SwapRecord>,
+    ) -> Result<bool> {
+        // Outputs locked for an accept the client may never have heard back about
+        let undelivered = record.status == SwapStatus::Pending && keys.locked_proofs.is_some();
+        let status = if undelivered {
+            SwapStatus::Accepted
+        } else {
+            record.status
+        };
+        let mut quote = quote_from_record(record, &self.swap_coordinator.config())?;
+        quote.status = status;
+
+        if status == SwapStatus::Pending
+            && quote.expires_at.is_some_and(|at| at <= self.swap_coordinator.clock().now())
+        {
+            return Ok(false);
+        }
+
+        let secrets = QuoteSecrets {
+            broker_swap_key: keys.broker_swap_key.clone(),
+            adaptor_secret: keys.adaptor_secret.clone(),
+            refund_at: keys.refund_at.map(|at| at as u64),
+        };
+
+        let (client_pubkey, encrypted_signature, locked_proofs) = if status == SwapStatus::Accepted
+        {
+            let (encrypted_signature, locked_proofs) = match swap {
+                Some(swap) => (
+                    swap.encrypted_signature.as_deref(),
+                    swap.target_proofs.as_deref(),
+                ),
+                None => (
+                    keys.encrypted_signature.as_deref(),
+                    keys.locked_proofs.as_deref(),
+                ),
+            };
+            let client_pubkey = record
+                .user_pubkey
+                .as_deref()
+                .map(hex::decode)
+                .transpose()
+                .map_err(|e| BrokerError::Database(format!("Invalid user_pubkey: {}", e)))?;
+            let encrypted_signature = encrypted_signature.map(decode_signature_hex).transpose()?;
+            let locked_proofs = locked_proofs.map(serde_json::from_str::<Proofs>).transpose()?;
+            (client_pubkey, encrypted_signature, locked_proofs)
+        } else {
+            (None, None, None)
+        };
+        let client_signature = keys
+            .client_signature
+            .as_deref()
+            .map(decode_signature_hex)
+            .transpose()?;
+
+        // Hold the output again; the quote still works if liquidity is short
+        let quote_id = quote.quote_id.clone();
+        if let Err(e) = self
+            .liquidity
+            .reserve(&quote.to_mint, &quote_id, quote.output_amount)
+            .await
+        {
+            warn!(
+                "Could not reserve liquidity for restored quote {}: {}",
+                quote_id, e
+            );
+        } else if status == SwapStatus::Accepted {
+            self.liquidity.lock(&quote_id).await;
+        }
+
+        if let Err(e) = self
+            .swap_coordinator
+            .restore_quote(
+                quote,
+                &secrets,
+                client_pubkey,
+                encrypted_signature,
+                locked_proofs,
+                client_signature,
+            )
+            .await
+        {
+            self.liquidity.release(&quote_id).await;
+            return Err(e);
+        }
+        Ok(true)
+    }
+
+    /// Complete a swap after client provides their tokens with witness
+    ///
+    /// `client_signature` is the client's adaptor signature over the swap
+    /// transcript, encrypted under the quote's adaptor point. Returns the
+    /// decrypted client signature. Once it verifies the broker owes the
+    /// client the swap, so it is stored before the claim: a claim that fails
+    /// is retried (see [`Broker::retry_claims`]) and the outputs are no
+    /// longer refunded.
+    #[instrument(name = "quote", skip_all, fields(quote_id = %quote_id))]
+    pub async fn complete_swap(
+        &self,
+        quote_id: &str,
+        client_tokens: Proofs,
+        client_signature: &EncryptedSignature,
+    ) -> Result<Signature> {
+        let quote = self
+            .swap_coordinator
+            .get_quote(quote_id)
+            .await
+            .ok_or_else(|| BrokerError::QuoteNotFound(quote_id.to_string()))?;
+
+        self.swap_coordinator
+            .verify_completion(quote_id, client_signature)
+            .await?;
+        if let Some(store) = &self.store {
+            let signature = hex::encode(encode_encrypted_signature(client_signature));
+            store.record_client_signature(quote_id, &signature).await?;
+        }
+        let (signature, amount_received) = self
+            .run_job(
+                quote_id,
+                &[&quote.from_mint],
+                self.swap_coordinator.complete_swap(
+                    quote_id,
+                    client_tokens,
+                    client_signature,
+                    &self.liquidity,
+                ),
+            )
+            .await?;
+
+        if let Some(quote) = self.swap_coordinator.get_quote(quote_id).await {
+            self.record_swap_ledger(&quote, amount_received).await;
+            self.record_client_volume(&quote).await;
+        }
+
+        Ok(signature)
+    }
+
+    /// Run a swap step on the job queue, recording its status on the quote
+    ///
+    /// Fails with [`BrokerError::QueueFull`] without running `job` if the
+    /// queue has no room.
+    async fn run_job<T>(
+        &self,
+        quote_id: &str,
+        mints: &[&str],
+        job: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let ticket = self.jobs.enqueue().inspect_err(|e| warn!("{}", e))?;
+        self.record_job_status(quote_id, JobStatus::Queued).await;
+        let _permit = self.jobs.start(ticket, mints).await;
+
+        self.record_job_status(quote_id, JobStatus::Running).await;
+        let result = job.await;
+        let status = if result.is_ok() {
+            JobStatus::Done
+        } else {
+            JobStatus::Failed
+        };
+        self.record_job_status(quote_id, status).await;
+        result
+    }
+
+    async fn record_job_status(&self, quote_id: &str, status: JobStatus) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.set_job_status(quote_id, status).await {
+                warn!("Failed to record job status of quote {}: {}", quote_id, e);
+            }
+        }
+    }
+
+    /// Volume a client completed over the volume discount window
+    ///
+    /// Zero without a store, without a client key, or when there are no
+    /// volume discounts to qualify for.
+    async fn client_volume(&self, client_pubkey: Option<&[u8]>) -> u64 {
+        let (Some(store), Some(pubkey)) = (&self.store, client_pubkey) else {
+            return 0;
+        };
+        if self.swap_coordinator.config().fees.volume_discounts.is_empty() {
+            return 0;
+        }
+
+        let since = Utc::now() - chrono::Duration::days(VOLUME_WINDOW_DAYS);
+        store
+            .client_volume(&hex::encode(pubkey), since)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to look up client volume: {}", e);
+                0
+            })
+    }
+
+    /// Count a completed swap towards its client's volume discount
+    async fn record_client_volume(&self, quote: &SwapQuote) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let Some(pubkey) = self.swap_coordinator.client_pubkey(&quote.quote_id).await else {
+            return;
+        };
+
+        if let Err(e) = store
+            .record_client_volume(&hex::encode(pubkey), quote.input_amount, Utc::now())
+            .await
+        {
+            warn!("Failed to record volume of quote {}: {}", quote.quote_id, e);
+        }
+    }
+
+    /// Record the fee, spread and source mint fee of a completed swap
+    async fn record_swap_ledger(&self, quote: &SwapQuote, amount_received: u64) {
+        let quote_id = Some(quote.quote_id.as_str());
+        self.record_ledger_entry(EntryKind::SwapFee, &quote.from_mint, quote.fee, quote_id)
+            .await;
+        self.record_ledger_entry(
+            EntryKind::MintFee,
+            &quote.from_mint,
+            quote.input_amount.saturating_sub(amount_received),
+            quote_id,
+        )
+        .await;
+        if quote.exchange_rate.is_some() {
+            let spread_rate = self.swap_coordinator.config().price_spread;
+            let spread = accounting::realized_spread(quote.output_amount, spread_rate);
+            self.record_ledger_entry(EntryKind::Spread, &quote.to_mint, spread, quote_id)
+                .await;
+        }
+    }
+
+    /// Get the adaptor secret of a completed swap
+    ///
+    /// Swaps completed before a restart are no longer held in memory; their
+    /// secret comes from the stored quote keys.
+    #[instrument(name = "quote", skip_all, fields(quote_id = %quote_id))]
+    pub async fn revealed_adaptor_secret(&self, quote_id: &str) -> Result<Vec<u8>> {
+        match self
+            .swap_coordinator
+            .revealed_adaptor_secret(quote_id)
+            .await
+        {
+            Err(BrokerError::QuoteNotFound(_)) => {}
+            result => return result,
+        }
+
+        let Some(store) = &self.store else {
+            return Err(BrokerError::QuoteNotFound(quote_id.to_string()));
+        };
+        let record = store
+            .get_quote(quote_id)
+            .await?
+            .ok_or_else(|| BrokerError::QuoteNotFound(quote_id.to_string()))?;
+        if record.status != SwapStatus::Completed {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Quote {} is not completed",
+                quote_id
+            )));
+        }
+
+        let keys = store.get_quote_keys(quote_id).await?.ok_or_else(|| {
+            BrokerError::Database(format!("No stored keys for quote {}", quote_id))
+        })?;
+        if !keys.adaptor_secret.is_empty() {
+            return Ok(keys.adaptor_secret.clone());
+        }
+
+        // A client-first swap's secret came from the client's claim
+        store
+            .get_swap_by_quote(quote_id)
+            .await?
+            .and_then(|swap| swap.adaptor_secret)
+            .and_then(|secret| hex::decode(secret).ok())
+            .ok_or_else(|| {
+                BrokerError::Database(format!("No adaptor secret recorded for quote {}", quote_id))
+            })
+    }
+
+    /// Get the balance held on a single mint
+    pub async fn get_mint_balance(&self, mint_url: &str) -> u64 {
+        self.liquidity.get_balance(mint_url).await
+    }
+
+    /// Health checks and circuit breaker state of every configured mint
+    pub fn get_mint_health(&self) -> Vec<MintHealthStatus> {
+        let health = self.liquidity.mint_health();
+
+        self.get_config()
+            .mints
+            .iter()
+            .map(|mint| MintHealthStatus {
+                mint_url: mint.mint_url.clone(),
+                name: mint.name.clone(),
+                health: health.get(&mint.mint_url).cloned().unwrap_or_default(),
+                circuit: self.liquidity.circuit_breaker().status(&mint.mint_url),
+            })
+            .collect()
+    }
+
+    /// How many configured mints have a wallet and can take part in a swap
+    pub fn mint_readiness(&self) -> MintReadiness {
+        let mints = self.get_config().mints;
+        let loaded: Vec<&MintConfig> = mints
+            .iter()
+            .filter(|mint| self.liquidity.get_wallet(&mint.mint_url).is_ok())
+            .collect();
+        let healthy = loaded
+            .iter()
+            .filter(|mint| {
+                self.liquidity.is_healthy(&mint.mint_url)
+                    && self.liquidity.circuit_breaker().status(&mint.mint_url).state
+                        != CircuitState::Open
+            })
+            .count();
+
+        MintReadiness {
+            configured: mints.len(),
+            wallets_loaded: loaded.len(),
+            healthy,
+        }
+    }
+
+    /// Get current liquidity status
+    pub async fn get_liquidity_status(&self) -> LiquidityStatus {
+        let liquidity: HashMap<String, MintLiquidity> = self
+            .liquidity
+            .get_all_liquidity()
+            .await
+            .into_iter()
+            .map(|liq| (liq.mint_url.clone(), liq))
+            .collect();
+
+        let mut mint_balances = Vec::new();
+
+        for mint in &self.get_config().mints {
+            let liq = liquidity.get(&mint.mint_url);
+            mint_balances.push(MintBalance {
+                mint_url: mint.mint_url.clone(),
+                name: mint.name.clone(),
+                balance: liq.map_or(0, |l| l.balance),
+                reserved: liq.map_or(0, MintLiquidity::reserved),
+                locked: liq.map_or(0, MintLiquidity::locked),
+                available: liq.map_or(0, MintLiquidity::available),
+                quarantined: liq.map_or(0, MintLiquidity::quarantined),
+                healthy: self.liquidity.is_healthy(&mint.mint_url),
+            });
+        }
+
+        let total_balance: u64 = mint_balances.iter().map(|mb| mb.balance).sum();
+
+        LiquidityStatus {
+            mints: mint_balances,
+            total_balance,
+        }
+    }
+
+    /// Get broker configuration
+    pub fn get_config(&self) -> BrokerConfig {
+        (*self.swap_coordinator.config()).clone()
+    }
+
+    /// Apply a reloaded configuration
+    ///
+    /// Fee rate, swap limits and quote expiry take effect for new quotes
+    /// immediately. New mints get a wallet; removed mints are dropped unless
+    /// they still have open quotes or funds, in which case they are kept
+    /// until the next reload. Mints added or removed through
+    /// [`Broker::add_mint`] and [`Broker::remove_mint`] are applied on top of
+    /// `config`. The new configuration is swapped in as a whole, so quotes
+    /// never see a mix of old and new settings.
+    pub async fn reload_config(&self, mut config: BrokerConfig) {
+        let current = self.swap_coordinator.config();
+
+        if let Some(store) = &self.store {
+            match store.list_mint_changes().await {
+                Ok(changes) => apply_mint_changes(&mut config.mints, &changes),
+                Err(e) => {
+                    warn!("Keeping the current mints, registry unavailable: {}", e);
+                    config.mints = current.mints.clone();
+                }
+            }
+        }
+        let mut mints = config.mints.clone();
+
+        for mint in &config.mints {
+            if current.mints.iter().any(|m| m.mint_url == mint.mint_url) {
+                continue;
+            }
+            if let Err(e) = self.liquidity.add_mint(mint).await {
+                warn!("Not adding mint {}: {}", mint.mint_url, e);
+                mints.retain(|m| m.mint_url != mint.mint_url);
+            }
+        }
+
+        for mint in &current.mints {
+            if config.mints.iter().any(|m| m.mint_url == mint.mint_url) {
+                continue;
+            }
+            if let Err(e) = self.detach_mint(&mint.mint_url).await {
+                warn!(
+                    "Keeping mint {} until the next reload: {}",
+                    mint.mint_url, e
+                );
+                mints.push(mint.clone());
+            }
+        }
+
+        let updated = BrokerConfig { mints, ..config };
+
+        info!(
+            "Configuration reloaded: fee rate {:.2}%, limits {}-{} sats, quote expiry {}s",
+            updated.fee_rate * 100.0,
+            updated.min_swap_amount,
+            updated.max_swap_amount,
+            updated.quote_expiry_seconds
+        );
+
+        self.swap_coordinator.update_config(updated);
+    }
+
+    /// Change the fee rate applied to new quotes
+    pub fn set_fee_rate(&self, fee_rate: f64) -> Result<()> {
+        if !(0.0..1.0).contains(&fee_rate) {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Fee rate {} must be in [0, 1)",
+                fee_rate
+            )));
+        }
+
+        self.swap_coordinator.modify_config(|config| BrokerConfig {
+            fee_rate,
+            ..config.clone()
+        });
+        info!("Fee rate set to {:.2}%", fee_rate * 100.0);
+
+        Ok(())
+    }
+
+    /// Start supporting a new mint
+    ///
+    /// Creates the mint's wallet and starts tracking its liquidity. With a
+    /// store the mint is registered there, so it is still supported after a
+    /// restart or reload even if it isn't in the configuration.
+    pub async fn add_mint(&self, mint: MintConfig) -> Result<()> {
+        MintUrl::from_str(mint.url()).map_err(|e| {
+            BrokerError::InvalidSwapRequest(format!("Invalid mint URL {}: {}", mint.mint_url, e))
+        })?;
+        if mint.url() != mint.mint_url && mint_key(mint.url(), &mint.unit) != mint.mint_url {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Mint key {} does not match unit {}",
+                mint.mint_url, mint.unit
+            )));
+        }
+        self.liquidity.add_mint(&mint).await?;
+
+        if let Some(store) = &self.store {
+            if let Err(e) = store.record_mint_change(&MintChange::added(mint.clone())).await {
+                self.liquidity.remove_mint(&mint.mint_url).await?;
+                return Err(e);
+            }
+        }
+
+        self.swap_coordinator.modify_config(|config| {
+            let mut config = config.clone();
+            config.mints.push(mint.clone());
+            config
+        });
+
+        Ok(())
+    }
+
+    /// Stop supporting a mint
+    ///
+    /// Fails while open quotes use the mint or the broker holds funds there.
+    /// With a store the removal is registered there and outlives a restart.
+    pub async fn remove_mint(&self, mint_url: &str) -> Result<()> {
+        let mint = self
+            .get_config()
+            .mints
+            .into_iter()
+            .find(|m| m.mint_url == mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+
+        self.detach_mint(mint_url).await?;
+
+        if let Some(store) = &self.store {
+            if let Err(e) = store.record_mint_change(&MintChange::removed(mint.clone())).await {
+                self.liquidity.add_mint(&mint).await?;
+                return Err(e);
+            }
+        }
+
+        self.swap_coordinator.modify_config(|config| {
+            let mut config = config.clone();
+            config.mints.retain(|m| m.mint_url != mint_url);
+            config
+        });
+
+        Ok(())
+    }
+
+    /// Drop a mint's wallet if no open quote or funds depend on it
+    async fn detach_mint(&self, mint_url: &str) -> Result<()> {
+        let in_use = self
+            .swap_coordinator
+            .list_quotes()
+            .await
+            .into_iter()
+            .any(|q| {
+                matches!(q.status, SwapStatus::Pending | SwapStatus::Accepted)
+                    && (q.from_mint == mint_url || q.to_mint == mint_url)
+            });
+        if in_use {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Mint {} has open quotes",
+                mint_url
+            )));
+        }
+
+        self.liquidity.remove_mint(mint_url).await
+    }
+
+    /// Expire a pending quote before its expiry time
+    #[instrument(name = "quote", skip_all, fields(quote_id = %quote_id))]
+    pub async fn expire_quote(&self, quote_id: &str) -> Result<()> {
+        self.swap_coordinator
+            .expire_quote(quote_id, &self.liquidity)
+            .await?;
+
+        if let Some(store) = &self.store {
+            store
+                .close_quote(
+                    quote_id,
+                    SwapStatus::Expired,
+                    Some("Expired by operator".to_string()),
+                )
+                .await?;
+        }
+
+        info!("Quote {} expired by operator", quote_id);
+
+        Ok(())
+    }
+
+    /// Push a pending quote's expiry out by `quote_extension_seconds`, once
+    ///
+    /// The quote is signed again, as its expiry is part of the signed terms.
+    #[instrument(name = "quote", skip_all, fields(quote_id = %quote_id))]
+    pub async fn extend_quote(&self, quote_id: &str) -> Result<SwapQuote> {
+        let extension = self.swap_coordinator.config().quote_extension_seconds;
+        if extension == 0 {
+            return Err(BrokerError::InvalidSwapRequest(
+                "Quote extension is disabled".to_string(),
+            ));
+        }
+
+        let mut quote = self
+            .swap_coordinator
+            .extend_quote(quote_id, Duration::from_secs(extension), &self.liquidity)
+            .await?;
+
+        if let Some(store) = &self.store {
+            let expires_at = quote
+                .expires_at
+                .unwrap_or_else(|| self.swap_coordinator.clock().now());
+            if !store.extend_quote(quote_id, expires_at.into()).await? {
+                return Err(BrokerError::InvalidSwapRequest(format!(
+                    "Quote {} can no longer be extended",
+                    quote_id
+                )));
+            }
+        }
+
+        quote.quote_signature = Some(self.identity.sign_quote(&quote));
+        info!("Quote {} extended by {}s", quote_id, extension);
+
+        Ok(quote)
+    }
+
+    /// Request a Lightning invoice that adds liquidity on a mint once paid
+    ///
+    /// Returns the mint quote; pass it to [`Broker::claim_deposit`] to wait
+    /// for payment and mint the ecash.
+    pub async fn create_deposit_invoice(&self, mint_url: &str, amount: u64) -> Result<MintQuote> {
+        let wallet = self.liquidity.get_wallet(mint_url)?;
+
+        self.liquidity
+            .mint_call(mint_url, MintOp::MintQuote, "Failed to create mint quote", || {
+                wallet.mint_quote(Amount::from(amount), None)
+            })
+            .await
+    }
+
+    /// Wait for a deposit invoice to be paid and add the minted ecash to liquidity
+    pub async fn claim_deposit(&self, mint_url: &str, quote: MintQuote) -> Result<u64> {
+        let proofs = self
+            .liquidity
+            .mint_when_paid(
+                mint_url,
+                &quote.id,
+                DEPOSIT_PAYMENT_TIMEOUT,
+                "Failed to mint deposit",
+            )
+            .await?;
+
+        let amount: u64 = proofs.iter().map(|p| u64::from(p.amount)).sum();
+        self.liquidity.add_proofs(mint_url, proofs).await?;
+        self.record_liquidity_event(mint_url, "deposit", amount, None)
+            .await;
+
+        Ok(amount)
+    }
+
+    /// Add liquidity on a mint from a cashu token
+    ///
+    /// The token's proofs are swapped at the mint for fresh ones the broker
+    /// alone knows, then added to liquidity. Returns the amount deposited.
+    pub async fn deposit_ecash(&self, mint_url: &str, token: &str) -> Result<u64> {
+        let token = Token::from_str(token.trim())
+            .map_err(|e| BrokerError::InvalidSwapRequest(format!("Invalid token: {}", e)))?;
+        let proofs = self.token_proofs(mint_url, &token).await?;
+        let wallet = self.liquidity.get_wallet(mint_url)?;
+
+        let amount = self.liquidity.amount_after_fees(mint_url, &proofs).await?;
+
+        // Swap so the depositor can no longer spend the proofs
+        let new_proofs = self
+            .liquidity
+            .mint_call(mint_url, MintOp::Swap, "Failed to swap deposited tokens", || {
+                wallet.swap(
+                    Some(Amount::from(amount)),
+                    SplitTarget::default(),
+                    proofs.clone(),
+                    None,
+                    false,
+                )
+            })
+            .await?
+            .unwrap_or_default();
+
+        let amount: u64 = new_proofs.iter().map(|p| u64::from(p.amount)).sum();
+        self.liquidity.add_proofs(mint_url, new_proofs).await?;
+        self.record_liquidity_event(mint_url, "deposit", amount, None)
+            .await;
+
+        info!("Deposited {} sats of ecash on {}", amount, mint_url);
+
+        Ok(amount)
+    }
+
+    /// Parse proofs a client sent for a mint, either as a `cashuA`/`cashuB`
+    /// token or as a JSON array of proofs
+    pub async fn parse_proofs(&self, mint_url: &str, input: &str) -> Result<Proofs> {
+        let input = input.trim();
+        if !input.starts_with("cashu") {
+            return serde_json::from_str(input).map_err(|e| {
+                BrokerError::InvalidSwapRequest(format!("Invalid proofs JSON: {}", e))
+            });
+        }
+
+        let token = Token::from_str(input)
+            .map_err(|e| BrokerError::InvalidSwapRequest(format!("Invalid token: {}", e)))?;
+        self.token_proofs(mint_url, &token).await
+    }
+
+    /// Proofs of a token, which must be for `mint_url`
+    async fn token_proofs(&self, mint_url: &str, token: &Token) -> Result<Proofs> {
+        let expected = MintUrl::from_str(mint_url_of(mint_url))
+            .map_err(|e| BrokerError::Cdk(format!("Invalid mint URL: {:?}", e)))?;
+        let token_mint = token
+            .mint_url()
+            .map_err(|e| BrokerError::InvalidSwapRequest(format!("Invalid token: {}", e)))?;
+        if token_mint != expected {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Token is for {}, not {}",
+                token_mint, mint_url
+            )));
+        }
+
+        self.liquidity.token_proofs(mint_url, token).await
+    }
+
+    /// Encode proofs on a mint as a `cashuB` token in the mint's unit
+    pub fn encode_token(
+        &self,
+        mint_url: &str,
+        proofs: Proofs,
+        memo: Option<String>,
+    ) -> Result<String> {
+        let mint = MintUrl::from_str(mint_url_of(mint_url))
+            .map_err(|e| BrokerError::Cdk(format!("Invalid mint URL: {:?}", e)))?;
+        let unit = self
+            .get_config()
+            .mints
+            .iter()
+            .find(|m| m.mint_url == mint_url)
+            .map(|m| CurrencyUnit::from_str(&m.unit))
+            .transpose()
+            .map_err(|e| BrokerError::Cdk(format!("Invalid unit: {:?}", e)))?
+            .unwrap_or(CurrencyUnit::Sat);
+
+        Ok(Token::new(mint, proofs, memo, unit).to_string())
+    }
+
+    /// Withdraw unreserved liquidity from a mint as a cashu token
+    ///
+    /// Proofs are handed out whole, so the token may exceed `amount` by less
+    /// than the largest selected denomination. Returns the token and its value.
+    pub async fn withdraw_ecash(&self, mint_url: &str, amount: u64) -> Result<(String, u64)> {
+        let proofs = self.liquidity.take_proofs(mint_url, amount).await?;
+        let withdrawn: u64 = proofs.iter().map(|p| u64::from(p.amount)).sum();
+
+        let token = match self.encode_token(mint_url, proofs.clone(), None) {
+            Ok(token) => token,
+            Err(e) => {
+                self.liquidity.add_proofs(mint_url, proofs).await?;
+                return Err(e);
+            }
+        };
+
+        self.record_liquidity_event(mint_url, "withdrawal", withdrawn, None)
+            .await;
+
+        Ok((token, withdrawn))
+    }
+
+    /// Pay a BOLT11 invoice from a mint's liquidity
+    ///
+    /// Selects proofs covering the invoice plus the mint's fee reserve and
+    /// melts them; change comes back into liquidity.
+    pub async fn withdraw_lightning(
+        &self,
+        mint_url: &str,
+        invoice: &str,
+    ) -> Result<InvoicePayment> {
+        let payment = self.liquidity.pay_invoice(mint_url, invoice).await?;
+
+        self.record_liquidity_event(mint_url, "withdrawal", payment.spent, None)
+            .await;
+        self.record_ledger_entry(EntryKind::LightningFee, mint_url, payment.fee_paid, None)
+            .await;
+
+        info!(
+            "Paid {} sat invoice from {} (fee {})",
+            payment.amount, mint_url, payment.fee_paid
+        );
+
+        Ok(payment)
+    }
+
+    /// Quarantined proofs on every mint, or only on `mint_url`
+    pub async fn quarantined_proofs(
+        &self,
+        mint_url: Option<&str>,
+    ) -> Vec<(String, QuarantinedProof)> {
+        self.liquidity
+            .get_all_liquidity()
+            .await
+            .into_iter()
+            .filter(|liq| mint_url.map_or(true, |url| liq.mint_url == url))
+            .flat_map(|liq| {
+                let mint_url = liq.mint_url;
+                liq.quarantine
+                    .into_iter()
+                    .map(move |entry| (mint_url.clone(), entry))
+            })
+            .collect()
+    }
+
+    /// Retry quarantined proofs on a mint, all of them or those with Ys in `ys`
+    pub async fn retry_quarantined(
+        &self,
+        mint_url: &str,
+        ys: &[PublicKey],
+    ) -> Result<QuarantineRetry> {
+        let outcome = self
+            .liquidity
+            .retry_quarantined(mint_url, |q| selected(q, ys))
+            .await?;
+        if outcome.released > 0 {
+            self.record_liquidity_event(mint_url, "deposit", outcome.released, None)
+                .await;
+        }
+        Ok(outcome)
+    }
 
-        for mint in &config.mints {
-            println!("  - {} ({})", mint.name, mint.mint_url);
+    /// Drop quarantined proofs on a mint for good, returning their amount
+    ///
+    /// Unlike a retry, `ys` must name the proofs.
+    pub async fn discard_quarantined(&self, mint_url: &str, ys: &[PublicKey]) -> Result<u64> {
+        if ys.is_empty() {
+            return Err(BrokerError::InvalidSwapRequest(
+                "Name the quarantined proofs to discard".to_string(),
+            ));
         }
+        self.liquidity
+            .discard_quarantined(mint_url, |q| selected(q, ys))
+            .await
+    }
 
-        println!("{}\n", "=".repeat(70));
+    /// Move liquidity over Lightning to mints that fell below the threshold
+    ///
+    /// Does nothing when `rebalance_threshold` is 0. Returns the transfers made.
+    /// Transfers whose invoice was paid but whose ecash couldn't be minted
+    /// yet are recorded as pending mints, and minted on a later round.
+    pub async fn rebalance(&self) -> Vec<RebalanceTransfer> {
+        self.mint_pending().await;
 
-        let liquidity = Arc::new(LiquidityManager::new(config.mints.clone()).await?);
-        let swap_coordinator = Arc::new(SwapCoordinator::new(config.clone()));
+        let config = self.get_config();
+        if config.rebalance_threshold == 0 {
+            return Vec::new();
+        }
 
-        Ok(Self {
-            config,
-            liquidity,
-            swap_coordinator,
-        })
+        let transfers = self
+            .liquidity
+            .rebalance(config.rebalance_threshold, config.rebalance_target)
+            .await;
+
+        for transfer in &transfers {
+            self.record_liquidity_event(&transfer.from_mint, "withdrawal", transfer.spent, None)
+                .await;
+            match &transfer.unminted {
+                Some(quote) => self.save_pending_mint(transfer, quote).await,
+                None => {
+                    self.record_liquidity_event(&transfer.to_mint, "deposit", transfer.amount, None)
+                        .await
+                }
+            }
+            self.record_ledger_entry(
+                EntryKind::LightningFee,
+                &transfer.from_mint,
+                transfer.fee_paid,
+                None,
+            )
+            .await;
+        }
+
+        transfers
     }
 
-    /// Initialize broker liquidity on all mints
+    /// Remember a paid mint quote of `transfer` to mint on a later round
     ///
-    /// In production, the broker would:
-    /// - Receive liquidity from users depositing ecash
-    /// - Mint via Lightning deposits
-    /// - Bootstrap with initial capital
-    pub async fn initialize(&self, amount_per_mint: u64) -> Result<()> {
-        self.liquidity.initialize_liquidity(amount_per_mint).await
+    /// The wallet's copy of the quote is kept with it, as a restarted
+    /// broker's wallets no longer know it.
+    async fn save_pending_mint(&self, transfer: &RebalanceTransfer, quote: &MintQuote) {
+        let Some(store) = &self.liquidity_store else {
+            warn!(
+                "No database to keep paid mint quote {} on {} for later",
+                quote.id, transfer.to_mint
+            );
+            return;
+        };
+
+        let saved = match serde_json::to_string(quote) {
+            Ok(json) => {
+                let pending = PendingMint {
+                    quote_id: quote.id.clone(),
+                    mint_url: transfer.to_mint.clone(),
+                    amount: transfer.spent.saturating_sub(transfer.fee_paid) as i64,
+                    from_mint: transfer.from_mint.clone(),
+                    quote: Some(json),
+                    created_at: Utc::now().to_rfc3339(),
+                };
+                store.save_pending_mint(&pending).await
+            }
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = saved {
+            warn!(
+                "Failed to record paid mint quote {} on {}: {}",
+                quote.id, transfer.to_mint, e
+            );
+        }
     }
 
-    /// Request a swap quote from the broker
-    pub async fn request_quote(&self, request: SwapRequest) -> Result<SwapQuote> {
-        let client_id = request.client_id.as_deref().unwrap_or("anonymous");
-        println!("\n📨 Swap request from {}", client_id);
-        println!("   {} → {}", request.from_mint, request.to_mint);
-        println!("   Amount: {} sats\n", request.amount);
+    /// Mint the paid mint quotes earlier rebalancing rounds left pending
+    ///
+    /// Quotes that still can't be minted stay pending for the next round.
+    /// Returns the amount minted.
+    pub async fn mint_pending(&self) -> u64 {
+        let Some(store) = &self.liquidity_store else {
+            return 0;
+        };
+        let pending = match store.list_pending_mints().await {
+            Ok(pending) => pending,
+            Err(e) => {
+                warn!("Failed to load pending mints: {}", e);
+                return 0;
+            }
+        };
 
-        self.swap_coordinator
-            .create_quote(request, &self.liquidity)
-            .await
+        let mut minted = 0;
+        for pending in pending {
+            if let Err(e) = self.import_pending_mint(&pending).await {
+                warn!("Could not hand mint quote {} back to its wallet: {}", pending.quote_id, e);
+                continue;
+            }
+            let amount = match self
+                .liquidity
+                .mint_paid(&pending.mint_url, &pending.quote_id, PENDING_MINT_TIMEOUT)
+                .await
+            {
+                Ok(amount) => amount,
+                Err(e) => {
+                    warn!(
+                        "Paid mint quote {} on {} still not minted: {}",
+                        pending.quote_id, pending.mint_url, e
+                    );
+                    continue;
+                }
+            };
+
+            info!(
+                "Minted {} sats of pending quote {} on {}",
+                amount, pending.quote_id, pending.mint_url
+            );
+            minted += amount;
+            self.record_liquidity_event(&pending.mint_url, "deposit", amount, None)
+                .await;
+            if let Err(e) = store.delete_pending_mint(&pending.quote_id).await {
+                warn!("Failed to clear pending mint {}: {}", pending.quote_id, e);
+            }
+        }
+
+        minted
     }
 
-    /// Accept a quote and prepare the broker's side of the swap
+    /// Give a pending mint's quote back to the wallet of its mint, which
+    /// doesn't know it after a restart
+    async fn import_pending_mint(&self, pending: &PendingMint) -> Result<()> {
+        let Some(quote) = &pending.quote else {
+            return Ok(());
+        };
+        let quote: MintQuote = serde_json::from_str(quote)?;
+        self.liquidity.import_mint_quote(&pending.mint_url, quote).await
+    }
+
+    /// Record a liquidity event when a database is attached
+    /// Swap fragmented proofs for fewer, larger ones on mints holding too many
     ///
-    /// Returns the P2PK locked tokens that the broker creates for the client
-    pub async fn accept_quote(&self, quote_id: &str, client_pubkey: &[u8]) -> Result<Proofs> {
-        println!("\n✅ Client accepted quote {}", quote_id);
+    /// Does nothing when `consolidation_threshold` is 0. The mints' input
+    /// fees for the swaps are recorded as withdrawals.
+    pub async fn consolidate_proofs(&self) -> Vec<Consolidation> {
+        let threshold = self.get_config().consolidation_threshold;
+        if threshold == 0 {
+            return Vec::new();
+        }
 
-        self.swap_coordinator
-            .prepare_swap(quote_id, client_pubkey, &self.liquidity)
-            .await
-    }
+        let consolidations = self.liquidity.consolidate_all(threshold).await;
 
-    /// Complete a swap after client provides their tokens with witness
-    pub async fn complete_swap(&self, quote_id: &str, client_tokens: Proofs) -> Result<()> {
-        self.swap_coordinator
-            .complete_swap(quote_id, client_tokens, &self.liquidity)
-            .await
+        for consolidation in &consolidations {
+            if consolidation.fee_paid > 0 {
+                self.record_liquidity_event(
+                    &consolidation.mint_url,
+                    "withdrawal",
+                    consolidation.fee_paid,
+                    None,
+                )
+                .await;
+                self.record_ledger_entry(
+                    EntryKind::MintFee,
+                    &consolidation.mint_url,
+                    consolidation.fee_paid,
+                    None,
+                )
+                .await;
+            }
+        }
+
+        consolidations
     }
 
-    /// Get current liquidity status
-    pub async fn get_liquidity_status(&self) -> LiquidityStatus {
-        let mut mint_balances = Vec::new();
+    async fn record_liquidity_event(
+        &self,
+        mint_url: &str,
+        event_type: &str,
+        amount: u64,
+        quote_id: Option<&str>,
+    ) {
+        let Some(store) = &self.liquidity_store else {
+            return;
+        };
 
-        for mint in &self.config.mints {
-            let balance = self.liquidity.get_balance(&mint.mint_url).await;
-            mint_balances.push(MintBalance {
-                mint_url: mint.mint_url.clone(),
-                name: mint.name.clone(),
-                balance,
-            });
+        let event = LiquidityEvent {
+            id: None,
+            mint_url: mint_url.to_string(),
+            event_type: event_type.to_string(),
+            amount: amount as i64,
+            balance_after: self.liquidity.get_balance(mint_url).await as i64,
+            quote_id: quote_id.map(str::to_string),
+            created_at: Utc::now().to_rfc3339(),
+        };
+
+        if let Err(e) = store.record_liquidity_event(&event).await {
+            warn!("Failed to record {} on {}: {}", event_type, mint_url, e);
         }
+    }
 
-        let total_balance: u64 = mint_balances.iter().map(|mb| mb.balance).sum();
+    /// Record a ledger entry when a ledger store is attached
+    ///
+    /// Zero amounts are skipped. The unit is the one configured for the mint.
+    async fn record_ledger_entry(
+        &self,
+        kind: EntryKind,
+        mint_url: &str,
+        amount: u64,
+        quote_id: Option<&str>,
+    ) {
+        let Some(ledger) = &self.ledger else {
+            return;
+        };
+        if amount == 0 {
+            return;
+        }
 
-        LiquidityStatus {
-            mints: mint_balances,
-            total_balance,
+        let config = self.swap_coordinator.config();
+        let unit = config
+            .mints
+            .iter()
+            .find(|m| m.mint_url == mint_url)
+            .map_or("sat", |m| m.unit.as_str());
+        let entry = LedgerEntry::new(kind, mint_url, unit, amount, quote_id);
+
+        if let Err(e) = ledger.record_ledger_entry(&entry).await {
+            warn!("Failed to record {} of {} on {}: {}", kind, amount, mint_url, e);
         }
     }
 
-    /// Get broker configuration
-    pub fn get_config(&self) -> &BrokerConfig {
-        &self.config
+    /// Flush in-memory liquidity and quote state to the stores
+    ///
+    /// Writes a snapshot of every mint's balance and proofs, and brings the
+    /// stored status of each in-memory quote in line with the coordinator.
+    pub async fn flush_state(&self) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        if let Some(liquidity_store) = &self.liquidity_store {
+            for liq in self.liquidity.get_all_liquidity().await {
+                let snapshot = LiquiditySnapshot {
+                    mint_url: liq.mint_url.clone(),
+                    balance: liq.balance as i64,
+                    proof_count: liq.proofs.len() as i64,
+                    proofs: serde_json::to_string(&liq.proofs)?,
+                    updated_at: now.clone(),
+                };
+                liquidity_store.save_liquidity_snapshot(&snapshot).await?;
+            }
+        }
+
+        let quotes = self.swap_coordinator.list_quotes().await;
+        if let Some(store) = &self.store {
+            for quote in &quotes {
+                if let Some(record) = store.get_quote(&quote.quote_id).await? {
+                    // An accept is recorded along with its swap record, which
+                    // one the client never heard back about doesn't have yet
+                    let undelivered = record.status == SwapStatus::Pending
+                        && quote.status == SwapStatus::Accepted;
+                    if record.status != quote.status && !undelivered {
+                        store
+                            .update_quote_status(&quote.quote_id, quote.status, None)
+                            .await?;
+                    }
+                }
+            }
+        }
+
+        info!("State flushed: {} quotes checkpointed", quotes.len());
+
+        Ok(())
     }
 
     /// Print broker status
@@ -123,29 +1991,570 @@ impl Broker {
         println!("{}\n", "=".repeat(70));
     }
 
+    /// Reclaim broker outputs from accepted swaps whose refund locktime passed
+    ///
+    /// Refunded quotes are marked expired in the database when one is attached.
+    pub async fn reclaim_expired_locks(&self) -> Vec<String> {
+        let refunded = self
+            .swap_coordinator
+            .reclaim_expired_locks(&self.liquidity)
+            .await;
+
+        if let Some(store) = &self.store {
+            for quote_id in &refunded {
+                if let Err(e) = store
+                    .close_quote(
+                        quote_id,
+                        SwapStatus::Expired,
+                        Some("Broker outputs refunded after locktime".to_string()),
+                    )
+                    .await
+                {
+                    warn!("Failed to record refund of quote {}: {}", quote_id, e);
+                }
+            }
+        }
+
+        refunded
+    }
+
+    /// Retry claiming the client's proofs of swaps whose complete failed at the mint
+    ///
+    /// The client handed over a valid signature, so the broker owes it the
+    /// swap: the claim is retried until it goes through rather than the
+    /// outputs refunded. Completed quotes are marked so in the database, where
+    /// the client finds the adaptor secret (`GET /quote/:id/secret`). Returns
+    /// the IDs of the quotes completed.
+    pub async fn retry_claims(&self) -> Vec<String> {
+        let mut completed = Vec::new();
+
+        for (quote_id, client_signature, proofs) in self.swap_coordinator.pending_claims().await {
+            let proofs = match proofs {
+                Some(proofs) => proofs,
+                None => match self.stored_source_proofs(&quote_id).await {
+                    Ok(proofs) => proofs,
+                    Err(e) => {
+                        warn!("Could not load the proofs of quote {} to claim: {}", quote_id, e);
+                        continue;
+                    }
+                },
+            };
+            let signature = match self.complete_swap(&quote_id, proofs, &client_signature).await {
+                Ok(signature) => signature,
+                Err(e) => {
+                    warn!("Retrying the claim of quote {} failed: {}", quote_id, e);
+                    continue;
+                }
+            };
+            info!("Claim of quote {} went through on retry", quote_id);
+
+            if let Some(store) = &self.store {
+                let adaptor_secret = self
+                    .revealed_adaptor_secret(&quote_id)
+                    .await
+                    .map(hex::encode)
+                    .ok();
+                if let Err(e) = store
+                    .complete_quote(
+                        &quote_id,
+                        Some(&hex::encode(signature.to_bytes())),
+                        adaptor_secret.as_deref(),
+                    )
+                    .await
+                {
+                    warn!("Failed to record completion of quote {}: {}", quote_id, e);
+                }
+            }
+            completed.push(quote_id);
+        }
+
+        completed
+    }
+
+    /// Claim the client's proofs of client-first swaps whose outputs the client redeemed
+    ///
+    /// The target mints are asked (NUT-07) for the witnesses of the broker's
+    /// outputs, which reveal the adaptor secret once the client redeemed them.
+    /// Outputs spent without revealing it were refunded by the broker, and
+    /// their quote is closed as expired. Returns the IDs of completed quotes.
+    pub async fn watch_client_claims(&self) -> Vec<String> {
+        let mut completed = Vec::new();
+        for (quote_id, mint_url, outputs) in self.swap_coordinator.awaiting_client_claims().await {
+            if self.check_client_claim(&quote_id, &mint_url, outputs).await {
+                completed.push(quote_id);
+            }
+        }
+        completed
+    }
+
+    /// Check a client-first swap's outputs on its target mint and settle it
+    ///
+    /// Returns whether the swap completed.
+    async fn check_client_claim(&self, quote_id: &str, mint_url: &str, outputs: Proofs) -> bool {
+        let states = match self.liquidity.proof_witnesses(mint_url, &outputs).await {
+            Ok(states) => states,
+            Err(e) => {
+                warn!("Could not check the outputs of quote {}: {}", quote_id, e);
+                return false;
+            }
+        };
+        let all_spent = states.iter().all(|(state, _)| *state == State::Spent);
+        let redeemed: Vec<(Proof, String)> = outputs
+            .into_iter()
+            .zip(states)
+            .filter(|(_, (state, _))| *state == State::Spent)
+            .filter_map(|(proof, (_, witness))| Some((proof, witness?)))
+            .collect();
+        if redeemed.is_empty() {
+            if all_spent {
+                warn!(
+                    "Outputs of quote {} are spent, but {} reports no witnesses",
+                    quote_id, mint_url
+                );
+            }
+            return false;
+        }
+
+        self.settle_client_claim(quote_id, &redeemed, all_spent).await
+    }
+
+    /// Claim the client's proofs of a client-first swap with the adaptor
+    /// secret revealed by its `redeemed` outputs
+    ///
+    /// If none reveals it and `all_spent`, the broker refunded the outputs
+    /// and the quote is closed as expired. Returns whether the swap completed.
+    async fn settle_client_claim(
+        &self,
+        quote_id: &str,
+        redeemed: &[(Proof, String)],
+        all_spent: bool,
+    ) -> bool {
+        let Some(quote) = self.swap_coordinator.get_quote(quote_id).await else {
+            return false;
+        };
+        let client_proofs = match self.stored_source_proofs(quote_id).await {
+            Ok(proofs) => proofs,
+            Err(e) => {
+                warn!("Could not load the proofs of quote {} to claim: {}", quote_id, e);
+                return false;
+            }
+        };
+        let claimed = self
+            .run_job(
+                quote_id,
+                &[&quote.from_mint],
+                self.swap_coordinator.claim_after_client(
+                    quote_id,
+                    redeemed,
+                    client_proofs,
+                    &self.liquidity,
+                ),
+            )
+            .await;
+
+        match claimed {
+            Ok(Some(amount)) => {
+                info!("Client redeemed the outputs of quote {}, claimed its proofs", quote_id);
+                if let Some(quote) = self.swap_coordinator.get_quote(quote_id).await {
+                    self.record_swap_ledger(&quote, amount).await;
+                    self.record_client_volume(&quote).await;
+                }
+                if let Some(store) = &self.store {
+                    let adaptor_secret = self
+                        .swap_coordinator
+                        .revealed_adaptor_secret(quote_id)
+                        .await
+                        .map(hex::encode)
+                        .ok();
+                    if let Err(e) = store
+                        .complete_quote(quote_id, None, adaptor_secret.as_deref())
+                        .await
+                    {
+                        warn!("Failed to record completion of quote {}: {}", quote_id, e);
+                    }
+                }
+                true
+            }
+            Ok(None) if all_spent => {
+                info!("Outputs of quote {} were refunded", quote_id);
+                if let Err(e) = self.close_refunded(quote_id).await {
+                    warn!("Failed to close refunded quote {}: {}", quote_id, e);
+                }
+                false
+            }
+            Ok(None) => false,
+            Err(e) => {
+                warn!("Claim of client-first quote {} failed: {}", quote_id, e);
+                false
+            }
+        }
+    }
+
+    /// Claim client-first swaps as soon as `mint_url` reports one of their
+    /// outputs spent (NUT-17), watching until `until`
+    ///
+    /// The witness comes with the notification when the mint includes it, and
+    /// is asked for (NUT-07) otherwise. Returns the IDs of completed quotes.
+    async fn watch_redeemed_outputs(
+        &self,
+        watch: &OutputWatch,
+        mint_url: &str,
+        until: std::time::Instant,
+    ) -> Result<Vec<String>> {
+        let mut spends = self
+            .liquidity
+            .subscribe_spends(mint_url, &watch.ys(mint_url))
+            .await?;
+        let mut completed: Vec<String> = Vec::new();
+
+        while let Ok(Some((y, witness))) =
+            tokio::time::timeout_at(until.into(), spends.next_spend()).await
+        {
+            let Some((quote_id, output)) = watch.output(mint_url, &y) else {
+                continue;
+            };
+            if completed.iter().any(|id| id == quote_id) {
+                continue;
+            }
+            let witness = match witness {
+                Some(witness) => Some(witness),
+                None => self
+                    .liquidity
+                    .proof_witnesses(mint_url, &vec![output.clone()])
+                    .await
+                    .ok()
+                    .and_then(|states| states.into_iter().next()?.1),
+            };
+            let Some(witness) = witness else {
+                continue;
+            };
+
+            // One output tells nothing of the others, so a refund is left to
+            // the full check
+            info!("Mint {} reports an output of quote {} spent", mint_url, quote_id);
+            if self
+                .settle_client_claim(quote_id, &[(output.clone(), witness)], false)
+                .await
+            {
+                completed.push(quote_id.to_string());
+            }
+        }
+
+        Ok(completed)
+    }
+
+    /// Mark a swap whose outputs the broker refunded as expired
+    async fn close_refunded(&self, quote_id: &str) -> Result<()> {
+        self.swap_coordinator
+            .close_refunded(quote_id, &self.liquidity)
+            .await?;
+        if let Some(store) = &self.store {
+            store
+                .close_quote(
+                    quote_id,
+                    SwapStatus::Expired,
+                    Some("Broker outputs refunded after locktime".to_string()),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Source proofs posted with a quote's accept, from its swap record
+    async fn stored_source_proofs(&self, quote_id: &str) -> Result<Proofs> {
+        let store = self
+            .store
+            .as_ref()
+            .ok_or_else(|| BrokerError::Database("No store to load the proofs from".to_string()))?;
+        let (Some(record), Some(swap)) = (
+            store.get_quote(quote_id).await?,
+            store.get_swap_by_quote(quote_id).await?,
+        ) else {
+            return Err(BrokerError::QuoteNotFound(quote_id.to_string()));
+        };
+        self.parse_proofs(&record.source_mint, &swap.source_proofs).await
+    }
+
+    /// Drop expired quotes from memory and mark them expired in the database
+    ///
+    /// Also forgets the stored keys of quotes that can no longer be executed.
+    /// Returns the number of quotes expired.
+    pub async fn sweep_expired_quotes(&self) -> Result<usize> {
+        let mut expired = self.swap_coordinator.expire_quotes(&self.liquidity).await;
+
+        // Quotes in the database are expired by one instance at a time
+        if let Some(store) = &self.store {
+            if leases::may_run(self.leases.as_ref(), QUOTE_SWEEP).await {
+                for quote_id in store.expire_stale_quotes().await? {
+                    if !expired.contains(&quote_id) {
+                        expired.push(quote_id);
+                    }
+                }
+            }
+
+            for quote_id in &expired {
+                store.delete_quote_keys(quote_id).await?;
+            }
+        }
+
+        if !expired.is_empty() {
+            info!("Expired {} stale quotes", expired.len());
+        }
+
+        Ok(expired.len())
+    }
+
     /// Run the broker service
     ///
-    /// TODO: Implement HTTP/gRPC API server
+    /// Drives the broker's background tasks: periodic status output, mint
+    /// health checks, refreshes of cached mint keysets and info, watches for
+    /// proofs spent at the mints, expiry of stale quotes, refunds of
+    /// unredeemed swap outputs, retries of claims that failed at complete,
+    /// Lightning rebalancing between mints, proof consolidation and, with
+    /// leases, dropping the quotes of mints whose lease was lost.
+    ///
     /// TODO: Integrate with Nostr for service announcements
     pub async fn run(&self) -> Result<()> {
         info!("Broker service is running...");
 
         // TODO: Phase 4 - Nostr Integration
         // - Announce broker service on Nostr
-        // - Listen for encrypted swap requests
-        // - Respond with quotes
+        // (swap requests over Nostr DMs are handled by `nostr::NostrListener`)
+
+        tokio::join!(
+            self.status_loop(),
+            self.health_loop(),
+            self.cache_refresh_loop(),
+            self.spent_proofs_loop(),
+            self.sweep_loop(),
+            self.refund_loop(),
+            self.claim_retry_loop(),
+            self.client_claim_loop(),
+            self.rebalance_loop(),
+            self.consolidation_loop(),
+            self.lease_loop()
+        );
 
-        // TODO: Phase 5 - Production API
-        // - HTTP/REST or gRPC endpoints
-        // - Database persistence
-        // - Metrics and monitoring
+        Ok(())
+    }
 
-        // For now, just keep running
+    /// Print broker status every minute
+    async fn status_loop(&self) {
         loop {
-            tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+            tokio::time::sleep(Duration::from_secs(60)).await;
             self.print_status().await;
         }
     }
+
+    /// Periodically ping every mint so quotes aren't issued into a mint that is down
+    async fn health_loop(&self) {
+        let mut interval = tokio::time::interval(MINT_HEALTH_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.liquidity.check_health().await;
+        }
+    }
+
+    /// Periodically refresh cached mint keysets and info, so swaps rarely wait on them,
+    /// and retry proofs quarantined for lack of keys
+    async fn cache_refresh_loop(&self) {
+        let mut interval = tokio::time::interval(MINT_CACHE_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.liquidity.refresh_cache().await;
+
+            // Keys fetched now may let proofs quarantined without them through
+            for mint in self.liquidity.get_all_liquidity().await {
+                let waiting_for_keys =
+                    |q: &QuarantinedProof| q.reason == QuarantineReason::KeysUnavailable;
+                if !mint.quarantine.iter().any(waiting_for_keys) {
+                    continue;
+                }
+                if let Err(e) = self
+                    .liquidity
+                    .retry_quarantined(&mint.mint_url, waiting_for_keys)
+                    .await
+                {
+                    warn!("Retrying quarantined proofs on {} failed: {}", mint.mint_url, e);
+                }
+            }
+        }
+    }
+
+    /// Drop proofs the mints report spent as soon as they do (NUT-17)
+    async fn spent_proofs_loop(&self) {
+        loop {
+            let until = std::time::Instant::now() + SPENT_PROOFS_WATCH_PERIOD;
+            let mints = self.liquidity.get_all_liquidity().await;
+            let watches = mints
+                .iter()
+                .map(|mint| self.liquidity.watch_spent_proofs(&mint.mint_url, until));
+            for (mint, result) in mints.iter().zip(futures::future::join_all(watches).await) {
+                match result {
+                    Ok(0) => {}
+                    Ok(dropped) => info!("Dropped {} spent proofs on {}", dropped, mint.mint_url),
+                    Err(e) => warn!("Watching proofs on {} failed: {}", mint.mint_url, e),
+                }
+            }
+            tokio::time::sleep_until(until.into()).await;
+        }
+    }
+
+    /// Periodically expire stale quotes
+    async fn sweep_loop(&self) {
+        let mut interval = tokio::time::interval(QUOTE_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.sweep_expired_quotes().await {
+                warn!("Quote sweep failed: {}", e);
+            }
+        }
+    }
+
+    /// Periodically rebalance liquidity between mints
+    async fn rebalance_loop(&self) {
+        let mut interval = tokio::time::interval(REBALANCE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.rebalance().await;
+        }
+    }
+
+    /// Stop serving quotes out of mints as their leases are lost
+    async fn lease_loop(&self) {
+        let Some(leases) = &self.leases else {
+            return;
+        };
+        let mut lost = leases.lost();
+        loop {
+            match lost.recv().await {
+                Ok(name) => {
+                    if let Some(mint_url) = leased_mint(&name) {
+                        self.mint_lease_lost(mint_url).await;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!("Missed {} lost leases", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Periodically consolidate fragmented proofs
+    async fn consolidation_loop(&self) {
+        let mut interval = tokio::time::interval(CONSOLIDATION_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.consolidate_proofs().await;
+        }
+    }
+
+    /// Periodically refund expired swap locks
+    async fn refund_loop(&self) {
+        let mut interval = tokio::time::interval(REFUND_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.reclaim_expired_locks().await;
+        }
+    }
+
+    /// Periodically retry claims that failed at complete
+    async fn claim_retry_loop(&self) {
+        let mut interval = tokio::time::interval(CLAIM_RETRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.retry_claims().await;
+        }
+    }
+
+    /// Claim client-first swaps as soon as clients redeem their outputs
+    ///
+    /// Each watch starts by checking every waiting swap, which catches spends
+    /// missed between watches and refunded outputs, then subscribes to the
+    /// outputs of the swaps still waiting.
+    async fn client_claim_loop(&self) {
+        loop {
+            let until = std::time::Instant::now() + CLIENT_CLAIM_WATCH_PERIOD;
+            self.watch_client_claims().await;
+
+            let watch = OutputWatch::new(self.swap_coordinator.awaiting_client_claims().await);
+            let mints: Vec<&str> = watch.mints().collect();
+            let watches = mints
+                .iter()
+                .map(|mint_url| self.watch_redeemed_outputs(&watch, mint_url, until));
+            for (mint_url, result) in mints.iter().zip(futures::future::join_all(watches).await) {
+                if let Err(e) = result {
+                    warn!("Watching swap outputs on {} failed: {}", mint_url, e);
+                }
+            }
+            tokio::time::sleep_until(until.into()).await;
+        }
+    }
+}
+
+/// Rebuild a quote from its database record, taking the units from `config`
+fn quote_from_record(record: &QuoteRecord, config: &BrokerConfig) -> Result<SwapQuote> {
+    let decode = |field: &str, value: &str| {
+        hex::decode(value).map_err(|e| BrokerError::Database(format!("Invalid {}: {}", field, e)))
+    };
+
+    let expires_at = DateTime::parse_from_rfc3339(&record.expires_at)
+        .map_err(|e| BrokerError::Database(format!("Invalid expires_at: {}", e)))?;
+    let expires_in = (expires_at.with_timezone(&Utc) - Utc::now())
+        .num_seconds()
+        .max(0) as u64;
+
+    Ok(SwapQuote {
+        quote_id: record.id.clone(),
+        from_mint: record.source_mint.clone(),
+        to_mint: record.target_mint.clone(),
+        from_unit: config.unit_of(&record.source_mint).unwrap_or_default(),
+        to_unit: config.unit_of(&record.target_mint).unwrap_or_default(),
+        input_amount: record.amount_in as u64,
+        output_amount: record.amount_out as u64,
+        fee: record.fee as u64,
+        fee_rate: record.fee_rate,
+        mint_fee: record.mint_fee as u64,
+        exchange_rate: record.exchange_rate,
+        broker_public_key: decode("broker_pubkey", &record.broker_pubkey)?,
+        adaptor_point: decode("adaptor_point", &record.adaptor_point)?,
+        tweaked_pubkey: Some(decode("tweaked_pubkey", &record.tweaked_pubkey)?),
+        dleq_proof: None,
+        expires_in,
+        expiry: expires_at.timestamp().max(0) as u64,
+        expires_at: Some(SystemTime::from(expires_at)),
+        quote_signature: None,
+        claim_order: record.claim_order,
+        extended: record.extended_at.is_some(),
+        status: record.status,
+    })
+}
+
+/// Decode an encrypted adaptor signature stored as hex
+fn decode_signature_hex(hex_sig: &str) -> Result<EncryptedSignature> {
+    hex::decode(hex_sig)
+        .map_err(|e| BrokerError::AdaptorSignature(e.to_string()))
+        .and_then(|bytes| decode_encrypted_signature(&bytes))
+}
+
+/// Whether a quarantined proof is among `ys`, or any when `ys` is empty
+fn selected(entry: &QuarantinedProof, ys: &[PublicKey]) -> bool {
+    ys.is_empty() || entry.y().is_some_and(|y| ys.contains(&y))
+}
+
+/// Apply mints added or removed at runtime to the configured ones
+///
+/// A registered mint that is already configured keeps its configuration.
+fn apply_mint_changes(mints: &mut Vec<MintConfig>, changes: &[MintChange]) {
+    for change in changes {
+        let url = &change.mint.mint_url;
+        if change.removed {
+            mints.retain(|m| &m.mint_url != url);
+        } else if !mints.iter().any(|m| &m.mint_url == url) {
+            mints.push(change.mint.clone());
+        }
+    }
 }
 
 /// Liquidity status summary
@@ -161,6 +2570,28 @@ pub struct MintBalance {
     pub mint_url: String,
     pub name: String,
     pub balance: u64,
+    pub reserved: u64,    // Held for pending quotes
+    pub locked: u64,      // Committed to accepted swaps that haven't completed
+    pub available: u64,   // Free for new quotes and withdrawals
+    pub quarantined: u64, // Held out of the balance, see `crate::quarantine`
+    pub healthy: bool,
+}
+
+/// Health of a specific mint
+#[derive(Debug, Clone)]
+pub struct MintHealthStatus {
+    pub mint_url: String,
+    pub name: String,
+    pub health: MintHealth,
+    pub circuit: CircuitStatus,
+}
+
+/// Configured mints the broker can swap on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MintReadiness {
+    pub configured: usize,
+    pub wallets_loaded: usize,
+    pub healthy: usize, // Loaded, passing health checks and with a circuit that isn't open
 }
 
 #[cfg(test)]
@@ -191,4 +2622,171 @@ mod tests {
         assert_eq!(status.mints.len(), 2);
         assert_eq!(status.total_balance, 0);
     }
+
+    #[tokio::test]
+    async fn test_token_encoding() {
+        let mint_url = "http://localhost:3338";
+        let broker = Broker::new(BrokerConfig {
+            mints: vec![MintConfig {
+                mint_url: mint_url.to_string(),
+                name: "Mint A".to_string(),
+                unit: "sat".to_string(),
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let encoded = broker
+            .encode_token(mint_url, Proofs::new(), Some("Atomic swap q1".to_string()))
+            .unwrap();
+        assert!(encoded.starts_with("cashuB"));
+
+        let token = Token::from_str(&encoded).unwrap();
+        assert_eq!(
+            token.mint_url().unwrap(),
+            MintUrl::from_str(mint_url).unwrap()
+        );
+        assert_eq!(token.memo().as_deref(), Some("Atomic swap q1"));
+
+        // Plain JSON needs no mint round trip
+        assert!(broker
+            .parse_proofs(mint_url, " [] ")
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            broker.parse_proofs(mint_url, "{not json").await,
+            Err(BrokerError::InvalidSwapRequest(_))
+        ));
+        assert!(matches!(
+            broker.parse_proofs(mint_url, "cashuBnot-a-token").await,
+            Err(BrokerError::InvalidSwapRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let mint_url = "http://localhost:3338";
+        let broker = Broker::new(BrokerConfig {
+            mints: vec![MintConfig {
+                mint_url: mint_url.to_string(),
+                name: "Mint A".to_string(),
+                unit: "sat".to_string(),
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        // Liquidity changes reach the broker's subscribers
+        let mut events = broker.subscribe();
+        broker
+            .liquidity
+            .add_proofs(mint_url, Proofs::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            events.recv().await.unwrap(),
+            BrokerEvent::BalanceChanged {
+                mint_url: mint_url.to_string(),
+                balance: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_mint_changes() {
+        let mint = |url: &str, name: &str| MintConfig {
+            mint_url: url.to_string(),
+            name: name.to_string(),
+            unit: "sat".to_string(),
+        };
+        let mut mints = vec![mint("http://mint-a.test", "A"), mint("http://mint-b.test", "B")];
+
+        apply_mint_changes(
+            &mut mints,
+            &[
+                MintChange::removed(mint("http://mint-a.test", "A")),
+                MintChange::added(mint("http://mint-c.test", "C")),
+                MintChange::added(mint("http://mint-b.test", "Renamed")),
+            ],
+        );
+
+        let names: Vec<&str> = mints.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["B", "C"]);
+    }
+
+    #[tokio::test]
+    async fn test_builder_stores() {
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Memory {
+            snapshots: Mutex<Vec<LiquiditySnapshot>>,
+            events: Mutex<Vec<BrokerEvent>>,
+        }
+
+        #[async_trait::async_trait]
+        impl LiquidityStore for Memory {
+            async fn record_liquidity_event(&self, _event: &LiquidityEvent) -> Result<()> {
+                Ok(())
+            }
+
+            async fn save_liquidity_snapshot(&self, snapshot: &LiquiditySnapshot) -> Result<()> {
+                self.snapshots.lock().unwrap().push(snapshot.clone());
+                Ok(())
+            }
+
+            async fn save_pending_mint(&self, _pending: &PendingMint) -> Result<()> {
+                Ok(())
+            }
+
+            async fn list_pending_mints(&self) -> Result<Vec<PendingMint>> {
+                Ok(Vec::new())
+            }
+
+            async fn delete_pending_mint(&self, _quote_id: &str) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        impl EventSink for Memory {
+            fn handle(&self, event: &BrokerEvent) {
+                self.events.lock().unwrap().push(event.clone());
+            }
+        }
+
+        let mint_url = "http://localhost:3338";
+        let memory = Arc::new(Memory::default());
+        let broker = Broker::builder(BrokerConfig {
+            mints: vec![MintConfig {
+                mint_url: mint_url.to_string(),
+                name: "Mint A".to_string(),
+                unit: "sat".to_string(),
+            }],
+            ..Default::default()
+        })
+        .liquidity_store(memory.clone())
+        .event_sink(memory.clone())
+        .build()
+        .await
+        .unwrap();
+
+        broker
+            .liquidity
+            .add_proofs(mint_url, Proofs::new())
+            .await
+            .unwrap();
+        assert!(matches!(
+            memory.events.lock().unwrap().as_slice(),
+            [BrokerEvent::BalanceChanged { balance: 0, .. }]
+        ));
+
+        // Without a quote store only liquidity is checkpointed
+        broker.flush_state().await.unwrap();
+        let snapshots = memory.snapshots.lock().unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].mint_url, mint_url);
+    }
 }
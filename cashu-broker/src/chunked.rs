@@ -0,0 +1,228 @@
+//! Swaps above a pair's maximum, run as a series of chunks
+//!
+//! A chunked swap splits an amount to pay that is larger than the pair's
+//! `max_swap_amount` into chunks of about equal size, none above the chunk
+//! amount asked for. Each chunk is an ordinary quote, accepted and completed
+//! on its own, so each is atomic: it either completes in full or leaves the
+//! client's proofs with the client. The broker only quotes the next chunk
+//! once the previous one completed, at the rate of the moment, so the client
+//! can stop after any chunk, keeping what it swapped so far. A chunk whose
+//! quote expired or failed is quoted again.
+
+use crate::db::{ChunkedSwapRecord, SwapChunk};
+use crate::types::SwapStatus;
+use serde::{Deserialize, Serialize};
+
+/// Most chunks a swap may be split into
+pub const MAX_CHUNKS: usize = 100;
+
+/// Split `amount` into chunks of at most `chunk_amount`, each at least
+/// `min_amount`
+pub fn plan(amount: u64, chunk_amount: u64, min_amount: u64) -> Result<Vec<u64>, String> {
+    if chunk_amount == 0 {
+        return Err("Chunk amount must be positive".to_string());
+    }
+    let count = amount.div_ceil(chunk_amount);
+    if count == 0 {
+        return Err("Amount must be positive".to_string());
+    }
+    if count > MAX_CHUNKS as u64 {
+        return Err(format!(
+            "Amount {} would take {} chunks of {}, more than {}",
+            amount, count, chunk_amount, MAX_CHUNKS
+        ));
+    }
+
+    // Even sizes keep the last chunk from falling below the minimum
+    let (size, rest) = (amount / count, amount % count);
+    if size < min_amount {
+        return Err(format!(
+            "Chunks of {} would be below the minimum swap amount {}",
+            size, min_amount
+        ));
+    }
+    Ok((0..count).map(|i| size + u64::from(i < rest)).collect())
+}
+
+/// Where a chunked swap stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkedStatus {
+    Active,    // Chunks remain to be swapped
+    Stopped,   // Stopped by the client before every chunk completed
+    Completed, // Every chunk completed
+}
+
+/// What comes after a chunked swap's latest chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NextChunk {
+    Quote(usize),       // Quote chunk with this index
+    InProgress(String), // Wait for the quote of the current chunk
+    Finished,           // Every chunk completed
+}
+
+/// The next step of a swap planned in `planned` chunks, given the quotes
+/// issued for them so far, in order
+pub fn next_chunk(planned: usize, chunks: &[SwapChunk]) -> NextChunk {
+    let Some(last) = chunks.last() else {
+        return NextChunk::Quote(0);
+    };
+    let index = last.index.max(0) as usize;
+    match last.status {
+        SwapStatus::Pending | SwapStatus::Accepted => NextChunk::InProgress(last.quote_id.clone()),
+        SwapStatus::Completed if index + 1 >= planned => NextChunk::Finished,
+        SwapStatus::Completed => NextChunk::Quote(index + 1),
+        SwapStatus::Expired | SwapStatus::Failed => NextChunk::Quote(index),
+    }
+}
+
+/// Progress of a chunked swap, as reported to its client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkedProgress {
+    pub id: String,
+    pub source_mint: String,
+    pub target_mint: String,
+    pub amount: u64, // Total the client pays
+    pub status: ChunkedStatus,
+    pub chunks: Vec<u64>, // Planned amount of each chunk
+    pub completed_chunks: usize,
+    pub amount_in: u64,         // Paid in completed chunks
+    pub amount_out: u64,        // Received from completed chunks
+    pub remaining: u64,         // Part of amount in chunks not completed yet
+    pub quotes: Vec<SwapChunk>, // Every quote issued for a chunk, in order
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_at: Option<String>,
+}
+
+impl ChunkedProgress {
+    pub fn new(swap: ChunkedSwapRecord, quotes: Vec<SwapChunk>) -> Self {
+        let completed: Vec<&SwapChunk> = quotes
+            .iter()
+            .filter(|chunk| chunk.status == SwapStatus::Completed)
+            .collect();
+        let done = |index: usize| completed.iter().any(|chunk| chunk.index == index as i64);
+        let remaining = swap
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !done(*index))
+            .map(|(_, amount)| amount)
+            .sum();
+        let status = match next_chunk(swap.chunks.len(), &quotes) {
+            NextChunk::Finished => ChunkedStatus::Completed,
+            _ if swap.stopped_at.is_some() => ChunkedStatus::Stopped,
+            _ => ChunkedStatus::Active,
+        };
+
+        Self {
+            completed_chunks: completed.len(),
+            amount_in: completed
+                .iter()
+                .map(|chunk| chunk.amount_in.max(0) as u64)
+                .sum(),
+            amount_out: completed
+                .iter()
+                .map(|chunk| chunk.amount_out.max(0) as u64)
+                .sum(),
+            remaining,
+            status,
+            id: swap.id,
+            source_mint: swap.source_mint,
+            target_mint: swap.target_mint,
+            amount: swap.amount.max(0) as u64,
+            chunks: swap.chunks,
+            created_at: swap.created_at,
+            stopped_at: swap.stopped_at,
+            quotes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(index: i64, status: SwapStatus) -> SwapChunk {
+        SwapChunk {
+            index,
+            quote_id: format!("quote-{}-{}", index, status),
+            status,
+            amount_in: 100,
+            amount_out: 99,
+        }
+    }
+
+    #[test]
+    fn test_plan() {
+        assert_eq!(plan(25_000, 10_000, 100), Ok(vec![8_334, 8_333, 8_333]));
+        assert_eq!(plan(20_000, 10_000, 100), Ok(vec![10_000, 10_000]));
+        assert_eq!(plan(500, 10_000, 100), Ok(vec![500]));
+        // An even split avoids a remainder below the minimum
+        assert_eq!(plan(10_050, 10_000, 100), Ok(vec![5_025, 5_025]));
+
+        assert!(plan(150, 100, 100).is_err());
+        assert!(plan(1_000, 0, 1).is_err());
+        assert!(plan(0, 100, 1).is_err());
+        assert!(plan(101 * 100, 100, 1).is_err());
+    }
+
+    #[test]
+    fn test_next_chunk() {
+        use SwapStatus::*;
+
+        assert_eq!(next_chunk(3, &[]), NextChunk::Quote(0));
+        let pending = [chunk(0, Completed), chunk(1, Pending)];
+        assert_eq!(
+            next_chunk(3, &pending),
+            NextChunk::InProgress("quote-1-pending".to_string())
+        );
+        assert_eq!(next_chunk(3, &[chunk(0, Completed)]), NextChunk::Quote(1));
+        // An expired chunk is quoted again
+        assert_eq!(
+            next_chunk(3, &[chunk(0, Completed), chunk(1, Expired)]),
+            NextChunk::Quote(1)
+        );
+        assert_eq!(
+            next_chunk(2, &[chunk(0, Completed), chunk(1, Completed)]),
+            NextChunk::Finished
+        );
+    }
+
+    #[test]
+    fn test_progress() {
+        use SwapStatus::*;
+
+        let swap = ChunkedSwapRecord {
+            id: "chunked-1".to_string(),
+            source_mint: "http://mint-a.test".to_string(),
+            target_mint: "http://mint-b.test".to_string(),
+            amount: 300,
+            chunks: vec![100, 100, 100],
+            user_pubkey: "02user1234".to_string(),
+            callback_url: None,
+            created_at: "2025-01-17T00:00:00+00:00".to_string(),
+            stopped_at: None,
+        };
+        let quotes = vec![chunk(0, Completed), chunk(1, Failed), chunk(1, Completed)];
+
+        let progress = ChunkedProgress::new(swap.clone(), quotes.clone());
+        assert_eq!(progress.status, ChunkedStatus::Active);
+        assert_eq!(progress.completed_chunks, 2);
+        assert_eq!((progress.amount_in, progress.amount_out), (200, 198));
+        assert_eq!(progress.remaining, 100);
+
+        let stopped = ChunkedSwapRecord {
+            stopped_at: Some("2025-01-17T00:10:00+00:00".to_string()),
+            ..swap
+        };
+        let progress = ChunkedProgress::new(stopped.clone(), quotes.clone());
+        assert_eq!(progress.status, ChunkedStatus::Stopped);
+
+        let mut all = quotes;
+        all.push(chunk(2, Completed));
+        let progress = ChunkedProgress::new(stopped, all);
+        assert_eq!(progress.status, ChunkedStatus::Completed);
+        assert_eq!(progress.remaining, 0);
+    }
+}
@@ -0,0 +1,253 @@
+//! Per-mint circuit breaker
+//!
+//! A mint that keeps failing would otherwise make every swap wait out its
+//! timeouts and retries. After `failure_threshold` failed calls in a row the
+//! circuit for that mint opens and calls fail fast with
+//! [`BrokerError::MintUnavailable`](crate::error::BrokerError::MintUnavailable).
+//! Once the cool-down has passed the circuit is half-open: the next call goes
+//! through as a trial, closing the circuit if it succeeds and reopening it for
+//! another cool-down if it fails.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// When circuits open and how long they stay open
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32, // Consecutive failures that open the circuit
+    pub cool_down: Duration,    // How long an open circuit rejects calls before a trial
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of one mint's circuit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,   // Calls go through
+    Open,     // Calls fail fast until the cool-down has passed
+    HalfOpen, // The next call is a trial
+}
+
+/// Snapshot of one mint's circuit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitStatus {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// Seconds until an open circuit allows a trial call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_seconds: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl Circuit {
+    fn state(&self, now: Instant, cool_down: Duration) -> CircuitState {
+        match self.opened_at {
+            None => CircuitState::Closed,
+            Some(opened) if now.duration_since(opened) < cool_down => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+}
+
+/// Circuit breakers for every mint the broker talks to
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    config: Mutex<CircuitBreakerConfig>,
+    circuits: Mutex<HashMap<String, Circuit>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config: Mutex::new(config),
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> CircuitBreakerConfig {
+        *self.config.lock().expect("circuit config lock poisoned")
+    }
+
+    pub fn set_config(&self, config: CircuitBreakerConfig) {
+        *self.config.lock().expect("circuit config lock poisoned") = config;
+    }
+
+    /// Whether a call to the mint may go ahead
+    ///
+    /// A half-open circuit lets one trial call through and restarts the
+    /// cool-down, so concurrent callers keep failing fast until it resolves.
+    pub fn allow(&self, mint_url: &str) -> bool {
+        self.allow_at(mint_url, Instant::now())
+    }
+
+    fn allow_at(&self, mint_url: &str, now: Instant) -> bool {
+        let cool_down = self.config().cool_down;
+        let mut circuits = self.circuits.lock().expect("circuits lock poisoned");
+        let Some(circuit) = circuits.get_mut(mint_url) else {
+            return true;
+        };
+
+        match circuit.state(now, cool_down) {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => {
+                circuit.opened_at = Some(now);
+                true
+            }
+        }
+    }
+
+    /// Record a call that reached the mint
+    pub fn record_success(&self, mint_url: &str) {
+        let mut circuits = self.circuits.lock().expect("circuits lock poisoned");
+        if let Some(circuit) = circuits.remove(mint_url) {
+            if circuit.opened_at.is_some() {
+                info!("Circuit for {} closed", mint_url);
+            }
+        }
+    }
+
+    /// Record a call that failed to reach the mint
+    pub fn record_failure(&self, mint_url: &str) {
+        self.record_failure_at(mint_url, Instant::now())
+    }
+
+    fn record_failure_at(&self, mint_url: &str, now: Instant) {
+        let threshold = self.config().failure_threshold;
+        let mut circuits = self.circuits.lock().expect("circuits lock poisoned");
+        let circuit = circuits.entry(mint_url.to_string()).or_default();
+
+        circuit.failures += 1;
+        if circuit.opened_at.is_some() {
+            // Failed trial: stay open for another cool-down
+            circuit.opened_at = Some(now);
+        } else if circuit.failures >= threshold {
+            warn!(
+                "Circuit for {} opened after {} failures",
+                mint_url, circuit.failures
+            );
+            circuit.opened_at = Some(now);
+        }
+    }
+
+    /// Current state of a mint's circuit
+    pub fn state(&self, mint_url: &str) -> CircuitState {
+        self.status(mint_url).state
+    }
+
+    /// Snapshot of a mint's circuit
+    pub fn status(&self, mint_url: &str) -> CircuitStatus {
+        self.status_at(mint_url, Instant::now())
+    }
+
+    fn status_at(&self, mint_url: &str, now: Instant) -> CircuitStatus {
+        let cool_down = self.config().cool_down;
+        let circuits = self.circuits.lock().expect("circuits lock poisoned");
+        let Some(circuit) = circuits.get(mint_url) else {
+            return CircuitStatus {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                retry_in_seconds: None,
+            };
+        };
+
+        let state = circuit.state(now, cool_down);
+        let retry_in_seconds = circuit
+            .opened_at
+            .filter(|_| state == CircuitState::Open)
+            .map(|opened| {
+                (opened + cool_down)
+                    .saturating_duration_since(now)
+                    .as_secs()
+            });
+
+        CircuitStatus {
+            state,
+            consecutive_failures: circuit.failures,
+            retry_in_seconds,
+        }
+    }
+
+    /// Forget a mint's circuit
+    pub fn remove(&self, mint_url: &str) {
+        self.circuits
+            .lock()
+            .expect("circuits lock poisoned")
+            .remove(mint_url);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let mint = "http://mint-a.test";
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 3,
+            cool_down: Duration::from_secs(30),
+        });
+        let start = Instant::now();
+
+        for _ in 0..2 {
+            breaker.record_failure_at(mint, start);
+        }
+        assert_eq!(breaker.status_at(mint, start).state, CircuitState::Closed);
+        assert!(breaker.allow_at(mint, start));
+
+        // Third failure opens it
+        breaker.record_failure_at(mint, start);
+        let status = breaker.status_at(mint, start + Duration::from_secs(10));
+        assert_eq!(status.state, CircuitState::Open);
+        assert_eq!(status.retry_in_seconds, Some(20));
+        assert!(!breaker.allow_at(mint, start + Duration::from_secs(10)));
+
+        // After the cool-down a single trial goes through
+        let later = start + Duration::from_secs(30);
+        assert_eq!(breaker.status_at(mint, later).state, CircuitState::HalfOpen);
+        assert!(breaker.allow_at(mint, later));
+        assert!(!breaker.allow_at(mint, later));
+
+        // A failed trial reopens it for another cool-down
+        breaker.record_failure_at(mint, later);
+        assert!(!breaker.allow_at(mint, later + Duration::from_secs(29)));
+        assert!(breaker.allow_at(mint, later + Duration::from_secs(30)));
+
+        // A successful trial closes it
+        breaker.record_success(mint);
+        assert_eq!(breaker.state(mint), CircuitState::Closed);
+        assert_eq!(breaker.status(mint).consecutive_failures, 0);
+    }
+
+    #[test]
+    fn test_circuits_are_per_mint() {
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 1,
+            cool_down: Duration::from_secs(30),
+        });
+
+        breaker.record_failure("http://mint-a.test");
+        assert_eq!(breaker.state("http://mint-a.test"), CircuitState::Open);
+        assert!(breaker.allow("http://mint-b.test"));
+
+        breaker.remove("http://mint-a.test");
+        assert!(breaker.allow("http://mint-a.test"));
+    }
+}
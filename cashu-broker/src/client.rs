@@ -0,0 +1,669 @@
+//! Client for the broker's HTTP API
+//!
+//! [`SwapClient`] runs the client's side of an atomic swap, so wallets don't
+//! have to reimplement the protocol:
+//!
+//! 1. [`request_quote`](SwapClient::request_quote) generates a one-time swap
+//!    key and asks for a quote, checking its signature against the broker's
+//!    identity key if one is pinned; [`extend`](SwapClient::extend) keeps it
+//!    open longer, once, where the broker allows it
+//! 2. [`lock_input`](SwapClient::lock_input) locks `amount_in` on the source
+//!    mint to the broker's tweaked key `P_broker + T`
+//! 3. [`accept`](SwapClient::accept) signs those proofs with the swap key and
+//!    hands them to the broker, which locks its outputs on the target mint to
+//!    `P_client + T`; its adaptor signature over the swap transcript is
+//!    checked before going on. This and the next step are signed with the
+//!    swap key (NIP-98), so only the client that requested the quote can
+//!    take them
+//! 4. [`complete`](SwapClient::complete) sends the client's own adaptor
+//!    signature; once the broker has claimed the input it reveals `t`, which
+//!    is checked against `T`
+//! 5. [`redeem`](SwapClient::redeem) unlocks the outputs with `sk_client + t`
+//!
+//! [`swap`](SwapClient::swap) runs all five steps between two wallets, and
+//! [`swap_chunked`](SwapClient::swap_chunked) does so chunk by chunk for
+//! amounts above the pair's maximum. Each
+//! step's result can be kept by the caller to resume after a crash; a lost
+//! complete response can be recovered with
+//! [`fetch_secret`](SwapClient::fetch_secret).
+//!
+//! Requests go to the routes of [`ApiVersion::CURRENT`], under `/v1`. The
+//! key and signature math is the `cashu-swap-protocol` crate's, which browser
+//! wallets use compiled to WebAssembly.
+
+use crate::adaptor::decode_encrypted_signature;
+use crate::api::{
+    AcceptQuoteRequest, AcceptQuoteResponse, ChunkedSwapRequest, ChunkedSwapResponse,
+    CompleteQuoteRequest, CompleteQuoteResponse, ErrorResponse, InfoResponse, QuoteRequest,
+    QuoteResponse, QuoteSecretResponse,
+};
+use crate::chunked::ChunkedProgress;
+use crate::binding;
+use crate::error::{BrokerError, Result};
+use crate::identity::verify_quote_signature;
+use crate::nip98::AuthEvent;
+use crate::swap::{swap_transcript, unix_now};
+use crate::types::{QuoteType, Sensitive, SwapQuote};
+use crate::versioning::ApiVersion;
+use cashu_swap_protocol::ProtocolError;
+use cdk::nuts::{Proofs, PublicKey, SecretKey, SpendingConditions};
+use cdk::wallet::{ReceiveOptions, SendOptions, Wallet};
+use cdk::Amount;
+use schnorr_fun::adaptor::EncryptedSignature;
+use schnorr_fun::fun::Scalar;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+
+/// Timeout for each request to the broker
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A quote the client asked for, with its one-time swap key
+#[derive(Clone)]
+pub struct QuotedSwap {
+    pub quote: SwapQuote,
+    /// Key the broker's outputs are locked to (tweaked by `T`); keep it until
+    /// the swap is redeemed
+    pub secret_key: SecretKey,
+}
+
+/// A swap the broker has accepted and locked its outputs for
+#[derive(Clone)]
+pub struct AcceptedSwap {
+    pub quote: SwapQuote,
+    pub secret_key: SecretKey,
+    /// The client's input, locked to the broker's tweaked key
+    pub locked_input: Proofs,
+    /// The broker's outputs, locked to the client's tweaked key
+    pub locked_output: Proofs,
+    /// Broker's adaptor signature over the swap transcript, encrypted under `T`
+    pub encrypted_signature: EncryptedSignature,
+    /// Unix time after which the broker can refund `locked_output`: complete
+    /// and redeem before then
+    pub refund_at: Option<u64>,
+}
+
+/// A completed swap whose outputs can be redeemed
+#[derive(Clone)]
+pub struct CompletedSwap {
+    pub quote: SwapQuote,
+    pub locked_output: Proofs,
+    /// `sk_client + t`, which unlocks the broker's outputs
+    pub unlock_key: SecretKey,
+}
+
+// Keys and proofs stay out of Debug output
+
+impl fmt::Debug for QuotedSwap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuotedSwap")
+            .field("quote", &self.quote)
+            .field("secret_key", &Sensitive(&self.secret_key))
+            .finish()
+    }
+}
+
+impl fmt::Debug for AcceptedSwap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcceptedSwap")
+            .field("quote", &self.quote)
+            .field("secret_key", &Sensitive(&self.secret_key))
+            .field("locked_input", &Sensitive(&self.locked_input))
+            .field("locked_output", &Sensitive(&self.locked_output))
+            .field("encrypted_signature", &self.encrypted_signature)
+            .field("refund_at", &self.refund_at)
+            .finish()
+    }
+}
+
+impl fmt::Debug for CompletedSwap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompletedSwap")
+            .field("quote", &self.quote)
+            .field("locked_output", &Sensitive(&self.locked_output))
+            .field("unlock_key", &Sensitive(&self.unlock_key))
+            .finish()
+    }
+}
+
+/// Client of a broker's HTTP API
+#[derive(Debug, Clone)]
+pub struct SwapClient {
+    http: reqwest::Client,
+    base_url: String,
+    identity_key: Option<Vec<u8>>,
+}
+
+impl SwapClient {
+    pub fn new(base_url: &str) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| BrokerError::Http(e.to_string()))?;
+
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            identity_key: None,
+        })
+    }
+
+    /// Only accept quotes signed by this identity key (the `pubkey` in `GET /info`)
+    pub fn with_identity_key(mut self, identity_key: Vec<u8>) -> Self {
+        self.identity_key = Some(identity_key);
+        self
+    }
+
+    /// The broker's mints, pairs and terms
+    pub async fn info(&self) -> Result<InfoResponse> {
+        self.get("/info").await
+    }
+
+    /// Generate a swap key and request a quote for it
+    pub async fn request_quote(
+        &self,
+        source_mint: &str,
+        target_mint: &str,
+        amount: u64,
+        quote_type: QuoteType,
+    ) -> Result<QuotedSwap> {
+        let secret_key = SecretKey::generate();
+        let request = QuoteRequest {
+            source_mint: source_mint.to_string(),
+            target_mint: target_mint.to_string(),
+            amount,
+            quote_type,
+            callback_url: None,
+            user_pubkey: Some(hex::encode(secret_key.public_key().to_bytes())),
+            adaptor_point: None,
+        };
+        let QuoteResponse { quote } = self.post("/quote", &request).await?;
+
+        self.check_quote(&quote, source_mint, target_mint)?;
+        Ok(QuotedSwap { quote, secret_key })
+    }
+
+    /// Check a quote is for the pair asked for, and signed by the pinned identity key
+    fn check_quote(&self, quote: &SwapQuote, source_mint: &str, target_mint: &str) -> Result<()> {
+        if quote.from_mint != source_mint || quote.to_mint != target_mint {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Quote {} is for {} → {}",
+                quote.quote_id, quote.from_mint, quote.to_mint
+            )));
+        }
+        if let Some(identity_key) = &self.identity_key {
+            verify_quote_signature(quote, identity_key)?;
+        }
+        Ok(())
+    }
+
+    /// Keep a quote open longer, by the broker's `quote_extension_seconds`
+    ///
+    /// A quote can be extended once. Returns the swap with the extended
+    /// quote, whose new expiry is signed again.
+    pub async fn extend(&self, swap: &QuotedSwap) -> Result<QuotedSwap> {
+        let quote = &swap.quote;
+        let QuoteResponse { quote: extended } = self
+            .post_signed(
+                &format!("/quote/{}/extend", quote.quote_id),
+                &serde_json::json!({}),
+                &swap.secret_key,
+            )
+            .await?;
+
+        self.check_quote(&extended, &quote.from_mint, &quote.to_mint)?;
+        let terms = |q: &SwapQuote| (q.quote_id.clone(), q.input_amount, q.output_amount);
+        if terms(&extended) != terms(quote) {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Extending quote {} changed its terms",
+                quote.quote_id
+            )));
+        }
+        Ok(QuotedSwap {
+            quote: extended,
+            secret_key: swap.secret_key.clone(),
+        })
+    }
+
+    /// Lock the quote's `amount_in` from `wallet` to the broker's tweaked key,
+    /// once it is checked to be `P_broker + T`
+    pub async fn lock_input(&self, wallet: &Wallet, swap: &QuotedSwap) -> Result<Proofs> {
+        let tweaked = swap.quote.tweaked_pubkey.as_deref().ok_or_else(|| {
+            BrokerError::InvalidSwapRequest(format!(
+                "Quote {} has no tweaked pubkey",
+                swap.quote.quote_id
+            ))
+        })?;
+        cashu_swap_protocol::check_tweaked_key(
+            &swap.quote.broker_public_key,
+            &swap.quote.adaptor_point,
+            tweaked,
+        )
+        .map_err(|e| BrokerError::AdaptorSignature(e.to_string()))?;
+        let pubkey = PublicKey::from_slice(tweaked).map_err(|e| BrokerError::Cdk(e.to_string()))?;
+
+        let prepared = wallet
+            .prepare_send(
+                Amount::from(swap.quote.input_amount),
+                SendOptions {
+                    conditions: Some(SpendingConditions::new_p2pk(pubkey, None)),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| BrokerError::Cdk(e.to_string()))?;
+        let token = prepared
+            .confirm(None)
+            .await
+            .map_err(|e| BrokerError::Cdk(e.to_string()))?;
+        let keysets = wallet
+            .get_mint_keysets()
+            .await
+            .map_err(|e| BrokerError::Cdk(e.to_string()))?;
+
+        token
+            .proofs(&keysets)
+            .map_err(|e| BrokerError::Cdk(e.to_string()))
+    }
+
+    /// Hand the locked input to the broker and check its side of the swap
+    pub async fn accept(&self, swap: &QuotedSwap, locked_input: Proofs) -> Result<AcceptedSwap> {
+        let quote = &swap.quote;
+        let locked_input = binding::sign_proofs(locked_input, &swap.secret_key)?;
+        let request = AcceptQuoteRequest {
+            source_proofs: serde_json::to_string(&locked_input)?,
+        };
+        let response: AcceptQuoteResponse = self
+            .post_signed(
+                &format!("/quote/{}/accept", quote.quote_id),
+                &request,
+                &swap.secret_key,
+            )
+            .await?;
+
+        let encrypted_signature = hex::decode(&response.encrypted_signature)
+            .map_err(|e| BrokerError::AdaptorSignature(e.to_string()))?;
+        cashu_swap_protocol::verify_encrypted_signature(
+            &quote.broker_public_key,
+            &quote.adaptor_point,
+            &swap_transcript(quote, &swap.secret_key.public_key().to_bytes()),
+            &encrypted_signature,
+        )
+        .map_err(|e| BrokerError::AdaptorSignature(e.to_string()))?;
+        let encrypted_signature = decode_encrypted_signature(&encrypted_signature)?;
+
+        let locked_output: Proofs = serde_json::from_str(&response.target_proofs)?;
+        let total: u64 = locked_output.iter().map(|p| u64::from(p.amount)).sum();
+        if total < quote.output_amount {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Broker locked {} of the {} quoted",
+                total, quote.output_amount
+            )));
+        }
+
+        Ok(AcceptedSwap {
+            quote: quote.clone(),
+            secret_key: swap.secret_key.clone(),
+            locked_input,
+            locked_output,
+            encrypted_signature,
+            refund_at: response.refund_at,
+        })
+    }
+
+    /// Authorize the swap and get the adaptor secret once the broker has claimed the input
+    pub async fn complete(&self, swap: &AcceptedSwap) -> Result<CompletedSwap> {
+        let quote = &swap.quote;
+        let client_signature = cashu_swap_protocol::create_encrypted_signature(
+            &swap.secret_key.to_secret_bytes(),
+            &quote.adaptor_point,
+            &swap_transcript(quote, &swap.secret_key.public_key().to_bytes()),
+        )
+        .map_err(|e| BrokerError::AdaptorSignature(e.to_string()))?;
+        let request = CompleteQuoteRequest {
+            decrypted_signature: serde_json::to_string(&swap.locked_input)?,
+            client_signature: hex::encode(client_signature),
+        };
+        let response: CompleteQuoteResponse = self
+            .post_signed(
+                &format!("/quote/{}/complete", quote.quote_id),
+                &request,
+                &swap.secret_key,
+            )
+            .await?;
+
+        completed(swap, &response.adaptor_secret)
+    }
+
+    /// Get the adaptor secret of a swap the broker already completed
+    ///
+    /// For when the response to [`complete`](Self::complete) was lost.
+    pub async fn fetch_secret(&self, swap: &AcceptedSwap) -> Result<CompletedSwap> {
+        let response: QuoteSecretResponse = self
+            .get(&format!("/quote/{}/secret", swap.quote.quote_id))
+            .await?;
+
+        completed(swap, &response.adaptor_secret)
+    }
+
+    /// Unlock the broker's outputs into `wallet`, returning the amount received
+    pub async fn redeem(&self, wallet: &Wallet, swap: &CompletedSwap) -> Result<u64> {
+        let received = wallet
+            .receive_proofs(
+                swap.locked_output.clone(),
+                ReceiveOptions {
+                    p2pk_signing_keys: vec![swap.unlock_key.clone()],
+                    ..Default::default()
+                },
+                None,
+            )
+            .await
+            .map_err(|e| BrokerError::Cdk(e.to_string()))?;
+
+        Ok(received.into())
+    }
+
+    /// Swap `amount` from `source` to `target`, returning the amount received
+    ///
+    /// The broker knows each mint's wallets by URL, and a mint's further
+    /// units as `<url>#<unit>`; wallets in those go through
+    /// [`Self::request_quote`] with the key instead.
+    pub async fn swap(
+        &self,
+        source: &Wallet,
+        target: &Wallet,
+        amount: u64,
+        quote_type: QuoteType,
+    ) -> Result<u64> {
+        let quoted = self
+            .request_quote(
+                &source.mint_url.to_string(),
+                &target.mint_url.to_string(),
+                amount,
+                quote_type,
+            )
+            .await?;
+        self.run_swap(source, target, &quoted).await
+    }
+
+    /// Swap `amount` from `source` to `target` in chunks of at most
+    /// `chunk_amount`, the pair's maximum by default, returning the amount
+    /// received
+    ///
+    /// Each chunk is swapped like [`Self::swap`], all with one key. If a
+    /// chunk fails, the chunked swap is stopped and the error returned; the
+    /// chunks before it stay swapped.
+    pub async fn swap_chunked(
+        &self,
+        source: &Wallet,
+        target: &Wallet,
+        amount: u64,
+        chunk_amount: Option<u64>,
+    ) -> Result<u64> {
+        let (source_mint, target_mint) = (source.mint_url.to_string(), target.mint_url.to_string());
+        let secret_key = SecretKey::generate();
+        let request = ChunkedSwapRequest {
+            source_mint: source_mint.clone(),
+            target_mint: target_mint.clone(),
+            amount,
+            chunk_amount,
+            callback_url: None,
+            user_pubkey: hex::encode(secret_key.public_key().to_bytes()),
+        };
+        let ChunkedSwapResponse { chunked, mut quote } = self.post("/chunked", &request).await?;
+
+        let mut received = 0;
+        while let Some(next) = quote {
+            let chunk = async {
+                self.check_quote(&next, &source_mint, &target_mint)?;
+                let quoted = QuotedSwap {
+                    quote: next,
+                    secret_key: secret_key.clone(),
+                };
+                self.run_swap(source, target, &quoted).await
+            };
+            match chunk.await {
+                Ok(amount) => received += amount,
+                Err(e) => {
+                    let path = format!("/chunked/{}/stop", chunked.id);
+                    let stopped: Result<ChunkedProgress> =
+                        self.post_signed(&path, &serde_json::json!({}), &secret_key).await;
+                    if let Err(stop_error) = stopped {
+                        let id = &chunked.id;
+                        tracing::warn!("Could not stop chunked swap {}: {}", id, stop_error);
+                    }
+                    return Err(e);
+                }
+            }
+
+            let path = format!("/chunked/{}/next", chunked.id);
+            let response: ChunkedSwapResponse =
+                self.post_signed(&path, &serde_json::json!({}), &secret_key).await?;
+            quote = response.quote;
+        }
+
+        Ok(received)
+    }
+
+    /// Progress of a chunked swap
+    pub async fn chunked_progress(&self, chunked_id: &str) -> Result<ChunkedProgress> {
+        self.get(&format!("/chunked/{}", chunked_id)).await
+    }
+
+    /// Lock, accept, complete and redeem a quoted swap
+    async fn run_swap(&self, source: &Wallet, target: &Wallet, quoted: &QuotedSwap) -> Result<u64> {
+        for (wallet, unit) in [(source, &quoted.quote.from_unit), (target, &quoted.quote.to_unit)] {
+            if !unit.is_empty() && *unit != wallet.unit.to_string() {
+                return Err(BrokerError::InvalidSwapRequest(format!(
+                    "Quote {} is in {}, but the wallet on {} holds {}",
+                    quoted.quote.quote_id, unit, wallet.mint_url, wallet.unit
+                )));
+            }
+        }
+        let locked_input = self.lock_input(source, quoted).await?;
+        let accepted = self.accept(quoted, locked_input).await?;
+        let completed = self.complete(&accepted).await?;
+
+        self.redeem(target, &completed).await
+    }
+
+    /// URL of `path` in the API version this client speaks
+    fn url(&self, path: &str) -> String {
+        format!("{}{}{}", self.base_url, ApiVersion::CURRENT.prefix(), path)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let request = self.http.get(self.url(path));
+        self.send(request).await
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        let request = self
+            .http
+            .post(self.url(path))
+            .json(body);
+        self.send(request).await
+    }
+
+    /// POST signed with `secret_key`, for the steps only the quote's owner may take
+    async fn post_signed<B: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+        secret_key: &SecretKey,
+    ) -> Result<T> {
+        let url = self.url(path);
+        let body = serde_json::to_vec(body)?;
+        let event = AuthEvent::sign(scalar(secret_key)?, "POST", &url, &body, unix_now());
+
+        let request = self
+            .http
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(reqwest::header::AUTHORIZATION, event.to_header())
+            .body(body);
+        self.send(request).await
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| BrokerError::Http(e.to_string()))?;
+        let status = response.status();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| BrokerError::Http(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(api_error(status.as_u16(), &body));
+        }
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+/// Error for a non-2xx response, from its `ErrorResponse` body if it has one
+fn api_error(status: u16, body: &[u8]) -> BrokerError {
+    match serde_json::from_slice::<ErrorResponse>(body) {
+        Ok(error) => BrokerError::Api {
+            status,
+            code: error.code,
+            message: error.error,
+            retryable: error.retryable,
+        },
+        // Gateways in front of the broker may answer for it while it is down
+        Err(_) => BrokerError::Api {
+            status,
+            code: "UNKNOWN".to_string(),
+            message: String::from_utf8_lossy(body).into_owned(),
+            retryable: matches!(status, 502..=504),
+        },
+    }
+}
+
+/// Check the revealed adaptor secret against `T` and derive the unlock key
+fn completed(swap: &AcceptedSwap, adaptor_secret_hex: &str) -> Result<CompletedSwap> {
+    let adaptor_secret = hex::decode(adaptor_secret_hex)
+        .map_err(|_| BrokerError::AdaptorSignature("Invalid adaptor secret".to_string()))?;
+    let unlock = cashu_swap_protocol::unlock_key(
+        &swap.secret_key.to_secret_bytes(),
+        &adaptor_secret,
+        &swap.quote.adaptor_point,
+    )
+    .map_err(|e| match e {
+        ProtocolError::SecretMismatch => BrokerError::AdaptorSignature(format!(
+            "Revealed secret does not match the adaptor point of quote {}",
+            swap.quote.quote_id
+        )),
+        e => BrokerError::AdaptorSignature(e.to_string()),
+    })?;
+    let unlock_key =
+        SecretKey::from_slice(&unlock).map_err(|e| BrokerError::Cdk(e.to_string()))?;
+
+    Ok(CompletedSwap {
+        quote: swap.quote.clone(),
+        locked_output: swap.locked_output.clone(),
+        unlock_key,
+    })
+}
+
+fn scalar(secret_key: &SecretKey) -> Result<Scalar> {
+    Scalar::from_bytes(secret_key.to_secret_bytes())
+        .and_then(|s| s.non_zero())
+        .ok_or_else(|| BrokerError::AdaptorSignature("Invalid client key".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adaptor::AdaptorContext;
+    use schnorr_fun::fun::Point;
+
+    fn point(bytes: &[u8]) -> Point {
+        Point::from_bytes(bytes.try_into().unwrap()).unwrap()
+    }
+
+    fn accepted_swap(adaptor_point: Vec<u8>) -> AcceptedSwap {
+        let ctx = AdaptorContext::new();
+        let key = ctx.generate_adaptor_secret();
+        let encrypted_signature = ctx
+            .create_encrypted_signature(&key, &ctx.adaptor_point_from_secret(&key), b"")
+            .unwrap();
+        let quote: SwapQuote = serde_json::from_value(serde_json::json!({
+            "id": "quote-1",
+            "source_mint": "http://mint-a.test",
+            "target_mint": "http://mint-b.test",
+            "amount_in": 100,
+            "amount_out": 99,
+            "fee": 1,
+            "fee_rate": 0.01,
+            "broker_pubkey": "02".repeat(33),
+            "adaptor_point": hex::encode(adaptor_point),
+            "expires_in": 300,
+            "status": "accepted",
+        }))
+        .unwrap();
+
+        AcceptedSwap {
+            quote,
+            secret_key: SecretKey::generate(),
+            locked_input: Proofs::new(),
+            locked_output: Proofs::new(),
+            encrypted_signature,
+            refund_at: None,
+        }
+    }
+
+    #[test]
+    fn test_completed_checks_secret() {
+        let ctx = AdaptorContext::new();
+        let t = ctx.generate_adaptor_secret();
+        let swap = accepted_swap(ctx.adaptor_point_from_secret(&t).to_bytes().to_vec());
+
+        // sk_client + t unlocks outputs locked to P_client + T
+        let completed = completed(&swap, &hex::encode(t.to_bytes())).unwrap();
+        let tweaked = ctx.tweak_public_key(
+            &point(&swap.secret_key.public_key().to_bytes()),
+            &point(&swap.quote.adaptor_point),
+        );
+        assert_eq!(
+            completed.unlock_key.public_key().to_bytes().to_vec(),
+            tweaked.to_bytes().to_vec()
+        );
+
+        // A secret for another point, or garbage, is refused
+        let other = ctx.generate_adaptor_secret();
+        assert!(completed_err(&swap, &hex::encode(other.to_bytes())));
+        assert!(completed_err(&swap, "zz"));
+        assert!(completed_err(&swap, &hex::encode([0u8; 32])));
+    }
+
+    fn completed_err(swap: &AcceptedSwap, secret: &str) -> bool {
+        matches!(
+            completed(swap, secret),
+            Err(BrokerError::AdaptorSignature(_))
+        )
+    }
+
+    #[test]
+    fn test_api_error() {
+        let body = br#"{"error":"Quote q1 is not completed (status: accepted)","code":"CONFLICT"}"#;
+        assert!(matches!(
+            api_error(409, body),
+            BrokerError::Api { status: 409, ref code, .. } if code == "CONFLICT"
+        ));
+
+        // Proxies answer in HTML
+        assert!(matches!(
+            api_error(502, b"<html>Bad Gateway</html>"),
+            BrokerError::Api { status: 502, ref code, ref message, retryable: true }
+                if code == "UNKNOWN" && message.contains("Bad Gateway")
+        ));
+
+        let body = br#"{"error":"Mint timed out","code":"MINT_TIMEOUT","retryable":true}"#;
+        assert!(api_error(504, body).retryable());
+        assert!(!api_error(409, br#"{"error":"Taken","code":"CONFLICT"}"#).retryable());
+    }
+}
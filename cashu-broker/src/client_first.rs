@@ -0,0 +1,188 @@
+//! Swaps where the client claims first
+//!
+//! In the default flow the broker picks the adaptor secret t and reveals it by
+//! claiming the client's proofs. A client-first swap turns that around. The
+//! client picks t and sends T = t·G with its quote request, then locks its
+//! source proofs to the broker's key tweaked by T, as usual. The broker locks
+//! its outputs to the client's key and its own swap key, 2-of-2, with its swap
+//! key as the refund key after the locktime. For each output it hands over its
+//! NUT-11 witness signature encrypted under T.
+//!
+//! The client decrypts those signatures with t and redeems the outputs,
+//! signing them itself as well. That leaves the decrypted signatures in the
+//! witnesses the mint reports for the spent outputs (NUT-07). The broker
+//! polls for them, recovers t from any one and claims the client's proofs with
+//! its swap key plus t. The client's proofs have no refund path, so there is
+//! no race on the broker's side; the client must redeem the outputs before
+//! the locktime, after which the broker takes them back.
+
+use crate::adaptor::AdaptorContext;
+use crate::error::{BrokerError, Result};
+use cdk::nuts::{
+    Conditions, Proof, Proofs, PublicKey, SecretKey, SigFlag, SpendingConditions, Witness,
+};
+use schnorr_fun::adaptor::EncryptedSignature;
+use schnorr_fun::fun::{marker::*, Point, Scalar};
+use schnorr_fun::Signature;
+
+/// Spending conditions of the broker's outputs: the client and the broker's
+/// swap key together, or the swap key alone after `refund_at`
+pub fn output_conditions(
+    client_pubkey: PublicKey,
+    broker_pubkey: PublicKey,
+    refund_at: u64,
+) -> SpendingConditions {
+    SpendingConditions::new_p2pk(
+        client_pubkey,
+        Some(Conditions {
+            locktime: Some(refund_at),
+            pubkeys: Some(vec![broker_pubkey]),
+            refund_keys: Some(vec![broker_pubkey]),
+            num_sigs: Some(2),
+            sig_flag: SigFlag::SigInputs,
+            ..Default::default()
+        }),
+    )
+}
+
+/// The broker's witness signature for each output, encrypted under the
+/// client's adaptor point
+///
+/// Signing is deterministic, so the same outputs always get the same
+/// signatures.
+pub fn output_signatures(
+    ctx: &AdaptorContext,
+    broker_key: &Scalar,
+    adaptor_point: &Point,
+    proofs: &Proofs,
+) -> Result<Vec<EncryptedSignature>> {
+    proofs
+        .iter()
+        .map(|proof| {
+            ctx.create_encrypted_witness(broker_key, adaptor_point, &proof.secret.to_string())
+        })
+        .collect()
+}
+
+/// Sign the broker's outputs for redeeming, on the client's side
+///
+/// Each output gets the client's signature and the broker's, decrypted with
+/// the adaptor secret. `output_signatures` are in the order of `proofs`, as
+/// returned at accept.
+pub fn sign_outputs(
+    ctx: &AdaptorContext,
+    mut proofs: Proofs,
+    output_signatures: &[EncryptedSignature],
+    adaptor_secret: &Scalar,
+    client_key: &SecretKey,
+) -> Result<Proofs> {
+    if proofs.len() != output_signatures.len() {
+        return Err(BrokerError::AdaptorSignature(format!(
+            "{} outputs but {} signatures",
+            proofs.len(),
+            output_signatures.len()
+        )));
+    }
+
+    for (proof, encrypted) in proofs.iter_mut().zip(output_signatures) {
+        proof
+            .sign_p2pk(client_key.clone())
+            .map_err(|e| BrokerError::Cdk(format!("Failed to sign output: {:?}", e)))?;
+        let broker_signature = ctx.decrypt_signature(adaptor_secret, encrypted.clone())?;
+        if let Some(witness) = proof.witness.as_mut() {
+            witness.add_signatures(vec![hex::encode(broker_signature.to_bytes())]);
+        }
+    }
+    Ok(proofs)
+}
+
+/// Recover the adaptor secret from the witness an output was redeemed with
+///
+/// `witness` is the JSON the mint reports for the spent `proof`. Returns
+/// `None` when none of its signatures is the broker's decrypted one, as when
+/// the broker refunded the output itself.
+pub fn recover_secret(
+    ctx: &AdaptorContext,
+    broker_key: &Scalar,
+    adaptor_point: &Point,
+    proof: &Proof,
+    witness: &str,
+) -> Option<Scalar> {
+    let witness: Witness = serde_json::from_str(witness).ok()?;
+    let encrypted = ctx
+        .create_encrypted_witness(broker_key, adaptor_point, &proof.secret.to_string())
+        .ok()?;
+
+    witness
+        .signatures()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|sig| hex::decode(sig).ok())
+        .filter_map(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        .filter_map(Signature::<Public>::from_bytes)
+        .filter_map(|sig| {
+            ctx.recover_adaptor_secret(adaptor_point, &encrypted, &sig)
+                .ok()
+        })
+        .find(|secret| ctx.adaptor_point_from_secret(secret) == *adaptor_point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::nuts::{Id, Nut10Secret};
+    use cdk::secret::Secret;
+    use cdk::Amount;
+    use std::str::FromStr;
+
+    fn output(conditions: SpendingConditions) -> Proof {
+        Proof::new(
+            Amount::from(8),
+            Id::from_str("009a1f293253e41e").unwrap(),
+            Secret::try_from(Nut10Secret::from(conditions)).unwrap(),
+            SecretKey::generate().public_key(),
+        )
+    }
+
+    #[test]
+    fn test_redeem_reveals_secret() {
+        let ctx = AdaptorContext::new();
+        let client = SecretKey::generate();
+        let broker_key = ctx.generate_adaptor_secret();
+        let broker = SecretKey::from_slice(&broker_key.to_bytes()).unwrap();
+        let adaptor_secret = ctx.generate_adaptor_secret();
+        let adaptor_point = ctx.adaptor_point_from_secret(&adaptor_secret);
+
+        // Redeemed before the locktime, so both signatures are needed
+        let refund_at = crate::swap::unix_now() + 3600;
+        let conditions = output_conditions(client.public_key(), broker.public_key(), refund_at);
+        let proofs = vec![output(conditions.clone()), output(conditions)];
+        let signatures = output_signatures(&ctx, &broker_key, &adaptor_point, &proofs).unwrap();
+        assert_eq!(signatures.len(), 2);
+
+        let signed =
+            sign_outputs(&ctx, proofs.clone(), &signatures, &adaptor_secret, &client).unwrap();
+        for proof in &signed {
+            assert_eq!(
+                proof.witness.as_ref().unwrap().signatures().unwrap().len(),
+                2
+            );
+            proof.verify_p2pk().unwrap();
+        }
+
+        // What the mint reports for the spent output gives the secret away
+        let witness = serde_json::to_string(signed[1].witness.as_ref().unwrap()).unwrap();
+        let recovered = recover_secret(&ctx, &broker_key, &adaptor_point, &proofs[1], &witness);
+        assert_eq!(recovered, Some(adaptor_secret));
+
+        // A refund by the broker alone doesn't
+        let mut refunded = proofs[0].clone();
+        refunded.sign_p2pk(broker).unwrap();
+        let witness = serde_json::to_string(refunded.witness.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            recover_secret(&ctx, &broker_key, &adaptor_point, &proofs[0], &witness),
+            None
+        );
+        assert!(sign_outputs(&ctx, proofs, &signatures[..1], &adaptor_secret, &client).is_err());
+    }
+}
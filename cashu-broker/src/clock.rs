@@ -0,0 +1,85 @@
+//! Source of the time quotes are stamped and expired with
+//!
+//! The swap coordinator reads the time through a [`Clock`]: [`SystemClock`]
+//! in production, or a [`ManualClock`] that tests and simulations set and
+//! advance by hand, so expiry, extension, refund locktimes and daily volume
+//! limits can be exercised without sleeping.
+
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Tells the time
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> SystemTime;
+
+    /// Seconds since the Unix epoch
+    fn unix_now(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+/// The system's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<SystemTime>,
+}
+
+impl ManualClock {
+    /// A clock stopped at `now`
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// A clock stopped `secs` seconds after the Unix epoch
+    pub fn at_unix(secs: u64) -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().expect("clock lock poisoned") = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().expect("clock lock poisoned") += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().expect("clock lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::at_unix(1_700_000_000);
+        assert_eq!(clock.unix_now(), 1_700_000_000);
+
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(clock.unix_now(), 1_700_000_090);
+
+        clock.set(UNIX_EPOCH);
+        assert_eq!(clock.unix_now(), 0);
+        assert!(SystemClock.unix_now() > 1_700_000_000);
+    }
+}
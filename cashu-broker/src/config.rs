@@ -1,6 +1,28 @@
+use crate::alerts::{AlertThresholds, Alerter, TelegramAlertSink, WebhookAlertSink};
+use crate::bootstrap::{FundingMode, InitialLiquidity};
+use crate::circuit_breaker::CircuitBreakerConfig;
+use crate::db::{Database, DatabaseOptions, Synchronous, DEFAULT_MAX_CONNECTIONS};
 use crate::error::BrokerError;
+use crate::fees::{FeeSchedule, FeeTier, VolumeDiscount};
+use crate::jobs::JobQueueConfig;
+use crate::leases::Leases;
+use crate::limits::RequestLimits;
+use crate::price::{CachedPriceFeed, CoinbasePriceFeed, FixedPriceFeed, KrakenPriceFeed, PriceFeed};
+use crate::pricing::{InventorySkew, PricingStrategy};
+use crate::rate_limit::RateLimitConfig;
+use crate::retention::{RetentionMode, RetentionPolicy};
+use crate::retry::RetryPolicy;
+use crate::risk::RiskLimits;
+use crate::selection::SelectionStrategy;
+use crate::timeouts::MintTimeouts;
+use crate::types::PairConfig;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Server configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,18 +33,137 @@ pub struct Config {
     /// HTTP server port (default: 3000)
     pub port: u16,
 
-    /// Database URL (default: sqlite://broker.db)
+    /// Database URL (default: sqlite://broker.db, or postgres://... with the `postgres` feature)
     pub database_url: String,
 
+    /// Connections in the database pool (default: 5 on SQLite, 10 on Postgres)
+    pub db_max_connections: u32,
+
+    /// Use SQLite's write-ahead log (default: true)
+    pub sqlite_wal: bool,
+
+    /// How long SQLite waits for a lock before "database is locked" (default: 5000 ms)
+    pub sqlite_busy_timeout_ms: u64,
+
+    /// SQLite synchronous level: off, normal, full or extra (default: normal)
+    pub sqlite_synchronous: Synchronous,
+
+    /// ID this instance takes leases under; give each instance sharing the
+    /// database its own (default: broker)
+    pub instance_id: String,
+
+    /// Seconds a lease lasts without renewal, and so how long an instance's
+    /// mints and sweeps wait after it dies before another takes them (default: 30)
+    pub lease_ttl_seconds: u64,
+
+    /// Hex-encoded 32-byte key for secrets stored in the database (optional)
+    #[serde(skip_serializing)]
+    pub encryption_key: Option<String>,
+
+    /// Key file used when ENCRYPTION_KEY is unset, created on first start (default: broker.key)
+    pub encryption_key_file: String,
+
+    /// Hex-encoded 32-byte identity key (loaded from IDENTITY_KEY_FILE when unset)
+    #[serde(skip_serializing)]
+    pub identity_key: Option<String>,
+
+    /// Key file used when IDENTITY_KEY is unset, created on first start (default: broker_identity.key)
+    pub identity_key_file: String,
+
+    /// PEM certificate chain for HTTPS (`tls` feature; plain HTTP when unset)
+    pub tls_cert_path: Option<String>,
+
+    /// PEM private key for the certificate (required with TLS_CERT_PATH)
+    pub tls_key_path: Option<String>,
+
     /// Log level (default: info)
     pub log_level: String,
 
     /// CORS allowed origins (comma-separated)
     pub cors_origins: Vec<String>,
 
+    /// Sustained requests per minute per client IP or pubkey (default: 60, 0 = no limit)
+    pub rate_limit_per_minute: u32,
+
+    /// Requests a client may make in a burst (default: 10)
+    pub rate_limit_burst: u32,
+
+    /// Take client IPs from X-Forwarded-For (only behind a trusted proxy; default: false)
+    pub trust_proxy: bool,
+
+    /// Require an X-API-Key for /quotes, /liquidity and /metrics (default: false)
+    pub require_api_key: bool,
+
+    /// Require NIP-98 signed requests for a client's quote history (default: true)
+    pub client_history_auth: bool,
+
+    /// Largest request body accepted, in bytes (default: 1048576)
+    pub max_body_bytes: usize,
+
+    /// Seconds before a request still running is answered with a timeout (default: 30)
+    pub request_timeout_seconds: u64,
+
+    /// Bearer token for the /admin API (admin API disabled when unset)
+    #[serde(skip_serializing)]
+    pub admin_token: Option<String>,
+
+    /// Nostr secret key (hex or nsec) for DM swap requests (Nostr listener disabled when unset)
+    #[serde(skip_serializing)]
+    pub nostr_secret_key: Option<String>,
+
+    /// Nostr relays to listen on (comma-separated)
+    pub nostr_relays: Vec<String>,
+
+    /// Relays rates are published to (comma-separated, default: NOSTR_RELAYS)
+    pub nostr_publish_relays: Vec<String>,
+
+    /// Seconds between rate announcements on Nostr (default: 900, 0 = off)
+    pub nostr_publish_interval_seconds: u64,
+
+    /// Secret for signing webhook callbacks (webhooks disabled when unset)
+    #[serde(skip_serializing)]
+    pub webhook_secret: Option<String>,
+
+    /// Callback hosts that may resolve to private addresses (comma-separated)
+    pub webhook_allowed_hosts: Vec<String>,
+
+    /// URL that alerts are posted to as JSON (optional)
+    pub alert_webhook_url: Option<String>,
+
+    /// Telegram bot token for alerts (optional, needs ALERT_TELEGRAM_CHAT_ID)
+    #[serde(skip_serializing)]
+    pub alert_telegram_bot_token: Option<String>,
+
+    /// Telegram chat that alerts are sent to
+    pub alert_telegram_chat_id: Option<String>,
+
+    /// Alert when a mint's balance drops below this many sats (default: 0 = off)
+    pub alert_low_balance: u64,
+
+    /// Alert after this many swaps fail in a row (default: 3, 0 = off)
+    pub alert_failed_swaps: u32,
+
+    /// Prune settled quotes older than this many days (default: 0 = keep forever)
+    pub retention_days: u64,
+
+    /// Archive pruned quotes or delete them outright (default: archive)
+    pub retention_mode: RetentionMode,
+
     /// Broker fee rate (default: 0.005 = 0.5%)
     pub fee_rate: f64,
 
+    /// How far fees widen on swaps into a mint short of its share (default: 0 = flat)
+    pub fee_skew: f64,
+
+    /// Fee rates by swap amount, over FEE_RATE (JSON array, default: none)
+    pub fee_tiers: Vec<FeeTier>,
+
+    /// Smallest fee charged on a swap (default: 0)
+    pub min_fee: u64,
+
+    /// Fee discounts by a client's 30-day volume (JSON array, default: none)
+    pub volume_discounts: Vec<VolumeDiscount>,
+
     /// Minimum swap amount in sats (default: 1)
     pub min_swap_amount: u64,
 
@@ -32,8 +173,101 @@ pub struct Config {
     /// Quote expiry in seconds (default: 300 = 5 minutes)
     pub quote_expiry_seconds: u64,
 
+    /// Seconds a client may extend a pending quote by, once (default: 0 = off)
+    pub quote_extension_seconds: u64,
+
+    /// Sats a client may be quoted per rolling 24 hours (default: 0 = off)
+    pub risk_daily_volume_per_client: u64,
+
+    /// Pending and accepted quotes a client may hold at once (default: 0 = off)
+    pub risk_max_open_quotes_per_client: usize,
+
+    /// Output sats of all open quotes together (default: 0 = off)
+    pub risk_max_exposure: u64,
+
+    /// Locktime on broker P2PK outputs before they can be refunded (default: 3600)
+    pub refund_locktime_seconds: u64,
+
+    /// Rebalance a mint over Lightning when it drops below this many sats (default: 0 = off)
+    pub rebalance_threshold: u64,
+
+    /// Balance to top a low mint up to (default: 0 = twice the threshold)
+    pub rebalance_target: u64,
+
+    /// Consolidate a mint's proofs once the broker holds more than this many (default: 100, 0 = off)
+    pub consolidation_threshold: usize,
+
+    /// How proofs are picked for payments: largest_first or branch_and_bound (default: branch_and_bound)
+    pub proof_selection: SelectionStrategy,
+
+    /// Quarantine received proofs without a DLEQ (default: true; false for mints lacking NUT-12)
+    pub require_dleq: bool,
+
+    /// Price provider for cross-unit swaps: coinbase, kraken or fixed (default: none)
+    pub price_feed: Option<String>,
+
+    /// BTC prices for the fixed provider, e.g. {"usd": 65000} (JSON object)
+    pub fixed_prices: HashMap<String, f64>,
+
+    /// How long fetched prices are reused, in seconds (default: 60)
+    pub price_cache_seconds: u64,
+
+    /// Spread taken off the exchange rate on cross-unit swaps (default: 0.01 = 1%)
+    pub price_spread: f64,
+
+    /// Attempts per mint call on network errors, including the first (default: 3)
+    pub retry_max_attempts: u32,
+
+    /// Delay before the first retry in milliseconds, doubled each time (default: 250)
+    pub retry_base_delay_ms: u64,
+
+    /// Longest delay between retries in milliseconds (default: 5000)
+    pub retry_max_delay_ms: u64,
+
+    /// Random spread applied to retry delays (default: 0.2 = ±20%)
+    pub retry_jitter: f64,
+
+    /// Consecutive failed calls that open a mint's circuit breaker (default: 5)
+    pub circuit_failure_threshold: u32,
+
+    /// Seconds an open circuit rejects calls before a trial call (default: 30)
+    pub circuit_cool_down_seconds: u64,
+
+    /// Seconds a mint may take to create a mint quote (default: 10)
+    pub timeout_mint_quote_seconds: u64,
+
+    /// Seconds a mint may take to mint a paid quote (default: 30)
+    pub timeout_mint_seconds: u64,
+
+    /// Seconds a mint may take to swap proofs (default: 30)
+    pub timeout_swap_seconds: u64,
+
+    /// Seconds a mint may take to report proof states (default: 10)
+    pub timeout_checkstate_seconds: u64,
+
+    /// Seconds a mint may take to quote or pay a melt (default: 120)
+    pub timeout_melt_seconds: u64,
+
+    /// Seconds any other mint call may take, e.g. fetching keysets (default: 30)
+    pub timeout_default_seconds: u64,
+
+    /// Accepts and completes that run at once (default: 8)
+    pub swap_workers: usize,
+
+    /// Running accepts and completes that call the same mint (default: 4)
+    pub swap_mint_concurrency: usize,
+
+    /// Accepts and completes that wait for a slot before more are refused (default: 64)
+    pub swap_queue_size: usize,
+
     /// Mints configuration (JSON array)
     pub mints: Vec<MintConfig>,
+
+    /// Per-pair fee and limit overrides (JSON array, default: none)
+    pub pairs: Vec<PairConfig>,
+
+    /// Starting balance per mint and how to fund it (JSON array, default: none)
+    pub initial_liquidity: Vec<InitialLiquidity>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +275,27 @@ pub struct MintConfig {
     pub mint_url: String,
     pub name: String,
     pub unit: String,
+    /// Further units the mint issues, each held in a wallet of its own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub units: Vec<String>,
+}
+
+impl MintConfig {
+    /// One broker-side entry per unit, keyed as [`crate::types::mint_key`] describes
+    pub fn wallets(&self) -> Vec<crate::types::MintConfig> {
+        let first = crate::types::MintConfig {
+            mint_url: self.mint_url.clone(),
+            name: self.name.clone(),
+            unit: self.unit.clone(),
+        };
+        std::iter::once(first)
+            .chain(self.units.iter().map(|unit| crate::types::MintConfig {
+                mint_url: crate::types::mint_key(&self.mint_url, unit),
+                name: format!("{} ({})", self.name, unit.to_lowercase()),
+                unit: unit.clone(),
+            }))
+            .collect()
+    }
 }
 
 impl Config {
@@ -56,6 +311,23 @@ impl Config {
 
         let database_url = env::var("DATABASE_URL")
             .unwrap_or_else(|_| "sqlite://broker.db".to_string());
+        let db_max_connections = env_parse("DB_MAX_CONNECTIONS", DEFAULT_MAX_CONNECTIONS)?;
+        let sqlite_wal = env_parse("SQLITE_WAL", true)?;
+        let sqlite_busy_timeout_ms = env_parse("SQLITE_BUSY_TIMEOUT_MS", 5000)?;
+        let sqlite_synchronous = env_parse("SQLITE_SYNCHRONOUS", Synchronous::default())?;
+        let instance_id = env::var("INSTANCE_ID").unwrap_or_else(|_| "broker".to_string());
+        let lease_ttl_seconds = env_parse("LEASE_TTL_SECONDS", 30)?;
+
+        let encryption_key = env::var("ENCRYPTION_KEY").ok();
+        let encryption_key_file =
+            env::var("ENCRYPTION_KEY_FILE").unwrap_or_else(|_| "broker.key".to_string());
+
+        let identity_key = env::var("IDENTITY_KEY").ok();
+        let identity_key_file = env::var("IDENTITY_KEY_FILE")
+            .unwrap_or_else(|_| "broker_identity.key".to_string());
+
+        let tls_cert_path = env::var("TLS_CERT_PATH").ok().filter(|p| !p.is_empty());
+        let tls_key_path = env::var("TLS_KEY_PATH").ok().filter(|p| !p.is_empty());
 
         let log_level = env::var("LOG_LEVEL").unwrap_or_else(|_| "info".to_string());
 
@@ -63,12 +335,63 @@ impl Config {
             .unwrap_or_else(|_| "*".to_string())
             .split(',')
             .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let rate_limit_per_minute = env_parse("RATE_LIMIT_PER_MINUTE", 60)?;
+        let rate_limit_burst = env_parse("RATE_LIMIT_BURST", 10)?;
+        let trust_proxy = env_parse("TRUST_PROXY", false)?;
+
+        let require_api_key = env_parse("REQUIRE_API_KEY", false)?;
+        let client_history_auth = env_parse("CLIENT_HISTORY_AUTH", true)?;
+        let max_body_bytes = env_parse("MAX_BODY_BYTES", 1024 * 1024)?;
+        let request_timeout_seconds = env_parse("REQUEST_TIMEOUT_SECONDS", 30)?;
+
+        let admin_token = env::var("ADMIN_TOKEN").ok().filter(|t| !t.is_empty());
+
+        let nostr_secret_key = env::var("NOSTR_SECRET_KEY").ok().filter(|k| !k.is_empty());
+        let nostr_relays: Vec<String> = env::var("NOSTR_RELAYS")
+            .unwrap_or_else(|_| "wss://relay.damus.io,wss://nos.lol".to_string())
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
             .collect();
+        let nostr_publish_relays = match env::var("NOSTR_PUBLISH_RELAYS") {
+            Ok(relays) => relays
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            Err(_) => nostr_relays.clone(),
+        };
+        let nostr_publish_interval_seconds = env_parse("NOSTR_PUBLISH_INTERVAL_SECONDS", 900)?;
+
+        let webhook_secret = env::var("WEBHOOK_SECRET").ok().filter(|s| !s.is_empty());
+        let webhook_allowed_hosts = env::var("WEBHOOK_ALLOWED_HOSTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        let alert_webhook_url = env::var("ALERT_WEBHOOK_URL").ok().filter(|u| !u.is_empty());
+        let alert_telegram_bot_token = env::var("ALERT_TELEGRAM_BOT_TOKEN")
+            .ok()
+            .filter(|t| !t.is_empty());
+        let alert_telegram_chat_id = env::var("ALERT_TELEGRAM_CHAT_ID")
+            .ok()
+            .filter(|c| !c.is_empty());
+        let alert_low_balance = env_parse("ALERT_LOW_BALANCE", 0)?;
+        let alert_failed_swaps = env_parse("ALERT_FAILED_SWAPS", 3)?;
+        let retention_days = env_parse("RETENTION_DAYS", 0)?;
+        let retention_mode = env_parse("RETENTION_MODE", RetentionMode::default())?;
 
         let fee_rate = env::var("FEE_RATE")
             .unwrap_or_else(|_| "0.005".to_string())
             .parse()
             .map_err(|e| BrokerError::Other(anyhow::anyhow!("Invalid FEE_RATE: {}", e)))?;
+        let fee_skew = env_parse("FEE_SKEW", 0.0)?;
+        let min_fee = env_parse("MIN_FEE", 0)?;
 
         let min_swap_amount = env::var("MIN_SWAP_AMOUNT")
             .unwrap_or_else(|_| "1".to_string())
@@ -87,6 +410,48 @@ impl Config {
                 BrokerError::Other(anyhow::anyhow!("Invalid QUOTE_EXPIRY_SECONDS: {}", e))
             })?;
 
+        let quote_extension_seconds = env_parse("QUOTE_EXTENSION_SECONDS", 0)?;
+        let risk_daily_volume_per_client = env_parse("RISK_DAILY_VOLUME_PER_CLIENT", 0)?;
+        let risk_max_open_quotes_per_client = env_parse("RISK_MAX_OPEN_QUOTES_PER_CLIENT", 0)?;
+        let risk_max_exposure = env_parse("RISK_MAX_EXPOSURE", 0)?;
+        let refund_locktime_seconds = env_parse("REFUND_LOCKTIME_SECONDS", 3600)?;
+        let rebalance_threshold = env_parse("REBALANCE_THRESHOLD", 0)?;
+        let rebalance_target = env_parse("REBALANCE_TARGET", 0)?;
+        let consolidation_threshold = env_parse("CONSOLIDATION_THRESHOLD", 100)?;
+        let proof_selection = env_parse("PROOF_SELECTION", SelectionStrategy::default())?;
+        let require_dleq = env_parse("REQUIRE_DLEQ", true)?;
+
+        let price_feed = env::var("PRICE_FEED")
+            .ok()
+            .map(|p| p.trim().to_lowercase())
+            .filter(|p| !p.is_empty() && p != "none");
+        let fixed_prices = match env::var("FIXED_PRICES") {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| BrokerError::Other(anyhow::anyhow!("Invalid FIXED_PRICES JSON: {}", e)))?,
+            Err(_) => HashMap::new(),
+        };
+        let price_cache_seconds = env_parse("PRICE_CACHE_SECONDS", 60)?;
+        let price_spread = env_parse("PRICE_SPREAD", 0.01)?;
+
+        let retry_max_attempts = env_parse("RETRY_MAX_ATTEMPTS", 3)?;
+        let retry_base_delay_ms = env_parse("RETRY_BASE_DELAY_MS", 250)?;
+        let retry_max_delay_ms = env_parse("RETRY_MAX_DELAY_MS", 5000)?;
+        let retry_jitter = env_parse("RETRY_JITTER", 0.2)?;
+
+        let circuit_failure_threshold = env_parse("CIRCUIT_FAILURE_THRESHOLD", 5)?;
+        let circuit_cool_down_seconds = env_parse("CIRCUIT_COOL_DOWN_SECONDS", 30)?;
+
+        let timeout_mint_quote_seconds = env_parse("TIMEOUT_MINT_QUOTE_SECONDS", 10)?;
+        let timeout_mint_seconds = env_parse("TIMEOUT_MINT_SECONDS", 30)?;
+        let timeout_swap_seconds = env_parse("TIMEOUT_SWAP_SECONDS", 30)?;
+        let timeout_checkstate_seconds = env_parse("TIMEOUT_CHECKSTATE_SECONDS", 10)?;
+        let timeout_melt_seconds = env_parse("TIMEOUT_MELT_SECONDS", 120)?;
+        let timeout_default_seconds = env_parse("TIMEOUT_DEFAULT_SECONDS", 30)?;
+
+        let swap_workers = env_parse("SWAP_WORKERS", 8)?;
+        let swap_mint_concurrency = env_parse("SWAP_MINT_CONCURRENCY", 4)?;
+        let swap_queue_size = env_parse("SWAP_QUEUE_SIZE", 64)?;
+
         // Parse mints from JSON array
         let mints_json = env::var("MINTS")
             .map_err(|_| BrokerError::Other(anyhow::anyhow!("MINTS environment variable is required")))?;
@@ -94,28 +459,498 @@ impl Config {
         let mints: Vec<MintConfig> = serde_json::from_str(&mints_json)
             .map_err(|e| BrokerError::Other(anyhow::anyhow!("Invalid MINTS JSON: {}", e)))?;
 
-        if mints.is_empty() {
-            return Err(BrokerError::Other(anyhow::anyhow!(
-                "At least one mint must be configured"
-            )));
-        }
+        let pairs: Vec<PairConfig> = match env::var("PAIRS") {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| BrokerError::Other(anyhow::anyhow!("Invalid PAIRS JSON: {}", e)))?,
+            Err(_) => Vec::new(),
+        };
 
-        Ok(Config {
+        let fee_tiers: Vec<FeeTier> = match env::var("FEE_TIERS") {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| BrokerError::Other(anyhow::anyhow!("Invalid FEE_TIERS JSON: {}", e)))?,
+            Err(_) => Vec::new(),
+        };
+
+        let volume_discounts: Vec<VolumeDiscount> = match env::var("VOLUME_DISCOUNTS") {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| {
+                BrokerError::Other(anyhow::anyhow!("Invalid VOLUME_DISCOUNTS JSON: {}", e))
+            })?,
+            Err(_) => Vec::new(),
+        };
+
+        let initial_liquidity: Vec<InitialLiquidity> = match env::var("INITIAL_LIQUIDITY") {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| {
+                BrokerError::Other(anyhow::anyhow!("Invalid INITIAL_LIQUIDITY JSON: {}", e))
+            })?,
+            Err(_) => Vec::new(),
+        };
+
+        let config = Config {
             host,
             port,
             database_url,
+            db_max_connections,
+            sqlite_wal,
+            sqlite_busy_timeout_ms,
+            sqlite_synchronous,
+            instance_id,
+            lease_ttl_seconds,
+            encryption_key,
+            encryption_key_file,
+            identity_key,
+            identity_key_file,
+            tls_cert_path,
+            tls_key_path,
             log_level,
             cors_origins,
+            rate_limit_per_minute,
+            rate_limit_burst,
+            trust_proxy,
+            require_api_key,
+            client_history_auth,
+            max_body_bytes,
+            request_timeout_seconds,
+            admin_token,
+            nostr_secret_key,
+            nostr_relays,
+            nostr_publish_relays,
+            nostr_publish_interval_seconds,
+            webhook_secret,
+            webhook_allowed_hosts,
+            alert_webhook_url,
+            alert_telegram_bot_token,
+            alert_telegram_chat_id,
+            alert_low_balance,
+            alert_failed_swaps,
+            retention_days,
+            retention_mode,
             fee_rate,
+            fee_skew,
+            fee_tiers,
+            min_fee,
+            volume_discounts,
             min_swap_amount,
             max_swap_amount,
             quote_expiry_seconds,
+            quote_extension_seconds,
+            risk_daily_volume_per_client,
+            risk_max_open_quotes_per_client,
+            risk_max_exposure,
+            refund_locktime_seconds,
+            rebalance_threshold,
+            rebalance_target,
+            consolidation_threshold,
+            proof_selection,
+            require_dleq,
+            price_feed,
+            fixed_prices,
+            price_cache_seconds,
+            price_spread,
+            retry_max_attempts,
+            retry_base_delay_ms,
+            retry_max_delay_ms,
+            retry_jitter,
+            circuit_failure_threshold,
+            circuit_cool_down_seconds,
+            timeout_mint_quote_seconds,
+            timeout_mint_seconds,
+            timeout_swap_seconds,
+            timeout_checkstate_seconds,
+            timeout_melt_seconds,
+            timeout_default_seconds,
+            swap_workers,
+            swap_mint_concurrency,
+            swap_queue_size,
             mints,
+            pairs,
+            initial_liquidity,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Check settings that parse but make no sense together
+    pub fn validate(&self) -> Result<(), BrokerError> {
+        let invalid = |msg: String| Err(BrokerError::Other(anyhow::anyhow!(msg)));
+
+        if self.mints.is_empty() {
+            return invalid("At least one mint must be configured".to_string());
+        }
+        for mint in &self.mints {
+            if mint.unit.trim().is_empty() || mint.units.iter().any(|u| u.trim().is_empty()) {
+                return invalid(format!("Mint {} has no unit", mint.mint_url));
+            }
+            if mint.mint_url.contains(crate::types::UNIT_SEPARATOR) {
+                return invalid(format!(
+                    "Mint URL {} must not contain '{}'",
+                    mint.mint_url,
+                    crate::types::UNIT_SEPARATOR
+                ));
+            }
+            let units: Vec<String> = std::iter::once(&mint.unit)
+                .chain(&mint.units)
+                .map(|u| u.to_lowercase())
+                .collect();
+            if (1..units.len()).any(|i| units[..i].contains(&units[i])) {
+                return invalid(format!("Mint {} lists a unit twice", mint.mint_url));
+            }
+        }
+        if self.db_max_connections == 0 {
+            return invalid("DB_MAX_CONNECTIONS must be at least 1".to_string());
+        }
+        if self.instance_id.trim().is_empty() {
+            return invalid("INSTANCE_ID must not be empty".to_string());
+        }
+        if self.lease_ttl_seconds == 0 {
+            return invalid("LEASE_TTL_SECONDS must be at least 1".to_string());
+        }
+        if let Err(e) = crate::cors::validate_origins(&self.cors_origins) {
+            return invalid(e);
+        }
+        if self.tls_cert_path.is_some() != self.tls_key_path.is_some() {
+            return invalid("TLS_CERT_PATH and TLS_KEY_PATH must be set together".to_string());
+        }
+        if self.tls_cert_path.is_some() && !cfg!(feature = "tls") {
+            return invalid(
+                "TLS_CERT_PATH is set but the broker was built without the `tls` feature"
+                    .to_string(),
+            );
+        }
+        for (i, mint) in self.mints.iter().enumerate() {
+            if self.mints[..i].iter().any(|m| m.mint_url == mint.mint_url) {
+                return invalid(format!("Mint {} is configured twice", mint.mint_url));
+            }
+        }
+        if !(0.0..1.0).contains(&self.fee_rate) {
+            return invalid(format!("FEE_RATE {} must be in [0, 1)", self.fee_rate));
+        }
+        if !(self.fee_skew.is_finite() && self.fee_skew >= 0.0) {
+            return invalid(format!("FEE_SKEW {} must be at least 0", self.fee_skew));
+        }
+        for tier in &self.fee_tiers {
+            if !(0.0..1.0).contains(&tier.fee_rate) {
+                return invalid(format!(
+                    "FEE_TIERS rate {} from {} sats must be in [0, 1)",
+                    tier.fee_rate, tier.min_amount
+                ));
+            }
+        }
+        if self.min_fee >= self.min_swap_amount.max(1) {
+            return invalid(format!(
+                "MIN_FEE {} must be below MIN_SWAP_AMOUNT {}",
+                self.min_fee, self.min_swap_amount
+            ));
+        }
+        for discount in &self.volume_discounts {
+            if !(0.0..=1.0).contains(&discount.discount) {
+                return invalid(format!(
+                    "VOLUME_DISCOUNTS discount {} from {} sats must be in [0, 1]",
+                    discount.discount, discount.min_volume
+                ));
+            }
+        }
+        if !(0.0..1.0).contains(&self.price_spread) {
+            return invalid(format!("PRICE_SPREAD {} must be in [0, 1)", self.price_spread));
+        }
+        if self.min_swap_amount > self.max_swap_amount {
+            return invalid(format!(
+                "MIN_SWAP_AMOUNT {} is above MAX_SWAP_AMOUNT {}",
+                self.min_swap_amount, self.max_swap_amount
+            ));
+        }
+        if self.quote_expiry_seconds == 0 {
+            return invalid("QUOTE_EXPIRY_SECONDS must be positive".to_string());
+        }
+        // Extended quotes hold their liquidity all that time
+        if self.quote_extension_seconds > 3600 {
+            return invalid(format!(
+                "QUOTE_EXTENSION_SECONDS {} is above 3600",
+                self.quote_extension_seconds
+            ));
+        }
+        if self.max_body_bytes == 0 {
+            return invalid("MAX_BODY_BYTES must be positive".to_string());
+        }
+        if self.request_timeout_seconds == 0 {
+            return invalid("REQUEST_TIMEOUT_SECONDS must be positive".to_string());
+        }
+        if self.retry_max_attempts == 0 {
+            return invalid("RETRY_MAX_ATTEMPTS must be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.retry_jitter) {
+            return invalid(format!("RETRY_JITTER {} must be in [0, 1]", self.retry_jitter));
+        }
+        if self.circuit_failure_threshold == 0 {
+            return invalid("CIRCUIT_FAILURE_THRESHOLD must be at least 1".to_string());
+        }
+        for (name, seconds) in [
+            ("TIMEOUT_MINT_QUOTE_SECONDS", self.timeout_mint_quote_seconds),
+            ("TIMEOUT_MINT_SECONDS", self.timeout_mint_seconds),
+            ("TIMEOUT_SWAP_SECONDS", self.timeout_swap_seconds),
+            ("TIMEOUT_CHECKSTATE_SECONDS", self.timeout_checkstate_seconds),
+            ("TIMEOUT_MELT_SECONDS", self.timeout_melt_seconds),
+            ("TIMEOUT_DEFAULT_SECONDS", self.timeout_default_seconds),
+        ] {
+            if seconds == 0 {
+                return invalid(format!("{} must be positive", name));
+            }
+        }
+        if self.swap_workers == 0 {
+            return invalid("SWAP_WORKERS must be at least 1".to_string());
+        }
+        if self.swap_mint_concurrency == 0 {
+            return invalid("SWAP_MINT_CONCURRENCY must be at least 1".to_string());
+        }
+        if self.alert_telegram_bot_token.is_some() != self.alert_telegram_chat_id.is_some() {
+            return invalid(
+                "ALERT_TELEGRAM_BOT_TOKEN and ALERT_TELEGRAM_CHAT_ID must be set together"
+                    .to_string(),
+            );
+        }
+        if let Some(url) = &self.alert_webhook_url {
+            if !reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) {
+                return invalid(format!("ALERT_WEBHOOK_URL {} must be an http(s) URL", url));
+            }
+        }
+
+        let broker_config = self.broker_config();
+        for pair in &self.pairs {
+            let name = format!("{} -> {}", pair.source_mint, pair.target_mint);
+            for url in [&pair.source_mint, &pair.target_mint] {
+                if !broker_config.mints.iter().any(|m| &m.mint_url == url) {
+                    return invalid(format!("Pair {} uses unconfigured mint {}", name, url));
+                }
+            }
+
+            let terms = broker_config.pair_terms(&pair.source_mint, &pair.target_mint);
+            if !(0.0..1.0).contains(&terms.fee_rate) {
+                return invalid(format!("Pair {} fee rate must be in [0, 1)", name));
+            }
+            if terms.min_swap_amount > terms.max_swap_amount {
+                return invalid(format!("Pair {} minimum is above its maximum", name));
+            }
+        }
+
+        for (i, entry) in self.initial_liquidity.iter().enumerate() {
+            let url = &entry.mint_url;
+            if !broker_config.mints.iter().any(|m| &m.mint_url == url) {
+                return invalid(format!("INITIAL_LIQUIDITY uses unconfigured mint {}", url));
+            }
+            if self.initial_liquidity[..i].iter().any(|e| &e.mint_url == url) {
+                return invalid(format!("INITIAL_LIQUIDITY lists mint {} twice", url));
+            }
+            if entry.funding == FundingMode::EcashTokenFile && entry.token_file.is_none() {
+                return invalid(format!(
+                    "INITIAL_LIQUIDITY for {} needs a token_file with {}",
+                    url, entry.funding
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Re-read the `.env` file and environment, overriding previously loaded values
+    pub fn reload() -> Result<Self, BrokerError> {
+        dotenvy::dotenv_override().ok();
+        Self::from_env()
+    }
+
+    /// Build the broker's identity key from the configured key or key file
+    pub fn broker_identity(&self) -> Result<crate::identity::BrokerIdentity, BrokerError> {
+        match &self.identity_key {
+            Some(key) => crate::identity::BrokerIdentity::from_hex(key),
+            None => crate::identity::BrokerIdentity::load_or_create(std::path::Path::new(
+                &self.identity_key_file,
+            )),
+        }
+    }
+
+    /// Build the cipher for database secrets from the configured key or key file
+    pub fn secret_cipher(&self) -> Result<crate::encryption::SecretCipher, BrokerError> {
+        match &self.encryption_key {
+            Some(key) => crate::encryption::SecretCipher::from_hex(key),
+            None => crate::encryption::SecretCipher::load_or_create(std::path::Path::new(
+                &self.encryption_key_file,
+            )),
+        }
+    }
+
+    /// Pool size and SQLite pragmas for the database
+    pub fn database_options(&self) -> DatabaseOptions {
+        DatabaseOptions {
+            max_connections: self.db_max_connections,
+            wal: self.sqlite_wal,
+            busy_timeout: Duration::from_millis(self.sqlite_busy_timeout_ms),
+            synchronous: self.sqlite_synchronous,
+        }
+    }
+
+    /// Leases this instance takes in `db`, shared with other instances
+    pub fn leases(&self, db: &Database) -> Leases {
+        Leases::new(Arc::new(db.clone()), self.instance_id.trim())
+            .with_ttl(Duration::from_secs(self.lease_ttl_seconds))
+    }
+
+    /// Rate limit for the public API, if enabled
+    pub fn rate_limit(&self) -> Option<RateLimitConfig> {
+        (self.rate_limit_per_minute > 0).then_some(RateLimitConfig {
+            requests_per_minute: self.rate_limit_per_minute,
+            burst: self.rate_limit_burst,
+            trust_proxy: self.trust_proxy,
+        })
+    }
+
+    /// Body size and time limits for API requests
+    pub fn request_limits(&self) -> RequestLimits {
+        RequestLimits {
+            max_body_bytes: self.max_body_bytes,
+            timeout: Duration::from_secs(self.request_timeout_seconds),
+        }
+    }
+
+    /// Retry policy for wallet calls to mints
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.retry_max_attempts,
+            base_delay: Duration::from_millis(self.retry_base_delay_ms),
+            max_delay: Duration::from_millis(self.retry_max_delay_ms),
+            jitter: self.retry_jitter,
+        }
+    }
+
+    /// Pricing of new quotes from the configured fee rates
+    pub fn pricing_strategy(&self) -> Arc<dyn PricingStrategy> {
+        Arc::new(InventorySkew::new(self.fee_skew))
+    }
+
+    /// Circuit breaker settings for mint calls
+    pub fn circuit_breaker(&self) -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_threshold: self.circuit_failure_threshold,
+            cool_down: Duration::from_secs(self.circuit_cool_down_seconds),
+        }
+    }
+
+    /// Timeouts of wallet calls to mints, per operation
+    pub fn mint_timeouts(&self) -> MintTimeouts {
+        MintTimeouts {
+            mint_quote: Duration::from_secs(self.timeout_mint_quote_seconds),
+            mint: Duration::from_secs(self.timeout_mint_seconds),
+            swap: Duration::from_secs(self.timeout_swap_seconds),
+            check_state: Duration::from_secs(self.timeout_checkstate_seconds),
+            melt: Duration::from_secs(self.timeout_melt_seconds),
+            other: Duration::from_secs(self.timeout_default_seconds),
+        }
+    }
+
+    /// Concurrency limits of accepts and completes
+    pub fn job_queue(&self) -> JobQueueConfig {
+        JobQueueConfig {
+            workers: self.swap_workers,
+            per_mint: self.swap_mint_concurrency,
+            max_queued: self.swap_queue_size,
+        }
+    }
+
+    /// Build the alerter for the configured sinks, if any
+    pub fn alerter(&self) -> Result<Option<Alerter>, BrokerError> {
+        let mut alerter = Alerter::new(AlertThresholds {
+            low_balance: self.alert_low_balance,
+            failed_swaps: self.alert_failed_swaps,
+        });
+        let mut configured = false;
+
+        if let Some(url) = &self.alert_webhook_url {
+            alerter = alerter.sink(Arc::new(WebhookAlertSink::new(url)?));
+            configured = true;
+        }
+        if let (Some(token), Some(chat_id)) =
+            (&self.alert_telegram_bot_token, &self.alert_telegram_chat_id)
+        {
+            alerter = alerter.sink(Arc::new(TelegramAlertSink::new(token, chat_id)?));
+            configured = true;
+        }
+
+        Ok(configured.then_some(alerter))
+    }
+
+    /// Retention policy for settled records, if pruning is enabled
+    pub fn retention_policy(&self) -> Option<RetentionPolicy> {
+        (self.retention_days > 0).then_some(RetentionPolicy {
+            days: self.retention_days,
+            mode: self.retention_mode,
         })
     }
 
+    /// Build the configured price feed, if any
+    pub fn price_feed(&self) -> Result<Option<Arc<dyn PriceFeed>>, BrokerError> {
+        let feed: Arc<dyn PriceFeed> = match self.price_feed.as_deref() {
+            None => return Ok(None),
+            Some("fixed") => {
+                return Ok(Some(Arc::new(FixedPriceFeed::new(self.fixed_prices.clone()))))
+            }
+            Some("coinbase") => Arc::new(CoinbasePriceFeed::new()?),
+            Some("kraken") => Arc::new(KrakenPriceFeed::new()?),
+            Some(other) => {
+                return Err(BrokerError::Other(anyhow::anyhow!(
+                    "Unknown PRICE_FEED: {} (expected coinbase, kraken or fixed)",
+                    other
+                )))
+            }
+        };
+
+        Ok(Some(Arc::new(CachedPriceFeed::new(
+            feed,
+            Duration::from_secs(self.price_cache_seconds),
+        ))))
+    }
+
+    /// Build the broker configuration from the server configuration
+    pub fn broker_config(&self) -> crate::types::BrokerConfig {
+        crate::types::BrokerConfig {
+            mints: self.mints.iter().flat_map(MintConfig::wallets).collect(),
+            pairs: self.pairs.clone(),
+            fee_rate: self.fee_rate,
+            min_swap_amount: self.min_swap_amount,
+            max_swap_amount: self.max_swap_amount,
+            quote_expiry_seconds: self.quote_expiry_seconds,
+            quote_extension_seconds: self.quote_extension_seconds,
+            refund_locktime_seconds: self.refund_locktime_seconds,
+            rebalance_threshold: self.rebalance_threshold,
+            rebalance_target: self.rebalance_target,
+            consolidation_threshold: self.consolidation_threshold,
+            price_spread: self.price_spread,
+            risk: RiskLimits {
+                daily_volume_per_client: self.risk_daily_volume_per_client,
+                max_open_quotes_per_client: self.risk_max_open_quotes_per_client,
+                max_exposure: self.risk_max_exposure,
+            },
+            fees: FeeSchedule {
+                tiers: self.fee_tiers.clone(),
+                min_fee: self.min_fee,
+                volume_discounts: self.volume_discounts.clone(),
+            },
+        }
+    }
+
     /// Get server address
     pub fn server_address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
 }
+
+/// Parse an environment variable, falling back to a default when unset
+fn env_parse<T>(key: &str, default: T) -> Result<T, BrokerError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map_err(|e| BrokerError::Other(anyhow::anyhow!("Invalid {}: {}", key, e))),
+        Err(_) => Ok(default),
+    }
+}
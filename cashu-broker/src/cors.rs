@@ -0,0 +1,218 @@
+//! CORS allowlist for browser wallets
+//!
+//! `CORS_ORIGINS` takes `*`, exact origins (`https://wallet.example.com`) and
+//! wildcard subdomains (`https://*.example.com`, which does not match the bare
+//! `https://example.com`). Preflight requests are answered for every route,
+//! including the POST swap endpoints.
+
+use crate::api::{NEXT_CURSOR_HEADER, TOTAL_COUNT_HEADER};
+use crate::api_keys::API_KEY_HEADER;
+use crate::idempotency::{IDEMPOTENCY_KEY_HEADER, REPLAYED_HEADER};
+use crate::request_id::REQUEST_ID_HEADER;
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::warn;
+
+/// How long browsers may cache a preflight response
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// One entry of the origin allowlist
+#[derive(Debug, Clone, PartialEq)]
+enum OriginPattern {
+    Exact(String),
+    /// `scheme://*.suffix`: any subdomain of `suffix` (which may include a port)
+    Subdomain {
+        scheme: String,
+        suffix: String,
+    },
+}
+
+impl OriginPattern {
+    fn parse(origin: &str) -> Result<Self, String> {
+        let origin = origin.trim().trim_end_matches('/').to_lowercase();
+        let (scheme, host) = origin
+            .split_once("://")
+            .filter(|(scheme, _)| matches!(*scheme, "http" | "https"))
+            .ok_or_else(|| format!("CORS origin {} must start with http:// or https://", origin))?;
+
+        if host.is_empty() || host.contains('/') {
+            return Err(format!("CORS origin {} must not have a path", origin));
+        }
+
+        match host.strip_prefix("*.") {
+            Some(suffix) if !suffix.is_empty() && !suffix.contains('*') => Ok(Self::Subdomain {
+                scheme: format!("{}://", scheme),
+                suffix: format!(".{}", suffix),
+            }),
+            Some(_) => Err(format!("Invalid wildcard CORS origin {}", origin)),
+            None if host.contains('*') => Err(format!(
+                "Wildcards are only allowed as a leading *. in {}",
+                origin
+            )),
+            None => Ok(Self::Exact(origin)),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_lowercase();
+        match self {
+            Self::Exact(allowed) => origin == *allowed,
+            Self::Subdomain { scheme, suffix } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|host| host.strip_suffix(suffix.as_str()))
+                .is_some_and(|subdomain| {
+                    !subdomain.is_empty() && subdomain.split('.').all(is_dns_label)
+                }),
+        }
+    }
+}
+
+fn is_dns_label(label: &str) -> bool {
+    !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Check that every configured origin can be parsed
+pub fn validate_origins(origins: &[String]) -> Result<(), String> {
+    origins
+        .iter()
+        .filter(|o| !o.trim().is_empty() && o.trim() != "*")
+        .try_for_each(|o| OriginPattern::parse(o).map(|_| ()))
+}
+
+/// Build the CORS layer for the configured origins
+///
+/// Invalid entries are skipped with a warning; `Config::validate` rejects
+/// them before the server starts.
+pub fn cors_layer(origins: &[String]) -> CorsLayer {
+    if origins.iter().any(|o| o.trim() == "*") {
+        return CorsLayer::permissive();
+    }
+
+    let patterns: Vec<OriginPattern> = origins
+        .iter()
+        .filter(|o| !o.trim().is_empty())
+        .filter_map(|o| match OriginPattern::parse(o) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                warn!("Ignoring CORS origin: {}", e);
+                None
+            }
+        })
+        .collect();
+
+    let allow_origin = if patterns
+        .iter()
+        .all(|p| matches!(p, OriginPattern::Exact(_)))
+    {
+        AllowOrigin::list(patterns.iter().filter_map(|p| match p {
+            OriginPattern::Exact(origin) => HeaderValue::from_str(origin).ok(),
+            OriginPattern::Subdomain { .. } => None,
+        }))
+    } else {
+        AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+            origin
+                .to_str()
+                .is_ok_and(|origin| patterns.iter().any(|p| p.matches(origin)))
+        })
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            HeaderName::from_static(API_KEY_HEADER),
+            HeaderName::from_static(IDEMPOTENCY_KEY_HEADER),
+            HeaderName::from_static(REQUEST_ID_HEADER),
+        ])
+        .expose_headers([
+            header::RETRY_AFTER,
+            HeaderName::from_static(TOTAL_COUNT_HEADER),
+            HeaderName::from_static(NEXT_CURSOR_HEADER),
+            HeaderName::from_static(REPLAYED_HEADER),
+            HeaderName::from_static(REQUEST_ID_HEADER),
+        ])
+        .max_age(PREFLIGHT_MAX_AGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, extract::Request, routing::post, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_origin_patterns() {
+        let exact = OriginPattern::parse("https://wallet.example.com/").unwrap();
+        assert!(exact.matches("https://wallet.example.com"));
+        assert!(exact.matches("https://Wallet.Example.com"));
+        assert!(!exact.matches("http://wallet.example.com"));
+
+        let wildcard = OriginPattern::parse("https://*.example.com").unwrap();
+        assert!(wildcard.matches("https://app.example.com"));
+        assert!(wildcard.matches("https://a.b.example.com"));
+        assert!(!wildcard.matches("https://example.com"));
+        assert!(!wildcard.matches("https://example.com.evil.io"));
+        assert!(!wildcard.matches("https://evilexample.com"));
+        assert!(!wildcard.matches("http://app.example.com"));
+        assert!(!wildcard.matches("https://app.example.com:8443"));
+
+        let with_port = OriginPattern::parse("http://*.local.test:8080").unwrap();
+        assert!(with_port.matches("http://wallet.local.test:8080"));
+
+        assert!(OriginPattern::parse("wallet.example.com").is_err());
+        assert!(OriginPattern::parse("https://wallet.example.com/app").is_err());
+        assert!(OriginPattern::parse("https://app.*.example.com").is_err());
+        assert!(OriginPattern::parse("https://*.").is_err());
+    }
+
+    #[test]
+    fn test_validate_origins() {
+        let origins = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert!(validate_origins(&origins(&["*"])).is_ok());
+        assert!(validate_origins(&origins(&["https://a.com", "https://*.b.com", ""])).is_ok());
+        assert!(validate_origins(&origins(&["https://a.com", "ftp://b.com"])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_preflight() {
+        let app = Router::new()
+            .route("/quote", post(|| async { "ok" }))
+            .layer(cors_layer(&["https://*.example.com".to_string()]));
+
+        let preflight = |origin: &str| {
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/quote")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .header(
+                    header::ACCESS_CONTROL_REQUEST_HEADERS,
+                    "content-type,idempotency-key",
+                )
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(preflight("https://wallet.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://wallet.example.com"
+        );
+        assert!(response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+
+        let response = app.oneshot(preflight("https://evil.io")).await.unwrap();
+        assert!(!response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+}
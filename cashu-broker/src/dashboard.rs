@@ -0,0 +1,344 @@
+//! Status page for operators
+//!
+//! `GET /dashboard` renders the broker's state as a plain HTML page that
+//! refreshes itself: liquidity per mint, mint health and circuits, swap counts
+//! and success rate over the last day, and the latest quotes. It reads the
+//! same state as `/liquidity`, `/mints/health` and `/metrics`, for operators
+//! who don't run Prometheus and Grafana. Like the admin API it is only served
+//! with an admin token set, and needs that token; since browsers can't send a
+//! bearer header on their own, the token is also taken as the password of
+//! HTTP Basic auth (any user name), which makes the browser prompt for it.
+
+use crate::admin;
+use crate::api::{ApiError, AppState};
+use crate::circuit_breaker::CircuitState;
+use crate::db::{QuoteFilter, QuoteRecord};
+use crate::types::SwapStatus;
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{Duration, Utc};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Latest quotes shown
+const RECENT_QUOTES: i64 = 20;
+
+/// Seconds between refreshes of the page
+const REFRESH_SECONDS: u32 = 15;
+
+/// Create the dashboard router, guarded by the admin token
+pub fn router(admin_token: String) -> Router<AppState> {
+    let admin_token: Arc<str> = admin_token.into();
+
+    Router::new()
+        .route("/dashboard", get(show))
+        .route_layer(middleware::from_fn(move |req: Request, next: Next| {
+            let admin_token = admin_token.clone();
+            async move { require_token(&admin_token, req, next).await }
+        }))
+}
+
+/// Ask for the admin token unless the request carries it
+async fn require_token(admin_token: &str, req: Request, next: Next) -> Response {
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(presented_token);
+
+    match provided {
+        Some(token) if admin::tokens_match(&token, admin_token) => next.run(req).await,
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"cashu-broker\"")],
+            "Admin token required",
+        )
+            .into_response(),
+    }
+}
+
+/// The token of a Bearer authorization, or the password of a Basic one
+fn presented_token(value: &str) -> Option<String> {
+    if let Some(token) = value.strip_prefix("Bearer ") {
+        return Some(token.to_string());
+    }
+    let decoded = BASE64.decode(value.strip_prefix("Basic ")?.trim()).ok()?;
+    let credentials = String::from_utf8(decoded).ok()?;
+    credentials
+        .split_once(':')
+        .map(|(_, password)| password.to_string())
+}
+
+/// Everything the page shows
+#[derive(Debug, Clone)]
+pub struct DashboardView {
+    pub generated_at: String,
+    pub maintenance: bool,
+    pub mints: Vec<MintRow>,
+    pub completed: u64, // Over the last 24 hours
+    pub failed: u64,
+    pub open: u64, // Pending or accepted
+    pub volume: u64,
+    pub fees: u64,
+    pub recent: Vec<QuoteRecord>,
+}
+
+impl DashboardView {
+    /// Completed share of the swaps that finished, if any did
+    pub fn success_rate(&self) -> Option<f64> {
+        let finished = self.completed + self.failed;
+        (finished > 0).then(|| self.completed as f64 / finished as f64)
+    }
+}
+
+/// Liquidity and health of one mint
+#[derive(Debug, Clone)]
+pub struct MintRow {
+    pub name: String,
+    pub mint_url: String,
+    pub balance: u64,
+    pub reserved: u64,
+    pub locked: u64,
+    pub available: u64,
+    pub quarantined: u64,
+    pub healthy: bool,
+    pub circuit: CircuitState,
+    pub last_error: Option<String>,
+}
+
+async fn show(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let view = view(&state).await?;
+    Ok(([(header::CACHE_CONTROL, "no-store")], Html(render(&view))).into_response())
+}
+
+/// Gather the page's state from the broker and database
+async fn view(state: &AppState) -> Result<DashboardView, ApiError> {
+    let liquidity = state.broker.get_liquidity_status().await;
+    let health = state.broker.get_mint_health();
+    let mints = liquidity
+        .mints
+        .into_iter()
+        .map(|balance| {
+            let health = health.iter().find(|h| h.mint_url == balance.mint_url);
+            MintRow {
+                healthy: balance.healthy,
+                circuit: health
+                    .map(|h| h.circuit.state)
+                    .unwrap_or(CircuitState::Closed),
+                last_error: health.and_then(|h| h.health.last_error.clone()),
+                name: balance.name,
+                mint_url: balance.mint_url,
+                balance: balance.balance,
+                reserved: balance.reserved,
+                locked: balance.locked,
+                available: balance.available,
+                quarantined: balance.quarantined,
+            }
+        })
+        .collect();
+
+    let summary = state
+        .db
+        .get_metrics_summary(Some(Utc::now() - Duration::hours(24)), None)
+        .await
+        .map_err(ApiError::from)?;
+    let completed = summary.status(SwapStatus::Completed);
+    let recent = state
+        .db
+        .list_quotes(&QuoteFilter::default(), RECENT_QUOTES)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(DashboardView {
+        generated_at: Utc::now().to_rfc3339(),
+        maintenance: state.broker.in_maintenance(),
+        mints,
+        completed: completed.count.max(0) as u64,
+        failed: summary.status(SwapStatus::Failed).count.max(0) as u64,
+        open: [SwapStatus::Pending, SwapStatus::Accepted]
+            .into_iter()
+            .map(|status| summary.status(status).count.max(0) as u64)
+            .sum(),
+        volume: completed.volume.max(0) as u64,
+        fees: completed.fees.max(0) as u64,
+        recent,
+    })
+}
+
+/// Render the page
+pub fn render(view: &DashboardView) -> String {
+    let mut html = String::new();
+    // Writing to a String can't fail
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"refresh\" content=\"{}\">\n<title>cashu-broker</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<h1>cashu-broker</h1>\n<p>Updated {}{}</p>\n",
+        REFRESH_SECONDS,
+        STYLE,
+        escape(&view.generated_at),
+        if view.maintenance {
+            " &middot; <strong class=\"bad\">maintenance mode</strong>"
+        } else {
+            ""
+        },
+    );
+
+    let success_rate = view
+        .success_rate()
+        .map(|rate| format!("{:.1}%", rate * 100.0))
+        .unwrap_or_else(|| "&ndash;".to_string());
+    let _ = write!(
+        html,
+        "<h2>Last 24 hours</h2>\n<table>\n<tr><th>Completed</th><th>Failed</th>\
+         <th>Success rate</th><th>Open</th><th>Volume</th><th>Fees</th></tr>\n\
+         <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n</table>\n",
+        view.completed, view.failed, success_rate, view.open, view.volume, view.fees,
+    );
+
+    html.push_str(
+        "<h2>Mints</h2>\n<table>\n<tr><th>Mint</th><th>Balance</th><th>Available</th>\
+         <th>Reserved</th><th>Locked</th><th>Quarantined</th><th>Health</th>\
+         <th>Circuit</th><th>Last error</th></tr>\n",
+    );
+    for mint in &view.mints {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}<br><small>{}</small></td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&mint.name),
+            escape(&mint.mint_url),
+            mint.balance,
+            mint.available,
+            mint.reserved,
+            mint.locked,
+            mint.quarantined,
+            if mint.healthy { "ok" } else { "bad" },
+            if mint.healthy { "healthy" } else { "unhealthy" },
+            circuit(mint.circuit),
+            escape(mint.last_error.as_deref().unwrap_or("")),
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str(
+        "<h2>Recent quotes</h2>\n<table>\n<tr><th>Created</th><th>Quote</th>\
+         <th>From</th><th>To</th><th>In</th><th>Out</th><th>Fee</th><th>Status</th></tr>\n",
+    );
+    for quote in &view.recent {
+        let status_class = match quote.status {
+            SwapStatus::Completed => "ok",
+            SwapStatus::Failed => "bad",
+            _ => "",
+        };
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td><td class=\"{}\" title=\"{}\">{}</td></tr>",
+            escape(&quote.created_at),
+            escape(&quote.id),
+            escape(&quote.source_mint),
+            escape(&quote.target_mint),
+            quote.amount_in,
+            quote.amount_out,
+            quote.fee,
+            status_class,
+            escape(quote.error_message.as_deref().unwrap_or("")),
+            quote.status,
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:1.5em}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left}\
+th{background:#f4f4f4}small{color:#666}.ok{color:#1a7f37}.bad{color:#cf222e}";
+
+fn circuit(state: CircuitState) -> &'static str {
+    match state {
+        CircuitState::Closed => "closed",
+        CircuitState::Open => "open",
+        CircuitState::HalfOpen => "half-open",
+    }
+}
+
+/// Escape text for HTML content and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presented_token() {
+        assert_eq!(presented_token("Bearer secret"), Some("secret".to_string()));
+        let basic = format!("Basic {}", BASE64.encode("operator:secret"));
+        assert_eq!(presented_token(&basic), Some("secret".to_string()));
+        assert_eq!(presented_token("Basic !!!"), None);
+        assert_eq!(presented_token("Nostr abc"), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let view = DashboardView {
+            generated_at: "2025-01-17T00:00:00+00:00".to_string(),
+            maintenance: true,
+            mints: vec![MintRow {
+                name: "<b>Mint A</b>".to_string(),
+                mint_url: "http://mint-a.test".to_string(),
+                balance: 1000,
+                reserved: 100,
+                locked: 50,
+                available: 850,
+                quarantined: 0,
+                healthy: false,
+                circuit: CircuitState::HalfOpen,
+                last_error: Some("connection refused".to_string()),
+            }],
+            completed: 3,
+            failed: 1,
+            open: 2,
+            volume: 300,
+            fees: 3,
+            recent: Vec::new(),
+        };
+        assert_eq!(view.success_rate(), Some(0.75));
+
+        let html = render(&view);
+        assert!(html.contains("75.0%"));
+        assert!(html.contains("maintenance mode"));
+        assert!(html.contains("&lt;b&gt;Mint A&lt;/b&gt;"));
+        assert!(!html.contains("<b>Mint A</b>"));
+        assert!(html.contains("half-open"));
+        assert!(html.contains("connection refused"));
+
+        let idle = DashboardView {
+            completed: 0,
+            failed: 0,
+            ..view
+        };
+        assert_eq!(idle.success_rate(), None);
+    }
+}
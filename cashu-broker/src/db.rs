@@ -1,46 +1,333 @@
+use crate::accounting::EntryKind;
+use crate::blacklist::BlacklistKind;
+use crate::encryption::{is_encrypted_text, SecretCipher};
 use crate::error::BrokerError;
-use crate::types::SwapStatus;
-use chrono::Utc;
+use crate::jobs::JobStatus;
+use crate::types::{ClaimOrder, MintConfig, Sensitive, SwapStatus};
+use crate::webhooks;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, StreamExt};
 use serde::{Deserialize, Serialize};
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
-use sqlx::{FromRow, Row};
+use sqlx::{FromRow, QueryBuilder, Row};
+use std::fmt;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+// Backend selection: SQLite by default, Postgres with the `postgres` feature.
+// Queries use `$N` placeholders, which both drivers accept.
+
+/// Connection pool of the selected backend
+#[cfg(not(feature = "postgres"))]
+pub type DbPool = sqlx::SqlitePool;
+#[cfg(feature = "postgres")]
+pub type DbPool = sqlx::PgPool;
+
+/// Database driver of the selected backend
+#[cfg(not(feature = "postgres"))]
+pub type Db = sqlx::Sqlite;
+#[cfg(feature = "postgres")]
+pub type Db = sqlx::Postgres;
+
+/// Row type of the selected backend
+#[cfg(not(feature = "postgres"))]
+pub type DbRow = sqlx::sqlite::SqliteRow;
+#[cfg(feature = "postgres")]
+pub type DbRow = sqlx::postgres::PgRow;
+
+/// Open transaction of the selected backend, see [`Database::with_tx`]
+pub type Tx = sqlx::Transaction<'static, Db>;
+
+// Quote statuses are stored as their lowercase names, which the `quotes`
+// table constrains to the values of `SwapStatus`
+impl sqlx::Type<Db> for SwapStatus {
+    fn type_info() -> <Db as sqlx::Database>::TypeInfo {
+        <String as sqlx::Type<Db>>::type_info()
+    }
+
+    fn compatible(ty: &<Db as sqlx::Database>::TypeInfo) -> bool {
+        <String as sqlx::Type<Db>>::compatible(ty)
+    }
+}
+
+impl<'q> sqlx::Encode<'q, Db> for SwapStatus {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <Db as sqlx::Database>::ArgumentBuffer<'q>,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <String as sqlx::Encode<'q, Db>>::encode(self.to_string(), buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Db> for SwapStatus {
+    fn decode(
+        value: <Db as sqlx::Database>::ValueRef<'r>,
+    ) -> Result<Self, sqlx::error::BoxDynError> {
+        let status = <&str as sqlx::Decode<'r, Db>>::decode(value)?;
+        Ok(status.parse()?)
+    }
+}
+
+/// How often SQLite syncs writes to disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+    Off,
+    #[default]
+    Normal, // Safe against corruption in WAL mode; a power loss can drop the last commits
+    Full,
+    Extra,
+}
+
+impl fmt::Display for Synchronous {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Synchronous::Off => write!(f, "off"),
+            Synchronous::Normal => write!(f, "normal"),
+            Synchronous::Full => write!(f, "full"),
+            Synchronous::Extra => write!(f, "extra"),
+        }
+    }
+}
+
+impl FromStr for Synchronous {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" => Ok(Synchronous::Off),
+            "normal" => Ok(Synchronous::Normal),
+            "full" => Ok(Synchronous::Full),
+            "extra" => Ok(Synchronous::Extra),
+            _ => Err(format!("Invalid synchronous level: {}", s)),
+        }
+    }
+}
+
+/// Connections the pool opens by default
+#[cfg(not(feature = "postgres"))]
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 5;
+#[cfg(feature = "postgres")]
+pub const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+
+/// Pool size and SQLite pragmas for [`Database::connect`]
+///
+/// The pragmas only apply to SQLite and are ignored on Postgres.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DatabaseOptions {
+    pub max_connections: u32,
+    pub wal: bool,              // Write-ahead log, so reads don't block on a write
+    pub busy_timeout: Duration, // How long a write waits for the lock before failing
+    pub synchronous: Synchronous,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> Self {
+        Self {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            wal: true,
+            busy_timeout: Duration::from_secs(5),
+            synchronous: Synchronous::Normal,
+        }
+    }
+}
 
 /// Database connection pool
 #[derive(Clone)]
 pub struct Database {
-    pool: SqlitePool,
+    pool: DbPool,
+    cipher: Option<Arc<SecretCipher>>,
+    webhooks: bool,
 }
 
 impl Database {
-    /// Create a new database connection
+    /// Create a new database connection with the default [`DatabaseOptions`]
     pub async fn new(database_url: &str) -> Result<Self, BrokerError> {
-        let options = SqliteConnectOptions::from_str(database_url)
+        Self::connect(database_url, DatabaseOptions::default()).await
+    }
+
+    /// Create a new database connection
+    #[cfg(not(feature = "postgres"))]
+    pub async fn connect(
+        database_url: &str,
+        options: DatabaseOptions,
+    ) -> Result<Self, BrokerError> {
+        use sqlx::sqlite::{
+            SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous,
+        };
+
+        let journal_mode = if options.wal {
+            SqliteJournalMode::Wal
+        } else {
+            SqliteJournalMode::Delete
+        };
+        let synchronous = match options.synchronous {
+            Synchronous::Off => SqliteSynchronous::Off,
+            Synchronous::Normal => SqliteSynchronous::Normal,
+            Synchronous::Full => SqliteSynchronous::Full,
+            Synchronous::Extra => SqliteSynchronous::Extra,
+        };
+        let connect_options = SqliteConnectOptions::from_str(database_url)
             .map_err(|e| BrokerError::Database(e.to_string()))?
-            .create_if_missing(true);
+            .create_if_missing(true)
+            .journal_mode(journal_mode)
+            .busy_timeout(options.busy_timeout)
+            .synchronous(synchronous);
 
         let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect_with(options)
+            .max_connections(options.max_connections)
+            .connect_with(connect_options)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(Self {
+            pool,
+            cipher: None,
+            webhooks: false,
+        })
+    }
+
+    /// Create a new database connection
+    #[cfg(feature = "postgres")]
+    pub async fn connect(
+        database_url: &str,
+        options: DatabaseOptions,
+    ) -> Result<Self, BrokerError> {
+        use sqlx::postgres::PgPoolOptions;
+
+        let pool = PgPoolOptions::new()
+            .max_connections(options.max_connections)
+            .connect(database_url)
             .await
             .map_err(|e| BrokerError::Database(e.to_string()))?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            cipher: None,
+            webhooks: false,
+        })
+    }
+
+    /// Use a cipher for secret columns
+    ///
+    /// Required for quote keys; without it a swap's adaptor secret and
+    /// decrypted signature are stored in plaintext.
+    pub fn with_cipher(mut self, cipher: SecretCipher) -> Self {
+        self.cipher = Some(Arc::new(cipher));
+        self
+    }
+
+    /// Queue webhook deliveries when quotes with a callback URL change status
+    pub fn with_webhooks(mut self) -> Self {
+        self.webhooks = true;
+        self
+    }
+
+    pub fn webhooks_enabled(&self) -> bool {
+        self.webhooks
+    }
+
+    fn cipher(&self) -> Result<&SecretCipher, BrokerError> {
+        self.cipher
+            .as_deref()
+            .ok_or_else(|| BrokerError::Encryption("No encryption key configured".to_string()))
+    }
+
+    /// Encrypt a secret text column value, if there is a cipher
+    fn seal(&self, value: Option<&str>) -> Result<Option<String>, BrokerError> {
+        match (value, &self.cipher) {
+            (Some(value), Some(cipher)) => cipher.encrypt_text(value).map(Some),
+            (value, _) => Ok(value.map(str::to_string)),
+        }
+    }
+
+    /// Decrypt a secret text column value written by [`Database::seal`]
+    fn open(&self, value: Option<String>) -> Result<Option<String>, BrokerError> {
+        match value {
+            Some(value) if is_encrypted_text(&value) => self.cipher()?.decrypt_text(&value).map(Some),
+            value => Ok(value),
+        }
+    }
+
+    fn open_swap(&self, mut swap: SwapRecord) -> Result<SwapRecord, BrokerError> {
+        swap.decrypted_signature = self.open(swap.decrypted_signature)?;
+        swap.adaptor_secret = self.open(swap.adaptor_secret)?;
+        Ok(swap)
+    }
+
+    /// Migrations of the selected backend
+    fn migrator() -> sqlx::migrate::Migrator {
+        #[cfg(not(feature = "postgres"))]
+        let migrator = sqlx::migrate!("./migrations");
+        #[cfg(feature = "postgres")]
+        let migrator = sqlx::migrate!("./migrations/postgres");
+        migrator
     }
 
     /// Run database migrations
     pub async fn migrate(&self) -> Result<(), BrokerError> {
-        sqlx::migrate!("./migrations")
+        Self::migrator()
             .run(&self.pool)
             .await
             .map_err(|e| BrokerError::Database(format!("Migration failed: {}", e)))?;
         Ok(())
     }
 
+    /// Number of migrations not yet applied to the database
+    ///
+    /// Fails on a database that was never migrated.
+    pub async fn pending_migrations(&self) -> Result<usize, BrokerError> {
+        use sqlx::migrate::Migrate;
+
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+        let applied = conn
+            .list_applied_migrations()
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(Self::migrator()
+            .iter()
+            .filter(|m| !m.migration_type.is_down_migration())
+            .filter(|m| !applied.iter().any(|a| a.version == m.version))
+            .count())
+    }
+
     /// Get the underlying pool
-    pub fn pool(&self) -> &SqlitePool {
+    pub fn pool(&self) -> &DbPool {
         &self.pool
     }
+
+    /// Run `f` in a transaction, committing if it succeeds
+    ///
+    /// The transaction is rolled back if `f` returns an error or the future
+    /// is dropped. `f` runs its statements on the transaction it is given
+    /// (`.execute(&mut **tx)`) and returns a boxed future, so anything it
+    /// uses from the caller has to be moved in:
+    /// `db.with_tx(move |tx| Box::pin(async move { ... })).await`.
+    pub async fn with_tx<T, F>(&self, f: F) -> Result<T, BrokerError>
+    where
+        T: Send,
+        F: for<'t> FnOnce(&'t mut Tx) -> BoxFuture<'t, Result<T, BrokerError>> + Send,
+    {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        let value = f(&mut tx).await?;
+
+        tx.commit()
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+        Ok(value)
+    }
 }
 
 // Quote repository
@@ -51,9 +338,10 @@ impl Database {
             r#"
             INSERT INTO quotes (
                 id, source_mint, target_mint, amount_in, amount_out, fee, fee_rate,
-                broker_pubkey, adaptor_point, tweaked_pubkey,
-                status, created_at, expires_at, user_pubkey
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                mint_fee, exchange_rate, broker_pubkey, adaptor_point, tweaked_pubkey,
+                status, created_at, expires_at, user_pubkey, callback_url, claim_order
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+                      $18)
             "#,
         )
         .bind(&quote.id)
@@ -63,13 +351,17 @@ impl Database {
         .bind(quote.amount_out)
         .bind(quote.fee)
         .bind(quote.fee_rate)
+        .bind(quote.mint_fee)
+        .bind(quote.exchange_rate)
         .bind(&quote.broker_pubkey)
         .bind(&quote.adaptor_point)
         .bind(&quote.tweaked_pubkey)
-        .bind(quote.status.to_string())
+        .bind(quote.status)
         .bind(&quote.created_at)
         .bind(&quote.expires_at)
         .bind(&quote.user_pubkey)
+        .bind(&quote.callback_url)
+        .bind(quote.claim_order.to_string())
         .execute(&self.pool)
         .await
         .map_err(|e| BrokerError::Database(e.to_string()))?;
@@ -79,20 +371,12 @@ impl Database {
 
     /// Get a quote by ID
     pub async fn get_quote(&self, id: &str) -> Result<Option<QuoteRecord>, BrokerError> {
-        let result = sqlx::query_as::<_, QuoteRecord>(
-            r#"
-            SELECT id, source_mint, target_mint, amount_in, amount_out, fee, fee_rate,
-                   broker_pubkey, adaptor_point, tweaked_pubkey,
-                   status, created_at, expires_at, accepted_at, completed_at,
-                   user_pubkey, error_message
-            FROM quotes
-            WHERE id = ?
-            "#,
-        )
-        .bind(id)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| BrokerError::Database(e.to_string()))?;
+        let sql = format!("SELECT {} FROM quotes WHERE id = $1", QUOTE_COLUMNS);
+        let result = sqlx::query_as::<_, QuoteRecord>(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
 
         Ok(result)
     }
@@ -104,111 +388,133 @@ impl Database {
         status: SwapStatus,
         error_message: Option<String>,
     ) -> Result<(), BrokerError> {
-        let timestamp = Utc::now().to_rfc3339();
-        let status_str = status.to_string();
+        let id = id.to_string();
+        let webhooks = self.webhooks;
 
-        match status {
-            SwapStatus::Accepted => {
-                sqlx::query(
-                    r#"
-                    UPDATE quotes
-                    SET status = ?, accepted_at = ?
-                    WHERE id = ?
-                    "#,
-                )
-                .bind(&status_str)
-                .bind(&timestamp)
-                .bind(id)
-                .execute(&self.pool)
-                .await
-                .map_err(|e| BrokerError::Database(e.to_string()))?;
-            }
-            SwapStatus::Completed => {
-                sqlx::query(
-                    r#"
-                    UPDATE quotes
-                    SET status = ?, completed_at = ?
-                    WHERE id = ?
-                    "#,
-                )
-                .bind(&status_str)
-                .bind(&timestamp)
-                .bind(id)
-                .execute(&self.pool)
-                .await
-                .map_err(|e| BrokerError::Database(e.to_string()))?;
-            }
-            SwapStatus::Failed | SwapStatus::Expired => {
-                sqlx::query(
-                    r#"
-                    UPDATE quotes
-                    SET status = ?, error_message = ?
-                    WHERE id = ?
-                    "#,
-                )
-                .bind(&status_str)
-                .bind(&error_message)
-                .bind(id)
-                .execute(&self.pool)
-                .await
-                .map_err(|e| BrokerError::Database(e.to_string()))?;
-            }
-            _ => {
+        self.with_tx(move |tx| {
+            Box::pin(async move {
+                set_quote_status(tx, &id, status, error_message.as_deref()).await?;
+                if webhooks {
+                    queue_webhook(tx, &id, status).await?;
+                }
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Record the status of a quote's latest accept or complete step
+    pub async fn set_job_status(&self, id: &str, status: JobStatus) -> Result<(), BrokerError> {
+        sqlx::query("UPDATE quotes SET job_status = $1 WHERE id = $2")
+            .bind(status.to_string())
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Mark a quote accepted and record its swap, in one transaction
+    pub async fn accept_quote(&self, swap: &SwapRecord) -> Result<(), BrokerError> {
+        let swap = swap.clone();
+        let webhooks = self.webhooks;
+
+        self.with_tx(move |tx| {
+            Box::pin(async move {
+                set_quote_status(tx, &swap.quote_id, SwapStatus::Accepted, None).await?;
+                insert_swap(&mut **tx, &swap).await?;
+                if webhooks {
+                    queue_webhook(tx, &swap.quote_id, SwapStatus::Accepted).await?;
+                }
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Mark a quote completed and settle its swap record, in one transaction
+    ///
+    /// The secrets are encrypted like in [`Database::complete_swap`]. A quote
+    /// without a swap record is only marked completed.
+    pub async fn complete_quote(
+        &self,
+        quote_id: &str,
+        decrypted_signature: Option<&str>,
+        adaptor_secret: Option<&str>,
+    ) -> Result<(), BrokerError> {
+        let quote_id = quote_id.to_string();
+        let decrypted_signature = self.seal(decrypted_signature)?;
+        let adaptor_secret = self.seal(adaptor_secret)?;
+        let webhooks = self.webhooks;
+
+        self.with_tx(move |tx| {
+            Box::pin(async move {
+                set_quote_status(tx, &quote_id, SwapStatus::Completed, None).await?;
                 sqlx::query(
                     r#"
-                    UPDATE quotes
-                    SET status = ?
-                    WHERE id = ?
+                    UPDATE swaps
+                    SET decrypted_signature = $1, adaptor_secret = $2, completed_at = $3
+                    WHERE quote_id = $4
                     "#,
                 )
-                .bind(&status_str)
-                .bind(id)
-                .execute(&self.pool)
+                .bind(decrypted_signature)
+                .bind(adaptor_secret)
+                .bind(Utc::now().to_rfc3339())
+                .bind(&quote_id)
+                .execute(&mut **tx)
                 .await
                 .map_err(|e| BrokerError::Database(e.to_string()))?;
-            }
-        }
+                if webhooks {
+                    queue_webhook(tx, &quote_id, SwapStatus::Completed).await?;
+                }
+                Ok(())
+            })
+        })
+        .await
+    }
 
-        Ok(())
+    /// Move a quote to a final status and delete its keys, in one transaction
+    pub async fn close_quote(
+        &self,
+        id: &str,
+        status: SwapStatus,
+        error_message: Option<String>,
+    ) -> Result<(), BrokerError> {
+        let id = id.to_string();
+        let webhooks = self.webhooks;
+
+        self.with_tx(move |tx| {
+            Box::pin(async move {
+                set_quote_status(tx, &id, status, error_message.as_deref()).await?;
+                sqlx::query("DELETE FROM quote_keys WHERE quote_id = $1")
+                    .bind(&id)
+                    .execute(&mut **tx)
+                    .await
+                    .map_err(|e| BrokerError::Database(e.to_string()))?;
+                if webhooks {
+                    queue_webhook(tx, &id, status).await?;
+                }
+                Ok(())
+            })
+        })
+        .await
     }
 
-    /// List quotes with optional filters
+    /// List the most recent quotes matching a filter
     pub async fn list_quotes(
         &self,
-        status: Option<SwapStatus>,
+        filter: &QuoteFilter,
         limit: i64,
     ) -> Result<Vec<QuoteRecord>, BrokerError> {
-        let query = if let Some(status) = status {
-            sqlx::query_as::<_, QuoteRecord>(
-                r#"
-                SELECT id, source_mint, target_mint, amount_in, amount_out, fee, fee_rate,
-                       broker_pubkey, adaptor_point, tweaked_pubkey,
-                       status, created_at, expires_at, accepted_at, completed_at,
-                       user_pubkey, error_message
-                FROM quotes
-                WHERE status = ?
-                ORDER BY created_at DESC
-                LIMIT ?
-                "#,
-            )
-            .bind(status.to_string())
-            .bind(limit)
-        } else {
-            sqlx::query_as::<_, QuoteRecord>(
-                r#"
-                SELECT id, source_mint, target_mint, amount_in, amount_out, fee, fee_rate,
-                       broker_pubkey, adaptor_point, tweaked_pubkey,
-                       status, created_at, expires_at, accepted_at, completed_at,
-                       user_pubkey, error_message
-                FROM quotes
-                ORDER BY created_at DESC
-                LIMIT ?
-                "#,
-            )
-            .bind(limit)
-        };
+        let mut query = QueryBuilder::<Db>::new(format!("SELECT {} FROM quotes", QUOTE_COLUMNS));
+        push_quote_filter(&mut query, filter);
+        query
+            .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+            .push_bind(limit);
 
         let quotes = query
+            .build_query_as::<QuoteRecord>()
             .fetch_all(&self.pool)
             .await
             .map_err(|e| BrokerError::Database(e.to_string()))?;
@@ -216,103 +522,447 @@ impl Database {
         Ok(quotes)
     }
 
-    /// Delete expired quotes
-    pub async fn delete_expired_quotes(&self) -> Result<u64, BrokerError> {
-        let now = Utc::now().to_rfc3339();
+    /// List one page of quotes, newest first
+    ///
+    /// Pages are keyed on `(created_at, id)`, so rows inserted while paging
+    /// don't shift later pages. `total` counts every quote matching the filter.
+    pub async fn list_quotes_page(
+        &self,
+        filter: &QuoteFilter,
+        limit: i64,
+        cursor: Option<&QuoteCursor>,
+    ) -> Result<QuotePage, BrokerError> {
+        let mut query = QueryBuilder::<Db>::new(format!("SELECT {} FROM quotes", QUOTE_COLUMNS));
+        push_quote_filter(&mut query, filter);
+        // A row value comparison lets the (filter, created_at, id) indices seek to the cursor
+        if let Some(cursor) = cursor {
+            query
+                .push(" AND (created_at, id) < (")
+                .push_bind(cursor.created_at.clone())
+                .push(", ")
+                .push_bind(cursor.id.clone())
+                .push(")");
+        }
+        // One extra row tells us whether there is a next page
+        query
+            .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+            .push_bind(limit + 1);
 
-        let result = sqlx::query(
-            r#"
-            DELETE FROM quotes
-            WHERE status = 'pending' AND expires_at < ?
-            "#,
-        )
-        .bind(&now)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| BrokerError::Database(e.to_string()))?;
+        let mut quotes = query
+            .build_query_as::<QuoteRecord>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
 
-        Ok(result.rows_affected())
+        let next_cursor = if quotes.len() as i64 > limit {
+            quotes.truncate(limit as usize);
+            quotes.last().map(|q| QuoteCursor {
+                created_at: q.created_at.clone(),
+                id: q.id.clone(),
+            })
+        } else {
+            None
+        };
+
+        Ok(QuotePage {
+            quotes,
+            total: self.count_quotes(filter).await?,
+            next_cursor,
+        })
     }
-}
 
-// Swap repository
-impl Database {
-    /// Create a swap execution record
-    pub async fn create_swap(&self, swap: &SwapRecord) -> Result<(), BrokerError> {
-        sqlx::query(
-            r#"
-            INSERT INTO swaps (
-                id, quote_id, source_proofs, encrypted_signature, started_at
-            ) VALUES (?, ?, ?, ?, ?)
-            "#,
-        )
-        .bind(&swap.id)
-        .bind(&swap.quote_id)
-        .bind(&swap.source_proofs)
-        .bind(&swap.encrypted_signature)
-        .bind(&swap.started_at)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| BrokerError::Database(e.to_string()))?;
+    /// Count quotes matching a filter
+    pub async fn count_quotes(&self, filter: &QuoteFilter) -> Result<i64, BrokerError> {
+        let mut query = QueryBuilder::<Db>::new("SELECT COUNT(*) FROM quotes");
+        push_quote_filter(&mut query, filter);
 
-        Ok(())
+        query
+            .build_query_scalar::<i64>()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))
     }
 
-    /// Complete a swap with target proofs and adaptor secret
-    pub async fn complete_swap(
+    /// Quote counts, volume and fees per status, over an optional time window
+    pub async fn get_metrics_summary(
         &self,
-        id: &str,
-        target_proofs: &str,
-        decrypted_signature: Option<&str>,
-        adaptor_secret: Option<&str>,
-    ) -> Result<(), BrokerError> {
-        let completed_at = Utc::now().to_rfc3339();
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<MetricsSummary, BrokerError> {
+        let filter = QuoteFilter {
+            from,
+            to,
+            ..Default::default()
+        };
 
-        sqlx::query(
+        // SUM over BIGINT is NUMERIC on Postgres, so cast back for decoding
+        let mut query = QueryBuilder::<Db>::new(
+            "SELECT status, COUNT(*) AS count, \
+             CAST(COALESCE(SUM(amount_in), 0) AS BIGINT) AS volume, \
+             CAST(COALESCE(SUM(fee), 0) AS BIGINT) AS fees \
+             FROM quotes",
+        );
+        push_quote_filter(&mut query, &filter);
+        query.push(" GROUP BY status");
+
+        let by_status = query
+            .build_query_as::<StatusMetrics>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(MetricsSummary { by_status })
+    }
+
+    /// Quotes created in `[from, to)` with their swap's completion time and
+    /// the mint fees paid on them, oldest first
+    ///
+    /// Rows are fetched as the stream is polled rather than loaded at once.
+    pub fn export_quotes(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> BoxStream<'_, Result<QuoteExport, BrokerError>> {
+        sqlx::query_as::<_, QuoteExport>(
             r#"
-            UPDATE swaps
-            SET target_proofs = ?, decrypted_signature = ?, adaptor_secret = ?, completed_at = ?
-            WHERE id = ?
+            SELECT q.id, q.status, q.source_mint, q.target_mint, q.amount_in, q.amount_out,
+                q.fee, q.fee_rate, q.mint_fee, q.exchange_rate, q.created_at, q.accepted_at,
+                q.completed_at, s.completed_at AS swap_completed_at, q.error_message,
+                (SELECT CAST(COALESCE(SUM(a.amount), 0) AS BIGINT) FROM accounting a
+                 WHERE a.quote_id = q.id AND a.kind = 'mint_fee') AS mint_fees_paid
+            FROM quotes q
+            LEFT JOIN swaps s ON s.quote_id = q.id
+            WHERE ($1 IS NULL OR q.created_at >= $1) AND ($2 IS NULL OR q.created_at < $2)
+            ORDER BY q.created_at, q.id
             "#,
         )
-        .bind(target_proofs)
-        .bind(decrypted_signature)
-        .bind(adaptor_secret)
-        .bind(&completed_at)
-        .bind(id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| BrokerError::Database(e.to_string()))?;
+        .bind(from.map(|from| from.to_rfc3339()))
+        .bind(to.map(|to| to.to_rfc3339()))
+        .fetch(&self.pool)
+        .map(|row| row.map_err(|e| BrokerError::Database(e.to_string())))
+        .boxed()
+    }
 
-        Ok(())
+    /// List quotes that are still pending or accepted
+    pub async fn list_open_quotes(&self) -> Result<Vec<QuoteRecord>, BrokerError> {
+        let sql = format!(
+            "SELECT {} FROM quotes WHERE status IN ('pending', 'accepted') ORDER BY created_at ASC",
+            QUOTE_COLUMNS
+        );
+        let quotes = sqlx::query_as::<_, QuoteRecord>(&sql)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(quotes)
     }
 
-    /// Get swap by ID
-    pub async fn get_swap(&self, id: &str) -> Result<Option<SwapRecord>, BrokerError> {
-        let result = sqlx::query_as::<_, SwapRecord>(
+    /// Move a pending quote's expiry to `expires_at`, unless it was extended before
+    ///
+    /// Returns false if the quote isn't pending, already expired, was
+    /// extended before or has outputs locked for it.
+    pub async fn extend_quote(
+        &self,
+        id: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<bool, BrokerError> {
+        let now = Utc::now().to_rfc3339();
+        let result = sqlx::query(
             r#"
-            SELECT id, quote_id, source_proofs, target_proofs, encrypted_signature,
-                   decrypted_signature, adaptor_secret, started_at, completed_at
-            FROM swaps
-            WHERE id = ?
+            UPDATE quotes
+            SET expires_at = $1, extended_at = $2
+            WHERE id = $3 AND status = 'pending' AND extended_at IS NULL AND expires_at > $2
+              AND id NOT IN (
+                  SELECT quote_id FROM quote_keys WHERE locked_proofs IS NOT NULL
+              )
             "#,
         )
+        .bind(expires_at.to_rfc3339())
+        .bind(&now)
         .bind(id)
-        .fetch_optional(&self.pool)
+        .execute(&self.pool)
         .await
         .map_err(|e| BrokerError::Database(e.to_string()))?;
 
-        Ok(result)
+        Ok(result.rows_affected() == 1)
     }
 
-    /// Get swap by quote ID
-    pub async fn get_swap_by_quote(&self, quote_id: &str) -> Result<Option<SwapRecord>, BrokerError> {
-        let result = sqlx::query_as::<_, SwapRecord>(
-            r#"
-            SELECT id, quote_id, source_proofs, target_proofs, encrypted_signature,
-                   decrypted_signature, adaptor_secret, started_at, completed_at
-            FROM swaps
-            WHERE quote_id = ?
+    /// Mark pending quotes past their expiry as expired, returning their IDs
+    ///
+    /// Quotes the broker already locked outputs for are left pending, as the
+    /// outputs still have to be handed out or refunded.
+    pub async fn expire_stale_quotes(&self) -> Result<Vec<String>, BrokerError> {
+        let now = Utc::now().to_rfc3339();
+        let webhooks = self.webhooks;
+
+        self.with_tx(move |tx| {
+            Box::pin(async move {
+                let ids = sqlx::query_scalar::<_, String>(
+                    r#"
+                    UPDATE quotes
+                    SET status = 'expired', error_message = 'Quote expired'
+                    WHERE status = 'pending' AND expires_at < $1
+                      AND id NOT IN (
+                          SELECT quote_id FROM quote_keys WHERE locked_proofs IS NOT NULL
+                      )
+                    RETURNING id
+                    "#,
+                )
+                .bind(&now)
+                .fetch_all(&mut **tx)
+                .await
+                .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+                if webhooks {
+                    for id in &ids {
+                        queue_webhook(tx, id, SwapStatus::Expired).await?;
+                    }
+                }
+
+                Ok(ids)
+            })
+        })
+        .await
+    }
+
+    /// Delete expired quotes
+    pub async fn delete_expired_quotes(&self) -> Result<u64, BrokerError> {
+        let now = Utc::now().to_rfc3339();
+
+        let result = sqlx::query(
+            r#"
+            DELETE FROM quotes
+            WHERE status = 'pending' AND expires_at < $1
+            "#,
+        )
+        .bind(&now)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Columns selected into a `QuoteRecord`
+const QUOTE_COLUMNS: &str = "id, source_mint, target_mint, amount_in, amount_out, fee, fee_rate, \
+    mint_fee, exchange_rate, broker_pubkey, adaptor_point, tweaked_pubkey, status, created_at, \
+    expires_at, accepted_at, completed_at, user_pubkey, error_message, callback_url, job_status, \
+    claim_order, extended_at";
+
+/// Append a `WHERE` clause for `filter`; callers can continue it with `AND ...`
+fn push_quote_filter(query: &mut QueryBuilder<'_, Db>, filter: &QuoteFilter) {
+    query.push(" WHERE 1 = 1");
+    if let Some(status) = filter.status {
+        query.push(" AND status = ").push_bind(status);
+    }
+    if let Some(source_mint) = &filter.source_mint {
+        query.push(" AND source_mint = ").push_bind(source_mint.clone());
+    }
+    if let Some(target_mint) = &filter.target_mint {
+        query.push(" AND target_mint = ").push_bind(target_mint.clone());
+    }
+    if let Some(user_pubkey) = &filter.user_pubkey {
+        query.push(" AND user_pubkey = ").push_bind(user_pubkey.clone());
+    }
+    // Timestamps are stored as UTC RFC 3339 strings, which sort chronologically
+    if let Some(from) = filter.from {
+        query.push(" AND created_at >= ").push_bind(from.to_rfc3339());
+    }
+    if let Some(to) = filter.to {
+        query.push(" AND created_at < ").push_bind(to.to_rfc3339());
+    }
+}
+
+/// Set a quote's status, stamping `accepted_at` or `completed_at` where it applies
+async fn set_quote_status(
+    tx: &mut Tx,
+    id: &str,
+    status: SwapStatus,
+    error_message: Option<&str>,
+) -> Result<(), BrokerError> {
+    let timestamp = Utc::now().to_rfc3339();
+
+    let query = match status {
+        SwapStatus::Accepted => sqlx::query(
+            "UPDATE quotes SET status = $1, accepted_at = $2 WHERE id = $3",
+        )
+        .bind(status)
+        .bind(timestamp),
+        SwapStatus::Completed => sqlx::query(
+            "UPDATE quotes SET status = $1, completed_at = $2 WHERE id = $3",
+        )
+        .bind(status)
+        .bind(timestamp),
+        SwapStatus::Failed | SwapStatus::Expired => sqlx::query(
+            "UPDATE quotes SET status = $1, error_message = $2 WHERE id = $3",
+        )
+        .bind(status)
+        .bind(error_message.map(str::to_string)),
+        _ => sqlx::query("UPDATE quotes SET status = $1 WHERE id = $2").bind(status),
+    };
+
+    query
+        .bind(id.to_string())
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+    // A settled quote no longer holds its source proofs
+    if matches!(
+        status,
+        SwapStatus::Completed | SwapStatus::Failed | SwapStatus::Expired
+    ) {
+        sqlx::query("DELETE FROM proof_claims WHERE quote_id = $1")
+            .bind(id)
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+    }
+
+    Ok(())
+}
+
+/// Queue a webhook delivery if the quote has a callback URL
+async fn queue_webhook(tx: &mut Tx, id: &str, status: SwapStatus) -> Result<(), BrokerError> {
+    if !webhooks::notifies(status) {
+        return Ok(());
+    }
+
+    let sql = format!("SELECT {} FROM quotes WHERE id = $1", QUOTE_COLUMNS);
+    let quote = sqlx::query_as::<_, QuoteRecord>(&sql)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+    let Some(quote) = quote else {
+        return Ok(());
+    };
+    if let Some(delivery) = webhooks::delivery_for(&quote, status)? {
+        insert_webhook_delivery(&mut **tx, &delivery).await?;
+    }
+
+    Ok(())
+}
+
+/// Insert a swap record on a pool or in a transaction
+async fn insert_swap<'e, E>(executor: E, swap: &SwapRecord) -> Result<(), BrokerError>
+where
+    E: sqlx::Executor<'e, Database = Db>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO swaps (
+            id, quote_id, source_proofs, target_proofs, encrypted_signature, started_at
+        ) VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(&swap.id)
+    .bind(&swap.quote_id)
+    .bind(&swap.source_proofs)
+    .bind(&swap.target_proofs)
+    .bind(&swap.encrypted_signature)
+    .bind(&swap.started_at)
+    .execute(executor)
+    .await
+    .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Insert a webhook delivery on a pool or in a transaction
+async fn insert_webhook_delivery<'e, E>(
+    executor: E,
+    delivery: &WebhookDelivery,
+) -> Result<(), BrokerError>
+where
+    E: sqlx::Executor<'e, Database = Db>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries (
+            id, quote_id, event, url, payload, status, attempts, next_attempt_at, created_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+    )
+    .bind(&delivery.id)
+    .bind(&delivery.quote_id)
+    .bind(&delivery.event)
+    .bind(&delivery.url)
+    .bind(&delivery.payload)
+    .bind(&delivery.status)
+    .bind(delivery.attempts)
+    .bind(&delivery.next_attempt_at)
+    .bind(&delivery.created_at)
+    .execute(executor)
+    .await
+    .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+    Ok(())
+}
+
+// Swap repository
+impl Database {
+    /// Create a swap execution record
+    pub async fn create_swap(&self, swap: &SwapRecord) -> Result<(), BrokerError> {
+        insert_swap(&self.pool, swap).await
+    }
+
+    /// Complete a swap with target proofs and adaptor secret
+    pub async fn complete_swap(
+        &self,
+        id: &str,
+        target_proofs: &str,
+        decrypted_signature: Option<&str>,
+        adaptor_secret: Option<&str>,
+    ) -> Result<(), BrokerError> {
+        let completed_at = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            UPDATE swaps
+            SET target_proofs = $1, decrypted_signature = $2, adaptor_secret = $3, completed_at = $4
+            WHERE id = $5
+            "#,
+        )
+        .bind(target_proofs)
+        .bind(self.seal(decrypted_signature)?)
+        .bind(self.seal(adaptor_secret)?)
+        .bind(&completed_at)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Get swap by ID
+    pub async fn get_swap(&self, id: &str) -> Result<Option<SwapRecord>, BrokerError> {
+        let result = sqlx::query_as::<_, SwapRecord>(
+            r#"
+            SELECT id, quote_id, source_proofs, target_proofs, encrypted_signature,
+                   decrypted_signature, adaptor_secret, started_at, completed_at
+            FROM swaps
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        result.map(|swap| self.open_swap(swap)).transpose()
+    }
+
+    /// Get swap by quote ID
+    pub async fn get_swap_by_quote(&self, quote_id: &str) -> Result<Option<SwapRecord>, BrokerError> {
+        let result = sqlx::query_as::<_, SwapRecord>(
+            r#"
+            SELECT id, quote_id, source_proofs, target_proofs, encrypted_signature,
+                   decrypted_signature, adaptor_secret, started_at, completed_at
+            FROM swaps
+            WHERE quote_id = $1
             "#,
         )
         .bind(quote_id)
@@ -320,7 +970,62 @@ impl Database {
         .await
         .map_err(|e| BrokerError::Database(e.to_string()))?;
 
-        Ok(result)
+        result.map(|swap| self.open_swap(swap)).transpose()
+    }
+
+    /// Encrypt swap secrets stored in plaintext before encryption was added
+    ///
+    /// Returns the number of swaps rewritten. Safe to run on every start.
+    pub async fn encrypt_swap_secrets(&self) -> Result<usize, BrokerError> {
+        let cipher = self.cipher()?;
+        let rows = sqlx::query(
+            r#"
+            SELECT id, decrypted_signature, adaptor_secret
+            FROM swaps
+            WHERE decrypted_signature IS NOT NULL OR adaptor_secret IS NOT NULL
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        let mut rewritten = 0;
+        for row in rows {
+            let id: String = row
+                .try_get("id")
+                .map_err(|e| BrokerError::Database(e.to_string()))?;
+            let mut plaintext = false;
+            let mut seal = |column: &str| -> Result<Option<String>, BrokerError> {
+                let value: Option<String> = row
+                    .try_get(column)
+                    .map_err(|e| BrokerError::Database(e.to_string()))?;
+                match value {
+                    Some(value) if !is_encrypted_text(&value) => {
+                        plaintext = true;
+                        cipher.encrypt_text(&value).map(Some)
+                    }
+                    value => Ok(value),
+                }
+            };
+            let decrypted_signature = seal("decrypted_signature")?;
+            let adaptor_secret = seal("adaptor_secret")?;
+            if !plaintext {
+                continue;
+            }
+
+            sqlx::query(
+                "UPDATE swaps SET decrypted_signature = $1, adaptor_secret = $2 WHERE id = $3",
+            )
+            .bind(decrypted_signature)
+            .bind(adaptor_secret)
+            .bind(&id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+            rewritten += 1;
+        }
+
+        Ok(rewritten)
     }
 }
 
@@ -335,7 +1040,7 @@ impl Database {
             r#"
             INSERT INTO liquidity_events (
                 mint_url, event_type, amount, balance_after, quote_id, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?)
+            ) VALUES ($1, $2, $3, $4, $5, $6)
             "#,
         )
         .bind(&event.mint_url)
@@ -361,9 +1066,9 @@ impl Database {
             r#"
             SELECT id, mint_url, event_type, amount, balance_after, quote_id, created_at
             FROM liquidity_events
-            WHERE mint_url = ?
+            WHERE mint_url = $1
             ORDER BY created_at DESC
-            LIMIT ?
+            LIMIT $2
             "#,
         )
         .bind(mint_url)
@@ -376,279 +1081,3479 @@ impl Database {
     }
 }
 
-// Database models
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct QuoteRecord {
-    pub id: String,
-    pub source_mint: String,
-    pub target_mint: String,
-    pub amount_in: i64,
-    pub amount_out: i64,
-    pub fee: i64,
-    pub fee_rate: f64,
-    pub broker_pubkey: String,
-    pub adaptor_point: String,
-    pub tweaked_pubkey: String,
-    pub status: String,
-    pub created_at: String,
-    pub expires_at: String,
-    pub accepted_at: Option<String>,
-    pub completed_at: Option<String>,
-    pub user_pubkey: Option<String>,
-    pub error_message: Option<String>,
-}
+// Pending mints repository
+impl Database {
+    /// Record a paid mint quote to mint later, keeping the first record of it
+    pub async fn save_pending_mint(&self, pending: &PendingMint) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO pending_mints (quote_id, mint_url, amount, from_mint, quote, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT(quote_id) DO NOTHING
+            "#,
+        )
+        .bind(&pending.quote_id)
+        .bind(&pending.mint_url)
+        .bind(pending.amount)
+        .bind(&pending.from_mint)
+        .bind(self.seal(pending.quote.as_deref())?)
+        .bind(&pending.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
 
-// Manual FromRow implementation for QuoteRecord
-impl FromRow<'_, sqlx::sqlite::SqliteRow> for QuoteRecord {
-    fn from_row(row: &sqlx::sqlite::SqliteRow) -> sqlx::Result<Self> {
-        Ok(QuoteRecord {
-            id: row.try_get("id")?,
-            source_mint: row.try_get("source_mint")?,
-            target_mint: row.try_get("target_mint")?,
-            amount_in: row.try_get("amount_in")?,
-            amount_out: row.try_get("amount_out")?,
-            fee: row.try_get("fee")?,
-            fee_rate: row.try_get("fee_rate")?,
-            broker_pubkey: row.try_get("broker_pubkey")?,
-            adaptor_point: row.try_get("adaptor_point")?,
-            tweaked_pubkey: row.try_get("tweaked_pubkey")?,
-            status: row.try_get("status")?,
-            created_at: row.try_get("created_at")?,
-            expires_at: row.try_get("expires_at")?,
-            accepted_at: row.try_get("accepted_at")?,
-            completed_at: row.try_get("completed_at")?,
-            user_pubkey: row.try_get("user_pubkey")?,
-            error_message: row.try_get("error_message")?,
-        })
+        Ok(())
+    }
+
+    /// Paid mint quotes not minted yet, oldest first
+    pub async fn list_pending_mints(&self) -> Result<Vec<PendingMint>, BrokerError> {
+        let pending = sqlx::query_as::<_, PendingMint>(
+            r#"
+            SELECT quote_id, mint_url, amount, from_mint, quote, created_at
+            FROM pending_mints
+            ORDER BY created_at ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        pending
+            .into_iter()
+            .map(|mut pending| {
+                pending.quote = self.open(pending.quote)?;
+                Ok(pending)
+            })
+            .collect()
+    }
+
+    /// Forget a pending mint once it is minted; returns whether it was recorded
+    pub async fn delete_pending_mint(&self, quote_id: &str) -> Result<bool, BrokerError> {
+        let result = sqlx::query("DELETE FROM pending_mints WHERE quote_id = $1")
+            .bind(quote_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SwapRecord {
-    pub id: String,
-    pub quote_id: String,
-    pub source_proofs: String,  // JSON serialized
-    pub target_proofs: Option<String>,  // JSON serialized
-    pub encrypted_signature: Option<String>,
-    pub decrypted_signature: Option<String>,
-    pub adaptor_secret: Option<String>,
-    pub started_at: String,
-    pub completed_at: Option<String>,
+// Liquidity snapshots repository
+impl Database {
+    /// Save (or replace) the liquidity snapshot for a mint
+    pub async fn save_liquidity_snapshot(
+        &self,
+        snapshot: &LiquiditySnapshot,
+    ) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO liquidity_snapshots (mint_url, balance, proof_count, proofs, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT(mint_url) DO UPDATE SET
+                balance = excluded.balance,
+                proof_count = excluded.proof_count,
+                proofs = excluded.proofs,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&snapshot.mint_url)
+        .bind(snapshot.balance)
+        .bind(snapshot.proof_count)
+        .bind(&snapshot.proofs)
+        .bind(&snapshot.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Get the latest liquidity snapshot for a mint
+    pub async fn get_liquidity_snapshot(
+        &self,
+        mint_url: &str,
+    ) -> Result<Option<LiquiditySnapshot>, BrokerError> {
+        let result = sqlx::query_as::<_, LiquiditySnapshot>(
+            r#"
+            SELECT mint_url, balance, proof_count, proofs, updated_at
+            FROM liquidity_snapshots
+            WHERE mint_url = $1
+            "#,
+        )
+        .bind(mint_url)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result)
+    }
 }
 
-impl FromRow<'_, sqlx::sqlite::SqliteRow> for SwapRecord {
-    fn from_row(row: &sqlx::sqlite::SqliteRow) -> sqlx::Result<Self> {
-        Ok(SwapRecord {
-            id: row.try_get("id")?,
-            quote_id: row.try_get("quote_id")?,
-            source_proofs: row.try_get("source_proofs")?,
-            target_proofs: row.try_get("target_proofs")?,
-            encrypted_signature: row.try_get("encrypted_signature")?,
-            decrypted_signature: row.try_get("decrypted_signature")?,
-            adaptor_secret: row.try_get("adaptor_secret")?,
-            started_at: row.try_get("started_at")?,
-            completed_at: row.try_get("completed_at")?,
-        })
+// Accounting repository
+impl Database {
+    /// Record an amount the broker earned or paid
+    pub async fn record_ledger_entry(&self, entry: &LedgerEntry) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO accounting (kind, mint_url, unit, amount, quote_id, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(&entry.kind)
+        .bind(&entry.mint_url)
+        .bind(&entry.unit)
+        .bind(entry.amount)
+        .bind(&entry.quote_id)
+        .bind(&entry.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Ledger entries of a swap, oldest first
+    pub async fn get_ledger_entries_by_quote(
+        &self,
+        quote_id: &str,
+    ) -> Result<Vec<LedgerEntry>, BrokerError> {
+        sqlx::query_as::<_, LedgerEntry>(
+            r#"
+            SELECT id, kind, mint_url, unit, amount, quote_id, created_at
+            FROM accounting
+            WHERE quote_id = $1
+            ORDER BY id
+            "#,
+        )
+        .bind(quote_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+
+    /// Ledger totals per mint, unit and kind, over an optional time window
+    pub async fn get_accounting_totals(
+        &self,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<LedgerTotal>, BrokerError> {
+        let mut query = QueryBuilder::<Db>::new(
+            "SELECT mint_url, unit, kind, \
+             CAST(COALESCE(SUM(amount), 0) AS BIGINT) AS amount \
+             FROM accounting WHERE 1 = 1",
+        );
+        if let Some(from) = from {
+            query.push(" AND created_at >= ").push_bind(from.to_rfc3339());
+        }
+        if let Some(to) = to {
+            query.push(" AND created_at < ").push_bind(to.to_rfc3339());
+        }
+        query.push(" GROUP BY mint_url, unit, kind");
+
+        query
+            .build_query_as::<LedgerTotal>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+}
+
+// Quote keys repository
+impl Database {
+    /// Store the private keys of a quote, encrypted
+    pub async fn save_quote_keys(&self, keys: &QuoteKeys) -> Result<(), BrokerError> {
+        let cipher = self.cipher()?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO quote_keys (quote_id, broker_swap_key, adaptor_secret, refund_at, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(&keys.quote_id)
+        .bind(cipher.encrypt(&keys.broker_swap_key)?)
+        .bind(cipher.encrypt(&keys.adaptor_secret)?)
+        .bind(keys.refund_at)
+        .bind(&keys.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Load and decrypt the private keys of a quote
+    pub async fn get_quote_keys(&self, quote_id: &str) -> Result<Option<QuoteKeys>, BrokerError> {
+        let cipher = self.cipher()?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT quote_id, broker_swap_key, adaptor_secret, refund_at, locked_proofs,
+                   encrypted_signature, client_signature, created_at
+            FROM quote_keys
+            WHERE quote_id = $1
+            "#,
+        )
+        .bind(quote_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let broker_swap_key: Vec<u8> = row
+            .try_get("broker_swap_key")
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+        let adaptor_secret: Vec<u8> = row
+            .try_get("adaptor_secret")
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(Some(QuoteKeys {
+            quote_id: row
+                .try_get("quote_id")
+                .map_err(|e| BrokerError::Database(e.to_string()))?,
+            broker_swap_key: cipher.decrypt(&broker_swap_key)?,
+            adaptor_secret: cipher.decrypt(&adaptor_secret)?,
+            refund_at: row
+                .try_get("refund_at")
+                .map_err(|e| BrokerError::Database(e.to_string()))?,
+            locked_proofs: self.open(
+                row.try_get("locked_proofs")
+                    .map_err(|e| BrokerError::Database(e.to_string()))?,
+            )?,
+            encrypted_signature: row
+                .try_get("encrypted_signature")
+                .map_err(|e| BrokerError::Database(e.to_string()))?,
+            client_signature: row
+                .try_get("client_signature")
+                .map_err(|e| BrokerError::Database(e.to_string()))?,
+            created_at: row
+                .try_get("created_at")
+                .map_err(|e| BrokerError::Database(e.to_string()))?,
+        }))
+    }
+
+    /// Record when the broker's outputs for a quote become refundable
+    pub async fn set_quote_refund_at(&self, quote_id: &str, refund_at: i64) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            UPDATE quote_keys
+            SET refund_at = $1
+            WHERE quote_id = $2
+            "#,
+        )
+        .bind(refund_at)
+        .bind(quote_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Record the outputs the broker locked for a quote and its adaptor
+    /// signature, before the client has them
+    ///
+    /// The outputs are encrypted like the quote's keys.
+    pub async fn record_locked_outputs(
+        &self,
+        quote_id: &str,
+        refund_at: i64,
+        locked_proofs: &str,
+        encrypted_signature: &str,
+    ) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            UPDATE quote_keys
+            SET refund_at = $1, locked_proofs = $2, encrypted_signature = $3
+            WHERE quote_id = $4
+            "#,
+        )
+        .bind(refund_at)
+        .bind(self.seal(Some(locked_proofs))?)
+        .bind(encrypted_signature)
+        .bind(quote_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Record the client's verified adaptor signature, before the broker
+    /// claims the client's proofs
+    pub async fn record_client_signature(
+        &self,
+        quote_id: &str,
+        client_signature: &str,
+    ) -> Result<(), BrokerError> {
+        sqlx::query("UPDATE quote_keys SET client_signature = $1 WHERE quote_id = $2")
+            .bind(client_signature)
+            .bind(quote_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Delete the stored keys of a quote that can no longer be executed
+    pub async fn delete_quote_keys(&self, quote_id: &str) -> Result<(), BrokerError> {
+        sqlx::query("DELETE FROM quote_keys WHERE quote_id = $1")
+            .bind(quote_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+// Idempotency keys repository
+impl Database {
+    /// Claim an idempotency key, returning false if it was already used in `scope`
+    ///
+    /// A request still in flight since before `stale_before` is taken to have
+    /// died with its process, and its key is claimed over it.
+    pub async fn reserve_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        stale_before: DateTime<Utc>,
+    ) -> Result<bool, BrokerError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (scope, key, request_hash, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (scope, key) DO UPDATE
+            SET request_hash = excluded.request_hash, created_at = excluded.created_at
+            WHERE idempotency_keys.status_code IS NULL AND idempotency_keys.created_at < $5
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(request_hash)
+        .bind(Utc::now().to_rfc3339())
+        .bind(stale_before.to_rfc3339())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Get the record of an idempotency key
+    pub async fn get_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+    ) -> Result<Option<IdempotencyRecord>, BrokerError> {
+        let record = sqlx::query_as::<_, IdempotencyRecord>(
+            r#"
+            SELECT scope, key, request_hash, status_code, response_body, created_at
+            FROM idempotency_keys
+            WHERE scope = $1 AND key = $2
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        record
+            .map(|mut record| {
+                record.response_body = self.open(record.response_body)?;
+                Ok(record)
+            })
+            .transpose()
+    }
+
+    /// Store the response of a request made under an idempotency key
+    ///
+    /// The body is sealed like the swap secrets: a `/complete` response
+    /// carries the adaptor secret.
+    pub async fn complete_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        status_code: i64,
+        response_body: &str,
+    ) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+            SET status_code = $1, response_body = $2
+            WHERE scope = $3 AND key = $4
+            "#,
+        )
+        .bind(status_code)
+        .bind(self.seal(Some(response_body))?)
+        .bind(scope)
+        .bind(key)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Free an in-flight idempotency key so the request can be retried
+    pub async fn release_idempotency_key(&self, scope: &str, key: &str) -> Result<(), BrokerError> {
+        sqlx::query(
+            "DELETE FROM idempotency_keys WHERE scope = $1 AND key = $2 AND status_code IS NULL",
+        )
+        .bind(scope)
+        .bind(key)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Delete idempotency keys claimed before `before`, in flight or not,
+    /// returning how many were deleted
+    pub async fn expire_idempotency_keys(&self, before: DateTime<Utc>) -> Result<u64, BrokerError> {
+        let result = sqlx::query("DELETE FROM idempotency_keys WHERE created_at < $1")
+            .bind(before.to_rfc3339())
+            .execute(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+// Webhook deliveries repository
+impl Database {
+    /// Queue a webhook delivery
+    pub async fn create_webhook_delivery(&self, delivery: &WebhookDelivery) -> Result<(), BrokerError> {
+        insert_webhook_delivery(&self.pool, delivery).await
+    }
+
+    /// Pending deliveries whose next attempt is due, oldest first
+    pub async fn due_webhook_deliveries(&self, limit: i64) -> Result<Vec<WebhookDelivery>, BrokerError> {
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            SELECT id, quote_id, event, url, payload, status, attempts, next_attempt_at,
+                   last_error, created_at, delivered_at
+            FROM webhook_deliveries
+            WHERE status = 'pending' AND next_attempt_at <= $1
+            ORDER BY next_attempt_at ASC
+            LIMIT $2
+            "#,
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(deliveries)
+    }
+
+    /// List the deliveries for a quote, oldest first
+    pub async fn list_webhook_deliveries(
+        &self,
+        quote_id: &str,
+    ) -> Result<Vec<WebhookDelivery>, BrokerError> {
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            SELECT id, quote_id, event, url, payload, status, attempts, next_attempt_at,
+                   last_error, created_at, delivered_at
+            FROM webhook_deliveries
+            WHERE quote_id = $1
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(quote_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(deliveries)
+    }
+
+    /// Mark a delivery as delivered
+    pub async fn mark_webhook_delivered(&self, id: &str, attempts: i64) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'delivered', attempts = $1, delivered_at = $2, last_error = NULL
+            WHERE id = $3
+            "#,
+        )
+        .bind(attempts)
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt, scheduling a retry or giving up when `retry_at` is None
+    pub async fn record_webhook_failure(
+        &self,
+        id: &str,
+        attempts: i64,
+        retry_at: Option<&str>,
+        error: &str,
+    ) -> Result<(), BrokerError> {
+        let status = if retry_at.is_some() { "pending" } else { "failed" };
+
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = $1, attempts = $2, next_attempt_at = COALESCE($3, next_attempt_at),
+                last_error = $4
+            WHERE id = $5
+            "#,
+        )
+        .bind(status)
+        .bind(attempts)
+        .bind(retry_at)
+        .bind(error)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+// API keys repository
+impl Database {
+    /// Store a new API key (hash only)
+    pub async fn create_api_key(&self, key: &ApiKeyRecord) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO api_keys (id, name, key_hash, created_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(&key.id)
+        .bind(&key.name)
+        .bind(&key.key_hash)
+        .bind(&key.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Find an unrevoked API key by the hash of its key
+    pub async fn find_api_key(&self, key_hash: &str) -> Result<Option<ApiKeyRecord>, BrokerError> {
+        let key = sqlx::query_as::<_, ApiKeyRecord>(
+            r#"
+            SELECT id, name, key_hash, created_at, last_used_at, revoked_at
+            FROM api_keys
+            WHERE key_hash = $1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(key_hash)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(key)
+    }
+
+    /// List all API keys, newest first
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKeyRecord>, BrokerError> {
+        let keys = sqlx::query_as::<_, ApiKeyRecord>(
+            r#"
+            SELECT id, name, key_hash, created_at, last_used_at, revoked_at
+            FROM api_keys
+            ORDER BY created_at DESC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(keys)
+    }
+
+    /// Record that an API key was used
+    pub async fn touch_api_key(&self, id: &str) -> Result<(), BrokerError> {
+        sqlx::query("UPDATE api_keys SET last_used_at = $1 WHERE id = $2")
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Revoke an API key, returning false if it doesn't exist or was already revoked
+    pub async fn revoke_api_key(&self, id: &str) -> Result<bool, BrokerError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE api_keys
+            SET revoked_at = $1
+            WHERE id = $2 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+// Audit log repository
+impl Database {
+    /// Record an administrative action
+    pub async fn record_audit_entry(&self, entry: &AuditEntry) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (
+                actor, action, target, before_value, after_value, request_id, created_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+        )
+        .bind(&entry.actor)
+        .bind(&entry.action)
+        .bind(&entry.target)
+        .bind(&entry.before)
+        .bind(&entry.after)
+        .bind(&entry.request_id)
+        .bind(&entry.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// List audit entries, newest first, optionally only those of one action
+    pub async fn list_audit_entries(
+        &self,
+        action: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>, BrokerError> {
+        let mut query = QueryBuilder::<Db>::new(
+            "SELECT id, actor, action, target, before_value, after_value, request_id, created_at \
+             FROM audit_log",
+        );
+        if let Some(action) = action {
+            query.push(" WHERE action = ").push_bind(action.to_string());
+        }
+        query.push(" ORDER BY id DESC LIMIT ").push_bind(limit);
+
+        query
+            .build_query_as::<AuditEntry>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+}
+
+/// Setting that holds whether maintenance mode is on
+const MAINTENANCE_SETTING: &str = "maintenance";
+
+// Settings repository
+impl Database {
+    /// Value of a runtime setting, if it was ever set
+    pub async fn get_setting(&self, key: &str) -> Result<Option<String>, BrokerError> {
+        sqlx::query_scalar::<_, String>("SELECT value FROM broker_settings WHERE key = $1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+
+    /// Set (or replace) a runtime setting
+    pub async fn set_setting(&self, key: &str, value: &str) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO broker_settings (key, value, updated_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT(key) DO UPDATE SET
+                value = excluded.value,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(key)
+        .bind(value)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Whether maintenance mode was left on (off if never set)
+    pub async fn maintenance_mode(&self) -> Result<bool, BrokerError> {
+        Ok(self.get_setting(MAINTENANCE_SETTING).await?.as_deref() == Some("true"))
+    }
+
+    /// Record whether maintenance mode is on
+    pub async fn set_maintenance_mode(&self, enabled: bool) -> Result<(), BrokerError> {
+        self.set_setting(MAINTENANCE_SETTING, &enabled.to_string()).await
+    }
+}
+
+// Mint registry repository
+impl Database {
+    /// Every mint added or removed at runtime, in the order of their last change
+    pub async fn list_mint_changes(&self) -> Result<Vec<MintChange>, BrokerError> {
+        sqlx::query_as::<_, MintChange>(
+            "SELECT mint_url, name, unit, action, updated_at FROM mint_registry \
+             ORDER BY updated_at, mint_url",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+
+    /// Record (or replace) the latest change to a mint
+    pub async fn record_mint_change(&self, change: &MintChange) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO mint_registry (mint_url, name, unit, action, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT(mint_url) DO UPDATE SET
+                name = excluded.name,
+                unit = excluded.unit,
+                action = excluded.action,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&change.mint.mint_url)
+        .bind(&change.mint.name)
+        .bind(&change.mint.unit)
+        .bind(if change.removed { "removed" } else { "added" })
+        .bind(&change.updated_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+// Lease repository
+impl Database {
+    /// Take lease `name` for `holder` until `ttl` from now, or renew it
+    ///
+    /// Only takes a lease that is free, expired or already `holder`'s.
+    /// Returns the lease as it stands afterwards: `holder` has it if the
+    /// returned holder is `holder`.
+    pub async fn acquire_lease(
+        &self,
+        name: &str,
+        holder: &str,
+        ttl: Duration,
+    ) -> Result<Lease, BrokerError> {
+        let now = Utc::now();
+        let ttl = i64::try_from(ttl.as_millis()).unwrap_or(i64::MAX);
+        let expires_at = now.timestamp_millis().saturating_add(ttl);
+
+        sqlx::query(
+            r#"
+            INSERT INTO leases (name, holder, expires_at, acquired_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT(name) DO UPDATE SET
+                holder = excluded.holder,
+                expires_at = excluded.expires_at,
+                acquired_at = CASE WHEN leases.holder = excluded.holder
+                    THEN leases.acquired_at ELSE excluded.acquired_at END
+            WHERE leases.holder = excluded.holder OR leases.expires_at <= $5
+            "#,
+        )
+        .bind(name)
+        .bind(holder)
+        .bind(expires_at)
+        .bind(now.to_rfc3339())
+        .bind(now.timestamp_millis())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        sqlx::query_as::<_, Lease>(
+            "SELECT name, holder, expires_at, acquired_at FROM leases WHERE name = $1",
+        )
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+
+    /// Give up lease `name` if `holder` has it; false if it didn't
+    pub async fn release_lease(&self, name: &str, holder: &str) -> Result<bool, BrokerError> {
+        let result = sqlx::query("DELETE FROM leases WHERE name = $1 AND holder = $2")
+            .bind(name)
+            .bind(holder)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Every lease, expired or not, by name
+    pub async fn list_leases(&self) -> Result<Vec<Lease>, BrokerError> {
+        sqlx::query_as::<_, Lease>(
+            "SELECT name, holder, expires_at, acquired_at FROM leases ORDER BY name",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+}
+
+// Blacklist repository
+impl Database {
+    /// Ban a public key or proof, replacing the reason if it is already banned
+    pub async fn add_blacklist_entry(&self, entry: &BlacklistEntry) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO blacklist (kind, value, reason, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT(kind, value) DO UPDATE SET
+                reason = excluded.reason
+            "#,
+        )
+        .bind(&entry.kind)
+        .bind(&entry.value)
+        .bind(&entry.reason)
+        .bind(&entry.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Lift a ban, returning false if there was none
+    pub async fn remove_blacklist_entry(
+        &self,
+        kind: BlacklistKind,
+        value: &str,
+    ) -> Result<bool, BrokerError> {
+        let result = sqlx::query("DELETE FROM blacklist WHERE kind = $1 AND value = $2")
+            .bind(kind.to_string())
+            .bind(value)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// All bans, newest first
+    pub async fn list_blacklist(&self) -> Result<Vec<BlacklistEntry>, BrokerError> {
+        sqlx::query_as::<_, BlacklistEntry>(
+            "SELECT kind, value, reason, created_at FROM blacklist \
+             ORDER BY created_at DESC, kind, value",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+
+    /// The ban on any of `values`, if one of them is banned
+    pub async fn find_blacklisted(
+        &self,
+        kind: BlacklistKind,
+        values: &[String],
+    ) -> Result<Option<BlacklistEntry>, BrokerError> {
+        if values.is_empty() {
+            return Ok(None);
+        }
+
+        let mut query = QueryBuilder::<Db>::new(
+            "SELECT kind, value, reason, created_at FROM blacklist WHERE kind = ",
+        );
+        query.push_bind(kind.to_string()).push(" AND value IN (");
+        let mut separated = query.separated(", ");
+        for value in values {
+            separated.push_bind(value.clone());
+        }
+        query.push(") LIMIT 1");
+
+        query
+            .build_query_as::<BlacklistEntry>()
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+}
+
+// Client volume repository
+impl Database {
+    /// Add a completed swap of `amount` to a client's volume on the day of `at`
+    pub async fn record_client_volume(
+        &self,
+        pubkey: &str,
+        amount: u64,
+        at: DateTime<Utc>,
+    ) -> Result<(), BrokerError> {
+        sqlx::query(
+            r#"
+            INSERT INTO client_volume (pubkey, day, amount)
+            VALUES ($1, $2, $3)
+            ON CONFLICT(pubkey, day) DO UPDATE SET
+                amount = client_volume.amount + excluded.amount
+            "#,
+        )
+        .bind(pubkey)
+        .bind(at.format("%Y-%m-%d").to_string())
+        .bind(amount as i64)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Volume a client completed from the day of `since` on
+    pub async fn client_volume(
+        &self,
+        pubkey: &str,
+        since: DateTime<Utc>,
+    ) -> Result<u64, BrokerError> {
+        let volume = sqlx::query_scalar::<_, i64>(
+            "SELECT CAST(COALESCE(SUM(amount), 0) AS BIGINT) FROM client_volume \
+             WHERE pubkey = $1 AND day >= $2",
+        )
+        .bind(pubkey)
+        .bind(since.format("%Y-%m-%d").to_string())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(volume.max(0) as u64)
+    }
+}
+
+// Proof claims repository
+impl Database {
+    /// Claim proofs, by their Y, for a quote's swap
+    ///
+    /// Fails with [`BrokerError::DuplicateProofs`], claiming none of them, if
+    /// another quote holds any. Proofs the quote already holds stay claimed.
+    /// Returns the Ys this call claimed, leaving out those already held.
+    pub async fn claim_proofs(
+        &self,
+        quote_id: &str,
+        ys: &[String],
+    ) -> Result<Vec<String>, BrokerError> {
+        let quote_id = quote_id.to_string();
+        let ys = ys.to_vec();
+        let created_at = Utc::now().to_rfc3339();
+
+        self.with_tx(move |tx| {
+            Box::pin(async move {
+                let mut claimed = Vec::new();
+                for y in &ys {
+                    let inserted = sqlx::query(
+                        r#"
+                        INSERT INTO proof_claims (y, quote_id, created_at)
+                        VALUES ($1, $2, $3)
+                        ON CONFLICT (y) DO NOTHING
+                        "#,
+                    )
+                    .bind(y)
+                    .bind(&quote_id)
+                    .bind(&created_at)
+                    .execute(&mut **tx)
+                    .await
+                    .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+                    let holder = sqlx::query_scalar::<_, String>(
+                        "SELECT quote_id FROM proof_claims WHERE y = $1",
+                    )
+                    .bind(y)
+                    .fetch_one(&mut **tx)
+                    .await
+                    .map_err(|e| BrokerError::Database(e.to_string()))?;
+                    if holder != quote_id {
+                        return Err(BrokerError::DuplicateProofs(format!(
+                            "proof {} is already part of another swap",
+                            y
+                        )));
+                    }
+                    if inserted.rows_affected() == 1 {
+                        claimed.push(y.clone());
+                    }
+                }
+                Ok(claimed)
+            })
+        })
+        .await
+    }
+
+    /// Release the proofs `ys` a quote holds, e.g. those one claim took
+    ///
+    /// Proofs the quote holds that aren't in `ys` stay claimed.
+    pub async fn release_proofs(&self, quote_id: &str, ys: &[String]) -> Result<(), BrokerError> {
+        let quote_id = quote_id.to_string();
+        let ys = ys.to_vec();
+
+        self.with_tx(move |tx| {
+            Box::pin(async move {
+                for y in &ys {
+                    sqlx::query("DELETE FROM proof_claims WHERE y = $1 AND quote_id = $2")
+                        .bind(y)
+                        .bind(&quote_id)
+                        .execute(&mut **tx)
+                        .await
+                        .map_err(|e| BrokerError::Database(e.to_string()))?;
+                }
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// Y of the proofs a quote holds
+    pub async fn claimed_proofs(&self, quote_id: &str) -> Result<Vec<String>, BrokerError> {
+        sqlx::query_scalar::<_, String>(
+            "SELECT y FROM proof_claims WHERE quote_id = $1 ORDER BY y",
+        )
+        .bind(quote_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+}
+
+// Chunked swaps repository
+impl Database {
+    /// Save a new chunked swap
+    pub async fn create_chunked_swap(&self, swap: &ChunkedSwapRecord) -> Result<(), BrokerError> {
+        let chunks =
+            serde_json::to_string(&swap.chunks).map_err(|e| BrokerError::Database(e.to_string()))?;
+        sqlx::query(
+            r#"
+            INSERT INTO chunked_swaps (
+                id, source_mint, target_mint, amount, chunks, user_pubkey, callback_url,
+                created_at, stopped_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+        )
+        .bind(&swap.id)
+        .bind(&swap.source_mint)
+        .bind(&swap.target_mint)
+        .bind(swap.amount)
+        .bind(chunks)
+        .bind(&swap.user_pubkey)
+        .bind(&swap.callback_url)
+        .bind(&swap.created_at)
+        .bind(&swap.stopped_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Get a chunked swap by ID
+    pub async fn get_chunked_swap(
+        &self,
+        id: &str,
+    ) -> Result<Option<ChunkedSwapRecord>, BrokerError> {
+        sqlx::query_as::<_, ChunkedSwapRecord>("SELECT * FROM chunked_swaps WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+
+    /// Stop a chunked swap, so no further chunks are quoted
+    ///
+    /// Returns false if it was already stopped.
+    pub async fn stop_chunked_swap(&self, id: &str) -> Result<bool, BrokerError> {
+        let result = sqlx::query(
+            "UPDATE chunked_swaps SET stopped_at = $1 WHERE id = $2 AND stopped_at IS NULL",
+        )
+        .bind(Utc::now().to_rfc3339())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Record a quote as chunk `index` of a chunked swap
+    ///
+    /// Records nothing and returns false if the chunked swap is stopped or
+    /// another of its chunks is pending or accepted, so two requests can't
+    /// run chunks side by side.
+    pub async fn add_swap_chunk(
+        &self,
+        chunked_swap_id: &str,
+        index: usize,
+        quote_id: &str,
+    ) -> Result<bool, BrokerError> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO swap_chunks (quote_id, chunked_swap_id, chunk_index, created_at)
+            SELECT $1, $2, $3, $4
+            WHERE EXISTS (
+                SELECT 1 FROM chunked_swaps WHERE id = $2 AND stopped_at IS NULL
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM swap_chunks c JOIN quotes q ON q.id = c.quote_id
+                WHERE c.chunked_swap_id = $2 AND q.status IN ('pending', 'accepted')
+            )
+            "#,
+        )
+        .bind(quote_id)
+        .bind(chunked_swap_id)
+        .bind(index as i64)
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Quotes issued for a chunked swap's chunks, in order
+    pub async fn list_swap_chunks(
+        &self,
+        chunked_swap_id: &str,
+    ) -> Result<Vec<SwapChunk>, BrokerError> {
+        sqlx::query_as::<_, SwapChunk>(
+            r#"
+            SELECT c.chunk_index, c.quote_id, q.status, q.amount_in, q.amount_out
+            FROM swap_chunks c JOIN quotes q ON q.id = c.quote_id
+            WHERE c.chunked_swap_id = $1
+            ORDER BY c.chunk_index, c.created_at
+            "#,
+        )
+        .bind(chunked_swap_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+
+    /// ID of the chunked swap a quote is a chunk of
+    pub async fn chunked_swap_of(&self, quote_id: &str) -> Result<Option<String>, BrokerError> {
+        sqlx::query_scalar::<_, String>(
+            "SELECT chunked_swap_id FROM swap_chunks WHERE quote_id = $1",
+        )
+        .bind(quote_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+}
+
+/// Quotes that retention may prune: settled and created before `$1`
+const PRUNABLE_QUOTES: &str =
+    "SELECT id FROM quotes WHERE status IN ('completed', 'expired', 'failed') AND created_at < $1";
+
+// Retention repository
+impl Database {
+    /// Prune settled quotes and liquidity events created before `cutoff`
+    ///
+    /// Liquidity events are first added to their mint's daily totals in
+    /// `liquidity_event_summaries`. Completed, expired and failed quotes are
+    /// deleted with their swap records, keys and webhook deliveries; with
+    /// `archive` each one is first copied to `quotes_archive` without any
+    /// proofs, keys or signatures. Ledger entries are kept. Runs in one
+    /// transaction.
+    pub async fn prune(
+        &self,
+        cutoff: DateTime<Utc>,
+        archive: bool,
+    ) -> Result<PruneReport, BrokerError> {
+        let cutoff = cutoff.to_rfc3339();
+        let now = Utc::now().to_rfc3339();
+
+        self.with_tx(move |tx| {
+            Box::pin(async move {
+                let mut report = PruneReport::default();
+
+                // Timestamps are UTC RFC 3339, so the first ten characters are the day
+                sqlx::query(
+                    r#"
+                    INSERT INTO liquidity_event_summaries (
+                        mint_url, event_type, day, event_count, total_amount
+                    )
+                    SELECT mint_url, event_type, SUBSTR(created_at, 1, 10), COUNT(*),
+                        CAST(COALESCE(SUM(amount), 0) AS BIGINT)
+                    FROM liquidity_events
+                    WHERE created_at < $1
+                    GROUP BY mint_url, event_type, SUBSTR(created_at, 1, 10)
+                    ON CONFLICT(mint_url, event_type, day) DO UPDATE SET
+                        event_count =
+                            liquidity_event_summaries.event_count + excluded.event_count,
+                        total_amount =
+                            liquidity_event_summaries.total_amount + excluded.total_amount
+                    "#,
+                )
+                .bind(&cutoff)
+                .execute(&mut **tx)
+                .await
+                .map_err(|e| BrokerError::Database(e.to_string()))?;
+
+                report.liquidity_events =
+                    sqlx::query("DELETE FROM liquidity_events WHERE created_at < $1")
+                        .bind(&cutoff)
+                        .execute(&mut **tx)
+                        .await
+                        .map_err(|e| BrokerError::Database(e.to_string()))?
+                        .rows_affected();
+
+                if archive {
+                    let sql = format!(
+                        "INSERT INTO quotes_archive ( \
+                            id, source_mint, target_mint, amount_in, amount_out, fee, \
+                            fee_rate, mint_fee, exchange_rate, status, created_at, \
+                            accepted_at, completed_at, swap_completed_at, user_pubkey, \
+                            error_message, archived_at \
+                         ) \
+                         SELECT q.id, q.source_mint, q.target_mint, q.amount_in, \
+                            q.amount_out, q.fee, q.fee_rate, q.mint_fee, q.exchange_rate, \
+                            q.status, q.created_at, q.accepted_at, q.completed_at, \
+                            s.completed_at, q.user_pubkey, q.error_message, $2 \
+                         FROM quotes q LEFT JOIN swaps s ON s.quote_id = q.id \
+                         WHERE q.id IN ({}) \
+                         ON CONFLICT(id) DO NOTHING",
+                        PRUNABLE_QUOTES
+                    );
+                    report.quotes_archived = sqlx::query(&sql)
+                        .bind(&cutoff)
+                        .bind(&now)
+                        .execute(&mut **tx)
+                        .await
+                        .map_err(|e| BrokerError::Database(e.to_string()))?
+                        .rows_affected();
+                }
+
+                // Dependent rows go explicitly, so this doesn't rely on foreign key enforcement
+                for table in
+                    ["swaps", "quote_keys", "webhook_deliveries", "proof_claims", "swap_chunks"]
+                {
+                    let sql =
+                        format!("DELETE FROM {} WHERE quote_id IN ({})", table, PRUNABLE_QUOTES);
+                    sqlx::query(&sql)
+                        .bind(&cutoff)
+                        .execute(&mut **tx)
+                        .await
+                        .map_err(|e| BrokerError::Database(e.to_string()))?;
+                }
+                for table in ["liquidity_events", "accounting"] {
+                    let sql = format!(
+                        "UPDATE {} SET quote_id = NULL WHERE quote_id IN ({})",
+                        table, PRUNABLE_QUOTES
+                    );
+                    sqlx::query(&sql)
+                        .bind(&cutoff)
+                        .execute(&mut **tx)
+                        .await
+                        .map_err(|e| BrokerError::Database(e.to_string()))?;
+                }
+
+                let sql = format!("DELETE FROM quotes WHERE id IN ({})", PRUNABLE_QUOTES);
+                report.quotes_deleted = sqlx::query(&sql)
+                    .bind(&cutoff)
+                    .execute(&mut **tx)
+                    .await
+                    .map_err(|e| BrokerError::Database(e.to_string()))?
+                    .rows_affected();
+
+                Ok(report)
+            })
+        })
+        .await
+    }
+
+    /// Daily liquidity event totals of a mint, oldest first
+    pub async fn get_liquidity_event_summaries(
+        &self,
+        mint_url: &str,
+    ) -> Result<Vec<LiquidityEventSummary>, BrokerError> {
+        sqlx::query_as::<_, LiquidityEventSummary>(
+            r#"
+            SELECT mint_url, event_type, day, event_count, total_amount
+            FROM liquidity_event_summaries
+            WHERE mint_url = $1
+            ORDER BY day, event_type
+            "#,
+        )
+        .bind(mint_url)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+
+    /// Number of quotes in the archive
+    pub async fn count_archived_quotes(&self) -> Result<i64, BrokerError> {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM quotes_archive")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| BrokerError::Database(e.to_string()))
+    }
+}
+
+// Database models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteRecord {
+    pub id: String,
+    pub source_mint: String,
+    pub target_mint: String,
+    pub amount_in: i64,
+    pub amount_out: i64,
+    pub fee: i64,
+    pub fee_rate: f64,
+    pub mint_fee: i64, // Expected mint input fees, in the source unit
+    pub exchange_rate: Option<f64>,
+    pub broker_pubkey: String,
+    pub adaptor_point: String,
+    pub tweaked_pubkey: String,
+    pub status: SwapStatus,
+    pub created_at: String,
+    pub expires_at: String,
+    pub accepted_at: Option<String>,
+    pub completed_at: Option<String>,
+    pub user_pubkey: Option<String>,
+    pub error_message: Option<String>,
+    pub callback_url: Option<String>, // Webhook for status changes
+    #[serde(default)]
+    pub job_status: Option<JobStatus>, // Latest accept or complete step
+    #[serde(default)]
+    pub claim_order: ClaimOrder,
+    #[serde(default)]
+    pub extended_at: Option<String>, // When the client extended the quote's expiry
+}
+
+// Manual FromRow implementation for QuoteRecord
+impl FromRow<'_, DbRow> for QuoteRecord {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(QuoteRecord {
+            id: row.try_get("id")?,
+            source_mint: row.try_get("source_mint")?,
+            target_mint: row.try_get("target_mint")?,
+            amount_in: row.try_get("amount_in")?,
+            amount_out: row.try_get("amount_out")?,
+            fee: row.try_get("fee")?,
+            fee_rate: row.try_get("fee_rate")?,
+            mint_fee: row.try_get("mint_fee")?,
+            exchange_rate: row.try_get("exchange_rate")?,
+            broker_pubkey: row.try_get("broker_pubkey")?,
+            adaptor_point: row.try_get("adaptor_point")?,
+            tweaked_pubkey: row.try_get("tweaked_pubkey")?,
+            status: row.try_get("status")?,
+            created_at: row.try_get("created_at")?,
+            expires_at: row.try_get("expires_at")?,
+            accepted_at: row.try_get("accepted_at")?,
+            completed_at: row.try_get("completed_at")?,
+            user_pubkey: row.try_get("user_pubkey")?,
+            error_message: row.try_get("error_message")?,
+            callback_url: row.try_get("callback_url")?,
+            job_status: row
+                .try_get::<Option<String>, _>("job_status")?
+                .and_then(|status| status.parse().ok()),
+            claim_order: row
+                .try_get::<String, _>("claim_order")?
+                .parse()
+                .map_err(|e: String| sqlx::Error::Decode(e.into()))?,
+            extended_at: row.try_get("extended_at")?,
+        })
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SwapRecord {
+    pub id: String,
+    pub quote_id: String,
+    pub source_proofs: String,  // JSON serialized
+    pub target_proofs: Option<String>,  // JSON serialized
+    pub encrypted_signature: Option<String>,
+    pub decrypted_signature: Option<String>,
+    pub adaptor_secret: Option<String>,
+    pub started_at: String,
+    pub completed_at: Option<String>,
+}
+
+impl std::fmt::Debug for SwapRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SwapRecord")
+            .field("id", &self.id)
+            .field("quote_id", &self.quote_id)
+            .field("source_proofs", &Sensitive(&self.source_proofs))
+            .field("target_proofs", &Sensitive(&self.target_proofs))
+            .field("encrypted_signature", &self.encrypted_signature)
+            .field("decrypted_signature", &Sensitive(&self.decrypted_signature))
+            .field("adaptor_secret", &Sensitive(&self.adaptor_secret))
+            .field("started_at", &self.started_at)
+            .field("completed_at", &self.completed_at)
+            .finish()
+    }
+}
+
+impl FromRow<'_, DbRow> for SwapRecord {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(SwapRecord {
+            id: row.try_get("id")?,
+            quote_id: row.try_get("quote_id")?,
+            source_proofs: row.try_get("source_proofs")?,
+            target_proofs: row.try_get("target_proofs")?,
+            encrypted_signature: row.try_get("encrypted_signature")?,
+            decrypted_signature: row.try_get("decrypted_signature")?,
+            adaptor_secret: row.try_get("adaptor_secret")?,
+            started_at: row.try_get("started_at")?,
+            completed_at: row.try_get("completed_at")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidityEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub mint_url: String,
+    pub event_type: String,  // 'deposit', 'withdrawal', 'swap_in', 'swap_out'
+    pub amount: i64,
+    pub balance_after: i64,
+    pub quote_id: Option<String>,
+    pub created_at: String,
+}
+
+impl FromRow<'_, DbRow> for LiquidityEvent {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(LiquidityEvent {
+            id: row.try_get("id").ok(),
+            mint_url: row.try_get("mint_url")?,
+            event_type: row.try_get("event_type")?,
+            amount: row.try_get("amount")?,
+            balance_after: row.try_get("balance_after")?,
+            quote_id: row.try_get("quote_id")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// Amount the broker earned or paid (see [`crate::accounting`])
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub kind: String,     // 'swap_fee', 'spread', 'mint_fee', 'lightning_fee'
+    pub mint_url: String, // Mint the amount was earned or paid on
+    pub unit: String,
+    pub amount: i64,
+    pub quote_id: Option<String>,
+    pub created_at: String,
+}
+
+impl LedgerEntry {
+    pub fn new(
+        kind: EntryKind,
+        mint_url: &str,
+        unit: &str,
+        amount: u64,
+        quote_id: Option<&str>,
+    ) -> Self {
+        Self {
+            id: None,
+            kind: kind.to_string(),
+            mint_url: mint_url.to_string(),
+            unit: unit.to_string(),
+            amount: amount as i64,
+            quote_id: quote_id.map(str::to_string),
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+impl FromRow<'_, DbRow> for LedgerEntry {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(LedgerEntry {
+            id: row.try_get("id").ok(),
+            kind: row.try_get("kind")?,
+            mint_url: row.try_get("mint_url")?,
+            unit: row.try_get("unit")?,
+            amount: row.try_get("amount")?,
+            quote_id: row.try_get("quote_id")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// Sum of the ledger entries of one kind on one mint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerTotal {
+    pub mint_url: String,
+    pub unit: String,
+    pub kind: String,
+    pub amount: i64,
+}
+
+impl FromRow<'_, DbRow> for LedgerTotal {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(LedgerTotal {
+            mint_url: row.try_get("mint_url")?,
+            unit: row.try_get("unit")?,
+            kind: row.try_get("kind")?,
+            amount: row.try_get("amount")?,
+        })
+    }
+}
+
+/// A quote as exported (see [`crate::export`])
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuoteExport {
+    pub id: String,
+    pub status: SwapStatus,
+    pub source_mint: String,
+    pub target_mint: String,
+    pub amount_in: i64,
+    pub amount_out: i64,
+    pub fee: i64,
+    pub fee_rate: f64,
+    pub mint_fee: i64,       // Charged to the client, in the source unit
+    pub mint_fees_paid: i64, // Paid by the broker on both mints, from the ledger
+    pub exchange_rate: Option<f64>,
+    pub created_at: String,
+    pub accepted_at: Option<String>,
+    pub completed_at: Option<String>,
+    pub swap_completed_at: Option<String>, // When the swap record was settled
+    pub error_message: Option<String>,
+}
+
+impl FromRow<'_, DbRow> for QuoteExport {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(QuoteExport {
+            id: row.try_get("id")?,
+            status: row.try_get("status")?,
+            source_mint: row.try_get("source_mint")?,
+            target_mint: row.try_get("target_mint")?,
+            amount_in: row.try_get("amount_in")?,
+            amount_out: row.try_get("amount_out")?,
+            fee: row.try_get("fee")?,
+            fee_rate: row.try_get("fee_rate")?,
+            mint_fee: row.try_get("mint_fee")?,
+            mint_fees_paid: row.try_get("mint_fees_paid")?,
+            exchange_rate: row.try_get("exchange_rate")?,
+            created_at: row.try_get("created_at")?,
+            accepted_at: row.try_get("accepted_at")?,
+            completed_at: row.try_get("completed_at")?,
+            swap_completed_at: row.try_get("swap_completed_at")?,
+            error_message: row.try_get("error_message")?,
+        })
+    }
+}
+
+/// What one retention run removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    pub quotes_deleted: u64,
+    pub quotes_archived: u64,  // Included in quotes_deleted
+    pub liquidity_events: u64, // Summarized, then deleted
+}
+
+impl PruneReport {
+    pub fn is_empty(&self) -> bool {
+        self.quotes_deleted == 0 && self.liquidity_events == 0
+    }
+}
+
+/// Totals of one day's liquidity events of one type on a mint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiquidityEventSummary {
+    pub mint_url: String,
+    pub event_type: String,
+    pub day: String, // YYYY-MM-DD (UTC)
+    pub event_count: i64,
+    pub total_amount: i64,
+}
+
+impl FromRow<'_, DbRow> for LiquidityEventSummary {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(LiquidityEventSummary {
+            mint_url: row.try_get("mint_url")?,
+            event_type: row.try_get("event_type")?,
+            day: row.try_get("day")?,
+            event_count: row.try_get("event_count")?,
+            total_amount: row.try_get("total_amount")?,
+        })
+    }
+}
+
+/// Filters for listing quotes
+#[derive(Debug, Clone, Default)]
+pub struct QuoteFilter {
+    pub status: Option<SwapStatus>,
+    pub source_mint: Option<String>,
+    pub target_mint: Option<String>,
+    pub user_pubkey: Option<String>,
+    pub from: Option<DateTime<Utc>>, // Created at or after
+    pub to: Option<DateTime<Utc>>,   // Created before
+}
+
+/// Position after the last quote of a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteCursor {
+    pub created_at: String,
+    pub id: String,
+}
+
+impl QuoteCursor {
+    /// Opaque string form handed to API clients
+    pub fn encode(&self) -> String {
+        hex::encode(format!("{}\n{}", self.created_at, self.id))
+    }
+
+    /// Parse a string produced by [`QuoteCursor::encode`]
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let text = String::from_utf8(hex::decode(cursor).ok()?).ok()?;
+        let (created_at, id) = text.split_once('\n')?;
+
+        Some(Self {
+            created_at: created_at.to_string(),
+            id: id.to_string(),
+        })
+    }
+}
+
+/// One page of a quote listing
+#[derive(Debug, Clone)]
+pub struct QuotePage {
+    pub quotes: Vec<QuoteRecord>,
+    pub total: i64,                      // Quotes matching the filter, across all pages
+    pub next_cursor: Option<QuoteCursor>, // None on the last page
+}
+
+/// Aggregates for the quotes with one status
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusMetrics {
+    pub status: SwapStatus,
+    pub count: i64,
+    pub volume: i64, // Sum of amount_in
+    pub fees: i64,
+}
+
+impl FromRow<'_, DbRow> for StatusMetrics {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(StatusMetrics {
+            status: row.try_get("status")?,
+            count: row.try_get("count")?,
+            volume: row.try_get("volume")?,
+            fees: row.try_get("fees")?,
+        })
+    }
+}
+
+/// Quote aggregates grouped by status
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSummary {
+    pub by_status: Vec<StatusMetrics>,
+}
+
+impl MetricsSummary {
+    /// Aggregates for `status`, zero if no quote has it
+    pub fn status(&self, status: SwapStatus) -> StatusMetrics {
+        self.by_status
+            .iter()
+            .find(|m| m.status == status)
+            .cloned()
+            .unwrap_or(StatusMetrics {
+                status,
+                count: 0,
+                volume: 0,
+                fees: 0,
+            })
+    }
+
+    pub fn total_quotes(&self) -> i64 {
+        self.by_status.iter().map(|m| m.count).sum()
+    }
+}
+
+/// Request made under an `Idempotency-Key`, with its response once finished
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    pub scope: String,
+    pub key: String,
+    pub request_hash: String,
+    pub status_code: Option<i64>, // None while the request is in flight
+    pub response_body: Option<String>,
+    pub created_at: String,
+}
+
+impl FromRow<'_, DbRow> for IdempotencyRecord {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(IdempotencyRecord {
+            scope: row.try_get("scope")?,
+            key: row.try_get("key")?,
+            request_hash: row.try_get("request_hash")?,
+            status_code: row.try_get("status_code")?,
+            response_body: row.try_get("response_body")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// Webhook call for a quote status change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub quote_id: String,
+    pub event: String, // Status the quote moved to
+    pub url: String,
+    pub payload: String, // JSON body, signed as-is on every attempt
+    pub status: String,  // pending, delivered or failed
+    pub attempts: i64,
+    pub next_attempt_at: String,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+}
+
+impl FromRow<'_, DbRow> for WebhookDelivery {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(WebhookDelivery {
+            id: row.try_get("id")?,
+            quote_id: row.try_get("quote_id")?,
+            event: row.try_get("event")?,
+            url: row.try_get("url")?,
+            payload: row.try_get("payload")?,
+            status: row.try_get("status")?,
+            attempts: row.try_get("attempts")?,
+            next_attempt_at: row.try_get("next_attempt_at")?,
+            last_error: row.try_get("last_error")?,
+            created_at: row.try_get("created_at")?,
+            delivered_at: row.try_get("delivered_at")?,
+        })
+    }
+}
+
+/// Private keys of a quote (plaintext in memory, encrypted in the database)
+///
+/// Wiped from memory when dropped.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct QuoteKeys {
+    pub quote_id: String,
+    pub broker_swap_key: Vec<u8>,
+    pub adaptor_secret: Vec<u8>, // Empty for a client-first quote: the client holds it
+    pub refund_at: Option<i64>,
+    pub locked_proofs: Option<String>,       // Broker's outputs as JSON, once locked
+    pub encrypted_signature: Option<String>, // Broker's, hex, recorded with the outputs
+    pub client_signature: Option<String>,    // Client's encrypted, hex, once verified
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+}
+
+impl FromRow<'_, DbRow> for ApiKeyRecord {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(ApiKeyRecord {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            key_hash: row.try_get("key_hash")?,
+            created_at: row.try_get("created_at")?,
+            last_used_at: row.try_get("last_used_at")?,
+            revoked_at: row.try_get("revoked_at")?,
+        })
+    }
+}
+
+/// An administrative action and the values it changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub before: Option<String>, // JSON
+    pub after: Option<String>,  // JSON
+    pub request_id: Option<String>,
+    pub created_at: String,
+}
+
+impl FromRow<'_, DbRow> for AuditEntry {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(AuditEntry {
+            id: row.try_get("id").ok(),
+            actor: row.try_get("actor")?,
+            action: row.try_get("action")?,
+            target: row.try_get("target")?,
+            before: row.try_get("before_value")?,
+            after: row.try_get("after_value")?,
+            request_id: row.try_get("request_id")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// A mint added or removed at runtime
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintChange {
+    pub mint: MintConfig,
+    pub removed: bool,
+    pub updated_at: String,
+}
+
+impl MintChange {
+    pub fn added(mint: MintConfig) -> Self {
+        Self {
+            mint,
+            removed: false,
+            updated_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn removed(mint: MintConfig) -> Self {
+        Self {
+            removed: true,
+            ..Self::added(mint)
+        }
+    }
+}
+
+impl FromRow<'_, DbRow> for MintChange {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        let action: String = row.try_get("action")?;
+        Ok(MintChange {
+            mint: MintConfig {
+                mint_url: row.try_get("mint_url")?,
+                name: row.try_get("name")?,
+                unit: row.try_get("unit")?,
+            },
+            removed: action == "removed",
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+/// A mint quote paid while rebalancing whose ecash hasn't been minted yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingMint {
+    pub quote_id: String, // Mint quote on mint_url
+    pub mint_url: String,
+    pub amount: i64,
+    pub from_mint: String, // Mint whose liquidity paid the invoice
+    pub quote: Option<String>, // The wallet's mint quote as JSON, to hand back after a restart
+    pub created_at: String,
+}
+
+impl FromRow<'_, DbRow> for PendingMint {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(PendingMint {
+            quote_id: row.try_get("quote_id")?,
+            mint_url: row.try_get("mint_url")?,
+            amount: row.try_get("amount")?,
+            from_mint: row.try_get("from_mint")?,
+            quote: row.try_get("quote")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// A lease held by a broker instance (see [`crate::leases`])
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lease {
+    pub name: String,
+    pub holder: String,
+    pub expires_at: i64, // Unix time in milliseconds
+    pub acquired_at: String,
+}
+
+impl Lease {
+    /// Whether the lease has lapsed at `now`, so anyone may take it
+    pub fn expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now.timestamp_millis()
+    }
+}
+
+impl FromRow<'_, DbRow> for Lease {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(Lease {
+            name: row.try_get("name")?,
+            holder: row.try_get("holder")?,
+            expires_at: row.try_get("expires_at")?,
+            acquired_at: row.try_get("acquired_at")?,
+        })
+    }
+}
+
+/// A banned public key or proof (see [`crate::blacklist`])
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlacklistEntry {
+    pub kind: String,  // 'pubkey' or 'proof'
+    pub value: String, // Compressed point as lowercase hex
+    pub reason: Option<String>,
+    pub created_at: String,
+}
+
+impl BlacklistEntry {
+    pub fn new(kind: BlacklistKind, value: &str, reason: Option<String>) -> Self {
+        Self {
+            kind: kind.to_string(),
+            value: value.to_string(),
+            reason,
+            created_at: Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+impl FromRow<'_, DbRow> for BlacklistEntry {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(BlacklistEntry {
+            kind: row.try_get("kind")?,
+            value: row.try_get("value")?,
+            reason: row.try_get("reason")?,
+            created_at: row.try_get("created_at")?,
+        })
+    }
+}
+
+/// A swap split into chunks (see [`crate::chunked`])
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkedSwapRecord {
+    pub id: String,
+    pub source_mint: String,
+    pub target_mint: String,
+    pub amount: i64,      // Total the client pays
+    pub chunks: Vec<u64>, // Planned amount of each chunk
+    pub user_pubkey: String,
+    pub callback_url: Option<String>,
+    pub created_at: String,
+    pub stopped_at: Option<String>,
+}
+
+impl FromRow<'_, DbRow> for ChunkedSwapRecord {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(ChunkedSwapRecord {
+            id: row.try_get("id")?,
+            source_mint: row.try_get("source_mint")?,
+            target_mint: row.try_get("target_mint")?,
+            amount: row.try_get("amount")?,
+            chunks: serde_json::from_str(&row.try_get::<String, _>("chunks")?)
+                .map_err(|e| sqlx::Error::Decode(e.into()))?,
+            user_pubkey: row.try_get("user_pubkey")?,
+            callback_url: row.try_get("callback_url")?,
+            created_at: row.try_get("created_at")?,
+            stopped_at: row.try_get("stopped_at")?,
+        })
+    }
+}
+
+/// A quote issued for a chunk of a chunked swap
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapChunk {
+    pub index: i64,
+    pub quote_id: String,
+    pub status: SwapStatus,
+    pub amount_in: i64,
+    pub amount_out: i64,
+}
+
+impl FromRow<'_, DbRow> for SwapChunk {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(SwapChunk {
+            index: row.try_get("chunk_index")?,
+            quote_id: row.try_get("quote_id")?,
+            status: row.try_get("status")?,
+            amount_in: row.try_get("amount_in")?,
+            amount_out: row.try_get("amount_out")?,
+        })
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LiquiditySnapshot {
+    pub mint_url: String,
+    pub balance: i64,
+    pub proof_count: i64,
+    pub proofs: String,  // JSON serialized
+    pub updated_at: String,
+}
+
+impl std::fmt::Debug for LiquiditySnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LiquiditySnapshot")
+            .field("mint_url", &self.mint_url)
+            .field("balance", &self.balance)
+            .field("proof_count", &self.proof_count)
+            .field("proofs", &Sensitive(&self.proofs))
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
+
+impl FromRow<'_, DbRow> for LiquiditySnapshot {
+    fn from_row(row: &DbRow) -> sqlx::Result<Self> {
+        Ok(LiquiditySnapshot {
+            mint_url: row.try_get("mint_url")?,
+            balance: row.try_get("balance")?,
+            proof_count: row.try_get("proof_count")?,
+            proofs: row.try_get("proofs")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+// Tests run against in-memory SQLite
+#[cfg(all(test, not(feature = "postgres")))]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    async fn setup_test_db() -> Database {
+        // Use in-memory SQLite for tests
+        let db = Database::new("sqlite::memory:")
+            .await
+            .expect("Failed to create test database")
+            .with_cipher(SecretCipher::new(&[42u8; 32]));
+        db.migrate().await.expect("Failed to run migrations");
+        db
+    }
+
+    fn create_test_quote() -> QuoteRecord {
+        QuoteRecord {
+            id: "test-quote-123".to_string(),
+            source_mint: "http://mint-a.test".to_string(),
+            target_mint: "http://mint-b.test".to_string(),
+            amount_in: 100,
+            amount_out: 99,
+            fee: 1,
+            fee_rate: 0.01,
+            mint_fee: 0,
+            exchange_rate: None,
+            broker_pubkey: "02abcd1234".to_string(),
+            adaptor_point: "03efgh5678".to_string(),
+            tweaked_pubkey: "02ijkl9012".to_string(),
+            status: SwapStatus::Pending,
+            created_at: Utc::now().to_rfc3339(),
+            expires_at: Utc::now()
+                .checked_add_signed(chrono::Duration::seconds(300))
+                .unwrap()
+                .to_rfc3339(),
+            accepted_at: None,
+            completed_at: None,
+            user_pubkey: Some("02user1234".to_string()),
+            error_message: None,
+            callback_url: None,
+            job_status: None,
+            claim_order: ClaimOrder::BrokerFirst,
+            extended_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_get_quote() {
+        let db = setup_test_db().await;
+        let quote = create_test_quote();
+
+        db.create_quote(&quote).await.expect("Failed to create quote");
+
+        let retrieved = db
+            .get_quote(&quote.id)
+            .await
+            .expect("Failed to get quote")
+            .expect("Quote not found");
+
+        assert_eq!(retrieved.id, quote.id);
+        assert_eq!(retrieved.amount_in, quote.amount_in);
+        assert_eq!(retrieved.status, quote.status);
+        assert_eq!(retrieved.claim_order, ClaimOrder::BrokerFirst);
+
+        let client_first = QuoteRecord {
+            id: "client-first-quote".to_string(),
+            claim_order: ClaimOrder::ClientFirst,
+            ..create_test_quote()
+        };
+        db.create_quote(&client_first).await.unwrap();
+        let open = db.list_open_quotes().await.unwrap();
+        assert!(open
+            .iter()
+            .any(|q| q.id == client_first.id && q.claim_order == ClaimOrder::ClientFirst));
+    }
+
+    #[tokio::test]
+    async fn test_connect_options() {
+        let options = DatabaseOptions {
+            busy_timeout: Duration::from_millis(1234),
+            synchronous: Synchronous::Full,
+            ..Default::default()
+        };
+        let db = Database::connect("sqlite::memory:", options).await.unwrap();
+
+        let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(busy_timeout, 1234);
+        let synchronous: i64 = sqlx::query_scalar("PRAGMA synchronous")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(synchronous, 2); // FULL
+
+        assert_eq!("EXTRA".parse(), Ok(Synchronous::Extra));
+        assert!("sometimes".parse::<Synchronous>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_settings() {
+        let db = setup_test_db().await;
+
+        assert_eq!(db.get_setting("missing").await.unwrap(), None);
+        assert!(!db.maintenance_mode().await.unwrap());
+
+        db.set_maintenance_mode(true).await.unwrap();
+        assert!(db.maintenance_mode().await.unwrap());
+        db.set_maintenance_mode(false).await.unwrap();
+        assert!(!db.maintenance_mode().await.unwrap());
+        assert_eq!(
+            db.get_setting("maintenance").await.unwrap().as_deref(),
+            Some("false")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_leases() {
+        let db = setup_test_db().await;
+        let ttl = Duration::from_secs(30);
+
+        let lease = db.acquire_lease("sweep:quotes", "a", ttl).await.unwrap();
+        assert_eq!(lease.holder, "a");
+        assert!(!lease.expired(Utc::now()));
+
+        // Held by another instance: b gets the lease as it stands, still a's
+        let other = db.acquire_lease("sweep:quotes", "b", ttl).await.unwrap();
+        assert_eq!(other, lease);
+        assert!(!db.release_lease("sweep:quotes", "b").await.unwrap());
+
+        // Renewing keeps when it was first taken
+        let renewed = db.acquire_lease("sweep:quotes", "a", ttl).await.unwrap();
+        assert_eq!(renewed.acquired_at, lease.acquired_at);
+        assert!(renewed.expires_at >= lease.expires_at);
+
+        // Released or lapsed leases go to whoever asks next
+        assert!(db.release_lease("sweep:quotes", "a").await.unwrap());
+        let taken = db.acquire_lease("sweep:quotes", "b", Duration::ZERO).await.unwrap();
+        assert_eq!(taken.holder, "b");
+        assert!(taken.expired(Utc::now()));
+        let taken = db.acquire_lease("sweep:quotes", "a", ttl).await.unwrap();
+        assert_eq!(taken.holder, "a");
+
+        db.acquire_lease("mint:http://mint-a.test", "b", ttl).await.unwrap();
+        let leases = db.list_leases().await.unwrap();
+        let held: Vec<(&str, &str)> = leases
+            .iter()
+            .map(|l| (l.name.as_str(), l.holder.as_str()))
+            .collect();
+        assert_eq!(held, [("mint:http://mint-a.test", "b"), ("sweep:quotes", "a")]);
+    }
+
+    #[tokio::test]
+    async fn test_mint_registry() {
+        let db = setup_test_db().await;
+        let mint = |url: &str| MintConfig {
+            mint_url: url.to_string(),
+            name: "Mint".to_string(),
+            unit: "sat".to_string(),
+        };
+
+        db.record_mint_change(&MintChange::added(mint("http://mint-c.test")))
+            .await
+            .unwrap();
+        db.record_mint_change(&MintChange::removed(mint("http://mint-a.test")))
+            .await
+            .unwrap();
+
+        // A later change replaces the earlier one
+        let mut renamed = mint("http://mint-c.test");
+        renamed.name = "Mint C".to_string();
+        db.record_mint_change(&MintChange::removed(renamed))
+            .await
+            .unwrap();
+
+        let changes = db.list_mint_changes().await.unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].mint.mint_url, "http://mint-a.test");
+        assert!(changes[0].removed);
+        assert_eq!(changes[1].mint.name, "Mint C");
+        assert!(changes[1].removed);
+    }
+
+    #[tokio::test]
+    async fn test_blacklist() {
+        let db = setup_test_db().await;
+        let key = "02".to_string() + &"ab".repeat(32);
+        let y = "03".to_string() + &"cd".repeat(32);
+
+        db.add_blacklist_entry(&BlacklistEntry::new(BlacklistKind::Pubkey, &key, None))
+            .await
+            .unwrap();
+        db.add_blacklist_entry(&BlacklistEntry::new(
+            BlacklistKind::Proof,
+            &y,
+            Some("stolen".to_string()),
+        ))
+        .await
+        .unwrap();
+        // Banning again replaces the reason
+        db.add_blacklist_entry(&BlacklistEntry::new(
+            BlacklistKind::Pubkey,
+            &key,
+            Some("abuse".to_string()),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(db.list_blacklist().await.unwrap().len(), 2);
+
+        let found = db
+            .find_blacklisted(BlacklistKind::Proof, &["00".to_string(), y.clone()])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.value, y);
+        assert_eq!(found.reason.as_deref(), Some("stolen"));
+        let found = db
+            .find_blacklisted(BlacklistKind::Pubkey, &[key.clone()])
+            .await
+            .unwrap();
+        assert_eq!(found.unwrap().reason.as_deref(), Some("abuse"));
+
+        // Kinds don't mix
+        assert!(db
+            .find_blacklisted(BlacklistKind::Pubkey, &[y.clone()])
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db
+            .find_blacklisted(BlacklistKind::Proof, &[])
+            .await
+            .unwrap()
+            .is_none());
+
+        assert!(db.remove_blacklist_entry(BlacklistKind::Proof, &y).await.unwrap());
+        assert!(!db.remove_blacklist_entry(BlacklistKind::Proof, &y).await.unwrap());
+        assert_eq!(db.list_blacklist().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_client_volume() {
+        let db = setup_test_db().await;
+        let now = Utc::now();
+
+        db.record_client_volume("02alice", 1_000, now).await.unwrap();
+        db.record_client_volume("02alice", 500, now).await.unwrap();
+        db.record_client_volume("02alice", 2_000, now - chrono::Duration::days(40))
+            .await
+            .unwrap();
+        db.record_client_volume("02bob", 300, now).await.unwrap();
+
+        let since = now - chrono::Duration::days(30);
+        assert_eq!(db.client_volume("02alice", since).await.unwrap(), 1_500);
+        assert_eq!(
+            db.client_volume("02alice", now - chrono::Duration::days(45))
+                .await
+                .unwrap(),
+            3_500
+        );
+        assert_eq!(db.client_volume("02bob", since).await.unwrap(), 300);
+        assert_eq!(db.client_volume("02carol", since).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_job_status() {
+        let db = setup_test_db().await;
+        let quote = create_test_quote();
+        db.create_quote(&quote).await.unwrap();
+        assert_eq!(db.get_quote(&quote.id).await.unwrap().unwrap().job_status, None);
+
+        db.set_job_status(&quote.id, JobStatus::Running).await.unwrap();
+        db.set_job_status(&quote.id, JobStatus::Failed).await.unwrap();
+        let stored = db.get_quote(&quote.id).await.unwrap().unwrap();
+        assert_eq!(stored.job_status, Some(JobStatus::Failed));
+        assert_eq!(stored.status, SwapStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_proof_claims() {
+        let db = setup_test_db().await;
+        let first = create_test_quote();
+        let mut second = create_test_quote();
+        second.id = "test-quote-456".to_string();
+        db.create_quote(&first).await.unwrap();
+        db.create_quote(&second).await.unwrap();
+        let ys = |names: &[&str]| names.iter().map(|y| y.to_string()).collect::<Vec<_>>();
+
+        let claimed = db.claim_proofs(&first.id, &ys(&["y1", "y2"])).await.unwrap();
+        assert_eq!(claimed, ys(&["y1", "y2"]));
+        // Claiming again for the same quote is fine, and claims nothing new
+        let again = db.claim_proofs(&first.id, &ys(&["y2", "y4"])).await.unwrap();
+        assert_eq!(again, ys(&["y4"]));
+
+        // Releasing one claim's proofs leaves the others held
+        db.release_proofs(&first.id, &again).await.unwrap();
+        assert_eq!(db.claimed_proofs(&first.id).await.unwrap(), claimed);
+
+        // One shared proof fails the whole claim
+        let err = db.claim_proofs(&second.id, &ys(&["y3", "y2"])).await;
+        assert!(matches!(err, Err(BrokerError::DuplicateProofs(_))));
+        assert!(db.claimed_proofs(&second.id).await.unwrap().is_empty());
+
+        // Settling the first quote frees its proofs
+        db.update_quote_status(&first.id, SwapStatus::Failed, None)
+            .await
+            .unwrap();
+        assert!(db.claimed_proofs(&first.id).await.unwrap().is_empty());
+        let claimed = db.claim_proofs(&second.id, &ys(&["y3", "y2"])).await.unwrap();
+        assert_eq!(db.claimed_proofs(&second.id).await.unwrap(), ys(&["y2", "y3"]));
+
+        // Another quote's Ys are left alone
+        db.release_proofs(&first.id, &claimed).await.unwrap();
+        assert_eq!(db.claimed_proofs(&second.id).await.unwrap(), ys(&["y2", "y3"]));
+        db.release_proofs(&second.id, &claimed).await.unwrap();
+        assert!(db.claimed_proofs(&second.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_chunked_swaps() {
+        let db = setup_test_db().await;
+        let swap = ChunkedSwapRecord {
+            id: "chunked-1".to_string(),
+            source_mint: "http://mint-a.test".to_string(),
+            target_mint: "http://mint-b.test".to_string(),
+            amount: 250,
+            chunks: vec![125, 125],
+            user_pubkey: "02user1234".to_string(),
+            callback_url: None,
+            created_at: Utc::now().to_rfc3339(),
+            stopped_at: None,
+        };
+        db.create_chunked_swap(&swap).await.unwrap();
+        assert_eq!(db.get_chunked_swap(&swap.id).await.unwrap(), Some(swap.clone()));
+
+        let first = create_test_quote();
+        let second = QuoteRecord {
+            id: "test-quote-456".to_string(),
+            ..create_test_quote()
+        };
+        db.create_quote(&first).await.unwrap();
+        db.create_quote(&second).await.unwrap();
+        assert!(db.add_swap_chunk(&swap.id, 0, &first.id).await.unwrap());
+        // Not while the first chunk's quote is open
+        assert!(!db.add_swap_chunk(&swap.id, 1, &second.id).await.unwrap());
+
+        db.update_quote_status(&first.id, SwapStatus::Completed, None)
+            .await
+            .unwrap();
+        assert!(db.add_swap_chunk(&swap.id, 1, &second.id).await.unwrap());
+        let chunks = db.list_swap_chunks(&swap.id).await.unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].index, chunks[0].status), (0, SwapStatus::Completed));
+        assert_eq!((chunks[1].index, chunks[1].quote_id.as_str()), (1, "test-quote-456"));
+        assert_eq!(db.chunked_swap_of(&second.id).await.unwrap(), Some(swap.id.clone()));
+        assert_eq!(db.chunked_swap_of("unknown").await.unwrap(), None);
+
+        // Stopping happens once, and takes no further chunks
+        assert!(db.stop_chunked_swap(&swap.id).await.unwrap());
+        assert!(!db.stop_chunked_swap(&swap.id).await.unwrap());
+        assert!(db.get_chunked_swap(&swap.id).await.unwrap().unwrap().stopped_at.is_some());
+        db.update_quote_status(&second.id, SwapStatus::Expired, None)
+            .await
+            .unwrap();
+        let third = QuoteRecord {
+            id: "test-quote-789".to_string(),
+            ..create_test_quote()
+        };
+        db.create_quote(&third).await.unwrap();
+        assert!(!db.add_swap_chunk(&swap.id, 1, &third.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_pending_migrations() {
+        // Before the first migration there is nothing to list them from
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        assert!(!matches!(db.pending_migrations().await, Ok(0)));
+
+        db.migrate().await.unwrap();
+        assert_eq!(db.pending_migrations().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_update_quote_status() {
+        let db = setup_test_db().await;
+        let quote = create_test_quote();
+
+        db.create_quote(&quote).await.expect("Failed to create quote");
+
+        db.update_quote_status(&quote.id, SwapStatus::Accepted, None)
+            .await
+            .expect("Failed to update status");
+
+        let updated = db
+            .get_quote(&quote.id)
+            .await
+            .expect("Failed to get quote")
+            .expect("Quote not found");
+
+        assert_eq!(updated.status, SwapStatus::Accepted);
+        assert!(updated.accepted_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_status_column() {
+        let db = setup_test_db().await;
+        let quote = create_test_quote();
+        db.create_quote(&quote).await.unwrap();
+
+        // Statuses are stored as their names and decoded straight into SwapStatus
+        let raw: String = sqlx::query_scalar("SELECT status FROM quotes WHERE id = $1")
+            .bind(&quote.id)
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(raw, "pending");
+        let status: SwapStatus = sqlx::query_scalar("SELECT status FROM quotes WHERE id = $1")
+            .bind(&quote.id)
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(status, SwapStatus::Pending);
+
+        // The table only takes known statuses
+        let bogus = sqlx::query("UPDATE quotes SET status = 'done' WHERE id = $1")
+            .bind(&quote.id)
+            .execute(db.pool())
+            .await;
+        assert!(bogus.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_quotes_with_filter() {
+        let db = setup_test_db().await;
+
+        for i in 0..3 {
+            let mut quote = create_test_quote();
+            quote.id = format!("pending-{}", i);
+            db.create_quote(&quote).await.expect("Failed to create quote");
+        }
+
+        for i in 0..2 {
+            let mut quote = create_test_quote();
+            quote.id = format!("completed-{}", i);
+            quote.status = SwapStatus::Completed;
+            db.create_quote(&quote).await.expect("Failed to create quote");
+        }
+
+        let filter = QuoteFilter {
+            status: Some(SwapStatus::Completed),
+            ..Default::default()
+        };
+        let completed = db
+            .list_quotes(&filter, 10)
+            .await
+            .expect("Failed to list quotes");
+
+        assert_eq!(completed.len(), 2);
+        assert!(completed.iter().all(|q| q.status == SwapStatus::Completed));
+    }
+
+    #[tokio::test]
+    async fn test_swap_lifecycle() {
+        let db = setup_test_db().await;
+        let quote = create_test_quote();
+
+        db.create_quote(&quote).await.expect("Failed to create quote");
+
+        let swap = SwapRecord {
+            id: "swap-123".to_string(),
+            quote_id: quote.id.clone(),
+            source_proofs: r#"[{"amount":100}]"#.to_string(),
+            target_proofs: None,
+            encrypted_signature: Some("enc_sig_123".to_string()),
+            decrypted_signature: None,
+            adaptor_secret: None,
+            started_at: Utc::now().to_rfc3339(),
+            completed_at: None,
+        };
+
+        db.create_swap(&swap).await.expect("Failed to create swap");
+
+        db.complete_swap(
+            &swap.id,
+            r#"[{"amount":99}]"#,
+            Some("dec_sig_123"),
+            Some("adaptor_secret_123"),
+        )
+        .await
+        .expect("Failed to complete swap");
+
+        let completed = db
+            .get_swap_by_quote(&quote.id)
+            .await
+            .expect("Failed to get swap")
+            .expect("Swap not found");
+
+        assert!(completed.target_proofs.is_some());
+        assert!(completed.decrypted_signature.is_some());
+        assert!(completed.adaptor_secret.is_some());
+        assert!(completed.completed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_quote_lifecycle_transactions() {
+        let db = setup_test_db().await;
+        let quote = create_test_quote();
+        db.create_quote(&quote).await.unwrap();
+
+        let swap = SwapRecord {
+            id: "swap-123".to_string(),
+            quote_id: quote.id.clone(),
+            source_proofs: r#"[{"amount":100}]"#.to_string(),
+            target_proofs: Some(r#"[{"amount":99}]"#.to_string()),
+            encrypted_signature: Some("enc_sig_123".to_string()),
+            decrypted_signature: None,
+            adaptor_secret: None,
+            started_at: Utc::now().to_rfc3339(),
+            completed_at: None,
+        };
+        db.accept_quote(&swap).await.expect("Failed to accept quote");
+
+        let accepted = db.get_quote(&quote.id).await.unwrap().unwrap();
+        assert_eq!(accepted.status, SwapStatus::Accepted);
+        assert!(accepted.accepted_at.is_some());
+        let stored = db.get_swap_by_quote(&quote.id).await.unwrap().unwrap();
+        assert_eq!(stored.target_proofs, swap.target_proofs);
+
+        // A failed swap insert leaves the quote pending
+        let mut other = quote.clone();
+        other.id = "test-quote-456".to_string();
+        db.create_quote(&other).await.unwrap();
+        let duplicate = SwapRecord {
+            quote_id: other.id.clone(),
+            ..swap.clone()
+        };
+        assert!(db.accept_quote(&duplicate).await.is_err());
+        let pending = db.get_quote(&other.id).await.unwrap().unwrap();
+        assert_eq!(pending.status, SwapStatus::Pending);
+        assert!(pending.accepted_at.is_none());
+
+        db.complete_quote(&quote.id, Some("dec_sig_123"), Some("secret_123"))
+            .await
+            .expect("Failed to complete quote");
+        let completed = db.get_quote(&quote.id).await.unwrap().unwrap();
+        assert_eq!(completed.status, SwapStatus::Completed);
+        let settled = db.get_swap_by_quote(&quote.id).await.unwrap().unwrap();
+        assert_eq!(settled.decrypted_signature.as_deref(), Some("dec_sig_123"));
+        assert_eq!(settled.adaptor_secret.as_deref(), Some("secret_123"));
+        assert!(settled.completed_at.is_some());
+
+        // Closing a quote forgets its keys
+        db.save_quote_keys(&QuoteKeys {
+            quote_id: other.id.clone(),
+            broker_swap_key: vec![1u8; 32],
+            adaptor_secret: vec![2u8; 32],
+            refund_at: None,
+            locked_proofs: None,
+            encrypted_signature: None,
+            client_signature: None,
+            created_at: Utc::now().to_rfc3339(),
+        })
+        .await
+        .unwrap();
+        db.close_quote(&other.id, SwapStatus::Failed, Some("Mint said no".to_string()))
+            .await
+            .expect("Failed to close quote");
+        let failed = db.get_quote(&other.id).await.unwrap().unwrap();
+        assert_eq!(failed.status, SwapStatus::Failed);
+        assert_eq!(failed.error_message.as_deref(), Some("Mint said no"));
+        assert!(db.get_quote_keys(&other.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_swap_secrets_encrypted() {
+        let db = setup_test_db().await;
+        let quote = create_test_quote();
+
+        for id in ["swap-new", "swap-old"] {
+            let mut quote = quote.clone();
+            quote.id = format!("quote-{}", id);
+            db.create_quote(&quote).await.unwrap();
+            db.create_swap(&SwapRecord {
+                id: id.to_string(),
+                quote_id: quote.id,
+                source_proofs: "[]".to_string(),
+                target_proofs: None,
+                encrypted_signature: None,
+                decrypted_signature: None,
+                adaptor_secret: None,
+                started_at: Utc::now().to_rfc3339(),
+                completed_at: None,
+            })
+            .await
+            .unwrap();
+        }
+        db.complete_swap("swap-new", "[]", Some("dec_sig"), Some("secret"))
+            .await
+            .unwrap();
+
+        // A row from before encryption was added
+        sqlx::query("UPDATE swaps SET decrypted_signature = 'old_sig', adaptor_secret = 'old_secret' WHERE id = 'swap-old'")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let raw = |id: &'static str| {
+            sqlx::query_scalar::<_, String>("SELECT adaptor_secret FROM swaps WHERE id = $1")
+                .bind(id)
+                .fetch_one(&db.pool)
+        };
+        assert!(is_encrypted_text(&raw("swap-new").await.unwrap()));
+        assert_eq!(raw("swap-old").await.unwrap(), "old_secret");
+
+        // Both read back in plaintext
+        let new = db.get_swap("swap-new").await.unwrap().unwrap();
+        assert_eq!(new.decrypted_signature.as_deref(), Some("dec_sig"));
+        assert_eq!(new.adaptor_secret.as_deref(), Some("secret"));
+        let old = db.get_swap_by_quote("quote-swap-old").await.unwrap().unwrap();
+        assert_eq!(old.adaptor_secret.as_deref(), Some("old_secret"));
+
+        // Only the old row needs rewriting, and only once
+        assert_eq!(db.encrypt_swap_secrets().await.unwrap(), 1);
+        assert_eq!(db.encrypt_swap_secrets().await.unwrap(), 0);
+        assert!(is_encrypted_text(&raw("swap-old").await.unwrap()));
+        let old = db.get_swap("swap-old").await.unwrap().unwrap();
+        assert_eq!(old.decrypted_signature.as_deref(), Some("old_sig"));
+    }
+
+    #[tokio::test]
+    async fn test_liquidity_events() {
+        let db = setup_test_db().await;
+
+        // Test event without quote_id (for manual deposits/withdrawals)
+        let event = LiquidityEvent {
+            id: None,
+            mint_url: "http://mint-a.test".to_string(),
+            event_type: "deposit".to_string(),
+            amount: 100,
+            balance_after: 500,
+            quote_id: None, // No quote_id for manual deposits
+            created_at: Utc::now().to_rfc3339(),
+        };
+
+        db.record_liquidity_event(&event)
+            .await
+            .expect("Failed to record event");
+
+        let events = db
+            .get_liquidity_events("http://mint-a.test", 10)
+            .await
+            .expect("Failed to get events");
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "deposit");
+    }
+
+    #[tokio::test]
+    async fn test_pending_mints() {
+        let db = setup_test_db().await;
+        let pending = PendingMint {
+            quote_id: "mint-quote-1".to_string(),
+            mint_url: "http://mint-b.test".to_string(),
+            amount: 1000,
+            from_mint: "http://mint-a.test".to_string(),
+            quote: Some(r#"{"id":"mint-quote-1"}"#.to_string()),
+            created_at: Utc::now().to_rfc3339(),
+        };
+
+        db.save_pending_mint(&pending).await.unwrap();
+        // Saving again keeps the first record
+        db.save_pending_mint(&PendingMint { amount: 1, ..pending.clone() })
+            .await
+            .unwrap();
+        assert_eq!(db.list_pending_mints().await.unwrap(), [pending.clone()]);
+        // The quote can hold the key it is locked to (NUT-20), so it is sealed
+        let stored = sqlx::query_scalar::<_, String>("SELECT quote FROM pending_mints")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert!(is_encrypted_text(&stored));
+
+        assert!(db.delete_pending_mint(&pending.quote_id).await.unwrap());
+        assert!(!db.delete_pending_mint(&pending.quote_id).await.unwrap());
+        assert!(db.list_pending_mints().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_liquidity_snapshot_upsert() {
+        let db = setup_test_db().await;
+
+        let mut snapshot = LiquiditySnapshot {
+            mint_url: "http://mint-a.test".to_string(),
+            balance: 100,
+            proof_count: 2,
+            proofs: "[]".to_string(),
+            updated_at: Utc::now().to_rfc3339(),
+        };
+
+        db.save_liquidity_snapshot(&snapshot)
+            .await
+            .expect("Failed to save snapshot");
+
+        snapshot.balance = 250;
+        snapshot.proof_count = 5;
+        db.save_liquidity_snapshot(&snapshot)
+            .await
+            .expect("Failed to update snapshot");
+
+        let stored = db
+            .get_liquidity_snapshot("http://mint-a.test")
+            .await
+            .expect("Failed to get snapshot")
+            .expect("Snapshot not found");
+
+        assert_eq!(stored.balance, 250);
+        assert_eq!(stored.proof_count, 5);
+    }
+
+    #[tokio::test]
+    async fn test_quote_keys_encrypted_roundtrip() {
+        let db = setup_test_db().await;
+
+        let keys = QuoteKeys {
+            quote_id: "test-quote-123".to_string(),
+            broker_swap_key: vec![1u8; 32],
+            adaptor_secret: vec![2u8; 32],
+            refund_at: None,
+            locked_proofs: None,
+            encrypted_signature: None,
+            client_signature: None,
+            created_at: Utc::now().to_rfc3339(),
+        };
+
+        db.save_quote_keys(&keys).await.expect("Failed to save keys");
+        db.set_quote_refund_at(&keys.quote_id, 1_700_000_000)
+            .await
+            .expect("Failed to set refund time");
+
+        // Stored ciphertext is not the plaintext scalar
+        let raw: Vec<u8> = sqlx::query_scalar("SELECT adaptor_secret FROM quote_keys WHERE quote_id = $1")
+            .bind(&keys.quote_id)
+            .fetch_one(db.pool())
+            .await
+            .expect("Failed to read raw row");
+        assert_ne!(raw, keys.adaptor_secret);
+
+        let loaded = db
+            .get_quote_keys(&keys.quote_id)
+            .await
+            .expect("Failed to load keys")
+            .expect("Keys not found");
+
+        assert_eq!(loaded.broker_swap_key, keys.broker_swap_key);
+        assert_eq!(loaded.adaptor_secret, keys.adaptor_secret);
+        assert_eq!(loaded.refund_at, Some(1_700_000_000));
+    }
+
+    #[tokio::test]
+    async fn test_list_open_quotes() {
+        let db = setup_test_db().await;
+
+        for (id, status) in [
+            ("open-pending", SwapStatus::Pending),
+            ("open-accepted", SwapStatus::Accepted),
+            ("done", SwapStatus::Completed),
+        ] {
+            let mut quote = create_test_quote();
+            quote.id = id.to_string();
+            quote.status = status;
+            db.create_quote(&quote).await.expect("Failed to create quote");
+        }
+
+        let open = db.list_open_quotes().await.expect("Failed to list quotes");
+        assert_eq!(open.len(), 2);
+        assert!(open.iter().all(|q| q.id.starts_with("open-")));
+    }
+
+    #[tokio::test]
+    async fn test_expire_stale_quotes() {
+        let db = setup_test_db().await;
+
+        let mut stale = create_test_quote();
+        stale.id = "stale".to_string();
+        stale.expires_at = (Utc::now() - chrono::Duration::seconds(10)).to_rfc3339();
+        db.create_quote(&stale).await.expect("Failed to create quote");
+
+        let mut fresh = create_test_quote();
+        fresh.id = "fresh".to_string();
+        db.create_quote(&fresh).await.expect("Failed to create quote");
+
+        let expired = db.expire_stale_quotes().await.expect("Failed to expire quotes");
+        assert_eq!(expired, vec!["stale".to_string()]);
+
+        let stale = db.get_quote("stale").await.unwrap().unwrap();
+        assert_eq!(stale.status, SwapStatus::Expired);
+        let fresh = db.get_quote("fresh").await.unwrap().unwrap();
+        assert_eq!(fresh.status, SwapStatus::Pending);
     }
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LiquidityEvent {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub id: Option<i64>,
-    pub mint_url: String,
-    pub event_type: String,  // 'deposit', 'withdrawal', 'swap_in', 'swap_out'
-    pub amount: i64,
-    pub balance_after: i64,
-    pub quote_id: Option<String>,
-    pub created_at: String,
-}
+    #[tokio::test]
+    async fn test_extend_quote() {
+        let db = setup_test_db().await;
 
-impl FromRow<'_, sqlx::sqlite::SqliteRow> for LiquidityEvent {
-    fn from_row(row: &sqlx::sqlite::SqliteRow) -> sqlx::Result<Self> {
-        Ok(LiquidityEvent {
-            id: row.try_get("id").ok(),
-            mint_url: row.try_get("mint_url")?,
-            event_type: row.try_get("event_type")?,
-            amount: row.try_get("amount")?,
-            balance_after: row.try_get("balance_after")?,
-            quote_id: row.try_get("quote_id")?,
-            created_at: row.try_get("created_at")?,
-        })
+        let quote = create_test_quote();
+        db.create_quote(&quote).await.unwrap();
+        let mut stale = create_test_quote();
+        stale.id = "stale".to_string();
+        stale.expires_at = (Utc::now() - chrono::Duration::seconds(10)).to_rfc3339();
+        db.create_quote(&stale).await.unwrap();
+
+        let expires_at = Utc::now() + chrono::Duration::seconds(600);
+        assert!(db.extend_quote(&quote.id, expires_at).await.unwrap());
+        let extended = db.get_quote(&quote.id).await.unwrap().unwrap();
+        assert_eq!(extended.expires_at, expires_at.to_rfc3339());
+        assert!(extended.extended_at.is_some());
+
+        // Once only, and never past the expiry
+        assert!(!db.extend_quote(&quote.id, expires_at).await.unwrap());
+        assert!(!db.extend_quote("stale", expires_at).await.unwrap());
+        assert!(!db.extend_quote("unknown", expires_at).await.unwrap());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use chrono::Utc;
+    #[tokio::test]
+    async fn test_locked_outputs_outlive_quote_expiry() {
+        let db = setup_test_db().await;
 
-    async fn setup_test_db() -> Database {
-        // Use in-memory SQLite for tests
-        let db = Database::new("sqlite::memory:")
+        let mut quote = create_test_quote();
+        quote.expires_at = (Utc::now() - chrono::Duration::seconds(10)).to_rfc3339();
+        db.create_quote(&quote).await.unwrap();
+        db.save_quote_keys(&QuoteKeys {
+            quote_id: quote.id.clone(),
+            broker_swap_key: vec![1u8; 32],
+            adaptor_secret: vec![2u8; 32],
+            refund_at: None,
+            locked_proofs: None,
+            encrypted_signature: None,
+            client_signature: None,
+            created_at: Utc::now().to_rfc3339(),
+        })
+        .await
+        .unwrap();
+
+        // Outputs locked but never handed out: the quote waits for them
+        db.record_locked_outputs(&quote.id, 1_700_000_000, "[]", "ab12")
             .await
-            .expect("Failed to create test database");
-        db.migrate().await.expect("Failed to run migrations");
-        db
+            .unwrap();
+        assert!(db.expire_stale_quotes().await.unwrap().is_empty());
+        db.record_client_signature(&quote.id, "cd34").await.unwrap();
+
+        let keys = db.get_quote_keys(&quote.id).await.unwrap().unwrap();
+        assert_eq!(keys.refund_at, Some(1_700_000_000));
+        assert_eq!(keys.locked_proofs.as_deref(), Some("[]"));
+        assert_eq!(keys.encrypted_signature.as_deref(), Some("ab12"));
+        assert_eq!(keys.client_signature.as_deref(), Some("cd34"));
+        let raw: Option<String> =
+            sqlx::query_scalar("SELECT locked_proofs FROM quote_keys WHERE quote_id = $1")
+                .bind(&quote.id)
+                .fetch_one(db.pool())
+                .await
+                .unwrap();
+        assert_ne!(raw.as_deref(), Some("[]"));
     }
 
-    fn create_test_quote() -> QuoteRecord {
-        QuoteRecord {
-            id: "test-quote-123".to_string(),
-            source_mint: "http://mint-a.test".to_string(),
-            target_mint: "http://mint-b.test".to_string(),
-            amount_in: 100,
-            amount_out: 99,
-            fee: 1,
-            fee_rate: 0.01,
-            broker_pubkey: "02abcd1234".to_string(),
-            adaptor_point: "03efgh5678".to_string(),
-            tweaked_pubkey: "02ijkl9012".to_string(),
-            status: SwapStatus::Pending.to_string(),
+    #[tokio::test]
+    async fn test_api_keys() {
+        let db = setup_test_db().await;
+
+        let key = ApiKeyRecord {
+            id: "key-1".to_string(),
+            name: "dashboard".to_string(),
+            key_hash: "abcd".to_string(),
             created_at: Utc::now().to_rfc3339(),
-            expires_at: Utc::now()
-                .checked_add_signed(chrono::Duration::seconds(300))
-                .unwrap()
-                .to_rfc3339(),
-            accepted_at: None,
-            completed_at: None,
-            user_pubkey: Some("02user1234".to_string()),
-            error_message: None,
-        }
+            last_used_at: None,
+            revoked_at: None,
+        };
+        db.create_api_key(&key).await.expect("Failed to create API key");
+
+        let found = db.find_api_key("abcd").await.unwrap().expect("Key not found");
+        assert_eq!(found.name, "dashboard");
+        assert!(db.find_api_key("other").await.unwrap().is_none());
+
+        db.touch_api_key("key-1").await.unwrap();
+        assert!(db.list_api_keys().await.unwrap()[0].last_used_at.is_some());
+
+        assert!(db.revoke_api_key("key-1").await.unwrap());
+        assert!(!db.revoke_api_key("key-1").await.unwrap());
+        assert!(db.find_api_key("abcd").await.unwrap().is_none());
     }
 
     #[tokio::test]
-    async fn test_create_and_get_quote() {
+    async fn test_list_quotes_page() {
         let db = setup_test_db().await;
-        let quote = create_test_quote();
 
-        db.create_quote(&quote).await.expect("Failed to create quote");
+        let created_at = Utc::now();
+        for i in 0..5 {
+            let mut quote = create_test_quote();
+            quote.id = format!("quote-{}", i);
+            quote.created_at = (created_at + chrono::Duration::seconds(i)).to_rfc3339();
+            if i == 4 {
+                quote.status = SwapStatus::Completed;
+            }
+            db.create_quote(&quote).await.expect("Failed to create quote");
+        }
 
-        let retrieved = db
-            .get_quote(&quote.id)
+        let filter = QuoteFilter::default();
+        let first = db.list_quotes_page(&filter, 2, None).await.unwrap();
+        assert_eq!(first.total, 5);
+        assert_eq!(
+            first.quotes.iter().map(|q| q.id.as_str()).collect::<Vec<_>>(),
+            vec!["quote-4", "quote-3"]
+        );
+
+        let cursor = QuoteCursor::decode(&first.next_cursor.unwrap().encode()).unwrap();
+        let second = db.list_quotes_page(&filter, 2, Some(&cursor)).await.unwrap();
+        assert_eq!(
+            second.quotes.iter().map(|q| q.id.as_str()).collect::<Vec<_>>(),
+            vec!["quote-2", "quote-1"]
+        );
+
+        let last = db
+            .list_quotes_page(&filter, 2, second.next_cursor.as_ref())
             .await
-            .expect("Failed to get quote")
-            .expect("Quote not found");
+            .unwrap();
+        assert_eq!(last.quotes.len(), 1);
+        assert!(last.next_cursor.is_none());
 
-        assert_eq!(retrieved.id, quote.id);
-        assert_eq!(retrieved.amount_in, quote.amount_in);
-        assert_eq!(retrieved.status, quote.status);
+        let pending = QuoteFilter {
+            status: Some(SwapStatus::Pending),
+            ..Default::default()
+        };
+        assert_eq!(db.count_quotes(&pending).await.unwrap(), 4);
+
+        assert!(QuoteCursor::decode("not-a-cursor").is_none());
     }
 
     #[tokio::test]
-    async fn test_update_quote_status() {
+    async fn test_quote_listing_uses_indices() {
         let db = setup_test_db().await;
-        let quote = create_test_quote();
 
-        db.create_quote(&quote).await.expect("Failed to create quote");
+        let plan = |filter: QuoteFilter| {
+            let db = db.clone();
+            async move {
+                let mut query = QueryBuilder::<Db>::new(format!(
+                    "EXPLAIN QUERY PLAN SELECT {} FROM quotes",
+                    QUOTE_COLUMNS
+                ));
+                push_quote_filter(&mut query, &filter);
+                query.push(" ORDER BY created_at DESC, id DESC LIMIT 10");
+                query
+                    .build()
+                    .fetch_all(db.pool())
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|row| row.get::<String, _>("detail"))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            }
+        };
 
-        db.update_quote_status(&quote.id, SwapStatus::Accepted, None)
-            .await
-            .expect("Failed to update status");
+        let by_status = plan(QuoteFilter {
+            status: Some(SwapStatus::Completed),
+            ..Default::default()
+        })
+        .await;
+        assert!(by_status.contains("idx_quotes_status_created_at"), "{}", by_status);
+        assert!(!by_status.contains("TEMP B-TREE"), "{}", by_status);
 
-        let updated = db
-            .get_quote(&quote.id)
-            .await
-            .expect("Failed to get quote")
-            .expect("Quote not found");
+        let by_user = plan(QuoteFilter {
+            user_pubkey: Some("02user1234".to_string()),
+            ..Default::default()
+        })
+        .await;
+        assert!(by_user.contains("idx_quotes_user_pubkey_created_at"), "{}", by_user);
 
-        assert_eq!(updated.status, SwapStatus::Accepted.to_string());
-        assert!(updated.accepted_at.is_some());
+        let by_pair = plan(QuoteFilter {
+            source_mint: Some("http://mint-a.test".to_string()),
+            target_mint: Some("http://mint-b.test".to_string()),
+            ..Default::default()
+        })
+        .await;
+        assert!(by_pair.contains("idx_quotes_mints"), "{}", by_pair);
     }
 
     #[tokio::test]
-    async fn test_list_quotes_with_filter() {
+    async fn test_list_quotes_filters() {
         let db = setup_test_db().await;
 
-        for i in 0..3 {
+        let now = Utc::now();
+        for i in 0..4 {
             let mut quote = create_test_quote();
-            quote.id = format!("pending-{}", i);
+            quote.id = format!("quote-{}", i);
+            quote.created_at = (now - chrono::Duration::days(i)).to_rfc3339();
+            if i % 2 == 1 {
+                quote.target_mint = "http://mint-c.test".to_string();
+                quote.user_pubkey = Some("02other5678".to_string());
+                quote.status = SwapStatus::Failed;
+            }
             db.create_quote(&quote).await.expect("Failed to create quote");
         }
 
-        for i in 0..2 {
+        let ids = |quotes: Vec<QuoteRecord>| {
+            quotes.into_iter().map(|q| q.id).collect::<Vec<_>>()
+        };
+
+        let into_c = QuoteFilter {
+            target_mint: Some("http://mint-c.test".to_string()),
+            ..Default::default()
+        };
+        let quotes = db.list_quotes(&into_c, 10).await.unwrap();
+        assert_eq!(ids(quotes), vec!["quote-1", "quote-3"]);
+
+        let by_user = QuoteFilter {
+            user_pubkey: Some("02user1234".to_string()),
+            source_mint: Some("http://mint-a.test".to_string()),
+            ..Default::default()
+        };
+        let quotes = db.list_quotes(&by_user, 10).await.unwrap();
+        assert_eq!(ids(quotes), vec!["quote-0", "quote-2"]);
+
+        // Failed swaps into mint C over the last two days
+        let recent_failures = QuoteFilter {
+            status: Some(SwapStatus::Failed),
+            target_mint: Some("http://mint-c.test".to_string()),
+            from: Some(now - chrono::Duration::hours(36)),
+            to: Some(now + chrono::Duration::seconds(1)),
+            ..Default::default()
+        };
+        let quotes = db.list_quotes(&recent_failures, 10).await.unwrap();
+        assert_eq!(ids(quotes), vec!["quote-1"]);
+        assert_eq!(db.count_quotes(&recent_failures).await.unwrap(), 1);
+
+        let window = QuoteFilter {
+            from: Some(now - chrono::Duration::hours(60)),
+            to: Some(now - chrono::Duration::hours(12)),
+            ..Default::default()
+        };
+        let quotes = db.list_quotes(&window, 10).await.unwrap();
+        assert_eq!(ids(quotes), vec!["quote-1", "quote-2"]);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_summary() {
+        let db = setup_test_db().await;
+
+        let now = Utc::now();
+        for (i, status) in [
+            SwapStatus::Completed,
+            SwapStatus::Completed,
+            SwapStatus::Failed,
+            SwapStatus::Pending,
+        ]
+        .into_iter()
+        .enumerate()
+        {
             let mut quote = create_test_quote();
-            quote.id = format!("completed-{}", i);
-            quote.status = SwapStatus::Completed.to_string();
+            quote.id = format!("quote-{}", i);
+            quote.status = status;
+            quote.created_at = (now - chrono::Duration::days(i as i64)).to_rfc3339();
             db.create_quote(&quote).await.expect("Failed to create quote");
         }
 
-        let completed = db
-            .list_quotes(Some(SwapStatus::Completed), 10)
-            .await
-            .expect("Failed to list quotes");
+        let summary = db.get_metrics_summary(None, None).await.unwrap();
+        assert_eq!(summary.total_quotes(), 4);
 
-        assert_eq!(completed.len(), 2);
-        assert!(completed.iter().all(|q| q.status == SwapStatus::Completed.to_string()));
+        let completed = summary.status(SwapStatus::Completed);
+        assert_eq!(completed.count, 2);
+        assert_eq!(completed.volume, 200);
+        assert_eq!(completed.fees, 2);
+        assert_eq!(summary.status(SwapStatus::Failed).count, 1);
+        assert_eq!(summary.status(SwapStatus::Expired).count, 0);
+
+        // Only the last day and a half
+        let recent = db
+            .get_metrics_summary(Some(now - chrono::Duration::hours(36)), None)
+            .await
+            .unwrap();
+        assert_eq!(recent.total_quotes(), 2);
+        assert_eq!(recent.status(SwapStatus::Completed).count, 2);
+        assert_eq!(recent.status(SwapStatus::Failed).count, 0);
     }
 
     #[tokio::test]
-    async fn test_swap_lifecycle() {
+    async fn test_accounting_totals() {
         let db = setup_test_db().await;
         let quote = create_test_quote();
-
         db.create_quote(&quote).await.expect("Failed to create quote");
 
+        let now = Utc::now();
+        let entries = [
+            LedgerEntry::new(EntryKind::SwapFee, "http://mint-a.test", "sat", 5, Some(&quote.id)),
+            LedgerEntry::new(EntryKind::SwapFee, "http://mint-a.test", "sat", 3, None),
+            LedgerEntry::new(EntryKind::MintFee, "http://mint-a.test", "sat", 1, Some(&quote.id)),
+            LedgerEntry {
+                created_at: (now - chrono::Duration::days(2)).to_rfc3339(),
+                ..LedgerEntry::new(EntryKind::LightningFee, "http://mint-b.test", "sat", 10, None)
+            },
+        ];
+        for entry in &entries {
+            db.record_ledger_entry(entry).await.expect("Failed to record entry");
+        }
+
+        let by_quote = db.get_ledger_entries_by_quote(&quote.id).await.unwrap();
+        assert_eq!(by_quote.len(), 2);
+        assert_eq!(by_quote[0].kind, "swap_fee");
+
+        let amount = |totals: &[LedgerTotal], mint: &str, kind: EntryKind| {
+            totals
+                .iter()
+                .find(|t| t.mint_url == mint && t.kind == kind.to_string())
+                .map(|t| t.amount)
+        };
+
+        let totals = db.get_accounting_totals(None, None).await.unwrap();
+        assert_eq!(totals.len(), 3);
+        assert_eq!(amount(&totals, "http://mint-a.test", EntryKind::SwapFee), Some(8));
+        assert_eq!(amount(&totals, "http://mint-b.test", EntryKind::LightningFee), Some(10));
+
+        // The rebalance two days ago falls outside the last day
+        let recent = db
+            .get_accounting_totals(Some(now - chrono::Duration::days(1)), None)
+            .await
+            .unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(amount(&recent, "http://mint-b.test", EntryKind::LightningFee), None);
+    }
+
+    #[tokio::test]
+    async fn test_export_quotes() {
+        let db = setup_test_db().await;
+
+        let now = Utc::now();
+        for i in 0..3 {
+            let mut quote = create_test_quote();
+            quote.id = format!("quote-{}", i);
+            quote.created_at = (now - chrono::Duration::days(2 - i)).to_rfc3339();
+            db.create_quote(&quote).await.expect("Failed to create quote");
+        }
+
         let swap = SwapRecord {
-            id: "swap-123".to_string(),
-            quote_id: quote.id.clone(),
+            id: "swap-2".to_string(),
+            quote_id: "quote-2".to_string(),
             source_proofs: r#"[{"amount":100}]"#.to_string(),
             target_proofs: None,
-            encrypted_signature: Some("enc_sig_123".to_string()),
+            encrypted_signature: None,
             decrypted_signature: None,
             adaptor_secret: None,
-            started_at: Utc::now().to_rfc3339(),
+            started_at: now.to_rfc3339(),
             completed_at: None,
         };
+        db.create_swap(&swap).await.expect("Failed to create swap");
+        db.complete_swap(&swap.id, "[]", None, None).await.unwrap();
+        for amount in [1, 2] {
+            let entry = LedgerEntry::new(
+                EntryKind::MintFee,
+                "http://mint-a.test",
+                "sat",
+                amount,
+                Some("quote-2"),
+            );
+            db.record_ledger_entry(&entry).await.unwrap();
+        }
 
+        let all: Vec<QuoteExport> = db
+            .export_quotes(None, None)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        let ids: Vec<&str> = all.iter().map(|q| q.id.as_str()).collect();
+        assert_eq!(ids, ["quote-0", "quote-1", "quote-2"]);
+        assert_eq!(all[0].swap_completed_at, None);
+        assert_eq!(all[0].mint_fees_paid, 0);
+        assert!(all[2].swap_completed_at.is_some());
+        assert_eq!(all[2].mint_fees_paid, 3);
+
+        // The last day and a half, up to an hour ago
+        let recent: Vec<QuoteExport> = db
+            .export_quotes(
+                Some(now - chrono::Duration::hours(36)),
+                Some(now - chrono::Duration::hours(1)),
+            )
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].id, "quote-1");
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let db = setup_test_db().await;
+
+        for (action, before, after) in [
+            ("set_fee_rate", r#"{"fee_rate":0.005}"#, r#"{"fee_rate":0.01}"#),
+            ("withdraw_ecash", r#"{"balance":1000}"#, r#"{"balance":400}"#),
+            ("set_fee_rate", r#"{"fee_rate":0.01}"#, r#"{"fee_rate":0.02}"#),
+        ] {
+            let entry = AuditEntry {
+                id: None,
+                actor: "alice".to_string(),
+                action: action.to_string(),
+                target: None,
+                before: Some(before.to_string()),
+                after: Some(after.to_string()),
+                request_id: Some("req-1".to_string()),
+                created_at: Utc::now().to_rfc3339(),
+            };
+            db.record_audit_entry(&entry).await.expect("Failed to record entry");
+        }
+
+        let all = db.list_audit_entries(None, 10).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].after.as_deref(), Some(r#"{"fee_rate":0.02}"#));
+        assert_eq!(all[0].actor, "alice");
+
+        let fee_changes = db.list_audit_entries(Some("set_fee_rate"), 10).await.unwrap();
+        assert_eq!(fee_changes.len(), 2);
+
+        let latest = db.list_audit_entries(None, 1).await.unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].id, all[0].id);
+    }
+
+    #[tokio::test]
+    async fn test_prune() {
+        let db = setup_test_db().await;
+
+        let now = Utc::now();
+        let old = (now - chrono::Duration::days(40)).to_rfc3339();
+        for (id, status, created_at) in [
+            ("old-completed", SwapStatus::Completed, &old),
+            ("old-failed", SwapStatus::Failed, &old),
+            ("old-accepted", SwapStatus::Accepted, &old),
+            ("new-completed", SwapStatus::Completed, &now.to_rfc3339()),
+        ] {
+            let mut quote = create_test_quote();
+            quote.id = id.to_string();
+            quote.status = status;
+            quote.created_at = created_at.clone();
+            db.create_quote(&quote).await.expect("Failed to create quote");
+        }
+
+        let swap = SwapRecord {
+            id: "old-swap".to_string(),
+            quote_id: "old-completed".to_string(),
+            source_proofs: r#"[{"amount":100}]"#.to_string(),
+            target_proofs: None,
+            encrypted_signature: None,
+            decrypted_signature: None,
+            adaptor_secret: None,
+            started_at: old.clone(),
+            completed_at: None,
+        };
         db.create_swap(&swap).await.expect("Failed to create swap");
+        db.record_ledger_entry(&LedgerEntry::new(
+            EntryKind::SwapFee,
+            "http://mint-a.test",
+            "sat",
+            1,
+            Some("old-completed"),
+        ))
+        .await
+        .unwrap();
 
-        db.complete_swap(
-            &swap.id,
-            r#"[{"amount":99}]"#,
-            Some("dec_sig_123"),
-            Some("adaptor_secret_123"),
+        for (amount, created_at) in [(100, &old), (50, &old), (10, &now.to_rfc3339())] {
+            let event = LiquidityEvent {
+                id: None,
+                mint_url: "http://mint-a.test".to_string(),
+                event_type: "deposit".to_string(),
+                amount,
+                balance_after: 0,
+                quote_id: Some("old-completed".to_string()),
+                created_at: created_at.clone(),
+            };
+            db.record_liquidity_event(&event).await.unwrap();
+        }
+
+        let cutoff = now - chrono::Duration::days(30);
+        let report = db.prune(cutoff, true).await.unwrap();
+        assert_eq!(
+            report,
+            PruneReport {
+                quotes_deleted: 2,
+                quotes_archived: 2,
+                liquidity_events: 2,
+            }
+        );
+
+        // Open and recent quotes stay, settled old ones are gone with their swap
+        assert!(db.get_quote("old-completed").await.unwrap().is_none());
+        assert!(db.get_quote("old-failed").await.unwrap().is_none());
+        assert!(db.get_quote("old-accepted").await.unwrap().is_some());
+        assert!(db.get_quote("new-completed").await.unwrap().is_some());
+        assert!(db.get_swap_by_quote("old-completed").await.unwrap().is_none());
+        assert_eq!(db.count_archived_quotes().await.unwrap(), 2);
+
+        // The ledger keeps the fee, detached from the quote
+        let totals = db.get_accounting_totals(None, None).await.unwrap();
+        assert_eq!(totals[0].amount, 1);
+
+        let summaries = db
+            .get_liquidity_event_summaries("http://mint-a.test")
+            .await
+            .unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].day, old[..10]);
+        assert_eq!(summaries[0].event_count, 2);
+        assert_eq!(summaries[0].total_amount, 150);
+        let events = db.get_liquidity_events("http://mint-a.test", 10).await.unwrap();
+        assert_eq!(events.len(), 1);
+
+        // Nothing left to prune
+        assert!(db.prune(cutoff, false).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_keys() {
+        let db = setup_test_db().await;
+        let scope = "POST /quote/q1/accept";
+        let stale_before = Utc::now() - chrono::Duration::minutes(10);
+
+        assert!(db
+            .reserve_idempotency_key(scope, "k1", "hash", stale_before)
+            .await
+            .unwrap());
+        assert!(!db
+            .reserve_idempotency_key(scope, "k1", "hash", stale_before)
+            .await
+            .unwrap());
+        // Keys are scoped to the endpoint
+        assert!(db
+            .reserve_idempotency_key("POST /quote/q1/complete", "k1", "hash", stale_before)
+            .await
+            .unwrap());
+
+        let in_flight = db.get_idempotency_key(scope, "k1").await.unwrap().unwrap();
+        assert_eq!(in_flight.status_code, None);
+
+        db.complete_idempotency_key(scope, "k1", 200, "{}").await.unwrap();
+        // Finished keys are never released
+        db.release_idempotency_key(scope, "k1").await.unwrap();
+
+        let done = db.get_idempotency_key(scope, "k1").await.unwrap().unwrap();
+        assert_eq!(done.status_code, Some(200));
+        assert_eq!(done.response_body.as_deref(), Some("{}"));
+        // Responses can hold an adaptor secret, so they are sealed
+        let stored = sqlx::query_scalar::<_, String>(
+            "SELECT response_body FROM idempotency_keys WHERE status_code = 200",
         )
+        .fetch_one(db.pool())
         .await
-        .expect("Failed to complete swap");
+        .unwrap();
+        assert!(is_encrypted_text(&stored));
 
-        let completed = db
-            .get_swap_by_quote(&quote.id)
+        assert!(db
+            .reserve_idempotency_key(scope, "k2", "hash", stale_before)
             .await
-            .expect("Failed to get swap")
-            .expect("Swap not found");
+            .unwrap());
+        db.release_idempotency_key(scope, "k2").await.unwrap();
+        assert!(db.get_idempotency_key(scope, "k2").await.unwrap().is_none());
 
-        assert!(completed.target_proofs.is_some());
-        assert!(completed.decrypted_signature.is_some());
-        assert!(completed.adaptor_secret.is_some());
-        assert!(completed.completed_at.is_some());
+        // A request left in flight past its time is taken over, a finished one
+        // is not
+        let now = Utc::now() + chrono::Duration::seconds(1);
+        assert!(db
+            .reserve_idempotency_key(scope, "k3", "hash", stale_before)
+            .await
+            .unwrap());
+        assert!(db
+            .reserve_idempotency_key(scope, "k3", "other", now)
+            .await
+            .unwrap());
+        let taken = db.get_idempotency_key(scope, "k3").await.unwrap().unwrap();
+        assert_eq!(taken.request_hash, "other");
+        assert!(!db
+            .reserve_idempotency_key(scope, "k1", "hash", now)
+            .await
+            .unwrap());
+
+        // Expiry drops every key claimed before the cutoff
+        assert_eq!(db.expire_idempotency_keys(stale_before).await.unwrap(), 0);
+        assert_eq!(db.expire_idempotency_keys(now).await.unwrap(), 3);
+        assert!(db.get_idempotency_key(scope, "k1").await.unwrap().is_none());
     }
 
     #[tokio::test]
-    async fn test_liquidity_events() {
-        let db = setup_test_db().await;
+    async fn test_webhook_deliveries() {
+        let db = setup_test_db().await.with_webhooks();
 
-        // Test event without quote_id (for manual deposits/withdrawals)
-        let event = LiquidityEvent {
-            id: None,
-            mint_url: "http://mint-a.test".to_string(),
-            event_type: "deposit".to_string(),
-            amount: 100,
-            balance_after: 500,
-            quote_id: None, // No quote_id for manual deposits
-            created_at: Utc::now().to_rfc3339(),
-        };
+        let mut quote = create_test_quote();
+        quote.callback_url = Some("https://shop.example.com/hooks".to_string());
+        db.create_quote(&quote).await.expect("Failed to create quote");
 
-        db.record_liquidity_event(&event)
+        let mut silent = create_test_quote();
+        silent.id = "no-callback".to_string();
+        db.create_quote(&silent).await.expect("Failed to create quote");
+
+        db.update_quote_status(&quote.id, SwapStatus::Accepted, None)
             .await
-            .expect("Failed to record event");
+            .unwrap();
+        db.update_quote_status(&silent.id, SwapStatus::Accepted, None)
+            .await
+            .unwrap();
 
-        let events = db
-            .get_liquidity_events("http://mint-a.test", 10)
+        let due = db.due_webhook_deliveries(10).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].quote_id, quote.id);
+        assert_eq!(due[0].event, "quote.accepted");
+        assert_eq!(due[0].url, "https://shop.example.com/hooks");
+
+        // A retry scheduled in the future is not due yet
+        let later = (Utc::now() + chrono::Duration::minutes(5)).to_rfc3339();
+        db.record_webhook_failure(&due[0].id, 1, Some(&later), "HTTP 500")
             .await
-            .expect("Failed to get events");
+            .unwrap();
+        assert!(db.due_webhook_deliveries(10).await.unwrap().is_empty());
 
-        assert_eq!(events.len(), 1);
-        assert_eq!(events[0].event_type, "deposit");
+        db.update_quote_status(&quote.id, SwapStatus::Completed, None)
+            .await
+            .unwrap();
+        let due = db.due_webhook_deliveries(10).await.unwrap();
+        assert_eq!(due.len(), 1);
+        db.mark_webhook_delivered(&due[0].id, 1).await.unwrap();
+
+        let deliveries = db.list_webhook_deliveries(&quote.id).await.unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert_eq!(deliveries[0].status, "pending");
+        assert_eq!(deliveries[0].last_error.as_deref(), Some("HTTP 500"));
+        assert_eq!(deliveries[1].status, "delivered");
+
+        // Nothing is queued unless webhooks are enabled
+        let db = setup_test_db().await;
+        db.create_quote(&quote).await.expect("Failed to create quote");
+        db.update_quote_status(&quote.id, SwapStatus::Failed, Some("boom".to_string()))
+            .await
+            .unwrap();
+        assert!(db.list_webhook_deliveries(&quote.id).await.unwrap().is_empty());
     }
 }
@@ -1,8 +1,8 @@
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::db::{Database, LiquidityEvent, QuoteRecord, SwapRecord};
-    use crate::types::SwapStatus;
+    use crate::db::{Database, LiquidityEvent, QuoteFilter, QuoteRecord, SwapRecord};
+    use crate::types::{ClaimOrder, SwapStatus};
     use chrono::Utc;
 
     async fn setup_test_db() -> Database {
@@ -23,6 +23,8 @@ mod tests {
             amount_out: 99,
             fee: 1,
             fee_rate: 0.01,
+            mint_fee: 0,
+            exchange_rate: None,
             broker_pubkey: "02abcd1234".to_string(),
             adaptor_point: "03efgh5678".to_string(),
             tweaked_pubkey: "02ijkl9012".to_string(),
@@ -36,6 +38,10 @@ mod tests {
             completed_at: None,
             user_pubkey: Some("02user1234".to_string()),
             error_message: None,
+            callback_url: None,
+            job_status: None,
+            claim_order: ClaimOrder::BrokerFirst,
+            extended_at: None,
         }
     }
 
@@ -144,7 +150,7 @@ mod tests {
         }
 
         let quotes = db
-            .list_quotes(None, 10)
+            .list_quotes(&QuoteFilter::default(), 10)
             .await
             .expect("Failed to list quotes");
 
@@ -170,8 +176,12 @@ mod tests {
         }
 
         // Get only completed
+        let filter = QuoteFilter {
+            status: Some(SwapStatus::Completed),
+            ..Default::default()
+        };
         let completed = db
-            .list_quotes(Some(SwapStatus::Completed), 10)
+            .list_quotes(&filter, 10)
             .await
             .expect("Failed to list quotes");
 
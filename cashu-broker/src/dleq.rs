@@ -0,0 +1,140 @@
+//! NUT-12 DLEQ checks of proofs the broker receives
+//!
+//! A mint's blind signature comes with a DLEQ proof that it was made with the
+//! key the mint publishes for that amount. Without checking it, a mint (or
+//! whoever sits between it and the broker) could hand out signatures under a
+//! key it keeps for tagging the broker's proofs, or ones it will never honor.
+//! Before proofs from a swap or a mint count as liquidity, each one's DLEQ is
+//! checked against its keyset's public keys. A proof without one can't be
+//! vouched for either, so it fails too unless DLEQs are optional, for mints
+//! that don't support NUT-12.
+
+use cdk::nuts::{Id, Keys, Proof, Proofs};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Why a proof failed [`partition`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    NoKeys,          // Its keyset's keys weren't at hand to check it against
+    Invalid(String), // Its DLEQ doesn't verify
+    Missing,         // It carries no DLEQ, and one is required
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::NoKeys => f.write_str("keys of its keyset are unavailable"),
+            Failure::Invalid(reason) => f.write_str(reason),
+            Failure::Missing => f.write_str("no DLEQ proof"),
+        }
+    }
+}
+
+/// Check a proof's DLEQ against its keyset's `keys`
+pub fn check_proof(proof: &Proof, keys: &Keys) -> Result<(), String> {
+    if proof.dleq.is_none() {
+        return Ok(());
+    }
+    let key = keys
+        .amount_key(proof.amount)
+        .ok_or_else(|| format!("keyset {} has no key for {}", proof.keyset_id, proof.amount))?;
+    proof
+        .verify_dleq(key)
+        .map_err(|e| format!("invalid DLEQ proof: {}", e))
+}
+
+/// Split `proofs` into those that pass [`check_proof`] and those that don't,
+/// with the reason
+///
+/// `keys` maps keyset IDs to their public keys; a proof with a DLEQ from a
+/// keyset missing there fails, as it can't be checked. A proof without a DLEQ
+/// fails unless `required` is false.
+pub fn partition(
+    proofs: Proofs,
+    keys: &HashMap<Id, Keys>,
+    required: bool,
+) -> (Proofs, Vec<(Proof, Failure)>) {
+    let mut valid = Vec::new();
+    let mut failed = Vec::new();
+
+    for proof in proofs {
+        let checked = match keys.get(&proof.keyset_id) {
+            _ if proof.dleq.is_none() && required => Err(Failure::Missing),
+            _ if proof.dleq.is_none() => Ok(()),
+            Some(keys) => check_proof(&proof, keys).map_err(Failure::Invalid),
+            None => Err(Failure::NoKeys),
+        };
+        match checked {
+            Ok(()) => valid.push(proof),
+            Err(reason) => failed.push((proof, reason)),
+        }
+    }
+
+    (valid, failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::dhke::{blind_message, construct_proofs, sign_message};
+    use cdk::nuts::{BlindSignature, SecretKey};
+    use cdk::secret::Secret;
+    use cdk::Amount;
+    use std::collections::BTreeMap;
+    use std::str::FromStr;
+
+    /// A proof of 8 signed with `mint_key`, with its DLEQ
+    fn signed_proof(keyset_id: Id, mint_key: &SecretKey) -> Proof {
+        let amount = Amount::from(8);
+        let secret = Secret::generate();
+        let (blinded, r) = blind_message(&secret.to_bytes(), None).unwrap();
+        let signature = BlindSignature::new(
+            amount,
+            sign_message(mint_key, &blinded).unwrap(),
+            keyset_id,
+            &blinded,
+            mint_key.clone(),
+        )
+        .unwrap();
+        let keys = Keys::new(BTreeMap::from([(amount, mint_key.public_key())]));
+
+        construct_proofs(vec![signature], vec![r], vec![secret], &keys)
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn test_partition() {
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+        let mint_key = SecretKey::generate();
+        let keys = HashMap::from([(
+            keyset_id,
+            Keys::new(BTreeMap::from([(Amount::from(8), mint_key.public_key())])),
+        )]);
+
+        let good = signed_proof(keyset_id, &mint_key);
+        assert!(good.dleq.is_some());
+        // Signed under a key other than the published one
+        let bad = signed_proof(keyset_id, &SecretKey::generate());
+        let mut without_dleq = signed_proof(keyset_id, &SecretKey::generate());
+        without_dleq.dleq = None;
+
+        let proofs = vec![good.clone(), bad.clone(), without_dleq.clone()];
+        let (valid, failed) = partition(proofs.clone(), &keys, true);
+        assert_eq!(valid, vec![good.clone()]);
+        assert_eq!(failed.len(), 2);
+        assert_eq!(failed[0].0, bad);
+        assert_eq!(failed[1], (without_dleq.clone(), Failure::Missing));
+
+        // Unless DLEQs are optional, when only bad ones fail
+        let (valid, failed) = partition(proofs, &keys, false);
+        assert_eq!(valid, vec![good.clone(), without_dleq]);
+        assert_eq!(failed.len(), 1);
+
+        // No keys to check against
+        let (valid, failed) = partition(vec![good], &HashMap::new(), true);
+        assert!(valid.is_empty());
+        assert_eq!(failed[0].1, Failure::NoKeys);
+    }
+}
@@ -0,0 +1,169 @@
+//! Encryption for secrets stored at rest
+//!
+//! ChaCha20-Poly1305 with a random 96-bit nonce prepended to each ciphertext.
+//! The 32-byte key comes from the environment (hex) or a key file that is
+//! created on first start. Binary columns hold `nonce || ciphertext`; text
+//! columns hold it hex-encoded behind an `enc:v1:` prefix, so values written
+//! before encryption was added can still be told apart and read.
+
+use crate::error::{BrokerError, Result};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::path::Path;
+
+const NONCE_LEN: usize = 12;
+
+/// Marks an encrypted value in a text column
+const TEXT_PREFIX: &str = "enc:v1:";
+
+/// Authenticated cipher for database secrets
+pub struct SecretCipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl SecretCipher {
+    /// Create a cipher from a raw 32-byte key
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+
+    /// Create a cipher from a hex-encoded 32-byte key
+    pub fn from_hex(hex_key: &str) -> Result<Self> {
+        let bytes = hex::decode(hex_key.trim())
+            .map_err(|e| BrokerError::Encryption(format!("Invalid key hex: {}", e)))?;
+        let key: [u8; 32] = bytes.try_into().map_err(|_| {
+            BrokerError::Encryption("Encryption key must be 32 bytes".to_string())
+        })?;
+
+        Ok(Self::new(&key))
+    }
+
+    /// Load the key from a file, generating and saving a new one if it doesn't exist
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let hex_key = std::fs::read_to_string(path)?;
+            return Self::from_hex(&hex_key);
+        }
+
+        let key: [u8; 32] = rand::random();
+        write_key_file(path, &hex::encode(key))?;
+        tracing::info!("Generated new encryption key at {}", path.display());
+
+        Ok(Self::new(&key))
+    }
+
+    /// Encrypt, returning `nonce || ciphertext`
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| BrokerError::Encryption(format!("Encryption failed: {}", e)))?;
+
+        let mut data = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    /// Decrypt data produced by [`SecretCipher::encrypt`]
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < NONCE_LEN {
+            return Err(BrokerError::Encryption("Ciphertext too short".to_string()));
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|e| BrokerError::Encryption(format!("Decryption failed: {}", e)))
+    }
+
+    /// Encrypt a value for a text column
+    pub fn encrypt_text(&self, plaintext: &str) -> Result<String> {
+        let data = self.encrypt(plaintext.as_bytes())?;
+        Ok(format!("{}{}", TEXT_PREFIX, hex::encode(data)))
+    }
+
+    /// Decrypt a value from [`SecretCipher::encrypt_text`]
+    ///
+    /// Values without the prefix were stored in plaintext and are returned as is.
+    pub fn decrypt_text(&self, value: &str) -> Result<String> {
+        let Some(encoded) = value.strip_prefix(TEXT_PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let data = hex::decode(encoded)
+            .map_err(|e| BrokerError::Encryption(format!("Invalid ciphertext hex: {}", e)))?;
+
+        String::from_utf8(self.decrypt(&data)?)
+            .map_err(|e| BrokerError::Encryption(format!("Decrypted value is not UTF-8: {}", e)))
+    }
+}
+
+/// Whether a text column value was written by [`SecretCipher::encrypt_text`]
+pub fn is_encrypted_text(value: &str) -> bool {
+    value.starts_with(TEXT_PREFIX)
+}
+
+#[cfg(unix)]
+pub(crate) fn write_key_file(path: &Path, contents: &str) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn write_key_file(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let cipher = SecretCipher::new(&[7u8; 32]);
+
+        let encrypted = cipher.encrypt(b"adaptor secret").unwrap();
+        assert_ne!(&encrypted[NONCE_LEN..], b"adaptor secret");
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"adaptor secret");
+    }
+
+    #[test]
+    fn test_decrypt_with_wrong_key_fails() {
+        let encrypted = SecretCipher::new(&[1u8; 32]).encrypt(b"secret").unwrap();
+        assert!(SecretCipher::new(&[2u8; 32]).decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn test_text_roundtrip() {
+        let cipher = SecretCipher::new(&[7u8; 32]);
+
+        let encrypted = cipher.encrypt_text("ab12cd").unwrap();
+        assert!(is_encrypted_text(&encrypted));
+        assert!(!encrypted.contains("ab12cd"));
+        assert_eq!(cipher.decrypt_text(&encrypted).unwrap(), "ab12cd");
+
+        // Plaintext from before encryption passes through
+        assert!(!is_encrypted_text("ab12cd"));
+        assert_eq!(cipher.decrypt_text("ab12cd").unwrap(), "ab12cd");
+
+        assert!(SecretCipher::new(&[8u8; 32]).decrypt_text(&encrypted).is_err());
+        assert!(cipher.decrypt_text("enc:v1:zz").is_err());
+    }
+
+    #[test]
+    fn test_from_hex_rejects_short_key() {
+        assert!(SecretCipher::from_hex("abcd").is_err());
+    }
+}
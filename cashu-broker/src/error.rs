@@ -1,5 +1,14 @@
 //! Error types for Cashu broker
+//!
+//! Every [`BrokerError`] maps to an [`ErrorCode`], the stable,
+//! machine-readable `code` of API error responses. Codes are listed with
+//! their meaning in `GET /info`; new ones may be added, but existing codes
+//! keep their name and whether they are worth retrying.
 
+use crate::blacklist::BlacklistKind;
+use crate::risk::RiskViolation;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, BrokerError>;
@@ -13,6 +22,9 @@ pub enum BrokerError {
         available: u64,
     },
 
+    #[error("Liquidity on mint {mint_url} is held by broker instance {holder}")]
+    MintLeased { mint_url: String, holder: String },
+
     #[error("Invalid swap request: {0}")]
     InvalidSwapRequest(String),
 
@@ -34,15 +46,74 @@ pub enum BrokerError {
     #[error("Cannot swap to same mint")]
     SameMintSwap,
 
+    #[error("Risk limit exceeded: {0}")]
+    RiskLimitExceeded(RiskViolation),
+
+    #[error("Blacklisted {kind}: {value}")]
+    Blacklisted { kind: BlacklistKind, value: String },
+
+    #[error("Broker is in maintenance mode and not taking new quotes")]
+    Maintenance,
+
+    #[error("Mint is failing health checks: {0}")]
+    MintUnhealthy(String),
+
+    #[error("Mint unavailable after repeated failures: {0}")]
+    MintUnavailable(String),
+
+    #[error("Mint did not answer in time: {0}")]
+    MintTimeout(String),
+
+    #[error("Swap queue is full ({0} waiting), try again shortly")]
+    QueueFull(usize),
+
+    #[error("Proofs not spendable: {0}")]
+    ProofsNotSpendable(String),
+
+    #[error("Proofs not bound to the quote: {0}")]
+    ProofsNotBound(String),
+
+    #[error("Proofs already in use: {0}")]
+    DuplicateProofs(String),
+
     #[error("Adaptor signature error: {0}")]
     AdaptorSignature(String),
 
+    #[error("Invalid quote signature: {0}")]
+    QuoteSignature(String),
+
     #[error("CDK error: {0}")]
     Cdk(String),
 
     #[error("Database error: {0}")]
     Database(String),
 
+    #[error("Encryption error: {0}")]
+    Encryption(String),
+
+    #[error("Price feed error: {0}")]
+    PriceFeed(String),
+
+    #[error("Nostr error: {0}")]
+    Nostr(String),
+
+    #[error("Webhook error: {0}")]
+    Webhook(String),
+
+    #[error("Alert delivery failed: {0}")]
+    Alert(String),
+
+    #[error("HTTP error: {0}")]
+    Http(String),
+
+    #[error("Broker returned {status} {code}: {message}")]
+    Api {
+        status: u16,
+        code: String,
+        message: String,
+        retryable: bool,
+    },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -52,3 +123,277 @@ pub enum BrokerError {
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+impl BrokerError {
+    /// Whether the operation may succeed if tried again later, unchanged
+    ///
+    /// For an error from a broker's API, as the broker said.
+    pub fn retryable(&self) -> bool {
+        match self {
+            BrokerError::Api { retryable, .. } => *retryable,
+            err => err.code().retryable(),
+        }
+    }
+
+    /// The API error code of this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            BrokerError::InsufficientLiquidity { .. } | BrokerError::MintLeased { .. } => {
+                ErrorCode::InsufficientLiquidity
+            }
+            BrokerError::InvalidSwapRequest(_) => ErrorCode::InvalidSwapRequest,
+            BrokerError::QuoteNotFound(_) => ErrorCode::QuoteNotFound,
+            BrokerError::QuoteExpired(_) => ErrorCode::QuoteExpired,
+            BrokerError::AmountTooLow { .. } => ErrorCode::AmountTooLow,
+            BrokerError::AmountTooHigh { .. } => ErrorCode::AmountTooHigh,
+            BrokerError::UnsupportedMint(_) => ErrorCode::UnsupportedMint,
+            BrokerError::SameMintSwap => ErrorCode::SameMint,
+            BrokerError::RiskLimitExceeded(_) => ErrorCode::RiskLimitExceeded,
+            BrokerError::Blacklisted { .. } => ErrorCode::Blacklisted,
+            BrokerError::Maintenance => ErrorCode::Maintenance,
+            BrokerError::MintUnhealthy(_) => ErrorCode::MintUnhealthy,
+            BrokerError::MintUnavailable(_) => ErrorCode::MintUnavailable,
+            BrokerError::MintTimeout(_) => ErrorCode::MintTimeout,
+            BrokerError::QueueFull(_) => ErrorCode::QueueFull,
+            BrokerError::ProofsNotSpendable(_) => ErrorCode::ProofsNotSpendable,
+            BrokerError::ProofsNotBound(_) => ErrorCode::ProofsNotBound,
+            BrokerError::DuplicateProofs(_) => ErrorCode::DuplicateProofs,
+            BrokerError::AdaptorSignature(_) => ErrorCode::InvalidSignature,
+            BrokerError::QuoteSignature(_) => ErrorCode::InvalidSignature,
+            BrokerError::Cdk(_) => ErrorCode::MintError,
+            BrokerError::Database(_) => ErrorCode::DatabaseError,
+            BrokerError::PriceFeed(_) => ErrorCode::PriceUnavailable,
+            BrokerError::Http(_) | BrokerError::Api { .. } => ErrorCode::UpstreamError,
+            BrokerError::Encryption(_)
+            | BrokerError::Nostr(_)
+            | BrokerError::Webhook(_)
+            | BrokerError::Alert(_)
+            | BrokerError::Io(_)
+            | BrokerError::Serialization(_)
+            | BrokerError::Other(_) => ErrorCode::InternalError,
+        }
+    }
+}
+
+/// Machine-readable code of an API error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    BadRequest,
+    ValidationFailed,
+    PayloadTooLarge,
+    Unauthorized,
+    NotFound,
+    Conflict,
+    RateLimited,
+    RequestTimeout,
+    InvalidSwapRequest,
+    AmountTooLow,
+    AmountTooHigh,
+    UnsupportedMint,
+    SameMint,
+    QuoteNotFound,
+    QuoteExpired,
+    InvalidSignature,
+    ProofsNotSpendable,
+    ProofsNotBound,
+    DuplicateProofs,
+    Blacklisted,
+    RiskLimitExceeded,
+    InsufficientLiquidity,
+    Maintenance,
+    QueueFull,
+    MintUnhealthy,
+    MintUnavailable,
+    MintTimeout,
+    MintError,
+    PriceUnavailable,
+    UpstreamError,
+    DatabaseError,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 32] = [
+        ErrorCode::BadRequest,
+        ErrorCode::ValidationFailed,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::Unauthorized,
+        ErrorCode::NotFound,
+        ErrorCode::Conflict,
+        ErrorCode::RateLimited,
+        ErrorCode::RequestTimeout,
+        ErrorCode::InvalidSwapRequest,
+        ErrorCode::AmountTooLow,
+        ErrorCode::AmountTooHigh,
+        ErrorCode::UnsupportedMint,
+        ErrorCode::SameMint,
+        ErrorCode::QuoteNotFound,
+        ErrorCode::QuoteExpired,
+        ErrorCode::InvalidSignature,
+        ErrorCode::ProofsNotSpendable,
+        ErrorCode::ProofsNotBound,
+        ErrorCode::DuplicateProofs,
+        ErrorCode::Blacklisted,
+        ErrorCode::RiskLimitExceeded,
+        ErrorCode::InsufficientLiquidity,
+        ErrorCode::Maintenance,
+        ErrorCode::QueueFull,
+        ErrorCode::MintUnhealthy,
+        ErrorCode::MintUnavailable,
+        ErrorCode::MintTimeout,
+        ErrorCode::MintError,
+        ErrorCode::PriceUnavailable,
+        ErrorCode::UpstreamError,
+        ErrorCode::DatabaseError,
+        ErrorCode::InternalError,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "BAD_REQUEST",
+            ErrorCode::ValidationFailed => "VALIDATION_FAILED",
+            ErrorCode::PayloadTooLarge => "PAYLOAD_TOO_LARGE",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::RateLimited => "RATE_LIMITED",
+            ErrorCode::RequestTimeout => "REQUEST_TIMEOUT",
+            ErrorCode::InvalidSwapRequest => "INVALID_SWAP_REQUEST",
+            ErrorCode::AmountTooLow => "AMOUNT_TOO_LOW",
+            ErrorCode::AmountTooHigh => "AMOUNT_TOO_HIGH",
+            ErrorCode::UnsupportedMint => "UNSUPPORTED_MINT",
+            ErrorCode::SameMint => "SAME_MINT",
+            ErrorCode::QuoteNotFound => "QUOTE_NOT_FOUND",
+            ErrorCode::QuoteExpired => "QUOTE_EXPIRED",
+            ErrorCode::InvalidSignature => "INVALID_SIGNATURE",
+            ErrorCode::ProofsNotSpendable => "PROOFS_NOT_SPENDABLE",
+            ErrorCode::ProofsNotBound => "PROOFS_NOT_BOUND",
+            ErrorCode::DuplicateProofs => "DUPLICATE_PROOFS",
+            ErrorCode::Blacklisted => "BLACKLISTED",
+            ErrorCode::RiskLimitExceeded => "RISK_LIMIT_EXCEEDED",
+            ErrorCode::InsufficientLiquidity => "INSUFFICIENT_LIQUIDITY",
+            ErrorCode::Maintenance => "MAINTENANCE",
+            ErrorCode::QueueFull => "QUEUE_FULL",
+            ErrorCode::MintUnhealthy => "MINT_UNHEALTHY",
+            ErrorCode::MintUnavailable => "MINT_UNAVAILABLE",
+            ErrorCode::MintTimeout => "MINT_TIMEOUT",
+            ErrorCode::MintError => "MINT_ERROR",
+            ErrorCode::PriceUnavailable => "PRICE_UNAVAILABLE",
+            ErrorCode::UpstreamError => "UPSTREAM_ERROR",
+            ErrorCode::DatabaseError => "DATABASE_ERROR",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+        }
+    }
+
+    /// Whether the same request may succeed later, unchanged
+    pub fn retryable(self) -> bool {
+        match self {
+            ErrorCode::RateLimited
+            | ErrorCode::RequestTimeout
+            | ErrorCode::RiskLimitExceeded
+            | ErrorCode::InsufficientLiquidity
+            | ErrorCode::Maintenance
+            | ErrorCode::QueueFull
+            | ErrorCode::MintUnhealthy
+            | ErrorCode::MintUnavailable
+            | ErrorCode::MintTimeout
+            | ErrorCode::MintError
+            | ErrorCode::PriceUnavailable
+            | ErrorCode::UpstreamError
+            | ErrorCode::DatabaseError => true,
+            ErrorCode::BadRequest
+            | ErrorCode::ValidationFailed
+            | ErrorCode::PayloadTooLarge
+            | ErrorCode::Unauthorized
+            | ErrorCode::NotFound
+            | ErrorCode::Conflict
+            | ErrorCode::InvalidSwapRequest
+            | ErrorCode::AmountTooLow
+            | ErrorCode::AmountTooHigh
+            | ErrorCode::UnsupportedMint
+            | ErrorCode::SameMint
+            | ErrorCode::QuoteNotFound
+            | ErrorCode::QuoteExpired
+            | ErrorCode::InvalidSignature
+            | ErrorCode::ProofsNotSpendable
+            | ErrorCode::ProofsNotBound
+            | ErrorCode::DuplicateProofs
+            | ErrorCode::Blacklisted
+            | ErrorCode::InternalError => false,
+        }
+    }
+
+    /// What the code means, for the listing in `GET /info`
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorCode::BadRequest => "The request is malformed",
+            ErrorCode::ValidationFailed => "Fields of the request are invalid; see fields",
+            ErrorCode::PayloadTooLarge => "The request body is above the broker's limit",
+            ErrorCode::Unauthorized => "Missing or invalid credentials or signature",
+            ErrorCode::NotFound => "No such resource",
+            ErrorCode::Conflict => "The resource is not in a state that allows the request",
+            ErrorCode::RateLimited => "Too many requests; retry after retry_after seconds",
+            ErrorCode::RequestTimeout => "The request took too long; it may still complete",
+            ErrorCode::InvalidSwapRequest => "The swap request is not valid for this broker",
+            ErrorCode::AmountTooLow => "Amount below the pair's minimum",
+            ErrorCode::AmountTooHigh => "Amount above the pair's maximum",
+            ErrorCode::UnsupportedMint => "The broker doesn't trade on this mint",
+            ErrorCode::SameMint => "Source and target mint are the same",
+            ErrorCode::QuoteNotFound => "No quote with this ID",
+            ErrorCode::QuoteExpired => "The quote expired",
+            ErrorCode::InvalidSignature => "A signature does not verify",
+            ErrorCode::ProofsNotSpendable => "Proofs are spent or pending at the mint",
+            ErrorCode::ProofsNotBound => "Proofs are not locked and signed for the quote",
+            ErrorCode::DuplicateProofs => "Proofs already back another open quote",
+            ErrorCode::Blacklisted => "A key or proof involved is banned",
+            ErrorCode::RiskLimitExceeded => "A per-client or overall limit on open quotes is hit",
+            ErrorCode::InsufficientLiquidity => "Not enough liquidity on the target mint",
+            ErrorCode::Maintenance => "The broker is not taking new quotes",
+            ErrorCode::QueueFull => "Too many swaps in flight",
+            ErrorCode::MintUnhealthy => "A mint is failing health checks",
+            ErrorCode::MintUnavailable => "A mint failed repeatedly and is not called for now",
+            ErrorCode::MintTimeout => "A mint did not answer in time",
+            ErrorCode::MintError => "A mint rejected or failed an operation",
+            ErrorCode::PriceUnavailable => "No exchange rate for a cross-unit swap",
+            ErrorCode::UpstreamError => "A service the broker depends on failed",
+            ErrorCode::DatabaseError => "The broker's database failed",
+            ErrorCode::InternalError => "Unexpected broker error",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        let mut names: Vec<&str> = ErrorCode::ALL.iter().map(|code| code.as_str()).collect();
+        for code in ErrorCode::ALL {
+            let json = serde_json::to_value(code).unwrap();
+            assert_eq!(json, code.as_str());
+            assert_eq!(serde_json::from_value::<ErrorCode>(json).unwrap(), code);
+        }
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), ErrorCode::ALL.len());
+
+        assert_eq!(
+            BrokerError::QuoteExpired("q1".to_string()).code(),
+            ErrorCode::QuoteExpired
+        );
+        assert_eq!(
+            BrokerError::Database("locked".to_string()).code(),
+            ErrorCode::DatabaseError
+        );
+        assert!(BrokerError::Maintenance.code().retryable());
+        assert!(!BrokerError::SameMintSwap.code().retryable());
+    }
+}
@@ -0,0 +1,135 @@
+//! ETags for read-only responses
+//!
+//! Quote listings and liquidity events can run to hundreds of KB, and
+//! dashboards poll them. Every successful `GET` gets a weak ETag over its
+//! body, and a request whose `If-None-Match` names the current one is
+//! answered `304 Not Modified` with no body. The tag is weak because the same
+//! body may be sent compressed or not. Streamed bodies, whose size isn't
+//! known up front, and bodies above `MAX_TAGGED_BODY` are passed through
+//! untagged.
+
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+/// Largest body that is buffered to compute its tag
+pub const MAX_TAGGED_BODY: u64 = 16 * 1024 * 1024;
+
+/// Middleware tagging `GET` responses and answering matching revalidations
+pub async fn etag(req: Request, next: Next) -> Response {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return next.run(req).await;
+    }
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+
+    let response = next.run(req).await;
+    let size = response.body().size_hint().exact();
+    let fits = size.is_some_and(|size| size <= MAX_TAGGED_BODY);
+    if response.status() != StatusCode::OK || !fits {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_TAGGED_BODY as usize).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let tag = tag(&bytes);
+
+    if let Ok(value) = HeaderValue::from_str(&tag) {
+        parts.headers.insert(header::ETAG, value);
+    }
+    if if_none_match.is_some_and(|value| names_tag(&value, &tag)) {
+        let mut headers = HeaderMap::new();
+        for name in [header::ETAG, header::CACHE_CONTROL, header::VARY] {
+            if let Some(value) = parts.headers.get(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Weak ETag of a body
+pub fn tag(body: &[u8]) -> String {
+    format!("W/\"{}\"", &hex::encode(Sha256::digest(body))[..32])
+}
+
+/// Whether an `If-None-Match` value names `tag`, comparing weakly
+fn names_tag(if_none_match: &HeaderValue, tag: &str) -> bool {
+    let Ok(value) = if_none_match.to_str() else {
+        return false;
+    };
+    let opaque = |t: &str| t.trim().trim_start_matches("W/").to_string();
+    value
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == opaque(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|| async { "listing" }).post(|| async { "created" }),
+            )
+            .layer(middleware::from_fn(etag))
+    }
+
+    async fn call(method: Method, if_none_match: Option<&str>) -> Response {
+        let mut req = Request::builder().method(method).uri("/");
+        if let Some(value) = if_none_match {
+            req = req.header(header::IF_NONE_MATCH, value);
+        }
+        app()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_etag() {
+        let response = call(Method::GET, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(etag, tag(b"listing"));
+        assert!(etag.starts_with("W/\""));
+
+        let response = call(Method::GET, Some(&etag)).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(body.is_empty());
+
+        // Strong and listed forms match too
+        let strong = etag.trim_start_matches("W/");
+        let listed = format!("\"other\", {}", strong);
+        assert_eq!(
+            call(Method::GET, Some(&listed)).await.status(),
+            StatusCode::NOT_MODIFIED
+        );
+        assert_eq!(
+            call(Method::GET, Some("\"other\"")).await.status(),
+            StatusCode::OK
+        );
+
+        // Only reads are tagged
+        let response = call(Method::POST, Some(&etag)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::ETAG).is_none());
+    }
+}
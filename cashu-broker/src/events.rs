@@ -0,0 +1,197 @@
+//! Broker event bus
+//!
+//! The swap coordinator and liquidity manager announce swap lifecycle and
+//! liquidity changes as [`BrokerEvent`]s on a shared broadcast channel.
+//! Subscribe through [`Broker::subscribe`](crate::broker::Broker::subscribe).
+//! Events are fire-and-forget: a subscriber that falls more than the channel
+//! capacity behind misses the oldest ones and gets `RecvError::Lagged`.
+//! [`EventSink`]s registered on the
+//! [`BrokerBuilder`](crate::broker::BrokerBuilder) see every event instead,
+//! called in line as it is emitted.
+
+use crate::quarantine::QuarantineReason;
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// Events buffered for slow subscribers
+pub(crate) const CHANNEL_CAPACITY: usize = 1024;
+
+/// Something that happened in the broker
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BrokerEvent {
+    QuoteCreated {
+        quote_id: String,
+        source_mint: String,
+        target_mint: String,
+        amount_in: u64,
+        amount_out: u64,
+    },
+    QuoteAccepted {
+        quote_id: String,
+    },
+    SwapCompleted {
+        quote_id: String,
+        amount_received: u64, // Claimed on the source mint, after its input fee
+    },
+    SwapFailed {
+        quote_id: String,
+        error: String,
+    },
+    QuoteExpired {
+        quote_id: String,
+    },
+    SwapRefunded {
+        quote_id: String,
+        amount: u64,
+    },
+    BalanceChanged {
+        mint_url: String,
+        balance: u64,
+    },
+    MintHealthChanged {
+        mint_url: String,
+        healthy: bool,
+    },
+    ProofsQuarantined {
+        mint_url: String,
+        amount: u64,
+        reason: QuarantineReason,
+    },
+}
+
+/// Receives every event as it is emitted
+///
+/// Called from the broker's own tasks, so it must not block; hand slow work
+/// (network, disk) off to a channel or task.
+pub trait EventSink: Send + Sync {
+    fn handle(&self, event: &BrokerEvent);
+}
+
+/// Broadcast channel for [`BrokerEvent`]s; clones share the same channel
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<BrokerEvent>,
+    sinks: Arc<[Arc<dyn EventSink>]>,
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.sender.receiver_count())
+            .field("sinks", &self.sinks.len())
+            .finish()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(CHANNEL_CAPACITY)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_sinks(capacity, Vec::new())
+    }
+
+    /// Bus that also hands every event to `sinks`
+    pub fn with_sinks(capacity: usize, sinks: Vec<Arc<dyn EventSink>>) -> Self {
+        Self {
+            sender: broadcast::channel(capacity).0,
+            sinks: sinks.into(),
+        }
+    }
+
+    /// Send an event to every sink and current subscriber
+    pub fn emit(&self, event: BrokerEvent) {
+        for sink in self.sinks.iter() {
+            sink.handle(&event);
+        }
+        // An error only means nobody is listening
+        let _ = self.sender.send(event);
+    }
+
+    /// Receive every event emitted from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<BrokerEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast::error::RecvError;
+
+    #[tokio::test]
+    async fn test_event_bus() {
+        let bus = EventBus::new(2);
+
+        // Emitting with no subscribers is fine
+        bus.emit(BrokerEvent::QuoteAccepted {
+            quote_id: "q0".to_string(),
+        });
+
+        let mut events = bus.subscribe();
+        let shared = bus.clone();
+        shared.emit(BrokerEvent::QuoteExpired {
+            quote_id: "q1".to_string(),
+        });
+        assert_eq!(
+            events.recv().await.unwrap(),
+            BrokerEvent::QuoteExpired {
+                quote_id: "q1".to_string()
+            }
+        );
+
+        // A subscriber that falls behind skips ahead
+        for i in 0..3 {
+            bus.emit(BrokerEvent::BalanceChanged {
+                mint_url: "http://mint-a.test".to_string(),
+                balance: i,
+            });
+        }
+        assert!(matches!(events.recv().await, Err(RecvError::Lagged(1))));
+        assert!(matches!(
+            events.recv().await,
+            Ok(BrokerEvent::BalanceChanged { balance: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_event_sinks() {
+        struct Collect(std::sync::Mutex<Vec<BrokerEvent>>);
+        impl EventSink for Collect {
+            fn handle(&self, event: &BrokerEvent) {
+                self.0.lock().unwrap().push(event.clone());
+            }
+        }
+
+        let sink = Arc::new(Collect(Default::default()));
+        let bus = EventBus::with_sinks(4, vec![sink.clone()]);
+
+        // Sinks see events even with no subscribers, through every clone
+        bus.clone().emit(BrokerEvent::QuoteAccepted {
+            quote_id: "q1".to_string(),
+        });
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            vec![BrokerEvent::QuoteAccepted {
+                quote_id: "q1".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_event_json() {
+        let event = BrokerEvent::SwapRefunded {
+            quote_id: "q1".to_string(),
+            amount: 99,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({"type": "swap_refunded", "quote_id": "q1", "amount": 99})
+        );
+    }
+}
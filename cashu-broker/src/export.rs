@@ -0,0 +1,215 @@
+//! Quote history export
+//!
+//! `GET /admin/export/quotes` writes quotes out as CSV or JSON lines, one row
+//! per quote with its swap's completion time and the fees charged and paid.
+//! Rows are formatted as they come out of the database and streamed to the
+//! client, so an export never holds the whole history in memory.
+
+use crate::db::QuoteExport;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// Columns of a CSV export, in order
+const CSV_COLUMNS: [&str; 16] = [
+    "id",
+    "status",
+    "source_mint",
+    "target_mint",
+    "amount_in",
+    "amount_out",
+    "fee",
+    "fee_rate",
+    "mint_fee",
+    "mint_fees_paid",
+    "exchange_rate",
+    "created_at",
+    "accepted_at",
+    "completed_at",
+    "swap_completed_at",
+    "error_message",
+];
+
+/// Output format of an export
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+
+    /// First line of the export, if the format has one
+    pub fn header(self) -> Option<String> {
+        match self {
+            ExportFormat::Csv => Some(format!("{}\n", CSV_COLUMNS.join(","))),
+            ExportFormat::Jsonl => None,
+        }
+    }
+
+    /// One quote as a line of the export, including the newline
+    pub fn row(self, quote: &QuoteExport) -> String {
+        match self {
+            ExportFormat::Csv => csv_row(quote),
+            ExportFormat::Jsonl => {
+                // A struct of strings and numbers always serializes
+                let mut line = serde_json::to_string(quote).unwrap_or_default();
+                line.push('\n');
+                line
+            }
+        }
+    }
+}
+
+fn csv_row(quote: &QuoteExport) -> String {
+    let optional = |value: &Option<String>| value.as_deref().map(csv_field).unwrap_or_default();
+    let fields = [
+        csv_field(&quote.id),
+        quote.status.to_string(),
+        csv_field(&quote.source_mint),
+        csv_field(&quote.target_mint),
+        quote.amount_in.to_string(),
+        quote.amount_out.to_string(),
+        quote.fee.to_string(),
+        quote.fee_rate.to_string(),
+        quote.mint_fee.to_string(),
+        quote.mint_fees_paid.to_string(),
+        quote
+            .exchange_rate
+            .map(|r| r.to_string())
+            .unwrap_or_default(),
+        csv_field(&quote.created_at),
+        optional(&quote.accepted_at),
+        optional(&quote.completed_at),
+        optional(&quote.swap_completed_at),
+        optional(&quote.error_message),
+    ];
+
+    let mut line = fields.join(",");
+    line.push('\n');
+    line
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if !value.contains([',', '"', '\n', '\r']) {
+        return value.to_string();
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' {
+            quoted.push('"');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Filename offered for download, e.g. `quotes-20250120T101500Z.csv`
+pub fn filename(format: ExportFormat, at: DateTime<Utc>) -> String {
+    format!(
+        "quotes-{}.{}",
+        at.format("%Y%m%dT%H%M%SZ"),
+        format.extension()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SwapStatus;
+
+    fn quote() -> QuoteExport {
+        QuoteExport {
+            id: "quote-1".to_string(),
+            status: SwapStatus::Completed,
+            source_mint: "http://mint-a.test".to_string(),
+            target_mint: "http://mint-b.test".to_string(),
+            amount_in: 100,
+            amount_out: 98,
+            fee: 1,
+            fee_rate: 0.01,
+            mint_fee: 1,
+            mint_fees_paid: 2,
+            exchange_rate: None,
+            created_at: "2025-01-20T10:00:00+00:00".to_string(),
+            accepted_at: Some("2025-01-20T10:00:05+00:00".to_string()),
+            completed_at: Some("2025-01-20T10:00:09+00:00".to_string()),
+            swap_completed_at: Some("2025-01-20T10:00:09+00:00".to_string()),
+            error_message: None,
+        }
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let header = ExportFormat::Csv.header().unwrap();
+        assert!(header.starts_with("id,status,source_mint,"));
+
+        let mut failed = quote();
+        failed.status = SwapStatus::Failed;
+        failed.error_message = Some("Mint said \"no\", twice".to_string());
+        let row = ExportFormat::Csv.row(&failed);
+
+        assert!(row.starts_with(
+            "quote-1,failed,http://mint-a.test,http://mint-b.test,100,98,1,0.01,1,2,,"
+        ));
+        assert!(row.ends_with(",\"Mint said \"\"no\"\", twice\"\n"));
+        // One field per column
+        assert_eq!(
+            ExportFormat::Csv
+                .row(&quote())
+                .trim_end()
+                .split(',')
+                .count(),
+            CSV_COLUMNS.len()
+        );
+    }
+
+    #[test]
+    fn test_jsonl_export() {
+        assert_eq!(ExportFormat::Jsonl.header(), None);
+
+        let row = ExportFormat::Jsonl.row(&quote());
+        assert!(row.ends_with('\n'));
+        let value: serde_json::Value = serde_json::from_str(row.trim_end()).unwrap();
+        assert_eq!(value["id"], "quote-1");
+        assert_eq!(value["mint_fees_paid"], 2);
+        assert_eq!(value["swap_completed_at"], "2025-01-20T10:00:09+00:00");
+    }
+
+    #[test]
+    fn test_filename() {
+        let at = DateTime::parse_from_rfc3339("2025-01-20T10:15:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            filename(ExportFormat::Jsonl, at),
+            "quotes-20250120T101500Z.jsonl"
+        );
+    }
+}
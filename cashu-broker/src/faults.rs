@@ -0,0 +1,301 @@
+//! Fault injection for wallet calls to mints
+//!
+//! Retries, timeouts, circuit breakers, reconciliation and refunds only run
+//! when a mint misbehaves, which the mock mint never does. A [`FaultInjector`]
+//! makes wallet calls made through
+//! [`LiquidityManager::mint_call`](crate::liquidity::LiquidityManager::mint_call)
+//! fail, stall or lose their answer on demand, per mint, per [`MintOp`] and
+//! with a given probability, so tests and simulations can drive those paths.
+//! It is empty, and costs nothing, unless rules are added; never add rules to
+//! a broker that holds real funds.
+//!
+//! Only built with the `faults` feature; without it wallet calls go straight
+//! to the mint.
+
+use crate::rng::SwapRng;
+use crate::timeouts::MintOp;
+use cdk::nuts::{ProofState, Proofs};
+use rand::Rng;
+use std::any::Any;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// What happens to a call a rule applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    Fail,            // Connection refused before reaching the mint; retried
+    Reject,          // The mint answers with a protocol error; not retried
+    Delay(Duration), // The call goes through late, or times out if past its timeout
+    LostResponse,    // The mint carries out the call but the answer never arrives
+    Partial,         // The call goes through but its last proof or state is dropped
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fail => write!(f, "fail"),
+            Self::Reject => write!(f, "reject"),
+            Self::Delay(delay) => write!(f, "delay {:?}", delay),
+            Self::LostResponse => write!(f, "lost response"),
+            Self::Partial => write!(f, "partial results"),
+        }
+    }
+}
+
+impl Fault {
+    /// Error the wallet call returns in place of its result
+    pub fn error(&self) -> cdk::Error {
+        match self {
+            Self::Reject => cdk::Error::HttpError(Some(400), "injected fault".to_string()),
+            _ => cdk::Error::HttpError(None, format!("injected fault: {}", self)),
+        }
+    }
+}
+
+/// Drop the last proof or proof state of a wallet call's result
+///
+/// Results holding neither are left as they are. Returns whether anything
+/// was dropped.
+pub fn truncate<T: Any>(result: &mut T) -> bool {
+    let result = result as &mut dyn Any;
+    if let Some(proofs) = result.downcast_mut::<Proofs>() {
+        proofs.pop().is_some()
+    } else if let Some(Some(proofs)) = result.downcast_mut::<Option<Proofs>>() {
+        proofs.pop().is_some()
+    } else if let Some(states) = result.downcast_mut::<Vec<ProofState>>() {
+        states.pop().is_some()
+    } else {
+        false
+    }
+}
+
+/// Which calls get a fault, and how often
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultRule {
+    pub fault: Fault,
+    pub mint_url: Option<String>, // None matches every mint
+    pub op: Option<MintOp>,       // None matches every operation
+    pub probability: f64,         // Chance that a matching attempt is hit (1.0 = always)
+    pub remaining: Option<u32>,   // Attempts left to hit; None never runs out
+}
+
+impl FaultRule {
+    /// Hit every attempt at every call with `fault`
+    pub fn new(fault: Fault) -> Self {
+        Self {
+            fault,
+            mint_url: None,
+            op: None,
+            probability: 1.0,
+            remaining: None,
+        }
+    }
+
+    /// Only apply to calls to `mint_url`
+    pub fn mint(mut self, mint_url: &str) -> Self {
+        self.mint_url = Some(mint_url.to_string());
+        self
+    }
+
+    /// Only apply to calls of kind `op`
+    pub fn op(mut self, op: MintOp) -> Self {
+        self.op = Some(op);
+        self
+    }
+
+    /// Hit a matching attempt with chance `probability`, between 0 and 1
+    pub fn probability(mut self, probability: f64) -> Self {
+        self.probability = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Stop after hitting `times` attempts
+    pub fn times(mut self, times: u32) -> Self {
+        self.remaining = Some(times);
+        self
+    }
+
+    fn matches(&self, mint_url: &str, op: MintOp) -> bool {
+        self.mint_url.as_deref().map_or(true, |url| url == mint_url)
+            && self.op.map_or(true, |rule_op| rule_op == op)
+            && self.remaining != Some(0)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Faults {
+    rules: Vec<FaultRule>,
+    injected: u64,
+}
+
+/// Shared set of fault rules
+///
+/// Clones share the rules, so a test can keep one and change the rules of a
+/// running broker. For each attempt the first matching rule decides; a
+/// seeded [`SwapRng`] makes which attempts are hit reproducible.
+#[derive(Clone, Default)]
+pub struct FaultInjector {
+    faults: Arc<Mutex<Faults>>,
+    rng: SwapRng,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decide probabilistic rules with `rng`
+    pub fn with_rng(mut self, rng: SwapRng) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    pub fn add(&self, rule: FaultRule) {
+        self.lock().rules.push(rule);
+    }
+
+    /// Remove every rule; later calls go through untouched
+    pub fn clear(&self) {
+        self.lock().rules.clear();
+    }
+
+    pub fn rules(&self) -> Vec<FaultRule> {
+        self.lock().rules.clone()
+    }
+
+    /// Number of attempts hit so far
+    pub fn injected(&self) -> u64 {
+        self.lock().injected
+    }
+
+    /// Fault for an attempt at `op` on `mint_url`, if a rule hits it
+    pub fn pick(&self, mint_url: &str, op: MintOp) -> Option<Fault> {
+        let mut faults = self.lock();
+        let rule = faults.rules.iter_mut().find(|r| r.matches(mint_url, op))?;
+        if rule.probability < 1.0 && self.rng.clone().gen::<f64>() >= rule.probability {
+            return None;
+        }
+        if let Some(remaining) = rule.remaining.as_mut() {
+            *remaining -= 1;
+        }
+        let fault = rule.fault;
+        faults.injected += 1;
+        Some(fault)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Faults> {
+        self.faults.lock().expect("fault lock poisoned")
+    }
+}
+
+impl fmt::Debug for FaultInjector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let faults = self.lock();
+        f.debug_struct("FaultInjector")
+            .field("rules", &faults.rules)
+            .field("injected", &faults.injected)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry;
+    use cdk::nuts::{Id, Proof, SecretKey, State};
+    use cdk::secret::Secret;
+    use cdk::Amount;
+    use std::str::FromStr;
+
+    const MINT_A: &str = "http://mint-a.test";
+    const MINT_B: &str = "http://mint-b.test";
+
+    #[test]
+    fn test_rules_match_mint_and_op() {
+        let faults = FaultInjector::new();
+        assert_eq!(faults.pick(MINT_A, MintOp::Swap), None);
+
+        faults.add(FaultRule::new(Fault::Reject).mint(MINT_A).op(MintOp::Swap));
+        faults.add(FaultRule::new(Fault::Fail).op(MintOp::Swap));
+        assert_eq!(faults.pick(MINT_A, MintOp::Swap), Some(Fault::Reject));
+        assert_eq!(faults.pick(MINT_B, MintOp::Swap), Some(Fault::Fail));
+        assert_eq!(faults.pick(MINT_A, MintOp::Melt), None);
+        assert_eq!(faults.injected(), 2);
+
+        faults.clear();
+        assert_eq!(faults.pick(MINT_A, MintOp::Swap), None);
+    }
+
+    #[test]
+    fn test_times_runs_out() {
+        let faults = FaultInjector::new();
+        faults.add(FaultRule::new(Fault::LostResponse).times(2));
+        faults.add(FaultRule::new(Fault::Fail));
+
+        assert_eq!(faults.pick(MINT_A, MintOp::Mint), Some(Fault::LostResponse));
+        assert_eq!(faults.pick(MINT_B, MintOp::Swap), Some(Fault::LostResponse));
+        // The next matching rule takes over
+        assert_eq!(faults.pick(MINT_A, MintOp::Mint), Some(Fault::Fail));
+        assert_eq!(faults.rules()[0].remaining, Some(0));
+    }
+
+    #[test]
+    fn test_probability_is_reproducible() {
+        let draw = |seed| {
+            let faults = FaultInjector::new().with_rng(SwapRng::seeded(seed));
+            faults.add(FaultRule::new(Fault::Fail).probability(0.3));
+            (0..200)
+                .map(|_| faults.pick(MINT_A, MintOp::Swap).is_some())
+                .collect::<Vec<_>>()
+        };
+        let hits = draw(1);
+        assert_eq!(hits, draw(1));
+
+        let count = hits.iter().filter(|hit| **hit).count();
+        assert!((30..90).contains(&count), "{} hits", count);
+
+        let never = FaultInjector::new();
+        never.add(FaultRule::new(Fault::Fail).probability(0.0));
+        assert_eq!(never.pick(MINT_A, MintOp::Swap), None);
+    }
+
+    #[test]
+    fn test_fault_errors() {
+        assert!(retry::is_retryable(&Fault::Fail.error()));
+        assert!(retry::is_retryable(&Fault::LostResponse.error()));
+        assert!(!retry::is_retryable(&Fault::Reject.error()));
+    }
+
+    #[test]
+    fn test_truncate_partial_results() {
+        let proof = || {
+            Proof::new(
+                Amount::from(8),
+                Id::from_str("009a1f293253e41e").unwrap(),
+                Secret::generate(),
+                SecretKey::generate().public_key(),
+            )
+        };
+        let mut proofs: Proofs = vec![proof(), proof()];
+        assert!(truncate(&mut proofs));
+        assert_eq!(proofs.len(), 1);
+
+        let mut swapped = Some(vec![proof()]);
+        assert!(truncate(&mut swapped));
+        assert_eq!(swapped, Some(vec![]));
+        assert!(!truncate(&mut swapped));
+
+        let mut states = vec![ProofState {
+            y: SecretKey::generate().public_key(),
+            state: State::Unspent,
+            witness: None,
+        }];
+        assert!(truncate(&mut states));
+        assert!(states.is_empty());
+
+        // Anything else goes through whole
+        assert!(!truncate(&mut ()));
+        assert!(!truncate(&mut vec![1u64, 2]));
+    }
+}
@@ -0,0 +1,291 @@
+//! Fee schedule
+//!
+//! The broker fee of a quote starts from a rate: a pair's own `fee_rate` if it
+//! has one, otherwise the tier of [`FeeSchedule::tiers`] the swap amount falls
+//! in, otherwise `FEE_RATE`. Clients that swapped enough over the last
+//! [`VOLUME_WINDOW_DAYS`] days get the best [`VolumeDiscount`] they qualify for
+//! off that rate; their volume is kept per public key in the database. The
+//! resulting rate is charged on the input amount, rounded up, but never less
+//! than [`FeeSchedule::min_fee`].
+//!
+//! [`Fee::exact_in`] and [`Fee::exact_out`] split a quote into input, fee and
+//! output, and refuse any split that leaves no output, charges less than the
+//! minimum fee or doesn't add up.
+
+use crate::error::{BrokerError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Days of completed swaps that count towards a client's volume discount
+pub const VOLUME_WINDOW_DAYS: i64 = 30;
+
+/// Fee rate for swaps of at least `min_amount`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeeTier {
+    pub min_amount: u64,
+    pub fee_rate: f64,
+}
+
+/// Share of the fee rate waived for clients who swapped at least `min_volume`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VolumeDiscount {
+    pub min_volume: u64,
+    pub discount: f64, // 0.2 = 20% off the rate
+}
+
+/// Tiers, minimum fee and volume discounts applied to every quote
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    #[serde(default)]
+    pub tiers: Vec<FeeTier>,
+    #[serde(default)]
+    pub min_fee: u64, // In the source mint's unit
+    #[serde(default)]
+    pub volume_discounts: Vec<VolumeDiscount>,
+}
+
+impl FeeSchedule {
+    /// Rate of the highest tier `amount` reaches, if any
+    pub fn tier_rate(&self, amount: u64) -> Option<f64> {
+        self.tiers
+            .iter()
+            .filter(|tier| amount >= tier.min_amount)
+            .max_by_key(|tier| tier.min_amount)
+            .map(|tier| tier.fee_rate)
+    }
+
+    /// `fee_rate` less the best discount `volume` qualifies for
+    pub fn discounted(&self, fee_rate: f64, volume: u64) -> f64 {
+        let discount = self
+            .volume_discounts
+            .iter()
+            .filter(|d| volume >= d.min_volume)
+            .map(|d| d.discount)
+            .fold(0.0, f64::max);
+        fee_rate * (1.0 - discount)
+    }
+}
+
+/// How the broker fee of a quote is worked out from its input amount
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fee {
+    pub rate: f64,
+    pub min: u64,
+}
+
+impl Fee {
+    pub fn new(rate: f64, min: u64) -> Self {
+        Self { rate, min }
+    }
+
+    /// Fee charged on `input`
+    pub fn charge(&self, input: u64) -> u64 {
+        (((input as f64) * self.rate).ceil() as u64).max(self.min)
+    }
+
+    /// Split `input` into the fee and the output it leaves
+    pub fn exact_in(&self, input: u64) -> Result<FeeSplit> {
+        let fee = self.charge(input);
+        if fee >= input {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "{} doesn't cover the broker fee of {}",
+                input, fee
+            )));
+        }
+        self.checked(FeeSplit {
+            input,
+            fee,
+            output: input - fee,
+        })
+    }
+
+    /// The smallest input that leaves `output` after the fee
+    ///
+    /// Any rounding surplus is kept as fee, so the output is exact.
+    pub fn exact_out(&self, output: u64) -> Result<FeeSplit> {
+        if !(0.0..1.0).contains(&self.rate) {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Exact-out quotes need a fee rate below 100% (got {})",
+                self.rate
+            )));
+        }
+        let too_large =
+            || BrokerError::InvalidSwapRequest(format!("{} is too large to quote", output));
+
+        // Start from the closed-form estimate and correct for rounding
+        let mut input = (((output as f64) / (1.0 - self.rate)).ceil() as u64)
+            .max(output.checked_add(self.min).ok_or_else(too_large)?);
+        while input > output && (input - 1).saturating_sub(self.charge(input - 1)) >= output {
+            input -= 1;
+        }
+        while input.saturating_sub(self.charge(input)) < output {
+            input = input.checked_add(1).ok_or_else(too_large)?;
+        }
+
+        self.checked(FeeSplit {
+            input,
+            fee: input - output,
+            output,
+        })
+    }
+
+    /// Refuse a split that breaks the invariants every quote keeps
+    fn checked(&self, split: FeeSplit) -> Result<FeeSplit> {
+        let broken = if split.output == 0 {
+            "leaves no output"
+        } else if split.fee < self.min {
+            "charges less than the minimum fee"
+        } else if split.output.checked_add(split.fee) != Some(split.input) {
+            "doesn't add up"
+        } else {
+            return Ok(split);
+        };
+        Err(BrokerError::InvalidSwapRequest(format!(
+            "Quote of {} in, {} fee and {} out {}",
+            split.input, split.fee, split.output, broken
+        )))
+    }
+}
+
+/// Input, broker fee and output of a quote, in the source unit
+///
+/// Only made by [`Fee::exact_in`] and [`Fee::exact_out`], so the output is
+/// never zero, the fee is at least the minimum and `input = output + fee`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSplit {
+    pub input: u64,
+    pub fee: u64,
+    pub output: u64,
+}
+
+impl From<f64> for Fee {
+    fn from(rate: f64) -> Self {
+        Self::new(rate, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn schedule() -> FeeSchedule {
+        FeeSchedule {
+            tiers: vec![
+                FeeTier {
+                    min_amount: 100_000,
+                    fee_rate: 0.003,
+                },
+                FeeTier {
+                    min_amount: 0,
+                    fee_rate: 0.007,
+                },
+                FeeTier {
+                    min_amount: 1_000,
+                    fee_rate: 0.005,
+                },
+            ],
+            min_fee: 2,
+            volume_discounts: vec![
+                VolumeDiscount {
+                    min_volume: 1_000_000,
+                    discount: 0.5,
+                },
+                VolumeDiscount {
+                    min_volume: 100_000,
+                    discount: 0.2,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_tier_rate() {
+        let schedule = schedule();
+        assert_eq!(schedule.tier_rate(999), Some(0.007));
+        assert_eq!(schedule.tier_rate(1_000), Some(0.005));
+        assert_eq!(schedule.tier_rate(250_000), Some(0.003));
+        assert_eq!(FeeSchedule::default().tier_rate(1_000), None);
+    }
+
+    #[test]
+    fn test_volume_discount() {
+        let schedule = schedule();
+        assert_eq!(schedule.discounted(0.01, 99_999), 0.01);
+        assert!((schedule.discounted(0.01, 100_000) - 0.008).abs() < 1e-12);
+        assert!((schedule.discounted(0.01, 5_000_000) - 0.005).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_min_fee() {
+        let fee = Fee::new(0.005, 2);
+        assert_eq!(fee.charge(100), 2);
+        assert_eq!(fee.charge(1_000), 5);
+        assert_eq!(Fee::from(0.005).charge(100), 1);
+    }
+
+    #[test]
+    fn test_fee_split() {
+        let fee = Fee::new(0.005, 2);
+        let split = fee.exact_in(1_000).unwrap();
+        assert_eq!((split.input, split.fee, split.output), (1_000, 5, 995));
+        assert_eq!(fee.exact_out(995).unwrap(), split);
+
+        // A fee that takes the whole input leaves nothing to swap
+        assert!(fee.exact_in(2).is_err());
+        assert!(Fee::from(0.5).exact_in(1).is_err());
+        assert!(fee.exact_out(0).is_err());
+        assert!(Fee::from(1.0).exact_out(10).is_err());
+        assert!(fee.exact_out(u64::MAX).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_exact_in_invariants(
+            input in 0u64..1 << 50,
+            rate in 0.0f64..1.5,
+            min in 0u64..1_000,
+        ) {
+            let fee = Fee::new(rate, min);
+            match fee.exact_in(input) {
+                Ok(split) => {
+                    prop_assert!(split.output > 0);
+                    prop_assert!(split.fee >= min);
+                    prop_assert_eq!(split.input, input);
+                    prop_assert_eq!(split.input, split.output + split.fee);
+                    prop_assert_eq!(split.fee, fee.charge(input));
+                }
+                // Only refused when the fee leaves nothing
+                Err(_) => prop_assert!(fee.charge(input) >= input),
+            }
+        }
+
+        #[test]
+        fn prop_exact_out_invariants(
+            output in 1u64..1 << 50,
+            rate in 0.0f64..0.99,
+            min in 0u64..1_000,
+        ) {
+            let fee = Fee::new(rate, min);
+            let split = fee.exact_out(output).unwrap();
+            prop_assert_eq!(split.output, output);
+            prop_assert!(split.fee >= min);
+            prop_assert!(split.fee >= fee.charge(split.input));
+            prop_assert_eq!(split.input, split.output + split.fee);
+
+            // The input is the smallest that covers the output...
+            let smaller = split.input - 1;
+            prop_assert!(smaller.saturating_sub(fee.charge(smaller)) < output);
+            // ...and quoting it exact-in gives at least the output back
+            prop_assert!(fee.exact_in(split.input).unwrap().output >= output);
+        }
+    }
+
+    #[test]
+    fn test_schedule_json() {
+        let schedule: FeeSchedule =
+            serde_json::from_str(r#"{"tiers":[{"min_amount":0,"fee_rate":0.007}]}"#).unwrap();
+        assert_eq!(schedule.tier_rate(10), Some(0.007));
+        assert_eq!(schedule.min_fee, 0);
+        assert!(schedule.volume_discounts.is_empty());
+    }
+}
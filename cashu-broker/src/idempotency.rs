@@ -0,0 +1,157 @@
+//! Idempotency keys for the swap endpoints
+//!
+//! A wallet that times out on `POST /quote/:id/accept` or `/complete` can
+//! retry with the same `Idempotency-Key` header and get the stored response
+//! instead of running the step twice. Keys are scoped to method and path, and
+//! reusing one with a different body is rejected.
+//!
+//! A key whose request is still running after [`IN_FLIGHT_MINUTES`], because
+//! its process crashed or its handler panicked, is taken over by the next
+//! request made with it. Keys are forgotten [`KEY_TTL_HOURS`] after they were
+//! claimed, and a retry after that runs the step again.
+
+use crate::api::{ApiError, AppState};
+use crate::db::Database;
+use crate::leases::{self, Leases, IDEMPOTENCY_SWEEP};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Header carrying the client's idempotency key
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Header set on responses replayed from a stored record
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Longest accepted key
+const MAX_KEY_LEN: usize = 255;
+
+/// Largest request or response body handled (proofs can be large)
+const MAX_BODY: usize = 1024 * 1024;
+
+/// How long a request may run before a retry with its key takes it over
+pub const IN_FLIGHT_MINUTES: i64 = 10;
+
+/// How long a key is kept, and its response replayed
+pub const KEY_TTL_HOURS: i64 = 24;
+
+/// How often expired keys are deleted
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Middleware deduplicating requests that carry an `Idempotency-Key`
+pub async fn idempotent(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => value
+            .to_str()
+            .ok()
+            .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN)
+            .ok_or_else(|| ApiError::BadRequest("Invalid Idempotency-Key header".to_string()))?
+            .to_string(),
+        None => return Ok(next.run(req).await),
+    };
+
+    let scope = format!("{} {}", req.method(), req.uri().path());
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, MAX_BODY)
+        .await
+        .map_err(|_| ApiError::BadRequest("Request body too large".to_string()))?;
+    let request_hash = hex::encode(Sha256::digest(&body));
+
+    let db = &state.db;
+    let stale_before = Utc::now() - chrono::Duration::minutes(IN_FLIGHT_MINUTES);
+    if !db
+        .reserve_idempotency_key(&scope, &key, &request_hash, stale_before)
+        .await?
+    {
+        let record = db
+            .get_idempotency_key(&scope, &key)
+            .await?
+            .ok_or_else(|| ApiError::Conflict("Idempotency-Key is being released".to_string()))?;
+
+        if record.request_hash != request_hash {
+            return Err(ApiError::BadRequest(
+                "Idempotency-Key was already used with a different request".to_string(),
+            ));
+        }
+
+        return match (record.status_code, record.response_body) {
+            (Some(status), Some(body)) => Ok(replay(status, body)),
+            _ => Err(ApiError::Conflict(
+                "A request with this Idempotency-Key is still in progress".to_string(),
+            )),
+        };
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    // Failed requests free the key so the client can retry them
+    if !response.status().is_success() {
+        if let Err(e) = db.release_idempotency_key(&scope, &key).await {
+            warn!("Failed to release idempotency key for {}: {}", scope, e);
+        }
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, MAX_BODY)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to read response: {}", e)))?;
+
+    let stored = String::from_utf8_lossy(&body);
+    if let Err(e) = db
+        .complete_idempotency_key(&scope, &key, parts.status.as_u16() as i64, &stored)
+        .await
+    {
+        warn!("Failed to store idempotent response for {}: {}", scope, e);
+    }
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Delete expired idempotency keys forever, on whichever instance holds the
+/// sweep's lease
+pub async fn expire_keys(db: Database, leases: Leases) {
+    let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+    loop {
+        interval.tick().await;
+        if !leases::may_run(Some(&leases), IDEMPOTENCY_SWEEP).await {
+            continue;
+        }
+        let before = Utc::now() - chrono::Duration::hours(KEY_TTL_HOURS);
+        match db.expire_idempotency_keys(before).await {
+            Ok(0) => {}
+            Ok(expired) => info!("Expired {} idempotency keys", expired),
+            Err(e) => warn!("Failed to expire idempotency keys: {}", e),
+        }
+    }
+}
+
+/// Rebuild a stored response
+fn replay(status: i64, body: String) -> Response {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = u16::try_from(status)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::OK);
+
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    headers.insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+
+    response
+}
@@ -0,0 +1,210 @@
+//! Long-term broker identity key
+//!
+//! Quotes use fresh per-swap keys; this key is what identifies the broker
+//! itself across restarts (published in `GET /info`). Like the encryption
+//! key, it comes from the environment (hex) or a key file created on first
+//! start.
+//!
+//! Every quote is signed with it: `quote_signature` is a BIP-340 signature
+//! over the SHA-256 of [`quote_message`], so a client holds proof of the terms
+//! it was offered. [`verify_quote_signature`] checks one.
+
+use crate::encryption::write_key_file;
+use crate::error::{BrokerError, Result};
+use crate::types::SwapQuote;
+use schnorr_fun::fun::{g, marker::*, Point, Scalar, G};
+use schnorr_fun::{Message, Schnorr, Signature};
+use secp256kfun::nonce;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// Domain separator at the start of every signed quote message
+const QUOTE_MESSAGE_TAG: &str = "cashu-broker/quote/v1";
+
+/// The broker's long-term secp256k1 key
+pub struct BrokerIdentity {
+    secret: Scalar,
+    public_key: Point,
+}
+
+impl BrokerIdentity {
+    /// Create an identity from a secret key
+    pub fn new(secret: Scalar) -> Self {
+        let public_key = g!(secret * G).normalize();
+        Self { secret, public_key }
+    }
+
+    /// Random identity that only lives as long as the process
+    pub fn generate() -> Self {
+        Self::new(Scalar::random(&mut rand::thread_rng()))
+    }
+
+    /// Create an identity from a hex-encoded 32-byte secret key
+    pub fn from_hex(hex_key: &str) -> Result<Self> {
+        let bytes: [u8; 32] = hex::decode(hex_key.trim())
+            .map_err(|e| BrokerError::Encryption(format!("Invalid identity key hex: {}", e)))?
+            .try_into()
+            .map_err(|_| BrokerError::Encryption("Identity key must be 32 bytes".to_string()))?;
+
+        let secret = Scalar::from_bytes(bytes)
+            .and_then(|s| s.non_zero())
+            .ok_or_else(|| BrokerError::Encryption("Invalid identity key".to_string()))?;
+
+        Ok(Self::new(secret))
+    }
+
+    /// Load the key from a file, generating and saving a new one if it doesn't exist
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        if path.exists() {
+            let hex_key = std::fs::read_to_string(path)?;
+            return Self::from_hex(&hex_key);
+        }
+
+        let identity = Self::generate();
+        write_key_file(path, &hex::encode(identity.secret.to_bytes()))?;
+        tracing::info!("Generated new identity key at {}", path.display());
+
+        Ok(identity)
+    }
+
+    /// Compressed public key (33 bytes)
+    pub fn public_key(&self) -> Vec<u8> {
+        self.public_key.to_bytes().to_vec()
+    }
+
+    /// Compressed public key, hex-encoded
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.public_key())
+    }
+
+    /// Sign a quote's terms (64-byte BIP-340 signature)
+    pub fn sign_quote(&self, quote: &SwapQuote) -> Vec<u8> {
+        let schnorr = Schnorr::<Sha256, nonce::Deterministic<Sha256>>::default();
+        let keypair = schnorr.new_keypair(self.secret);
+        let digest = Sha256::digest(quote_message(quote));
+
+        let signature = schnorr.sign(&keypair, Message::<Public>::raw(&digest));
+        signature.to_bytes().to_vec()
+    }
+}
+
+/// The quote terms covered by `quote_signature`
+///
+/// Fields are joined with `|` after the tag: ID, source and target mint,
+/// amount in, amount out, fee, mint fee, exchange rate (as in the JSON, empty
+/// when unset), expiry (Unix time), adaptor point and broker pubkey (hex).
+pub fn quote_message(quote: &SwapQuote) -> Vec<u8> {
+    let exchange_rate = quote
+        .exchange_rate
+        .map(|rate| serde_json::Value::from(rate).to_string())
+        .unwrap_or_default();
+
+    format!(
+        "{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}",
+        QUOTE_MESSAGE_TAG,
+        quote.quote_id,
+        quote.from_mint,
+        quote.to_mint,
+        quote.input_amount,
+        quote.output_amount,
+        quote.fee,
+        quote.mint_fee,
+        exchange_rate,
+        quote.expiry,
+        hex::encode(&quote.adaptor_point),
+        hex::encode(&quote.broker_public_key),
+    )
+    .into_bytes()
+}
+
+/// Check a quote's `quote_signature` against the broker's identity key
+///
+/// `identity_key` is the `pubkey` from `GET /info`, compressed (33 bytes) or
+/// x-only (32 bytes).
+pub fn verify_quote_signature(quote: &SwapQuote, identity_key: &[u8]) -> Result<()> {
+    let invalid = |msg: &str| BrokerError::QuoteSignature(msg.to_string());
+
+    let xonly = match identity_key.len() {
+        33 => <[u8; 32]>::try_from(&identity_key[1..]),
+        _ => <[u8; 32]>::try_from(identity_key),
+    }
+    .map_err(|_| invalid("identity key must be 32 or 33 bytes"))?;
+    let public_key =
+        Point::<EvenY>::from_xonly_bytes(xonly).ok_or_else(|| invalid("invalid identity key"))?;
+
+    let bytes: [u8; 64] = quote
+        .quote_signature
+        .as_deref()
+        .ok_or_else(|| invalid("quote is not signed"))?
+        .try_into()
+        .map_err(|_| invalid("signature must be 64 bytes"))?;
+    let signature =
+        Signature::<Public>::from_bytes(bytes).ok_or_else(|| invalid("malformed signature"))?;
+
+    let digest = Sha256::digest(quote_message(quote));
+    let schnorr = Schnorr::<Sha256, nonce::Deterministic<Sha256>>::default();
+    if schnorr.verify(&public_key, Message::<Public>::raw(&digest), &signature) {
+        Ok(())
+    } else {
+        Err(invalid("signature does not match the quote"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_key_file() {
+        let path = std::env::temp_dir().join(format!("identity-{}.key", uuid::Uuid::new_v4()));
+
+        let created = BrokerIdentity::load_or_create(&path).unwrap();
+        let loaded = BrokerIdentity::load_or_create(&path).unwrap();
+        assert_eq!(created.public_key_hex(), loaded.public_key_hex());
+        assert_eq!(created.public_key().len(), 33);
+
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(BrokerIdentity::from_hex("00").is_err());
+        assert!(BrokerIdentity::from_hex(&"00".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_quote_signature() {
+        let identity = BrokerIdentity::generate();
+        let mut quote: SwapQuote = serde_json::from_value(serde_json::json!({
+            "id": "quote-1",
+            "source_mint": "http://mint-a.test",
+            "target_mint": "http://mint-b.test",
+            "amount_in": 100,
+            "amount_out": 99,
+            "fee": 1,
+            "fee_rate": 0.01,
+            "broker_pubkey": "02".repeat(33),
+            "adaptor_point": "03".repeat(33),
+            "expires_in": 300,
+            "expiry": 1_700_000_300,
+            "status": "pending",
+        }))
+        .unwrap();
+
+        assert!(verify_quote_signature(&quote, &identity.public_key()).is_err());
+
+        quote.quote_signature = Some(identity.sign_quote(&quote));
+        verify_quote_signature(&quote, &identity.public_key()).unwrap();
+        // The x-only key works too
+        verify_quote_signature(&quote, &identity.public_key()[1..]).unwrap();
+
+        // The signature survives the trip through JSON
+        let mut json = serde_json::to_value(&quote).unwrap();
+        assert_eq!(json["quote_signature"].as_str().unwrap().len(), 128);
+        let mut received: SwapQuote = serde_json::from_value(json).unwrap();
+        verify_quote_signature(&received, &identity.public_key()).unwrap();
+
+        // Any change to the terms breaks it
+        received.output_amount = 100;
+        assert!(verify_quote_signature(&received, &identity.public_key()).is_err());
+        let other = BrokerIdentity::generate();
+        assert!(verify_quote_signature(&quote, &other.public_key()).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+//! Quote identifiers
+//!
+//! Quote IDs are UUIDv7: the first 48 bits are the Unix time in milliseconds
+//! the quote was made, the rest random. They sort by creation time, so pages
+//! of quotes and log lines about them line up without looking anything up,
+//! and [`created_at`] reads the time back out of an ID.
+//!
+//! Quotes made before were given 32 random hex characters. IDs are only ever
+//! compared as strings, so those keep working everywhere; they just carry no
+//! time, and [`created_at`] returns `None` for them.
+
+use crate::rng::SwapRng;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::{Builder, Uuid};
+
+/// A new quote ID for a quote made at `now`, with its random bits from `rng`
+pub fn quote_id(now: SystemTime, rng: &SwapRng) -> String {
+    let millis = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    Builder::from_unix_timestamp_millis(millis, &rng.bytes::<10>())
+        .into_uuid()
+        .hyphenated()
+        .to_string()
+}
+
+/// When the quote with ID `id` was made, to the millisecond
+///
+/// `None` for IDs from before UUIDv7, and anything else that isn't one.
+pub fn created_at(id: &str) -> Option<SystemTime> {
+    // Only the hyphenated form: old 32-character IDs would parse as UUIDs too
+    if id.len() != 36 {
+        return None;
+    }
+    let uuid = Uuid::parse_str(id).ok()?;
+    if uuid.get_version_num() != 7 {
+        return None;
+    }
+
+    let mut millis = [0u8; 8];
+    millis[2..].copy_from_slice(&uuid.as_bytes()[..6]);
+    Some(UNIX_EPOCH + Duration::from_millis(u64::from_be_bytes(millis)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_ids_sort_by_time() {
+        let rng = SwapRng::os();
+        let start = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+
+        let first = quote_id(start, &rng);
+        let later = quote_id(start + Duration::from_millis(1), &rng);
+        let much_later = quote_id(start + Duration::from_secs(86_400), &rng);
+        assert!(first < later && later < much_later);
+
+        assert_eq!(first.len(), 36);
+        assert_eq!(Uuid::parse_str(&first).unwrap().get_version_num(), 7);
+        assert_eq!(created_at(&first), Some(start));
+        assert_ne!(first, quote_id(start, &rng));
+    }
+
+    #[test]
+    fn test_legacy_ids_have_no_time() {
+        assert_eq!(created_at("2f1c9a5be0d74e3f8a61c0b7d4e29f35"), None);
+        assert_eq!(created_at(&Uuid::new_v4().to_string()), None);
+        assert_eq!(created_at("quote-1"), None);
+    }
+}
@@ -0,0 +1,242 @@
+//! Bounded execution of swap steps
+//!
+//! Accepting a quote mints and locks the broker's outputs on the target mint,
+//! and completing it swaps the client's proofs on the source mint. Run straight
+//! from the request handlers, a burst of accepts would put all of those mint
+//! calls in flight at once. Instead every such step is a job on the
+//! [`JobQueue`]: at most `workers` jobs run at a time, at most `per_mint` of
+//! them call the same mint, and at most `max_queued` more wait for a slot.
+//! A job that finds the queue full is refused right away with
+//! [`BrokerError::QueueFull`], so clients back off rather than pile up. Jobs
+//! run in the task of the request that queued them. The broker keeps the
+//! status of each quote's latest job in its quote record.
+
+use crate::error::{BrokerError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// How many jobs run and wait at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobQueueConfig {
+    pub workers: usize,    // Jobs running at once
+    pub per_mint: usize,   // Running jobs calling the same mint
+    pub max_queued: usize, // Jobs waiting for a slot before new ones are refused
+}
+
+impl Default for JobQueueConfig {
+    fn default() -> Self {
+        Self {
+            workers: 8,
+            per_mint: 4,
+            max_queued: 64,
+        }
+    }
+}
+
+/// Status of a quote's latest job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,  // Waiting for a slot
+    Running, // Calling the mints
+    Done,
+    Failed, // The quote's status says whether it can be retried
+}
+
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Queued => write!(f, "queued"),
+            Self::Running => write!(f, "running"),
+            Self::Done => write!(f, "done"),
+            Self::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "queued" => Ok(Self::Queued),
+            "running" => Ok(Self::Running),
+            "done" => Ok(Self::Done),
+            "failed" => Ok(Self::Failed),
+            _ => Err(format!("Invalid job status: {}", s)),
+        }
+    }
+}
+
+/// Runs swap steps with bounded concurrency
+pub struct JobQueue {
+    config: JobQueueConfig,
+    workers: Arc<Semaphore>,
+    mints: Mutex<HashMap<String, Arc<Semaphore>>>,
+    waiting: Arc<AtomicUsize>,
+}
+
+/// A job admitted to the queue and waiting for its slots
+pub struct Ticket {
+    waiting: Arc<AtomicUsize>,
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Slots held by a running job, given back when dropped
+pub struct JobPermit {
+    _mints: Vec<OwnedSemaphorePermit>,
+    _worker: OwnedSemaphorePermit,
+}
+
+impl JobQueue {
+    pub fn new(config: JobQueueConfig) -> Self {
+        Self {
+            config,
+            workers: Arc::new(Semaphore::new(config.workers.max(1))),
+            mints: Mutex::new(HashMap::new()),
+            waiting: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn config(&self) -> JobQueueConfig {
+        self.config
+    }
+
+    /// Jobs running now
+    pub fn running(&self) -> usize {
+        self.config.workers.max(1) - self.workers.available_permits()
+    }
+
+    /// Jobs waiting for a slot
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    /// Admit a job, unless every worker is busy and the queue is full
+    pub fn enqueue(&self) -> Result<Ticket> {
+        let admitted = self
+            .waiting
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |waiting| {
+                let full =
+                    waiting >= self.config.max_queued && self.workers.available_permits() == 0;
+                (!full).then_some(waiting + 1)
+            });
+        match admitted {
+            Ok(_) => Ok(Ticket {
+                waiting: self.waiting.clone(),
+            }),
+            Err(waiting) => Err(BrokerError::QueueFull(waiting)),
+        }
+    }
+
+    /// Wait for a slot on each of `mints` and then for a worker
+    ///
+    /// Mint slots are taken in a fixed order, so two jobs can't each hold one
+    /// the other is waiting for, and before the worker, so jobs held up by a
+    /// busy mint don't keep other mints' jobs from running.
+    pub async fn start(&self, ticket: Ticket, mints: &[&str]) -> JobPermit {
+        let mut mints = mints.to_vec();
+        mints.sort_unstable();
+        mints.dedup();
+
+        let mut held = Vec::with_capacity(mints.len());
+        for mint in mints {
+            let slots = self.mint_slots(mint);
+            held.push(
+                slots
+                    .acquire_owned()
+                    .await
+                    .expect("job semaphores are never closed"),
+            );
+        }
+        let worker = self
+            .workers
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("job semaphores are never closed");
+
+        drop(ticket);
+        JobPermit {
+            _mints: held,
+            _worker: worker,
+        }
+    }
+
+    fn mint_slots(&self, mint_url: &str) -> Arc<Semaphore> {
+        let mut mints = self.mints.lock().unwrap();
+        mints
+            .entry(mint_url.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(self.config.per_mint.max(1))))
+            .clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn queue(workers: usize, per_mint: usize, max_queued: usize) -> JobQueue {
+        JobQueue::new(JobQueueConfig {
+            workers,
+            per_mint,
+            max_queued,
+        })
+    }
+
+    #[test]
+    fn test_status_roundtrip() {
+        for status in [
+            JobStatus::Queued,
+            JobStatus::Running,
+            JobStatus::Done,
+            JobStatus::Failed,
+        ] {
+            assert_eq!(status.to_string().parse::<JobStatus>(), Ok(status));
+        }
+        assert!("stuck".parse::<JobStatus>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let jobs = queue(1, 1, 1);
+
+        let running = jobs.start(jobs.enqueue().unwrap(), &["a"]).await;
+        assert_eq!(jobs.running(), 1);
+
+        // One may wait, the next is refused
+        let waiting = jobs.enqueue().unwrap();
+        assert_eq!(jobs.waiting(), 1);
+        assert!(matches!(jobs.enqueue(), Err(BrokerError::QueueFull(1))));
+
+        drop(waiting);
+        drop(running);
+        assert_eq!((jobs.running(), jobs.waiting()), (0, 0));
+        assert!(jobs.enqueue().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_per_mint_cap() {
+        let jobs = queue(4, 1, 4);
+        let _a = jobs.start(jobs.enqueue().unwrap(), &["a"]).await;
+
+        // Another mint goes ahead; the same mint waits
+        let _b = jobs.start(jobs.enqueue().unwrap(), &["b", "b"]).await;
+        let same = jobs.start(jobs.enqueue().unwrap(), &["a"]);
+        assert!(tokio::time::timeout(Duration::from_millis(50), same)
+            .await
+            .is_err());
+        assert_eq!(jobs.running(), 2);
+    }
+}
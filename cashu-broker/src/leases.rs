@@ -0,0 +1,395 @@
+//! Coordination between broker instances sharing a database
+//!
+//! Several brokers can run against one database, e.g. replicas behind a load
+//! balancer. Left alone, two of them quoting payouts from the same mint would
+//! each reserve its liquidity without seeing the other's reservations, and
+//! sweeps over the shared tables would run on every instance at once.
+//!
+//! [`Leases`] takes named, expiring leases in a [`LeaseStore`] on behalf of
+//! one instance. A broker only quotes out of a mint, and restores or
+//! reconciles the quotes paying out of it, while it holds that mint's lease
+//! ([`mint_lease`]); only the holder of a sweep's lease runs that sweep.
+//! Leases are renewed while the instance runs and lapse [`Leases::ttl`]
+//! after it stops, when another instance can take them over. An instance
+//! that restarts under the same ID picks its leases straight back up. One
+//! that finds a lease taken, or can't renew it before it lapses, announces it
+//! on [`Leases::lost`] so the broker stops serving quotes out of that mint.
+//!
+//! Two processes running under the same ID would each take the other's
+//! leases for their own, so a process first claims its ID with
+//! [`Leases::claim_instance`].
+
+use crate::db::Lease;
+use crate::error::{BrokerError, Result};
+use crate::store::LeaseStore;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+/// How long a lease lasts unless renewed
+pub const DEFAULT_LEASE_TTL: Duration = Duration::from_secs(30);
+
+/// Lease on marking expired quotes in the database
+pub const QUOTE_SWEEP: &str = "sweep:quotes";
+/// Lease on pruning records past the retention period
+pub const RETENTION_SWEEP: &str = "sweep:retention";
+/// Lease on sending queued webhook deliveries
+pub const WEBHOOK_SWEEP: &str = "sweep:webhooks";
+/// Lease on deleting expired idempotency keys
+pub const IDEMPOTENCY_SWEEP: &str = "sweep:idempotency";
+
+/// Lease on quoting out of, and reserving, the liquidity on `mint_url`
+pub fn mint_lease(mint_url: &str) -> String {
+    format!("mint:{}", mint_url)
+}
+
+/// Lease on running as instance `holder`, held by one process at a time
+pub fn instance_lease(holder: &str) -> String {
+    format!("instance:{}", holder)
+}
+
+/// Mint a lease from [`mint_lease`] is on
+pub fn leased_mint(name: &str) -> Option<&str> {
+    name.strip_prefix("mint:")
+}
+
+/// Leases held by one broker instance
+///
+/// Clones share the set of leases held, so the broker, the pruner and the
+/// webhook dispatcher can each take theirs and one loop renews them all.
+#[derive(Clone)]
+pub struct Leases {
+    store: Arc<dyn LeaseStore>,
+    holder: String,
+    ttl: Duration,
+    held: Arc<Mutex<BTreeMap<String, Instant>>>, // Name -> when last taken or renewed
+    lost: broadcast::Sender<String>,
+}
+
+impl Leases {
+    /// Leases in `store` held as instance `holder`
+    ///
+    /// Every instance sharing the store needs its own `holder` ID.
+    pub fn new(store: Arc<dyn LeaseStore>, holder: impl Into<String>) -> Self {
+        Self {
+            store,
+            holder: holder.into(),
+            ttl: DEFAULT_LEASE_TTL,
+            held: Arc::default(),
+            lost: broadcast::channel(64).0,
+        }
+    }
+
+    /// Let leases lapse `ttl` after they were last renewed
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// ID of the instance these leases are held as
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// How often [`Leases::renew`] should run to keep leases from lapsing
+    pub fn renew_interval(&self) -> Duration {
+        (self.ttl / 3).max(Duration::from_millis(100))
+    }
+
+    /// Take lease `name`, or renew it, returning it as it stands: this
+    /// instance's if its holder is [`Leases::holder`]
+    ///
+    /// A lease this instance held that another one has now is announced on
+    /// [`Leases::lost`].
+    pub async fn acquire(&self, name: &str) -> Result<Lease> {
+        let lease = self
+            .store
+            .acquire_lease(name, &self.holder, self.ttl)
+            .await?;
+        if lease.holder == self.holder {
+            self.lock().insert(name.to_string(), Instant::now());
+        } else if self.lock().remove(name).is_some() {
+            warn!("Lost lease {} to instance {}", name, lease.holder);
+            let _ = self.lost.send(name.to_string());
+        }
+        Ok(lease)
+    }
+
+    /// Claim this instance's ID for the running process
+    ///
+    /// Takes [`instance_lease`] under a random token of the process, so a
+    /// second process started with the same ID can't take this one's leases
+    /// for its own. A process that stopped without releasing it holds it for
+    /// at most [`Leases::ttl`], which a restart waits out; a live one keeps
+    /// it, and the claim fails. Returns the leases held as the process, to
+    /// renew with [`Leases::run`] for as long as it runs.
+    pub async fn claim_instance(&self) -> Result<Leases> {
+        let process = Leases {
+            holder: format!("{}@{:016x}", self.holder, rand::random::<u64>()),
+            held: Arc::default(),
+            lost: broadcast::channel(64).0,
+            ..self.clone()
+        };
+        let name = instance_lease(&self.holder);
+        let deadline = Instant::now() + self.ttl;
+
+        loop {
+            let lease = process.acquire(&name).await?;
+            if lease.holder == process.holder {
+                return Ok(process);
+            }
+            if Instant::now() >= deadline {
+                return Err(BrokerError::Other(anyhow::anyhow!(
+                    "Another process runs as instance {} ({}); give each its own INSTANCE_ID",
+                    self.holder,
+                    lease.holder
+                )));
+            }
+            warn!(
+                "Instance {} is claimed by {}, waiting for it to lapse",
+                self.holder, lease.holder
+            );
+            tokio::time::sleep(process.renew_interval()).await;
+        }
+    }
+
+    /// Whether this instance holds lease `name`, taking it if it is free
+    pub async fn hold(&self, name: &str) -> Result<bool> {
+        Ok(self.acquire(name).await?.holder == self.holder)
+    }
+
+    /// Whether a sweep guarded by lease `name` should run here now
+    ///
+    /// A store that can't be reached counts as another instance holding the
+    /// lease: the sweep is skipped rather than run twice.
+    pub async fn may_run(&self, name: &str) -> bool {
+        match self.hold(name).await {
+            Ok(held) => held,
+            Err(e) => {
+                warn!("Could not take lease {}: {}", name, e);
+                false
+            }
+        }
+    }
+
+    /// Give up lease `name`, so another instance can take it right away
+    pub async fn release(&self, name: &str) -> Result<()> {
+        self.lock().remove(name);
+        self.store.release_lease(name, &self.holder).await
+    }
+
+    /// Names of the leases this instance holds, as of their last renewal
+    pub fn held(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    /// Names of leases as this instance loses them
+    pub fn lost(&self) -> broadcast::Receiver<String> {
+        self.lost.subscribe()
+    }
+
+    /// Renew every lease held, returning the names of those lost: taken by
+    /// another instance in the meantime, or lapsed
+    ///
+    /// Leases that fail to renew are kept and tried again next time, until
+    /// [`Leases::ttl`] has passed since they were last renewed. Lost leases
+    /// are announced on [`Leases::lost`].
+    pub async fn renew(&self) -> Vec<String> {
+        let mut lost = Vec::new();
+        for name in self.held() {
+            match self.acquire(&name).await {
+                Ok(lease) if lease.holder != self.holder => lost.push(name),
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Could not renew lease {}: {}", name, e);
+                    let lapsed = {
+                        let mut held = self.lock();
+                        let lapsed = held.get(&name).is_some_and(|at| at.elapsed() >= self.ttl);
+                        if lapsed {
+                            held.remove(&name);
+                        }
+                        lapsed
+                    };
+                    if lapsed {
+                        warn!("Lease {} lapsed", name);
+                        let _ = self.lost.send(name.clone());
+                        lost.push(name);
+                    }
+                }
+            }
+        }
+        lost
+    }
+
+    /// Renew held leases forever
+    pub async fn run(&self) {
+        let mut interval = tokio::time::interval(self.renew_interval());
+        loop {
+            interval.tick().await;
+            self.renew().await;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Instant>> {
+        self.held.lock().expect("lease lock poisoned")
+    }
+}
+
+impl fmt::Debug for Leases {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Leases")
+            .field("holder", &self.holder)
+            .field("ttl", &self.ttl)
+            .field("held", &self.held())
+            .finish()
+    }
+}
+
+/// Whether a sweep guarded by lease `name` should run, always with no leases
+pub async fn may_run(leases: Option<&Leases>, name: &str) -> bool {
+    match leases {
+        Some(leases) => leases.may_run(name).await,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    async fn setup_store() -> Arc<Database> {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.migrate().await.unwrap();
+        Arc::new(db)
+    }
+
+    #[tokio::test]
+    async fn test_instances_take_turns() {
+        let store = setup_store().await;
+        let a = Leases::new(store.clone(), "a");
+        let b = Leases::new(store.clone(), "b").with_ttl(Duration::ZERO);
+        let mint = mint_lease("http://mint-a.test");
+
+        assert!(a.hold(&mint).await.unwrap());
+        assert!(a.may_run(QUOTE_SWEEP).await);
+        assert!(!b.hold(&mint).await.unwrap());
+        assert!(!b.may_run(QUOTE_SWEEP).await);
+        assert_eq!(a.held(), [mint.clone(), QUOTE_SWEEP.to_string()]);
+        assert!(b.held().is_empty());
+
+        // A released lease is free at once; b's own lapse straight away
+        a.release(&mint).await.unwrap();
+        assert!(b.hold(&mint).await.unwrap());
+        assert_eq!(b.held(), [mint.clone()]);
+        assert!(a.hold(&mint).await.unwrap());
+        let mut lost = b.lost();
+        assert!(b.renew().await == [mint.clone()] && b.held().is_empty());
+        assert_eq!(lost.try_recv().unwrap(), mint);
+        assert_eq!(leased_mint(&mint), Some("http://mint-a.test"));
+        assert_eq!(leased_mint(QUOTE_SWEEP), None);
+
+        // Clones share what is held; no leases at all means run everywhere
+        assert_eq!(a.clone().held(), a.held());
+        assert!(may_run(None, QUOTE_SWEEP).await);
+        assert!(!may_run(Some(&b), QUOTE_SWEEP).await);
+    }
+
+    #[tokio::test]
+    async fn test_renew_keeps_leases() {
+        let store = setup_store().await;
+        let a = Leases::new(store.clone(), "a").with_ttl(Duration::from_secs(60));
+        let b = Leases::new(store.clone(), "b");
+
+        let first = a.acquire(RETENTION_SWEEP).await.unwrap();
+        assert!(a.renew().await.is_empty());
+        let leases = store.list_leases().await.unwrap();
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].acquired_at, first.acquired_at);
+        assert!(leases[0].expires_at >= first.expires_at);
+
+        let lease = b.acquire(RETENTION_SWEEP).await.unwrap();
+        assert_eq!(lease.holder, "a");
+        assert_eq!(a.renew_interval(), Duration::from_secs(20));
+    }
+
+    #[tokio::test]
+    async fn test_one_process_per_instance() {
+        let store = setup_store().await;
+        let a = Leases::new(store.clone(), "a").with_ttl(Duration::from_millis(300));
+
+        // A second process under the same ID is refused while the first runs
+        let first = a.claim_instance().await.unwrap();
+        assert_ne!(first.holder(), a.holder());
+        let running = tokio::spawn({
+            let first = first.clone();
+            async move { first.run().await }
+        });
+        assert!(a.claim_instance().await.is_err());
+
+        // but takes over once the first stops renewing
+        running.abort();
+        let second = a.claim_instance().await.unwrap();
+        assert_ne!(second.holder(), first.holder());
+        assert_eq!(second.held(), [instance_lease("a")]);
+        assert!(first.renew().await == [instance_lease("a")]);
+    }
+
+    #[tokio::test]
+    async fn test_unrenewed_leases_lapse() {
+        use crate::error::BrokerError;
+        use async_trait::async_trait;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        /// A store that can be taken down
+        struct Flaky {
+            db: Arc<Database>,
+            down: AtomicBool,
+        }
+
+        #[async_trait]
+        impl LeaseStore for Flaky {
+            async fn acquire_lease(
+                &self,
+                name: &str,
+                holder: &str,
+                ttl: Duration,
+            ) -> Result<Lease> {
+                if self.down.load(Ordering::SeqCst) {
+                    return Err(BrokerError::Database("down".to_string()));
+                }
+                self.db.acquire_lease(name, holder, ttl).await
+            }
+
+            async fn release_lease(&self, name: &str, holder: &str) -> Result<()> {
+                self.db.release_lease(name, holder).await?;
+                Ok(())
+            }
+        }
+
+        let store = Arc::new(Flaky {
+            db: setup_store().await,
+            down: AtomicBool::new(false),
+        });
+        let a = Leases::new(store.clone(), "a").with_ttl(Duration::from_millis(50));
+        let mut lost = a.lost();
+        assert!(a.hold(QUOTE_SWEEP).await.unwrap());
+
+        // Renewals that fail keep the lease until it would have lapsed
+        store.down.store(true, Ordering::SeqCst);
+        assert!(a.renew().await.is_empty());
+        assert_eq!(a.held(), [QUOTE_SWEEP.to_string()]);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(a.renew().await, [QUOTE_SWEEP.to_string()]);
+        assert!(a.held().is_empty());
+        assert_eq!(lost.try_recv().unwrap(), QUOTE_SWEEP);
+    }
+}
@@ -33,6 +33,7 @@
 //!         min_swap_amount: 1,
 //!         max_swap_amount: 10_000,
 //!         quote_expiry_seconds: 300,
+//!         ..Default::default()
 //!     };
 //!
 //!     let broker = Broker::new(config).await?;
@@ -45,19 +46,78 @@
 //! }
 //! ```
 
+pub mod accounting;
 pub mod adaptor;
+pub mod alerts;
+pub mod admin;
 pub mod api;
+pub mod api_keys;
+pub mod binding;
+pub mod blacklist;
+pub mod bootstrap;
 pub mod broker;
+pub mod chunked;
+pub mod circuit_breaker;
+pub mod client;
+pub mod client_first;
+pub mod clock;
 pub mod config;
+pub mod cors;
+pub mod dashboard;
 pub mod db;
+pub mod dleq;
+pub mod encryption;
 pub mod error;
+pub mod etag;
+pub mod events;
+pub mod export;
+#[cfg(feature = "faults")]
+pub mod faults;
+pub mod fees;
+pub mod idempotency;
+pub mod identity;
+pub mod ids;
+pub mod jobs;
+pub mod leases;
+pub mod limits;
 pub mod liquidity;
+pub mod nip98;
+#[cfg(feature = "nostr")]
+pub mod nostr;
+pub mod price;
+pub mod pricing;
+pub mod quarantine;
+pub mod rate_limit;
+pub mod reconcile;
+pub mod request_id;
+pub mod retention;
+pub mod retry;
+pub mod risk;
+pub mod rng;
+pub mod selection;
+pub mod store;
+pub mod subscriptions;
 pub mod swap;
+#[cfg(feature = "test-support")]
+pub mod testkit;
+pub mod timeouts;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod top;
 pub mod types;
+pub mod validation;
+pub mod versioning;
+pub mod watcher;
+pub mod webhooks;
 
 pub use api::AppState;
-pub use broker::Broker;
+pub use broker::{Broker, BrokerBuilder};
+pub use client::SwapClient;
+pub use clock::{Clock, ManualClock, SystemClock};
 pub use config::Config;
 pub use db::Database;
-pub use error::{BrokerError, Result};
-pub use types::{BrokerConfig, MintConfig, SwapQuote, SwapRequest};
+pub use error::{BrokerError, ErrorCode, Result};
+pub use identity::verify_quote_signature;
+pub use rng::SwapRng;
+pub use store::{LeaseStore, LedgerStore, LiquidityStore, QuoteStore};
+pub use types::{BrokerConfig, MintConfig, PairConfig, QuoteType, Sensitive, SwapQuote, SwapRequest};
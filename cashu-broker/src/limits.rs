@@ -0,0 +1,135 @@
+//! Size and time limits on HTTP requests
+//!
+//! Accepts carry every source proof, so bodies can get large. A body above
+//! `max_body_bytes` is refused with `413 Payload Too Large`: up front with a
+//! `PAYLOAD_TOO_LARGE` error when its length is declared, and when it is read
+//! otherwise. A request still running after `timeout` gets `503
+//! REQUEST_TIMEOUT`. Its handler is not cancelled, so a swap step never stops
+//! halfway through a mint call; as accept and complete are safe to retry, the
+//! client gets the result by retrying.
+
+use crate::api::ApiError;
+use axum::{
+    body::HttpBody,
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::time::Duration;
+use tracing::Instrument;
+
+/// Limits applied to every request
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    pub max_body_bytes: usize, // Largest request body accepted
+    pub timeout: Duration,     // Time before the client gets a timeout error
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 1024 * 1024,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Middleware refusing bodies known to be above `max_body_bytes`
+///
+/// Bodies of unknown length are cut off by `DefaultBodyLimit` when read.
+pub async fn body_limit(max_body_bytes: usize, req: Request, next: Next) -> Response {
+    // Incoming bodies know their length from the Content-Length header
+    let length = req.body().size_hint().lower();
+    if length > max_body_bytes as u64 {
+        return ApiError::PayloadTooLarge(format!(
+            "Request body of {} bytes is above the limit of {} bytes",
+            length, max_body_bytes
+        ))
+        .into_response();
+    }
+    next.run(req).await
+}
+
+/// Middleware answering with a timeout error once `timeout` has passed
+pub async fn timeout(timeout: Duration, req: Request, next: Next) -> Response {
+    let handler = tokio::spawn(next.run(req).in_current_span());
+
+    match tokio::time::timeout(timeout, handler).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => ApiError::Internal(format!("Request failed: {}", e)).into_response(),
+        Err(_) => {
+            tracing::warn!("Request still running after {:?}", timeout);
+            ApiError::Timeout(timeout).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::{to_bytes, Body},
+        http::StatusCode,
+        middleware,
+        routing::post,
+        Router,
+    };
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_body_limit() {
+        let app = Router::new()
+            .route("/", post(|body: String| async move { body }))
+            .layer(middleware::from_fn(|req: Request, next: Next| {
+                body_limit(8, req, next)
+            }));
+
+        let response = app
+            .clone()
+            .oneshot(Request::post("/").body(Body::from("12345678")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(Request::post("/").body(Body::from("123456789")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "PAYLOAD_TOO_LARGE");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_keeps_handler_running() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let flag = finished.clone();
+        let app = Router::new()
+            .route(
+                "/",
+                post(move || async move {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    flag.store(true, Ordering::SeqCst);
+                }),
+            )
+            .layer(middleware::from_fn(|req: Request, next: Next| {
+                timeout(Duration::from_millis(10), req, next)
+            }));
+
+        let response = app
+            .oneshot(Request::post("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "REQUEST_TIMEOUT");
+        assert_eq!(body["retryable"], true);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(finished.load(Ordering::SeqCst));
+    }
+}
@@ -2,18 +2,33 @@
 //!
 //! Tracks and manages Charlie's ecash balances across multiple mints
 
+use crate::circuit_breaker::CircuitBreaker;
+use crate::dleq::{self, Failure};
 use crate::error::{BrokerError, Result};
+use crate::events::{BrokerEvent, EventBus};
+#[cfg(feature = "faults")]
+use crate::faults::{self, Fault, FaultInjector};
+use crate::quarantine::{Disposition, QuarantineReason, QuarantineRetry, QuarantinedProof};
+use crate::retry::{self, RetryPolicy};
+use crate::selection::SelectionStrategy;
+use crate::subscriptions::{self, SpentProofs};
+use crate::timeouts::{MintOp, MintTimeouts};
 use crate::types::MintConfig;
 use cdk::amount::SplitTarget;
-use cdk::nuts::{CurrencyUnit, Proofs};
+use cdk::nuts::{
+    CurrencyUnit, Id, KeySetInfo, MintInfo, Proof, Proofs, PublicKey, State, Token,
+};
 use cdk::nuts::nut00::ProofsMethods;
-use cdk::wallet::Wallet;
+use cdk::cdk_database::WalletDatabase;
+use cdk::wallet::{MintQuote, Wallet};
 use cdk::Amount;
 use cdk_sqlite::wallet::memory;
 use rand::random;
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock as StdRwLock};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
@@ -23,13 +38,233 @@ pub struct MintLiquidity {
     pub mint_url: String,
     pub balance: u64,
     pub proofs: Proofs,
+    pub reservations: HashMap<String, u64>, // quote_id -> amount held for a pending quote
+    pub locked: HashMap<String, u64>,       // quote_id -> amount committed to an accepted swap
+    pub quarantine: Vec<QuarantinedProof>, // Held out of the balance, see [`crate::quarantine`]
     pub last_updated: SystemTime,
 }
 
+impl MintLiquidity {
+    fn empty(mint_url: &str) -> Self {
+        Self {
+            mint_url: mint_url.to_string(),
+            balance: 0,
+            proofs: vec![],
+            reservations: HashMap::new(),
+            locked: HashMap::new(),
+            quarantine: vec![],
+            last_updated: SystemTime::now(),
+        }
+    }
+
+    /// Total amount held for pending quotes
+    pub fn reserved(&self) -> u64 {
+        self.reservations.values().sum()
+    }
+
+    /// Total amount committed to accepted swaps that haven't completed
+    pub fn locked(&self) -> u64 {
+        self.locked.values().sum()
+    }
+
+    /// Total amount of quarantined proofs
+    pub fn quarantined(&self) -> u64 {
+        self.quarantine.iter().map(QuarantinedProof::amount).sum()
+    }
+
+    /// Balance neither reserved for a quote nor locked in a swap
+    pub fn available(&self) -> u64 {
+        self.balance
+            .saturating_sub(self.reserved())
+            .saturating_sub(self.locked())
+    }
+}
+
+/// Result of paying an invoice from a mint's liquidity
+#[derive(Debug, Clone)]
+pub struct InvoicePayment {
+    pub amount: u64,   // Invoice amount
+    pub fee_paid: u64, // Lightning fee charged by the mint
+    pub spent: u64,    // Balance consumed (amount + fee, after change)
+    pub preimage: Option<String>,
+}
+
+/// A completed Lightning transfer between two mints
+#[derive(Debug, Clone)]
+pub struct RebalanceTransfer {
+    pub from_mint: String,
+    pub to_mint: String,
+    pub amount: u64,   // Minted on the destination mint
+    pub spent: u64,    // Consumed on the source mint, including fees
+    pub fee_paid: u64,
+    pub unminted: Option<MintQuote>, // Paid mint quote on to_mint that couldn't be minted yet
+}
+
+/// Proofs on a mint swapped for fewer, larger ones
+#[derive(Debug, Clone)]
+pub struct Consolidation {
+    pub mint_url: String,
+    pub proofs_before: usize,
+    pub proofs_after: usize,
+    pub fee_paid: u64, // Input fee charged by the mint for the swap
+}
+
+/// Outcome of recent health checks against a mint
+#[derive(Debug, Clone, Default)]
+pub struct MintHealth {
+    pub consecutive_failures: u32,
+    pub last_checked: Option<SystemTime>,
+    pub last_error: Option<String>,
+    pub latency: Option<Duration>, // Moving average over successful wallet calls
+}
+
+impl MintHealth {
+    /// Mints stay usable until several checks in a row have failed
+    pub fn is_healthy(&self) -> bool {
+        self.consecutive_failures < UNHEALTHY_AFTER_FAILURES
+    }
+}
+
+/// Input fees charged by a mint's keysets (NUT-02)
+#[derive(Debug, Clone)]
+pub struct KeysetFees {
+    pub fee_ppk: HashMap<Id, u64>, // Fee per input in parts per thousand, by keyset
+    pub active_fee_ppk: u64,       // Highest fee among the active keysets in the wallet's unit
+}
+
+impl KeysetFees {
+    /// Fees of the keysets in `unit`; proofs of the mint's other units count as unknown
+    fn new(keysets: &[KeySetInfo], unit: &CurrencyUnit) -> Self {
+        Self {
+            fee_ppk: keysets
+                .iter()
+                .filter(|k| k.unit == *unit)
+                .map(|k| (k.id, k.input_fee_ppk))
+                .collect(),
+            active_fee_ppk: keysets
+                .iter()
+                .filter(|k| k.active && k.unit == *unit)
+                .map(|k| k.input_fee_ppk)
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Fee the mint charges to spend `proofs`, or `None` if a keyset is unknown
+    pub fn proofs_fee(&self, proofs: &Proofs) -> Option<u64> {
+        let total_ppk = proofs
+            .iter()
+            .map(|p| self.fee_ppk.get(&p.keyset_id).copied())
+            .sum::<Option<u64>>()?;
+        Some(total_ppk.div_ceil(1000))
+    }
+}
+
+/// Fee to spend `amount` as the fewest proofs of a keyset charging `fee_ppk`
+///
+/// Proofs come in powers of two, so that is one proof per set bit.
+pub fn input_fee(amount: u64, fee_ppk: u64) -> u64 {
+    (u64::from(amount.count_ones()) * fee_ppk).div_ceil(1000)
+}
+
+/// How long to wait for the destination mint to see a rebalance payment
+const REBALANCE_MINT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Consecutive failed health checks before a mint is considered down
+const UNHEALTHY_AFTER_FAILURES: u32 = 3;
+
+/// The newest call counts for one part in this many of a mint's latency
+const LATENCY_SMOOTHING: u32 = 5;
+
+/// Timeout for a single health check
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long fetched keysets and mint info are used before asking the mint again
+const MINT_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+/// Something fetched from a mint, and when
+#[derive(Debug, Clone)]
+struct Cached<T> {
+    value: T,
+    fetched_at: Instant,
+}
+
+impl<T: Clone> Cached<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            fetched_at: Instant::now(),
+        }
+    }
+
+    /// The value, unless it is older than [`MINT_CACHE_TTL`]
+    fn fresh(&self) -> Option<T> {
+        (self.fetched_at.elapsed() < MINT_CACHE_TTL).then(|| self.value.clone())
+    }
+}
+
+/// Run `attempt` as `fault` has it; without a fault it runs untouched
+#[cfg(feature = "faults")]
+async fn inject<T, Fut>(
+    fault: Option<Fault>,
+    mint_url: &str,
+    op: MintOp,
+    attempt: Fut,
+) -> std::result::Result<T, cdk::Error>
+where
+    T: 'static,
+    Fut: Future<Output = std::result::Result<T, cdk::Error>>,
+{
+    let Some(fault) = fault else {
+        return attempt.await;
+    };
+    warn!("Injecting fault into {} on {}: {}", op, mint_url, fault);
+    match fault {
+        Fault::Fail | Fault::Reject => Err(fault.error()),
+        Fault::Delay(delay) => {
+            tokio::time::sleep(delay).await;
+            attempt.await
+        }
+        Fault::LostResponse => {
+            let _ = attempt.await;
+            Err(fault.error())
+        }
+        Fault::Partial => {
+            let mut result = attempt.await?;
+            faults::truncate(&mut result);
+            Ok(result)
+        }
+    }
+}
+
+/// A mint's keysets and info as last fetched
+#[derive(Debug, Clone, Default)]
+struct MintCache {
+    keysets: Option<Cached<Vec<KeySetInfo>>>,
+    info: Option<Cached<MintInfo>>,
+}
+
+/// Failure of one attempt at a wallet call
+#[derive(Debug)]
+enum CallError {
+    TimedOut,
+    Mint(cdk::Error),
+}
+
 /// Manages liquidity across multiple mints
 pub struct LiquidityManager {
     liquidity: Arc<RwLock<HashMap<String, MintLiquidity>>>,
-    wallets: HashMap<String, Arc<Wallet>>,
+    wallets: StdRwLock<HashMap<String, Arc<Wallet>>>,
+    health: StdRwLock<HashMap<String, MintHealth>>,
+    cache: StdRwLock<HashMap<String, MintCache>>,
+    retry: StdRwLock<RetryPolicy>,
+    timeouts: StdRwLock<MintTimeouts>,
+    selection: StdRwLock<SelectionStrategy>,
+    require_dleq: StdRwLock<bool>,
+    circuits: CircuitBreaker,
+    #[cfg(feature = "faults")]
+    faults: StdRwLock<FaultInjector>,
+    events: EventBus,
 }
 
 impl LiquidityManager {
@@ -39,51 +274,194 @@ impl LiquidityManager {
         let mut liquidity = HashMap::new();
 
         for mint in mints {
-            // Create a wallet for each mint
-            // TODO: In production, use persistent storage instead of memory
-            let localstore = Arc::new(memory::empty().await
-                .map_err(|e| BrokerError::Cdk(format!("Failed to create memory store: {:?}", e)))?);
-
-            // Generate a random seed for the wallet
-            let mut seed = [0u8; 64];
-            for byte in seed.iter_mut() {
-                *byte = random();
-            }
-
-            let wallet = Wallet::new(
-                &mint.mint_url,
-                CurrencyUnit::Sat,
-                localstore,
-                seed,
-                None,
-            )
-            .map_err(|e| BrokerError::Cdk(format!("Failed to create wallet: {:?}", e)))?;
-
-            liquidity.insert(
-                mint.mint_url.clone(),
-                MintLiquidity {
-                    mint_url: mint.mint_url.clone(),
-                    balance: 0,
-                    proofs: vec![],
-                    last_updated: SystemTime::now(),
-                },
-            );
+            let wallet = create_wallet(&mint).await?;
 
+            liquidity.insert(mint.mint_url.clone(), MintLiquidity::empty(&mint.mint_url));
             wallets.insert(mint.mint_url.clone(), Arc::new(wallet));
         }
 
         Ok(Self {
             liquidity: Arc::new(RwLock::new(liquidity)),
-            wallets,
+            wallets: StdRwLock::new(wallets),
+            health: StdRwLock::new(HashMap::new()),
+            cache: StdRwLock::new(HashMap::new()),
+            retry: StdRwLock::new(RetryPolicy::default()),
+            timeouts: StdRwLock::new(MintTimeouts::default()),
+            selection: StdRwLock::new(SelectionStrategy::default()),
+            require_dleq: StdRwLock::new(true),
+            circuits: CircuitBreaker::default(),
+            #[cfg(feature = "faults")]
+            faults: StdRwLock::new(FaultInjector::default()),
+            events: EventBus::default(),
         })
     }
 
+    /// Announce balance and mint health changes on `events`
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Start tracking a new mint with an empty balance
+    pub async fn add_mint(&self, mint: &MintConfig) -> Result<()> {
+        if self.get_wallet(&mint.mint_url).is_ok() {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Mint {} is already configured",
+                mint.mint_url
+            )));
+        }
+
+        let wallet = create_wallet(mint).await?;
+
+        self.liquidity
+            .write()
+            .await
+            .insert(mint.mint_url.clone(), MintLiquidity::empty(&mint.mint_url));
+        self.wallets
+            .write()
+            .expect("wallets lock poisoned")
+            .insert(mint.mint_url.clone(), Arc::new(wallet));
+
+        info!("Added mint {}", mint.mint_url);
+
+        Ok(())
+    }
+
+    /// Stop tracking a mint
+    ///
+    /// Refuses while the broker still holds a balance, reservations, locked swaps or
+    /// quarantined proofs there, so withdraw the liquidity first.
+    pub async fn remove_mint(&self, mint_url: &str) -> Result<()> {
+        let mut liq = self.liquidity.write().await;
+        let mint_liq = liq
+            .get(mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+
+        if mint_liq.balance > 0
+            || !mint_liq.reservations.is_empty()
+            || !mint_liq.locked.is_empty()
+        {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Mint {} still holds {} sats ({} reserved, {} locked)",
+                mint_url,
+                mint_liq.balance,
+                mint_liq.reserved(),
+                mint_liq.locked()
+            )));
+        }
+        if !mint_liq.quarantine.is_empty() {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Mint {} still has {} sats of quarantined proofs; retry or discard them first",
+                mint_url,
+                mint_liq.quarantined()
+            )));
+        }
+
+        liq.remove(mint_url);
+        self.wallets
+            .write()
+            .expect("wallets lock poisoned")
+            .remove(mint_url);
+        self.health
+            .write()
+            .expect("health lock poisoned")
+            .remove(mint_url);
+        self.cache
+            .write()
+            .expect("mint cache lock poisoned")
+            .remove(mint_url);
+        self.circuits.remove(mint_url);
+
+        info!("Removed mint {}", mint_url);
+
+        Ok(())
+    }
+
     /// Get current balance on a mint
     pub async fn get_balance(&self, mint_url: &str) -> u64 {
         let liq = self.liquidity.read().await;
         liq.get(mint_url).map(|l| l.balance).unwrap_or(0)
     }
 
+    /// Get the balance on a mint that isn't reserved for quotes or locked in swaps
+    pub async fn get_available_balance(&self, mint_url: &str) -> u64 {
+        let liq = self.liquidity.read().await;
+        liq.get(mint_url).map(|l| l.available()).unwrap_or(0)
+    }
+
+    /// Hold `amount` on a mint for a quote
+    ///
+    /// Fails if the unreserved balance can't cover it, so concurrent quotes
+    /// can never promise more than the broker holds.
+    pub async fn reserve(&self, mint_url: &str, quote_id: &str, amount: u64) -> Result<()> {
+        let mut liq = self.liquidity.write().await;
+        let mint_liq = liq
+            .get_mut(mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+
+        let available = mint_liq.available();
+        if available < amount {
+            return Err(BrokerError::InsufficientLiquidity {
+                mint_url: mint_url.to_string(),
+                needed: amount,
+                available,
+            });
+        }
+
+        mint_liq.reservations.insert(quote_id.to_string(), amount);
+        debug!("Reserved {} sats on {} for quote {}", amount, mint_url, quote_id);
+
+        Ok(())
+    }
+
+    /// Move a quote's reservation to locked once the client has accepted it
+    ///
+    /// Returns the amount locked, or `None` if nothing was reserved.
+    pub async fn lock(&self, quote_id: &str) -> Option<u64> {
+        let mut liq = self.liquidity.write().await;
+        let locked = liq.values_mut().find_map(|mint_liq| {
+            let amount = mint_liq.reservations.remove(quote_id)?;
+            mint_liq.locked.insert(quote_id.to_string(), amount);
+            Some(amount)
+        });
+
+        if let Some(amount) = locked {
+            debug!("Locked {} sats for accepted quote {}", amount, quote_id);
+        }
+
+        locked
+    }
+
+    /// Whether a quote still holds a reservation its mint's balance covers
+    ///
+    /// Reservations are only checked against the balance when made, so one
+    /// can end up uncovered once proofs leave the mint (a withdrawal or a
+    /// quarantine, say).
+    pub async fn reservation_covered(&self, quote_id: &str) -> bool {
+        let liq = self.liquidity.read().await;
+        liq.values().any(|mint_liq| {
+            mint_liq.reservations.contains_key(quote_id)
+                && mint_liq.balance >= mint_liq.reserved() + mint_liq.locked()
+        })
+    }
+
+    /// Release what a quote holds, reserved or locked, returning the amount released
+    pub async fn release(&self, quote_id: &str) -> Option<u64> {
+        let mut liq = self.liquidity.write().await;
+        let released = liq.values_mut().find_map(|mint_liq| {
+            mint_liq
+                .reservations
+                .remove(quote_id)
+                .or_else(|| mint_liq.locked.remove(quote_id))
+        });
+
+        if let Some(amount) = released {
+            debug!("Released {} sats held for quote {}", amount, quote_id);
+        }
+
+        released
+    }
+
     /// Get available proofs on a mint
     pub async fn get_proofs(&self, mint_url: &str) -> Proofs {
         let liq = self.liquidity.read().await;
@@ -93,105 +471,1255 @@ impl LiquidityManager {
     }
 
     /// Add proofs to liquidity (e.g., after minting or receiving)
+    ///
+    /// Proofs whose DLEQ doesn't verify against the mint's keys are
+    /// quarantined instead (see [`crate::dleq`]).
     pub async fn add_proofs(&self, mint_url: &str, proofs: Proofs) -> Result<()> {
+        let (proofs, failed) = self.verify_dleq(mint_url, proofs).await;
+        let mut liq = self.liquidity.write().await;
+        let mint_liq = liq
+            .get_mut(mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+
+        self.hold(mint_liq, failed.into_iter().map(dleq_quarantine).collect());
+
+        let amount: u64 = proofs.total_amount()
+            .map_err(|e| BrokerError::Cdk(format!("Failed to calculate total amount: {:?}", e)))?
+            .into();
+        mint_liq.proofs.extend(proofs);
+        mint_liq.balance += amount;
+        mint_liq.last_updated = SystemTime::now();
+
+        info!(
+            "💰 Added {} sats to {} (new balance: {})",
+            amount, mint_url, mint_liq.balance
+        );
+        self.events.emit(BrokerEvent::BalanceChanged {
+            mint_url: mint_url.to_string(),
+            balance: mint_liq.balance,
+        });
+
+        Ok(())
+    }
+
+    /// Remove proofs from liquidity (e.g., after spending)
+    pub async fn remove_proofs(&self, mint_url: &str, proofs_to_remove: &Proofs) -> Result<()> {
+        let mut liq = self.liquidity.write().await;
+        let mint_liq = liq
+            .get_mut(mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+
+        // Remove proofs by secret (unique identifier). Only those still held
+        // count, so proofs already dropped as spent aren't taken off twice.
+        let secrets_to_remove: Vec<_> = proofs_to_remove.iter().map(|p| &p.secret).collect();
+        let (removed, kept): (Proofs, Proofs) = mint_liq
+            .proofs
+            .drain(..)
+            .partition(|p| secrets_to_remove.contains(&&p.secret));
+        mint_liq.proofs = kept;
+
+        let amount: u64 = removed.total_amount()
+            .map_err(|e| BrokerError::Cdk(format!("Failed to calculate total amount: {:?}", e)))?
+            .into();
+
+        mint_liq.balance = mint_liq.balance.saturating_sub(amount);
+        mint_liq.last_updated = SystemTime::now();
+
+        info!(
+            "💸 Removed {} sats from {} (new balance: {})",
+            amount, mint_url, mint_liq.balance
+        );
+        self.events.emit(BrokerEvent::BalanceChanged {
+            mint_url: mint_url.to_string(),
+            balance: mint_liq.balance,
+        });
+
+        Ok(())
+    }
+
+    /// Select proofs totaling at least the specified amount
+    ///
+    /// Uses the configured [`SelectionStrategy`] to keep the overshoot, and
+    /// with it the change, as small as possible.
+    pub async fn select_proofs(&self, mint_url: &str, amount: u64) -> Result<Proofs> {
+        let liq = self.liquidity.read().await;
+        let mint_liq = liq
+            .get(mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+
+        let amounts: Vec<u64> = mint_liq.proofs.iter().map(|p| u64::from(p.amount)).collect();
+        let selected = self
+            .selection_strategy()
+            .select(&amounts, amount)
+            .ok_or_else(|| BrokerError::InsufficientLiquidity {
+                mint_url: mint_url.to_string(),
+                needed: amount,
+                available: amounts.iter().sum(),
+            })?;
+
+        Ok(selected
+            .into_iter()
+            .map(|i| mint_liq.proofs[i].clone())
+            .collect())
+    }
+
+    /// Select proofs totaling at least `amount` and remove them from liquidity
+    ///
+    /// Selection and removal happen under one write lock, against the
+    /// balance neither reserved nor locked, so two concurrent callers can't
+    /// take the same proofs or eat into what quotes hold. Fails with
+    /// [`BrokerError::InsufficientLiquidity`] if the proofs selected, change
+    /// included, would come out of that.
+    pub async fn take_proofs(&self, mint_url: &str, amount: u64) -> Result<Proofs> {
         let mut liq = self.liquidity.write().await;
         let mint_liq = liq
             .get_mut(mint_url)
             .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
 
-        let amount: u64 = proofs.total_amount()
-            .map_err(|e| BrokerError::Cdk(format!("Failed to calculate total amount: {:?}", e)))?
-            .into();
-        mint_liq.proofs.extend(proofs);
-        mint_liq.balance += amount;
-        mint_liq.last_updated = SystemTime::now();
+        let available = mint_liq.available();
+        let insufficient = || BrokerError::InsufficientLiquidity {
+            mint_url: mint_url.to_string(),
+            needed: amount,
+            available,
+        };
+        if available < amount {
+            return Err(insufficient());
+        }
+
+        let amounts: Vec<u64> = mint_liq.proofs.iter().map(|p| u64::from(p.amount)).collect();
+        let selected = self
+            .selection_strategy()
+            .select(&amounts, amount)
+            .ok_or_else(insufficient)?;
+        let taken: u64 = selected.iter().map(|&i| amounts[i]).sum();
+        if taken > available {
+            return Err(insufficient());
+        }
+
+        let mut index = 0;
+        let (proofs, kept): (Proofs, Proofs) = mint_liq.proofs.drain(..).partition(|_| {
+            index += 1;
+            selected.contains(&(index - 1))
+        });
+        mint_liq.proofs = kept;
+        mint_liq.balance -= taken;
+        mint_liq.last_updated = SystemTime::now();
+
+        info!(
+            "💸 Took {} sats from {} (new balance: {})",
+            taken, mint_url, mint_liq.balance
+        );
+        self.events.emit(BrokerEvent::BalanceChanged {
+            mint_url: mint_url.to_string(),
+            balance: mint_liq.balance,
+        });
+
+        Ok(proofs)
+    }
+
+    /// Check with the mint (NUT-07) that none of `proofs` are spent or pending
+    pub async fn check_unspent(&self, mint_url: &str, proofs: &Proofs) -> Result<()> {
+        if proofs.is_empty() {
+            return Err(BrokerError::InvalidSwapRequest("No proofs given".to_string()));
+        }
+
+        let states = self.proof_states(mint_url, proofs).await?;
+        match unspendable_summary(&states) {
+            Some(summary) => Err(BrokerError::ProofsNotSpendable(summary)),
+            None => Ok(()),
+        }
+    }
+
+    /// Ask the mint (NUT-07) for the state of each of `proofs`, in order
+    pub async fn proof_states(&self, mint_url: &str, proofs: &Proofs) -> Result<Vec<State>> {
+        let states = self.proof_witnesses(mint_url, proofs).await?;
+        Ok(states.into_iter().map(|(state, _)| state).collect())
+    }
+
+    /// Like [`Self::proof_states`], with the witness each spent proof was
+    /// redeemed with, where the mint reports it
+    pub async fn proof_witnesses(
+        &self,
+        mint_url: &str,
+        proofs: &Proofs,
+    ) -> Result<Vec<(State, Option<String>)>> {
+        let wallet = self.get_wallet(mint_url)?;
+        let states = self
+            .mint_call(mint_url, MintOp::CheckState, "Failed to check proof states", || {
+                wallet.check_proofs_spent(proofs.clone())
+            })
+            .await?;
+
+        if states.len() != proofs.len() {
+            return Err(BrokerError::Cdk(format!(
+                "Mint returned {} proof states for {} proofs",
+                states.len(),
+                proofs.len()
+            )));
+        }
+
+        Ok(states.into_iter().map(|s| (s.state, s.witness)).collect())
+    }
+
+    /// A mint's keysets, fetched from the mint at most once an hour
+    pub async fn keysets(&self, mint_url: &str) -> Result<Vec<KeySetInfo>> {
+        match self.cached(mint_url, |cache| cache.keysets.as_ref()?.fresh()) {
+            Some(keysets) => Ok(keysets),
+            None => self.refresh_keysets(mint_url).await,
+        }
+    }
+
+    /// Fetch a mint's keysets, replacing any cached ones
+    pub async fn refresh_keysets(&self, mint_url: &str) -> Result<Vec<KeySetInfo>> {
+        let wallet = self.get_wallet(mint_url)?;
+        let keysets = self
+            .mint_call(mint_url, MintOp::Other, "Failed to get keysets", || {
+                wallet.get_mint_keysets()
+            })
+            .await?;
+
+        let mut cache = self.cache.write().expect("mint cache lock poisoned");
+        let entry = cache.entry(mint_url.to_string()).or_default();
+        let ids = |keysets: &[KeySetInfo]| keysets.iter().map(|k| k.id).collect::<Vec<_>>();
+        if let Some(previous) = &entry.keysets {
+            if ids(&previous.value) != ids(&keysets) {
+                info!("Keysets of {} changed", mint_url);
+            }
+        }
+        entry.keysets = Some(Cached::new(keysets.clone()));
+        debug!("Fetched {} keysets of {}", keysets.len(), mint_url);
+
+        Ok(keysets)
+    }
+
+    /// Drop a mint's cached keysets, so the next use fetches them again
+    pub fn invalidate_keysets(&self, mint_url: &str) {
+        if let Some(entry) = self
+            .cache
+            .write()
+            .expect("mint cache lock poisoned")
+            .get_mut(mint_url)
+        {
+            entry.keysets = None;
+        }
+    }
+
+    /// Split `proofs` by whether their DLEQ verifies against the mint's keys
+    ///
+    /// Proofs without a DLEQ fail while [`LiquidityManager::require_dleq`].
+    /// Keys are only fetched for keysets of proofs that carry a DLEQ; the
+    /// wallet keeps them once fetched.
+    async fn verify_dleq(&self, mint_url: &str, proofs: Proofs) -> (Proofs, Vec<(Proof, Failure)>) {
+        let mut ids: Vec<Id> = proofs
+            .iter()
+            .filter(|p| p.dleq.is_some())
+            .map(|p| p.keyset_id)
+            .collect();
+        ids.sort();
+        ids.dedup();
+
+        let mut keys = HashMap::new();
+        if !ids.is_empty() {
+            match self.get_wallet(mint_url) {
+                Ok(wallet) => {
+                    for id in ids {
+                        let loaded = self
+                            .mint_call(mint_url, MintOp::Other, "Failed to get keyset keys", || {
+                                wallet.load_keyset_keys(id)
+                            })
+                            .await;
+                        match loaded {
+                            Ok(loaded) => {
+                                keys.insert(id, loaded);
+                            }
+                            Err(e) => warn!("No keys of keyset {} on {}: {}", id, mint_url, e),
+                        }
+                    }
+                }
+                Err(e) => warn!("No wallet to check DLEQs on {}: {}", mint_url, e),
+            }
+        }
+
+        dleq::partition(proofs, &keys, self.require_dleq())
+    }
+
+    /// Take `proofs` out of circulation on a mint, for `reason`
+    ///
+    /// They must no longer be in its liquidity; see [`crate::quarantine`].
+    pub async fn quarantine(
+        &self,
+        mint_url: &str,
+        proofs: Proofs,
+        reason: QuarantineReason,
+        detail: &str,
+    ) -> Result<()> {
+        let mut liq = self.liquidity.write().await;
+        let mint_liq = liq
+            .get_mut(mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+        let entries = proofs
+            .into_iter()
+            .map(|proof| QuarantinedProof::new(proof, reason, detail))
+            .collect();
+        self.hold(mint_liq, entries);
+        Ok(())
+    }
+
+    /// Add entries to a mint's quarantine, announcing each reason once
+    fn hold(&self, mint_liq: &mut MintLiquidity, entries: Vec<QuarantinedProof>) {
+        let mut amounts: Vec<(QuarantineReason, u64)> = Vec::new();
+        for entry in entries {
+            warn!(
+                "Quarantining proof of {} on {} ({}): {}",
+                entry.amount(),
+                mint_liq.mint_url,
+                entry.reason,
+                entry.detail
+            );
+            match amounts.iter_mut().find(|(reason, _)| *reason == entry.reason) {
+                Some((_, amount)) => *amount += entry.amount(),
+                None => amounts.push((entry.reason, entry.amount())),
+            }
+            mint_liq.quarantine.push(entry);
+        }
+
+        for (reason, amount) in amounts {
+            self.events.emit(BrokerEvent::ProofsQuarantined {
+                mint_url: mint_liq.mint_url.clone(),
+                amount,
+                reason,
+            });
+        }
+    }
+
+    /// Put back proofs a failed melt or swap handed to the mint
+    ///
+    /// A call the circuit breaker stopped never reached the mint, so its
+    /// proofs go straight back to liquidity. Otherwise the mint may have spent
+    /// them, and they are quarantined until a retry asks it.
+    async fn put_back(
+        &self,
+        mint_url: &str,
+        proofs: Proofs,
+        reason: QuarantineReason,
+        error: &BrokerError,
+    ) -> Result<()> {
+        if matches!(error, BrokerError::MintUnavailable(_)) {
+            return self.add_proofs(mint_url, proofs).await;
+        }
+        self.quarantine(mint_url, proofs, reason, &error.to_string())
+            .await
+    }
+
+    /// Quarantined proofs on a mint
+    pub async fn quarantined(&self, mint_url: &str) -> Vec<QuarantinedProof> {
+        self.liquidity
+            .read()
+            .await
+            .get(mint_url)
+            .map(|l| l.quarantine.clone())
+            .unwrap_or_default()
+    }
+
+    /// Retry the quarantined proofs on a mint that `select` picks
+    ///
+    /// The mint is asked for their state (NUT-07). Spent proofs are dropped,
+    /// unspent ones whose DLEQ checks out go back to liquidity, and the rest
+    /// stay in quarantine.
+    pub async fn retry_quarantined(
+        &self,
+        mint_url: &str,
+        select: impl Fn(&QuarantinedProof) -> bool,
+    ) -> Result<QuarantineRetry> {
+        let picked = self.take_quarantined(mint_url, &select).await?;
+        if picked.is_empty() {
+            return Ok(QuarantineRetry::default());
+        }
+
+        let proofs: Proofs = picked.iter().map(|q| q.proof.clone()).collect();
+        let states = match self.proof_states(mint_url, &proofs).await {
+            Ok(states) => states,
+            Err(e) => {
+                self.return_quarantined(mint_url, picked).await?;
+                return Err(e);
+            }
+        };
+
+        let mut outcome = QuarantineRetry::default();
+        let mut kept = Vec::new();
+        let mut unspent = Vec::new();
+        for (entry, state) in picked.into_iter().zip(states) {
+            match Disposition::of(state) {
+                Disposition::Drop => outcome.dropped += entry.amount(),
+                Disposition::Keep => kept.push(entry),
+                Disposition::Check => unspent.push(entry),
+            }
+        }
+
+        let proofs = unspent.iter().map(|q| q.proof.clone()).collect();
+        let (valid, failed) = self.verify_dleq(mint_url, proofs).await;
+        for (proof, failure) in failed {
+            let mut entry = dleq_quarantine((proof, failure));
+            if let Some(previous) = unspent.iter().find(|q| q.proof.secret == entry.proof.secret) {
+                entry.since = previous.since;
+            }
+            kept.push(entry);
+        }
+        outcome.released = valid.iter().map(|p| u64::from(p.amount)).sum();
+        outcome.kept = kept.iter().map(QuarantinedProof::amount).sum();
+
+        let mut liq = self.liquidity.write().await;
+        let mint_liq = liq
+            .get_mut(mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+        mint_liq.quarantine.extend(kept);
+        if outcome.released > 0 {
+            mint_liq.proofs.extend(valid);
+            mint_liq.balance += outcome.released;
+            mint_liq.last_updated = SystemTime::now();
+            self.events.emit(BrokerEvent::BalanceChanged {
+                mint_url: mint_url.to_string(),
+                balance: mint_liq.balance,
+            });
+        }
+
+        info!(
+            "Quarantine retry on {}: {} released, {} dropped as spent, {} kept",
+            mint_url, outcome.released, outcome.dropped, outcome.kept
+        );
+        Ok(outcome)
+    }
+
+    /// Drop the quarantined proofs on a mint that `select` picks, returning their amount
+    pub async fn discard_quarantined(
+        &self,
+        mint_url: &str,
+        select: impl Fn(&QuarantinedProof) -> bool,
+    ) -> Result<u64> {
+        let discarded: u64 = self
+            .take_quarantined(mint_url, &select)
+            .await?
+            .iter()
+            .map(QuarantinedProof::amount)
+            .sum();
+        if discarded > 0 {
+            warn!("Discarded {} sats of quarantined proofs on {}", discarded, mint_url);
+        }
+        Ok(discarded)
+    }
+
+    async fn take_quarantined(
+        &self,
+        mint_url: &str,
+        select: &impl Fn(&QuarantinedProof) -> bool,
+    ) -> Result<Vec<QuarantinedProof>> {
+        let mut liq = self.liquidity.write().await;
+        let mint_liq = liq
+            .get_mut(mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+        let (picked, rest) = mint_liq.quarantine.drain(..).partition(|q| select(q));
+        mint_liq.quarantine = rest;
+        Ok(picked)
+    }
+
+    async fn return_quarantined(
+        &self,
+        mint_url: &str,
+        entries: Vec<QuarantinedProof>,
+    ) -> Result<()> {
+        self.liquidity
+            .write()
+            .await
+            .get_mut(mint_url)
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?
+            .quarantine
+            .extend(entries);
+        Ok(())
+    }
+
+    /// A mint's info (NUT-06), fetched from the mint at most once an hour
+    ///
+    /// Health checks refresh it as a side effect.
+    pub async fn mint_info(&self, mint_url: &str) -> Result<MintInfo> {
+        match self.cached(mint_url, |cache| cache.info.as_ref()?.fresh()) {
+            Some(info) => Ok(info),
+            None => self.refresh_mint_info(mint_url).await,
+        }
+    }
+
+    /// Fetch a mint's info, replacing any cached one
+    pub async fn refresh_mint_info(&self, mint_url: &str) -> Result<MintInfo> {
+        let wallet = self.get_wallet(mint_url)?;
+        let info = self
+            .mint_call(mint_url, MintOp::Other, "Failed to get mint info", || {
+                wallet.fetch_mint_info()
+            })
+            .await?
+            .ok_or_else(|| BrokerError::Cdk(format!("Mint {} returned no info", mint_url)))?;
+        self.cache_info(mint_url, info.clone());
+
+        Ok(info)
+    }
+
+    /// Fetch every mint's keysets and info again, before the cached ones go stale
+    pub async fn refresh_cache(&self) {
+        let mint_urls: Vec<String> = self
+            .wallets
+            .read()
+            .expect("wallets lock poisoned")
+            .keys()
+            .cloned()
+            .collect();
+
+        for mint_url in mint_urls {
+            if let Err(e) = self.refresh_keysets(&mint_url).await {
+                warn!("Failed to refresh keysets of {}: {}", mint_url, e);
+            }
+            if let Err(e) = self.refresh_mint_info(&mint_url).await {
+                warn!("Failed to refresh info of {}: {}", mint_url, e);
+            }
+        }
+    }
+
+    fn cache_info(&self, mint_url: &str, info: MintInfo) {
+        self.cache
+            .write()
+            .expect("mint cache lock poisoned")
+            .entry(mint_url.to_string())
+            .or_default()
+            .info = Some(Cached::new(info));
+    }
+
+    fn cached<T>(&self, mint_url: &str, f: impl FnOnce(&MintCache) -> Option<T>) -> Option<T> {
+        self.cache
+            .read()
+            .expect("mint cache lock poisoned")
+            .get(mint_url)
+            .and_then(f)
+    }
+
+    /// Proofs of a token from `mint_url`, resolving its keyset IDs (V4 tokens
+    /// carry short ones) against the mint's keysets
+    ///
+    /// A token from a keyset that isn't cached yet fetches the keysets again.
+    /// Proofs of another unit than the wallet's are refused, since a mint
+    /// issuing several units has a wallet per unit.
+    pub async fn token_proofs(&self, mint_url: &str, token: &Token) -> Result<Proofs> {
+        let mut keysets = self.keysets(mint_url).await?;
+        let proofs = match token.proofs(&keysets) {
+            Ok(proofs) => proofs,
+            Err(_) => {
+                keysets = self.refresh_keysets(mint_url).await?;
+                token.proofs(&keysets).map_err(|e| {
+                    BrokerError::Cdk(format!("Failed to extract proofs from token: {:?}", e))
+                })?
+            }
+        };
+
+        let unit = self.get_wallet(mint_url)?.unit.clone();
+        let other_unit = proofs.iter().any(|proof| {
+            keysets
+                .iter()
+                .any(|keyset| keyset.id == proof.keyset_id && keyset.unit != unit)
+        });
+        if other_unit {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Token is not in {} for {}",
+                unit, mint_url
+            )));
+        }
+        Ok(proofs)
+    }
+
+    /// Input fees of a mint's keysets
+    pub async fn keyset_fees(&self, mint_url: &str) -> Result<KeysetFees> {
+        let wallet = self.get_wallet(mint_url)?;
+        Ok(KeysetFees::new(&self.keysets(mint_url).await?, &wallet.unit))
+    }
+
+    /// Fee the mint charges to spend `proofs`
+    ///
+    /// Proofs from a keyset that isn't cached yet (e.g. after a rotation)
+    /// trigger a fresh fetch of the mint's keysets.
+    pub async fn proofs_fee(&self, mint_url: &str, proofs: &Proofs) -> Result<u64> {
+        if let Some(fee) = self.keyset_fees(mint_url).await?.proofs_fee(proofs) {
+            return Ok(fee);
+        }
+
+        let wallet = self.get_wallet(mint_url)?;
+        KeysetFees::new(&self.refresh_keysets(mint_url).await?, &wallet.unit)
+            .proofs_fee(proofs)
+            .ok_or_else(|| {
+                BrokerError::InvalidSwapRequest(format!(
+                    "Proofs from an unknown keyset of {}",
+                    mint_url
+                ))
+            })
+    }
+
+    /// Total value of `proofs` once the mint's input fee is taken off
+    ///
+    /// This is what a swap of exactly these proofs can ask for as outputs.
+    pub async fn amount_after_fees(&self, mint_url: &str, proofs: &Proofs) -> Result<u64> {
+        let total: u64 = proofs.iter().map(|p| u64::from(p.amount)).sum();
+        let fee = self.proofs_fee(mint_url, proofs).await?;
+        total.checked_sub(fee).filter(|&amount| amount > 0).ok_or_else(|| {
+            BrokerError::InvalidSwapRequest(format!(
+                "Proofs worth {} don't cover the {} input fee on {}",
+                total, fee, mint_url
+            ))
+        })
+    }
+
+    /// Check if we have enough unreserved liquidity for a swap
+    pub async fn can_swap(&self, mint_url: &str, amount: u64) -> bool {
+        self.get_available_balance(mint_url).await >= amount
+    }
+
+    /// Get wallet for a mint
+    pub fn get_wallet(&self, mint_url: &str) -> Result<Arc<Wallet>> {
+        self.wallets
+            .read()
+            .expect("wallets lock poisoned")
+            .get(mint_url)
+            .cloned()
+            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))
+    }
+
+    /// Ping every mint's info endpoint and record the outcome
+    pub async fn check_health(&self) {
+        let wallets: Vec<(String, Arc<Wallet>)> = self
+            .wallets
+            .read()
+            .expect("wallets lock poisoned")
+            .iter()
+            .map(|(url, wallet)| (url.clone(), wallet.clone()))
+            .collect();
+
+        // Check mints concurrently so one slow mint doesn't delay the rest
+        let mut checks = tokio::task::JoinSet::new();
+        for (mint_url, wallet) in wallets {
+            checks.spawn(async move {
+                let check = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, wallet.fetch_mint_info());
+                let result = match check.await {
+                    Ok(Ok(Some(info))) => Ok(info),
+                    Ok(Ok(None)) => Err("Mint returned no info".to_string()),
+                    Ok(Err(e)) => Err(format!("{:?}", e)),
+                    Err(_) => Err("Timed out".to_string()),
+                };
+                (mint_url, result)
+            });
+        }
+
+        while let Some(check) = checks.join_next().await {
+            if let Ok((mint_url, result)) = check {
+                if let Ok(info) = &result {
+                    self.cache_info(&mint_url, info.clone());
+                }
+                self.record_health_check(&mint_url, result.map(|_| ()));
+            }
+        }
+    }
+
+    /// Record the result of a health check
+    pub fn record_health_check(&self, mint_url: &str, result: std::result::Result<(), String>) {
+        let mut health = self.health.write().expect("health lock poisoned");
+        let entry = health.entry(mint_url.to_string()).or_default();
+        let was_healthy = entry.is_healthy();
+
+        entry.last_checked = Some(SystemTime::now());
+        match result {
+            Ok(()) => {
+                entry.consecutive_failures = 0;
+                entry.last_error = None;
+            }
+            Err(e) => {
+                entry.consecutive_failures += 1;
+                entry.last_error = Some(e);
+            }
+        }
+
+        let healthy = entry.is_healthy();
+        match (was_healthy, healthy) {
+            (true, false) => warn!(
+                "Mint {} is down after {} failed checks: {}",
+                mint_url,
+                entry.consecutive_failures,
+                entry.last_error.as_deref().unwrap_or_default()
+            ),
+            (false, true) => info!("Mint {} is back up", mint_url),
+            _ => return,
+        }
+
+        self.events.emit(BrokerEvent::MintHealthChanged {
+            mint_url: mint_url.to_string(),
+            healthy,
+        });
+    }
+
+    /// Whether a mint is passing health checks (mints not yet checked count as healthy)
+    pub fn is_healthy(&self, mint_url: &str) -> bool {
+        self.health
+            .read()
+            .expect("health lock poisoned")
+            .get(mint_url)
+            .map_or(true, MintHealth::is_healthy)
+    }
+
+    /// Fold the time a successful wallet call took into the mint's latency
+    fn record_latency(&self, mint_url: &str, elapsed: Duration) {
+        let mut health = self.health.write().expect("health lock poisoned");
+        let entry = health.entry(mint_url.to_string()).or_default();
+        entry.latency = Some(match entry.latency {
+            Some(average) => (average * (LATENCY_SMOOTHING - 1) + elapsed) / LATENCY_SMOOTHING,
+            None => elapsed,
+        });
+    }
+
+    /// Health of every checked mint
+    pub fn mint_health(&self) -> HashMap<String, MintHealth> {
+        self.health.read().expect("health lock poisoned").clone()
+    }
+
+    /// How wallet calls to mints are retried
+    pub fn retry_policy(&self) -> RetryPolicy {
+        *self.retry.read().expect("retry lock poisoned")
+    }
+
+    /// Change how wallet calls to mints are retried
+    pub fn set_retry_policy(&self, policy: RetryPolicy) {
+        *self.retry.write().expect("retry lock poisoned") = policy;
+    }
+
+    /// How proofs are picked for payments and withdrawals
+    pub fn selection_strategy(&self) -> SelectionStrategy {
+        *self.selection.read().expect("selection lock poisoned")
+    }
+
+    /// Change how proofs are picked for payments and withdrawals
+    pub fn set_selection_strategy(&self, strategy: SelectionStrategy) {
+        *self.selection.write().expect("selection lock poisoned") = strategy;
+    }
+
+    /// Whether received proofs without a DLEQ are quarantined (the default)
+    pub fn require_dleq(&self) -> bool {
+        *self.require_dleq.read().expect("require_dleq lock poisoned")
+    }
+
+    /// Count received proofs without a DLEQ as liquidity, for mints without NUT-12
+    pub fn set_require_dleq(&self, required: bool) {
+        *self.require_dleq.write().expect("require_dleq lock poisoned") = required;
+    }
+
+    /// Per-mint circuit breakers guarding wallet calls
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.circuits
+    }
+
+    /// How long each kind of wallet call to a mint may take
+    pub fn timeouts(&self) -> MintTimeouts {
+        *self.timeouts.read().expect("timeouts lock poisoned")
+    }
+
+    /// Change how long each kind of wallet call to a mint may take
+    pub fn set_timeouts(&self, timeouts: MintTimeouts) {
+        *self.timeouts.write().expect("timeouts lock poisoned") = timeouts;
+    }
+
+    /// Fault rules wallet calls are checked against
+    #[cfg(feature = "faults")]
+    pub fn faults(&self) -> FaultInjector {
+        self.faults.read().expect("faults lock poisoned").clone()
+    }
+
+    /// Inject the faults of `faults` into wallet calls, for tests and simulations
+    #[cfg(feature = "faults")]
+    pub fn set_faults(&self, faults: FaultInjector) {
+        *self.faults.write().expect("faults lock poisoned") = faults;
+    }
+
+    /// Run a wallet call against a mint, retrying transient failures
+    ///
+    /// Fails fast with [`BrokerError::MintUnavailable`] while the mint's
+    /// circuit is open. Each attempt is given up after the timeout of `op`;
+    /// if none answers in time the call fails with
+    /// [`BrokerError::MintTimeout`]. Other errors become [`BrokerError::Cdk`]
+    /// prefixed with `context`, e.g. "Failed to swap".
+    pub async fn mint_call<T, F, Fut>(
+        &self,
+        mint_url: &str,
+        op: MintOp,
+        context: &str,
+        f: F,
+    ) -> Result<T>
+    where
+        T: 'static,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<T, cdk::Error>>,
+    {
+        self.guarded(mint_url, op, context, self.retry_policy(), f).await
+    }
+
+    /// Like [`LiquidityManager::mint_call`], with a single attempt for calls that aren't safe to repeat
+    pub async fn mint_call_once<T, F, Fut>(
+        &self,
+        mint_url: &str,
+        op: MintOp,
+        context: &str,
+        f: F,
+    ) -> Result<T>
+    where
+        T: 'static,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<T, cdk::Error>>,
+    {
+        self.guarded(mint_url, op, context, RetryPolicy::none(), f).await
+    }
+
+    async fn guarded<T, F, Fut>(
+        &self,
+        mint_url: &str,
+        op: MintOp,
+        context: &str,
+        policy: RetryPolicy,
+        mut f: F,
+    ) -> Result<T>
+    where
+        T: 'static,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<T, cdk::Error>>,
+    {
+        if !self.circuits.allow(mint_url) {
+            return Err(BrokerError::MintUnavailable(mint_url.to_string()));
+        }
+
+        let context = format!("{} on {}", context, mint_url);
+        let timeout = self.timeouts().get(op);
+        #[cfg(feature = "faults")]
+        let faults = self.faults();
+        let retryable = |e: &CallError| match e {
+            CallError::TimedOut => true,
+            CallError::Mint(e) => retry::is_retryable(e),
+        };
+        let result = policy
+            .run_if(&context, retryable, || {
+                #[cfg(feature = "faults")]
+                let attempt = inject(faults.pick(mint_url, op), mint_url, op, f());
+                #[cfg(not(feature = "faults"))]
+                let attempt = f();
+                async move {
+                    let started = Instant::now();
+                    match tokio::time::timeout(timeout, attempt).await {
+                        Ok(Ok(value)) => {
+                            self.record_latency(mint_url, started.elapsed());
+                            Ok(value)
+                        }
+                        Ok(Err(e)) => Err(CallError::Mint(e)),
+                        Err(_) => Err(CallError::TimedOut),
+                    }
+                }
+            })
+            .await;
+
+        // Only errors that never got an answer count against the mint; a
+        // protocol error means it is up and talking
+        match &result {
+            Err(e) if retryable(e) => self.circuits.record_failure(mint_url),
+            _ => self.circuits.record_success(mint_url),
+        }
+        if let Err(CallError::Mint(e)) = &result {
+            if is_keyset_error(e) {
+                debug!("Dropping cached keysets of {} after {:?}", mint_url, e);
+                self.invalidate_keysets(mint_url);
+            }
+        }
+
+        result.map_err(|e| match e {
+            CallError::TimedOut => {
+                BrokerError::MintTimeout(format!("{} on {} after {:?}", op, mint_url, timeout))
+            }
+            CallError::Mint(e) => BrokerError::Cdk(format!("{}: {:?}", context, e)),
+        })
+    }
+
+    /// Get all liquidity info
+    pub async fn get_all_liquidity(&self) -> Vec<MintLiquidity> {
+        let liq = self.liquidity.read().await;
+        liq.values().cloned().collect()
+    }
+
+    /// Pay a BOLT11 invoice with proofs from a mint
+    ///
+    /// Proofs leave liquidity before the melt so no swap can select them
+    /// meanwhile. If the melt fails they are quarantined, unless it never
+    /// reached the mint. Change is added back.
+    pub async fn pay_invoice(&self, mint_url: &str, invoice: &str) -> Result<InvoicePayment> {
+        let wallet = self.get_wallet(mint_url)?;
+
+        let quote = self
+            .mint_call(mint_url, MintOp::Melt, "Failed to create melt quote", || {
+                wallet.melt_quote(invoice.trim().to_string(), None)
+            })
+            .await?;
+
+        let needed = u64::from(quote.amount) + u64::from(quote.fee_reserve);
+        let proofs = self.take_proofs(mint_url, needed).await?;
+        let selected: u64 = proofs.iter().map(|p| u64::from(p.amount)).sum();
+
+        // Not retried: the invoice may have been paid even if the response was lost
+        let melted = match self
+            .mint_call_once(mint_url, MintOp::Melt, "Failed to pay invoice", || {
+                wallet.melt_proofs(&quote.id, proofs.clone())
+            })
+            .await
+        {
+            Ok(melted) => melted,
+            Err(e) => {
+                warn!("Melt on {} failed: {}", mint_url, e);
+                self.put_back(mint_url, proofs, QuarantineReason::FailedMelt, &e)
+                    .await?;
+                return Err(e);
+            }
+        };
+
+        let change: u64 = melted
+            .change
+            .as_ref()
+            .map(|c| c.iter().map(|p| u64::from(p.amount)).sum())
+            .unwrap_or(0);
+        if let Some(change_proofs) = melted.change {
+            self.add_proofs(mint_url, change_proofs).await?;
+        }
+
+        Ok(InvoicePayment {
+            amount: u64::from(melted.amount),
+            fee_paid: u64::from(melted.fee_paid),
+            spent: selected.saturating_sub(change),
+            preimage: melted.preimage,
+        })
+    }
+
+    /// Swap a mint's small proofs for the fewest proofs of the same total
+    ///
+    /// Does nothing unless the mint holds more than `threshold` proofs. Only
+    /// proofs out of the balance neither reserved nor locked are swapped (see
+    /// [`LiquidityManager::take_surplus`]), so what quotes hold stays backed
+    /// during the swap and after its fee. The proofs leave liquidity for the
+    /// swap and are quarantined if it fails, unless it never reached the mint.
+    pub async fn consolidate(
+        &self,
+        mint_url: &str,
+        threshold: usize,
+    ) -> Result<Option<Consolidation>> {
+        if threshold == 0 || self.get_proofs(mint_url).await.len() <= threshold {
+            return Ok(None);
+        }
+
+        let wallet = self.get_wallet(mint_url)?;
+        let fees = self.keyset_fees(mint_url).await?;
+        let Some((proofs, fee)) = self.take_surplus(mint_url, threshold, &fees).await? else {
+            return Ok(None);
+        };
+        let total: u64 = proofs.iter().map(|p| u64::from(p.amount)).sum();
+        let amount = total - fee;
+
+        // Not retried: the mint may have swapped the proofs even if the response was lost
+        let swapped = match self
+            .mint_call_once(mint_url, MintOp::Swap, "Failed to consolidate proofs", || {
+                wallet.swap(
+                    Some(Amount::from(amount)),
+                    SplitTarget::default(),
+                    proofs.clone(),
+                    None,
+                    false,
+                )
+            })
+            .await
+        {
+            Ok(swapped) => swapped.unwrap_or_default(),
+            Err(e) => {
+                warn!("Consolidation on {} failed: {}", mint_url, e);
+                self.put_back(mint_url, proofs, QuarantineReason::FailedSwap, &e)
+                    .await?;
+                return Err(e);
+            }
+        };
+
+        let consolidation = Consolidation {
+            mint_url: mint_url.to_string(),
+            proofs_before: proofs.len(),
+            proofs_after: swapped.len(),
+            fee_paid: total - amount,
+        };
+        self.add_proofs(mint_url, swapped).await?;
 
         info!(
-            "💰 Added {} sats to {} (new balance: {})",
-            amount, mint_url, mint_liq.balance
+            "🧹 Consolidated {} proofs into {} on {} (fee {})",
+            consolidation.proofs_before,
+            consolidation.proofs_after,
+            mint_url,
+            consolidation.fee_paid
         );
 
-        Ok(())
+        Ok(Some(consolidation))
     }
 
-    /// Remove proofs from liquidity (e.g., after spending)
-    pub async fn remove_proofs(&self, mint_url: &str, proofs_to_remove: &Proofs) -> Result<()> {
+    /// Take the smallest proofs worth at most the mint's unheld balance, with
+    /// the input fee to swap them, to consolidate them
+    ///
+    /// Selection and removal happen under one write lock, like
+    /// [`LiquidityManager::take_proofs`], so a concurrent send can't take the
+    /// same proofs. `None` unless the mint holds more than `threshold` proofs
+    /// and at least two of them fit, worth more than their fee.
+    async fn take_surplus(
+        &self,
+        mint_url: &str,
+        threshold: usize,
+        fees: &KeysetFees,
+    ) -> Result<Option<(Proofs, u64)>> {
         let mut liq = self.liquidity.write().await;
         let mint_liq = liq
             .get_mut(mint_url)
             .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+        if mint_liq.proofs.len() <= threshold {
+            return Ok(None);
+        }
 
-        let amount: u64 = proofs_to_remove.total_amount()
-            .map_err(|e| BrokerError::Cdk(format!("Failed to calculate total amount: {:?}", e)))?
-            .into();
+        let available = mint_liq.available();
+        let mut order: Vec<usize> = (0..mint_liq.proofs.len())
+            .filter(|&i| fees.fee_ppk.contains_key(&mint_liq.proofs[i].keyset_id))
+            .collect();
+        order.sort_by_key(|&i| u64::from(mint_liq.proofs[i].amount));
+        let mut selected = Vec::new();
+        let mut taken = 0;
+        for i in order {
+            let amount = u64::from(mint_liq.proofs[i].amount);
+            if taken + amount > available {
+                break;
+            }
+            taken += amount;
+            selected.push(i);
+        }
 
-        // Remove proofs by secret (unique identifier)
-        let secrets_to_remove: Vec<_> = proofs_to_remove.iter().map(|p| &p.secret).collect();
-        mint_liq
-            .proofs
-            .retain(|p| !secrets_to_remove.contains(&&p.secret));
+        let candidates: Proofs = selected.iter().map(|&i| mint_liq.proofs[i].clone()).collect();
+        let fee = fees.proofs_fee(&candidates).unwrap_or(taken);
+        if selected.len() < 2 || fee >= taken {
+            return Ok(None);
+        }
 
-        mint_liq.balance = mint_liq.balance.saturating_sub(amount);
+        let mut index = 0;
+        let (proofs, kept): (Proofs, Proofs) = mint_liq.proofs.drain(..).partition(|_| {
+            index += 1;
+            selected.contains(&(index - 1))
+        });
+        mint_liq.proofs = kept;
+        mint_liq.balance -= taken;
         mint_liq.last_updated = SystemTime::now();
+        self.events.emit(BrokerEvent::BalanceChanged {
+            mint_url: mint_url.to_string(),
+            balance: mint_liq.balance,
+        });
 
-        info!(
-            "💸 Removed {} sats from {} (new balance: {})",
-            amount, mint_url, mint_liq.balance
-        );
+        Ok(Some((proofs, fee)))
+    }
 
-        Ok(())
+    /// Consolidate every mint holding more than `threshold` proofs
+    ///
+    /// Failures are logged and skipped.
+    pub async fn consolidate_all(&self, threshold: usize) -> Vec<Consolidation> {
+        let mint_urls: Vec<String> = self.liquidity.read().await.keys().cloned().collect();
+
+        let mut consolidations = Vec::new();
+        for mint_url in mint_urls {
+            match self.consolidate(&mint_url, threshold).await {
+                Ok(Some(consolidation)) => consolidations.push(consolidation),
+                Ok(None) => {}
+                Err(e) => warn!("Proof consolidation on {} failed: {}", mint_url, e),
+            }
+        }
+
+        consolidations
     }
 
-    /// Select proofs totaling at least the specified amount
-    pub async fn select_proofs(&self, mint_url: &str, amount: u64) -> Result<Proofs> {
-        let liq = self.liquidity.read().await;
-        let mint_liq = liq
-            .get(mint_url)
-            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))?;
+    /// Top up mints whose unreserved balance fell below `threshold`
+    ///
+    /// Each deficit mint is brought up to `target` (or `2 * threshold` when
+    /// `target` is 0) from the mint in the same unit with the largest
+    /// surplus, by minting on the deficit mint and paying the invoice from
+    /// the surplus mint. Failed transfers are logged and skipped.
+    pub async fn rebalance(&self, threshold: u64, target: u64) -> Vec<RebalanceTransfer> {
+        let target = if target == 0 {
+            threshold.saturating_mul(2)
+        } else {
+            target
+        };
 
-        let mut available = mint_liq.proofs.clone();
-        let mut selected: Proofs = vec![];
-        let mut total: u64 = 0;
+        let balances: Vec<(String, String, u64)> = {
+            let liq = self.liquidity.read().await;
+            liq.values()
+                .filter_map(|l| {
+                    let unit = self.get_wallet(&l.mint_url).ok()?.unit.to_string();
+                    Some((l.mint_url.clone(), unit, l.available()))
+                })
+                .collect()
+        };
 
-        // Simple greedy selection (largest first)
-        available.sort_by(|a, b| b.amount.cmp(&a.amount));
+        let mut transfers = Vec::new();
 
-        for proof in available.iter() {
-            if total >= amount {
-                break;
+        for (from_mint, to_mint, amount) in plan_rebalance(&balances, threshold, target) {
+            info!(
+                "⚖️  Rebalancing {} from {} to {}",
+                amount, from_mint, to_mint
+            );
+
+            match self.transfer(&from_mint, &to_mint, amount).await {
+                Ok(transfer) => transfers.push(transfer),
+                Err(e) => warn!(
+                    "Rebalance of {} sats from {} to {} failed: {}",
+                    amount, from_mint, to_mint, e
+                ),
             }
-            selected.push(proof.clone());
-            total += u64::from(proof.amount);
         }
 
-        if total < amount {
-            return Err(BrokerError::InsufficientLiquidity {
-                mint_url: mint_url.to_string(),
-                needed: amount,
-                available: total,
-            });
+        transfers
+    }
+
+    /// Move `amount` between mints over Lightning
+    async fn transfer(
+        &self,
+        from_mint: &str,
+        to_mint: &str,
+        amount: u64,
+    ) -> Result<RebalanceTransfer> {
+        let to_wallet = self.get_wallet(to_mint)?;
+
+        let mint_quote = self
+            .mint_call(to_mint, MintOp::MintQuote, "Failed to create mint quote", || {
+                to_wallet.mint_quote(Amount::from(amount), None)
+            })
+            .await?;
+
+        let payment = self.pay_invoice(from_mint, &mint_quote.request).await?;
+
+        // The invoice is paid, so the transfer goes through either way; what
+        // can't be minted now is left to mint later
+        let mut transfer = RebalanceTransfer {
+            from_mint: from_mint.to_string(),
+            to_mint: to_mint.to_string(),
+            amount: 0,
+            spent: payment.spent,
+            fee_paid: payment.fee_paid,
+            unminted: None,
+        };
+        match self
+            .mint_paid(to_mint, &mint_quote.id, REBALANCE_MINT_TIMEOUT)
+            .await
+        {
+            Ok(minted) => transfer.amount = minted,
+            Err(e) => {
+                warn!(
+                    "Paid mint quote {} on {} but couldn't mint it yet: {}",
+                    mint_quote.id, to_mint, e
+                );
+                transfer.unminted = Some(mint_quote);
+            }
         }
 
-        Ok(selected)
+        Ok(transfer)
     }
 
-    /// Check if we have enough liquidity for a swap
-    pub async fn can_swap(&self, mint_url: &str, amount: u64) -> bool {
-        self.get_balance(mint_url).await >= amount
+    /// Hand a mint quote to the mint's wallet, so a quote created by an
+    /// earlier wallet, e.g. before a restart, can be minted
+    ///
+    /// Wallets live in memory with a fresh seed, so after a restart they
+    /// know none of the quotes created before it.
+    pub async fn import_mint_quote(&self, mint_url: &str, quote: MintQuote) -> Result<()> {
+        let wallet = self.get_wallet(mint_url)?;
+        wallet
+            .localstore
+            .add_mint_quote(quote)
+            .await
+            .map_err(|e| BrokerError::Cdk(format!("Failed to import mint quote: {:?}", e)))
     }
 
-    /// Get wallet for a mint
-    pub fn get_wallet(&self, mint_url: &str) -> Result<Arc<Wallet>> {
-        self.wallets
-            .get(mint_url)
-            .cloned()
-            .ok_or_else(|| BrokerError::UnsupportedMint(mint_url.to_string()))
+    /// Mint a paid mint quote into liquidity, returning the amount minted
+    pub async fn mint_paid(
+        &self,
+        mint_url: &str,
+        quote_id: &str,
+        timeout: Duration,
+    ) -> Result<u64> {
+        let proofs = self
+            .mint_when_paid(mint_url, quote_id, timeout, "Failed to mint rebalanced tokens")
+            .await?;
+        let minted: u64 = proofs.iter().map(|p| u64::from(p.amount)).sum();
+        self.add_proofs(mint_url, proofs).await?;
+        Ok(minted)
     }
 
-    /// Get all liquidity info
-    pub async fn get_all_liquidity(&self) -> Vec<MintLiquidity> {
-        let liq = self.liquidity.read().await;
-        liq.values().cloned().collect()
+    /// Mint a quote as soon as the mint reports it paid, waiting at most `timeout`
+    pub async fn mint_when_paid(
+        &self,
+        mint_url: &str,
+        quote_id: &str,
+        timeout: Duration,
+        context: &str,
+    ) -> Result<Proofs> {
+        let wallet = self.get_wallet(mint_url)?;
+        subscriptions::wait_for_payment(&wallet, quote_id, timeout).await?;
+
+        self.mint_call(mint_url, MintOp::Mint, context, || {
+            wallet.mint(quote_id, SplitTarget::default(), None)
+        })
+        .await
+    }
+
+    /// Drop held proofs that the mint reports spent, watching until `until`
+    ///
+    /// Only the proofs held when the watch starts are subscribed to. Returns
+    /// how many proofs were dropped.
+    pub async fn watch_spent_proofs(&self, mint_url: &str, until: Instant) -> Result<usize> {
+        let wallet = self.get_wallet(mint_url)?;
+        let held = self.get_proofs(mint_url).await;
+        let ys = held
+            .iter()
+            .map(|p| p.y())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| BrokerError::Cdk(format!("Invalid proof: {:?}", e)))?;
+        if ys.is_empty() {
+            return Ok(0);
+        }
+
+        let mut spent = SpentProofs::subscribe(&wallet, &ys).await;
+        let mut dropped = 0;
+        while let Ok(Some(y)) = tokio::time::timeout_at(until.into(), spent.next()).await {
+            // The broker may have spent and removed it itself meanwhile
+            let current = self.get_proofs(mint_url).await;
+            let proofs: Proofs = held
+                .iter()
+                .zip(&ys)
+                .filter(|(_, proof_y)| **proof_y == y)
+                .map(|(proof, _)| proof)
+                .filter(|proof| current.iter().any(|p| p.secret == proof.secret))
+                .cloned()
+                .collect();
+            if proofs.is_empty() {
+                continue;
+            }
+
+            info!("Mint {} reports proof {} spent, dropping it", mint_url, y);
+            self.remove_proofs(mint_url, &proofs).await?;
+            dropped += proofs.len();
+        }
+
+        Ok(dropped)
+    }
+
+    /// Subscribe (NUT-17) to spends of the proofs with Ys `ys` on a mint,
+    /// whether the broker holds them or not
+    pub async fn subscribe_spends(&self, mint_url: &str, ys: &[PublicKey]) -> Result<SpentProofs> {
+        let wallet = self.get_wallet(mint_url)?;
+        Ok(SpentProofs::subscribe(&wallet, ys).await)
     }
 
     /// Initialize liquidity by minting tokens on each mint
@@ -202,7 +1730,15 @@ impl LiquidityManager {
             amount_per_mint
         );
 
-        for (mint_url, wallet) in &self.wallets {
+        let wallets: Vec<(String, Arc<Wallet>)> = self
+            .wallets
+            .read()
+            .expect("wallets lock poisoned")
+            .iter()
+            .map(|(url, wallet)| (url.clone(), wallet.clone()))
+            .collect();
+
+        for (mint_url, wallet) in &wallets {
             match self.mint_tokens(mint_url, wallet, amount_per_mint).await {
                 Ok(proofs) => {
                     self.add_proofs(mint_url, proofs).await?;
@@ -229,10 +1765,11 @@ impl LiquidityManager {
         info!("Minting {} sats on {}...", amount, mint_url);
 
         // Create a mint quote
-        let quote = wallet
-            .mint_quote(Amount::from(amount), None)
-            .await
-            .map_err(|e| BrokerError::Cdk(format!("Failed to create mint quote: {:?}", e)))?;
+        let quote = self
+            .mint_call(mint_url, MintOp::MintQuote, "Failed to create mint quote", || {
+                wallet.mint_quote(Amount::from(amount), None)
+            })
+            .await?;
 
         debug!("Mint quote created: {:?}", quote);
 
@@ -240,10 +1777,11 @@ impl LiquidityManager {
         // In production, you would pay the Lightning invoice: quote.request
 
         // Wait for quote to be paid and mint the tokens
-        let proofs = wallet
-            .mint(&quote.id, SplitTarget::default(), None)
-            .await
-            .map_err(|e| BrokerError::Cdk(format!("Failed to mint: {:?}", e)))?;
+        let proofs = self
+            .mint_call(mint_url, MintOp::Mint, "Failed to mint", || {
+                wallet.mint(&quote.id, SplitTarget::default(), None)
+            })
+            .await?;
 
         info!("✅ Minted {} sats", amount);
 
@@ -256,19 +1794,141 @@ impl LiquidityManager {
         println!("💰 Charlie's Liquidity:");
         for liq in &all_liq {
             println!(
-                "  {}: {} sats ({} proofs)",
+                "  {}: {} sats ({} proofs, {} reserved, {} locked, {} available)",
                 liq.mint_url,
                 liq.balance,
-                liq.proofs.len()
+                liq.proofs.len(),
+                liq.reserved(),
+                liq.locked(),
+                liq.available()
             );
         }
         println!();
     }
 }
 
+/// Whether a wallet error means the mint's keysets have changed under us
+fn is_keyset_error(error: &cdk::Error) -> bool {
+    matches!(
+        error,
+        cdk::Error::UnknownKeySet | cdk::Error::InactiveKeyset | cdk::Error::CouldNotVerifyDleq
+    )
+}
+
+/// Describe the proofs a mint reported as spent or pending, if any
+fn unspendable_summary(states: &[State]) -> Option<String> {
+    let spent = states.iter().filter(|s| **s == State::Spent).count();
+    let pending = states
+        .iter()
+        .filter(|s| matches!(s, State::Pending | State::PendingSpent))
+        .count();
+
+    match (spent, pending) {
+        (0, 0) => None,
+        (spent, 0) => Some(format!("{} of {} proofs already spent", spent, states.len())),
+        (0, pending) => Some(format!(
+            "{} of {} proofs pending in another transaction",
+            pending,
+            states.len()
+        )),
+        (spent, pending) => Some(format!(
+            "{} of {} proofs already spent, {} pending",
+            spent,
+            states.len(),
+            pending
+        )),
+    }
+}
+
+/// Plan transfers `(from, to, amount)` that top deficit mints up to `target`
+///
+/// `balances` are `(mint, unit, available)`. Transfers only run between
+/// mints in the same unit, as the amount is minted in the one and paid out
+/// of the other. Donors never drop below `target` themselves, so a transfer
+/// can be smaller than the deficit, or skipped when no mint has a surplus.
+fn plan_rebalance(
+    balances: &[(String, String, u64)],
+    threshold: u64,
+    target: u64,
+) -> Vec<(String, String, u64)> {
+    let mut balances = balances.to_vec();
+    let mut plan = Vec::new();
+
+    let deficits: Vec<usize> = (0..balances.len())
+        .filter(|&i| balances[i].2 < threshold)
+        .collect();
+
+    for to in deficits {
+        let needed = target.saturating_sub(balances[to].2);
+
+        let Some(from) = (0..balances.len())
+            .filter(|&i| i != to && balances[i].1 == balances[to].1 && balances[i].2 > target)
+            .max_by_key(|&i| balances[i].2)
+        else {
+            continue;
+        };
+
+        let amount = needed.min(balances[from].2 - target);
+        if amount == 0 {
+            continue;
+        }
+
+        balances[from].2 -= amount;
+        balances[to].2 += amount;
+        plan.push((balances[from].0.clone(), balances[to].0.clone(), amount));
+    }
+
+    plan
+}
+
+/// Quarantine entry for a proof that failed DLEQ verification
+fn dleq_quarantine((proof, failure): (Proof, Failure)) -> QuarantinedProof {
+    let reason = match failure {
+        Failure::NoKeys => QuarantineReason::KeysUnavailable,
+        Failure::Invalid(_) => QuarantineReason::InvalidDleq,
+        Failure::Missing => QuarantineReason::MissingDleq,
+    };
+    QuarantinedProof::new(proof, reason, failure.to_string())
+}
+
+/// Create an in-memory wallet in a mint's configured unit with a random seed
+///
+/// Wallets in further units of the same mint talk to the same URL.
+async fn create_wallet(mint: &MintConfig) -> Result<Wallet> {
+    let unit = CurrencyUnit::from_str(&mint.unit).map_err(|e| {
+        BrokerError::InvalidSwapRequest(format!("Invalid unit {}: {}", mint.unit, e))
+    })?;
+
+    // TODO: In production, use persistent storage instead of memory
+    let localstore = Arc::new(memory::empty().await
+        .map_err(|e| BrokerError::Cdk(format!("Failed to create memory store: {:?}", e)))?);
+
+    // Generate a random seed for the wallet
+    let mut seed = [0u8; 64];
+    for byte in seed.iter_mut() {
+        *byte = random();
+    }
+
+    Wallet::new(
+        mint.url(),
+        unit,
+        localstore,
+        seed,
+        None,
+    )
+    .map_err(|e| BrokerError::Cdk(format!("Failed to create wallet: {:?}", e)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::circuit_breaker::CircuitBreakerConfig;
+    #[cfg(feature = "faults")]
+    use crate::faults::FaultRule;
+    use crate::testkit::MockMint;
+    use cdk::nuts::SecretKey;
+    use cdk::secret::Secret;
+    use std::str::FromStr;
 
     #[tokio::test]
     async fn test_liquidity_manager() {
@@ -291,4 +1951,747 @@ mod tests {
         assert_eq!(manager.get_balance("http://localhost:3338").await, 0);
         assert_eq!(manager.get_balance("http://localhost:3339").await, 0);
     }
+
+    #[tokio::test]
+    async fn test_reservations() {
+        let mint_url = "http://localhost:3338";
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+
+        // Fake a balance without touching the mint
+        manager.liquidity.write().await.get_mut(mint_url).unwrap().balance = 100;
+
+        manager.reserve(mint_url, "quote-1", 60).await.unwrap();
+        assert_eq!(manager.get_available_balance(mint_url).await, 40);
+        assert!(!manager.can_swap(mint_url, 50).await);
+        assert!(manager.reservation_covered("quote-1").await);
+
+        // Funds leaving the mint can leave a reservation uncovered
+        manager.liquidity.write().await.get_mut(mint_url).unwrap().balance = 50;
+        assert!(!manager.reservation_covered("quote-1").await);
+        manager.liquidity.write().await.get_mut(mint_url).unwrap().balance = 100;
+
+        // A second quote can't claim the reserved funds
+        assert!(manager.reserve(mint_url, "quote-2", 50).await.is_err());
+
+        assert_eq!(manager.release("quote-1").await, Some(60));
+        assert_eq!(manager.release("quote-1").await, None);
+        manager.reserve(mint_url, "quote-2", 50).await.unwrap();
+        assert_eq!(manager.get_available_balance(mint_url).await, 50);
+
+        // Accepting moves the hold from reserved to locked; it still isn't spendable
+        assert_eq!(manager.lock("quote-2").await, Some(50));
+        assert_eq!(manager.lock("quote-2").await, None);
+        let liq = manager.get_all_liquidity().await;
+        assert_eq!((liq[0].reserved(), liq[0].locked()), (0, 50));
+        assert_eq!(manager.get_available_balance(mint_url).await, 50);
+        assert!(manager.remove_mint(mint_url).await.is_err());
+
+        assert_eq!(manager.release("quote-2").await, Some(50));
+        assert_eq!(manager.get_available_balance(mint_url).await, 100);
+    }
+
+    #[tokio::test]
+    async fn test_take_proofs() {
+        let mint_url = "http://localhost:3338";
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+        let proof = |amount: u64| {
+            Proof::new(
+                Amount::from(amount),
+                Id::from_str("009a1f293253e41e").unwrap(),
+                Secret::generate(),
+                SecretKey::generate().public_key(),
+            )
+        };
+        {
+            let mut liq = manager.liquidity.write().await;
+            let mint_liq = liq.get_mut(mint_url).unwrap();
+            mint_liq.proofs = vec![proof(32), proof(16), proof(8), proof(4)];
+            mint_liq.balance = 60;
+        }
+        manager.reserve(mint_url, "quote-1", 20).await.unwrap();
+
+        // Taken proofs leave at once, so they can't be taken twice
+        let taken = manager.take_proofs(mint_url, 24).await.unwrap();
+        assert_eq!(taken.iter().map(|p| u64::from(p.amount)).sum::<u64>(), 24);
+        assert_eq!(manager.get_balance(mint_url).await, 36);
+        assert_eq!(manager.get_proofs(mint_url).await.len(), 2);
+
+        // The reservation keeps its share, change included
+        let result = manager.take_proofs(mint_url, 16).await;
+        assert!(matches!(
+            result,
+            Err(BrokerError::InsufficientLiquidity { available: 16, .. })
+        ));
+        assert!(manager.take_proofs(mint_url, 20).await.is_err());
+        assert_eq!(manager.get_balance(mint_url).await, 36);
+
+        manager.release("quote-1").await;
+        let taken = manager.take_proofs(mint_url, 20).await.unwrap();
+        assert_eq!(taken.len(), 1);
+        assert_eq!(manager.get_balance(mint_url).await, 4);
+    }
+
+    #[tokio::test]
+    async fn test_remove_proofs_once() {
+        let mint_url = "http://localhost:3338";
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+        let proof = |amount: u64| {
+            Proof::new(
+                Amount::from(amount),
+                Id::from_str("009a1f293253e41e").unwrap(),
+                Secret::generate(),
+                SecretKey::generate().public_key(),
+            )
+        };
+        let spent = vec![proof(8)];
+        manager.set_require_dleq(false);
+        manager.add_proofs(mint_url, vec![spent[0].clone(), proof(4)]).await.unwrap();
+
+        // Dropped as spent, then removed again by the spend that caused it
+        manager.remove_proofs(mint_url, &spent).await.unwrap();
+        manager.remove_proofs(mint_url, &spent).await.unwrap();
+        assert_eq!(manager.get_balance(mint_url).await, 4);
+        assert_eq!(manager.get_proofs(mint_url).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wallet_per_unit() {
+        let mint_url = "http://localhost:3338";
+        let usd = crate::types::mint_key(mint_url, "usd");
+        let mint = |mint_url: &str, unit: &str| MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: unit.to_string(),
+        };
+        let manager = LiquidityManager::new(vec![mint(mint_url, "sat"), mint(&usd, "usd")])
+            .await
+            .unwrap();
+
+        let sat_wallet = manager.get_wallet(mint_url).unwrap();
+        let usd_wallet = manager.get_wallet(&usd).unwrap();
+        assert_eq!(sat_wallet.unit, CurrencyUnit::Sat);
+        assert_eq!(usd_wallet.unit, CurrencyUnit::Usd);
+        assert_eq!(usd_wallet.mint_url, sat_wallet.mint_url);
+
+        // Balances are tracked per unit
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+        let proof = Proof::new(
+            Amount::from(100),
+            keyset_id,
+            Secret::generate(),
+            SecretKey::generate().public_key(),
+        );
+        manager.set_require_dleq(false);
+        manager.add_proofs(&usd, vec![proof]).await.unwrap();
+        assert_eq!(manager.get_balance(&usd).await, 100);
+        assert_eq!(manager.get_balance(mint_url).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_mint() {
+        let manager = LiquidityManager::new(vec![]).await.unwrap();
+        let mint = MintConfig {
+            mint_url: "http://localhost:3340".to_string(),
+            name: "Mint C".to_string(),
+            unit: "sat".to_string(),
+        };
+
+        manager.add_mint(&mint).await.unwrap();
+        assert!(manager.get_wallet(&mint.mint_url).is_ok());
+        assert!(manager.add_mint(&mint).await.is_err());
+
+        // Can't remove a mint that still holds funds
+        manager.liquidity.write().await.get_mut(&mint.mint_url).unwrap().balance = 10;
+        assert!(manager.remove_mint(&mint.mint_url).await.is_err());
+
+        manager.liquidity.write().await.get_mut(&mint.mint_url).unwrap().balance = 0;
+        manager.remove_mint(&mint.mint_url).await.unwrap();
+        assert!(manager.get_wallet(&mint.mint_url).is_err());
+    }
+
+    #[test]
+    fn test_plan_rebalance() {
+        let mint = |name: &str, unit: &str, available: u64| {
+            (name.to_string(), unit.to_string(), available)
+        };
+        let balances = vec![mint("a", "sat", 50), mint("b", "sat", 1_000), mint("c", "sat", 400)];
+
+        // a is below the threshold, b has the largest surplus over the target
+        let plan = plan_rebalance(&balances, 100, 300);
+        assert_eq!(plan, vec![("b".to_string(), "a".to_string(), 250)]);
+
+        // Donors never go below the target
+        let balances = vec![mint("a", "sat", 0), mint("b", "sat", 350)];
+        let plan = plan_rebalance(&balances, 100, 300);
+        assert_eq!(plan, vec![("b".to_string(), "a".to_string(), 50)]);
+
+        // Nothing to do when every mint is above the threshold
+        let balances = vec![mint("a", "sat", 200), mint("b", "sat", 350)];
+        assert!(plan_rebalance(&balances, 100, 300).is_empty());
+
+        // Mints only top up others in their own unit
+        let balances = vec![
+            mint("a", "sat", 0),
+            mint("b#usd", "usd", 5_000),
+            mint("c", "sat", 400),
+            mint("d#usd", "usd", 10),
+        ];
+        let plan = plan_rebalance(&balances, 100, 300);
+        assert_eq!(
+            plan,
+            vec![
+                ("c".to_string(), "a".to_string(), 100),
+                ("b#usd".to_string(), "d#usd".to_string(), 290),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mint_health() {
+        let mint_url = "http://localhost:3338";
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+
+        assert!(manager.is_healthy(mint_url));
+
+        for _ in 0..UNHEALTHY_AFTER_FAILURES - 1 {
+            manager.record_health_check(mint_url, Err("connection refused".to_string()));
+        }
+        assert!(manager.is_healthy(mint_url));
+
+        manager.record_health_check(mint_url, Err("connection refused".to_string()));
+        assert!(!manager.is_healthy(mint_url));
+        assert_eq!(
+            manager.mint_health()[mint_url].last_error.as_deref(),
+            Some("connection refused")
+        );
+
+        // One good check brings it back
+        let mut events = manager.events.subscribe();
+        manager.record_health_check(mint_url, Ok(()));
+        assert!(manager.is_healthy(mint_url));
+        assert_eq!(manager.mint_health()[mint_url].consecutive_failures, 0);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            BrokerEvent::MintHealthChanged {
+                mint_url: mint_url.to_string(),
+                healthy: true,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_latency_average() {
+        let mint_url = "http://localhost:3338";
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+
+        manager.record_latency(mint_url, Duration::from_millis(100));
+        assert_eq!(
+            manager.mint_health()[mint_url].latency,
+            Some(Duration::from_millis(100))
+        );
+
+        manager.record_latency(mint_url, Duration::from_millis(200));
+        let latency = manager.mint_health()[mint_url].latency.unwrap();
+        assert_eq!(latency, Duration::from_millis(120));
+
+        // Health checks keep the average
+        manager.record_health_check(mint_url, Ok(()));
+        assert_eq!(manager.mint_health()[mint_url].latency, Some(latency));
+    }
+
+    #[tokio::test]
+    async fn test_open_circuit_fails_fast() {
+        let mint_url = "http://localhost:3338";
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+        manager.circuit_breaker().set_config(CircuitBreakerConfig {
+            failure_threshold: 1,
+            cool_down: Duration::from_secs(60),
+        });
+
+        let result: Result<()> = manager
+            .mint_call_once(mint_url, MintOp::Other, "Failed to ping", || async {
+                Err(cdk::Error::HttpError(None, "connection refused".to_string()))
+            })
+            .await;
+        assert!(matches!(result, Err(BrokerError::Cdk(_))));
+
+        // The mint isn't called again until the cool-down has passed
+        let mut called = false;
+        let result: Result<()> = manager
+            .mint_call(mint_url, MintOp::Other, "Failed to ping", || {
+                called = true;
+                async { Ok(()) }
+            })
+            .await;
+        assert!(matches!(result, Err(BrokerError::MintUnavailable(_))));
+        assert!(!called);
+    }
+
+    #[tokio::test]
+    async fn test_hung_call_times_out() {
+        let mint_url = "http://localhost:3338";
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+        manager.set_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            jitter: 0.0,
+        });
+        manager.set_timeouts(MintTimeouts {
+            swap: Duration::from_millis(20),
+            ..Default::default()
+        });
+
+        // Each attempt is given up and retried, then the call fails as a timeout
+        let mut attempts = 0;
+        let result: Result<()> = manager
+            .mint_call(mint_url, MintOp::Swap, "Failed to swap", || {
+                attempts += 1;
+                async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    Ok(())
+                }
+            })
+            .await;
+        assert!(matches!(result, Err(BrokerError::MintTimeout(_))));
+        assert_eq!(attempts, 2);
+
+        // Other operations keep their own timeout
+        let result: Result<()> = manager
+            .mint_call(mint_url, MintOp::CheckState, "Failed to check", || async {
+                tokio::time::sleep(Duration::from_millis(40)).await;
+                Ok(())
+            })
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[cfg(feature = "faults")]
+    #[tokio::test]
+    async fn test_injected_faults() {
+        let mint_url = "http://localhost:3338";
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+        manager.set_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            jitter: 0.0,
+        });
+        manager.set_timeouts(MintTimeouts {
+            swap: Duration::from_millis(20),
+            ..Default::default()
+        });
+        let faults = FaultInjector::new();
+        manager.set_faults(faults.clone());
+
+        // A failed attempt never reaches the mint and is retried
+        faults.add(FaultRule::new(Fault::Fail).op(MintOp::Swap).times(1));
+        let mut calls = 0;
+        let result: Result<()> = manager
+            .mint_call(mint_url, MintOp::Swap, "Failed to swap", || {
+                calls += 1;
+                async { Ok(()) }
+            })
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(calls, 2);
+
+        // A lost response means the mint did the work
+        faults.add(FaultRule::new(Fault::LostResponse).times(1));
+        let mut calls = 0;
+        let result: Result<()> = manager
+            .mint_call_once(mint_url, MintOp::Swap, "Failed to swap", || {
+                calls += 1;
+                async { Ok(()) }
+            })
+            .await;
+        assert!(matches!(result, Err(BrokerError::Cdk(_))));
+        assert_eq!(calls, 1);
+
+        // Rejections aren't retried
+        faults.add(FaultRule::new(Fault::Reject).mint(mint_url).times(1));
+        let result: Result<()> = manager
+            .mint_call(mint_url, MintOp::Mint, "Failed to mint", || async { Ok(()) })
+            .await;
+        assert!(matches!(result, Err(BrokerError::Cdk(_))));
+
+        // A delay past the timeout of the operation times it out
+        faults.add(FaultRule::new(Fault::Delay(Duration::from_millis(50))).times(2));
+        let result: Result<()> = manager
+            .mint_call(mint_url, MintOp::Swap, "Failed to swap", || async { Ok(()) })
+            .await;
+        assert!(matches!(result, Err(BrokerError::MintTimeout(_))));
+
+        // Partial results go through with their last proof dropped
+        faults.add(FaultRule::new(Fault::Partial).op(MintOp::Mint).times(1));
+        let proof = || {
+            Proof::new(
+                Amount::from(8),
+                Id::from_str("009a1f293253e41e").unwrap(),
+                Secret::generate(),
+                SecretKey::generate().public_key(),
+            )
+        };
+        let minted = vec![proof(), proof(), proof()];
+        let result: Result<Proofs> = manager
+            .mint_call(mint_url, MintOp::Mint, "Failed to mint", || {
+                let minted = minted.clone();
+                async move { Ok(minted) }
+            })
+            .await;
+        assert_eq!(result.unwrap(), minted[..2]);
+        assert_eq!(faults.injected(), 6);
+
+        faults.clear();
+        let result: Result<()> = manager
+            .mint_call(mint_url, MintOp::Swap, "Failed to swap", || async { Ok(()) })
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bad_dleq_is_quarantined() {
+        let mint = MockMint::start().await.unwrap();
+        let mint_url = mint.url().to_string();
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.clone(),
+            name: "Mock".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+
+        let keyset_id = manager.keysets(&mint_url).await.unwrap()[0].id;
+        let proof = |amount: u64| {
+            Proof::new(
+                Amount::from(amount),
+                keyset_id,
+                Secret::generate(),
+                SecretKey::generate().public_key(),
+            )
+        };
+        let mut forged = proof(8);
+        forged.dleq = Some(cdk::nuts::ProofDleq::new(
+            SecretKey::generate(),
+            SecretKey::generate(),
+            SecretKey::generate(),
+        ));
+
+        // Proofs without a DLEQ can't be vouched for either
+        manager.add_proofs(&mint_url, vec![proof(2)]).await.unwrap();
+        assert_eq!(manager.get_balance(&mint_url).await, 0);
+        let quarantined = manager.quarantined(&mint_url).await;
+        assert_eq!(quarantined[0].reason, QuarantineReason::MissingDleq);
+
+        // Unless DLEQs are optional, when a retry releases them
+        manager.set_require_dleq(false);
+        let outcome = manager.retry_quarantined(&mint_url, |_| true).await.unwrap();
+        assert_eq!(outcome.released, 2);
+
+        // Proofs without a DLEQ then count, the forged one is quarantined
+        manager.add_proofs(&mint_url, vec![forged.clone(), proof(4)]).await.unwrap();
+        assert_eq!(manager.get_balance(&mint_url).await, 6);
+        let quarantined = manager.quarantined(&mint_url).await;
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].proof, forged);
+        assert_eq!(quarantined[0].reason, QuarantineReason::InvalidDleq);
+
+        // And stays there on a second look, though the mint says it's unspent
+        let outcome = manager.retry_quarantined(&mint_url, |_| true).await.unwrap();
+        assert_eq!(outcome.kept, 8);
+        assert_eq!(manager.quarantined(&mint_url).await.len(), 1);
+        assert_eq!(manager.get_balance(&mint_url).await, 6);
+    }
+
+    #[tokio::test]
+    async fn test_failed_swap_is_quarantined() {
+        let mint = MockMint::start().await.unwrap();
+        let mint_url = mint.url().to_string();
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.clone(),
+            name: "Mock".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+
+        let keyset_id = manager.keysets(&mint_url).await.unwrap()[0].id;
+        let proofs: Proofs = (0..3)
+            .map(|_| {
+                Proof::new(
+                    Amount::from(2),
+                    keyset_id,
+                    Secret::generate(),
+                    SecretKey::generate().public_key(),
+                )
+            })
+            .collect();
+        manager.set_require_dleq(false);
+        manager.add_proofs(&mint_url, proofs).await.unwrap();
+
+        // The mint refuses the unsigned proofs; for all the broker knows it
+        // could have spent them
+        assert!(manager.consolidate(&mint_url, 1).await.is_err());
+        assert_eq!(manager.get_balance(&mint_url).await, 0);
+        let quarantined = manager.quarantined(&mint_url).await;
+        assert_eq!(quarantined.len(), 3);
+        assert!(quarantined
+            .iter()
+            .all(|q| q.reason == QuarantineReason::FailedSwap));
+
+        // It reports them unspent, so a retry releases the one picked
+        let y = quarantined[0].y();
+        let outcome = manager
+            .retry_quarantined(&mint_url, |q| q.y() == y)
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            QuarantineRetry {
+                released: 2,
+                dropped: 0,
+                kept: 0
+            }
+        );
+        assert_eq!(manager.get_balance(&mint_url).await, 2);
+
+        assert_eq!(manager.discard_quarantined(&mint_url, |_| true).await.unwrap(), 4);
+        assert!(manager.quarantined(&mint_url).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mint_quote_survives_restart() {
+        let mint = MockMint::start().await.unwrap();
+        let mint_url = mint.url().to_string();
+        let config = vec![MintConfig {
+            mint_url: mint_url.clone(),
+            name: "Mock".to_string(),
+            unit: "sat".to_string(),
+        }];
+        let before = LiquidityManager::new(config.clone()).await.unwrap();
+        let quote = before
+            .get_wallet(&mint_url)
+            .unwrap()
+            .mint_quote(Amount::from(16), None)
+            .await
+            .unwrap();
+
+        // A fresh wallet only knows the quote once it is handed back
+        let after = LiquidityManager::new(config).await.unwrap();
+        after.import_mint_quote(&mint_url, quote.clone()).await.unwrap();
+        let minted = after.mint_paid(&mint_url, &quote.id, Duration::from_secs(5)).await;
+        assert_eq!(minted.unwrap(), 16);
+        assert_eq!(after.get_balance(&mint_url).await, 16);
+    }
+
+    #[tokio::test]
+    async fn test_keyset_and_info_cache() {
+        let mint = MockMint::start().await.unwrap();
+        let mint_url = mint.url().to_string();
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.clone(),
+            name: "Mock".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+
+        let keysets = manager.keysets(&mint_url).await.unwrap();
+        assert_eq!(keysets.len(), 1);
+        manager.mint_info(&mint_url).await.unwrap();
+
+        // Once the mint is gone, cached values are still served
+        drop(mint);
+        assert_eq!(manager.keysets(&mint_url).await.unwrap(), keysets);
+        manager.mint_info(&mint_url).await.unwrap();
+
+        // Until they are invalidated
+        manager.invalidate_keysets(&mint_url);
+        assert!(manager.keysets(&mint_url).await.is_err());
+        manager.mint_info(&mint_url).await.unwrap();
+    }
+
+    #[test]
+    fn test_input_fees() {
+        // 13 = 8 + 4 + 1: three proofs
+        assert_eq!(input_fee(13, 0), 0);
+        assert_eq!(input_fee(13, 100), 1);
+        assert_eq!(input_fee(13, 400), 2);
+        assert_eq!(input_fee(64, 1000), 1);
+
+        let active = Id::from_str("009a1f293253e41e").unwrap();
+        let old = Id::from_str("00ad268c4d1f5826").unwrap();
+        let keyset = |id: Id, active: bool, input_fee_ppk: u64| -> KeySetInfo {
+            serde_json::from_value(serde_json::json!({
+                "id": id.to_string(),
+                "unit": "sat",
+                "active": active,
+                "input_fee_ppk": input_fee_ppk,
+            }))
+            .unwrap()
+        };
+        let fees = KeysetFees::new(
+            &[keyset(active, true, 200), keyset(old, false, 500)],
+            &CurrencyUnit::Sat,
+        );
+        assert_eq!(fees.active_fee_ppk, 200);
+
+        let proof = |keyset_id: Id| {
+            Proof::new(
+                Amount::from(1),
+                keyset_id,
+                Secret::generate(),
+                SecretKey::generate().public_key(),
+            )
+        };
+        // 200 + 200 + 500 ppk rounds up to 1
+        assert_eq!(fees.proofs_fee(&vec![proof(active), proof(active), proof(old)]), Some(1));
+        assert_eq!(fees.proofs_fee(&vec![proof(active); 6]), Some(2));
+        assert_eq!(fees.proofs_fee(&vec![]), Some(0));
+
+        let unknown = Id::from_str("00ffffffffffffff").unwrap();
+        assert_eq!(fees.proofs_fee(&vec![proof(unknown)]), None);
+
+        // The same keysets seen from the mint's wallet in another unit
+        let usd = KeysetFees::new(&[keyset(active, true, 200)], &CurrencyUnit::Usd);
+        assert_eq!(usd.proofs_fee(&vec![proof(active)]), None);
+    }
+
+    #[tokio::test]
+    async fn test_consolidation_threshold() {
+        let mint_url = "http://localhost:3338";
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.to_string(),
+            name: "Mint A".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+
+        let keyset_id = Id::from_str("009a1f293253e41e").unwrap();
+        let proofs: Proofs = (0..4)
+            .map(|_| {
+                Proof::new(
+                    Amount::from(1),
+                    keyset_id,
+                    Secret::generate(),
+                    SecretKey::generate().public_key(),
+                )
+            })
+            .collect();
+        manager.set_require_dleq(false);
+        manager.add_proofs(mint_url, proofs).await.unwrap();
+
+        // At or below the threshold (or with it off) the mint isn't contacted
+        assert!(manager.consolidate(mint_url, 4).await.unwrap().is_none());
+        assert!(manager.consolidate(mint_url, 0).await.unwrap().is_none());
+        assert!(manager.consolidate_all(10).await.is_empty());
+        assert_eq!(manager.get_proofs(mint_url).await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_consolidation_keeps_holds_backed() {
+        let mint = MockMint::start().await.unwrap();
+        let mint_url = mint.url().to_string();
+        let manager = LiquidityManager::new(vec![MintConfig {
+            mint_url: mint_url.clone(),
+            name: "Mock".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+        let wallet = manager.get_wallet(&mint_url).unwrap();
+        for amount in [3, 3, 8] {
+            let quote = wallet.mint_quote(Amount::from(amount), None).await.unwrap();
+            let timeout = Duration::from_secs(5);
+            manager.mint_paid(&mint_url, &quote.id, timeout).await.unwrap();
+        }
+        assert_eq!(manager.get_proofs(&mint_url).await.len(), 5);
+
+        // The 8 a quote holds stays put; the 2+1+2+1 around it are swapped
+        manager.reserve(&mint_url, "quote-1", 8).await.unwrap();
+        let consolidation = manager.consolidate(&mint_url, 2).await.unwrap().unwrap();
+        assert_eq!(consolidation.proofs_before, 4);
+        assert_eq!(consolidation.proofs_after, 2);
+
+        let mut amounts: Vec<u64> = manager
+            .get_proofs(&mint_url)
+            .await
+            .iter()
+            .map(|p| u64::from(p.amount))
+            .collect();
+        amounts.sort();
+        assert_eq!(amounts, [2, 4, 8]);
+        assert_eq!(manager.get_balance(&mint_url).await, 14);
+        assert_eq!(manager.get_available_balance(&mint_url).await, 6);
+
+        // Nothing left over to swap once the holds cover the rest
+        manager.reserve(&mint_url, "quote-2", 6).await.unwrap();
+        assert!(manager.consolidate(&mint_url, 2).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unspendable_summary() {
+        assert_eq!(unspendable_summary(&[State::Unspent, State::Unspent]), None);
+        assert_eq!(
+            unspendable_summary(&[State::Unspent, State::Spent]).as_deref(),
+            Some("1 of 2 proofs already spent")
+        );
+        assert_eq!(
+            unspendable_summary(&[State::Pending, State::Unspent]).as_deref(),
+            Some("1 of 2 proofs pending in another transaction")
+        );
+        assert_eq!(
+            unspendable_summary(&[State::Spent, State::PendingSpent, State::Spent]).as_deref(),
+            Some("2 of 3 proofs already spent, 1 pending")
+        );
+    }
 }
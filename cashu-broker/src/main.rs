@@ -1,6 +1,11 @@
+use cashu_broker::bootstrap::bootstrap;
+use cashu_broker::idempotency;
+use cashu_broker::retention::Pruner;
+use cashu_broker::webhooks::WebhookDispatcher;
 use cashu_broker::{api, AppState, Broker, Config, Database};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{error, info};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
 #[tokio::main]
@@ -18,51 +23,217 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Mints: {}", config.mints.len());
 
     // Initialize database
-    let db = Database::new(&config.database_url).await?;
+    let mut db = Database::connect(&config.database_url, config.database_options())
+        .await?
+        .with_cipher(config.secret_cipher()?);
+    if config.webhook_secret.is_some() {
+        db = db.with_webhooks();
+    }
     info!("Running database migrations...");
     db.migrate().await?;
+    let encrypted = db.encrypt_swap_secrets().await?;
+    if encrypted > 0 {
+        info!("Encrypted plaintext secrets of {} swaps", encrypted);
+    }
     info!("Database ready");
 
-    // Initialize broker
-    let broker_config = cashu_broker::types::BrokerConfig {
-        mints: config.mints.iter().map(|m| cashu_broker::MintConfig {
-            mint_url: m.mint_url.clone(),
-            name: m.name.clone(),
-            unit: m.unit.clone(),
-        }).collect(),
-        fee_rate: config.fee_rate,
-        min_swap_amount: config.min_swap_amount,
-        max_swap_amount: config.max_swap_amount,
-        quote_expiry_seconds: config.quote_expiry_seconds,
-    };
+    // Other instances may share the database: take leases as this one
+    let leases = config.leases(&db);
+    info!("Instance: {} (leases last {:?})", leases.holder(), leases.ttl());
+    let process = leases.claim_instance().await?;
+    tokio::spawn(async move { process.run().await });
+    tokio::spawn({
+        let leases = leases.clone();
+        async move { leases.run().await }
+    });
 
-    let broker = Broker::new(broker_config).await?;
+    // Initialize broker
+    let mut builder = Broker::builder(config.broker_config())
+        .database(db.clone())
+        .identity(config.broker_identity()?)
+        .retry_policy(config.retry_policy())
+        .selection_strategy(config.proof_selection)
+        .require_dleq(config.require_dleq)
+        .pricing_strategy(config.pricing_strategy())
+        .circuit_breaker(config.circuit_breaker())
+        .mint_timeouts(config.mint_timeouts())
+        .job_queue(config.job_queue())
+        .leases(leases.clone());
+    if let Some(price_feed) = config.price_feed()? {
+        info!("Price feed: {}", price_feed.name());
+        builder = builder.price_feed(price_feed);
+    }
+    let broker = Arc::new(builder.build().await?);
     info!("Broker initialized");
 
-    // Initialize broker liquidity
-    // TODO: Load initial liquidity from config or database
-    // For now, we'll start with empty liquidity and add it manually
+    // Settle swaps that an unclean shutdown left half recorded, then pick up
+    // the ones still in flight
+    broker.reconcile_quotes().await?;
+    broker.restore_quotes().await?;
+
+    // Fund mints up to their starting balance before taking requests
+    if config.initial_liquidity.is_empty() {
+        info!("INITIAL_LIQUIDITY not set, fund mints through the admin API");
+    } else {
+        bootstrap(&broker, &config.initial_liquidity).await;
+    }
     info!("Broker ready to accept requests");
 
+    // Run background tasks (quote expiry, refunds of unredeemed swap outputs, status)
+    tokio::spawn({
+        let broker = broker.clone();
+        async move {
+            if let Err(e) = broker.run().await {
+                error!("Broker background tasks stopped: {}", e);
+            }
+        }
+    });
+
+    // Send webhook callbacks for quote status changes
+    match &config.webhook_secret {
+        Some(secret) => {
+            let dispatcher = WebhookDispatcher::with_allowed_hosts(
+                db.clone(),
+                secret,
+                config.webhook_allowed_hosts.clone(),
+            )?
+            .with_leases(leases.clone());
+            tokio::spawn(async move { dispatcher.run().await });
+        }
+        None => info!("WEBHOOK_SECRET not set, webhooks disabled"),
+    }
+
+    // Alert the operator about low liquidity, failing swaps and mints going down
+    match config.alerter()? {
+        Some(alerter) => {
+            tokio::spawn(alerter.run(broker.subscribe()));
+        }
+        None => info!("No alert sinks configured, alerts disabled"),
+    }
+
+    // Prune settled quotes and old liquidity events past the retention period
+    match config.retention_policy() {
+        Some(policy) => {
+            let pruner = Pruner::new(db.clone(), policy).with_leases(leases.clone());
+            tokio::spawn(async move { pruner.run().await });
+        }
+        None => info!("RETENTION_DAYS is 0, keeping all records"),
+    }
+
+    // Forget Idempotency-Keys once retries with them are no longer expected
+    tokio::spawn(idempotency::expire_keys(db.clone(), leases.clone()));
+
+    // Checkpoint state and reload configuration on SIGHUP
+    #[cfg(unix)]
+    tokio::spawn(handle_sighup(broker.clone()));
+
+    // Announce rates to aggregators on Nostr
+    #[cfg(feature = "nostr")]
+    if let Some(secret_key) = &config.nostr_secret_key {
+        if config.nostr_publish_interval_seconds > 0 {
+            let publisher = cashu_broker::nostr::RatePublisher::new(
+                secret_key,
+                &config.nostr_publish_relays,
+                broker.clone(),
+                std::time::Duration::from_secs(config.nostr_publish_interval_seconds),
+            )
+            .await?;
+            tokio::spawn(async move { publisher.run().await });
+        }
+    }
+
     // Create app state
-    let state = AppState {
-        broker: Arc::new(broker),
-        db,
-    };
+    let state = AppState { broker, db };
+
+    // Answer swap requests sent as Nostr DMs
+    #[cfg(feature = "nostr")]
+    match &config.nostr_secret_key {
+        Some(secret_key) => {
+            let listener = cashu_broker::nostr::NostrListener::new(
+                secret_key,
+                &config.nostr_relays,
+                state.clone(),
+            )
+            .await?;
+            info!("Nostr listener key: {}", listener.public_key());
+            tokio::spawn(async move {
+                if let Err(e) = listener.run().await {
+                    error!("Nostr listener stopped: {}", e);
+                }
+            });
+        }
+        None => info!("NOSTR_SECRET_KEY not set, Nostr listener disabled"),
+    }
 
     // Create router
-    let app = api::create_router(state, config.cors_origins.clone());
+    if config.admin_token.is_none() {
+        info!("ADMIN_TOKEN not set, admin API disabled");
+    }
+    if config.rate_limit().is_none() {
+        info!("RATE_LIMIT_PER_MINUTE is 0, rate limiting disabled");
+    }
+    let app = api::create_router(
+        state,
+        config.cors_origins.clone(),
+        config.admin_token.clone(),
+        config.rate_limit(),
+        config.require_api_key,
+        config.client_history_auth,
+        config.request_limits(),
+    );
 
     // Start HTTP server
     let addr = config.server_address();
-    info!("Listening on http://{}", addr);
-
     let listener = tokio::net::TcpListener::bind(&addr).await?;
+    // Connection info gives the rate limiter each client's address
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    #[cfg(feature = "tls")]
+    if let (Some(cert), Some(key)) = (&config.tls_cert_path, &config.tls_key_path) {
+        let tls = cashu_broker::tls::load(cert.as_ref(), key.as_ref()).await?;
+        tokio::spawn(cashu_broker::tls::watch(tls.clone(), cert.into(), key.into()));
+
+        info!("Listening on https://{}", addr);
+        axum_server::from_tcp_rustls(listener.into_std()?, tls)
+            .serve(app)
+            .await?;
+        return Ok(());
+    }
+
+    info!("Listening on http://{}", addr);
     axum::serve(listener, app).await?;
 
     Ok(())
 }
 
+/// Flush broker state to the database and reload configuration on every SIGHUP
+#[cfg(unix)]
+async fn handle_sighup(broker: Arc<Broker>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!("Failed to install SIGHUP handler: {}", e);
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        info!("SIGHUP received, checkpointing state...");
+
+        if let Err(e) = broker.flush_state().await {
+            error!("State flush failed: {}", e);
+            continue;
+        }
+
+        match Config::reload() {
+            Ok(config) => broker.reload_config(config.broker_config()).await,
+            Err(e) => error!("Configuration reload failed, keeping current settings: {}", e),
+        }
+    }
+}
+
 fn init_logging(log_level: &str) -> Result<(), Box<dyn std::error::Error>> {
     let filter = EnvFilter::try_from_default_env()
         .or_else(|_| EnvFilter::try_new(log_level))
@@ -0,0 +1,330 @@
+//! Signed swap steps (NIP-98 HTTP auth)
+//!
+//! A quote requested with a `user_pubkey` belongs to whoever holds that key,
+//! as does a chunked swap. `POST /quote/:id/accept` and `/complete` on such a
+//! quote, and `POST /chunked/:id/next` and `/stop`, need an
+//! `Authorization: Nostr <base64 event>` header: a kind 27235 event signed by
+//! the same key, with the request's URL in a `u` tag, its method in a
+//! `method` tag and the SHA-256 of its body in a `payload` tag. The event must
+//! be at most a minute old. Only the path of the URL is compared, version
+//! prefix included, since the broker may sit behind a proxy that serves it
+//! under another host. Nostr keys are x-only, so the event's `pubkey` is
+//! matched against the x coordinate of `user_pubkey`. With
+//! `CLIENT_HISTORY_AUTH` on, the same goes for `GET /client/:pubkey/quotes`,
+//! signed by `pubkey`.
+
+use crate::api::{ApiError, AppState};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{OriginalUri, Path, Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use schnorr_fun::fun::{marker::*, Point, Scalar};
+use schnorr_fun::{Message, Schnorr, Signature};
+use secp256kfun::nonce;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Scheme of the `Authorization` header
+pub const AUTH_SCHEME: &str = "Nostr";
+
+/// Event kind of NIP-98 HTTP auth
+pub const HTTP_AUTH_KIND: u64 = 27235;
+
+/// How far an event's `created_at` may be from the broker's clock
+const MAX_CLOCK_SKEW_SECS: u64 = 60;
+
+/// Largest request body handled (proofs can be large)
+const MAX_BODY: usize = 1024 * 1024;
+
+/// Nostr event authorizing one HTTP request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthEvent {
+    pub id: String,     // SHA-256 of the serialized event, hex
+    pub pubkey: String, // x-only, hex
+    pub created_at: u64,
+    pub kind: u64,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String, // BIP-340 signature over the id, hex
+}
+
+impl AuthEvent {
+    /// Sign a request to `url` with `secret`
+    pub fn sign(secret: Scalar, method: &str, url: &str, body: &[u8], created_at: u64) -> Self {
+        let schnorr = Schnorr::<Sha256, nonce::Deterministic<Sha256>>::default();
+        let keypair = schnorr.new_keypair(secret);
+
+        let mut tags = vec![
+            vec!["u".to_string(), url.to_string()],
+            vec!["method".to_string(), method.to_uppercase()],
+        ];
+        if !body.is_empty() {
+            tags.push(vec![
+                "payload".to_string(),
+                hex::encode(Sha256::digest(body)),
+            ]);
+        }
+
+        let mut event = Self {
+            id: String::new(),
+            pubkey: hex::encode(keypair.public_key().to_xonly_bytes()),
+            created_at,
+            kind: HTTP_AUTH_KIND,
+            tags,
+            content: String::new(),
+            sig: String::new(),
+        };
+        let id = event.compute_id();
+        let signature = schnorr.sign(&keypair, Message::<Public>::raw(&id));
+        event.id = hex::encode(id);
+        event.sig = hex::encode(signature.to_bytes());
+        event
+    }
+
+    /// Value for the `Authorization` header
+    pub fn to_header(&self) -> String {
+        let json = serde_json::to_vec(self).expect("event serializes");
+        format!("{} {}", AUTH_SCHEME, BASE64.encode(json))
+    }
+
+    /// Parse an `Authorization` header
+    pub fn from_header(value: &str) -> Result<Self, String> {
+        let encoded = value
+            .strip_prefix(AUTH_SCHEME)
+            .and_then(|rest| rest.strip_prefix(' '))
+            .ok_or_else(|| format!("expected the {} scheme", AUTH_SCHEME))?;
+        let json = BASE64
+            .decode(encoded.trim())
+            .map_err(|e| format!("invalid base64: {}", e))?;
+        serde_json::from_slice(&json).map_err(|e| format!("invalid event: {}", e))
+    }
+
+    /// Check the event authorizes this request, returning its x-only signer
+    pub fn verify(
+        &self,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        now: u64,
+    ) -> Result<[u8; 32], String> {
+        if self.kind != HTTP_AUTH_KIND {
+            return Err(format!("event kind must be {}", HTTP_AUTH_KIND));
+        }
+        if self.created_at.abs_diff(now) > MAX_CLOCK_SKEW_SECS {
+            return Err("event is too old or in the future".to_string());
+        }
+
+        let url = self.tag("u").ok_or("missing u tag")?;
+        let url = reqwest::Url::parse(url).map_err(|e| format!("invalid u tag: {}", e))?;
+        if url.path() != path {
+            return Err(format!("event is for {}, not {}", url.path(), path));
+        }
+        if !self
+            .tag("method")
+            .is_some_and(|m| m.eq_ignore_ascii_case(method))
+        {
+            return Err(format!("event is not for a {} request", method));
+        }
+        if !body.is_empty() {
+            let payload = self.tag("payload").ok_or("missing payload tag")?;
+            if !payload.eq_ignore_ascii_case(&hex::encode(Sha256::digest(body))) {
+                return Err("payload tag does not match the body".to_string());
+            }
+        }
+
+        let id = self.compute_id();
+        if !self.id.eq_ignore_ascii_case(&hex::encode(id)) {
+            return Err("event id does not match its content".to_string());
+        }
+        let pubkey: [u8; 32] = hex::decode(&self.pubkey)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or("invalid pubkey")?;
+        let public_key = Point::<EvenY>::from_xonly_bytes(pubkey).ok_or("invalid pubkey")?;
+        let signature = hex::decode(&self.sig)
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+            .and_then(Signature::<Public>::from_bytes)
+            .ok_or("invalid signature")?;
+
+        let schnorr = Schnorr::<Sha256, nonce::Deterministic<Sha256>>::default();
+        if !schnorr.verify(&public_key, Message::<Public>::raw(&id), &signature) {
+            return Err("signature does not match the event".to_string());
+        }
+        Ok(pubkey)
+    }
+
+    /// First value of the tag named `name`
+    fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|tag| tag.first().is_some_and(|n| n == name))
+            .and_then(|tag| tag.get(1))
+            .map(String::as_str)
+    }
+
+    /// NIP-01 id: SHA-256 of `[0, pubkey, created_at, kind, tags, content]`
+    fn compute_id(&self) -> [u8; 32] {
+        let serialized = serde_json::json!([
+            0,
+            self.pubkey,
+            self.created_at,
+            self.kind,
+            self.tags,
+            self.content
+        ]);
+        Sha256::digest(serialized.to_string().as_bytes()).into()
+    }
+}
+
+/// x coordinate of a compressed (33 bytes) or x-only (32 bytes) hex key
+fn xonly(pubkey_hex: &str) -> Option<[u8; 32]> {
+    let bytes = hex::decode(pubkey_hex).ok()?;
+    match bytes.len() {
+        33 => bytes[1..].try_into().ok(),
+        _ => bytes.try_into().ok(),
+    }
+}
+
+/// Middleware letting only the owner of a quote accept, complete or extend it, and
+/// only the owner of a chunked swap continue or stop it
+///
+/// Quotes without a `user_pubkey`, and unknown IDs, are passed on to the
+/// handler unchecked.
+pub async fn require_quote_owner(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let owner = match state.db.get_quote(&id).await.map_err(ApiError::from)? {
+        Some(quote) => quote.user_pubkey,
+        None => state
+            .db
+            .get_chunked_swap(&id)
+            .await
+            .map_err(ApiError::from)?
+            .map(|swap| swap.user_pubkey),
+    };
+    let Some(owner) = owner else {
+        return Ok(next.run(req).await);
+    };
+
+    let req = authorize(req, &owner)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized(format!("Quote {} belongs to another key", id)))?;
+    Ok(next.run(req).await)
+}
+
+/// Middleware letting only the holder of the key in the path list its quotes
+pub async fn require_key_holder(
+    Path(pubkey): Path<String>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let req = authorize(req, &pubkey).await?.ok_or_else(|| {
+        ApiError::Unauthorized(format!("Request not signed by {}", pubkey))
+    })?;
+    Ok(next.run(req).await)
+}
+
+/// Check the request's NIP-98 authorization, returning the request if it
+/// was signed by `owner` and `None` if by another key
+async fn authorize(req: Request, owner: &str) -> Result<Option<Request>, ApiError> {
+    let event = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ApiError::Unauthorized("Missing Nostr authorization".to_string()))
+        .and_then(|value| {
+            AuthEvent::from_header(value)
+                .map_err(|e| ApiError::Unauthorized(format!("Invalid Nostr authorization: {}", e)))
+        })?;
+
+    let method = req.method().to_string();
+    // The full path, with the version prefix the router strips
+    let path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, MAX_BODY)
+        .await
+        .map_err(|_| ApiError::BadRequest("Request body too large".to_string()))?;
+
+    let signer = event
+        .verify(&method, &path, &body, crate::swap::unix_now())
+        .map_err(|e| ApiError::Unauthorized(format!("Invalid Nostr authorization: {}", e)))?;
+    if xonly(owner) != Some(signer) {
+        return Ok(None);
+    }
+
+    Ok(Some(Request::from_parts(parts, Body::from(body))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://broker.test/quote/q1/accept";
+    const PATH: &str = "/quote/q1/accept";
+    const NOW: u64 = 1_700_000_000;
+
+    fn signed(body: &[u8]) -> (AuthEvent, [u8; 32]) {
+        let secret = Scalar::random(&mut rand::thread_rng());
+        let event = AuthEvent::sign(secret, "post", URL, body, NOW);
+        let pubkey = xonly(&event.pubkey).unwrap();
+        (event, pubkey)
+    }
+
+    #[test]
+    fn test_verify() {
+        let body = br#"{"source_proofs":"[]"}"#;
+        let (event, pubkey) = signed(body);
+        assert_eq!(event.verify("POST", PATH, body, NOW + 30), Ok(pubkey));
+
+        // Through the header and back
+        let parsed = AuthEvent::from_header(&event.to_header()).unwrap();
+        assert_eq!(parsed, event);
+        assert!(AuthEvent::from_header("Bearer abc").is_err());
+
+        assert!(event.verify("POST", PATH, body, NOW + 61).is_err());
+        assert!(event.verify("GET", PATH, body, NOW).is_err());
+        assert!(event.verify("POST", "/quote/q2/accept", body, NOW).is_err());
+        assert!(event.verify("POST", PATH, b"{}", NOW).is_err());
+    }
+
+    #[test]
+    fn test_tampered_event() {
+        let (event, _) = signed(b"");
+
+        let mut other_key = event.clone();
+        other_key.pubkey = signed(b"").0.pubkey;
+        assert!(other_key.verify("POST", PATH, b"", NOW).is_err());
+
+        // Re-deriving the id doesn't help without the key
+        let mut retagged = event.clone();
+        retagged.tags[0][1] = "https://broker.test/quote/q2/accept".to_string();
+        retagged.id = hex::encode(retagged.compute_id());
+        assert!(retagged
+            .verify("POST", "/quote/q2/accept", b"", NOW)
+            .is_err());
+
+        let mut other_kind = event;
+        other_kind.kind = 1;
+        assert!(other_kind.verify("POST", PATH, b"", NOW).is_err());
+    }
+
+    #[test]
+    fn test_xonly() {
+        let key = "02a9acc1e48c25eeeb9289b5031cc57da9fe72f3fe2861d264bdc074209b107ba2";
+        assert_eq!(xonly(key), xonly(&key[2..]));
+        assert_eq!(xonly(key), xonly(&format!("03{}", &key[2..])));
+        assert_eq!(xonly("02abcd"), None);
+    }
+}
@@ -0,0 +1,389 @@
+//! Nostr transport for swap quote requests
+//!
+//! Clients can send a quote request as an encrypted direct message instead of
+//! calling `POST /quote`. Both NIP-17 (gift-wrapped) and legacy NIP-04 messages
+//! are accepted; the reply uses the same scheme as the request.
+//!
+//! The message content is the JSON body of `POST /quote` and the reply is the
+//! JSON body the HTTP API would have returned (`QuoteResponse` or `ErrorResponse`).
+//!
+//! The broker can also announce its rates for aggregators: [`RatePublisher`]
+//! periodically replaces a NIP-78 application data event (kind 30078, `d` tag
+//! `cashu-broker/rates`) holding a [`RateAnnouncement`].
+
+use crate::api::{self, ApiError, AppState, QuoteRequest, QuoteResponse};
+use crate::broker::Broker;
+use crate::error::{BrokerError, Result};
+use crate::types::SwapRequest;
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+/// NIP-59 gift wraps are backdated by up to two days
+const GIFT_WRAP_LOOKBACK_SECS: u64 = 2 * 24 * 60 * 60;
+
+/// `d` tag of the rates event, so each publish replaces the last one
+const RATES_IDENTIFIER: &str = "cashu-broker/rates";
+
+/// Connect to the relays with the broker's Nostr key (hex or nsec)
+async fn connect(secret_key: &str, relays: &[String]) -> Result<(Keys, Client)> {
+    let keys = Keys::parse(secret_key)
+        .map_err(|e| BrokerError::Nostr(format!("Invalid secret key: {}", e)))?;
+    let client = Client::new(keys.clone());
+
+    for relay in relays {
+        client
+            .add_relay(relay.as_str())
+            .await
+            .map_err(|e| BrokerError::Nostr(format!("Invalid relay {}: {}", relay, e)))?;
+    }
+    client.connect().await;
+
+    Ok((keys, client))
+}
+
+/// Listens for encrypted swap requests on a set of relays
+pub struct NostrListener {
+    client: Client,
+    keys: Keys,
+    state: AppState,
+}
+
+impl NostrListener {
+    /// Connect to the relays with the broker's Nostr key (hex or nsec)
+    pub async fn new(secret_key: &str, relays: &[String], state: AppState) -> Result<Self> {
+        let (keys, client) = connect(secret_key, relays).await?;
+
+        Ok(Self {
+            client,
+            keys,
+            state,
+        })
+    }
+
+    /// Public key clients send their requests to
+    pub fn public_key(&self) -> PublicKey {
+        self.keys.public_key()
+    }
+
+    /// Answer incoming swap requests until the relay pool shuts down
+    pub async fn run(&self) -> Result<()> {
+        let started_at = Timestamp::now();
+        let pubkey = self.public_key();
+
+        let nip04 = Filter::new()
+            .kind(Kind::EncryptedDirectMessage)
+            .pubkey(pubkey)
+            .since(started_at);
+        let nip17 = Filter::new()
+            .kind(Kind::GiftWrap)
+            .pubkey(pubkey)
+            .since(started_at - GIFT_WRAP_LOOKBACK_SECS);
+
+        self.client
+            .subscribe(vec![nip04, nip17], None)
+            .await
+            .map_err(|e| BrokerError::Nostr(e.to_string()))?;
+
+        info!("Listening for Nostr swap requests as {}", pubkey);
+
+        let mut notifications = self.client.notifications();
+        while let Ok(notification) = notifications.recv().await {
+            if let RelayPoolNotification::Event { event, .. } = notification {
+                let result = match event.kind {
+                    Kind::EncryptedDirectMessage => self.handle_nip04(&event).await,
+                    Kind::GiftWrap => self.handle_gift_wrap(&event, started_at).await,
+                    _ => Ok(()),
+                };
+
+                if let Err(e) = result {
+                    warn!("Failed to handle Nostr event {}: {}", event.id, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a NIP-04 direct message and reply in kind
+    async fn handle_nip04(&self, event: &Event) -> Result<()> {
+        let content = nip04::decrypt(self.keys.secret_key(), &event.pubkey, &event.content)
+            .map_err(|e| BrokerError::Nostr(format!("NIP-04 decryption failed: {}", e)))?;
+
+        let reply = self.handle_request(&event.pubkey, &content).await;
+
+        let builder =
+            EventBuilder::encrypted_direct_msg(&self.keys, event.pubkey, reply, Some(event.id))
+                .map_err(|e| BrokerError::Nostr(e.to_string()))?;
+        self.client
+            .send_event_builder(builder)
+            .await
+            .map_err(|e| BrokerError::Nostr(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Handle a NIP-17 private message and reply with a gift wrap
+    async fn handle_gift_wrap(&self, event: &Event, started_at: Timestamp) -> Result<()> {
+        let UnwrappedGift { sender, rumor } = self
+            .client
+            .unwrap_gift_wrap(event)
+            .await
+            .map_err(|e| BrokerError::Nostr(format!("Gift wrap unwrapping failed: {}", e)))?;
+
+        // The lookback window replays old messages; only answer new ones
+        if rumor.kind != Kind::PrivateDirectMessage || rumor.created_at < started_at {
+            return Ok(());
+        }
+
+        let reply = self.handle_request(&sender, &rumor.content).await;
+
+        self.client
+            .send_private_msg(sender, reply, None)
+            .await
+            .map_err(|e| BrokerError::Nostr(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Run a quote request through the broker, returning the JSON reply
+    async fn handle_request(&self, sender: &PublicKey, content: &str) -> String {
+        debug!("Nostr swap request from {}", sender);
+
+        let reply = match self.request_quote(sender, content).await {
+            Ok(response) => serde_json::to_string(&response),
+            Err(err) => serde_json::to_string(&err.into_body().1),
+        };
+
+        reply.unwrap_or_else(|e| {
+            error!("Failed to serialize Nostr reply: {}", e);
+            r#"{"error":"Internal error","code":"INTERNAL_ERROR"}"#.to_string()
+        })
+    }
+
+    /// Same flow as `POST /quote`, with the sender's npub as the client id
+    async fn request_quote(
+        &self,
+        sender: &PublicKey,
+        content: &str,
+    ) -> std::result::Result<QuoteResponse, ApiError> {
+        let req = parse_request(content)?;
+
+        let swap_request = SwapRequest {
+            client_id: Some(format!("nostr:{}", sender)),
+            from_mint: req.source_mint.clone(),
+            to_mint: req.target_mint.clone(),
+            amount: req.amount,
+            quote_type: req.quote_type,
+            client_public_key: req
+                .user_pubkey
+                .as_ref()
+                .and_then(|hex_str| hex::decode(hex_str).ok()),
+            adaptor_point: api::client_adaptor_point(&req)?,
+        };
+
+        let quote = self
+            .state
+            .broker
+            .request_quote(swap_request)
+            .await
+            .map_err(ApiError::from)?;
+
+        self.state
+            .db
+            .create_quote(&api::quote_record(&quote, req.user_pubkey))
+            .await
+            .map_err(ApiError::from)?;
+
+        info!("Issued quote {} over Nostr to {}", quote.quote_id, sender);
+
+        Ok(QuoteResponse { quote })
+    }
+}
+
+/// Rates, fees and liquidity for one swap direction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairRate {
+    pub source_mint: String,
+    pub target_mint: String,
+    pub source_unit: String,
+    pub target_unit: String,
+    pub fee_rate: f64,
+    pub min_swap_amount: u64,
+    pub max_swap_amount: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange_rate: Option<f64>, // Target units per source unit (cross-unit pairs only)
+    pub liquidity: u64, // Available on the target mint, rounded down to a power of ten
+}
+
+/// Content of the rates event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateAnnouncement {
+    pub broker_pubkey: String, // Long-term identity key, as in `GET /info`
+    pub api_version: String,
+    pub pairs: Vec<PairRate>,
+}
+
+/// Round a balance down to a power of ten, so exact balances aren't published
+pub fn liquidity_bucket(available: u64) -> u64 {
+    match available {
+        0 => 0,
+        n => 10u64.pow(n.ilog10()),
+    }
+}
+
+/// Current rates for every pair the broker can quote right now
+///
+/// Pairs with an unhealthy mint, or a cross-unit pair without a price, are left out.
+pub async fn rate_announcement(broker: &Broker) -> RateAnnouncement {
+    let config = broker.get_config();
+    let liquidity: HashMap<String, _> = broker
+        .get_liquidity_status()
+        .await
+        .mints
+        .into_iter()
+        .map(|mb| (mb.mint_url.clone(), mb))
+        .collect();
+    let healthy = |url: &str| liquidity.get(url).is_some_and(|mb| mb.healthy);
+
+    let mut pairs = Vec::new();
+    for (source, target) in config.swap_pairs(broker.supports_cross_unit()) {
+        if !healthy(&source.mint_url) || !healthy(&target.mint_url) {
+            continue;
+        }
+        let exchange_rate = match broker
+            .exchange_rate(&source.mint_url, &target.mint_url)
+            .await
+        {
+            Ok(rate) => rate,
+            Err(e) => {
+                debug!("No rate for {} -> {}: {}", source.mint_url, target.mint_url, e);
+                continue;
+            }
+        };
+
+        let terms = config.pair_terms(&source.mint_url, &target.mint_url);
+        pairs.push(PairRate {
+            source_mint: source.mint_url.clone(),
+            target_mint: target.mint_url.clone(),
+            source_unit: source.unit.clone(),
+            target_unit: target.unit.clone(),
+            fee_rate: terms.fee_rate,
+            min_swap_amount: terms.min_swap_amount,
+            max_swap_amount: terms.max_swap_amount,
+            exchange_rate,
+            liquidity: liquidity_bucket(liquidity[&target.mint_url].available),
+        });
+    }
+
+    RateAnnouncement {
+        broker_pubkey: broker.identity().public_key_hex(),
+        api_version: api::API_VERSION.to_string(),
+        pairs,
+    }
+}
+
+/// Publishes the broker's rates to a set of relays on a schedule
+pub struct RatePublisher {
+    client: Client,
+    broker: Arc<Broker>,
+    interval: Duration,
+}
+
+impl RatePublisher {
+    /// Connect to the relays with the broker's Nostr key (hex or nsec)
+    pub async fn new(
+        secret_key: &str,
+        relays: &[String],
+        broker: Arc<Broker>,
+        interval: Duration,
+    ) -> Result<Self> {
+        let (_, client) = connect(secret_key, relays).await?;
+
+        Ok(Self {
+            client,
+            broker,
+            interval,
+        })
+    }
+
+    /// Publish now and then every interval
+    pub async fn run(&self) {
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.publish().await {
+                warn!("Failed to publish rates to Nostr: {}", e);
+            }
+        }
+    }
+
+    /// Replace the rates event with the current rates
+    pub async fn publish(&self) -> Result<()> {
+        let announcement = rate_announcement(&self.broker).await;
+        let content = serde_json::to_string(&announcement)?;
+
+        let mut tags = vec![Tag::identifier(RATES_IDENTIFIER)];
+        for mint in &self.broker.get_config().mints {
+            tags.push(Tag::custom(TagKind::custom("mint"), [mint.mint_url.clone()]));
+        }
+
+        let builder = EventBuilder::new(Kind::ApplicationSpecificData, content, tags);
+        self.client
+            .send_event_builder(builder)
+            .await
+            .map_err(|e| BrokerError::Nostr(e.to_string()))?;
+
+        debug!("Published rates for {} pairs", announcement.pairs.len());
+
+        Ok(())
+    }
+}
+
+/// Parse a direct message as a `POST /quote` body
+fn parse_request(content: &str) -> std::result::Result<QuoteRequest, ApiError> {
+    let req: QuoteRequest = serde_json::from_str(content)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid quote request: {}", e)))?;
+    req.validate()?;
+    Ok(req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let req = parse_request(
+            r#"{"source_mint":"http://mint-a.test","target_mint":"http://mint-b.test","amount":100}"#,
+        )
+        .unwrap();
+        assert_eq!(req.amount, 100);
+        assert!(req.user_pubkey.is_none());
+
+        let err = parse_request("swap 100 sats please").unwrap_err();
+        let (status, code, _) = err.into_parts();
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(code, crate::error::ErrorCode::BadRequest);
+
+        let err = parse_request(
+            r#"{"source_mint":"mint-a","target_mint":"http://mint-b.test","amount":100}"#,
+        )
+        .unwrap_err();
+        let (status, code, _) = err.into_parts();
+        assert_eq!(status, axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(code, crate::error::ErrorCode::ValidationFailed);
+    }
+
+    #[test]
+    fn test_liquidity_bucket() {
+        assert_eq!(liquidity_bucket(0), 0);
+        assert_eq!(liquidity_bucket(7), 1);
+        assert_eq!(liquidity_bucket(10), 10);
+        assert_eq!(liquidity_bucket(54_321), 10_000);
+        assert_eq!(liquidity_bucket(u64::MAX), 10_000_000_000_000_000_000);
+    }
+}
@@ -0,0 +1,260 @@
+//! Price feeds for cross-unit swaps
+//!
+//! Mints denominate ecash in different units (`sat`, `msat`, `usd` cents, ...).
+//! Swapping between units needs the BTC price in the fiat currency involved,
+//! which comes from a [`PriceFeed`]. Feeds report the price of one BTC in whole
+//! currency units; [`exchange_rate`] turns that into target units per source unit.
+
+use crate::error::{BrokerError, Result};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::debug;
+
+/// Timeout for price provider requests
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Source of BTC prices
+#[async_trait]
+pub trait PriceFeed: Send + Sync {
+    /// Price of one BTC in `currency` (e.g. "usd"), in whole currency units
+    async fn btc_price(&self, currency: &str) -> Result<f64>;
+
+    /// Provider name for logs
+    fn name(&self) -> &str;
+}
+
+/// Target units per source unit, e.g. `usd` cents per `sat`
+///
+/// Fiat units are taken to be cents, as on Cashu mints.
+pub async fn exchange_rate(feed: &dyn PriceFeed, from_unit: &str, to_unit: &str) -> Result<f64> {
+    if from_unit.eq_ignore_ascii_case(to_unit) {
+        return Ok(1.0);
+    }
+
+    let from = units_per_btc(feed, from_unit).await?;
+    let to = units_per_btc(feed, to_unit).await?;
+
+    Ok(to / from)
+}
+
+/// How many of `unit` one BTC is worth
+async fn units_per_btc(feed: &dyn PriceFeed, unit: &str) -> Result<f64> {
+    let unit = unit.to_lowercase();
+    let units = match unit.as_str() {
+        "sat" => 1e8,
+        "msat" => 1e11,
+        currency => feed.btc_price(currency).await? * 100.0,
+    };
+
+    if !units.is_finite() || units <= 0.0 {
+        return Err(BrokerError::PriceFeed(format!(
+            "{} returned an invalid price for {}",
+            feed.name(),
+            unit
+        )));
+    }
+
+    Ok(units)
+}
+
+/// Fixed prices from configuration (for testing and pegged setups)
+pub struct FixedPriceFeed {
+    prices: HashMap<String, f64>,
+}
+
+impl FixedPriceFeed {
+    pub fn new(prices: HashMap<String, f64>) -> Self {
+        Self {
+            prices: prices
+                .into_iter()
+                .map(|(currency, price)| (currency.to_lowercase(), price))
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceFeed for FixedPriceFeed {
+    async fn btc_price(&self, currency: &str) -> Result<f64> {
+        self.prices
+            .get(&currency.to_lowercase())
+            .copied()
+            .ok_or_else(|| BrokerError::PriceFeed(format!("No fixed price for {}", currency)))
+    }
+
+    fn name(&self) -> &str {
+        "fixed"
+    }
+}
+
+/// Coinbase spot price API
+pub struct CoinbasePriceFeed {
+    client: reqwest::Client,
+}
+
+impl CoinbasePriceFeed {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+        })
+    }
+}
+
+#[async_trait]
+impl PriceFeed for CoinbasePriceFeed {
+    async fn btc_price(&self, currency: &str) -> Result<f64> {
+        let url = format!(
+            "https://api.coinbase.com/v2/prices/BTC-{}/spot",
+            currency.to_uppercase()
+        );
+        let body = fetch_json(&self.client, &url).await?;
+
+        body["data"]["amount"]
+            .as_str()
+            .and_then(|amount| amount.parse().ok())
+            .ok_or_else(|| {
+                BrokerError::PriceFeed(format!("Unexpected Coinbase response: {}", body))
+            })
+    }
+
+    fn name(&self) -> &str {
+        "coinbase"
+    }
+}
+
+/// Kraken public ticker API
+pub struct KrakenPriceFeed {
+    client: reqwest::Client,
+}
+
+impl KrakenPriceFeed {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+        })
+    }
+}
+
+#[async_trait]
+impl PriceFeed for KrakenPriceFeed {
+    async fn btc_price(&self, currency: &str) -> Result<f64> {
+        let url = format!(
+            "https://api.kraken.com/0/public/Ticker?pair=XBT{}",
+            currency.to_uppercase()
+        );
+        let body = fetch_json(&self.client, &url).await?;
+
+        // The result is keyed by Kraken's pair name (e.g. XXBTZUSD); "c" is the last trade
+        body["result"]
+            .as_object()
+            .and_then(|pairs| pairs.values().next())
+            .and_then(|ticker| ticker["c"][0].as_str())
+            .and_then(|price| price.parse().ok())
+            .ok_or_else(|| BrokerError::PriceFeed(format!("Unexpected Kraken response: {}", body)))
+    }
+
+    fn name(&self) -> &str {
+        "kraken"
+    }
+}
+
+/// Caches another feed's prices so quotes don't hit the provider every time
+pub struct CachedPriceFeed {
+    inner: Arc<dyn PriceFeed>,
+    ttl: Duration,
+    cache: RwLock<HashMap<String, (f64, Instant)>>,
+}
+
+impl CachedPriceFeed {
+    pub fn new(inner: Arc<dyn PriceFeed>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl PriceFeed for CachedPriceFeed {
+    async fn btc_price(&self, currency: &str) -> Result<f64> {
+        let currency = currency.to_lowercase();
+
+        if let Some((price, fetched_at)) = self.cache.read().await.get(&currency) {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(*price);
+            }
+        }
+
+        let price = self.inner.btc_price(&currency).await?;
+        debug!("BTC/{} from {}: {}", currency, self.inner.name(), price);
+
+        self.cache
+            .write()
+            .await
+            .insert(currency, (price, Instant::now()));
+
+        Ok(price)
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+}
+
+fn http_client() -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| BrokerError::PriceFeed(e.to_string()))
+}
+
+async fn fetch_json(client: &reqwest::Client, url: &str) -> Result<serde_json::Value> {
+    client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| BrokerError::PriceFeed(e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| BrokerError::PriceFeed(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed_feed() -> FixedPriceFeed {
+        FixedPriceFeed::new(HashMap::from([
+            ("USD".to_string(), 50_000.0),
+            ("eur".to_string(), 40_000.0),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn test_exchange_rate() {
+        let feed = fixed_feed();
+
+        // 1 BTC = 5,000,000 cents = 100,000,000 sats
+        assert_eq!(exchange_rate(&feed, "sat", "usd").await.unwrap(), 0.05);
+        assert_eq!(exchange_rate(&feed, "usd", "sat").await.unwrap(), 20.0);
+        assert_eq!(exchange_rate(&feed, "usd", "eur").await.unwrap(), 0.8);
+        assert_eq!(exchange_rate(&feed, "sat", "msat").await.unwrap(), 1_000.0);
+        assert_eq!(exchange_rate(&feed, "sat", "SAT").await.unwrap(), 1.0);
+
+        assert!(exchange_rate(&feed, "sat", "jpy").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cached_price_feed() {
+        let feed = CachedPriceFeed::new(Arc::new(fixed_feed()), Duration::from_secs(60));
+
+        assert_eq!(feed.btc_price("usd").await.unwrap(), 50_000.0);
+        assert!(feed.cache.read().await.contains_key("usd"));
+        assert_eq!(feed.name(), "fixed");
+    }
+}
@@ -0,0 +1,117 @@
+//! Inventory-aware pricing
+//!
+//! A flat fee prices every direction the same, but a swap that drains a mint
+//! the broker already holds little on costs more: the broker has to rebalance
+//! sooner, and pays Lightning fees to do it. A [`PricingStrategy`] turns the
+//! configured fee rate of a pair into the rate a quote is issued at, given the
+//! broker's inventory across the mints of the target's unit. The default
+//! [`InventorySkew`] widens the fee on swaps into a mint whose share of that
+//! inventory is below an even split, and leaves every other quote at the
+//! configured rate.
+
+use std::fmt;
+
+/// Widened fees never go above this, so exact-out quotes stay possible
+const MAX_SKEWED_FEE_RATE: f64 = 0.5;
+
+/// Unreserved balances of a swap's target mint and the mints it shares a unit with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Inventory {
+    pub target_balance: u64,
+    pub total_balance: u64, // Over all mints of the target's unit, the target included
+    pub mints: usize,       // Mints of the target's unit, the target included
+}
+
+impl Inventory {
+    /// Target mint's share of the inventory, if there is any
+    pub fn target_share(&self) -> Option<f64> {
+        (self.total_balance > 0).then(|| self.target_balance as f64 / self.total_balance as f64)
+    }
+
+    /// Share each mint would hold if the inventory were split evenly
+    pub fn target_allocation(&self) -> f64 {
+        1.0 / self.mints.max(1) as f64
+    }
+}
+
+/// Sets the fee rate of new quotes
+pub trait PricingStrategy: Send + Sync + fmt::Debug {
+    /// Fee rate for a swap into a mint holding `inventory`, given the pair's
+    /// configured `fee_rate`
+    fn fee_rate(&self, fee_rate: f64, inventory: &Inventory) -> f64;
+}
+
+/// Widens fees on swaps into mints holding less than an even share
+///
+/// A target mint holding its even share or more is quoted at the configured
+/// rate. Below that the rate grows linearly with the shortfall, up to
+/// `1 + skew` times the configured rate for a mint that is empty. A skew of 0
+/// prices every quote at the configured rate.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InventorySkew {
+    pub skew: f64,
+}
+
+impl InventorySkew {
+    pub fn new(skew: f64) -> Self {
+        Self { skew }
+    }
+}
+
+impl PricingStrategy for InventorySkew {
+    fn fee_rate(&self, fee_rate: f64, inventory: &Inventory) -> f64 {
+        let allocation = inventory.target_allocation();
+        let Some(share) = inventory.target_share() else {
+            return fee_rate;
+        };
+        if self.skew <= 0.0 || share >= allocation {
+            return fee_rate;
+        }
+
+        let shortfall = (allocation - share) / allocation;
+        (fee_rate * (1.0 + self.skew * shortfall)).min(MAX_SKEWED_FEE_RATE.max(fee_rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory(target_balance: u64, total_balance: u64, mints: usize) -> Inventory {
+        Inventory {
+            target_balance,
+            total_balance,
+            mints,
+        }
+    }
+
+    #[test]
+    fn test_share() {
+        let two_mints = inventory(250, 1000, 2);
+        assert_eq!(two_mints.target_share(), Some(0.25));
+        assert_eq!(two_mints.target_allocation(), 0.5);
+        assert_eq!(inventory(0, 0, 2).target_share(), None);
+        assert_eq!(inventory(0, 0, 0).target_allocation(), 1.0);
+    }
+
+    #[test]
+    fn test_inventory_skew() {
+        let pricing = InventorySkew::new(1.0);
+
+        // At or above an even share the configured rate applies
+        assert_eq!(pricing.fee_rate(0.01, &inventory(500, 1000, 2)), 0.01);
+        assert_eq!(pricing.fee_rate(0.01, &inventory(900, 1000, 2)), 0.01);
+        // Nothing to compare against
+        assert_eq!(pricing.fee_rate(0.01, &inventory(0, 0, 2)), 0.01);
+        assert_eq!(pricing.fee_rate(0.01, &inventory(100, 100, 1)), 0.01);
+
+        // Half the even share widens by half the skew, an empty mint by all of it
+        assert!((pricing.fee_rate(0.01, &inventory(250, 1000, 2)) - 0.015).abs() < 1e-12);
+        assert!((pricing.fee_rate(0.01, &inventory(0, 1000, 2)) - 0.02).abs() < 1e-12);
+        assert!((pricing.fee_rate(0.01, &inventory(0, 900, 3)) - 0.02).abs() < 1e-12);
+
+        // No skew, no change; and widened rates are capped
+        assert_eq!(InventorySkew::default().fee_rate(0.01, &inventory(0, 1000, 2)), 0.01);
+        assert_eq!(InventorySkew::new(100.0).fee_rate(0.01, &inventory(0, 1000, 2)), 0.5);
+    }
+}
@@ -0,0 +1,121 @@
+//! Quarantined proofs
+//!
+//! Proofs the broker can't vouch for are kept in a per-mint quarantine
+//! instead of its liquidity: never selected for a swap or a payment, and not
+//! counted in the balance. Two things put them there. Proofs received from a
+//! mint whose NUT-12 DLEQ fails, is missing, or can't be checked because the
+//! mint's keys are out of reach. And proofs the broker handed to a melt or a swap that
+//! failed after reaching the mint, which may have spent them anyway.
+//!
+//! Operators inspect, retry and discard quarantined proofs through the admin
+//! API. A retry asks the mint for each proof's state: spent ones are dropped,
+//! unspent ones whose DLEQ checks out go back to liquidity, and the rest stay.
+
+use cdk::nuts::{Proof, PublicKey, State};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::SystemTime;
+
+/// Why a proof was quarantined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuarantineReason {
+    InvalidDleq,     // Not signed with the key the mint publishes for its amount
+    MissingDleq,     // Came without a DLEQ while DLEQs are required
+    KeysUnavailable, // The mint's keys couldn't be fetched to check its DLEQ
+    FailedMelt,      // A melt failed after reaching the mint
+    FailedSwap,      // A swap failed after reaching the mint
+}
+
+impl fmt::Display for QuarantineReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            QuarantineReason::InvalidDleq => "invalid_dleq",
+            QuarantineReason::MissingDleq => "missing_dleq",
+            QuarantineReason::KeysUnavailable => "keys_unavailable",
+            QuarantineReason::FailedMelt => "failed_melt",
+            QuarantineReason::FailedSwap => "failed_swap",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// A proof held out of liquidity
+#[derive(Debug, Clone)]
+pub struct QuarantinedProof {
+    pub proof: Proof,
+    pub reason: QuarantineReason,
+    pub detail: String, // The error that put it here
+    pub since: SystemTime,
+}
+
+impl QuarantinedProof {
+    pub fn new(proof: Proof, reason: QuarantineReason, detail: impl Into<String>) -> Self {
+        Self {
+            proof,
+            reason,
+            detail: detail.into(),
+            since: SystemTime::now(),
+        }
+    }
+
+    /// Y of the proof, which identifies it to the mint and the admin API
+    pub fn y(&self) -> Option<PublicKey> {
+        self.proof.y().ok()
+    }
+
+    pub fn amount(&self) -> u64 {
+        u64::from(self.proof.amount)
+    }
+}
+
+/// Amounts a retry released to liquidity, dropped as spent and left in quarantine
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantineRetry {
+    pub released: u64,
+    pub dropped: u64,
+    pub kept: u64,
+}
+
+/// What a retry does with a proof the mint reports in `state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    Check, // Unspent: release it if its DLEQ checks out
+    Drop,  // Spent: the failed call went through after all
+    Keep,  // Pending or unknown: look again later
+}
+
+impl Disposition {
+    pub fn of(state: State) -> Self {
+        match state {
+            State::Unspent => Disposition::Check,
+            State::Spent => Disposition::Drop,
+            _ => Disposition::Keep,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disposition() {
+        assert_eq!(Disposition::of(State::Unspent), Disposition::Check);
+        assert_eq!(Disposition::of(State::Spent), Disposition::Drop);
+        assert_eq!(Disposition::of(State::Pending), Disposition::Keep);
+        assert_eq!(Disposition::of(State::Reserved), Disposition::Keep);
+    }
+
+    #[test]
+    fn test_reason_json() {
+        assert_eq!(
+            serde_json::to_string(&QuarantineReason::FailedMelt).unwrap(),
+            r#""failed_melt""#
+        );
+        assert_eq!(
+            QuarantineReason::KeysUnavailable.to_string(),
+            "keys_unavailable"
+        );
+    }
+}
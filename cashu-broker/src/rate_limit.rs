@@ -0,0 +1,224 @@
+//! Rate limiting for the public API
+//!
+//! Token buckets keyed by client IP, plus the `user_pubkey` of quote requests,
+//! so a single client can't flood `/quote` by rotating addresses or keys.
+//! Rejected requests get `429 Too Many Requests` with a `Retry-After` header.
+
+use crate::api::ApiError;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Largest `/quote` body inspected for a pubkey
+const MAX_QUOTE_BODY: usize = 64 * 1024;
+
+/// Drop idle buckets once this many are tracked
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Rate limit settings
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    pub requests_per_minute: u32, // Sustained rate per client
+    pub burst: u32,               // Requests allowed at once
+    pub trust_proxy: bool,        // Take the client IP from X-Forwarded-For
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token-bucket limiter keyed by client
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.config.requests_per_minute as f64 / 60.0
+    }
+
+    fn capacity(&self) -> f64 {
+        self.config.burst.max(1) as f64
+    }
+
+    /// Take a token for `key`, or return how long until one is available
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let capacity = self.capacity();
+        let refill = self.refill_per_sec();
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+
+        if buckets.len() >= PRUNE_THRESHOLD {
+            // A bucket that would be full again carries no state worth keeping
+            buckets.retain(|_, b| {
+                b.tokens + now.duration_since(b.updated).as_secs_f64() * refill < capacity
+            });
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if refill > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / refill))
+        } else {
+            Err(Duration::from_secs(60))
+        }
+    }
+}
+
+/// Middleware applying the limiter to every request it wraps
+pub async fn limit(
+    limiter: Arc<RateLimiter>,
+    req: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let ip = client_ip(&req, limiter.config.trust_proxy);
+    limiter.check(&format!("ip:{}", ip)).map_err(rate_limited)?;
+
+    // Quote requests are also limited per pubkey
+    if req.method() == Method::POST && req.uri().path() == "/quote" {
+        let (parts, body) = req.into_parts();
+        let bytes = to_bytes(body, MAX_QUOTE_BODY)
+            .await
+            .map_err(|_| ApiError::BadRequest("Request body too large".to_string()))?;
+
+        if let Some(pubkey) = user_pubkey(&bytes) {
+            limiter
+                .check(&format!("pubkey:{}", pubkey))
+                .map_err(rate_limited)?;
+        }
+
+        return Ok(next
+            .run(Request::from_parts(parts, Body::from(bytes)))
+            .await);
+    }
+
+    Ok(next.run(req).await)
+}
+
+fn rate_limited(retry_after: Duration) -> ApiError {
+    ApiError::RateLimited(retry_after.as_secs_f64().ceil().max(1.0) as u64)
+}
+
+/// Client address, from the proxy header when trusted
+///
+/// Only the rightmost `X-Forwarded-For` entry is taken: the trusted proxy
+/// appends the address it saw, while anything left of it came from the
+/// client and can be made up to dodge the limit.
+fn client_ip(req: &Request, trust_proxy: bool) -> String {
+    if trust_proxy {
+        let forwarded = req
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .last()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty());
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// `user_pubkey` field of a quote request body, if any
+fn user_pubkey(body: &[u8]) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_slice(body).ok()?;
+    value["user_pubkey"].as_str().map(|s| s.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_ip_from_proxy() {
+        let req = |forwarded: &[&str]| {
+            let mut builder = axum::http::Request::builder().uri("/quote");
+            for value in forwarded {
+                builder = builder.header("x-forwarded-for", *value);
+            }
+            let mut req = builder.body(Body::empty()).unwrap();
+            req.extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 9], 443))));
+            req
+        };
+
+        // The proxy's own entry wins over whatever the client sent
+        assert_eq!(client_ip(&req(&["6.6.6.6, 1.2.3.4"]), true), "1.2.3.4");
+        assert_eq!(client_ip(&req(&["6.6.6.6", "1.2.3.4"]), true), "1.2.3.4");
+        assert_eq!(client_ip(&req(&["1.2.3.4"]), true), "1.2.3.4");
+        assert_eq!(client_ip(&req(&[""]), true), "10.0.0.9");
+        assert_eq!(client_ip(&req(&["1.2.3.4"]), false), "10.0.0.9");
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            requests_per_minute: 60,
+            burst: 2,
+            trust_proxy: false,
+        });
+        let start = Instant::now();
+
+        assert!(limiter.check_at("ip:1.2.3.4", start).is_ok());
+        assert!(limiter.check_at("ip:1.2.3.4", start).is_ok());
+
+        // Burst used up: one token per second comes back
+        let retry = limiter.check_at("ip:1.2.3.4", start).unwrap_err();
+        assert!(retry <= Duration::from_secs(1));
+
+        // Other clients are unaffected
+        assert!(limiter.check_at("ip:5.6.7.8", start).is_ok());
+
+        assert!(limiter
+            .check_at("ip:1.2.3.4", start + Duration::from_secs(1))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_user_pubkey() {
+        assert_eq!(
+            user_pubkey(br#"{"amount":100,"user_pubkey":"02ABCD"}"#),
+            Some("02abcd".to_string())
+        );
+        assert_eq!(user_pubkey(br#"{"amount":100}"#), None);
+        assert_eq!(user_pubkey(b"not json"), None);
+    }
+}
@@ -0,0 +1,142 @@
+//! Startup reconciliation of accepted quotes
+//!
+//! The accept and complete handlers talk to the mints before they write to
+//! the database, so a crash in between leaves quotes marked accepted that
+//! already settled, or that never got as far as a swap record. Before old
+//! quotes are restored, the broker asks the mints (NUT-07) about the proofs on
+//! both sides of every accepted swap and settles each quote with
+//! [`reconcile`]. Given the same proof states the outcome is always the same.
+
+use cdk::nuts::State;
+use std::fmt;
+
+/// What reconciliation makes of an accepted quote
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reconciliation {
+    /// The broker claimed the client's proofs: mark the quote completed
+    Completed,
+    /// No locked outputs were recorded, so the client never got any: mark it failed
+    Failed,
+    /// The broker's locked outputs were refunded: mark the quote expired
+    Refunded,
+    /// Past its refund time with both sides unspent; the refund loop reclaims it
+    RefundPending,
+    /// Still open, or a mint reports proofs pending: leave it accepted
+    InFlight,
+}
+
+impl fmt::Display for Reconciliation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Completed => write!(f, "completed"),
+            Self::Failed => write!(f, "failed"),
+            Self::Refunded => write!(f, "refunded"),
+            Self::RefundPending => write!(f, "refund_pending"),
+            Self::InFlight => write!(f, "in_flight"),
+        }
+    }
+}
+
+/// Decide the fate of an accepted quote from the state of its proofs
+///
+/// `source` holds the states of the client's proofs on the source mint and
+/// `target` those of the broker's locked outputs on the target mint; either
+/// is `None` when the proofs were never recorded. Only the broker can spend
+/// the client's proofs, and only the client (once the swap reveals the
+/// adaptor secret) or the broker's refund can spend the outputs.
+pub fn reconcile(
+    source: Option<&[State]>,
+    target: Option<&[State]>,
+    refund_due: bool,
+) -> Reconciliation {
+    let Some(target) = target.filter(|states| !states.is_empty()) else {
+        return Reconciliation::Failed;
+    };
+    let source = source.unwrap_or_default();
+    let all_spent =
+        |states: &[State]| !states.is_empty() && states.iter().all(|s| *s == State::Spent);
+
+    if all_spent(source) {
+        Reconciliation::Completed
+    } else if all_spent(target) {
+        Reconciliation::Refunded
+    } else if source.iter().chain(target).any(|s| *s != State::Unspent) {
+        // A swap or refund caught mid-flight; look again next time
+        Reconciliation::InFlight
+    } else if refund_due {
+        Reconciliation::RefundPending
+    } else {
+        Reconciliation::InFlight
+    }
+}
+
+/// Outcome of a reconciliation pass
+#[derive(Debug, Clone, Default)]
+pub struct ReconcileReport {
+    /// Pending quotes past their expiry, marked expired
+    pub expired: usize,
+    /// Accepted quotes checked with the mints, by quote ID
+    pub outcomes: Vec<(String, Reconciliation)>,
+    /// Accepted quotes left alone because a mint could not be reached
+    pub skipped: Vec<String>,
+}
+
+impl ReconcileReport {
+    /// Number of accepted quotes that reached the given outcome
+    pub fn count(&self, outcome: Reconciliation) -> usize {
+        self.outcomes.iter().filter(|(_, o)| *o == outcome).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconcile() {
+        use State::{Pending, Spent, Unspent};
+
+        // Crashed between accepting and writing the swap record
+        assert_eq!(
+            reconcile(Some(&[Unspent]), None, false),
+            Reconciliation::Failed
+        );
+        assert_eq!(reconcile(None, Some(&[]), true), Reconciliation::Failed);
+
+        // Crashed after claiming the client's proofs
+        assert_eq!(
+            reconcile(Some(&[Spent, Spent]), Some(&[Unspent]), false),
+            Reconciliation::Completed
+        );
+        assert_eq!(
+            reconcile(Some(&[Spent]), Some(&[Spent]), true),
+            Reconciliation::Completed
+        );
+
+        // Crashed after refunding the locked outputs
+        assert_eq!(
+            reconcile(Some(&[Unspent]), Some(&[Spent, Spent]), true),
+            Reconciliation::Refunded
+        );
+
+        // Untouched swaps wait for the client, or for the refund loop
+        assert_eq!(
+            reconcile(Some(&[Unspent]), Some(&[Unspent]), false),
+            Reconciliation::InFlight
+        );
+        assert_eq!(
+            reconcile(Some(&[Unspent]), Some(&[Unspent]), true),
+            Reconciliation::RefundPending
+        );
+
+        // Anything half done is left alone
+        assert_eq!(
+            reconcile(Some(&[Spent, Pending]), Some(&[Unspent]), true),
+            Reconciliation::InFlight
+        );
+        assert_eq!(
+            reconcile(Some(&[Unspent]), Some(&[Spent, Unspent]), true),
+            Reconciliation::InFlight
+        );
+    }
+}
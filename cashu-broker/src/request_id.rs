@@ -0,0 +1,110 @@
+//! Request IDs for the HTTP API
+//!
+//! Every request runs in a `request` span carrying an ID, taken from the
+//! client's `X-Request-Id` header when it is usable and generated otherwise,
+//! and the ID is echoed back in the response. Swap steps open a nested `quote`
+//! span keyed on `quote_id`, so a filter on either one shows a swap's path
+//! through the API, broker, swap coordinator and liquidity manager.
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use tracing::{info_span, Instrument};
+use uuid::Uuid;
+
+/// Header carrying the request ID, both ways
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied ID that is kept
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// ID of the current request, available to handlers as an extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// Middleware that assigns the request ID and runs the request in its span
+pub async fn request_id(mut req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    req.extensions_mut().insert(RequestId(id.clone()));
+
+    let mut response = next.run(req).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Client IDs end up in logs, so only short, plain tokens are accepted
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Extension, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|Extension(id): Extension<RequestId>| async move { id.0 }),
+            )
+            .layer(middleware::from_fn(request_id))
+    }
+
+    async fn call(id: Option<&str>) -> (String, String) {
+        let mut req = Request::builder().uri("/");
+        if let Some(id) = id {
+            req = req.header(REQUEST_ID_HEADER, id);
+        }
+        let response = app()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let header = response.headers()[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), 1024)
+            .await
+            .unwrap();
+        (header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_request_id() {
+        // A client's ID is kept and echoed
+        let (header, seen) = call(Some("wallet-42.retry_1")).await;
+        assert_eq!(header, "wallet-42.retry_1");
+        assert_eq!(seen, header);
+
+        // Otherwise one is generated
+        let (header, seen) = call(None).await;
+        assert!(Uuid::parse_str(&header).is_ok());
+        assert_eq!(seen, header);
+
+        // Unusable IDs are replaced
+        let (header, _) = call(Some("has spaces")).await;
+        assert!(Uuid::parse_str(&header).is_ok());
+        let (header, _) = call(Some(&"a".repeat(65))).await;
+        assert!(Uuid::parse_str(&header).is_ok());
+    }
+}
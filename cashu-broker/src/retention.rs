@@ -0,0 +1,160 @@
+//! Data retention
+//!
+//! Settled quotes keep their swap's proof JSON in the database until they are
+//! pruned. With `RETENTION_DAYS` set, the [`Pruner`] removes completed,
+//! expired and failed quotes older than that every hour, along with their
+//! swap records, keys and webhook deliveries. In `archive` mode each quote is
+//! first copied to `quotes_archive` without its proofs, keys or signatures;
+//! in `delete` mode nothing is kept. Liquidity events past the cutoff are
+//! rolled up into daily totals per mint and event type before they are
+//! deleted. The accounting ledger and the audit log are never pruned.
+
+use crate::db::{Database, PruneReport};
+use crate::error::Result;
+use crate::leases::{self, Leases, RETENTION_SWEEP};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How often old records are pruned
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// What happens to a quote once it is past the retention period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionMode {
+    /// Keep a summary row in `quotes_archive`
+    #[default]
+    Archive,
+    /// Delete it outright
+    Delete,
+}
+
+impl fmt::Display for RetentionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Archive => write!(f, "archive"),
+            Self::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+impl FromStr for RetentionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "archive" => Ok(Self::Archive),
+            "delete" => Ok(Self::Delete),
+            other => Err(format!(
+                "unknown retention mode {} (expected archive or delete)",
+                other
+            )),
+        }
+    }
+}
+
+/// How long settled records are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub days: u64,
+    pub mode: RetentionMode,
+}
+
+impl RetentionPolicy {
+    /// Records created before this are pruned
+    pub fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        i64::try_from(self.days)
+            .ok()
+            .and_then(chrono::Duration::try_days)
+            .and_then(|period| now.checked_sub_signed(period))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+}
+
+/// Prunes records past the retention period
+pub struct Pruner {
+    db: Database,
+    policy: RetentionPolicy,
+    leases: Option<Leases>,
+}
+
+impl Pruner {
+    pub fn new(db: Database, policy: RetentionPolicy) -> Self {
+        Self {
+            db,
+            policy,
+            leases: None,
+        }
+    }
+
+    /// Only prune while holding the retention lease, so one of the
+    /// instances sharing the database prunes it
+    pub fn with_leases(mut self, leases: Leases) -> Self {
+        self.leases = Some(leases);
+        self
+    }
+
+    /// Prune forever, starting now
+    pub async fn run(&self) {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if !leases::may_run(self.leases.as_ref(), RETENTION_SWEEP).await {
+                continue;
+            }
+            match self.prune().await {
+                Ok(report) if report.is_empty() => {}
+                Ok(report) => info!(
+                    "Pruned {} quotes ({} archived) and {} liquidity events older than {} days",
+                    report.quotes_deleted,
+                    report.quotes_archived,
+                    report.liquidity_events,
+                    self.policy.days
+                ),
+                Err(e) => warn!("Pruning failed: {}", e),
+            }
+        }
+    }
+
+    /// Prune once
+    pub async fn prune(&self) -> Result<PruneReport> {
+        let cutoff = self.policy.cutoff(Utc::now());
+        self.db
+            .prune(cutoff, self.policy.mode == RetentionMode::Archive)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retention_mode() {
+        assert_eq!("archive".parse(), Ok(RetentionMode::Archive));
+        assert_eq!(" Delete ".parse(), Ok(RetentionMode::Delete));
+        assert!("keep".parse::<RetentionMode>().is_err());
+        assert_eq!(RetentionMode::default().to_string(), "archive");
+    }
+
+    #[test]
+    fn test_cutoff() {
+        let now = Utc::now();
+        let policy = RetentionPolicy {
+            days: 30,
+            mode: RetentionMode::Delete,
+        };
+        assert_eq!(now - policy.cutoff(now), chrono::Duration::days(30));
+
+        // Absurd periods don't overflow
+        let forever = RetentionPolicy {
+            days: u64::MAX,
+            ..policy
+        };
+        assert!(forever.cutoff(now) < now);
+    }
+}
@@ -0,0 +1,203 @@
+//! Retries for wallet calls to mints
+//!
+//! A dropped connection or a 502 from a mint's reverse proxy shouldn't fail a
+//! swap. Calls wrapped in [`RetryPolicy::run`] are retried with exponential
+//! backoff and jitter when the failure is a transport error or a 408, 429 or
+//! 5xx answer. Protocol errors from the mint (spent proofs, unpaid quotes,
+//! bad signatures) are returned straight away, since retrying can't fix them.
+
+use rand::Rng;
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+/// How mint calls are retried
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,    // Attempts including the first (1 = no retries)
+    pub base_delay: Duration, // Delay before the first retry, doubled on each further one
+    pub max_delay: Duration,  // Longest delay between attempts
+    pub jitter: f64,          // Each delay is randomised by up to this fraction (0.2 = ±20%)
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(5),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that makes a single attempt
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before retry number `retry` (starting at 1), without jitter
+    pub fn delay(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(16);
+        self.base_delay
+            .saturating_mul(2u32.pow(exponent))
+            .min(self.max_delay)
+    }
+
+    fn jittered(&self, delay: Duration) -> Duration {
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        let factor = 1.0 + rand::thread_rng().gen_range(-self.jitter..=self.jitter);
+        delay.mul_f64(factor.max(0.0))
+    }
+
+    /// Run a CDK wallet call, retrying transient failures
+    ///
+    /// `context` describes a failure in the retry log, e.g. "Failed to swap".
+    pub async fn run<T, F, Fut>(&self, context: &str, f: F) -> Result<T, cdk::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, cdk::Error>>,
+    {
+        self.run_if(context, is_retryable, f).await
+    }
+
+    /// Run `f`, retrying while `retryable` accepts the error and attempts remain
+    pub async fn run_if<T, E, F, Fut>(
+        &self,
+        context: &str,
+        retryable: impl Fn(&E) -> bool,
+        mut f: F,
+    ) -> Result<T, E>
+    where
+        E: std::fmt::Debug,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.max_attempts && retryable(&e) => {
+                    let delay = self.jittered(self.delay(attempt));
+                    warn!(
+                        "{} (attempt {}/{}), retrying in {:?}: {:?}",
+                        context, attempt, self.max_attempts, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Whether a wallet error is a network failure worth retrying
+pub fn is_retryable(error: &cdk::Error) -> bool {
+    match error {
+        // No status: the request never got an answer
+        cdk::Error::HttpError(None, _) => true,
+        cdk::Error::HttpError(Some(status), _) => {
+            matches!(*status, 408 | 429) || (500..600).contains(status)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn instant(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+            jitter: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(250));
+        assert_eq!(policy.delay(2), Duration::from_millis(500));
+        assert_eq!(policy.delay(3), Duration::from_secs(1));
+        assert_eq!(policy.delay(30), policy.max_delay);
+
+        let jittery = RetryPolicy::default();
+        for _ in 0..100 {
+            let delay = jittery.jittered(Duration::from_secs(1));
+            assert!(delay >= Duration::from_millis(800) && delay <= Duration::from_millis(1200));
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let http = |status: Option<u16>| cdk::Error::HttpError(status, "error".to_string());
+
+        assert!(is_retryable(&http(None)));
+        assert!(is_retryable(&http(Some(502))));
+        assert!(is_retryable(&http(Some(429))));
+        assert!(!is_retryable(&http(Some(400))));
+        assert!(!is_retryable(&cdk::Error::TokenAlreadySpent));
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let calls = &AtomicU32::new(0);
+        let result: Result<u32, &str> = instant(3)
+            .run_if(
+                "test",
+                |e| *e == "transient",
+                || async move {
+                    match calls.fetch_add(1, Ordering::SeqCst) {
+                        0 | 1 => Err("transient"),
+                        n => Ok(n),
+                    }
+                },
+            )
+            .await;
+        assert_eq!(result, Ok(2));
+
+        // Gives up once attempts run out
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), &str> = instant(2)
+            .run_if(
+                "test",
+                |_| true,
+                || async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err("transient")
+                },
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Protocol errors fail immediately
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), &str> = instant(5)
+            .run_if(
+                "test",
+                |e| *e == "transient",
+                || async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err("spent")
+                },
+            )
+            .await;
+        assert_eq!(result, Err("spent"));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
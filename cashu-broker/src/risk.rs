@@ -0,0 +1,207 @@
+//! Per-client risk limits
+//!
+//! Three limits bound what the broker commits to, each off when zero: the
+//! input amount a single client may be quoted over a rolling 24 hours, how
+//! many open (pending or accepted) quotes a client may hold at once, and the
+//! output amount of all open quotes together. Clients are told apart by the
+//! `user_pubkey` of their requests, or by their Nostr sender; requests that
+//! carry neither share one allowance. A quote over a limit is refused with
+//! [`BrokerError::RiskLimitExceeded`](crate::error::BrokerError) before any
+//! liquidity is reserved for it.
+
+use crate::types::SwapRequest;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+
+/// Window of the per-client volume limit
+const VOLUME_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// Client that requests without a key or sender count against
+pub const ANONYMOUS_CLIENT: &str = "anonymous";
+
+/// Risk limits on new quotes (0 = no limit)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RiskLimits {
+    pub daily_volume_per_client: u64,      // Input amount quoted per client per 24h
+    pub max_open_quotes_per_client: usize, // Pending and accepted quotes per client
+    pub max_exposure: u64,                 // Output amount of all open quotes
+}
+
+impl RiskLimits {
+    /// Check a new quote against the limits, given what is already committed
+    pub fn check(
+        &self,
+        quoted_today: u64,
+        open_quotes: usize,
+        outstanding: u64,
+        amount_in: u64,
+        amount_out: u64,
+    ) -> Result<(), RiskViolation> {
+        if self.daily_volume_per_client > 0
+            && quoted_today.saturating_add(amount_in) > self.daily_volume_per_client
+        {
+            return Err(RiskViolation::DailyVolume {
+                limit: self.daily_volume_per_client,
+                used: quoted_today,
+            });
+        }
+        if self.max_open_quotes_per_client > 0 && open_quotes >= self.max_open_quotes_per_client {
+            return Err(RiskViolation::OpenQuotes {
+                limit: self.max_open_quotes_per_client,
+            });
+        }
+        if self.max_exposure > 0 && outstanding.saturating_add(amount_out) > self.max_exposure {
+            return Err(RiskViolation::Exposure {
+                limit: self.max_exposure,
+                outstanding,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The limit a refused quote would have broken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RiskViolation {
+    DailyVolume { limit: u64, used: u64 },
+    OpenQuotes { limit: usize },
+    Exposure { limit: u64, outstanding: u64 },
+}
+
+impl fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DailyVolume { limit, used } => write!(
+                f,
+                "daily volume limit of {} reached ({} quoted in the last 24 hours)",
+                limit, used
+            ),
+            Self::OpenQuotes { limit } => write!(f, "at most {} open quotes per client", limit),
+            Self::Exposure { limit, outstanding } => write!(
+                f,
+                "broker exposure limit of {} reached ({} outstanding)",
+                limit, outstanding
+            ),
+        }
+    }
+}
+
+/// Who a quote request counts against
+pub fn client_key(request: &SwapRequest) -> String {
+    match (&request.client_public_key, &request.client_id) {
+        (Some(pubkey), _) => hex::encode(pubkey),
+        (None, Some(id)) => id.clone(),
+        (None, None) => ANONYMOUS_CLIENT.to_string(),
+    }
+}
+
+/// Input amounts quoted to each client over the last 24 hours
+#[derive(Debug, Default)]
+pub struct VolumeTracker {
+    quoted: Mutex<HashMap<String, VecDeque<(u64, u64)>>>, // Unix time and amount, oldest first
+}
+
+impl VolumeTracker {
+    /// Amount quoted to `client` in the window ending at `now`
+    pub fn volume(&self, client: &str, now: u64) -> u64 {
+        let mut quoted = self.quoted.lock().expect("volume lock poisoned");
+        let Some(entries) = quoted.get_mut(client) else {
+            return 0;
+        };
+
+        let start = now.saturating_sub(VOLUME_WINDOW_SECS);
+        while entries.front().is_some_and(|(at, _)| *at <= start) {
+            entries.pop_front();
+        }
+        if entries.is_empty() {
+            quoted.remove(client);
+            return 0;
+        }
+        entries.iter().map(|(_, amount)| amount).sum()
+    }
+
+    /// Count a quote of `amount` against `client`
+    pub fn record(&self, client: &str, amount: u64, now: u64) {
+        self.quoted
+            .lock()
+            .expect("volume lock poisoned")
+            .entry(client.to_string())
+            .or_default()
+            .push_back((now, amount));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::QuoteType;
+
+    #[test]
+    fn test_limits() {
+        let limits = RiskLimits {
+            daily_volume_per_client: 1000,
+            max_open_quotes_per_client: 2,
+            max_exposure: 5000,
+        };
+
+        assert!(limits.check(900, 1, 4000, 100, 99).is_ok());
+        assert_eq!(
+            limits.check(901, 0, 0, 100, 99),
+            Err(RiskViolation::DailyVolume {
+                limit: 1000,
+                used: 901
+            })
+        );
+        assert_eq!(
+            limits.check(0, 2, 0, 100, 99),
+            Err(RiskViolation::OpenQuotes { limit: 2 })
+        );
+        assert!(matches!(
+            limits.check(0, 0, 4950, 100, 99),
+            Err(RiskViolation::Exposure { .. })
+        ));
+
+        // Zero turns a limit off
+        assert!(RiskLimits::default()
+            .check(u64::MAX, usize::MAX, u64::MAX, 100, 99)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_volume_window() {
+        let tracker = VolumeTracker::default();
+        tracker.record("alice", 300, 1_000);
+        tracker.record("alice", 200, 1_000 + VOLUME_WINDOW_SECS / 2);
+        tracker.record("bob", 50, 1_000);
+
+        assert_eq!(tracker.volume("alice", 1_000 + VOLUME_WINDOW_SECS / 2), 500);
+        assert_eq!(tracker.volume("bob", 1_000), 50);
+        assert_eq!(tracker.volume("carol", 1_000), 0);
+
+        // The first quote drops out of the window a day later
+        assert_eq!(tracker.volume("alice", 1_000 + VOLUME_WINDOW_SECS), 200);
+        assert_eq!(tracker.volume("bob", 1_000 + VOLUME_WINDOW_SECS), 0);
+    }
+
+    #[test]
+    fn test_client_key() {
+        let mut request = SwapRequest {
+            client_id: None,
+            from_mint: "http://mint-a.test".to_string(),
+            to_mint: "http://mint-b.test".to_string(),
+            amount: 100,
+            quote_type: QuoteType::ExactIn,
+            client_public_key: None,
+            adaptor_point: None,
+        };
+        assert_eq!(client_key(&request), ANONYMOUS_CLIENT);
+
+        request.client_id = Some("npub1alice".to_string());
+        assert_eq!(client_key(&request), "npub1alice");
+
+        request.client_public_key = Some(vec![0x02, 0xab]);
+        assert_eq!(client_key(&request), "02ab");
+    }
+}
@@ -0,0 +1,213 @@
+//! Proof selection
+//!
+//! Picking proofs largest-first overshoots most amounts, and the overshoot
+//! comes back as change from an extra swap at the mint. The default
+//! branch-and-bound strategy searches for a subset that adds up to the amount
+//! exactly and otherwise keeps the one with the least overshoot (fewest proofs
+//! on a tie). The search is capped, so with thousands of proofs it can settle
+//! for the best subset found so far, which is never worse than largest-first.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Subsets tried before the search settles for the best found so far
+const MAX_TRIES: usize = 100_000;
+
+/// How proofs are picked to cover an amount
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionStrategy {
+    /// Largest proofs first until the amount is covered
+    LargestFirst,
+    /// Exact match if one exists, otherwise the least overshoot
+    #[default]
+    BranchAndBound,
+}
+
+impl fmt::Display for SelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LargestFirst => write!(f, "largest_first"),
+            Self::BranchAndBound => write!(f, "branch_and_bound"),
+        }
+    }
+}
+
+impl FromStr for SelectionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "largest_first" => Ok(Self::LargestFirst),
+            "branch_and_bound" => Ok(Self::BranchAndBound),
+            other => Err(format!(
+                "unknown selection strategy {} (expected largest_first or branch_and_bound)",
+                other
+            )),
+        }
+    }
+}
+
+impl SelectionStrategy {
+    /// Indices into `amounts` of proofs that cover `target`
+    ///
+    /// Returns `None` when all of them together fall short.
+    pub fn select(&self, amounts: &[u64], target: u64) -> Option<Vec<usize>> {
+        // Largest first; the search relies on equal amounts being adjacent
+        let mut order: Vec<usize> = (0..amounts.len()).collect();
+        order.sort_by(|&a, &b| amounts[b].cmp(&amounts[a]));
+        let sorted: Vec<u64> = order.iter().map(|&i| amounts[i]).collect();
+
+        let greedy = largest_first(&sorted, target)?;
+        let selected = match self {
+            Self::LargestFirst => greedy,
+            Self::BranchAndBound => branch_and_bound(&sorted, target, greedy),
+        };
+
+        Some(selected.into_iter().map(|i| order[i]).collect())
+    }
+}
+
+fn largest_first(sorted: &[u64], target: u64) -> Option<Vec<usize>> {
+    let mut total = 0;
+    let mut selected = Vec::new();
+    for (i, &amount) in sorted.iter().enumerate() {
+        if total >= target {
+            break;
+        }
+        selected.push(i);
+        total += amount;
+    }
+    (total >= target).then_some(selected)
+}
+
+fn branch_and_bound(sorted: &[u64], target: u64, initial: Vec<usize>) -> Vec<usize> {
+    // remaining[i]: sum of sorted[i..], for pruning branches that can't reach the target
+    let mut remaining = vec![0; sorted.len() + 1];
+    for i in (0..sorted.len()).rev() {
+        remaining[i] = remaining[i + 1] + sorted[i];
+    }
+
+    let mut search = Search {
+        sorted,
+        remaining,
+        target,
+        best_total: initial.iter().map(|&i| sorted[i]).sum(),
+        best: initial,
+        current: Vec::new(),
+        tries: 0,
+    };
+    search.run(0, 0);
+    search.best
+}
+
+struct Search<'a> {
+    sorted: &'a [u64],
+    remaining: Vec<u64>,
+    target: u64,
+    best: Vec<usize>,
+    best_total: u64,
+    current: Vec<usize>,
+    tries: usize,
+}
+
+impl Search<'_> {
+    fn run(&mut self, index: usize, total: u64) {
+        // Stop once out of tries, or when nothing below can beat an exact match
+        let exact = self.best_total == self.target;
+        if self.tries >= MAX_TRIES || (exact && self.current.len() >= self.best.len()) {
+            return;
+        }
+        self.tries += 1;
+
+        if total >= self.target {
+            let fewer = total == self.best_total && self.current.len() < self.best.len();
+            if total < self.best_total || fewer {
+                self.best = self.current.clone();
+                self.best_total = total;
+            }
+            return;
+        }
+
+        if index == self.sorted.len() || total + self.remaining[index] < self.target {
+            return;
+        }
+
+        // Take this proof, unless that already overshoots the best subset
+        let amount = self.sorted[index];
+        if total + amount <= self.best_total {
+            self.current.push(index);
+            self.run(index + 1, total + amount);
+            self.current.pop();
+        }
+
+        // Leave it out, along with its equals: taking one of those instead
+        // would only repeat the subsets already tried
+        let mut next = index + 1;
+        while next < self.sorted.len() && self.sorted[next] == amount {
+            next += 1;
+        }
+        self.run(next, total);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total(amounts: &[u64], selected: &[usize]) -> u64 {
+        selected.iter().map(|&i| amounts[i]).sum()
+    }
+
+    #[test]
+    fn test_exact_match() {
+        let amounts = [64, 32, 8, 4, 2, 1, 1];
+
+        // Largest-first overshoots 37 with 64...
+        let greedy = SelectionStrategy::LargestFirst.select(&amounts, 37).unwrap();
+        assert_eq!(total(&amounts, &greedy), 64);
+
+        // ...while 32 + 4 + 1 is exact
+        let selected = SelectionStrategy::BranchAndBound.select(&amounts, 37).unwrap();
+        assert_eq!(total(&amounts, &selected), 37);
+        assert_eq!(selected.len(), 3);
+    }
+
+    #[test]
+    fn test_least_overshoot() {
+        // Nothing adds up to 10; 8 + 4 = 12 is closer than 16
+        let amounts = [16, 8, 4, 4];
+        let selected = SelectionStrategy::BranchAndBound.select(&amounts, 10).unwrap();
+        assert_eq!(total(&amounts, &selected), 12);
+
+        // On a tie the fewest proofs win
+        let amounts = [2, 2, 4];
+        let selected = SelectionStrategy::BranchAndBound.select(&amounts, 4).unwrap();
+        assert_eq!(selected, vec![2]);
+    }
+
+    #[test]
+    fn test_insufficient_and_large_sets() {
+        assert!(SelectionStrategy::BranchAndBound.select(&[4, 2], 7).is_none());
+        assert_eq!(SelectionStrategy::BranchAndBound.select(&[], 0), Some(vec![]));
+
+        // Thousands of small proofs still finish, and exactly
+        let amounts: Vec<u64> = (0..5_000).map(|i| 1 << (i % 4)).collect();
+        let selected = SelectionStrategy::BranchAndBound.select(&amounts, 1_001).unwrap();
+        assert_eq!(total(&amounts, &selected), 1_001);
+    }
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!(
+            "largest_first".parse::<SelectionStrategy>().unwrap(),
+            SelectionStrategy::LargestFirst
+        );
+        assert_eq!(
+            SelectionStrategy::BranchAndBound.to_string().parse::<SelectionStrategy>(),
+            Ok(SelectionStrategy::BranchAndBound)
+        );
+        assert!("random".parse::<SelectionStrategy>().is_err());
+    }
+}
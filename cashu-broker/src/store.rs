@@ -0,0 +1,340 @@
+//! Pluggable storage for the broker
+//!
+//! The broker works on open quotes and proofs in memory and writes through to
+//! a [`QuoteStore`] (quote status, per-quote keys and swap records), a
+//! [`LiquidityStore`] (liquidity history and snapshots) and a [`LedgerStore`]
+//! (fees earned and paid). Brokers sharing a database coordinate through a
+//! [`LeaseStore`]. [`Database`] implements all four on SQLite or Postgres;
+//! embedders can hand their own to
+//! [`BrokerBuilder`](crate::broker::BrokerBuilder). Without stores the broker
+//! keeps everything in memory and loses open swaps on restart.
+
+use crate::blacklist::BlacklistKind;
+use crate::db::{
+    BlacklistEntry, Database, Lease, LedgerEntry, LiquidityEvent, LiquiditySnapshot,
+    MintChange, PendingMint, QuoteKeys, QuoteRecord, SwapRecord,
+};
+use crate::error::Result;
+use crate::jobs::JobStatus;
+use crate::types::SwapStatus;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// Durable state of quotes and swaps, and the runtime settings that govern them
+#[async_trait]
+pub trait QuoteStore: Send + Sync {
+    async fn get_quote(&self, id: &str) -> Result<Option<QuoteRecord>>;
+
+    /// Pending and accepted quotes, oldest first
+    async fn list_open_quotes(&self) -> Result<Vec<QuoteRecord>>;
+
+    async fn update_quote_status(
+        &self,
+        id: &str,
+        status: SwapStatus,
+        error_message: Option<String>,
+    ) -> Result<()>;
+
+    /// Record the status of a quote's latest accept or complete step
+    async fn set_job_status(&self, id: &str, status: JobStatus) -> Result<()>;
+
+    /// Mark a quote completed and settle its swap record, atomically
+    async fn complete_quote(
+        &self,
+        quote_id: &str,
+        decrypted_signature: Option<&str>,
+        adaptor_secret: Option<&str>,
+    ) -> Result<()>;
+
+    /// Move a quote to a final status and delete its keys, atomically
+    async fn close_quote(
+        &self,
+        id: &str,
+        status: SwapStatus,
+        error_message: Option<String>,
+    ) -> Result<()>;
+
+    /// Move a pending quote's expiry out, once; false if it isn't pending,
+    /// already expired or was extended before
+    async fn extend_quote(&self, id: &str, expires_at: DateTime<Utc>) -> Result<bool>;
+
+    /// Mark pending quotes past their expiry as expired, returning their IDs
+    async fn expire_stale_quotes(&self) -> Result<Vec<String>>;
+
+    /// Store a quote's secret keys; implementations should encrypt them at rest
+    async fn save_quote_keys(&self, keys: &QuoteKeys) -> Result<()>;
+
+    async fn get_quote_keys(&self, quote_id: &str) -> Result<Option<QuoteKeys>>;
+
+    async fn set_quote_refund_at(&self, quote_id: &str, refund_at: i64) -> Result<()>;
+
+    /// Record the outputs locked for a quote (JSON) and the broker's adaptor
+    /// signature (hex), before the client has them; implementations should
+    /// encrypt the outputs at rest
+    async fn record_locked_outputs(
+        &self,
+        quote_id: &str,
+        refund_at: i64,
+        locked_proofs: &str,
+        encrypted_signature: &str,
+    ) -> Result<()>;
+
+    /// Record the client's verified adaptor signature (hex), before the broker
+    /// claims its proofs
+    async fn record_client_signature(&self, quote_id: &str, client_signature: &str) -> Result<()>;
+
+    async fn delete_quote_keys(&self, quote_id: &str) -> Result<()>;
+
+    async fn get_swap_by_quote(&self, quote_id: &str) -> Result<Option<SwapRecord>>;
+
+    async fn complete_swap(
+        &self,
+        id: &str,
+        target_proofs: &str,
+        decrypted_signature: Option<&str>,
+        adaptor_secret: Option<&str>,
+    ) -> Result<()>;
+
+    /// Whether the broker was left in maintenance mode
+    async fn maintenance_mode(&self) -> Result<bool>;
+
+    async fn set_maintenance_mode(&self, enabled: bool) -> Result<()>;
+
+    /// Mints added or removed at runtime, applied on top of the configured ones
+    async fn list_mint_changes(&self) -> Result<Vec<MintChange>>;
+
+    async fn record_mint_change(&self, change: &MintChange) -> Result<()>;
+
+    /// The ban on any of `values`, if one of them is banned
+    async fn find_blacklisted(
+        &self,
+        kind: BlacklistKind,
+        values: &[String],
+    ) -> Result<Option<BlacklistEntry>>;
+
+    /// Add a completed swap of `amount` to a client's volume
+    async fn record_client_volume(
+        &self,
+        pubkey: &str,
+        amount: u64,
+        at: DateTime<Utc>,
+    ) -> Result<()>;
+
+    /// Volume a client completed from the day of `since` on
+    async fn client_volume(&self, pubkey: &str, since: DateTime<Utc>) -> Result<u64>;
+}
+
+/// History and checkpoints of the broker's liquidity
+#[async_trait]
+pub trait LiquidityStore: Send + Sync {
+    async fn record_liquidity_event(&self, event: &LiquidityEvent) -> Result<()>;
+
+    async fn save_liquidity_snapshot(&self, snapshot: &LiquiditySnapshot) -> Result<()>;
+
+    /// Remember a mint quote paid while rebalancing that couldn't be minted yet
+    async fn save_pending_mint(&self, pending: &PendingMint) -> Result<()>;
+
+    async fn list_pending_mints(&self) -> Result<Vec<PendingMint>>;
+
+    /// Forget a pending mint once its ecash is minted
+    async fn delete_pending_mint(&self, quote_id: &str) -> Result<()>;
+}
+
+/// Accounting ledger of fees earned and paid
+#[async_trait]
+pub trait LedgerStore: Send + Sync {
+    async fn record_ledger_entry(&self, entry: &LedgerEntry) -> Result<()>;
+}
+
+/// Expiring leases that keep broker instances sharing a database apart
+#[async_trait]
+pub trait LeaseStore: Send + Sync {
+    /// Take lease `name` for `holder` for `ttl`, or renew it, unless another
+    /// holder has it; returns the lease as it stands afterwards
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: Duration) -> Result<Lease>;
+
+    /// Give up lease `name` if `holder` has it
+    async fn release_lease(&self, name: &str, holder: &str) -> Result<()>;
+}
+
+#[async_trait]
+impl QuoteStore for Database {
+    async fn get_quote(&self, id: &str) -> Result<Option<QuoteRecord>> {
+        Database::get_quote(self, id).await
+    }
+
+    async fn list_open_quotes(&self) -> Result<Vec<QuoteRecord>> {
+        Database::list_open_quotes(self).await
+    }
+
+    async fn update_quote_status(
+        &self,
+        id: &str,
+        status: SwapStatus,
+        error_message: Option<String>,
+    ) -> Result<()> {
+        Database::update_quote_status(self, id, status, error_message).await
+    }
+
+    async fn set_job_status(&self, id: &str, status: JobStatus) -> Result<()> {
+        Database::set_job_status(self, id, status).await
+    }
+
+    async fn complete_quote(
+        &self,
+        quote_id: &str,
+        decrypted_signature: Option<&str>,
+        adaptor_secret: Option<&str>,
+    ) -> Result<()> {
+        Database::complete_quote(self, quote_id, decrypted_signature, adaptor_secret).await
+    }
+
+    async fn close_quote(
+        &self,
+        id: &str,
+        status: SwapStatus,
+        error_message: Option<String>,
+    ) -> Result<()> {
+        Database::close_quote(self, id, status, error_message).await
+    }
+
+    async fn extend_quote(&self, id: &str, expires_at: DateTime<Utc>) -> Result<bool> {
+        Database::extend_quote(self, id, expires_at).await
+    }
+
+    async fn expire_stale_quotes(&self) -> Result<Vec<String>> {
+        Database::expire_stale_quotes(self).await
+    }
+
+    async fn save_quote_keys(&self, keys: &QuoteKeys) -> Result<()> {
+        Database::save_quote_keys(self, keys).await
+    }
+
+    async fn get_quote_keys(&self, quote_id: &str) -> Result<Option<QuoteKeys>> {
+        Database::get_quote_keys(self, quote_id).await
+    }
+
+    async fn set_quote_refund_at(&self, quote_id: &str, refund_at: i64) -> Result<()> {
+        Database::set_quote_refund_at(self, quote_id, refund_at).await
+    }
+
+    async fn record_locked_outputs(
+        &self,
+        quote_id: &str,
+        refund_at: i64,
+        locked_proofs: &str,
+        encrypted_signature: &str,
+    ) -> Result<()> {
+        Database::record_locked_outputs(
+            self,
+            quote_id,
+            refund_at,
+            locked_proofs,
+            encrypted_signature,
+        )
+        .await
+    }
+
+    async fn record_client_signature(&self, quote_id: &str, client_signature: &str) -> Result<()> {
+        Database::record_client_signature(self, quote_id, client_signature).await
+    }
+
+    async fn delete_quote_keys(&self, quote_id: &str) -> Result<()> {
+        Database::delete_quote_keys(self, quote_id).await
+    }
+
+    async fn get_swap_by_quote(&self, quote_id: &str) -> Result<Option<SwapRecord>> {
+        Database::get_swap_by_quote(self, quote_id).await
+    }
+
+    async fn complete_swap(
+        &self,
+        id: &str,
+        target_proofs: &str,
+        decrypted_signature: Option<&str>,
+        adaptor_secret: Option<&str>,
+    ) -> Result<()> {
+        Database::complete_swap(self, id, target_proofs, decrypted_signature, adaptor_secret).await
+    }
+
+    async fn maintenance_mode(&self) -> Result<bool> {
+        Database::maintenance_mode(self).await
+    }
+
+    async fn set_maintenance_mode(&self, enabled: bool) -> Result<()> {
+        Database::set_maintenance_mode(self, enabled).await
+    }
+
+    async fn list_mint_changes(&self) -> Result<Vec<MintChange>> {
+        Database::list_mint_changes(self).await
+    }
+
+    async fn record_mint_change(&self, change: &MintChange) -> Result<()> {
+        Database::record_mint_change(self, change).await
+    }
+
+    async fn find_blacklisted(
+        &self,
+        kind: BlacklistKind,
+        values: &[String],
+    ) -> Result<Option<BlacklistEntry>> {
+        Database::find_blacklisted(self, kind, values).await
+    }
+
+    async fn record_client_volume(
+        &self,
+        pubkey: &str,
+        amount: u64,
+        at: DateTime<Utc>,
+    ) -> Result<()> {
+        Database::record_client_volume(self, pubkey, amount, at).await
+    }
+
+    async fn client_volume(&self, pubkey: &str, since: DateTime<Utc>) -> Result<u64> {
+        Database::client_volume(self, pubkey, since).await
+    }
+}
+
+#[async_trait]
+impl LiquidityStore for Database {
+    async fn record_liquidity_event(&self, event: &LiquidityEvent) -> Result<()> {
+        Database::record_liquidity_event(self, event).await
+    }
+
+    async fn save_liquidity_snapshot(&self, snapshot: &LiquiditySnapshot) -> Result<()> {
+        Database::save_liquidity_snapshot(self, snapshot).await
+    }
+
+    async fn save_pending_mint(&self, pending: &PendingMint) -> Result<()> {
+        Database::save_pending_mint(self, pending).await
+    }
+
+    async fn list_pending_mints(&self) -> Result<Vec<PendingMint>> {
+        Database::list_pending_mints(self).await
+    }
+
+    async fn delete_pending_mint(&self, quote_id: &str) -> Result<()> {
+        Database::delete_pending_mint(self, quote_id).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LedgerStore for Database {
+    async fn record_ledger_entry(&self, entry: &LedgerEntry) -> Result<()> {
+        Database::record_ledger_entry(self, entry).await
+    }
+}
+
+#[async_trait]
+impl LeaseStore for Database {
+    async fn acquire_lease(&self, name: &str, holder: &str, ttl: Duration) -> Result<Lease> {
+        Database::acquire_lease(self, name, holder, ttl).await
+    }
+
+    async fn release_lease(&self, name: &str, holder: &str) -> Result<()> {
+        Database::release_lease(self, name, holder).await?;
+        Ok(())
+    }
+}
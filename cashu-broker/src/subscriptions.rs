@@ -0,0 +1,88 @@
+//! NUT-17 subscriptions to mints
+//!
+//! Rather than polling a mint for the state of a quote or a proof, the broker
+//! subscribes to its WebSocket notifications. Mints that don't advertise
+//! NUT-17 for a kind of notification are polled over HTTP by the CDK wallet
+//! instead, so callers don't need to know which a mint supports.
+//!
+//! Three things are watched: mint quotes the broker is waiting on, so minting
+//! after a rebalance, a deposit or for a swap starts as soon as the invoice is
+//! paid; the proofs the broker holds, so any a mint reports as spent are
+//! dropped from liquidity before they are picked for a payment or a swap; and
+//! the outputs of client-first swaps, whose witnesses reveal the adaptor
+//! secret once the client redeems them.
+
+use crate::error::{BrokerError, Result};
+use cdk::nuts::nut17::NotificationPayload;
+use cdk::nuts::{MintQuoteState, PublicKey, State};
+use cdk::wallet::subscription::ActiveSubscription;
+use cdk::wallet::{Wallet, WalletSubscription};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Wait until the mint reports `quote_id` paid, for at most `timeout`
+pub async fn wait_for_payment(wallet: &Wallet, quote_id: &str, timeout: Duration) -> Result<()> {
+    let mut subscription = wallet
+        .subscribe(WalletSubscription::Bolt11MintQuoteState(vec![
+            quote_id.to_string()
+        ]))
+        .await;
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        match tokio::time::timeout_at(deadline, subscription.recv()).await {
+            Ok(Some(NotificationPayload::MintQuoteBolt11Response(quote)))
+                if quote.quote == quote_id =>
+            {
+                if matches!(quote.state, MintQuoteState::Paid | MintQuoteState::Issued) {
+                    return Ok(());
+                }
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return Err(BrokerError::Cdk(format!(
+                    "Subscription to mint quote {} closed",
+                    quote_id
+                )))
+            }
+            Err(_) => {
+                return Err(BrokerError::Cdk(format!(
+                    "Mint quote {} was not paid within {:?}",
+                    quote_id, timeout
+                )))
+            }
+        }
+    }
+}
+
+/// Proofs a mint reports as spent, out of those subscribed to
+pub struct SpentProofs {
+    subscription: ActiveSubscription,
+}
+
+impl SpentProofs {
+    /// Subscribe to the state of the proofs with these Ys
+    pub async fn subscribe(wallet: &Wallet, ys: &[PublicKey]) -> Self {
+        let ys = ys.iter().map(|y| y.to_hex()).collect();
+        let subscription = wallet.subscribe(WalletSubscription::ProofState(ys)).await;
+        Self { subscription }
+    }
+
+    /// Y of the next proof reported spent, or `None` once the subscription ends
+    pub async fn next(&mut self) -> Option<PublicKey> {
+        self.next_spend().await.map(|(y, _)| y)
+    }
+
+    /// Like [`Self::next`], with the witness the proof was spent with if the
+    /// mint reports it
+    pub async fn next_spend(&mut self) -> Option<(PublicKey, Option<String>)> {
+        loop {
+            match self.subscription.recv().await? {
+                NotificationPayload::ProofState(proof) if proof.state == State::Spent => {
+                    return Some((proof.y, proof.witness))
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -2,75 +2,372 @@
 //!
 //! Handles atomic swap execution between Charlie (broker) and clients
 
-use crate::adaptor::AdaptorContext;
+use crate::adaptor::{encode_encrypted_signature, AdaptorContext, SecretScalar};
+use crate::binding;
+use crate::client_first;
+use crate::circuit_breaker::CircuitState;
+use crate::clock::{Clock, SystemClock};
 use crate::error::{BrokerError, Result};
-use crate::liquidity::LiquidityManager;
-use crate::types::{BrokerConfig, SwapExecution, SwapQuote, SwapRequest, SwapStatus};
+use crate::events::{BrokerEvent, EventBus};
+use crate::fees::Fee;
+use crate::ids;
+use crate::liquidity::{input_fee, LiquidityManager};
+use crate::pricing::{Inventory, InventorySkew, PricingStrategy};
+use crate::risk::{self, RiskLimits, VolumeTracker};
+use crate::rng::SwapRng;
+use crate::timeouts::MintOp;
+use crate::types::{
+    BrokerConfig, ClaimOrder, QuoteType, Sensitive, SwapExecution, SwapQuote, SwapRequest,
+    SwapStatus,
+};
+use cashu_swap_protocol::SwapTerms;
 use cdk::amount::SplitTarget;
-use cdk::nuts::{Proofs, PublicKey, SpendingConditions};
+use cdk::nuts::{Conditions, Proof, Proofs, PublicKey, SecretKey, SpendingConditions};
 use cdk::wallet::SendOptions;
 use cdk::Amount;
+use schnorr_fun::adaptor::EncryptedSignature;
 use schnorr_fun::fun::{Point, Scalar};
+use schnorr_fun::Signature;
 use std::collections::HashMap;
+use arc_swap::ArcSwap;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
-use tokio::sync::RwLock;
-use tracing::info;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{info, info_span, warn, Instrument};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// How long to wait for the broker's own mint quote to be paid
+const MINT_PAYMENT_TIMEOUT: Duration = Duration::from_secs(60);
 
 /// Coordinates atomic swap execution between broker and clients
 pub struct SwapCoordinator {
-    config: BrokerConfig,
+    config: ArcSwap<BrokerConfig>,
     adaptor_ctx: AdaptorContext,
-    quotes: Arc<RwLock<HashMap<String, QuoteData>>>,
+    quotes: Arc<RwLock<HashMap<String, Arc<QuoteEntry>>>>,
     executions: Arc<RwLock<HashMap<String, SwapExecution>>>,
+    events: EventBus,
+    volume: VolumeTracker,
+    pricing: Arc<dyn PricingStrategy>,
+    rng: SwapRng,
+    clock: Arc<dyn Clock>,
+}
+
+/// A quote held in memory
+///
+/// The map of quotes is only locked to look entries up, add or remove them.
+/// Each quote's data sits behind its own lock, which accepting, completing or
+/// refunding it holds across the mint calls involved, so swaps on different
+/// quotes go ahead in parallel while steps on the same quote take turns.
+/// What the risk check needs of every quote is kept outside that lock.
+struct QuoteEntry {
+    client: Option<String>,
+    output_amount: u64,
+    data: Mutex<QuoteData>,
+}
+
+impl QuoteEntry {
+    fn new(data: QuoteData) -> Arc<Self> {
+        Arc::new(Self {
+            client: data.client.clone(),
+            output_amount: data.quote.output_amount,
+            data: Mutex::new(data),
+        })
+    }
+
+    /// Whether the quote is pending or accepted; one that is busy is taken
+    /// to be, since it is being accepted, completed or refunded
+    fn is_open(&self) -> bool {
+        self.data.try_lock().map_or(true, |data| {
+            matches!(data.quote.status, SwapStatus::Pending | SwapStatus::Accepted)
+        })
+    }
 }
 
 /// Internal quote data with private keys
 struct QuoteData {
     pub quote: SwapQuote,
-    pub broker_swap_key: Scalar,
-    pub adaptor_secret: Scalar,
+    pub client: Option<String>, // Who the quote counts against for risk limits (unknown once restored)
+    pub broker_swap_key: SecretScalar,
+    pub adaptor_secret: Option<SecretScalar>, // The client's in a client-first swap, once recovered
+    pub client_pubkey: Option<Vec<u8>>,
+    pub encrypted_signature: Option<EncryptedSignature>,
+    pub refund_at: Option<u64>, // Unix time after which the broker's refund key can spend its outputs
+    pub client_signature: Option<EncryptedSignature>, // Verified at complete, the claim is owed
+}
+
+impl QuoteData {
+    /// The adaptor secret, which only a broker-first quote has from the start
+    fn adaptor_secret(&self) -> Result<Scalar> {
+        self.adaptor_secret
+            .as_ref()
+            .map(SecretScalar::scalar)
+            .ok_or_else(|| {
+                BrokerError::InvalidSwapRequest(format!(
+                    "Quote {} is client-first: it completes once the client redeems its outputs",
+                    self.quote.quote_id
+                ))
+            })
+    }
+
+    /// T, whoever holds its secret
+    fn adaptor_point(&self) -> Result<Point> {
+        compressed_bytes_to_point(&self.quote.adaptor_point)
+    }
+}
+
+/// Broker's side of an accepted swap
+#[derive(Clone)]
+pub struct PreparedSwap {
+    /// P2PK proofs locked to the client's tweaked key (P_client + T)
+    pub proofs: Proofs,
+    /// Broker's adaptor signature over the swap transcript, encrypted under T
+    pub encrypted_signature: EncryptedSignature,
+    /// For a client-first swap, the broker's witness signature for each of
+    /// `proofs`, encrypted under T
+    pub output_signatures: Vec<EncryptedSignature>,
+    /// Target mint input fees the broker covers on top of the output
+    pub mint_fee: u64,
+}
+
+impl std::fmt::Debug for PreparedSwap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PreparedSwap")
+            .field("proofs", &Sensitive(&self.proofs))
+            .field("encrypted_signature", &self.encrypted_signature_hex())
+            .field("output_signatures", &self.output_signatures_hex())
+            .field("mint_fee", &self.mint_fee)
+            .finish()
+    }
+}
+
+impl PreparedSwap {
+    /// Encrypted signature as hex, as returned by the API
+    pub fn encrypted_signature_hex(&self) -> String {
+        hex::encode(encode_encrypted_signature(&self.encrypted_signature))
+    }
+
+    /// Output signatures as hex, as returned by the API
+    pub fn output_signatures_hex(&self) -> Vec<String> {
+        self.output_signatures
+            .iter()
+            .map(|sig| hex::encode(encode_encrypted_signature(sig)))
+            .collect()
+    }
+}
+
+/// Private keys of a quote, exported for persistence; wiped when dropped
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct QuoteSecrets {
+    pub broker_swap_key: Vec<u8>,
+    /// Empty for a client-first quote until the client's claim reveals it
+    pub adaptor_secret: Vec<u8>,
+    /// Unix time after which the broker can refund its locked outputs
+    pub refund_at: Option<u64>,
+}
+
+/// Canonical swap transcript signed by the broker's adaptor signature
+///
+/// Binds the quote terms to the client key the broker locked funds to:
+/// `quote_id|from_mint|to_mint|input_amount|output_amount|adaptor_point|client_pubkey`
+/// (see [`cashu_swap_protocol::swap_transcript`], which clients build it with).
+pub fn swap_transcript(quote: &SwapQuote, client_pubkey: &[u8]) -> Vec<u8> {
+    let terms = SwapTerms {
+        quote_id: &quote.quote_id,
+        source_mint: &quote.from_mint,
+        target_mint: &quote.to_mint,
+        amount_in: quote.input_amount,
+        amount_out: quote.output_amount,
+        adaptor_point: &quote.adaptor_point,
+    };
+    cashu_swap_protocol::swap_transcript(&terms, client_pubkey)
 }
 
 impl SwapCoordinator {
     /// Create a new swap coordinator
     pub fn new(config: BrokerConfig) -> Self {
         Self {
-            config,
+            config: ArcSwap::from_pointee(config),
             adaptor_ctx: AdaptorContext::new(),
             quotes: Arc::new(RwLock::new(HashMap::new())),
             executions: Arc::new(RwLock::new(HashMap::new())),
+            events: EventBus::default(),
+            volume: VolumeTracker::default(),
+            pricing: Arc::new(InventorySkew::default()),
+            rng: SwapRng::os(),
+            clock: Arc::new(SystemClock),
         }
     }
 
-    /// Generate a swap quote for a client request
+    /// Announce quote and swap lifecycle changes on `events`
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Set the fee rate of new quotes with `pricing`
+    pub fn with_pricing(mut self, pricing: Arc<dyn PricingStrategy>) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Draw swap keys, adaptor secrets and quote IDs from `rng`
+    pub fn with_rng(mut self, rng: SwapRng) -> Self {
+        self.adaptor_ctx = AdaptorContext::new().with_rng(rng.clone());
+        self.rng = rng;
+        self
+    }
+
+    /// Stamp, expire and extend quotes by `clock`
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Clock quotes are stamped and expired by
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    /// Snapshot of the current configuration
+    ///
+    /// A quote is priced from a single snapshot, so it never mixes settings
+    /// from before and after a reload.
+    pub fn config(&self) -> Arc<BrokerConfig> {
+        self.config.load_full()
+    }
+
+    /// Replace the configuration used for new quotes
+    ///
+    /// Quotes that were already issued keep the terms they were created with.
+    pub fn update_config(&self, config: BrokerConfig) {
+        self.config.store(Arc::new(config));
+    }
+
+    /// Atomically derive a new configuration from the current one
+    pub fn modify_config<F>(&self, f: F)
+    where
+        F: Fn(&BrokerConfig) -> BrokerConfig,
+    {
+        self.config.rcu(|current| f(current));
+    }
+
+    /// Generate a swap quote for a client request between mints of the same unit
     pub async fn create_quote(
         &self,
         request: SwapRequest,
         liquidity: &LiquidityManager,
     ) -> Result<SwapQuote> {
-        // Validate request
-        self.validate_swap_request(&request).await?;
-
-        // Calculate fee and output amount
-        let fee = ((request.amount as f64) * self.config.fee_rate).ceil() as u64;
-        let output_amount = request.amount.saturating_sub(fee);
-
-        // Check liquidity
-        if !liquidity.can_swap(&request.to_mint, output_amount).await {
-            return Err(BrokerError::InsufficientLiquidity {
-                mint_url: request.to_mint.clone(),
-                needed: output_amount,
-                available: liquidity.get_balance(&request.to_mint).await,
-            });
+        self.create_quote_at_rate(request, None, 0, liquidity).await
+    }
+
+    /// Generate a swap quote, converting the output at `exchange_rate`
+    ///
+    /// The rate is in target units per source unit with any spread already
+    /// applied. It is required when the two mints use different units.
+    /// `volume` is what the client swapped over the volume discount window.
+    pub async fn create_quote_at_rate(
+        &self,
+        request: SwapRequest,
+        exchange_rate: Option<f64>,
+        volume: u64,
+        liquidity: &LiquidityManager,
+    ) -> Result<SwapQuote> {
+        let config = self.config();
+        let inventory = Self::inventory(&config, &request.to_mint, liquidity).await;
+        let fee_rate = config.fee_rate_for(&request.from_mint, &request.to_mint, request.amount);
+        let fee_rate = config.fees.discounted(fee_rate, volume);
+        let fee_rate = self.pricing.fee_rate(fee_rate, &inventory);
+        let broker_fee = Fee::new(fee_rate, config.fees.min_fee);
+
+        // Validate request against the amounts before mint fees. An amount
+        // too small to pay the fee is reported against the pair's minimum first.
+        let amounts = match exchange_rate {
+            Some(rate) => {
+                quote_amounts_at_rate(request.quote_type, request.amount, broker_fee, rate)
+            }
+            None => quote_amounts(request.quote_type, request.amount, broker_fee),
+        };
+        let input_amount = match amounts {
+            Ok((input_amount, _, _)) => input_amount,
+            Err(e) => {
+                self.validate_swap_request(&request, request.amount, &config).await?;
+                return Err(e);
+            }
+        };
+        self.validate_swap_request(&request, input_amount, &config).await?;
+
+        // In a client-first swap the client holds the adaptor secret and sends T
+        let client_adaptor_point = match &request.adaptor_point {
+            Some(bytes) => {
+                if request.client_public_key.is_none() {
+                    return Err(BrokerError::InvalidSwapRequest(
+                        "A client-first swap needs the client's public key".to_string(),
+                    ));
+                }
+                Some(compressed_bytes_to_point(bytes).map_err(|_| {
+                    BrokerError::InvalidSwapRequest("Invalid adaptor point".to_string())
+                })?)
+            }
+            None => None,
+        };
+
+        // Don't quote into a mint that won't be able to complete the swap
+        for mint_url in [&request.from_mint, &request.to_mint] {
+            if !liquidity.is_healthy(mint_url) {
+                return Err(BrokerError::MintUnhealthy(mint_url.clone()));
+            }
+            if liquidity.circuit_breaker().state(mint_url) == CircuitState::Open {
+                return Err(BrokerError::MintUnavailable(mint_url.clone()));
+            }
         }
 
-        // Generate adaptor secret and point
-        let adaptor_secret = self.adaptor_ctx.generate_adaptor_secret();
-        let adaptor_point = self.adaptor_ctx.adaptor_point_from_secret(&adaptor_secret);
+        let from_unit = config.unit_of(&request.from_mint).unwrap_or_default();
+        let to_unit = config.unit_of(&request.to_mint).unwrap_or_default();
+        if exchange_rate.is_none() && from_unit != to_unit {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Swaps from {} to {} need an exchange rate",
+                from_unit, to_unit
+            )));
+        }
+
+        // Calculate fees and the amounts on both sides, including what the
+        // mints charge to spend the proofs each side receives
+        let source_fees = liquidity.keyset_fees(&request.from_mint).await?;
+        let target_fees = liquidity.keyset_fees(&request.to_mint).await?;
+        let (input_amount, fee, mint_fee, output_amount) = quote_amounts_with_mint_fees(
+            request.quote_type,
+            request.amount,
+            broker_fee,
+            exchange_rate,
+            source_fees.active_fee_ppk,
+            target_fees.active_fee_ppk,
+        )?;
+
+        // Check the risk limits and hold the output amount for this quote until
+        // it completes or expires. The quotes stay locked until the new one is
+        // stored, so concurrent requests can't get past the limits together.
+        let client = risk::client_key(&request);
+        let mut quotes = self.quotes.write().await;
+        self.check_risk(&quotes, &config.risk, &client, input_amount, output_amount)?;
+
+        let quote_id = self.generate_quote_id();
+        liquidity
+            .reserve(&request.to_mint, &quote_id, output_amount)
+            .await?;
+        self.volume.record(&client, input_amount, self.clock.unix_now());
+
+        // Generate adaptor secret and point, unless the client brought T
+        let (adaptor_secret, adaptor_point, claim_order) = match client_adaptor_point {
+            Some(point) => (None, point, ClaimOrder::ClientFirst),
+            None => {
+                let secret = self.adaptor_ctx.generate_adaptor_secret();
+                let point = self.adaptor_ctx.adaptor_point_from_secret(&secret);
+                (Some(secret), point, ClaimOrder::BrokerFirst)
+            }
+        };
 
         // Generate broker's swap key
-        let broker_swap_key = Scalar::random(&mut rand::thread_rng());
+        let broker_swap_key = self.rng.scalar();
         // TODO: Fix - secp256kfun 0.11 changed Point multiplication API
         let broker_pubkey_point = self.adaptor_ctx.adaptor_point_from_secret(&broker_swap_key);
 
@@ -83,54 +380,89 @@ impl SwapCoordinator {
         let tweaked_pubkey_point = self.adaptor_ctx.tweak_public_key(&broker_pubkey_point, &adaptor_point);
         let tweaked_pubkey_bytes = point_to_compressed_bytes(&tweaked_pubkey_point);
 
-        let expires_at = SystemTime::now() + Duration::from_secs(self.config.quote_expiry_seconds);
+        // Prove to the client that T is exactly the tweak applied to the broker key.
+        // A client that picked T itself has nothing to check.
+        let dleq_proof = adaptor_secret.as_ref().map(|secret| {
+            self.adaptor_ctx.create_dleq_proof(
+                secret,
+                &broker_pubkey_point,
+                &tweaked_pubkey_point,
+                quote_id.as_bytes(),
+            )
+        });
+
+        let expires_at = self.clock.now() + Duration::from_secs(config.quote_expiry_seconds);
 
         let quote = SwapQuote {
-            quote_id: Self::generate_quote_id(),
+            quote_id,
             from_mint: request.from_mint,
             to_mint: request.to_mint,
-            input_amount: request.amount,
+            from_unit,
+            to_unit,
+            input_amount,
             output_amount,
             fee,
-            fee_rate: self.config.fee_rate,
+            fee_rate,
+            mint_fee,
+            exchange_rate,
             broker_public_key: broker_pubkey_bytes,
             adaptor_point: adaptor_point_bytes,
             tweaked_pubkey: Some(tweaked_pubkey_bytes),
-            adaptor_secret: scalar_to_bytes(&adaptor_secret),
-            expires_in: self.config.quote_expiry_seconds,
+            dleq_proof: dleq_proof.map(|proof| proof.to_bytes()),
+            expires_in: config.quote_expiry_seconds,
+            expiry: self.clock.unix_now() + config.quote_expiry_seconds,
             expires_at: Some(expires_at),
+            quote_signature: None,
+            claim_order,
+            extended: false,
             status: SwapStatus::Pending,
         };
 
         info!(
-            "Quote {}: {} → {} sats (fee: {})",
-            quote.quote_id, request.amount, output_amount, fee
+            "Quote {}: {} → {} sats (fee: {}, mint fees: {})",
+            quote.quote_id, input_amount, output_amount, fee, mint_fee
         );
 
         // Store quote with private keys
         let quote_data = QuoteData {
             quote: quote.clone(),
-            broker_swap_key,
-            adaptor_secret,
+            client: Some(client),
+            broker_swap_key: SecretScalar::new(&broker_swap_key),
+            adaptor_secret: adaptor_secret.as_ref().map(SecretScalar::new),
+            client_pubkey: None,
+            encrypted_signature: None,
+            refund_at: None,
+            client_signature: None,
         };
 
-        let mut quotes = self.quotes.write().await;
-        quotes.insert(quote.quote_id.clone(), quote_data);
+        quotes.insert(quote.quote_id.clone(), QuoteEntry::new(quote_data));
+
+        self.events.emit(BrokerEvent::QuoteCreated {
+            quote_id: quote.quote_id.clone(),
+            source_mint: quote.from_mint.clone(),
+            target_mint: quote.to_mint.clone(),
+            amount_in: input_amount,
+            amount_out: output_amount,
+        });
 
         Ok(quote)
     }
 
     /// Prepare broker's side of the swap (mint locked tokens)
+    ///
+    /// Also produces the broker's adaptor signature over the swap transcript,
+    /// encrypted under the quote's adaptor point. A client-first swap's outputs
+    /// are locked to the client and the broker together instead, and come with
+    /// the broker's witness signatures encrypted under T (see
+    /// [`client_first`]).
     pub async fn prepare_swap(
         &self,
         quote_id: &str,
         client_pubkey: &[u8],
         liquidity: &LiquidityManager,
-    ) -> Result<Proofs> {
-        let mut quotes = self.quotes.write().await;
-        let quote_data = quotes
-            .get_mut(quote_id)
-            .ok_or_else(|| BrokerError::QuoteNotFound(quote_id.to_string()))?;
+    ) -> Result<PreparedSwap> {
+        let entry = self.entry(quote_id).await?;
+        let mut quote_data = entry.data.lock().await;
 
         if quote_data.quote.status != SwapStatus::Pending {
             return Err(BrokerError::InvalidSwapRequest(format!(
@@ -141,9 +473,7 @@ impl SwapCoordinator {
 
         // Parse client pubkey and compute tweaked key: client + T
         let client_point = compressed_bytes_to_point(client_pubkey)?;
-        let adaptor_point =
-            self.adaptor_ctx
-                .adaptor_point_from_secret(&quote_data.adaptor_secret);
+        let adaptor_point = quote_data.adaptor_point()?;
         let client_tweaked = self.adaptor_ctx.tweak_public_key(&client_point, &adaptor_point);
         let client_tweaked_bytes = point_to_compressed_bytes(&client_tweaked);
 
@@ -153,40 +483,68 @@ impl SwapCoordinator {
         );
 
         // Get wallet and mint tokens
-        let wallet = liquidity.get_wallet(&quote_data.quote.to_mint)?;
-
-        // Step 1: Mint tokens (broker pays Lightning invoice)
-        let mint_amount = Amount::from(quote_data.quote.output_amount);
-        let mint_quote = wallet.mint_quote(mint_amount, None).await
-            .map_err(|e| BrokerError::Cdk(format!("Failed to create mint quote: {:?}", e)))?;
+        let to_mint = quote_data.quote.to_mint.clone();
+        let wallet = liquidity.get_wallet(&to_mint)?;
+
+        // Step 1: Mint tokens (broker pays Lightning invoice). Besides the
+        // output this covers the target mint's input fees for locking the
+        // tokens and for the client redeeming them, which the quote's mint fee
+        // charged for. Any change stays in the wallet.
+        let output_amount = quote_data.quote.output_amount;
+        let fee_ppk = liquidity.keyset_fees(&to_mint).await?.active_fee_ppk;
+        let send_amount = output_amount + input_fee(output_amount, fee_ppk);
+        let mint_amount = Amount::from(send_amount + input_fee(send_amount, fee_ppk));
+        let mint_quote = liquidity
+            .mint_call(&to_mint, MintOp::MintQuote, "Failed to create mint quote", || {
+                wallet.mint_quote(mint_amount, None)
+            })
+            .await?;
 
         // Wait for quote to complete (in production, this would be paid via Lightning)
         // The minted tokens are automatically added to the wallet's balance
-        let _minted_proofs = wallet
-            .wait_and_mint_quote(
-                mint_quote,
-                Default::default(),
-                Default::default(),
-                std::time::Duration::from_secs(60),
+        let _minted_proofs = liquidity
+            .mint_when_paid(
+                &to_mint,
+                &mint_quote.id,
+                MINT_PAYMENT_TIMEOUT,
+                "Failed to mint tokens",
             )
-            .await
-            .map_err(|e| BrokerError::Cdk(format!("Failed to mint tokens: {:?}", e)))?;
-
-        // Step 2: Lock the minted tokens to the tweaked pubkey (P + T)
-        // Create PublicKey from tweaked point bytes
-        let tweaked_pubkey = PublicKey::from_slice(&client_tweaked_bytes)
-            .map_err(|e| BrokerError::Cdk(format!("Failed to create public key: {:?}", e)))?;
-
-        // Create P2PK spending conditions
-        let spending_conditions = SpendingConditions::new_p2pk(tweaked_pubkey, None);
+            .await?;
+
+        // Step 2: Lock the minted tokens to the tweaked pubkey (P + T).
+        // Create P2PK spending conditions. After the locktime the broker can
+        // reclaim the outputs with its swap key if the client never redeems them.
+        let refund_at = self.clock.unix_now() + self.config().refund_locktime_seconds;
+        let refund_pubkey = SecretKey::from_slice(&quote_data.broker_swap_key.to_bytes())
+            .map_err(|e| BrokerError::Cdk(format!("Failed to create refund key: {:?}", e)))?
+            .public_key();
+        let spending_conditions = match quote_data.quote.claim_order {
+            ClaimOrder::BrokerFirst => {
+                let tweaked_pubkey = PublicKey::from_slice(&client_tweaked_bytes).map_err(|e| {
+                    BrokerError::Cdk(format!("Failed to create public key: {:?}", e))
+                })?;
+                let conditions = Conditions {
+                    locktime: Some(refund_at),
+                    refund_keys: Some(vec![refund_pubkey]),
+                    ..Default::default()
+                };
+                SpendingConditions::new_p2pk(tweaked_pubkey, Some(conditions))
+            }
+            // The client redeems with its key and the broker's decrypted signature
+            ClaimOrder::ClientFirst => {
+                let client_key = PublicKey::from_slice(client_pubkey)
+                    .map_err(|e| BrokerError::Cdk(format!("Invalid client key: {:?}", e)))?;
+                client_first::output_conditions(client_key, refund_pubkey, refund_at)
+            }
+        };
 
         // Use prepare_send to create tokens locked to the tweaked pubkey
         let prepared_send = wallet
             .prepare_send(
-                mint_amount,
+                Amount::from(output_amount),
                 SendOptions {
                     conditions: Some(spending_conditions),
-                    include_fee: false, // No additional fee for internal send
+                    include_fee: true, // Cover the target mint's fee for redeeming the outputs
                     ..Default::default()
                 },
             )
@@ -197,22 +555,35 @@ impl SwapCoordinator {
         let token = prepared_send.confirm(None).await
             .map_err(|e| BrokerError::Cdk(format!("Failed to create locked tokens: {:?}", e)))?;
 
-        // Get keysets from wallet to extract proofs from token
-        let keysets = wallet.get_mint_keysets().await
-            .map_err(|e| BrokerError::Cdk(format!("Failed to get keysets: {:?}", e)))?;
-
         // Extract proofs from token
-        let proofs = token.proofs(&keysets)
-            .map_err(|e| BrokerError::Cdk(format!("Failed to extract proofs from token: {:?}", e)))?;
+        let proofs = liquidity.token_proofs(&to_mint, &token).await?;
+
+        // Step 3: Sign the swap transcript with an adaptor signature under T
+        let transcript = swap_transcript(&quote_data.quote, client_pubkey);
+        let encrypted_signature = self.adaptor_ctx.create_encrypted_signature(
+            &quote_data.broker_swap_key.scalar(),
+            &adaptor_point,
+            &transcript,
+        )?;
+        let output_signatures = self.sign_outputs(&quote_data, &proofs)?;
 
         // Update quote status
         quote_data.quote.status = SwapStatus::Accepted;
+        quote_data.client_pubkey = Some(client_pubkey.to_vec());
+        quote_data.encrypted_signature = Some(encrypted_signature.clone());
+        quote_data.refund_at = Some(refund_at);
+
+        // The output is now committed to this swap until it completes or is refunded
+        liquidity.lock(quote_id).await;
+        self.events.emit(BrokerEvent::QuoteAccepted {
+            quote_id: quote_id.to_string(),
+        });
 
         // Store execution details
         let execution = SwapExecution {
             quote_id: quote_id.to_string(),
-            client_tokens: vec![],
-            broker_tokens: serialize_proofs(&proofs),
+            client_tokens: Sensitive::default(),
+            broker_tokens: serialize_proofs(&proofs).into(),
             client_swap_complete: false,
             broker_swap_complete: false,
             completed_at: None,
@@ -223,55 +594,318 @@ impl SwapCoordinator {
 
         info!("Broker locked {} sats for swap {}", quote_data.quote.output_amount, quote_id);
 
-        Ok(proofs)
+        Ok(PreparedSwap {
+            proofs,
+            encrypted_signature,
+            output_signatures,
+            mint_fee: u64::from(mint_amount) - output_amount,
+        })
+    }
+
+    /// The broker's side of a quote already accepted by `client_pubkey`
+    ///
+    /// Lets a client that never heard back from its accept get the same
+    /// outputs and adaptor signature again, rather than a second batch.
+    pub async fn prepared_swap(
+        &self,
+        quote_id: &str,
+        client_pubkey: &[u8],
+    ) -> Option<PreparedSwap> {
+        let entry = self.entry(quote_id).await.ok()?;
+        let quote_data = entry.data.lock().await;
+        if quote_data.quote.status != SwapStatus::Accepted
+            || quote_data.client_pubkey.as_deref() != Some(client_pubkey)
+        {
+            return None;
+        }
+
+        let proofs = self
+            .executions
+            .read()
+            .await
+            .get(quote_id)
+            .and_then(|e| serde_json::from_slice::<Proofs>(e.broker_tokens.expose()).ok())?;
+        let output_signatures = self.sign_outputs(&quote_data, &proofs).ok()?;
+        Some(PreparedSwap {
+            proofs,
+            encrypted_signature: quote_data.encrypted_signature.clone()?,
+            output_signatures,
+            mint_fee: quote_data.quote.mint_fee,
+        })
+    }
+
+    /// The broker's encrypted witness signatures for a client-first quote's
+    /// locked outputs, as handed out at accept; empty for other quotes
+    pub async fn output_signatures(
+        &self,
+        quote_id: &str,
+        proofs: &Proofs,
+    ) -> Result<Vec<EncryptedSignature>> {
+        let entry = self.entry(quote_id).await?;
+        let quote_data = entry.data.lock().await;
+        self.sign_outputs(&quote_data, proofs)
+    }
+
+    /// Unix time after which the broker can refund an accepted quote's
+    /// locked outputs; `None` before the accept
+    pub async fn refund_at(&self, quote_id: &str) -> Result<Option<u64>> {
+        let entry = self.entry(quote_id).await?;
+        let refund_at = entry.data.lock().await.refund_at;
+        Ok(refund_at)
+    }
+
+    fn sign_outputs(
+        &self,
+        quote_data: &QuoteData,
+        proofs: &Proofs,
+    ) -> Result<Vec<EncryptedSignature>> {
+        match quote_data.quote.claim_order {
+            ClaimOrder::BrokerFirst => Ok(Vec::new()),
+            ClaimOrder::ClientFirst => client_first::output_signatures(
+                &self.adaptor_ctx,
+                &quote_data.broker_swap_key.scalar(),
+                &quote_data.adaptor_point()?,
+                proofs,
+            ),
+        }
+    }
+
+    /// Accepted client-first swaps waiting on the client to redeem the outputs
+    ///
+    /// Each comes with its target mint and the outputs the broker locked.
+    /// Quotes busy being claimed or refunded are left out.
+    pub async fn awaiting_client_claims(&self) -> Vec<(String, String, Proofs)> {
+        let mut awaiting = Vec::new();
+
+        for entry in self.entries().await {
+            let Ok(quote_data) = entry.data.try_lock() else {
+                continue;
+            };
+            if quote_data.quote.status != SwapStatus::Accepted
+                || quote_data.quote.claim_order != ClaimOrder::ClientFirst
+            {
+                continue;
+            }
+
+            let quote_id = quote_data.quote.quote_id.clone();
+            let locked_proofs = self
+                .executions
+                .read()
+                .await
+                .get(&quote_id)
+                .and_then(|e| serde_json::from_slice::<Proofs>(e.broker_tokens.expose()).ok());
+            if let Some(proofs) = locked_proofs {
+                awaiting.push((quote_id, quote_data.quote.to_mint.clone(), proofs));
+            }
+        }
+
+        awaiting
+    }
+
+    /// Complete a client-first swap whose outputs the client redeemed
+    ///
+    /// `redeemed` pairs spent outputs with the witnesses the target mint
+    /// reports for them. The adaptor secret is recovered from the broker's
+    /// decrypted signature in any of them, then the client's proofs are
+    /// claimed with it. Returns `None` without claiming when no witness
+    /// reveals the secret, as when the broker refunded the outputs itself.
+    pub async fn claim_after_client(
+        &self,
+        quote_id: &str,
+        redeemed: &[(Proof, String)],
+        client_proofs: Proofs,
+        liquidity: &LiquidityManager,
+    ) -> Result<Option<u64>> {
+        let entry = self.entry(quote_id).await?;
+        let mut quote_data = entry.data.lock().await;
+
+        if quote_data.quote.status != SwapStatus::Accepted
+            || quote_data.quote.claim_order != ClaimOrder::ClientFirst
+        {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Quote {} is not an accepted client-first swap",
+                quote_id
+            )));
+        }
+
+        let broker_swap_key = quote_data.broker_swap_key.scalar();
+        let adaptor_point = quote_data.adaptor_point()?;
+        let Some(adaptor_secret) = redeemed.iter().find_map(|(proof, witness)| {
+            client_first::recover_secret(
+                &self.adaptor_ctx,
+                &broker_swap_key,
+                &adaptor_point,
+                proof,
+                witness,
+            )
+        }) else {
+            return Ok(None);
+        };
+
+        // A failed claim is retried on the next poll, as the witnesses stay
+        quote_data.adaptor_secret = Some(SecretScalar::new(&adaptor_secret));
+
+        let amount = self
+            .claim_client_proofs(&mut quote_data, &adaptor_secret, client_proofs, liquidity)
+            .await?;
+        Ok(Some(amount))
+    }
+
+    /// Close an accepted swap whose outputs were spent by the broker's refund
+    ///
+    /// For a refund that went through without being recorded, as across a
+    /// restart.
+    pub async fn close_refunded(&self, quote_id: &str, liquidity: &LiquidityManager) -> Result<()> {
+        let entry = self.entry(quote_id).await?;
+        let mut quote_data = entry.data.lock().await;
+        if quote_data.quote.status == SwapStatus::Accepted {
+            quote_data.quote.status = SwapStatus::Expired;
+            liquidity.release(quote_id).await;
+        }
+        Ok(())
+    }
+
+    /// Check the client's encrypted signature for an accepted quote, without
+    /// claiming anything
+    pub async fn verify_completion(
+        &self,
+        quote_id: &str,
+        client_encrypted_signature: &EncryptedSignature,
+    ) -> Result<()> {
+        let entry = self.entry(quote_id).await?;
+        let quote_data = entry.data.lock().await;
+        self.verify_client_signature(&quote_data, client_encrypted_signature)
+            .map(|_| ())
     }
 
     /// Complete swap after client provides their tokens with witness
+    ///
+    /// The client authorizes the swap with its own adaptor signature over the
+    /// swap transcript, encrypted under T. The broker only claims the client's
+    /// tokens once that signature verifies and decrypts under the quote's
+    /// adaptor point. Returns the decrypted client signature and the amount
+    /// received after the source mint's input fee.
     pub async fn complete_swap(
         &self,
         quote_id: &str,
         client_proofs_with_witness: Proofs,
+        client_encrypted_signature: &EncryptedSignature,
         liquidity: &LiquidityManager,
-    ) -> Result<()> {
-        let quotes = self.quotes.read().await;
-        let quote_data = quotes
-            .get(quote_id)
-            .ok_or_else(|| BrokerError::QuoteNotFound(quote_id.to_string()))?;
+    ) -> Result<(Signature, u64)> {
+        let entry = self.entry(quote_id).await?;
+        let mut quote_data = entry.data.lock().await;
+
+        let broker_swap_key = &quote_data.broker_swap_key.scalar();
+        let adaptor_secret = &quote_data.adaptor_secret()?;
+
+        // Check the adaptor signature issued at accept time still matches the quote
+        let (client_pubkey, encrypted_signature) = match (
+            quote_data.client_pubkey.as_ref(),
+            quote_data.encrypted_signature.as_ref(),
+        ) {
+            (Some(pubkey), Some(sig)) => (pubkey, sig),
+            _ => {
+                return Err(BrokerError::InvalidSwapRequest(format!(
+                    "Quote {} has not been accepted",
+                    quote_id
+                )))
+            }
+        };
+        let broker_point = self.adaptor_ctx.adaptor_point_from_secret(broker_swap_key);
+        let adaptor_point = self.adaptor_ctx.adaptor_point_from_secret(adaptor_secret);
+        self.adaptor_ctx.verify_encrypted_signature(
+            &broker_point,
+            &adaptor_point,
+            &swap_transcript(&quote_data.quote, client_pubkey),
+            encrypted_signature,
+        )?;
+
+        // Only proceed if the client's signature decrypts under T
+        let client_signature =
+            self.verify_client_signature(&quote_data, client_encrypted_signature)?;
+
+        // The client has done its part: from here on a failed claim is
+        // retried with these proofs rather than the outputs refunded
+        quote_data.client_signature = Some(client_encrypted_signature.clone());
+        if let Some(execution) = self.executions.write().await.get_mut(quote_id) {
+            execution.client_tokens = serialize_proofs(&client_proofs_with_witness).into();
+        }
+
+        let total_amount = self
+            .claim_client_proofs(
+                &mut quote_data,
+                adaptor_secret,
+                client_proofs_with_witness,
+                liquidity,
+            )
+            .await?;
 
-        let broker_swap_key = &quote_data.broker_swap_key;
-        let adaptor_secret = &quote_data.adaptor_secret;
+        Ok((client_signature, total_amount))
+    }
+
+    /// Claim the client's proofs, locked to the broker's key tweaked by T,
+    /// with the broker's swap key plus `adaptor_secret`
+    ///
+    /// Settles the quote as completed and returns the amount received after
+    /// the source mint's input fee.
+    async fn claim_client_proofs(
+        &self,
+        quote_data: &mut QuoteData,
+        adaptor_secret: &Scalar,
+        mut client_proofs: Proofs,
+        liquidity: &LiquidityManager,
+    ) -> Result<u64> {
+        let quote_id = quote_data.quote.quote_id.clone();
 
         // Compute broker's tweaked key: broker_key + adaptor_secret
-        let _broker_with_adaptor = self.adaptor_ctx.add_scalars(broker_swap_key, adaptor_secret);
+        let broker_with_adaptor = self
+            .adaptor_ctx
+            .add_scalars(&quote_data.broker_swap_key.scalar(), adaptor_secret);
+        let signing_key = SecretKey::from_slice(&scalar_to_bytes(&broker_with_adaptor))
+            .map_err(|e| BrokerError::Cdk(format!("Failed to create signing key: {:?}", e)))?;
 
         info!("Charlie completing swap {}...", quote_id);
 
         // Create proofs with broker's signature
         let wallet = liquidity.get_wallet(&quote_data.quote.from_mint)?;
 
-        // For each client proof, we need to sign with broker's tweaked key
-        // In practice, the client has already added their witness
-        // Charlie just needs to swap these tokens at the mint
-        let total_amount: u64 = client_proofs_with_witness
-            .iter()
-            .map(|p| u64::from(p.amount))
-            .sum();
-
-        // Swap the client's tokens for new tokens
-        let new_proofs = wallet
-            .swap(
-                Some(Amount::from(total_amount)),
-                SplitTarget::default(),
-                client_proofs_with_witness,
-                None,
-                false,
-            )
-            .await
-            .map_err(|e| BrokerError::Cdk(format!("Failed to swap client tokens: {:?}", e)))?;
+        // The client locked their proofs to the broker's tweaked key (P + T),
+        // so sign each one with broker_key + adaptor_secret before swapping.
+        // The client's own signatures were only for the accept check.
+        binding::strip_witnesses(&mut client_proofs);
+        for proof in client_proofs.iter_mut() {
+            proof
+                .sign_p2pk(signing_key.clone())
+                .map_err(|e| BrokerError::Cdk(format!("Failed to sign client proof: {:?}", e)))?;
+        }
 
-        // Save mint URL before releasing the lock
         let from_mint = quote_data.quote.from_mint.clone();
 
+        // The source mint takes its input fee out of the swap
+        let total_amount = liquidity
+            .amount_after_fees(&from_mint, &client_proofs)
+            .await?;
+
+        // Swap the client's tokens for new tokens. If a retried attempt already
+        // went through, the mint reports the proofs as spent and we stop there.
+        let new_proofs = liquidity
+            .mint_call(&from_mint, MintOp::Swap, "Failed to swap client tokens", || {
+                wallet.swap(
+                    Some(Amount::from(total_amount)),
+                    SplitTarget::default(),
+                    client_proofs.clone(),
+                    None,
+                    false,
+                )
+            })
+            .await
+            .inspect_err(|e| {
+                self.events.emit(BrokerEvent::SwapFailed {
+                    quote_id: quote_id.clone(),
+                    error: e.to_string(),
+                })
+            })?;
+
         // Add to broker's liquidity
         if let Some(proofs) = new_proofs {
             liquidity
@@ -279,55 +913,565 @@ impl SwapCoordinator {
                 .await?;
         }
 
+        // The swap is settled, so the output no longer needs to be held
+        liquidity.release(&quote_id).await;
+
         // Update execution status
         let mut executions = self.executions.write().await;
-        if let Some(execution) = executions.get_mut(quote_id) {
+        if let Some(execution) = executions.get_mut(&quote_id) {
             execution.client_swap_complete = true;
             execution.broker_swap_complete = true;
-            execution.completed_at = Some(SystemTime::now());
+            execution.completed_at = Some(self.clock.now());
         }
 
         // Update quote status
-        drop(quotes); // Release read lock
-        let mut quotes = self.quotes.write().await;
-        if let Some(quote_data) = quotes.get_mut(quote_id) {
-            quote_data.quote.status = SwapStatus::Completed;
-        }
+        quote_data.quote.status = SwapStatus::Completed;
+        self.events.emit(BrokerEvent::SwapCompleted {
+            quote_id,
+            amount_received: total_amount,
+        });
 
         info!(
             "Charlie swap complete! Received {} sats from {}",
             total_amount, from_mint
         );
 
-        Ok(())
+        Ok(total_amount)
+    }
+
+    /// Verify the client's encrypted signature and decrypt it with the adaptor secret
+    ///
+    /// Checks the signature against the client key the quote was accepted with,
+    /// then confirms that the secret recovered from the (encrypted, decrypted)
+    /// pair is the quote's adaptor secret.
+    fn verify_client_signature(
+        &self,
+        quote_data: &QuoteData,
+        client_encrypted_signature: &EncryptedSignature,
+    ) -> Result<Signature> {
+        let client_pubkey = quote_data.client_pubkey.as_ref().ok_or_else(|| {
+            BrokerError::InvalidSwapRequest(format!(
+                "Quote {} has no client pubkey",
+                quote_data.quote.quote_id
+            ))
+        })?;
+        let client_point = compressed_bytes_to_point(client_pubkey)?;
+        let adaptor_secret = quote_data.adaptor_secret()?;
+        let adaptor_point = self.adaptor_ctx.adaptor_point_from_secret(&adaptor_secret);
+        let transcript = swap_transcript(&quote_data.quote, client_pubkey);
+
+        self.adaptor_ctx.verify_encrypted_signature(
+            &client_point,
+            &adaptor_point,
+            &transcript,
+            client_encrypted_signature,
+        )?;
+
+        let decrypted = self
+            .adaptor_ctx
+            .decrypt_signature(&adaptor_secret, client_encrypted_signature.clone())?;
+
+        let recovered = self.adaptor_ctx.recover_adaptor_secret(
+            &adaptor_point,
+            client_encrypted_signature,
+            &decrypted,
+        )?;
+        if recovered != adaptor_secret {
+            return Err(BrokerError::AdaptorSignature(
+                "Client signature does not decrypt under the quote's adaptor point".to_string(),
+            ));
+        }
+
+        Ok(decrypted)
+    }
+
+    /// Look up a quote's entry, without keeping the map locked
+    async fn entry(&self, quote_id: &str) -> Result<Arc<QuoteEntry>> {
+        self.quotes
+            .read()
+            .await
+            .get(quote_id)
+            .cloned()
+            .ok_or_else(|| BrokerError::QuoteNotFound(quote_id.to_string()))
+    }
+
+    /// All quote entries, without keeping the map locked
+    async fn entries(&self) -> Vec<Arc<QuoteEntry>> {
+        self.quotes.read().await.values().cloned().collect()
     }
 
     /// Get a quote by ID
     pub async fn get_quote(&self, quote_id: &str) -> Option<SwapQuote> {
-        let quotes = self.quotes.read().await;
-        quotes.get(quote_id).map(|qd| qd.quote.clone())
+        let entry = self.entry(quote_id).await.ok()?;
+        let quote_data = entry.data.lock().await;
+        Some(quote_data.quote.clone())
+    }
+
+    /// Drop pending quotes past their expiry and quotes whose outputs were refunded
+    ///
+    /// Liquidity reserved for the dropped quotes is released. Returns the IDs
+    /// of the pending quotes that expired. Quotes busy being accepted,
+    /// completed or refunded are left for the next run.
+    pub async fn expire_quotes(&self, liquidity: &LiquidityManager) -> Vec<String> {
+        let now = self.clock.now();
+        let mut quotes = self.quotes.write().await;
+        let idle: Vec<(String, SwapStatus, Option<SystemTime>)> = quotes
+            .iter()
+            .filter_map(|(quote_id, entry)| {
+                let data = entry.data.try_lock().ok()?;
+                Some((quote_id.clone(), data.quote.status, data.quote.expires_at))
+            })
+            .collect();
+
+        let expired: Vec<String> = idle
+            .iter()
+            .filter(|(_, status, expires_at)| {
+                *status == SwapStatus::Pending && expires_at.is_some_and(|at| at <= now)
+            })
+            .map(|(quote_id, _, _)| quote_id.clone())
+            .collect();
+
+        for quote_id in &expired {
+            quotes.remove(quote_id);
+            liquidity.release(quote_id).await;
+            self.events.emit(BrokerEvent::QuoteExpired {
+                quote_id: quote_id.clone(),
+            });
+        }
+
+        // Refunded swaps have nothing left to do
+        let refunded: Vec<String> = idle
+            .into_iter()
+            .filter(|(_, status, _)| *status == SwapStatus::Expired)
+            .map(|(quote_id, _, _)| quote_id)
+            .collect();
+
+        let mut executions = self.executions.write().await;
+        for quote_id in &refunded {
+            quotes.remove(quote_id);
+            executions.remove(quote_id);
+            liquidity.release(quote_id).await;
+        }
+
+        expired
+    }
+
+    /// Expire a single pending quote now and release its reservation
+    pub async fn expire_quote(&self, quote_id: &str, liquidity: &LiquidityManager) -> Result<()> {
+        let entry = self.entry(quote_id).await?;
+        let quote_data = entry.data.lock().await;
+
+        if quote_data.quote.status != SwapStatus::Pending {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Quote {} is not pending",
+                quote_id
+            )));
+        }
+
+        self.quotes.write().await.remove(quote_id);
+        liquidity.release(quote_id).await;
+        self.events.emit(BrokerEvent::QuoteExpired {
+            quote_id: quote_id.to_string(),
+        });
+
+        Ok(())
+    }
+
+    /// Push a pending quote's expiry out by `extension`, once
+    ///
+    /// The quote keeps its liquidity reservation, so it is only extended while
+    /// the target mint's balance still covers it.
+    pub async fn extend_quote(
+        &self,
+        quote_id: &str,
+        extension: Duration,
+        liquidity: &LiquidityManager,
+    ) -> Result<SwapQuote> {
+        let entry = self.entry(quote_id).await?;
+        let mut quote_data = entry.data.lock().await;
+        let quote = &mut quote_data.quote;
+
+        if quote.status != SwapStatus::Pending {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Quote {} is not pending",
+                quote_id
+            )));
+        }
+        let Some(expires_at) = quote.expires_at.filter(|at| *at > self.clock.now()) else {
+            return Err(BrokerError::QuoteExpired(quote_id.to_string()));
+        };
+        if quote.extended {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Quote {} was extended before",
+                quote_id
+            )));
+        }
+        if !liquidity.reservation_covered(quote_id).await {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Liquidity for quote {} is no longer available",
+                quote_id
+            )));
+        }
+
+        let expires_at = expires_at + extension;
+        quote.expires_at = Some(expires_at);
+        quote.expiry += extension.as_secs();
+        quote.expires_in = expires_at
+            .duration_since(self.clock.now())
+            .unwrap_or_default()
+            .as_secs();
+        quote.extended = true;
+
+        Ok(quote.clone())
+    }
+
+    /// Get the key the client signed an accepted quote with
+    pub async fn client_pubkey(&self, quote_id: &str) -> Option<Vec<u8>> {
+        let entry = self.entry(quote_id).await.ok()?;
+        let quote_data = entry.data.lock().await;
+        quote_data.client_pubkey.clone()
+    }
+
+    /// Get the private keys of a quote
+    pub async fn quote_secrets(&self, quote_id: &str) -> Option<QuoteSecrets> {
+        let entry = self.entry(quote_id).await.ok()?;
+        let qd = entry.data.lock().await;
+        Some(QuoteSecrets {
+            broker_swap_key: qd.broker_swap_key.to_bytes(),
+            adaptor_secret: qd
+                .adaptor_secret
+                .as_ref()
+                .map(SecretScalar::to_bytes)
+                .unwrap_or_default(),
+            refund_at: qd.refund_at,
+        })
+    }
+
+    /// Re-insert a quote loaded from storage after a restart
+    ///
+    /// For accepted quotes, pass the client key, the broker's encrypted
+    /// signature and the outputs it locked so the swap can still complete or
+    /// be refunded, and the client's signature if it completed without the
+    /// claim going through. The keys are checked against the quote's public
+    /// points.
+    pub async fn restore_quote(
+        &self,
+        quote: SwapQuote,
+        secrets: &QuoteSecrets,
+        client_pubkey: Option<Vec<u8>>,
+        encrypted_signature: Option<EncryptedSignature>,
+        locked_proofs: Option<Proofs>,
+        client_signature: Option<EncryptedSignature>,
+    ) -> Result<()> {
+        let broker_swap_key = SecretScalar::from_bytes(&secrets.broker_swap_key)?;
+        let adaptor_secret = if secrets.adaptor_secret.is_empty() {
+            None
+        } else {
+            Some(SecretScalar::from_bytes(&secrets.adaptor_secret)?)
+        };
+
+        let adaptor_point = adaptor_secret
+            .as_ref()
+            .map(|secret| self.adaptor_ctx.adaptor_point_from_secret(&secret.scalar()));
+        let broker_pubkey = self.adaptor_ctx.adaptor_point_from_secret(&broker_swap_key.scalar());
+        let secret_matches = match adaptor_point {
+            Some(point) => point_to_compressed_bytes(&point) == quote.adaptor_point,
+            None => quote.claim_order == ClaimOrder::ClientFirst,
+        };
+        if !secret_matches || point_to_compressed_bytes(&broker_pubkey) != quote.broker_public_key {
+            return Err(BrokerError::AdaptorSignature(format!(
+                "Stored keys do not match quote {}",
+                quote.quote_id
+            )));
+        }
+
+        let quote_id = quote.quote_id.clone();
+
+        if let Some(proofs) = &locked_proofs {
+            let execution = SwapExecution {
+                quote_id: quote_id.clone(),
+                client_tokens: Sensitive::default(),
+                broker_tokens: serialize_proofs(proofs).into(),
+                client_swap_complete: false,
+                broker_swap_complete: false,
+                completed_at: None,
+            };
+            self.executions.write().await.insert(quote_id.clone(), execution);
+        }
+
+        let quote_data = QuoteData {
+            quote,
+            client: None,
+            broker_swap_key,
+            adaptor_secret,
+            client_pubkey,
+            encrypted_signature,
+            refund_at: secrets.refund_at,
+            client_signature,
+        };
+        self.quotes
+            .write()
+            .await
+            .insert(quote_id, QuoteEntry::new(quote_data));
+
+        Ok(())
+    }
+
+    /// Reclaim broker outputs whose refund locktime has passed
+    ///
+    /// For every accepted swap the client never redeemed, the broker spends its
+    /// locked outputs with the refund key and returns them to liquidity. The
+    /// quote is marked expired. Swaps whose client already handed over a
+    /// valid signature are left alone, as the broker owes them the claim (see
+    /// [`pending_claims`](Self::pending_claims)). Returns the IDs of refunded
+    /// quotes.
+    pub async fn reclaim_expired_locks(&self, liquidity: &LiquidityManager) -> Vec<String> {
+        let now = self.clock.unix_now();
+
+        let mut refunded = Vec::new();
+
+        for entry in self.entries().await {
+            // Hold the quote while refunding, so it can't complete meanwhile
+            let mut quote_data = entry.data.lock().await;
+            if quote_data.quote.status != SwapStatus::Accepted
+                || !quote_data.refund_at.is_some_and(|at| at <= now)
+                || quote_data.client_signature.is_some()
+            {
+                continue;
+            }
+            let quote_id = quote_data.quote.quote_id.clone();
+            let mint_url = quote_data.quote.to_mint.clone();
+
+            let locked_proofs = {
+                let executions = self.executions.read().await;
+                executions
+                    .get(&quote_id)
+                    .and_then(|e| serde_json::from_slice::<Proofs>(e.broker_tokens.expose()).ok())
+            };
+
+            let Some(locked_proofs) = locked_proofs else {
+                warn!("No locked outputs recorded for quote {}, skipping refund", quote_id);
+                continue;
+            };
+
+            match self
+                .refund_locked_proofs(
+                    &mint_url,
+                    locked_proofs,
+                    &quote_data.broker_swap_key,
+                    liquidity,
+                )
+                .instrument(info_span!("quote", quote_id = %quote_id))
+                .await
+            {
+                Ok(amount) => {
+                    info!("Refunded {} sats from unredeemed swap {}", amount, quote_id);
+
+                    quote_data.quote.status = SwapStatus::Expired;
+                    liquidity.release(&quote_id).await;
+                    self.events.emit(BrokerEvent::SwapRefunded {
+                        quote_id: quote_id.clone(),
+                        amount,
+                    });
+                    refunded.push(quote_id);
+                }
+                Err(e) => {
+                    // The client may have redeemed the outputs in the meantime
+                    warn!("Refund of swap {} failed: {}", quote_id, e);
+                }
+            }
+        }
+
+        refunded
+    }
+
+    /// Accepted swaps whose claim of the client's proofs failed after the
+    /// client's signature verified
+    ///
+    /// Each comes with the client's encrypted signature and, unless the quote
+    /// was restored from storage, the proofs it completed with. Quotes busy
+    /// being completed are left out.
+    pub async fn pending_claims(&self) -> Vec<(String, EncryptedSignature, Option<Proofs>)> {
+        let mut claims = Vec::new();
+
+        for entry in self.entries().await {
+            let Ok(quote_data) = entry.data.try_lock() else {
+                continue;
+            };
+            let Some(client_signature) = quote_data.client_signature.clone() else {
+                continue;
+            };
+            if quote_data.quote.status != SwapStatus::Accepted {
+                continue;
+            }
+
+            let quote_id = quote_data.quote.quote_id.clone();
+            let proofs = self
+                .executions
+                .read()
+                .await
+                .get(&quote_id)
+                .filter(|e| !e.client_tokens.is_empty())
+                .and_then(|e| serde_json::from_slice::<Proofs>(e.client_tokens.expose()).ok());
+            claims.push((quote_id, client_signature, proofs));
+        }
+
+        claims
+    }
+
+    /// Spend locked outputs with the refund key and add the result to liquidity
+    async fn refund_locked_proofs(
+        &self,
+        mint_url: &str,
+        mut proofs: Proofs,
+        refund_key: &SecretScalar,
+        liquidity: &LiquidityManager,
+    ) -> Result<u64> {
+        let signing_key = SecretKey::from_slice(&refund_key.to_bytes())
+            .map_err(|e| BrokerError::Cdk(format!("Failed to create refund key: {:?}", e)))?;
+
+        for proof in proofs.iter_mut() {
+            proof
+                .sign_p2pk(signing_key.clone())
+                .map_err(|e| BrokerError::Cdk(format!("Failed to sign refund: {:?}", e)))?;
+        }
+
+        let amount = liquidity.amount_after_fees(mint_url, &proofs).await?;
+        let wallet = liquidity.get_wallet(mint_url)?;
+
+        let new_proofs = liquidity
+            .mint_call(mint_url, MintOp::Swap, "Failed to swap refunded tokens", || {
+                wallet.swap(
+                    Some(Amount::from(amount)),
+                    SplitTarget::default(),
+                    proofs.clone(),
+                    None,
+                    false,
+                )
+            })
+            .await?;
+
+        if let Some(new_proofs) = new_proofs {
+            liquidity.add_proofs(mint_url, new_proofs).await?;
+        }
+
+        Ok(amount)
+    }
+
+    /// Get the adaptor secret of a completed swap
+    ///
+    /// The broker reveals `t` only after it has claimed the client's tokens,
+    /// which lets the client unlock the outputs locked to `client_pubkey + T`.
+    /// For a client-first swap it is the secret the client's claim revealed.
+    pub async fn revealed_adaptor_secret(&self, quote_id: &str) -> Result<Vec<u8>> {
+        let entry = self.entry(quote_id).await?;
+        let quote_data = entry.data.lock().await;
+
+        if quote_data.quote.status != SwapStatus::Completed {
+            return Err(BrokerError::InvalidSwapRequest(format!(
+                "Quote {} is not completed",
+                quote_id
+            )));
+        }
+
+        Ok(quote_data.adaptor_secret()?.to_bytes().to_vec())
+    }
+
+    /// Get all quotes currently held in memory
+    pub async fn list_quotes(&self) -> Vec<SwapQuote> {
+        let mut quotes = Vec::new();
+        for entry in self.entries().await {
+            quotes.push(entry.data.lock().await.quote.clone());
+        }
+        quotes
+    }
+
+    /// Unreserved balances of `target_mint` and the other mints of its unit
+    async fn inventory(
+        config: &BrokerConfig,
+        target_mint: &str,
+        liquidity: &LiquidityManager,
+    ) -> Inventory {
+        let Some(target) = config.mints.iter().find(|m| m.mint_url == target_mint) else {
+            return Inventory::default();
+        };
+
+        let mut inventory = Inventory::default();
+        for mint in config
+            .mints
+            .iter()
+            .filter(|m| m.unit.eq_ignore_ascii_case(&target.unit))
+        {
+            let balance = liquidity.get_available_balance(&mint.mint_url).await;
+            if mint.mint_url == target_mint {
+                inventory.target_balance = balance;
+            }
+            inventory.total_balance += balance;
+            inventory.mints += 1;
+        }
+        inventory
+    }
+
+    /// Refuse a quote that would break `limits`, given the open quotes
+    fn check_risk(
+        &self,
+        quotes: &HashMap<String, Arc<QuoteEntry>>,
+        limits: &RiskLimits,
+        client: &str,
+        amount_in: u64,
+        amount_out: u64,
+    ) -> Result<()> {
+        if *limits == RiskLimits::default() {
+            return Ok(());
+        }
+
+        let (open_quotes, outstanding) = quotes
+            .values()
+            .filter(|entry| entry.is_open())
+            .fold((0, 0), |(count, total), entry| {
+                let own = entry.client.as_deref() == Some(client);
+                (count + usize::from(own), total + entry.output_amount)
+            });
+        let quoted_today = self.volume.volume(client, self.clock.unix_now());
+
+        limits
+            .check(quoted_today, open_quotes, outstanding, amount_in, amount_out)
+            .map_err(|violation| {
+                warn!("Refusing quote for {}: {}", client, violation);
+                BrokerError::RiskLimitExceeded(violation)
+            })
     }
 
     /// Validate a swap request
-    async fn validate_swap_request(&self, request: &SwapRequest) -> Result<()> {
+    ///
+    /// Swap limits apply to the input amount, whichever side the client fixed,
+    /// and come from the pair's overrides when it has any.
+    async fn validate_swap_request(
+        &self,
+        request: &SwapRequest,
+        input_amount: u64,
+        config: &BrokerConfig,
+    ) -> Result<()> {
+        let terms = config.pair_terms(&request.from_mint, &request.to_mint);
+
         // Check amount bounds
-        if request.amount < self.config.min_swap_amount {
+        if input_amount < terms.min_swap_amount {
             return Err(BrokerError::AmountTooLow {
-                amount: request.amount,
-                min: self.config.min_swap_amount,
+                amount: input_amount,
+                min: terms.min_swap_amount,
             });
         }
 
-        if request.amount > self.config.max_swap_amount {
+        if input_amount > terms.max_swap_amount {
             return Err(BrokerError::AmountTooHigh {
-                amount: request.amount,
-                max: self.config.max_swap_amount,
+                amount: input_amount,
+                max: terms.max_swap_amount,
             });
         }
 
         // Check mint support
         let supported_mints: Vec<String> =
-            self.config.mints.iter().map(|m| m.mint_url.clone()).collect();
+            config.mints.iter().map(|m| m.mint_url.clone()).collect();
 
         if !supported_mints.contains(&request.from_mint) {
             return Err(BrokerError::UnsupportedMint(request.from_mint.clone()));
@@ -346,11 +1490,131 @@ impl SwapCoordinator {
     }
 
     /// Generate a unique quote ID
-    fn generate_quote_id() -> String {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        let bytes: [u8; 16] = rng.gen();
-        hex::encode(bytes)
+    fn generate_quote_id(&self) -> String {
+        ids::quote_id(self.clock.now(), &self.rng)
+    }
+}
+
+/// Work out `(input, fee, output)` for a requested amount
+///
+/// The fee is `ceil(input * rate)`, or the minimum fee if that is more. For
+/// exact-out quotes this picks the smallest input whose output after the fee
+/// covers the requested amount; any rounding surplus is kept as fee so the
+/// output is exactly `amount`. Amounts that would leave no output are refused
+/// (see [`Fee::exact_in`]).
+pub fn quote_amounts(
+    quote_type: QuoteType,
+    amount: u64,
+    fee: impl Into<Fee>,
+) -> Result<(u64, u64, u64)> {
+    let fee = fee.into();
+    let split = match quote_type {
+        QuoteType::ExactIn => fee.exact_in(amount)?,
+        QuoteType::ExactOut => fee.exact_out(amount)?,
+    };
+    Ok((split.input, split.fee, split.output))
+}
+
+/// Work out `(input, fee, output)` for a cross-unit swap
+///
+/// The fee is charged in the source unit; the remainder is converted at
+/// `exchange_rate` (target units per source unit), rounding down. For
+/// exact-out quotes `amount` is in the target unit.
+pub fn quote_amounts_at_rate(
+    quote_type: QuoteType,
+    amount: u64,
+    fee: impl Into<Fee>,
+    exchange_rate: f64,
+) -> Result<(u64, u64, u64)> {
+    let fee = fee.into();
+    if !exchange_rate.is_finite() || exchange_rate <= 0.0 {
+        return Err(BrokerError::InvalidSwapRequest(format!(
+            "Invalid exchange rate {}",
+            exchange_rate
+        )));
+    }
+
+    match quote_type {
+        QuoteType::ExactIn => {
+            let (input, fee, net) = quote_amounts(QuoteType::ExactIn, amount, fee)?;
+            let output = ((net as f64) * exchange_rate).floor() as u64;
+            if output == 0 {
+                return Err(BrokerError::InvalidSwapRequest(format!(
+                    "{} left after the broker fee is worth nothing at {}",
+                    net, exchange_rate
+                )));
+            }
+            Ok((input, fee, output))
+        }
+        QuoteType::ExactOut => {
+            let net = ((amount as f64) / exchange_rate).ceil() as u64;
+            let (input, fee, _) = quote_amounts(QuoteType::ExactOut, net, fee)?;
+            Ok((input, fee, amount))
+        }
+    }
+}
+
+/// Work out `(input, fee, mint_fee, output)` including the mints' input fees
+///
+/// `mint_fee` is in the source unit. It covers the broker spending the
+/// client's proofs on the source mint (`source_fee_ppk`) and the client
+/// spending the locked outputs on the target mint (`target_fee_ppk`),
+/// estimated for the fewest proofs of each amount. Exact-in quotes take it out
+/// of the output; exact-out quotes add it to the input, before the broker fee.
+pub fn quote_amounts_with_mint_fees(
+    quote_type: QuoteType,
+    amount: u64,
+    broker_fee: impl Into<Fee>,
+    exchange_rate: Option<f64>,
+    source_fee_ppk: u64,
+    target_fee_ppk: u64,
+) -> Result<(u64, u64, u64, u64)> {
+    let broker_fee = broker_fee.into();
+    let (input, fee, output) = match exchange_rate {
+        Some(rate) => quote_amounts_at_rate(quote_type, amount, broker_fee, rate)?,
+        None => quote_amounts(quote_type, amount, broker_fee)?,
+    };
+
+    // The target mint's fee is charged in its own unit
+    let target_fee = input_fee(output, target_fee_ppk);
+    let mint_fee = input_fee(input, source_fee_ppk)
+        + match exchange_rate {
+            Some(rate) => ((target_fee as f64) / rate).ceil() as u64,
+            None => target_fee,
+        };
+    if mint_fee == 0 {
+        return Ok((input, fee, 0, output));
+    }
+
+    match quote_type {
+        QuoteType::ExactIn => {
+            let net = input
+                .saturating_sub(fee)
+                .checked_sub(mint_fee)
+                .filter(|&net| net > 0)
+                .ok_or_else(|| {
+                    BrokerError::InvalidSwapRequest(format!(
+                        "{} doesn't cover the broker fee of {} and mint fees of {}",
+                        input, fee, mint_fee
+                    ))
+                })?;
+            let output = match exchange_rate {
+                Some(rate) => ((net as f64) * rate).floor() as u64,
+                None => net,
+            };
+            if output == 0 {
+                return Err(BrokerError::InvalidSwapRequest(format!(
+                    "{} left after fees is worth nothing at the exchange rate",
+                    net
+                )));
+            }
+            Ok((input, fee, mint_fee, output))
+        }
+        QuoteType::ExactOut => {
+            let net = input - fee + mint_fee;
+            let (input, fee, _) = quote_amounts(QuoteType::ExactOut, net, broker_fee)?;
+            Ok((input, fee, mint_fee, output))
+        }
     }
 }
 
@@ -373,6 +1637,10 @@ fn scalar_to_bytes(scalar: &Scalar) -> Vec<u8> {
     scalar.to_bytes().to_vec()
 }
 
+pub(crate) fn unix_now() -> u64 {
+    SystemClock.unix_now()
+}
+
 fn serialize_proofs(proofs: &Proofs) -> Vec<u8> {
     // Serialize proofs to JSON bytes
     serde_json::to_vec(proofs).unwrap_or_default()
@@ -381,7 +1649,11 @@ fn serialize_proofs(proofs: &Proofs) -> Vec<u8> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::MintConfig;
+    use crate::clock::ManualClock;
+    use crate::fees::{FeeSchedule, FeeTier};
+    use crate::types::{MintConfig, PairConfig};
+    use proptest::prelude::*;
+    use std::str::FromStr;
 
     #[tokio::test]
     async fn test_swap_coordinator_creation() {
@@ -398,4 +1670,767 @@ mod tests {
         let quotes = coordinator.quotes.read().await;
         assert!(quotes.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_update_config() {
+        let coordinator = SwapCoordinator::new(BrokerConfig::default());
+
+        coordinator.update_config(BrokerConfig {
+            fee_rate: 0.01,
+            max_swap_amount: 50_000,
+            ..Default::default()
+        });
+
+        let config = coordinator.config();
+        assert_eq!(config.fee_rate, 0.01);
+        assert_eq!(config.max_swap_amount, 50_000);
+
+        coordinator.modify_config(|c| BrokerConfig {
+            fee_rate: 0.02,
+            ..c.clone()
+        });
+
+        // Snapshots taken earlier are unaffected
+        assert_eq!(config.fee_rate, 0.01);
+        assert_eq!(coordinator.config().fee_rate, 0.02);
+        assert_eq!(coordinator.config().max_swap_amount, 50_000);
+    }
+
+    #[tokio::test]
+    async fn test_pair_overrides() {
+        let mint = |url: &str| MintConfig {
+            mint_url: url.to_string(),
+            name: url.to_string(),
+            unit: "sat".to_string(),
+        };
+        let config = BrokerConfig {
+            mints: vec![mint("http://mint-a.test"), mint("http://mint-b.test")],
+            pairs: vec![PairConfig {
+                source_mint: "http://mint-a.test".to_string(),
+                target_mint: "http://mint-b.test".to_string(),
+                fee_rate: Some(0.02),
+                min_swap_amount: Some(100),
+                max_swap_amount: None,
+            }],
+            ..Default::default()
+        };
+        let coordinator = SwapCoordinator::new(config.clone());
+
+        let request = |from: &str, to: &str| SwapRequest {
+            client_id: None,
+            from_mint: from.to_string(),
+            to_mint: to.to_string(),
+            amount: 50,
+            quote_type: QuoteType::ExactIn,
+            client_public_key: None,
+            adaptor_point: None,
+        };
+
+        // Overrides apply to A -> B only
+        let a_to_b = config.pair_terms("http://mint-a.test", "http://mint-b.test");
+        assert_eq!(a_to_b.fee_rate, 0.02);
+        assert_eq!(a_to_b.min_swap_amount, 100);
+        assert_eq!(a_to_b.max_swap_amount, config.max_swap_amount);
+
+        let b_to_a = config.pair_terms("http://mint-b.test", "http://mint-a.test");
+        assert_eq!(b_to_a.fee_rate, config.fee_rate);
+
+        let forward = request("http://mint-a.test", "http://mint-b.test");
+        assert!(matches!(
+            coordinator.validate_swap_request(&forward, 50, &config).await,
+            Err(BrokerError::AmountTooLow { min: 100, .. })
+        ));
+
+        let reverse = request("http://mint-b.test", "http://mint-a.test");
+        assert!(coordinator
+            .validate_swap_request(&reverse, 50, &config)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_mixed_units_need_rate() {
+        let mint = |url: &str, unit: &str| MintConfig {
+            mint_url: url.to_string(),
+            name: url.to_string(),
+            unit: unit.to_string(),
+        };
+        let mints = vec![
+            mint("http://mint-a.test", "sat"),
+            mint("http://mint-b.test", "USD"),
+        ];
+        let config = BrokerConfig {
+            mints: mints.clone(),
+            ..Default::default()
+        };
+        assert_eq!(config.unit_of("http://mint-b.test").as_deref(), Some("usd"));
+
+        let coordinator = SwapCoordinator::new(config);
+        let liquidity = LiquidityManager::new(mints).await.unwrap();
+        assert_eq!(
+            liquidity.get_wallet("http://mint-b.test").unwrap().unit,
+            cdk::nuts::CurrencyUnit::Usd
+        );
+
+        let request = SwapRequest {
+            client_id: None,
+            from_mint: "http://mint-a.test".to_string(),
+            to_mint: "http://mint-b.test".to_string(),
+            amount: 1000,
+            quote_type: QuoteType::ExactIn,
+            client_public_key: None,
+            adaptor_point: None,
+        };
+        assert!(matches!(
+            coordinator.create_quote(request, &liquidity).await,
+            Err(BrokerError::InvalidSwapRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_mint_rejected() {
+        let mint = |url: &str| MintConfig {
+            mint_url: url.to_string(),
+            name: url.to_string(),
+            unit: "sat".to_string(),
+        };
+        let mints = vec![mint("http://mint-a.test"), mint("http://mint-b.test")];
+        let coordinator = SwapCoordinator::new(BrokerConfig {
+            mints: mints.clone(),
+            ..Default::default()
+        });
+        let liquidity = LiquidityManager::new(mints).await.unwrap();
+
+        for _ in 0..3 {
+            liquidity.record_health_check("http://mint-b.test", Err("timed out".to_string()));
+        }
+
+        let request = SwapRequest {
+            client_id: None,
+            from_mint: "http://mint-a.test".to_string(),
+            to_mint: "http://mint-b.test".to_string(),
+            amount: 1000,
+            quote_type: QuoteType::ExactIn,
+            client_public_key: None,
+            adaptor_point: None,
+        };
+        assert!(matches!(
+            coordinator.create_quote(request, &liquidity).await,
+            Err(BrokerError::MintUnhealthy(url)) if url == "http://mint-b.test"
+        ));
+    }
+
+    fn accepted_quote_data(ctx: &AdaptorContext, client_pubkey: &[u8]) -> QuoteData {
+        let adaptor_secret = ctx.generate_adaptor_secret();
+        let broker_swap_key = ctx.generate_adaptor_secret();
+
+        QuoteData {
+            quote: SwapQuote {
+                quote_id: "quote-1".to_string(),
+                from_mint: "http://localhost:3338".to_string(),
+                to_mint: "http://localhost:3339".to_string(),
+                from_unit: "sat".to_string(),
+                to_unit: "sat".to_string(),
+                input_amount: 100,
+                output_amount: 99,
+                fee: 1,
+                fee_rate: 0.01,
+                mint_fee: 0,
+                exchange_rate: None,
+                broker_public_key: point_to_compressed_bytes(
+                    &ctx.adaptor_point_from_secret(&broker_swap_key),
+                ),
+                adaptor_point: point_to_compressed_bytes(
+                    &ctx.adaptor_point_from_secret(&adaptor_secret),
+                ),
+                tweaked_pubkey: None,
+                dleq_proof: None,
+                expires_in: 300,
+                expiry: 0,
+                expires_at: None,
+                quote_signature: None,
+                claim_order: ClaimOrder::BrokerFirst,
+                extended: false,
+                status: SwapStatus::Accepted,
+            },
+            client: None,
+            broker_swap_key: SecretScalar::new(&broker_swap_key),
+            adaptor_secret: Some(SecretScalar::new(&adaptor_secret)),
+            client_pubkey: Some(client_pubkey.to_vec()),
+            encrypted_signature: None,
+            refund_at: None,
+            client_signature: None,
+        }
+    }
+
+    #[test]
+    fn test_verify_client_signature() {
+        let coordinator = SwapCoordinator::new(BrokerConfig::default());
+        let ctx = AdaptorContext::new();
+
+        let client_key = ctx.generate_adaptor_secret();
+        let client_pubkey = point_to_compressed_bytes(&ctx.adaptor_point_from_secret(&client_key));
+        let quote_data = accepted_quote_data(&ctx, &client_pubkey);
+        let adaptor_point = quote_data.adaptor_point().unwrap();
+        let transcript = swap_transcript(&quote_data.quote, &client_pubkey);
+
+        // Encrypted under the quote's adaptor point: accepted
+        let valid = ctx
+            .create_encrypted_signature(&client_key, &adaptor_point, &transcript)
+            .unwrap();
+        assert!(coordinator.verify_client_signature(&quote_data, &valid).is_ok());
+
+        // Encrypted under a different point: rejected
+        let other_point = ctx.adaptor_point_from_secret(&ctx.generate_adaptor_secret());
+        let wrong_point = ctx
+            .create_encrypted_signature(&client_key, &other_point, &transcript)
+            .unwrap();
+        assert!(coordinator.verify_client_signature(&quote_data, &wrong_point).is_err());
+
+        // Signed over a different transcript: rejected
+        let wrong_message = ctx
+            .create_encrypted_signature(&client_key, &adaptor_point, b"other transcript")
+            .unwrap();
+        assert!(coordinator.verify_client_signature(&quote_data, &wrong_message).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_redelivery_and_pending_claims() {
+        let coordinator = SwapCoordinator::new(BrokerConfig::default());
+        let ctx = AdaptorContext::new();
+        let client_key = ctx.generate_adaptor_secret();
+        let client_pubkey = point_to_compressed_bytes(&ctx.adaptor_point_from_secret(&client_key));
+
+        let mut quote_data = accepted_quote_data(&ctx, &client_pubkey);
+        let adaptor_point = quote_data.adaptor_point().unwrap();
+        let transcript = swap_transcript(&quote_data.quote, &client_pubkey);
+        quote_data.encrypted_signature = Some(
+            ctx.create_encrypted_signature(
+                &quote_data.broker_swap_key.scalar(),
+                &adaptor_point,
+                &transcript,
+            )
+            .unwrap(),
+        );
+        let client_signature = ctx
+            .create_encrypted_signature(&client_key, &adaptor_point, &transcript)
+            .unwrap();
+        coordinator
+            .quotes
+            .write()
+            .await
+            .insert("quote-1".to_string(), QuoteEntry::new(quote_data));
+        coordinator.executions.write().await.insert(
+            "quote-1".to_string(),
+            SwapExecution {
+                quote_id: "quote-1".to_string(),
+                client_tokens: Sensitive::default(),
+                broker_tokens: serialize_proofs(&Proofs::new()).into(),
+                client_swap_complete: false,
+                broker_swap_complete: false,
+                completed_at: None,
+            },
+        );
+
+        // The same outputs again, for the client that accepted only
+        let prepared = coordinator.prepared_swap("quote-1", &client_pubkey).await.unwrap();
+        assert!(prepared.proofs.is_empty());
+        assert!(coordinator.prepared_swap("quote-1", &[2u8; 33]).await.is_none());
+
+        coordinator
+            .verify_completion("quote-1", &client_signature)
+            .await
+            .unwrap();
+        assert!(coordinator.pending_claims().await.is_empty());
+
+        // A claim that failed after the client's signature verified is owed
+        {
+            let entry = coordinator.entry("quote-1").await.unwrap();
+            let mut quote_data = entry.data.lock().await;
+            quote_data.client_signature = Some(client_signature);
+        }
+        let claims = coordinator.pending_claims().await;
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].0, "quote-1");
+        assert!(claims[0].2.is_none());
+    }
+
+    #[test]
+    fn test_quote_amounts() {
+        // Exact-in: fee comes out of the output
+        assert_eq!(quote_amounts(QuoteType::ExactIn, 1_000, 0.005).unwrap(), (1_000, 5, 995));
+
+        // Exact-out: fee is added to the input and the output is exact
+        let (input, fee, output) = quote_amounts(QuoteType::ExactOut, 1_000, 0.005).unwrap();
+        assert_eq!(output, 1_000);
+        assert_eq!(input, 1_000 + fee);
+        assert_eq!(quote_amounts(QuoteType::ExactIn, input, 0.005).unwrap().2, 1_000);
+        // ...using the smallest input that gets there
+        assert!(quote_amounts(QuoteType::ExactIn, input - 1, 0.005).unwrap().2 < 1_000);
+
+        // Rounding never shortchanges the output
+        for amount in [1, 7, 99, 100, 101, 12_345] {
+            for fee_rate in [0.0, 0.001, 0.01, 0.3] {
+                let (input, fee, output) =
+                    quote_amounts(QuoteType::ExactOut, amount, fee_rate).unwrap();
+                assert_eq!(output, amount);
+                assert!(fee >= ((input as f64) * fee_rate).ceil() as u64);
+            }
+        }
+
+        assert!(quote_amounts(QuoteType::ExactOut, 1_000, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_quote_amounts_min_fee() {
+        let fee = Fee::new(0.005, 3);
+        assert_eq!(quote_amounts(QuoteType::ExactIn, 100, fee).unwrap(), (100, 3, 97));
+        assert_eq!(quote_amounts(QuoteType::ExactIn, 10_000, fee).unwrap(), (10_000, 50, 9_950));
+        assert_eq!(quote_amounts(QuoteType::ExactOut, 100, fee).unwrap(), (103, 3, 100));
+
+        let (input, fee, output) = quote_amounts(QuoteType::ExactOut, 10_000, fee).unwrap();
+        assert_eq!(output, 10_000);
+        assert_eq!(input, 10_000 + fee);
+        assert!(fee > 3);
+    }
+
+    #[test]
+    fn test_fee_rate_for() {
+        let config = BrokerConfig {
+            pairs: vec![PairConfig {
+                source_mint: "http://mint-a.test".to_string(),
+                target_mint: "http://mint-b.test".to_string(),
+                fee_rate: Some(0.02),
+                min_swap_amount: None,
+                max_swap_amount: None,
+            }],
+            fees: FeeSchedule {
+                tiers: vec![
+                    FeeTier {
+                        min_amount: 1_000,
+                        fee_rate: 0.007,
+                    },
+                    FeeTier {
+                        min_amount: 100_000,
+                        fee_rate: 0.003,
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (a, b) = ("http://mint-a.test", "http://mint-b.test");
+
+        // Below every tier the default rate applies
+        assert_eq!(config.fee_rate_for(b, a, 999), config.fee_rate);
+        assert_eq!(config.fee_rate_for(b, a, 5_000), 0.007);
+        assert_eq!(config.fee_rate_for(b, a, 100_000), 0.003);
+        // A pair's own rate wins over the tiers
+        assert_eq!(config.fee_rate_for(a, b, 100_000), 0.02);
+    }
+
+    #[test]
+    fn test_quote_amounts_at_rate() {
+        // 10,000 sats at 0.05 cents/sat with a 1% fee: 100 sat fee, 9,900 sats -> 495 cents
+        assert_eq!(
+            quote_amounts_at_rate(QuoteType::ExactIn, 10_000, 0.01, 0.05).unwrap(),
+            (10_000, 100, 495)
+        );
+
+        // Exactly 495 cents out needs the same input
+        assert_eq!(
+            quote_amounts_at_rate(QuoteType::ExactOut, 495, 0.01, 0.05).unwrap(),
+            (10_000, 100, 495)
+        );
+
+        // A rate of 1 matches same-unit quotes
+        assert_eq!(
+            quote_amounts_at_rate(QuoteType::ExactIn, 1_000, 0.005, 1.0).unwrap(),
+            quote_amounts(QuoteType::ExactIn, 1_000, 0.005).unwrap()
+        );
+
+        assert!(quote_amounts_at_rate(QuoteType::ExactIn, 1_000, 0.01, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_quote_amounts_with_mint_fees() {
+        // Mints without input fees leave the quote unchanged
+        assert_eq!(
+            quote_amounts_with_mint_fees(QuoteType::ExactIn, 1_000, 0.005, None, 0, 0).unwrap(),
+            (1_000, 5, 0, 995)
+        );
+
+        // 1,000 is six proofs at 100 ppk (1 sat); 995 out is seven at 1,000 ppk (7 sats)
+        assert_eq!(
+            quote_amounts_with_mint_fees(QuoteType::ExactIn, 1_000, 0.005, None, 100, 1_000)
+                .unwrap(),
+            (1_000, 5, 8, 987)
+        );
+
+        // Exact-out keeps the output and grows the input to cover them
+        let (input, fee, mint_fee, output) =
+            quote_amounts_with_mint_fees(QuoteType::ExactOut, 995, 0.005, None, 100, 1_000)
+                .unwrap();
+        assert_eq!((mint_fee, output), (8, 995));
+        assert_eq!(input - fee - mint_fee, 995);
+
+        // Target fees are converted into the source unit
+        let (_, _, mint_fee, output) =
+            quote_amounts_with_mint_fees(QuoteType::ExactIn, 10_000, 0.01, Some(0.05), 0, 1_000)
+                .unwrap();
+        assert_eq!(mint_fee, 160); // 495 cents is eight proofs: 8 cents = 160 sats
+        assert_eq!(output, 487);
+
+        // Amounts eaten up by fees can't be quoted
+        assert!(matches!(
+            quote_amounts_with_mint_fees(QuoteType::ExactIn, 3, 0.005, None, 100, 1_000),
+            Err(BrokerError::InvalidSwapRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_quote_amounts_leave_an_output() {
+        // A fee as large as the amount used to quote zero out
+        assert!(quote_amounts(QuoteType::ExactIn, 1, 0.01).is_err());
+        assert!(quote_amounts(QuoteType::ExactIn, 2, Fee::new(0.0, 2)).is_err());
+        assert!(quote_amounts(QuoteType::ExactIn, 3, Fee::new(0.0, 2)).is_ok());
+
+        // ...or nothing once converted
+        assert!(quote_amounts_at_rate(QuoteType::ExactIn, 10, 0.01, 0.05).is_err());
+        // 21 sats less a 1 sat fee is worth a cent, but not after 3 sats of mint fees
+        assert!(quote_amounts_at_rate(QuoteType::ExactIn, 21, 0.01, 0.05).is_ok());
+        assert!(
+            quote_amounts_with_mint_fees(QuoteType::ExactIn, 21, 0.01, Some(0.05), 1_000, 0)
+                .is_err()
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_quote_amounts_with_mint_fees(
+            exact_out in any::<bool>(),
+            amount in 1u64..1 << 40,
+            rate in 0.0f64..0.5,
+            min in 0u64..100,
+            source_fee_ppk in 0u64..2_000,
+            target_fee_ppk in 0u64..2_000,
+        ) {
+            let quote_type = if exact_out { QuoteType::ExactOut } else { QuoteType::ExactIn };
+            let fee = Fee::new(rate, min);
+            let result = quote_amounts_with_mint_fees(
+                quote_type,
+                amount,
+                fee,
+                None,
+                source_fee_ppk,
+                target_fee_ppk,
+            );
+            // Exact-out quotes can always be made; exact-in ones fail only
+            // when the fees take the whole amount
+            let (input, fee, mint_fee, output) = match result {
+                Ok(amounts) => amounts,
+                Err(_) => {
+                    prop_assert!(!exact_out);
+                    return Ok(());
+                }
+            };
+            prop_assert!(output > 0);
+            prop_assert!(fee >= min);
+            prop_assert_eq!(input, output + fee + mint_fee);
+            match quote_type {
+                QuoteType::ExactIn => prop_assert_eq!(input, amount),
+                QuoteType::ExactOut => prop_assert_eq!(output, amount),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restore_quote_roundtrip() {
+        let coordinator = SwapCoordinator::new(BrokerConfig::default());
+        let ctx = AdaptorContext::new();
+        let quote_data = accepted_quote_data(&ctx, &[2u8; 33]);
+        let quote = quote_data.quote.clone();
+
+        let secrets = QuoteSecrets {
+            broker_swap_key: quote_data.broker_swap_key.to_bytes(),
+            adaptor_secret: quote_data.adaptor_secret().unwrap().to_bytes().to_vec(),
+            refund_at: Some(1_700_000_000),
+        };
+
+        coordinator
+            .restore_quote(quote.clone(), &secrets, None, None, None, None)
+            .await
+            .unwrap();
+
+        let restored = coordinator.quote_secrets(&quote.quote_id).await.unwrap();
+        assert_eq!(restored.adaptor_secret, secrets.adaptor_secret);
+        assert_eq!(restored.refund_at, Some(1_700_000_000));
+
+        // Keys that don't match the quote's points are rejected
+        let wrong = QuoteSecrets {
+            broker_swap_key: secrets.broker_swap_key.clone(),
+            adaptor_secret: scalar_to_bytes(&ctx.generate_adaptor_secret()),
+            refund_at: secrets.refund_at,
+        };
+        assert!(coordinator
+            .restore_quote(quote.clone(), &wrong, None, None, None, None)
+            .await
+            .is_err());
+
+        // Only a client-first quote may come without its adaptor secret
+        let without_secret = QuoteSecrets {
+            broker_swap_key: secrets.broker_swap_key.clone(),
+            adaptor_secret: Vec::new(),
+            refund_at: secrets.refund_at,
+        };
+        assert!(coordinator
+            .restore_quote(quote.clone(), &without_secret, None, None, None, None)
+            .await
+            .is_err());
+        let client_first = SwapQuote {
+            quote_id: "quote-2".to_string(),
+            claim_order: ClaimOrder::ClientFirst,
+            ..quote
+        };
+        coordinator
+            .restore_quote(client_first, &without_secret, None, None, None, None)
+            .await
+            .unwrap();
+        let restored = coordinator.quote_secrets("quote-2").await.unwrap();
+        assert!(restored.adaptor_secret.is_empty());
+        assert!(coordinator.revealed_adaptor_secret("quote-2").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_claim_after_client() {
+        let coordinator = SwapCoordinator::new(BrokerConfig::default());
+        let liquidity = LiquidityManager::new(vec![]).await.unwrap();
+        let ctx = AdaptorContext::new();
+        let client_key = ctx.generate_adaptor_secret();
+        let client_pubkey = point_to_compressed_bytes(&ctx.adaptor_point_from_secret(&client_key));
+
+        let mut quote_data = accepted_quote_data(&ctx, &client_pubkey);
+        quote_data.quote.claim_order = ClaimOrder::ClientFirst;
+        quote_data.adaptor_secret = None;
+        let locked = Proof::new(
+            Amount::from(8),
+            cdk::nuts::Id::from_str("009a1f293253e41e").unwrap(),
+            cdk::secret::Secret::generate(),
+            SecretKey::generate().public_key(),
+        );
+        let signatures = coordinator.sign_outputs(&quote_data, &vec![locked.clone()]).unwrap();
+        assert_eq!(signatures.len(), 1);
+        coordinator
+            .quotes
+            .write()
+            .await
+            .insert("quote-1".to_string(), QuoteEntry::new(quote_data));
+        coordinator.executions.write().await.insert(
+            "quote-1".to_string(),
+            SwapExecution {
+                quote_id: "quote-1".to_string(),
+                client_tokens: Sensitive::default(),
+                broker_tokens: serialize_proofs(&vec![locked.clone()]).into(),
+                client_swap_complete: false,
+                broker_swap_complete: false,
+                completed_at: None,
+            },
+        );
+
+        let awaiting = coordinator.awaiting_client_claims().await;
+        assert_eq!(awaiting.len(), 1);
+        assert_eq!(awaiting[0].2, vec![locked.clone()]);
+
+        // Spent by the broker's own refund: nothing to recover or claim
+        let refund_witness = r#"{"signatures":["00"]}"#.to_string();
+        let claimed = coordinator
+            .claim_after_client("quote-1", &[(locked, refund_witness)], Vec::new(), &liquidity)
+            .await
+            .unwrap();
+        assert_eq!(claimed, None);
+        assert_eq!(
+            coordinator.get_quote("quote-1").await.unwrap().status,
+            SwapStatus::Accepted
+        );
+        assert!(coordinator.revealed_adaptor_secret("quote-1").await.is_err());
+
+        coordinator.close_refunded("quote-1", &liquidity).await.unwrap();
+        assert!(coordinator.awaiting_client_claims().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expire_quotes() {
+        let coordinator = SwapCoordinator::new(BrokerConfig::default());
+        let ctx = AdaptorContext::new();
+
+        let mut stale = accepted_quote_data(&ctx, &[2u8; 33]);
+        stale.quote.quote_id = "stale".to_string();
+        stale.quote.status = SwapStatus::Pending;
+        stale.quote.expires_at = Some(SystemTime::now() - Duration::from_secs(1));
+
+        let mut fresh = accepted_quote_data(&ctx, &[2u8; 33]);
+        fresh.quote.quote_id = "fresh".to_string();
+        fresh.quote.status = SwapStatus::Pending;
+        fresh.quote.expires_at = Some(SystemTime::now() + Duration::from_secs(300));
+
+        // Accepted quotes are kept even after the quote expiry
+        let mut accepted = accepted_quote_data(&ctx, &[2u8; 33]);
+        accepted.quote.expires_at = Some(SystemTime::now() - Duration::from_secs(1));
+
+        {
+            let mut quotes = coordinator.quotes.write().await;
+            quotes.insert("stale".to_string(), QuoteEntry::new(stale));
+            quotes.insert("fresh".to_string(), QuoteEntry::new(fresh));
+            quotes.insert("quote-1".to_string(), QuoteEntry::new(accepted));
+        }
+
+        let liquidity = LiquidityManager::new(vec![]).await.unwrap();
+        assert_eq!(
+            coordinator.expire_quotes(&liquidity).await,
+            vec!["stale".to_string()]
+        );
+        assert!(coordinator.get_quote("stale").await.is_none());
+        assert!(coordinator.get_quote("fresh").await.is_some());
+        assert!(coordinator.get_quote("quote-1").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_clock_drives_expiry() {
+        let clock = Arc::new(ManualClock::at_unix(1_700_000_000));
+        let coordinator = SwapCoordinator::new(BrokerConfig::default()).with_clock(clock.clone());
+        let ctx = AdaptorContext::new();
+
+        let mut pending = accepted_quote_data(&ctx, &[2u8; 33]);
+        pending.quote.status = SwapStatus::Pending;
+        pending.quote.expires_at = Some(clock.now() + Duration::from_secs(60));
+        coordinator
+            .quotes
+            .write()
+            .await
+            .insert("quote-1".to_string(), QuoteEntry::new(pending));
+
+        let liquidity = LiquidityManager::new(vec![]).await.unwrap();
+        assert!(coordinator.expire_quotes(&liquidity).await.is_empty());
+
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(
+            coordinator.expire_quotes(&liquidity).await,
+            vec!["quote-1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        // Quote IDs carry the time, so the clock is fixed too
+        let coordinator = || {
+            SwapCoordinator::new(BrokerConfig::default())
+                .with_rng(SwapRng::seeded(42))
+                .with_clock(Arc::new(ManualClock::at_unix(1_700_000_000)))
+        };
+        let (a, b) = (coordinator(), coordinator());
+
+        let id = a.generate_quote_id();
+        assert_eq!(id, b.generate_quote_id());
+        assert_eq!(id.len(), 36);
+        let next = a.generate_quote_id();
+        assert_ne!(next, id);
+        assert_eq!(next, b.generate_quote_id());
+
+        // The adaptor context draws from the same sequence
+        assert_eq!(
+            a.adaptor_ctx.generate_adaptor_secret(),
+            b.adaptor_ctx.generate_adaptor_secret()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_extend_quote() {
+        let coordinator = SwapCoordinator::new(BrokerConfig::default());
+        let to_mint = "http://localhost:3339";
+        let liquidity = LiquidityManager::new(vec![MintConfig {
+            mint_url: to_mint.to_string(),
+            name: "Mint B".to_string(),
+            unit: "sat".to_string(),
+        }])
+        .await
+        .unwrap();
+        let ctx = AdaptorContext::new();
+
+        let mut pending = accepted_quote_data(&ctx, &[2u8; 33]);
+        pending.quote.status = SwapStatus::Pending;
+        pending.quote.expires_at = Some(SystemTime::now() + Duration::from_secs(60));
+        pending.quote.expiry = unix_now() + 60;
+        let mut stale = accepted_quote_data(&ctx, &[2u8; 33]);
+        stale.quote.quote_id = "stale".to_string();
+        stale.quote.status = SwapStatus::Pending;
+        stale.quote.expires_at = Some(SystemTime::now() - Duration::from_secs(1));
+        {
+            let mut quotes = coordinator.quotes.write().await;
+            quotes.insert("quote-1".to_string(), QuoteEntry::new(pending));
+            quotes.insert("stale".to_string(), QuoteEntry::new(stale));
+        }
+        let extension = Duration::from_secs(300);
+
+        // Without its reservation the quote has no liquidity to keep
+        assert!(coordinator
+            .extend_quote("quote-1", extension, &liquidity)
+            .await
+            .is_err());
+
+        // Nothing to hold, so the empty mint covers it
+        liquidity.reserve(to_mint, "quote-1", 0).await.unwrap();
+        let quote = coordinator
+            .extend_quote("quote-1", extension, &liquidity)
+            .await
+            .unwrap();
+        assert!(quote.extended);
+        assert!(quote.expires_in > 300 && quote.expires_in <= 360);
+        assert!(quote.expiry > unix_now() + 300);
+        let stored = coordinator.get_quote("quote-1").await.unwrap();
+        assert_eq!((stored.expiry, stored.expires_at), (quote.expiry, quote.expires_at));
+
+        // Only once
+        assert!(coordinator
+            .extend_quote("quote-1", extension, &liquidity)
+            .await
+            .is_err());
+
+        liquidity.reserve(to_mint, "stale", 0).await.unwrap();
+        assert!(matches!(
+            coordinator.extend_quote("stale", extension, &liquidity).await,
+            Err(BrokerError::QuoteExpired(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_busy_quote_does_not_block_others() {
+        let coordinator = SwapCoordinator::new(BrokerConfig::default());
+        let ctx = AdaptorContext::new();
+
+        let mut busy = accepted_quote_data(&ctx, &[2u8; 33]);
+        busy.quote.quote_id = "busy".to_string();
+        busy.quote.status = SwapStatus::Pending;
+        busy.quote.expires_at = Some(SystemTime::now() - Duration::from_secs(1));
+        let busy = QuoteEntry::new(busy);
+        let idle = accepted_quote_data(&ctx, &[2u8; 33]);
+        {
+            let mut quotes = coordinator.quotes.write().await;
+            quotes.insert("busy".to_string(), busy.clone());
+            quotes.insert("quote-1".to_string(), QuoteEntry::new(idle));
+        }
+
+        // As if a mint call for "busy" were in flight
+        let guard = busy.data.lock().await;
+        let quote = tokio::time::timeout(Duration::from_secs(1), coordinator.get_quote("quote-1"))
+            .await
+            .expect("other quotes stay available");
+        assert!(quote.is_some());
+        assert!(busy.is_open());
+
+        // Expiry leaves it alone until it is done
+        let liquidity = LiquidityManager::new(vec![]).await.unwrap();
+        assert!(coordinator.expire_quotes(&liquidity).await.is_empty());
+        drop(guard);
+        assert_eq!(coordinator.expire_quotes(&liquidity).await, vec!["busy".to_string()]);
+    }
 }
@@ -0,0 +1,420 @@
+//! In-process mock mint for tests
+//!
+//! [`MockMint`] serves the parts of the Cashu mint API the broker and its
+//! clients use — info, keys and keysets (NUT-01/02), bolt11 mint quotes and
+//! minting (NUT-04), swaps (NUT-03) and checkstate (NUT-07) — on a random
+//! local port, so the whole accept/complete flow can run without real mints.
+//! Mint quotes are paid as soon as they are created, like a FakeWallet
+//! backend. Swaps check that inputs are signed by the mint, unspent and
+//! balanced, and that P2PK-locked inputs (NUT-11) carry a valid witness.
+//! Blind signatures carry DLEQ proofs (NUT-12). There are no input fees or
+//! melts.
+//!
+//! Only built with the `test-support` feature.
+
+use crate::error::{BrokerError, Result};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use cdk::dhke::{sign_message, verify_message};
+use cdk::nuts::{BlindSignature, CurrencyUnit, Id, Nut10Secret, Proofs, PublicKey, SecretKey};
+use cdk::wallet::Wallet;
+use cdk::Amount;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+/// Largest denomination is 2^(MAX_ORDER - 1)
+const MAX_ORDER: u32 = 32;
+
+/// A mock mint listening on localhost; stops when dropped
+pub struct MockMint {
+    url: String,
+    state: Arc<Mutex<MintState>>,
+    server: JoinHandle<()>,
+}
+
+struct MintState {
+    keyset_id: String,
+    keys: BTreeMap<u64, SecretKey>,
+    /// Mint quote ID → (amount, already minted)
+    quotes: HashMap<String, (u64, bool)>,
+    /// Y = hash_to_curve(secret) of every spent proof
+    spent: HashSet<PublicKey>,
+}
+
+impl MockMint {
+    /// Start a mint with a fresh sat keyset on a random port
+    pub async fn start() -> Result<Self> {
+        let keys: BTreeMap<u64, SecretKey> = (0..MAX_ORDER)
+            .map(|order| (1u64 << order, SecretKey::generate()))
+            .collect();
+        let state = Arc::new(Mutex::new(MintState {
+            keyset_id: keyset_id(&keys),
+            keys,
+            quotes: HashMap::new(),
+            spent: HashSet::new(),
+        }));
+
+        let router = Router::new()
+            .route("/v1/info", get(info))
+            .route("/v1/keys", get(keys))
+            .route("/v1/keys/:id", get(keys))
+            .route("/v1/keysets", get(keysets))
+            .route("/v1/mint/quote/bolt11", post(create_mint_quote))
+            .route("/v1/mint/quote/bolt11/:id", get(get_mint_quote))
+            .route("/v1/mint/bolt11", post(mint))
+            .route("/v1/swap", post(swap))
+            .route("/v1/checkstate", post(check_state))
+            .with_state(state.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+
+        Ok(Self { url, state, server })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Number of proofs spent so far
+    pub fn spent_count(&self) -> usize {
+        self.state.lock().expect("mint state poisoned").spent.len()
+    }
+
+    /// An empty in-memory wallet for this mint
+    pub async fn wallet(&self) -> Result<Wallet> {
+        let localstore = Arc::new(
+            cdk_sqlite::wallet::memory::empty()
+                .await
+                .map_err(|e| BrokerError::Cdk(e.to_string()))?,
+        );
+        let seed: [u8; 64] = std::array::from_fn(|_| rand::random());
+
+        Wallet::new(&self.url, CurrencyUnit::Sat, localstore, seed, None)
+            .map_err(|e| BrokerError::Cdk(e.to_string()))
+    }
+}
+
+impl Drop for MockMint {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Keyset ID (NUT-02 v1): "00" and the first 7 bytes of the SHA-256 of the
+/// public keys, ordered by amount
+fn keyset_id(keys: &BTreeMap<u64, SecretKey>) -> String {
+    let mut hasher = Sha256::new();
+    for key in keys.values() {
+        hasher.update(key.public_key().to_bytes());
+    }
+    format!("00{}", hex::encode(&hasher.finalize()[..7]))
+}
+
+/// NUT error response
+struct MintError {
+    code: u32,
+    detail: String,
+}
+
+impl MintError {
+    fn new(code: u32, detail: impl Into<String>) -> Self {
+        Self {
+            code,
+            detail: detail.into(),
+        }
+    }
+}
+
+impl IntoResponse for MintError {
+    fn into_response(self) -> Response {
+        let body = json!({"detail": self.detail, "code": self.code});
+        (StatusCode::BAD_REQUEST, Json(body)).into_response()
+    }
+}
+
+type Shared = State<Arc<Mutex<MintState>>>;
+
+#[derive(Deserialize)]
+struct BlindedMessage {
+    amount: u64,
+    id: String,
+    #[serde(rename = "B_")]
+    blinded_secret: PublicKey,
+}
+
+#[derive(Deserialize)]
+struct MintQuoteRequest {
+    amount: u64,
+}
+
+#[derive(Deserialize)]
+struct MintRequest {
+    quote: String,
+    outputs: Vec<BlindedMessage>,
+}
+
+#[derive(Deserialize)]
+struct SwapRequest {
+    inputs: Proofs,
+    outputs: Vec<BlindedMessage>,
+}
+
+#[derive(Deserialize)]
+struct CheckStateRequest {
+    #[serde(rename = "Ys")]
+    ys: Vec<PublicKey>,
+}
+
+async fn info() -> Json<Value> {
+    Json(json!({
+        "name": "Mock mint",
+        "version": "mock/0.1.0",
+        "nuts": {
+            "4": {
+                "methods": [{"method": "bolt11", "unit": "sat", "min_amount": 1, "max_amount": 1_000_000}],
+                "disabled": false
+            },
+            "5": {"methods": [], "disabled": true},
+            "7": {"supported": true},
+            "10": {"supported": true},
+            "11": {"supported": true}
+        }
+    }))
+}
+
+async fn keys(State(state): Shared) -> Json<Value> {
+    let state = state.lock().expect("mint state poisoned");
+    let keys: BTreeMap<String, String> = state
+        .keys
+        .iter()
+        .map(|(amount, key)| (amount.to_string(), key.public_key().to_hex()))
+        .collect();
+
+    Json(json!({
+        "keysets": [{"id": state.keyset_id, "unit": "sat", "keys": keys}]
+    }))
+}
+
+async fn keysets(State(state): Shared) -> Json<Value> {
+    let state = state.lock().expect("mint state poisoned");
+    Json(json!({
+        "keysets": [{"id": state.keyset_id, "unit": "sat", "active": true, "input_fee_ppk": 0}]
+    }))
+}
+
+fn quote_json(id: &str, amount: u64, issued: bool) -> Json<Value> {
+    let expiry = crate::swap::unix_now() + 3600;
+    Json(json!({
+        "quote": id,
+        "request": format!("lnbcrt{}n1mock{}", amount * 10, id.replace('-', "")),
+        "amount": amount,
+        "unit": "sat",
+        "state": if issued { "ISSUED" } else { "PAID" },
+        "expiry": expiry
+    }))
+}
+
+async fn create_mint_quote(State(state): Shared, Json(req): Json<MintQuoteRequest>) -> Json<Value> {
+    let id = Uuid::new_v4().to_string();
+    state
+        .lock()
+        .expect("mint state poisoned")
+        .quotes
+        .insert(id.clone(), (req.amount, false));
+    quote_json(&id, req.amount, false)
+}
+
+async fn get_mint_quote(
+    State(state): Shared,
+    Path(id): Path<String>,
+) -> std::result::Result<Json<Value>, MintError> {
+    let state = state.lock().expect("mint state poisoned");
+    let (amount, issued) = state
+        .quotes
+        .get(&id)
+        .copied()
+        .ok_or_else(|| MintError::new(20004, "Unknown quote"))?;
+    Ok(quote_json(&id, amount, issued))
+}
+
+async fn mint(
+    State(state): Shared,
+    Json(req): Json<MintRequest>,
+) -> std::result::Result<Json<Value>, MintError> {
+    let mut state = state.lock().expect("mint state poisoned");
+    let (amount, issued) = state
+        .quotes
+        .get(&req.quote)
+        .copied()
+        .ok_or_else(|| MintError::new(20004, "Unknown quote"))?;
+    if issued {
+        return Err(MintError::new(20002, "Tokens already issued for quote"));
+    }
+    if outputs_total(&req.outputs) != amount {
+        return Err(MintError::new(
+            11002,
+            "Outputs do not match the quote amount",
+        ));
+    }
+
+    let signatures = state.sign(&req.outputs)?;
+    state.quotes.insert(req.quote, (amount, true));
+    Ok(Json(json!({"signatures": signatures})))
+}
+
+async fn swap(
+    State(state): Shared,
+    Json(req): Json<SwapRequest>,
+) -> std::result::Result<Json<Value>, MintError> {
+    let mut state = state.lock().expect("mint state poisoned");
+    let ys = state.verify_inputs(&req.inputs)?;
+
+    let inputs_total: u64 = req.inputs.iter().map(|p| u64::from(p.amount)).sum();
+    if inputs_total != outputs_total(&req.outputs) {
+        return Err(MintError::new(11002, "Transaction is not balanced"));
+    }
+
+    let signatures = state.sign(&req.outputs)?;
+    state.spent.extend(ys);
+    Ok(Json(json!({"signatures": signatures})))
+}
+
+async fn check_state(State(state): Shared, Json(req): Json<CheckStateRequest>) -> Json<Value> {
+    let state = state.lock().expect("mint state poisoned");
+    let states: Vec<Value> = req
+        .ys
+        .iter()
+        .map(|y| {
+            let spent = state.spent.contains(y);
+            json!({"Y": y.to_hex(), "state": if spent { "SPENT" } else { "UNSPENT" }, "witness": null})
+        })
+        .collect();
+    Json(json!({"states": states}))
+}
+
+fn outputs_total(outputs: &[BlindedMessage]) -> u64 {
+    outputs.iter().map(|o| o.amount).sum()
+}
+
+impl MintState {
+    fn key(&self, keyset_id: &str, amount: u64) -> std::result::Result<&SecretKey, MintError> {
+        if keyset_id != self.keyset_id {
+            return Err(MintError::new(12001, "Unknown keyset"));
+        }
+        self.keys
+            .get(&amount)
+            .ok_or_else(|| MintError::new(11005, format!("Unsupported amount {}", amount)))
+    }
+
+    /// Blind signatures on `outputs`
+    fn sign(&self, outputs: &[BlindedMessage]) -> std::result::Result<Vec<Value>, MintError> {
+        outputs
+            .iter()
+            .map(|output| {
+                let key = self.key(&output.id, output.amount)?;
+                let c = sign_message(key, &output.blinded_secret)
+                    .map_err(|e| MintError::new(10002, e.to_string()))?;
+                let id =
+                    Id::from_str(&output.id).map_err(|e| MintError::new(12001, e.to_string()))?;
+                let signature = BlindSignature::new(
+                    Amount::from(output.amount),
+                    c,
+                    id,
+                    &output.blinded_secret,
+                    key.clone(),
+                )
+                .map_err(|e| MintError::new(10002, e.to_string()))?;
+                Ok(json!({
+                    "amount": output.amount,
+                    "id": output.id,
+                    "C_": c.to_hex(),
+                    "dleq": signature.dleq,
+                }))
+            })
+            .collect()
+    }
+
+    /// Check the inputs can be spent, returning their Ys
+    fn verify_inputs(&self, inputs: &Proofs) -> std::result::Result<Vec<PublicKey>, MintError> {
+        let mut ys = Vec::with_capacity(inputs.len());
+        for proof in inputs {
+            let y = proof
+                .y()
+                .map_err(|e| MintError::new(10003, e.to_string()))?;
+            if self.spent.contains(&y) || ys.contains(&y) {
+                return Err(MintError::new(11001, "Token already spent"));
+            }
+
+            let key = self.key(&proof.keyset_id.to_string(), u64::from(proof.amount))?;
+            verify_message(key, proof.c, proof.secret.as_bytes())
+                .map_err(|_| MintError::new(10003, "Proof could not be verified"))?;
+
+            let locked = serde_json::from_str::<Nut10Secret>(&proof.secret.to_string()).is_ok();
+            if locked {
+                proof
+                    .verify_p2pk()
+                    .map_err(|e| MintError::new(10003, format!("Witness invalid: {}", e)))?;
+            }
+            ys.push(y);
+        }
+        Ok(ys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::amount::SplitTarget;
+    use cdk::Amount;
+
+    #[tokio::test]
+    async fn test_mint_issues_once() {
+        let mint = MockMint::start().await.unwrap();
+        let wallet = mint.wallet().await.unwrap();
+
+        let quote = wallet.mint_quote(Amount::from(100), None).await.unwrap();
+        let proofs = wallet
+            .mint(&quote.id, SplitTarget::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(u64::from(wallet.total_balance().await.unwrap()), 100);
+
+        // Minting twice on one quote is refused
+        assert!(wallet
+            .mint(&quote.id, SplitTarget::default(), None)
+            .await
+            .is_err());
+
+        // The mint knows nothing about the proofs until they are swapped
+        assert_eq!(mint.spent_count(), 0);
+        let ys: Vec<PublicKey> = proofs.iter().map(|p| p.y().unwrap()).collect();
+        let state = mint.state.lock().unwrap();
+        assert!(state.verify_inputs(&proofs).is_ok());
+        assert!(ys.iter().all(|y| !state.spent.contains(y)));
+    }
+
+    #[test]
+    fn test_keyset_id() {
+        let keys: BTreeMap<u64, SecretKey> = (0..4)
+            .map(|order| (1u64 << order, SecretKey::generate()))
+            .collect();
+        let id = keyset_id(&keys);
+        assert_eq!(id.len(), 16);
+        assert!(id.starts_with("00"));
+        assert_eq!(id, keyset_id(&keys));
+    }
+}
@@ -0,0 +1,88 @@
+//! Timeouts for wallet calls to mints
+//!
+//! A mint that takes a connection and never answers would otherwise hold up a
+//! swap, and the request waiting on it, for good. Every call through
+//! [`LiquidityManager::mint_call`](crate::liquidity::LiquidityManager::mint_call)
+//! names its [`MintOp`] and is given up after that operation's timeout. A
+//! timed-out attempt is retried like a dropped connection and counts against
+//! the mint's circuit breaker; if no attempt answers in time the call fails
+//! with [`BrokerError::MintTimeout`](crate::error::BrokerError::MintTimeout),
+//! which clients can retry.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Kind of wallet call made to a mint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MintOp {
+    MintQuote,  // NUT-04 mint quote
+    Mint,       // Minting a paid mint quote
+    Swap,       // NUT-03 swap
+    CheckState, // NUT-07 proof states
+    Melt,       // NUT-05 melt quote or payment
+    Other,      // Keysets, mint info
+}
+
+impl fmt::Display for MintOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MintQuote => write!(f, "mint quote"),
+            Self::Mint => write!(f, "mint"),
+            Self::Swap => write!(f, "swap"),
+            Self::CheckState => write!(f, "checkstate"),
+            Self::Melt => write!(f, "melt"),
+            Self::Other => write!(f, "call"),
+        }
+    }
+}
+
+/// How long each kind of mint call may take per attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MintTimeouts {
+    pub mint_quote: Duration,
+    pub mint: Duration,
+    pub swap: Duration,
+    pub check_state: Duration,
+    pub melt: Duration, // Includes the Lightning payment
+    pub other: Duration,
+}
+
+impl Default for MintTimeouts {
+    fn default() -> Self {
+        Self {
+            mint_quote: Duration::from_secs(10),
+            mint: Duration::from_secs(30),
+            swap: Duration::from_secs(30),
+            check_state: Duration::from_secs(10),
+            melt: Duration::from_secs(120),
+            other: Duration::from_secs(30),
+        }
+    }
+}
+
+impl MintTimeouts {
+    /// Timeout of one attempt at `op`
+    pub fn get(&self, op: MintOp) -> Duration {
+        match op {
+            MintOp::MintQuote => self.mint_quote,
+            MintOp::Mint => self.mint,
+            MintOp::Swap => self.swap,
+            MintOp::CheckState => self.check_state,
+            MintOp::Melt => self.melt,
+            MintOp::Other => self.other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let timeouts = MintTimeouts::default();
+        assert_eq!(timeouts.get(MintOp::Swap), Duration::from_secs(30));
+        assert_eq!(timeouts.get(MintOp::CheckState), Duration::from_secs(10));
+        assert_eq!(MintOp::CheckState.to_string(), "checkstate");
+    }
+}
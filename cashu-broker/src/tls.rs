@@ -0,0 +1,88 @@
+//! HTTPS for the built-in server (`tls` feature)
+//!
+//! For deployments without a reverse proxy, the broker can terminate TLS
+//! itself with rustls, from a PEM certificate chain and private key. The files
+//! are checked for changes every minute and a renewed certificate (e.g. from
+//! certbot) is loaded without dropping connections or restarting. A renewal
+//! that fails to load is logged and the current certificate is kept.
+
+use crate::error::BrokerError;
+use axum_server::tls_rustls::RustlsConfig;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+/// How often the certificate and key files are checked for changes
+const CERT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Load the certificate chain and private key
+pub async fn load(cert_path: &Path, key_path: &Path) -> Result<RustlsConfig, BrokerError> {
+    RustlsConfig::from_pem_file(cert_path, key_path)
+        .await
+        .map_err(|e| {
+            BrokerError::Other(anyhow::anyhow!(
+                "Failed to load TLS certificate {} with key {}: {}",
+                cert_path.display(),
+                key_path.display(),
+                e
+            ))
+        })
+}
+
+/// Reload the certificate whenever its files change
+pub async fn watch(config: RustlsConfig, cert_path: PathBuf, key_path: PathBuf) {
+    let mut loaded = modified(&cert_path, &key_path);
+    let mut interval = tokio::time::interval(CERT_CHECK_INTERVAL);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let current = modified(&cert_path, &key_path);
+        if current.is_none() || current == loaded {
+            continue;
+        }
+
+        // Until both files are readable and match, retry on the next check
+        match config.reload_from_pem_file(&cert_path, &key_path).await {
+            Ok(()) => {
+                info!("Reloaded TLS certificate from {}", cert_path.display());
+                loaded = current;
+            }
+            Err(e) => warn!(
+                "Failed to reload TLS certificate, keeping the current one: {}",
+                e
+            ),
+        }
+    }
+}
+
+/// Modification times of the certificate and key, if both can be read
+fn modified(cert_path: &Path, key_path: &Path) -> Option<(SystemTime, SystemTime)> {
+    let mtime = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    Some((mtime(cert_path)?, mtime(key_path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_errors() {
+        let dir = std::env::temp_dir().join(format!("broker-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cert = dir.join("cert.pem");
+        let key = dir.join("key.pem");
+
+        let err = load(&cert, &key).await.unwrap_err();
+        assert!(err.to_string().contains("cert.pem"));
+        assert_eq!(modified(&cert, &key), None);
+
+        // Files that aren't PEM are rejected too
+        std::fs::write(&cert, "not a certificate").unwrap();
+        std::fs::write(&key, "not a key").unwrap();
+        assert!(load(&cert, &key).await.is_err());
+        assert!(modified(&cert, &key).is_some());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+//! Terminal monitor for operators
+//!
+//! `broker-top` polls a running broker's API and redraws a screen with the
+//! liquidity of each mint, the mints' health, circuits and latency, the swaps
+//! in flight (accepted and not yet completed) and the latest failed swaps. It
+//! only reads the public routes under `/v1`, with an API key where those are
+//! guarded, so it works over an ssh session on the broker's box without the
+//! admin token. [`Monitor`] fetches a [`Snapshot`] and [`render`] turns it
+//! into text; the binary clears the terminal between frames.
+
+use crate::api::{
+    ErrorResponse, LiquidityResponse, MintHealthInfo, MintsHealthResponse, TOTAL_COUNT_HEADER,
+};
+use crate::api_keys::API_KEY_HEADER;
+use crate::circuit_breaker::CircuitState;
+use crate::db::QuoteRecord;
+use crate::error::{BrokerError, Result};
+use crate::versioning::ApiVersion;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Timeout for each request to the broker
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// In-flight swaps listed
+pub const IN_FLIGHT_SHOWN: usize = 10;
+
+/// Failed swaps listed
+pub const FAILURES_SHOWN: usize = 10;
+
+/// Escape sequence clearing the terminal and moving the cursor home
+pub const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// One poll of the broker
+#[derive(Debug)]
+pub struct Snapshot {
+    pub taken_at: DateTime<Utc>,
+    pub liquidity: LiquidityResponse,
+    pub health: Vec<MintHealthInfo>,
+    pub in_flight: Vec<QuoteRecord>, // Newest first
+    pub in_flight_total: u64,
+    pub failures: Vec<QuoteRecord>, // Newest first
+}
+
+/// Polls a broker's API
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl Monitor {
+    pub fn new(base_url: &str, api_key: Option<String>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| BrokerError::Http(e.to_string()))?;
+
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        })
+    }
+
+    /// Fetch everything the screen shows
+    pub async fn snapshot(&self) -> Result<Snapshot> {
+        let in_flight_path = format!("/quotes?status=accepted&limit={}", IN_FLIGHT_SHOWN);
+        let failures_path = format!("/quotes?status=failed&limit={}", FAILURES_SHOWN);
+        let (liquidity, health, in_flight, failures) = tokio::try_join!(
+            self.get::<LiquidityResponse>("/liquidity"),
+            self.get::<MintsHealthResponse>("/mints/health"),
+            self.get::<Vec<QuoteRecord>>(&in_flight_path),
+            self.get::<Vec<QuoteRecord>>(&failures_path),
+        )?;
+        let (in_flight, in_flight_total) = in_flight;
+
+        Ok(Snapshot {
+            taken_at: Utc::now(),
+            liquidity: liquidity.0,
+            health: health.0.mints,
+            in_flight_total: in_flight_total.unwrap_or(in_flight.len() as u64),
+            in_flight,
+            failures: failures.0,
+        })
+    }
+
+    /// GET a route under the current version, with the total count of a listing
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<(T, Option<u64>)> {
+        let url = format!("{}{}{}", self.base_url, ApiVersion::CURRENT.prefix(), path);
+        let mut request = self.http.get(&url);
+        if let Some(api_key) = &self.api_key {
+            request = request.header(API_KEY_HEADER, api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| BrokerError::Http(e.to_string()))?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = match response.json::<ErrorResponse>().await {
+                Ok(error) => error.error,
+                Err(_) => status.to_string(),
+            };
+            return Err(BrokerError::Http(format!("GET {}: {}", url, message)));
+        }
+
+        let total = response
+            .headers()
+            .get(TOTAL_COUNT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+        let body = response
+            .json()
+            .await
+            .map_err(|e| BrokerError::Http(e.to_string()))?;
+        Ok((body, total))
+    }
+}
+
+/// Render a snapshot as a screen of text
+pub fn render(snapshot: &Snapshot, source: &str) -> String {
+    let mut out = String::new();
+    let liquidity = &snapshot.liquidity;
+    // Writing to a String can't fail
+    let _ = writeln!(
+        out,
+        "broker-top  {}  {}",
+        source,
+        snapshot.taken_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    let _ = writeln!(
+        out,
+        "balance {}  available {}  locked {}\n",
+        liquidity.total_balance, liquidity.total_available, liquidity.total_locked
+    );
+
+    let _ = writeln!(
+        out,
+        "{:<20} {:>10} {:>10} {:>9} {:>9} {:>9}  {:<9} {:<9} {:>8}",
+        "MINT",
+        "BALANCE",
+        "AVAILABLE",
+        "RESERVED",
+        "LOCKED",
+        "QUARANT",
+        "HEALTH",
+        "CIRCUIT",
+        "LATENCY"
+    );
+    for mint in &liquidity.mints {
+        let health = snapshot
+            .health
+            .iter()
+            .find(|health| health.mint_url == mint.mint_url);
+        let latency = health
+            .and_then(|health| health.latency_ms)
+            .map(|ms| format!("{}ms", ms))
+            .unwrap_or_else(|| "-".to_string());
+        let _ = writeln!(
+            out,
+            "{:<20} {:>10} {:>10} {:>9} {:>9} {:>9}  {:<9} {:<9} {:>8}",
+            truncate(&mint.name, 20),
+            mint.balance,
+            mint.available,
+            mint.reserved,
+            mint.locked,
+            mint.quarantined,
+            if mint.healthy { "ok" } else { "DOWN" },
+            health.map_or("-", |health| circuit(health.circuit.state)),
+            latency,
+        );
+        if let Some(error) = health.and_then(|health| health.last_error.as_deref()) {
+            let _ = writeln!(out, "  last error: {}", truncate(error, 90));
+        }
+    }
+
+    let _ = writeln!(out, "\nIN FLIGHT ({})", snapshot.in_flight_total);
+    if snapshot.in_flight.is_empty() {
+        out.push_str("  none\n");
+    }
+    for quote in &snapshot.in_flight {
+        let _ = writeln!(
+            out,
+            "  {}  {:>10} -> {:<10}  {} -> {}  since {}",
+            truncate(&quote.id, 12),
+            quote.amount_in,
+            quote.amount_out,
+            mint_name(snapshot, &quote.source_mint),
+            mint_name(snapshot, &quote.target_mint),
+            quote.accepted_at.as_deref().unwrap_or(&quote.created_at),
+        );
+    }
+
+    out.push_str("\nRECENT ERRORS\n");
+    if snapshot.failures.is_empty() {
+        out.push_str("  none\n");
+    }
+    for quote in &snapshot.failures {
+        let _ = writeln!(
+            out,
+            "  {}  {}  {}",
+            truncate(&quote.id, 12),
+            quote.completed_at.as_deref().unwrap_or(&quote.created_at),
+            truncate(
+                quote.error_message.as_deref().unwrap_or("unknown error"),
+                70
+            ),
+        );
+    }
+    out
+}
+
+/// Name of a mint the broker lists, or its URL
+fn mint_name<'a>(snapshot: &'a Snapshot, mint_url: &'a str) -> &'a str {
+    snapshot
+        .liquidity
+        .mints
+        .iter()
+        .find(|mint| mint.mint_url == mint_url)
+        .map_or(mint_url, |mint| mint.name.as_str())
+}
+
+fn circuit(state: CircuitState) -> &'static str {
+    match state {
+        CircuitState::Closed => "closed",
+        CircuitState::Open => "OPEN",
+        CircuitState::HalfOpen => "half-open",
+    }
+}
+
+/// At most `max` characters of `text`, marking a cut with `~`
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    cut.push('~');
+    cut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::MintLiquidity;
+    use crate::circuit_breaker::CircuitStatus;
+    use crate::types::{ClaimOrder, SwapStatus};
+
+    fn quote(id: &str, status: SwapStatus, error_message: Option<&str>) -> QuoteRecord {
+        QuoteRecord {
+            id: id.to_string(),
+            source_mint: "http://mint-a.test".to_string(),
+            target_mint: "http://mint-b.test".to_string(),
+            amount_in: 100,
+            amount_out: 99,
+            fee: 1,
+            fee_rate: 0.01,
+            mint_fee: 0,
+            exchange_rate: None,
+            broker_pubkey: "02abcd1234".to_string(),
+            adaptor_point: "03efgh5678".to_string(),
+            tweaked_pubkey: "02ijkl9012".to_string(),
+            status,
+            created_at: "2025-01-17T00:00:00+00:00".to_string(),
+            expires_at: "2025-01-17T00:05:00+00:00".to_string(),
+            accepted_at: Some("2025-01-17T00:01:00+00:00".to_string()),
+            completed_at: None,
+            user_pubkey: None,
+            error_message: error_message.map(str::to_string),
+            callback_url: None,
+            job_status: None,
+            claim_order: ClaimOrder::BrokerFirst,
+            extended_at: None,
+        }
+    }
+
+    fn mint(name: &str, mint_url: &str, healthy: bool) -> MintLiquidity {
+        MintLiquidity {
+            mint_url: mint_url.to_string(),
+            name: name.to_string(),
+            balance: 1000,
+            reserved: 100,
+            locked: 50,
+            available: 850,
+            quarantined: 0,
+            unit: "sat".to_string(),
+            healthy,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let snapshot = Snapshot {
+            taken_at: Utc::now(),
+            liquidity: LiquidityResponse {
+                mints: vec![
+                    mint("Mint A", "http://mint-a.test", true),
+                    mint("Mint B", "http://mint-b.test", false),
+                ],
+                total_balance: 2000,
+                total_available: 1700,
+                total_locked: 100,
+            },
+            health: vec![MintHealthInfo {
+                mint_url: "http://mint-b.test".to_string(),
+                name: "Mint B".to_string(),
+                available: false,
+                healthy: false,
+                consecutive_failures: 3,
+                last_checked: None,
+                last_error: Some("connection refused".to_string()),
+                circuit: CircuitStatus {
+                    state: CircuitState::Open,
+                    consecutive_failures: 3,
+                    retry_in_seconds: Some(20),
+                },
+                latency_ms: Some(42),
+            }],
+            in_flight: vec![quote("quote-in-flight", SwapStatus::Accepted, None)],
+            in_flight_total: 4,
+            failures: vec![quote(
+                "quote-failed",
+                SwapStatus::Failed,
+                Some("target mint timed out"),
+            )],
+        };
+
+        let screen = render(&snapshot, "http://localhost:3000");
+        assert!(screen.contains("balance 2000  available 1700  locked 100"));
+        assert!(screen.contains("DOWN"));
+        assert!(screen.contains("OPEN"));
+        assert!(screen.contains("42ms"));
+        assert!(screen.contains("last error: connection refused"));
+        assert!(screen.contains("IN FLIGHT (4)"));
+        assert!(screen.contains("Mint A -> Mint B"));
+        assert!(screen.contains("target mint timed out"));
+        assert!(!screen.contains("none"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Mint A", 20), "Mint A");
+        assert_eq!(truncate("abcdef", 4), "abc~");
+    }
+}
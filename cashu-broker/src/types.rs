@@ -1,9 +1,60 @@
 //! Type definitions for Cashu broker
 
+use crate::fees::FeeSchedule;
+use crate::risk::RiskLimits;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
 use std::time::SystemTime;
 
+/// A value that must never show up in logs
+///
+/// Serializes as the value itself, but its `Debug` output is `<redacted>`, so
+/// structs holding proofs, tokens or keys can still derive or implement
+/// `Debug`. Wrap a borrowed field (`Sensitive(&self.proofs)`) to redact it in
+/// a hand-written `Debug` impl.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Sensitive<T>(pub T);
+
+impl<T> Sensitive<T> {
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Sensitive<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<T> for Sensitive<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> fmt::Debug for Sensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Separates a mint's URL from the unit in the key of its further wallets
+pub const UNIT_SEPARATOR: char = '#';
+
 /// Mint configuration that the broker supports
+///
+/// One entry per wallet: a mint issuing several units has one entry per unit.
+/// `mint_url` is the key the broker tracks the wallet under, the mint's URL
+/// for its first unit and `<url>#<unit>` (see [`mint_key`]) for the others.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MintConfig {
     pub mint_url: String,
@@ -11,28 +62,145 @@ pub struct MintConfig {
     pub unit: String, // 'sat', 'usd', etc.
 }
 
+impl MintConfig {
+    /// URL of the mint itself, without the unit of a further wallet
+    pub fn url(&self) -> &str {
+        mint_url_of(&self.mint_url)
+    }
+}
+
+/// Key of the broker's wallet in `unit` on a mint whose first unit is another
+pub fn mint_key(mint_url: &str, unit: &str) -> String {
+    format!("{}{}{}", mint_url, UNIT_SEPARATOR, unit.to_lowercase())
+}
+
+/// URL of the mint a wallet key belongs to
+pub fn mint_url_of(key: &str) -> &str {
+    key.split_once(UNIT_SEPARATOR).map_or(key, |(url, _)| url)
+}
+
+/// Overrides for swaps from one mint to another
+///
+/// Unset fields fall back to the broker-wide settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairConfig {
+    pub source_mint: String,
+    pub target_mint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_rate: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_swap_amount: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_swap_amount: Option<u64>,
+}
+
+/// Fee and limits that apply to one direction of a mint pair
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairTerms {
+    pub fee_rate: f64,
+    pub min_swap_amount: u64,
+    pub max_swap_amount: u64,
+}
+
 /// Broker configuration
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct BrokerConfig {
     pub mints: Vec<MintConfig>,
+    pub pairs: Vec<PairConfig>,     // Per-pair overrides (directional)
     pub fee_rate: f64,              // Default 0.005 (0.5%)
     pub min_swap_amount: u64,       // Minimum swap in sats
     pub max_swap_amount: u64,       // Maximum swap in sats
     pub quote_expiry_seconds: u64,  // How long quotes are valid
+    pub quote_extension_seconds: u64, // How much longer a client may keep a quote, once (0 = off)
+    pub refund_locktime_seconds: u64, // Locktime on broker outputs before refund keys can spend
+    pub rebalance_threshold: u64,   // Rebalance a mint below this many sats (0 = off)
+    pub rebalance_target: u64,      // Balance to top a mint up to (0 = 2 * threshold)
+    pub consolidation_threshold: usize, // Consolidate a mint's proofs above this many (0 = off)
+    pub price_spread: f64,          // Taken off the exchange rate on cross-unit swaps (0.01 = 1%)
+    pub risk: RiskLimits,           // Per-client and overall limits on open quotes
+    pub fees: FeeSchedule,          // Fee tiers, minimum fee and volume discounts
 }
 
 impl Default for BrokerConfig {
     fn default() -> Self {
         Self {
             mints: Vec::new(),
+            pairs: Vec::new(),
             fee_rate: 0.005,
             min_swap_amount: 1,
             max_swap_amount: 10_000,
             quote_expiry_seconds: 300,
+            quote_extension_seconds: 0,
+            refund_locktime_seconds: 3600,
+            rebalance_threshold: 0,
+            rebalance_target: 0,
+            consolidation_threshold: 100,
+            price_spread: 0.01,
+            risk: RiskLimits::default(),
+            fees: FeeSchedule::default(),
         }
     }
 }
 
+impl BrokerConfig {
+    /// Unit of a configured mint, lowercased
+    pub fn unit_of(&self, mint_url: &str) -> Option<String> {
+        self.mints
+            .iter()
+            .find(|m| m.mint_url == mint_url)
+            .map(|m| m.unit.to_lowercase())
+    }
+
+    /// Fee and limits for swaps from `source_mint` to `target_mint`
+    pub fn pair_terms(&self, source_mint: &str, target_mint: &str) -> PairTerms {
+        let pair = self
+            .pairs
+            .iter()
+            .find(|p| p.source_mint == source_mint && p.target_mint == target_mint);
+
+        PairTerms {
+            fee_rate: pair.and_then(|p| p.fee_rate).unwrap_or(self.fee_rate),
+            min_swap_amount: pair
+                .and_then(|p| p.min_swap_amount)
+                .unwrap_or(self.min_swap_amount),
+            max_swap_amount: pair
+                .and_then(|p| p.max_swap_amount)
+                .unwrap_or(self.max_swap_amount),
+        }
+    }
+
+    /// Fee rate for swapping `amount` from `source_mint` to `target_mint`
+    ///
+    /// A pair's own fee rate wins over the fee tiers, and the tiers over
+    /// `fee_rate`. Volume discounts are not applied here.
+    pub fn fee_rate_for(&self, source_mint: &str, target_mint: &str, amount: u64) -> f64 {
+        self.pairs
+            .iter()
+            .find(|p| p.source_mint == source_mint && p.target_mint == target_mint)
+            .and_then(|p| p.fee_rate)
+            .or_else(|| self.fees.tier_rate(amount))
+            .unwrap_or(self.fee_rate)
+    }
+
+    /// Every direction between two configured mints that quotes can be asked for
+    ///
+    /// Mints of different units only pair up when `cross_unit` is set.
+    pub fn swap_pairs(&self, cross_unit: bool) -> Vec<(&MintConfig, &MintConfig)> {
+        let mut pairs = Vec::new();
+        for source in &self.mints {
+            for target in &self.mints {
+                if source.mint_url == target.mint_url
+                    || (!cross_unit && !source.unit.eq_ignore_ascii_case(&target.unit))
+                {
+                    continue;
+                }
+                pairs.push((source, target));
+            }
+        }
+        pairs
+    }
+}
+
 /// Swap request from a client (Bob)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SwapRequest {
@@ -42,9 +210,58 @@ pub struct SwapRequest {
     pub from_mint: String,       // Mint URL Bob has tokens on
     #[serde(alias = "target_mint")]
     pub to_mint: String,          // Mint URL Bob wants tokens on
-    pub amount: u64,              // Amount Bob wants to swap
+    pub amount: u64,              // Amount Bob pays (exact_in) or receives (exact_out)
+    #[serde(default)]
+    pub quote_type: QuoteType,
     #[serde(default, skip_serializing_if = "Option::is_none", alias = "user_pubkey")]
     pub client_public_key: Option<Vec<u8>>, // Bob's signing key (compressed, optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptor_point: Option<Vec<u8>>, // Bob's adaptor point, for a client-first swap (compressed)
+}
+
+/// Which side of the swap a requested amount refers to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteType {
+    /// Amount is what the client pays; the fee comes out of the output
+    #[default]
+    ExactIn,
+    /// Amount is what the client receives; the fee is added to the input
+    ExactOut,
+}
+
+/// Who claims their side of a swap first, revealing the adaptor secret
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimOrder {
+    /// The broker holds the adaptor secret and reveals it by claiming the
+    /// client's proofs with the client's encrypted signature
+    #[default]
+    BrokerFirst,
+    /// The client holds the adaptor secret and reveals it by redeeming the
+    /// broker's outputs with the broker's encrypted witness signatures
+    ClientFirst,
+}
+
+impl fmt::Display for ClaimOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClaimOrder::BrokerFirst => write!(f, "broker_first"),
+            ClaimOrder::ClientFirst => write!(f, "client_first"),
+        }
+    }
+}
+
+impl std::str::FromStr for ClaimOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "broker_first" => Ok(ClaimOrder::BrokerFirst),
+            "client_first" => Ok(ClaimOrder::ClientFirst),
+            _ => Err(format!("Invalid claim order: {}", s)),
+        }
+    }
 }
 
 /// Swap quote from the broker
@@ -56,24 +273,40 @@ pub struct SwapQuote {
     pub from_mint: String,
     #[serde(rename = "target_mint", alias = "to_mint")]
     pub to_mint: String,
+    #[serde(rename = "source_unit", alias = "from_unit", default)]
+    pub from_unit: String,        // Unit of amount_in, fee and mint_fee
+    #[serde(rename = "target_unit", alias = "to_unit", default)]
+    pub to_unit: String,          // Unit of amount_out
     #[serde(rename = "amount_in", alias = "input_amount")]
     pub input_amount: u64,        // What Bob pays
     #[serde(rename = "amount_out", alias = "output_amount")]
-    pub output_amount: u64,       // What Bob receives (after fee)
-    pub fee: u64,                 // Broker fee
+    pub output_amount: u64,       // What Bob receives (after fee, in the target mint's unit)
+    pub fee: u64,                 // Broker fee (in the source mint's unit)
     pub fee_rate: f64,            // Fee percentage
+    #[serde(default)]
+    pub mint_fee: u64,            // Expected mint input fees on both sides (in the source mint's unit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange_rate: Option<f64>, // Target units per source unit after spread (cross-unit swaps only)
     #[serde(rename = "broker_pubkey", alias = "broker_public_key", with = "hex_serde")]
     pub broker_public_key: Vec<u8>, // Broker's signing key (compressed)
     #[serde(with = "hex_serde")]
     pub adaptor_point: Vec<u8>,   // Adaptor point for atomic swap (compressed)
-    #[serde(skip_serializing_if = "Option::is_none", with = "hex_serde_opt")]
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex_serde_opt")]
     pub tweaked_pubkey: Option<Vec<u8>>,  // Tweaked pubkey P' = P + T (compressed, optional)
-    #[serde(skip_serializing)]
-    pub adaptor_secret: Vec<u8>,  // Adaptor secret (NOT shared with client in API)
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex_serde_opt")]
+    pub dleq_proof: Option<Vec<u8>>,  // Proof that P' - P == T (e || s, optional)
     #[serde(rename = "expires_in")]
     pub expires_in: u64,          // Seconds until expiry (for API)
+    #[serde(default)]
+    pub expiry: u64,              // Unix time the quote expires at
     #[serde(skip, default)]
     pub expires_at: Option<SystemTime>,   // Internal expiry time
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "hex_serde_opt")]
+    pub quote_signature: Option<Vec<u8>>, // Broker identity's signature over the quote terms (64 bytes)
+    #[serde(default)]
+    pub claim_order: ClaimOrder,
+    #[serde(default)]
+    pub extended: bool,           // Expiry already pushed out by the client (allowed once)
     pub status: SwapStatus,
 }
 
@@ -88,8 +321,8 @@ pub enum SwapStatus {
     Failed,
 }
 
-impl std::fmt::Display for SwapStatus {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl fmt::Display for SwapStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             SwapStatus::Pending => write!(f, "pending"),
             SwapStatus::Accepted => write!(f, "accepted"),
@@ -119,8 +352,8 @@ impl std::str::FromStr for SwapStatus {
 #[derive(Debug, Clone)]
 pub struct SwapExecution {
     pub quote_id: String,
-    pub client_tokens: Vec<u8>,     // Serialized client tokens
-    pub broker_tokens: Vec<u8>,    // Serialized broker's tokens
+    pub client_tokens: Sensitive<Vec<u8>>, // Serialized client tokens
+    pub broker_tokens: Sensitive<Vec<u8>>, // Serialized broker's tokens
     pub client_swap_complete: bool,
     pub broker_swap_complete: bool,
     pub completed_at: Option<SystemTime>,
@@ -197,3 +430,56 @@ mod system_time_serde {
         Ok(UNIX_EPOCH + std::time::Duration::from_secs(secs))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensitive_redacts_debug() {
+        let execution = SwapExecution {
+            quote_id: "quote-1".to_string(),
+            client_tokens: Sensitive::default(),
+            broker_tokens: br#"[{"secret":"s3cr3t"}]"#.to_vec().into(),
+            client_swap_complete: false,
+            broker_swap_complete: false,
+            completed_at: None,
+        };
+        assert_eq!(
+            format!("{:?}", execution),
+            "SwapExecution { quote_id: \"quote-1\", client_tokens: <redacted>, \
+             broker_tokens: <redacted>, client_swap_complete: false, \
+             broker_swap_complete: false, completed_at: None }"
+        );
+
+        // Serialized as the plain value
+        let value = Sensitive("s3cr3t".to_string());
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#""s3cr3t""#);
+        assert_eq!(
+            serde_json::from_str::<Sensitive<String>>(r#""s3cr3t""#).unwrap(),
+            value
+        );
+        assert_eq!(value.len(), 6);
+    }
+
+    #[test]
+    fn test_claim_order() {
+        assert_eq!(ClaimOrder::default(), ClaimOrder::BrokerFirst);
+        assert_eq!(
+            serde_json::to_string(&ClaimOrder::ClientFirst).unwrap(),
+            r#""client_first""#
+        );
+        for order in [ClaimOrder::BrokerFirst, ClaimOrder::ClientFirst] {
+            assert_eq!(order.to_string().parse::<ClaimOrder>(), Ok(order));
+        }
+        assert!("first".parse::<ClaimOrder>().is_err());
+    }
+
+    #[test]
+    fn test_mint_key() {
+        let key = mint_key("http://localhost:3338", "USD");
+        assert_eq!(key, "http://localhost:3338#usd");
+        assert_eq!(mint_url_of(&key), "http://localhost:3338");
+        assert_eq!(mint_url_of("http://localhost:3338"), "http://localhost:3338");
+    }
+}
@@ -0,0 +1,196 @@
+//! API versions
+//!
+//! Every API route is served under the prefix of each version the broker
+//! speaks, `/v1` for now, with the request's [`ApiVersion`] available to
+//! handlers as an extension. A breaking change (token strings in place of
+//! proof arrays, say) ships as a new version next to the old one, so a wallet
+//! keeps working until it moves on.
+//!
+//! The unversioned paths of the first releases stay as aliases of `/v1`.
+//! They are deprecated: their responses carry `Deprecation: true` and a
+//! `Link` to the `/v1` path. A client may send `X-API-Version` on them; a
+//! version they don't serve is refused and pointed at its prefix. The
+//! `/health` probes are not versioned.
+
+use crate::api::ApiError;
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::fmt;
+
+/// Header a client may ask for a version with, and that every versioned
+/// response names its version in
+pub const VERSION_HEADER: &str = "x-api-version";
+
+/// A version of the HTTP API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ApiVersion {
+    V1,
+}
+
+impl ApiVersion {
+    /// Newest version, the one [`crate::client::SwapClient`] speaks
+    pub const CURRENT: ApiVersion = ApiVersion::V1;
+
+    /// Version served on the unversioned paths
+    pub const LEGACY: ApiVersion = ApiVersion::V1;
+
+    /// Every version served, oldest first
+    pub const SUPPORTED: [ApiVersion; 1] = [ApiVersion::V1];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "1",
+        }
+    }
+
+    /// Path prefix the version's routes are served under
+    pub const fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/v1",
+        }
+    }
+
+    /// Parse a version as sent in `X-API-Version` (`1` or `v1`)
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let number = value.strip_prefix(['v', 'V']).unwrap_or(value);
+        Self::SUPPORTED
+            .into_iter()
+            .find(|version| version.as_str() == number)
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Middleware for the routes under `version`'s prefix
+pub async fn versioned(version: ApiVersion, mut req: Request, next: Next) -> Response {
+    req.extensions_mut().insert(version);
+    let mut response = next.run(req).await;
+    response
+        .headers_mut()
+        .insert(VERSION_HEADER, HeaderValue::from_static(version.as_str()));
+    response
+}
+
+/// Middleware for the deprecated unversioned paths
+pub async fn legacy(mut req: Request, next: Next) -> Response {
+    let requested = req
+        .headers()
+        .get(VERSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if let Some(requested) = requested {
+        match ApiVersion::parse(&requested) {
+            None => {
+                return ApiError::BadRequest(format!(
+                    "Unsupported API version {}; supported: {}",
+                    requested,
+                    supported()
+                ))
+                .into_response()
+            }
+            Some(version) if version != ApiVersion::LEGACY => {
+                return ApiError::BadRequest(format!(
+                    "API version {} is served under {}",
+                    version,
+                    version.prefix()
+                ))
+                .into_response()
+            }
+            Some(_) => {}
+        }
+    }
+
+    let successor = format!(
+        "<{}{}>; rel=\"successor-version\"",
+        ApiVersion::LEGACY.prefix(),
+        req.uri().path()
+    );
+    req.extensions_mut().insert(ApiVersion::LEGACY);
+
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        VERSION_HEADER,
+        HeaderValue::from_static(ApiVersion::LEGACY.as_str()),
+    );
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(value) = HeaderValue::from_str(&successor) {
+        headers.insert(header::LINK, value);
+    }
+    response
+}
+
+/// Supported versions, comma-separated
+fn supported() -> String {
+    ApiVersion::SUPPORTED
+        .iter()
+        .map(|version| version.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, middleware, routing::get, Extension, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ApiVersion::parse("1"), Some(ApiVersion::V1));
+        assert_eq!(ApiVersion::parse(" v1"), Some(ApiVersion::V1));
+        assert_eq!(ApiVersion::parse("2"), None);
+        assert_eq!(ApiVersion::parse(""), None);
+        assert_eq!(ApiVersion::CURRENT.prefix(), "/v1");
+    }
+
+    #[tokio::test]
+    async fn test_legacy_paths() {
+        let routes = Router::new().route(
+            "/info",
+            get(|Extension(version): Extension<ApiVersion>| async move { version.to_string() }),
+        );
+        let app = Router::new()
+            .nest(
+                "/v1",
+                routes
+                    .clone()
+                    .route_layer(middleware::from_fn(|req: Request, next: Next| {
+                        versioned(ApiVersion::V1, req, next)
+                    })),
+            )
+            .merge(routes.route_layer(middleware::from_fn(legacy)));
+        let get = |uri: &str, version: Option<&str>| {
+            let mut req = Request::builder().uri(uri);
+            if let Some(version) = version {
+                req = req.header(VERSION_HEADER, version);
+            }
+            app.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+
+        let response = get("/v1/info", None).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[VERSION_HEADER], "1");
+        assert!(response.headers().get("deprecation").is_none());
+
+        let response = get("/info", Some("1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["deprecation"], "true");
+        assert_eq!(
+            response.headers()[header::LINK],
+            "</v1/info>; rel=\"successor-version\""
+        );
+
+        let response = get("/info", Some("7")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
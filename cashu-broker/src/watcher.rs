@@ -0,0 +1,125 @@
+//! Watching target mints for clients redeeming client-first outputs
+//!
+//! A client-first swap completes when the broker sees the witness its client
+//! redeemed the outputs with (see [`crate::client_first`]). Rather than wait
+//! for the next poll, the broker subscribes (NUT-17) to the state of the
+//! outputs of every swap waiting on its client, and claims as soon as the
+//! mint reports one spent with a witness that reveals the adaptor secret. The
+//! CDK wallet polls mints without NUT-17 in its place. Between watches the
+//! broker still checks every waiting swap over NUT-07, which also picks up
+//! swaps accepted since the watch started and outputs the broker refunded.
+
+use cdk::nuts::{Proof, Proofs, PublicKey};
+use std::collections::HashMap;
+
+/// Outputs of waiting client-first swaps, by target mint and Y
+#[derive(Debug, Clone, Default)]
+pub struct OutputWatch {
+    mints: HashMap<String, HashMap<PublicKey, (String, Proof)>>,
+}
+
+impl OutputWatch {
+    /// Watch the outputs of `awaiting` swaps, given as (quote ID, target
+    /// mint, locked outputs)
+    ///
+    /// Outputs whose Y can't be computed are left out; the poll still covers
+    /// them.
+    pub fn new(awaiting: Vec<(String, String, Proofs)>) -> Self {
+        let mut mints: HashMap<String, HashMap<PublicKey, (String, Proof)>> = HashMap::new();
+        for (quote_id, mint_url, outputs) in awaiting {
+            let watched = mints.entry(mint_url).or_default();
+            for proof in outputs {
+                if let Ok(y) = proof.y() {
+                    watched.insert(y, (quote_id.clone(), proof));
+                }
+            }
+        }
+        Self { mints }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mints.values().all(HashMap::is_empty)
+    }
+
+    /// Target mints with outputs to watch
+    pub fn mints(&self) -> impl Iterator<Item = &str> {
+        self.mints
+            .iter()
+            .filter(|(_, outputs)| !outputs.is_empty())
+            .map(|(mint_url, _)| mint_url.as_str())
+    }
+
+    /// Ys of the outputs watched on `mint_url`
+    pub fn ys(&self, mint_url: &str) -> Vec<PublicKey> {
+        self.mints
+            .get(mint_url)
+            .map(|outputs| outputs.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// The quote and output a spend reported by `mint_url` is for
+    pub fn output(&self, mint_url: &str, y: &PublicKey) -> Option<(&str, &Proof)> {
+        self.mints
+            .get(mint_url)?
+            .get(y)
+            .map(|(quote_id, proof)| (quote_id.as_str(), proof))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdk::nuts::{Id, SecretKey};
+    use cdk::secret::Secret;
+    use cdk::Amount;
+    use std::str::FromStr;
+
+    fn output() -> Proof {
+        Proof::new(
+            Amount::from(8),
+            Id::from_str("009a1f293253e41e").unwrap(),
+            Secret::generate(),
+            SecretKey::generate().public_key(),
+        )
+    }
+
+    #[test]
+    fn test_output_watch() {
+        let (a, b, c) = (output(), output(), output());
+        let watch = OutputWatch::new(vec![
+            (
+                "q1".to_string(),
+                "http://mint-b.test".to_string(),
+                vec![a.clone(), b],
+            ),
+            (
+                "q2".to_string(),
+                "http://mint-c.test".to_string(),
+                vec![c.clone()],
+            ),
+            (
+                "q3".to_string(),
+                "http://mint-d.test".to_string(),
+                Vec::new(),
+            ),
+        ]);
+        assert!(!watch.is_empty());
+
+        let mut mints: Vec<&str> = watch.mints().collect();
+        mints.sort();
+        assert_eq!(mints, ["http://mint-b.test", "http://mint-c.test"]);
+        assert_eq!(watch.ys("http://mint-b.test").len(), 2);
+        assert!(watch.ys("http://mint-d.test").is_empty());
+
+        let y = a.y().unwrap();
+        assert_eq!(watch.output("http://mint-b.test", &y), Some(("q1", &a)));
+        // Spends are matched on the mint they were reported by
+        assert_eq!(watch.output("http://mint-c.test", &y), None);
+        assert_eq!(
+            watch.output("http://mint-c.test", &c.y().unwrap()),
+            Some(("q2", &c))
+        );
+
+        assert!(OutputWatch::new(Vec::new()).is_empty());
+    }
+}
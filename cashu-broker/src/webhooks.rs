@@ -0,0 +1,525 @@
+//! Webhook callbacks on quote status changes
+//!
+//! A quote requested with a `callback_url` gets a POST whenever it is
+//! accepted, completed, failed or expired. Deliveries are queued in the
+//! `webhook_deliveries` table by the database layer and sent by the
+//! [`WebhookDispatcher`], which retries failures with exponential backoff.
+//!
+//! Each body is signed with HMAC-SHA256 under the broker's webhook secret; the
+//! hex digest is sent as `X-Broker-Signature: sha256=<hex>`.
+//!
+//! Anyone can request a quote, so callbacks only go to public addresses:
+//! hosts are resolved by [`PublicResolver`], which drops loopback, private,
+//! link-local and other non-global addresses, and the connection is made to
+//! the addresses it returns, so a host can't resolve differently in between.
+//! Hosts the operator allows (`WEBHOOK_ALLOWED_HOSTS`) are exempt.
+
+use crate::db::{Database, QuoteRecord, WebhookDelivery};
+use crate::error::{BrokerError, Result};
+use crate::leases::{self, Leases, WEBHOOK_SWEEP};
+use crate::types::SwapStatus;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Header carrying the body signature
+pub const SIGNATURE_HEADER: &str = "x-broker-signature";
+
+/// Header carrying the event name
+pub const EVENT_HEADER: &str = "x-broker-event";
+
+/// Header carrying the delivery ID, stable across retries
+pub const DELIVERY_HEADER: &str = "x-broker-delivery";
+
+/// Attempts before a delivery is given up
+const MAX_ATTEMPTS: i64 = 8;
+
+/// Delay before the first retry, doubled on each further attempt
+const BASE_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Longest delay between attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// How often due deliveries are checked
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Deliveries sent per poll
+const BATCH_SIZE: i64 = 50;
+
+/// Timeout for a single callback
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether a move to `status` is announced to the callback URL
+pub fn notifies(status: SwapStatus) -> bool {
+    matches!(
+        status,
+        SwapStatus::Accepted | SwapStatus::Completed | SwapStatus::Failed | SwapStatus::Expired
+    )
+}
+
+/// Reject callback URLs that aren't plain http(s), or that name a
+/// non-global IP address
+///
+/// Host names are only checked when a delivery resolves them.
+pub fn validate_callback_url(url: &str) -> Result<()> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| BrokerError::InvalidSwapRequest(format!("Invalid callback_url: {}", e)))?;
+
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(BrokerError::InvalidSwapRequest(format!(
+            "callback_url must be an http(s) URL: {}",
+            url
+        )));
+    }
+    if host_ip(&parsed).is_some_and(|ip| !is_global(ip)) {
+        return Err(BrokerError::InvalidSwapRequest(format!(
+            "callback_url must be a public address: {}",
+            url
+        )));
+    }
+
+    Ok(())
+}
+
+/// IP address a URL names directly, if its host is one
+fn host_ip(url: &reqwest::Url) -> Option<IpAddr> {
+    let host = url.host_str()?;
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Whether `ip` is reachable on the public internet
+///
+/// Loopback, private (RFC 1918 and unique local), link-local (including
+/// cloud metadata endpoints), shared, documentation, benchmarking, reserved,
+/// multicast and unspecified addresses are not.
+pub fn is_global(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_global_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_global_v4(ip),
+            None => is_global_v6(ip),
+        },
+    }
+}
+
+fn is_global_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)) // Shared address space (RFC 6598)
+        || (a == 192 && b == 0 && c == 0) // IETF protocol assignments
+        || (a == 198 && (18..20).contains(&b)) // Benchmarking (RFC 2544)
+        || a >= 240)
+}
+
+fn is_global_v6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || (segments[0] & 0xfe00) == 0xfc00 // Unique local
+        || (segments[0] & 0xffc0) == 0xfe80 // Link-local
+        || (segments[0] & 0xffc0) == 0xfec0 // Site-local (deprecated)
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8) // Documentation
+        || (segments[0] == 0x64 && segments[1] == 0xff9b)) // NAT64 of any IPv4 address
+}
+
+/// Resolves callback hosts to their global addresses only
+///
+/// A host with none fails to resolve, so the request is never sent. Hosts
+/// in the allowlist resolve to all their addresses.
+#[derive(Debug, Clone, Default)]
+pub struct PublicResolver {
+    allowed_hosts: Arc<Vec<String>>,
+}
+
+impl PublicResolver {
+    pub fn new(allowed_hosts: Vec<String>) -> Self {
+        Self {
+            allowed_hosts: Arc::new(
+                allowed_hosts
+                    .into_iter()
+                    .map(|host| host.to_lowercase())
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Whether `host` may be called whatever it resolves to
+    pub fn allows(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        self.allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+
+    /// Addresses of `host` a callback may connect to
+    pub async fn resolve_host(&self, host: &str) -> std::io::Result<Vec<SocketAddr>> {
+        let addrs = tokio::net::lookup_host((host, 0)).await?;
+        if self.allows(host) {
+            return Ok(addrs.collect());
+        }
+
+        let addrs: Vec<SocketAddr> = addrs.filter(|addr| is_global(addr.ip())).collect();
+        if addrs.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("{} has no public address", host),
+            ));
+        }
+        Ok(addrs)
+    }
+}
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let addrs = resolver.resolve_host(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Body of a webhook call
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    event: String,
+    quote_id: &'a str,
+    status: String,
+    source_mint: &'a str,
+    target_mint: &'a str,
+    amount_in: i64,
+    amount_out: i64,
+    fee: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<&'a str>,
+    timestamp: String,
+}
+
+/// Delivery announcing that `quote` moved to `status`, if it has a callback URL
+pub fn delivery_for(quote: &QuoteRecord, status: SwapStatus) -> Result<Option<WebhookDelivery>> {
+    let Some(url) = &quote.callback_url else {
+        return Ok(None);
+    };
+
+    let now = Utc::now().to_rfc3339();
+    let payload = WebhookPayload {
+        event: format!("quote.{}", status),
+        quote_id: &quote.id,
+        status: status.to_string(),
+        source_mint: &quote.source_mint,
+        target_mint: &quote.target_mint,
+        amount_in: quote.amount_in,
+        amount_out: quote.amount_out,
+        fee: quote.fee,
+        error_message: quote.error_message.as_deref(),
+        timestamp: now.clone(),
+    };
+
+    Ok(Some(WebhookDelivery {
+        id: uuid::Uuid::new_v4().to_string(),
+        quote_id: quote.id.clone(),
+        event: payload.event.clone(),
+        url: url.clone(),
+        payload: serde_json::to_string(&payload)?,
+        status: "pending".to_string(),
+        attempts: 0,
+        next_attempt_at: now.clone(),
+        last_error: None,
+        created_at: now,
+        delivered_at: None,
+    }))
+}
+
+/// Hex HMAC-SHA256 of `body` under `secret`
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Delay before the next attempt after `attempts` failures
+fn backoff(attempts: i64) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    BASE_BACKOFF
+        .saturating_mul(2u32.pow(exponent))
+        .min(MAX_BACKOFF)
+}
+
+/// Sends queued webhook deliveries
+pub struct WebhookDispatcher {
+    db: Database,
+    client: reqwest::Client,
+    resolver: PublicResolver,
+    secret: Vec<u8>,
+    leases: Option<Leases>,
+}
+
+impl WebhookDispatcher {
+    pub fn new(db: Database, secret: &str) -> Result<Self> {
+        Self::with_allowed_hosts(db, secret, Vec::new())
+    }
+
+    /// Dispatcher that may also call `allowed_hosts` on private addresses,
+    /// e.g. an internal service the operator runs
+    pub fn with_allowed_hosts(
+        db: Database,
+        secret: &str,
+        allowed_hosts: Vec<String>,
+    ) -> Result<Self> {
+        let resolver = PublicResolver::new(allowed_hosts);
+        // No proxy: it would resolve the host instead of the resolver
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy()
+            .dns_resolver(Arc::new(resolver.clone()))
+            .build()
+            .map_err(|e| BrokerError::Webhook(e.to_string()))?;
+
+        Ok(Self {
+            db,
+            client,
+            resolver,
+            secret: secret.as_bytes().to_vec(),
+            leases: None,
+        })
+    }
+
+    /// Only send while holding the webhook lease, so deliveries queued in
+    /// a shared database are sent by one instance
+    pub fn with_leases(mut self, leases: Leases) -> Self {
+        self.leases = Some(leases);
+        self
+    }
+
+    /// Send due deliveries forever
+    pub async fn run(&self) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            if !leases::may_run(self.leases.as_ref(), WEBHOOK_SWEEP).await {
+                continue;
+            }
+            if let Err(e) = self.dispatch_due().await {
+                warn!("Webhook dispatch failed: {}", e);
+            }
+        }
+    }
+
+    /// Attempt every due delivery once, returning how many were delivered
+    pub async fn dispatch_due(&self) -> Result<usize> {
+        let mut delivered = 0;
+
+        for delivery in self.db.due_webhook_deliveries(BATCH_SIZE).await? {
+            let attempts = delivery.attempts + 1;
+
+            match self.send(&delivery).await {
+                Ok(()) => {
+                    debug!(
+                        "Delivered {} for quote {}",
+                        delivery.event, delivery.quote_id
+                    );
+                    self.db
+                        .mark_webhook_delivered(&delivery.id, attempts)
+                        .await?;
+                    delivered += 1;
+                }
+                Err(e) => {
+                    let retry_at = (attempts < MAX_ATTEMPTS).then(|| {
+                        let delay = chrono::Duration::from_std(backoff(attempts))
+                            .unwrap_or_else(|_| chrono::Duration::hours(1));
+                        (Utc::now() + delay).to_rfc3339()
+                    });
+
+                    if retry_at.is_none() {
+                        warn!(
+                            "Giving up on {} for quote {} after {} attempts: {}",
+                            delivery.event, delivery.quote_id, attempts, e
+                        );
+                    }
+
+                    self.db
+                        .record_webhook_failure(
+                            &delivery.id,
+                            attempts,
+                            retry_at.as_deref(),
+                            &e.to_string(),
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    async fn send(&self, delivery: &WebhookDelivery) -> Result<()> {
+        // Hosts given as an IP address skip the resolver
+        let url =
+            reqwest::Url::parse(&delivery.url).map_err(|e| BrokerError::Webhook(e.to_string()))?;
+        if let Some(ip) = host_ip(&url) {
+            let allowed = url
+                .host_str()
+                .is_some_and(|host| self.resolver.allows(host));
+            if !is_global(ip) && !allowed {
+                return Err(BrokerError::Webhook(format!(
+                    "{} is not a public address",
+                    ip
+                )));
+            }
+        }
+
+        let signature = sign(&self.secret, delivery.payload.as_bytes());
+
+        self.client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, &delivery.id)
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| BrokerError::Webhook(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), Duration::from_secs(10));
+        assert_eq!(backoff(2), Duration::from_secs(20));
+        assert_eq!(backoff(4), Duration::from_secs(80));
+        assert_eq!(backoff(20), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_validate_callback_url() {
+        assert!(validate_callback_url("https://shop.example.com/hooks/broker").is_ok());
+        assert!(validate_callback_url("http://localhost:8080/cb").is_ok());
+        assert!(validate_callback_url("ftp://example.com").is_err());
+        assert!(validate_callback_url("not a url").is_err());
+        assert!(validate_callback_url("http://127.0.0.1:8080/cb").is_err());
+        assert!(validate_callback_url("http://169.254.169.254/latest/meta-data").is_err());
+        assert!(validate_callback_url("http://[::ffff:10.0.0.1]/cb").is_err());
+        assert!(validate_callback_url("https://93.184.216.34/cb").is_ok());
+    }
+
+    #[test]
+    fn test_is_global() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:192.168.1.1",
+            "64:ff9b::7f00:1",
+        ] {
+            assert!(!is_global(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["93.184.216.34", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_global(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolver_refuses_private_hosts() {
+        let resolver = PublicResolver::new(vec!["LOCALHOST".to_string()]);
+        let addrs = resolver.resolve_host("localhost").await.unwrap();
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+
+        let err = PublicResolver::default()
+            .resolve_host("localhost")
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+
+    #[tokio::test]
+    async fn test_private_callbacks_are_not_sent() {
+        use axum::routing::post;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.migrate().await.unwrap();
+        let received = Arc::new(AtomicUsize::new(0));
+        let counter = received.clone();
+        let router = axum::Router::new().route(
+            "/cb",
+            post(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async {}
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let delivery = |url: String| WebhookDelivery {
+            id: uuid::Uuid::new_v4().to_string(),
+            quote_id: "quote-1".to_string(),
+            event: "quote.completed".to_string(),
+            url,
+            payload: "{}".to_string(),
+            status: "pending".to_string(),
+            attempts: 0,
+            next_attempt_at: Utc::now().to_rfc3339(),
+            last_error: None,
+            created_at: Utc::now().to_rfc3339(),
+            delivered_at: None,
+        };
+        let by_name = delivery(format!("http://localhost:{}/cb", port));
+        let by_ip = delivery(format!("http://127.0.0.1:{}/cb", port));
+
+        // Loopback is refused whether named or given as an address
+        let dispatcher = WebhookDispatcher::new(db.clone(), "secret").unwrap();
+        assert!(dispatcher.send(&by_name).await.is_err());
+        assert!(dispatcher.send(&by_ip).await.is_err());
+        assert_eq!(received.load(Ordering::SeqCst), 0);
+
+        // unless the operator allows the host
+        let hosts = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+        let dispatcher = WebhookDispatcher::with_allowed_hosts(db, "secret", hosts).unwrap();
+        dispatcher.send(&by_name).await.unwrap();
+        dispatcher.send(&by_ip).await.unwrap();
+        assert_eq!(received.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,30 +1,85 @@
-use cashu_broker::{api, AppState, Broker, Config, Database};
+//! Runs against in-memory SQLite, so it is skipped with the `postgres` feature
+#![cfg(not(feature = "postgres"))]
+
+use cashu_broker::accounting::EntryKind;
+use cashu_broker::chunked::ChunkedStatus;
+use cashu_broker::db::LedgerEntry;
+use cashu_broker::encryption::SecretCipher;
+use cashu_broker::jobs::JobStatus;
+use cashu_broker::limits::RequestLimits;
+use cashu_broker::rate_limit::RateLimitConfig;
+use cashu_broker::testkit::MockMint;
+use cashu_broker::types::SwapStatus;
+use cashu_broker::{api, AppState, Broker, Config, Database, QuoteType, SwapClient};
 use axum::{
     body::Body,
     http::{Request, StatusCode},
 };
+use cdk::amount::SplitTarget;
+use cdk::Amount;
 use serde_json::{json, Value};
 use std::sync::Arc;
 use tower::ServiceExt;
 
+const TEST_ADMIN_TOKEN: &str = "test-admin-token";
+
 /// Helper to setup test environment
 async fn setup_test_app() -> (axum::Router, Database) {
-    // Create in-memory database
+    setup_test_app_with(None, false).await
+}
+
+/// Helper to setup test environment with optional rate limiting and API keys
+async fn setup_test_app_with(
+    rate_limit: Option<RateLimitConfig>,
+    require_api_key: bool,
+) -> (axum::Router, Database) {
+    let (app, db, _) = setup_app(
+        ["http://mint-a.test", "http://mint-b.test"],
+        rate_limit,
+        require_api_key,
+    )
+    .await;
+    (app, db)
+}
+
+/// Helper to setup a broker against two in-process mock mints, funded with
+/// `liquidity` sats on each
+async fn setup_mock_mint_app(liquidity: u64) -> (axum::Router, Database, [MockMint; 2]) {
+    let mints = [
+        MockMint::start().await.expect("Failed to start mock mint"),
+        MockMint::start().await.expect("Failed to start mock mint"),
+    ];
+    let (app, db, broker) = setup_app([mints[0].url(), mints[1].url()], None, false).await;
+    broker
+        .initialize(liquidity)
+        .await
+        .expect("Failed to fund broker");
+
+    (app, db, mints)
+}
+
+async fn setup_app(
+    mint_urls: [&str; 2],
+    rate_limit: Option<RateLimitConfig>,
+    require_api_key: bool,
+) -> (axum::Router, Database, Arc<Broker>) {
+    // Create in-memory database, encrypting quote keys as in production
     let db = Database::new("sqlite::memory:")
         .await
-        .expect("Failed to create test database");
+        .expect("Failed to create test database")
+        .with_cipher(SecretCipher::new(&[42u8; 32]));
     db.migrate().await.expect("Failed to run migrations");
 
     // Create broker config
     let broker_config = cashu_broker::types::BrokerConfig {
         mints: vec![
             cashu_broker::types::MintConfig {
-                mint_url: "http://mint-a.test".to_string(),
+                mint_url: mint_urls[0].to_string(),
                 name: "Mint A".to_string(),
                 unit: "sat".to_string(),
             },
             cashu_broker::types::MintConfig {
-                mint_url: "http://mint-b.test".to_string(),
+                mint_url: mint_urls[1].to_string(),
                 name: "Mint B".to_string(),
                 unit: "sat".to_string(),
             },
@@ -33,20 +88,33 @@ async fn setup_test_app() -> (axum::Router, Database) {
         min_swap_amount: 1,
         max_swap_amount: 10000,
         quote_expiry_seconds: 300,
+        ..Default::default()
     };
 
-    let broker = Broker::new(broker_config)
-        .await
-        .expect("Failed to create broker");
+    let broker = Arc::new(
+        Broker::builder(broker_config)
+            .database(db.clone())
+            .build()
+            .await
+            .expect("Failed to create broker"),
+    );
 
     let state = AppState {
-        broker: Arc::new(broker),
+        broker: broker.clone(),
         db: db.clone(),
     };
 
-    let app = api::create_router(state, vec!["*".to_string()]);
+    let app = api::create_router(
+        state,
+        vec!["*".to_string()],
+        Some(TEST_ADMIN_TOKEN.to_string()),
+        rate_limit,
+        require_api_key,
+        true,
+        RequestLimits::default(),
+    );
 
-    (app, db)
+    (app, db, broker)
 }
 
 /// Helper to parse JSON response
@@ -72,6 +140,7 @@ async fn test_health_endpoint() {
         .unwrap();
 
     assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().contains_key("x-request-id"));
 
     let body = parse_json_response(response.into_body()).await;
     assert_eq!(body["status"], "ok");
@@ -80,43 +149,50 @@ async fn test_health_endpoint() {
 }
 
 #[tokio::test]
-async fn test_request_quote_success() {
+async fn test_health_probes() {
     let (app, _db) = setup_test_app().await;
 
-    let request_body = json!({
-        "source_mint": "http://mint-a.test",
-        "target_mint": "http://mint-b.test",
-        "amount": 100
-    });
-
     let response = app
+        .clone()
         .oneshot(
             Request::builder()
-                .uri("/quote")
-                .method("POST")
-                .header("content-type", "application/json")
-                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .uri("/health/live")
+                .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["status"], "ok");
 
-    // Note: This will fail without actual mints, but tests the API structure
-    // In real integration tests, you'd mock the broker or have test mints running
-    assert!(
-        response.status() == StatusCode::OK
-            || response.status() == StatusCode::INTERNAL_SERVER_ERROR
-    );
+    // Migrated, with both wallets loaded and mints not yet checked
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/health/ready")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["migrations"], "ok");
+    assert_eq!(body["mints"], 2);
+    assert_eq!(body["wallets_loaded"], 2);
+    assert_eq!(body["healthy_mints"], 2);
 }
 
 #[tokio::test]
-async fn test_request_quote_invalid_amount() {
-    let (app, _db) = setup_test_app().await;
+async fn test_request_quote_success() {
+    let (app, _db, mints) = setup_mock_mint_app(1000).await;
 
     let request_body = json!({
-        "source_mint": "http://mint-a.test",
-        "target_mint": "http://mint-b.test",
-        "amount": 0  // Below minimum
+        "source_mint": mints[0].url(),
+        "target_mint": mints[1].url(),
+        "amount": 100
     });
 
     let response = app
@@ -131,179 +207,2194 @@ async fn test_request_quote_invalid_amount() {
         .await
         .unwrap();
 
-    assert!(response.status().is_client_error() || response.status().is_server_error());
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["quote"]["amount_in"], 100);
+    assert_eq!(body["quote"]["amount_out"], 99);
+    assert_eq!(body["quote"]["status"], "pending");
 }
 
 #[tokio::test]
-async fn test_get_liquidity() {
-    let (app, _db) = setup_test_app().await;
+async fn test_swap_end_to_end() {
+    let (app, db, mints) = setup_mock_mint_app(1000).await;
 
-    let response = app
-        .oneshot(
-            Request::builder()
-                .uri("/liquidity")
-                .body(Body::empty())
-                .unwrap(),
-        )
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broker_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    ));
+
+    // The client holds 100 sats on mint A and wants them on mint B
+    let wallet_a = mints[0].wallet().await.unwrap();
+    let wallet_b = mints[1].wallet().await.unwrap();
+    let funding = wallet_a.mint_quote(Amount::from(100), None).await.unwrap();
+    wallet_a
+        .mint(&funding.id, SplitTarget::default(), None)
         .await
         .unwrap();
 
-    assert_eq!(response.status(), StatusCode::OK);
+    let client = SwapClient::new(&broker_url).unwrap();
+    let info = client.info().await.unwrap();
+    let client = client.with_identity_key(hex::decode(&info.pubkey).unwrap());
 
-    let body = parse_json_response(response.into_body()).await;
-    assert!(body["mints"].is_array());
-    assert!(body["total_balance"].is_number());
+    let quoted = client
+        .request_quote(mints[0].url(), mints[1].url(), 100, QuoteType::ExactIn)
+        .await
+        .unwrap();
+    let locked_input = client.lock_input(&wallet_a, &quoted).await.unwrap();
+    let accepted = client.accept(&quoted, locked_input).await.unwrap();
+    let now = std::time::UNIX_EPOCH.elapsed().unwrap().as_secs();
+    assert!(accepted.refund_at.is_some_and(|at| at > now));
+    let completed = client.complete(&accepted).await.unwrap();
+
+    // A lost complete response can be recovered
+    let recovered = client.fetch_secret(&accepted).await.unwrap();
+    assert_eq!(
+        recovered.unlock_key.public_key(),
+        completed.unlock_key.public_key()
+    );
+
+    let received = client.redeem(&wallet_b, &completed).await.unwrap();
+    assert_eq!(received, quoted.quote.output_amount);
+    assert_eq!(u64::from(wallet_b.total_balance().await.unwrap()), received);
+    assert_eq!(u64::from(wallet_a.total_balance().await.unwrap()), 0);
+
+    let record = db.get_quote(&quoted.quote.quote_id).await.unwrap().unwrap();
+    assert_eq!(record.status, SwapStatus::Completed);
+    assert_eq!(record.job_status, Some(JobStatus::Done));
+
+    // The locked outputs can only be redeemed once
+    assert!(client.redeem(&wallet_b, &completed).await.is_err());
 }
 
 #[tokio::test]
-async fn test_get_metrics() {
-    let (app, _db) = setup_test_app().await;
+async fn test_chunked_swap() {
+    let (app, db, mints) = setup_mock_mint_app(1000).await;
 
-    let response = app
-        .oneshot(
-            Request::builder()
-                .uri("/metrics")
-                .body(Body::empty())
-                .unwrap(),
-        )
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broker_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    ));
+
+    let wallet_a = mints[0].wallet().await.unwrap();
+    let wallet_b = mints[1].wallet().await.unwrap();
+    let funding = wallet_a.mint_quote(Amount::from(100), None).await.unwrap();
+    wallet_a
+        .mint(&funding.id, SplitTarget::default(), None)
         .await
         .unwrap();
 
-    assert_eq!(response.status(), StatusCode::OK);
+    // 100 in chunks of at most 40: 34, 33 and 33
+    let client = SwapClient::new(&broker_url).unwrap();
+    let received = client
+        .swap_chunked(&wallet_a, &wallet_b, 100, Some(40))
+        .await
+        .unwrap();
+    assert_eq!(u64::from(wallet_b.total_balance().await.unwrap()), received);
+    assert_eq!(u64::from(wallet_a.total_balance().await.unwrap()), 0);
 
-    let body = parse_json_response(response.into_body()).await;
-    assert!(body["total_quotes"].is_number());
-    assert!(body["completed_swaps"].is_number());
-    assert!(body["failed_swaps"].is_number());
-    assert!(body["total_volume"].is_number());
-    assert!(body["total_fees"].is_number());
+    let quotes = db.list_quotes(&Default::default(), 10).await.unwrap();
+    assert_eq!(quotes.len(), 3);
+    let chunked_id = db.chunked_swap_of(&quotes[0].id).await.unwrap().unwrap();
+    let progress = client.chunked_progress(&chunked_id).await.unwrap();
+    assert_eq!(progress.status, ChunkedStatus::Completed);
+    assert_eq!(progress.chunks, vec![34, 33, 33]);
+    assert_eq!(progress.completed_chunks, 3);
+    assert_eq!((progress.amount_in, progress.amount_out), (100, received));
+    assert_eq!(progress.remaining, 0);
 }
 
 #[tokio::test]
-async fn test_list_quotes_empty() {
-    let (app, _db) = setup_test_app().await;
+async fn test_chunked_swap_stop() {
+    use cashu_broker::nip98::AuthEvent;
+    use cdk::nuts::SecretKey;
+    use schnorr_fun::fun::Scalar;
+
+    let (app, db, mints) = setup_mock_mint_app(1000).await;
+    let owner = SecretKey::generate();
+    let post = |path: String, body: Value, signer: Option<&SecretKey>| {
+        let body = serde_json::to_vec(&body).unwrap();
+        let mut request = Request::builder()
+            .uri(&path)
+            .method("POST")
+            .header("content-type", "application/json");
+        if let Some(key) = signer {
+            let secret = Scalar::from_bytes(key.to_secret_bytes())
+                .and_then(|s| s.non_zero())
+                .unwrap();
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            let url = format!("https://broker.test{}", path);
+            let event = AuthEvent::sign(secret, "POST", &url, &body, now);
+            request = request.header("authorization", event.to_header());
+        }
+        app.clone().oneshot(request.body(Body::from(body)).unwrap())
+    };
+
+    // Above the pair's maximum of 10000, but not by chunks above it
+    let request = json!({
+        "source_mint": mints[0].url(),
+        "target_mint": mints[1].url(),
+        "amount": 25_000,
+        "chunk_amount": 20_000,
+        "user_pubkey": owner.public_key().to_hex(),
+    });
+    let response = post("/chunked".to_string(), request.clone(), None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let mut request = request;
+    request["amount"] = json!(500);
+    request["chunk_amount"] = json!(200);
+    let response = post("/chunked".to_string(), request, None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_json_response(response.into_body()).await;
+    let id = body["chunked"]["id"].as_str().unwrap().to_string();
+    let first_quote = body["quote"]["id"].as_str().unwrap().to_string();
+    assert_eq!(body["chunked"]["chunks"], json!([167, 167, 166]));
+    assert_eq!(body["chunked"]["status"], "active");
+    assert_eq!(body["quote"]["amount_in"], 167);
 
+    // The chunk's quote reports the chunked swap's progress
     let response = app
+        .clone()
         .oneshot(
             Request::builder()
-                .uri("/quotes")
+                .uri(format!("/quote/{}", first_quote))
                 .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["chunked"]["id"], id.as_str());
+    assert_eq!(body["chunked"]["quotes"][0]["quote_id"], first_quote.as_str());
 
-    assert_eq!(response.status(), StatusCode::OK);
+    // Only the owner moves on, and not while a chunk is open
+    let next = format!("/chunked/{}/next", id);
+    let response = post(next.clone(), json!({}), None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = post(next.clone(), json!({}), Some(&owner)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
 
+    // Stopping expires the chunk not yet accepted, and takes no more
+    let response = post(format!("/chunked/{}/stop", id), json!({}), Some(&owner))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
     let body = parse_json_response(response.into_body()).await;
-    assert!(body.is_array());
-    assert_eq!(body.as_array().unwrap().len(), 0);
+    assert_eq!(body["status"], "stopped");
+    assert_eq!(body["remaining"], 500);
+    let quote = db.get_quote(&first_quote).await.unwrap().unwrap();
+    assert_eq!(quote.status, SwapStatus::Expired);
+
+    let response = post(next, json!({}), Some(&owner)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
 }
 
 #[tokio::test]
-async fn test_list_quotes_with_filter() {
-    let (app, _db) = setup_test_app().await;
+async fn test_extend_quote() {
+    let mints = [
+        MockMint::start().await.expect("Failed to start mock mint"),
+        MockMint::start().await.expect("Failed to start mock mint"),
+    ];
+    let (app, db, broker) = setup_app([mints[0].url(), mints[1].url()], None, false).await;
+    broker.initialize(1000).await.unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broker_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    ));
+
+    let client = SwapClient::new(&broker_url).unwrap();
+    let info = client.info().await.unwrap();
+    let client = client.with_identity_key(hex::decode(&info.pubkey).unwrap());
+    let quoted = client
+        .request_quote(mints[0].url(), mints[1].url(), 100, QuoteType::ExactIn)
+        .await
+        .unwrap();
+
+    // Off unless configured
+    assert!(matches!(
+        client.extend(&quoted).await,
+        Err(cashu_broker::BrokerError::Api { status: 400, .. })
+    ));
+
+    broker
+        .reload_config(cashu_broker::types::BrokerConfig {
+            quote_extension_seconds: 600,
+            ..broker.get_config()
+        })
+        .await;
+    assert_eq!(client.info().await.unwrap().quote_extension_seconds, 600);
+
+    // The extended quote is signed again, with its new expiry
+    let extended = client.extend(&quoted).await.unwrap();
+    assert_eq!(extended.quote.expiry, quoted.quote.expiry + 600);
+    assert!(extended.quote.extended);
+    let record = db.get_quote(&quoted.quote.quote_id).await.unwrap().unwrap();
+    assert!(record.extended_at.is_some());
+    let expires_at = chrono::DateTime::parse_from_rfc3339(&record.expires_at).unwrap();
+    assert!(expires_at.timestamp() as u64 > quoted.quote.expiry + 500);
+
+    // Once only
+    assert!(matches!(
+        client.extend(&extended).await,
+        Err(cashu_broker::BrokerError::Api { status: 409, .. })
+    ));
+
+    // It stays a quote like any other
+    let wallet_a = mints[0].wallet().await.unwrap();
+    let funding = wallet_a.mint_quote(Amount::from(100), None).await.unwrap();
+    wallet_a
+        .mint(&funding.id, SplitTarget::default(), None)
+        .await
+        .unwrap();
+    let locked_input = client.lock_input(&wallet_a, &extended).await.unwrap();
+    client.accept(&extended, locked_input).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_accept_requires_bound_proofs() {
+    use cashu_broker::nip98::AuthEvent;
+    use schnorr_fun::fun::Scalar;
+
+    let (app, _db, mints) = setup_mock_mint_app(1000).await;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broker_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(
+        listener,
+        app.clone().into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    ));
+
+    let wallet_a = mints[0].wallet().await.unwrap();
+    let funding = wallet_a.mint_quote(Amount::from(100), None).await.unwrap();
+    wallet_a
+        .mint(&funding.id, SplitTarget::default(), None)
+        .await
+        .unwrap();
+    let client = SwapClient::new(&broker_url).unwrap();
+    let quoted = client
+        .request_quote(mints[0].url(), mints[1].url(), 100, QuoteType::ExactIn)
+        .await
+        .unwrap();
+    let locked_input = client.lock_input(&wallet_a, &quoted).await.unwrap();
 
+    // Properly authorized, but the proofs carry no signature by the swap key
+    let path = format!("/quote/{}/accept", quoted.quote.quote_id);
+    let body = json!({ "source_proofs": serde_json::to_string(&locked_input).unwrap() })
+        .to_string();
+    let secret = Scalar::from_bytes(quoted.secret_key.to_secret_bytes())
+        .and_then(|s| s.non_zero())
+        .unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let event = AuthEvent::sign(
+        secret,
+        "POST",
+        &format!("{}{}", broker_url, path),
+        body.as_bytes(),
+        now,
+    );
     let response = app
         .oneshot(
             Request::builder()
-                .uri("/quotes?status=completed&limit=10")
-                .body(Body::empty())
+                .uri(&path)
+                .method("POST")
+                .header("content-type", "application/json")
+                .header("authorization", event.to_header())
+                .body(Body::from(body))
                 .unwrap(),
         )
         .await
         .unwrap();
-
-    assert_eq!(response.status(), StatusCode::OK);
-
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     let body = parse_json_response(response.into_body()).await;
-    assert!(body.is_array());
+    assert_eq!(body["code"], "PROOFS_NOT_BOUND");
+
+    // The client signs them, and the swap goes through
+    let accepted = client.accept(&quoted, locked_input).await.unwrap();
+    client.complete(&accepted).await.unwrap();
 }
 
 #[tokio::test]
-async fn test_get_nonexistent_quote() {
-    let (app, _db) = setup_test_app().await;
+async fn test_maintenance_mode_drains() {
+    let (app, db, mints) = setup_mock_mint_app(1000).await;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broker_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(
+        listener,
+        app.clone().into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    ));
+
+    let wallet_a = mints[0].wallet().await.unwrap();
+    let funding = wallet_a.mint_quote(Amount::from(100), None).await.unwrap();
+    wallet_a
+        .mint(&funding.id, SplitTarget::default(), None)
+        .await
+        .unwrap();
+
+    let client = SwapClient::new(&broker_url).unwrap();
+    let quoted = client
+        .request_quote(mints[0].url(), mints[1].url(), 100, QuoteType::ExactIn)
+        .await
+        .unwrap();
 
     let response = app
+        .clone()
         .oneshot(
             Request::builder()
-                .uri("/quote/nonexistent-id")
-                .body(Body::empty())
+                .uri("/admin/maintenance")
+                .method("PUT")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&json!({ "enabled": true })).unwrap()))
                 .unwrap(),
         )
         .await
         .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(db.maintenance_mode().await.unwrap());
 
-    assert_eq!(response.status(), StatusCode::NOT_FOUND);
-}
+    // New quotes are refused
+    let refused = client
+        .request_quote(mints[0].url(), mints[1].url(), 100, QuoteType::ExactIn)
+        .await;
+    assert!(matches!(
+        refused,
+        Err(cashu_broker::BrokerError::Api { status: 503, ref code, .. }) if code == "MAINTENANCE"
+    ));
+    assert!(client.info().await.unwrap().maintenance);
 
-#[tokio::test]
-async fn test_cors_headers() {
-    let (app, _db) = setup_test_app().await;
+    // The open quote still goes through
+    let locked_input = client.lock_input(&wallet_a, &quoted).await.unwrap();
+    let accepted = client.accept(&quoted, locked_input).await.unwrap();
+    client.complete(&accepted).await.unwrap();
+    let record = db.get_quote(&quoted.quote.quote_id).await.unwrap().unwrap();
+    assert_eq!(record.status, SwapStatus::Completed);
+
+    // A restarted broker comes back in maintenance mode
+    let restarted = Broker::builder(Default::default())
+        .database(db.clone())
+        .build()
+        .await
+        .unwrap();
+    assert!(restarted.in_maintenance());
 
     let response = app
         .oneshot(
             Request::builder()
-                .uri("/health")
-                .header("origin", "http://example.com")
+                .uri("/admin/maintenance")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
                 .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
-
-    // CORS should be configured
-    assert!(response.headers().contains_key("access-control-allow-origin"));
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["enabled"], true);
 }
 
 #[tokio::test]
-async fn test_request_quote_same_mint_error() {
-    let (app, _db) = setup_test_app().await;
+async fn test_blacklist() {
+    let (app, _db, mints) = setup_mock_mint_app(1000).await;
 
-    let request_body = json!({
-        "source_mint": "http://mint-a.test",
-        "target_mint": "http://mint-a.test",  // Same mint!
-        "amount": 100
-    });
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broker_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(
+        listener,
+        app.clone().into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    ));
+    let admin = |method: &str, uri: String, body: Option<Value>| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri(uri)
+                .method(method)
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .header("content-type", "application/json")
+                .body(body.map_or_else(Body::empty, |b| Body::from(b.to_string())))
+                .unwrap(),
+        )
+    };
+
+    // A banned client can't get a quote
+    let banned_key = cdk::nuts::SecretKey::generate().public_key().to_hex();
+    let response = admin(
+        "POST",
+        "/admin/blacklist".to_string(),
+        Some(json!({ "kind": "pubkey", "value": banned_key, "reason": "abuse" })),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
 
     let response = app
+        .clone()
         .oneshot(
             Request::builder()
                 .uri("/quote")
                 .method("POST")
                 .header("content-type", "application/json")
-                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .body(Body::from(
+                    json!({
+                        "source_mint": mints[0].url(),
+                        "target_mint": mints[1].url(),
+                        "amount": 100,
+                        "user_pubkey": banned_key,
+                    })
+                    .to_string(),
+                ))
                 .unwrap(),
         )
         .await
         .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["code"], "BLACKLISTED");
 
-    // Should return error for same-mint swap
-    assert!(response.status().is_client_error() || response.status().is_server_error());
+    // A quote can't be accepted with a banned proof
+    let wallet_a = mints[0].wallet().await.unwrap();
+    let funding = wallet_a.mint_quote(Amount::from(100), None).await.unwrap();
+    wallet_a
+        .mint(&funding.id, SplitTarget::default(), None)
+        .await
+        .unwrap();
+    let client = SwapClient::new(&broker_url).unwrap();
+    let quoted = client
+        .request_quote(mints[0].url(), mints[1].url(), 100, QuoteType::ExactIn)
+        .await
+        .unwrap();
+    let locked_input = client.lock_input(&wallet_a, &quoted).await.unwrap();
+    let stolen = locked_input[0].y().unwrap().to_hex();
+
+    let response = admin(
+        "POST",
+        "/admin/blacklist".to_string(),
+        Some(json!({ "kind": "proof", "value": stolen.to_uppercase() })),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let response = admin("GET", "/admin/blacklist".to_string(), None).await.unwrap();
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(body[0]["value"], stolen);
+
+    let refused = client.accept(&quoted, locked_input.clone()).await;
+    assert!(matches!(
+        refused,
+        Err(cashu_broker::BrokerError::Api { status: 403, ref code, .. }) if code == "BLACKLISTED"
+    ));
+
+    // Lifting the ban lets it through
+    let response = admin("DELETE", format!("/admin/blacklist/proof/{}", stolen), None)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = admin("DELETE", format!("/admin/blacklist/proof/{}", stolen), None)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let accepted = client.accept(&quoted, locked_input).await.unwrap();
+    client.complete(&accepted).await.unwrap();
 }
 
 #[tokio::test]
-async fn test_request_quote_unsupported_mint() {
-    let (app, _db) = setup_test_app().await;
+async fn test_bootstrap_initial_liquidity() {
+    use cashu_broker::bootstrap::{bootstrap, FundingMode, InitialLiquidity, Outcome};
 
-    let request_body = json!({
-        "source_mint": "http://unknown-mint.test",
-        "target_mint": "http://mint-b.test",
-        "amount": 100
-    });
+    let mints = [
+        MockMint::start().await.expect("Failed to start mock mint"),
+        MockMint::start().await.expect("Failed to start mock mint"),
+    ];
+    let (_app, _db, broker) = setup_app([mints[0].url(), mints[1].url()], None, false).await;
 
-    let response = app
-        .oneshot(
+    // Mint B is funded from a token someone minted there
+    let wallet = mints[1].wallet().await.unwrap();
+    let funding = wallet.mint_quote(Amount::from(500), None).await.unwrap();
+    let proofs = wallet
+        .mint(&funding.id, SplitTarget::default(), None)
+        .await
+        .unwrap();
+    let token = broker.encode_token(mints[1].url(), proofs, None).unwrap();
+    let token_file = std::env::temp_dir().join(format!("bootstrap-{}.token", uuid::Uuid::new_v4()));
+    std::fs::write(&token_file, token).unwrap();
+
+    let plan = [
+        InitialLiquidity {
+            mint_url: mints[0].url().to_string(),
+            amount: 1000,
+            funding: FundingMode::LightningInvoicePrint,
+            token_file: None,
+        },
+        InitialLiquidity {
+            mint_url: mints[1].url().to_string(),
+            amount: 500,
+            funding: FundingMode::EcashTokenFile,
+            token_file: Some(token_file.clone()),
+        },
+    ];
+
+    let outcomes = bootstrap(&broker, &plan).await;
+    std::fs::remove_file(&token_file).unwrap();
+    assert!(outcomes.contains(&(mints[0].url().to_string(), Outcome::Funded(1000))));
+    assert!(outcomes.contains(&(mints[1].url().to_string(), Outcome::Funded(500))));
+    assert_eq!(broker.get_liquidity_status().await.total_balance, 1500);
+
+    // Nothing left to fund on the next start
+    let outcomes = bootstrap(&broker, &plan).await;
+    assert!(outcomes.iter().all(|(_, outcome)| *outcome == Outcome::AlreadyFunded));
+}
+
+#[tokio::test]
+async fn test_risk_limits() {
+    use cashu_broker::risk::RiskLimits;
+
+    let mints = [
+        MockMint::start().await.expect("Failed to start mock mint"),
+        MockMint::start().await.expect("Failed to start mock mint"),
+    ];
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.migrate().await.unwrap();
+    let mint = |url: &str| cashu_broker::MintConfig {
+        mint_url: url.to_string(),
+        name: url.to_string(),
+        unit: "sat".to_string(),
+    };
+    let broker = Arc::new(
+        Broker::new(cashu_broker::BrokerConfig {
+            mints: vec![mint(mints[0].url()), mint(mints[1].url())],
+            fee_rate: 0.01,
+            risk: RiskLimits {
+                daily_volume_per_client: 250,
+                max_open_quotes_per_client: 2,
+                max_exposure: 300,
+            },
+            ..Default::default()
+        })
+        .await
+        .unwrap(),
+    );
+    broker.initialize(1000).await.unwrap();
+    let app = api::create_router(
+        AppState { broker, db },
+        vec!["*".to_string()],
+        None,
+        None,
+        false,
+        true,
+        RequestLimits::default(),
+    );
+
+    let quote = |user_pubkey: &str, amount: u64| {
+        let body = json!({
+            "source_mint": mints[0].url(),
+            "target_mint": mints[1].url(),
+            "amount": amount,
+            "user_pubkey": user_pubkey,
+        });
+        app.clone().oneshot(
             Request::builder()
                 .uri("/quote")
                 .method("POST")
                 .header("content-type", "application/json")
-                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
                 .unwrap(),
         )
+    };
+    let alice = format!("02{}", "aa".repeat(32));
+    let bob = format!("02{}", "bb".repeat(32));
+
+    for _ in 0..2 {
+        let response = quote(&alice, 100).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    // Within her volume, but with two quotes already open
+    let response = quote(&alice, 50).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["code"], "RISK_LIMIT_EXCEEDED");
+
+    // Over the daily volume on its own
+    let response = quote(&bob, 260).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // 198 sats are already owed, so the broker can't take on another 148
+    let response = quote(&bob, 150).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body = parse_json_response(response.into_body()).await;
+    assert!(body["error"].as_str().unwrap().contains("exposure"));
+
+    let response = quote(&bob, 50).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_inventory_pricing() {
+    use cashu_broker::pricing::InventorySkew;
+
+    let mints = [
+        MockMint::start().await.expect("Failed to start mock mint"),
+        MockMint::start().await.expect("Failed to start mock mint"),
+    ];
+    let mint = |url: &str| cashu_broker::MintConfig {
+        mint_url: url.to_string(),
+        name: url.to_string(),
+        unit: "sat".to_string(),
+    };
+    let broker = Broker::builder(cashu_broker::BrokerConfig {
+        mints: vec![mint(mints[0].url()), mint(mints[1].url())],
+        fee_rate: 0.01,
+        ..Default::default()
+    })
+    .pricing_strategy(Arc::new(InventorySkew::new(1.0)))
+    .build()
+    .await
+    .unwrap();
+    broker.initialize(1000).await.unwrap();
+    let request = |from: usize, to: usize, amount: u64| cashu_broker::SwapRequest {
+        client_id: None,
+        from_mint: mints[from].url().to_string(),
+        to_mint: mints[to].url().to_string(),
+        amount,
+        quote_type: QuoteType::ExactIn,
+        client_public_key: None,
+        adaptor_point: None,
+    };
+
+    // Balanced inventory: the configured rate
+    let first = broker.request_quote(request(0, 1, 500)).await.unwrap();
+    assert_eq!(first.fee_rate, 0.01);
+    assert_eq!(first.fee, 5);
+
+    // Mint B now holds a third of the unreserved sats, so swaps into it cost more
+    let into_b = broker.request_quote(request(0, 1, 100)).await.unwrap();
+    assert!(into_b.fee_rate > 0.013 && into_b.fee_rate < 0.014);
+    assert_eq!(into_b.fee, 2);
+
+    // while swaps that refill it stay at the configured rate
+    let into_a = broker.request_quote(request(1, 0, 100)).await.unwrap();
+    assert_eq!(into_a.fee_rate, 0.01);
+    assert_eq!(into_a.fee, 1);
+}
+
+#[tokio::test]
+async fn test_instances_share_database() {
+    use cashu_broker::leases::{mint_lease, Leases, QUOTE_SWEEP};
+    use cashu_broker::{BrokerError, ErrorCode};
+
+    let mints = [
+        MockMint::start().await.expect("Failed to start mock mint"),
+        MockMint::start().await.expect("Failed to start mock mint"),
+    ];
+    let db = Database::new("sqlite::memory:")
         .await
-        .unwrap();
+        .unwrap()
+        .with_cipher(SecretCipher::new(&[7u8; 32]));
+    db.migrate().await.unwrap();
+    let mint = |url: &str| cashu_broker::MintConfig {
+        mint_url: url.to_string(),
+        name: url.to_string(),
+        unit: "sat".to_string(),
+    };
+    let instance = |id: &str| {
+        Broker::builder(cashu_broker::BrokerConfig {
+            mints: vec![mint(mints[0].url()), mint(mints[1].url())],
+            fee_rate: 0.01,
+            ..Default::default()
+        })
+        .database(db.clone())
+        .leases(Leases::new(Arc::new(db.clone()), id))
+        .build()
+    };
+    let a = instance("a").await.unwrap();
+    let b = instance("b").await.unwrap();
+    a.initialize(1000).await.unwrap();
+    b.initialize(1000).await.unwrap();
+    let request = |from: usize, to: usize| cashu_broker::SwapRequest {
+        client_id: None,
+        from_mint: mints[from].url().to_string(),
+        to_mint: mints[to].url().to_string(),
+        amount: 100,
+        quote_type: QuoteType::ExactIn,
+        client_public_key: None,
+        adaptor_point: None,
+    };
 
-    // Should return error for unsupported mint
-    assert!(response.status().is_client_error() || response.status().is_server_error());
+    // The first instance to quote out of a mint keeps its liquidity
+    a.request_quote(request(0, 1)).await.unwrap();
+    let err = b.request_quote(request(0, 1)).await.unwrap_err();
+    assert!(matches!(&err, BrokerError::MintLeased { holder, .. } if holder == "a"));
+    assert_eq!(err.code(), ErrorCode::InsufficientLiquidity);
+    assert!(err.retryable());
+
+    // while the other quotes out of the mints that are free
+    b.request_quote(request(1, 0)).await.unwrap();
+    assert!(a.request_quote(request(1, 0)).await.is_err());
+    a.request_quote(request(0, 1)).await.unwrap();
+
+    // Expired quotes in the database are swept by one instance
+    a.sweep_expired_quotes().await.unwrap();
+    b.sweep_expired_quotes().await.unwrap();
+    let holders: Vec<(String, String)> = db
+        .list_leases()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|lease| (lease.name, lease.holder))
+        .collect();
+    let lease = |name: String, holder: &str| (name, holder.to_string());
+    let mut expected = vec![
+        lease(mint_lease(mints[0].url()), "b"),
+        lease(mint_lease(mints[1].url()), "a"),
+        lease(QUOTE_SWEEP.to_string(), "a"),
+    ];
+    expected.sort();
+    assert_eq!(holders, expected);
+
+    // Once another instance has a mint's lease, quotes out of it can't be
+    // accepted, and losing the lease drops them with their reservations
+    let quote = a.request_quote(request(0, 1)).await.unwrap();
+    let leased = mint_lease(mints[1].url());
+    Leases::new(Arc::new(db.clone()), "a").release(&leased).await.unwrap();
+    assert!(Leases::new(Arc::new(db.clone()), "b").hold(&leased).await.unwrap());
+    let err = a.accept_quote(&quote.quote_id, &[2u8; 33]).await.unwrap_err();
+    assert!(matches!(&err, BrokerError::MintLeased { holder, .. } if holder == "b"));
+
+    assert_eq!(a.mint_lease_lost(mints[1].url()).await, 3);
+    assert!(matches!(
+        a.accept_quote(&quote.quote_id, &[2u8; 33]).await,
+        Err(BrokerError::QuoteNotFound(_))
+    ));
+    let status = a.get_liquidity_status().await;
+    assert!(status.mints.iter().all(|mint| mint.reserved == 0));
+}
+
+#[tokio::test]
+async fn test_restore_skips_malformed_quotes() {
+    use cashu_broker::BrokerError;
+
+    let mints = [
+        MockMint::start().await.expect("Failed to start mock mint"),
+        MockMint::start().await.expect("Failed to start mock mint"),
+    ];
+    let db = Database::new("sqlite::memory:")
+        .await
+        .unwrap()
+        .with_cipher(SecretCipher::new(&[7u8; 32]));
+    db.migrate().await.unwrap();
+    let mint = |url: &str| cashu_broker::MintConfig {
+        mint_url: url.to_string(),
+        name: url.to_string(),
+        unit: "sat".to_string(),
+    };
+    let broker = || {
+        Broker::builder(cashu_broker::BrokerConfig {
+            mints: vec![mint(mints[0].url()), mint(mints[1].url())],
+            fee_rate: 0.01,
+            ..Default::default()
+        })
+        .database(db.clone())
+        .build()
+    };
+    let before = broker().await.unwrap();
+    before.initialize(1000).await.unwrap();
+    let request = || cashu_broker::SwapRequest {
+        client_id: None,
+        from_mint: mints[0].url().to_string(),
+        to_mint: mints[1].url().to_string(),
+        amount: 100,
+        quote_type: QuoteType::ExactIn,
+        client_public_key: None,
+        adaptor_point: None,
+    };
+    let good = before.request_quote(request()).await.unwrap();
+    let bad = before.request_quote(request()).await.unwrap();
+    db.record_client_signature(&bad.quote_id, "not hex").await.unwrap();
+
+    // The bad record is skipped instead of failing the whole restart
+    let after = broker().await.unwrap();
+    assert_eq!(after.restore_quotes().await.unwrap(), 1);
+    after.expire_quote(&good.quote_id).await.unwrap();
+    assert!(matches!(
+        after.expire_quote(&bad.quote_id).await,
+        Err(BrokerError::QuoteNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_fee_schedule() {
+    use cashu_broker::fees::{FeeSchedule, FeeTier, VolumeDiscount};
+
+    let mints = [
+        MockMint::start().await.expect("Failed to start mock mint"),
+        MockMint::start().await.expect("Failed to start mock mint"),
+    ];
+    let db = Database::new("sqlite::memory:")
+        .await
+        .unwrap()
+        .with_cipher(SecretCipher::new(&[42u8; 32]));
+    db.migrate().await.unwrap();
+    let mint = |url: &str| cashu_broker::MintConfig {
+        mint_url: url.to_string(),
+        name: url.to_string(),
+        unit: "sat".to_string(),
+    };
+    let broker = Broker::builder(cashu_broker::BrokerConfig {
+        mints: vec![mint(mints[0].url()), mint(mints[1].url())],
+        fee_rate: 0.01,
+        fees: FeeSchedule {
+            tiers: vec![
+                FeeTier {
+                    min_amount: 0,
+                    fee_rate: 0.02,
+                },
+                FeeTier {
+                    min_amount: 500,
+                    fee_rate: 0.01,
+                },
+            ],
+            min_fee: 3,
+            volume_discounts: vec![VolumeDiscount {
+                min_volume: 10_000,
+                discount: 0.5,
+            }],
+        },
+        ..Default::default()
+    })
+    .database(db.clone())
+    .build()
+    .await
+    .unwrap();
+    broker.initialize(5000).await.unwrap();
+    let request = |amount: u64, client_public_key: Option<Vec<u8>>| cashu_broker::SwapRequest {
+        client_id: None,
+        from_mint: mints[0].url().to_string(),
+        to_mint: mints[1].url().to_string(),
+        amount,
+        quote_type: QuoteType::ExactIn,
+        client_public_key,
+        adaptor_point: None,
+    };
+    let alice = vec![0x02; 33];
+    let bob = vec![0x03; 33];
+
+    // 2% of 100 sats rounds up to 2, below the minimum fee
+    let small = broker.request_quote(request(100, None)).await.unwrap();
+    assert_eq!(small.fee_rate, 0.02);
+    assert_eq!(small.fee, 3);
+    assert_eq!(small.output_amount, 97);
+
+    let large = broker.request_quote(request(1000, None)).await.unwrap();
+    assert_eq!(large.fee_rate, 0.01);
+    assert_eq!(large.fee, 10);
+
+    // Alice's volume over the last 30 days earns her half off
+    db.record_client_volume(&hex::encode(&alice), 12_000, chrono::Utc::now())
+        .await
+        .unwrap();
+    let discounted = broker
+        .request_quote(request(1000, Some(alice)))
+        .await
+        .unwrap();
+    assert_eq!(discounted.fee_rate, 0.005);
+    assert_eq!(discounted.fee, 5);
+
+    let full_price = broker.request_quote(request(1000, Some(bob))).await.unwrap();
+    assert_eq!(full_price.fee, 10);
+}
+
+#[tokio::test]
+async fn test_request_quote_invalid_amount() {
+    let (app, _db) = setup_test_app().await;
+
+    let request_body = json!({
+        "source_mint": "http://mint-a.test",
+        "target_mint": "http://mint-b.test",
+        "amount": 0  // Below minimum
+    });
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quote")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert!(response.status().is_client_error() || response.status().is_server_error());
+}
+
+#[tokio::test]
+async fn test_get_liquidity() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/liquidity")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = parse_json_response(response.into_body()).await;
+    assert!(body["mints"].is_array());
+    assert!(body["total_balance"].is_number());
+    assert_eq!(body["total_available"], 0);
+    assert_eq!(body["total_locked"], 0);
+}
+
+#[tokio::test]
+async fn test_get_info() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/info")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["api_version"], "1");
+    assert_eq!(body["pubkey"].as_str().unwrap().len(), 66);
+    assert_eq!(body["mints"].as_array().unwrap().len(), 2);
+    // Both directions between the two sat mints
+    assert_eq!(body["pairs"].as_array().unwrap().len(), 2);
+    assert_eq!(body["pairs"][0]["fee_rate"], 0.01);
+    assert_eq!(body["quote_expiry_seconds"], 300);
+    assert_eq!(body["features"]["webhooks"], false);
+
+    let codes = body["error_codes"].as_array().unwrap();
+    let expired = codes.iter().find(|c| c["code"] == "QUOTE_EXPIRED").unwrap();
+    assert_eq!(expired["retryable"], false);
+    let liquidity = codes
+        .iter()
+        .find(|c| c["code"] == "INSUFFICIENT_LIQUIDITY")
+        .unwrap();
+    assert_eq!(liquidity["retryable"], true);
+}
+
+#[tokio::test]
+async fn test_versioned_routes() {
+    let (app, _db) = setup_test_app().await;
+    let get = |uri: &str| {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+    };
+
+    let response = get("/v1/info").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-api-version"], "1");
+    assert!(response.headers().get("deprecation").is_none());
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["api_versions"], json!(["1"]));
+
+    // The unversioned path is a deprecated alias
+    let response = get("/info").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["deprecation"], "true");
+    assert_eq!(
+        response.headers()["link"],
+        "</v1/info>; rel=\"successor-version\""
+    );
+
+    let response = get("/v1/quotes").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // Probes are not versioned
+    assert_eq!(get("/health").await.unwrap().status(), StatusCode::OK);
+    assert_eq!(get("/v1/health").await.unwrap().status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_mints_health() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/mints/health")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = parse_json_response(response.into_body()).await;
+    let mints = body["mints"].as_array().unwrap();
+    assert_eq!(mints.len(), 2);
+    // Nothing has been checked or called yet
+    assert_eq!(mints[0]["available"], true);
+    assert_eq!(mints[0]["last_checked"], Value::Null);
+    assert_eq!(mints[0]["circuit"]["state"], "closed");
+}
+
+#[tokio::test]
+async fn test_get_metrics() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/metrics")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = parse_json_response(response.into_body()).await;
+    assert!(body["total_quotes"].is_number());
+    assert!(body["completed_swaps"].is_number());
+    assert!(body["failed_swaps"].is_number());
+    assert!(body["total_volume"].is_number());
+    assert!(body["total_fees"].is_number());
+    assert!(body["quotes_by_status"].is_object());
+}
+
+#[tokio::test]
+async fn test_get_metrics_window() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/metrics?from=2025-01-13T00%3A00%3A00Z&to=2025-01-20T00%3A00%3A00Z")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["total_quotes"], 0);
+}
+
+#[tokio::test]
+async fn test_list_quotes_empty() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quotes")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = parse_json_response(response.into_body()).await;
+    assert!(body.is_array());
+    assert_eq!(body.as_array().unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_list_quotes_etag_and_compression() {
+    let (app, _db) = setup_test_app().await;
+    let list = |if_none_match: Option<String>| {
+        let mut req = Request::builder().uri("/quotes");
+        if let Some(tag) = if_none_match {
+            req = req.header("if-none-match", tag);
+        }
+        app.clone().oneshot(req.body(Body::empty()).unwrap())
+    };
+
+    let response = list(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+    let response = list(Some(etag.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    // A new quote changes the listing, and so its tag
+    let request_body = json!({
+        "source_mint": "http://mint-a.test",
+        "target_mint": "http://mint-b.test",
+        "amount": 100
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/quote")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = list(Some(etag.clone())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()["etag"], etag.as_str());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quotes")
+                .header("accept-encoding", "gzip")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+}
+
+#[tokio::test]
+async fn test_list_quotes_with_filter() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quotes?status=completed&limit=10")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = parse_json_response(response.into_body()).await;
+    assert!(body.is_array());
+}
+
+#[tokio::test]
+async fn test_list_quotes_pair_and_date_filters() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/quotes?status=failed&target_mint=http%3A%2F%2Fmint-b.test&from=2025-01-13T00%3A00%3A00Z&to=2025-01-20T00%3A00%3A00Z")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_json_response(response.into_body()).await;
+    assert!(body.is_array());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quotes?from=last-week")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_list_quotes_pagination() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/quotes?limit=10")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-total-count"], "0");
+    assert!(response.headers().get("x-next-cursor").is_none());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quotes?cursor=not-a-cursor")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_get_nonexistent_quote() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quote/nonexistent-id")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_quote_secret_requires_completion() {
+    let (app, db) = setup_test_app().await;
+
+    let now = chrono::Utc::now();
+    db.create_quote(&cashu_broker::db::QuoteRecord {
+        id: "pending-quote".to_string(),
+        source_mint: "http://mint-a.test".to_string(),
+        target_mint: "http://mint-b.test".to_string(),
+        amount_in: 100,
+        amount_out: 99,
+        fee: 1,
+        fee_rate: 0.01,
+        mint_fee: 0,
+        exchange_rate: None,
+        broker_pubkey: "02abcd1234".to_string(),
+        adaptor_point: "03efgh5678".to_string(),
+        tweaked_pubkey: "02ijkl9012".to_string(),
+        status: SwapStatus::Pending,
+        created_at: now.to_rfc3339(),
+        expires_at: (now + chrono::Duration::seconds(300)).to_rfc3339(),
+        accepted_at: None,
+        completed_at: None,
+        user_pubkey: None,
+        error_message: None,
+        callback_url: None,
+        job_status: None,
+        claim_order: Default::default(),
+        extended_at: None,
+    })
+    .await
+    .unwrap();
+
+    let secret = |id: &str| {
+        Request::builder()
+            .uri(format!("/quote/{}/secret", id))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // Not revealed before the broker has claimed the client's tokens
+    let response = app.clone().oneshot(secret("pending-quote")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let response = app.oneshot(secret("nonexistent-id")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_request_quote_callback_requires_webhooks() {
+    let (app, _db) = setup_test_app().await;
+
+    let request_body = json!({
+        "source_mint": "http://mint-a.test",
+        "target_mint": "http://mint-b.test",
+        "amount": 100,
+        "callback_url": "https://shop.example.com/hooks"
+    });
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quote")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // The test app has no WEBHOOK_SECRET
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_idempotency_key() {
+    let (app, _db) = setup_test_app().await;
+
+    let accept = |key: &str| {
+        Request::builder()
+            .uri("/quote/nonexistent-id/accept")
+            .method("POST")
+            .header("content-type", "application/json")
+            .header("idempotency-key", key)
+            .body(Body::from(r#"{"source_proofs":"[]"}"#))
+            .unwrap()
+    };
+
+    // Failed requests release their key, so a retry runs again
+    let response = app.clone().oneshot(accept("retry-1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = app.clone().oneshot(accept("retry-1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(response.headers().get("idempotent-replayed").is_none());
+
+    let response = app.oneshot(accept("")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_swap_steps_need_owner_signature() {
+    use cashu_broker::nip98::AuthEvent;
+    use cdk::nuts::SecretKey;
+    use schnorr_fun::fun::Scalar;
+
+    let (app, _db, mints) = setup_mock_mint_app(1000).await;
+    let owner = SecretKey::generate();
+    let request_body = json!({
+        "source_mint": mints[0].url(),
+        "target_mint": mints[1].url(),
+        "amount": 100,
+        "user_pubkey": owner.public_key().to_hex(),
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/quote")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_json_response(response.into_body()).await;
+    let path = format!("/quote/{}/accept", body["quote"]["id"].as_str().unwrap());
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let accept_body = br#"{"source_proofs":"not proofs"}"#;
+    let accept = |signer: Option<&SecretKey>| {
+        let mut request = Request::builder()
+            .uri(&path)
+            .method("POST")
+            .header("content-type", "application/json");
+        if let Some(key) = signer {
+            let secret = Scalar::from_bytes(key.to_secret_bytes())
+                .and_then(|s| s.non_zero())
+                .unwrap();
+            let url = format!("https://broker.test{}", path);
+            let event = AuthEvent::sign(secret, "POST", &url, accept_body, now);
+            request = request.header("authorization", event.to_header());
+        }
+        app.clone()
+            .oneshot(request.body(Body::from(&accept_body[..])).unwrap())
+    };
+
+    let response = accept(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = accept(Some(&SecretKey::generate())).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body = parse_json_response(response.into_body()).await;
+    assert!(body["error"].as_str().unwrap().contains("another key"));
+
+    // The owner gets past the check, to the handler rejecting the proofs
+    let response = accept(Some(&owner)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_client_quote_history() {
+    use cashu_broker::nip98::AuthEvent;
+    use cdk::nuts::SecretKey;
+    use schnorr_fun::fun::Scalar;
+
+    let (app, _db, mints) = setup_mock_mint_app(1000).await;
+    let client = SecretKey::generate();
+    let other = SecretKey::generate();
+    for (key, amount) in [(&client, 100), (&client, 200), (&other, 300), (&client, 400)] {
+        let request_body = json!({
+            "source_mint": mints[0].url(),
+            "target_mint": mints[1].url(),
+            "amount": amount,
+            "user_pubkey": key.public_key().to_hex(),
+        });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/quote")
+                    .method("POST")
+                    .header("content-type", "application/json")
+                    .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let path = format!("/client/{}/quotes", client.public_key().to_hex());
+    let history = |query: String, signer: Option<&SecretKey>| {
+        let mut request = Request::builder().uri(format!("{}{}", path, query));
+        if let Some(key) = signer {
+            let secret = Scalar::from_bytes(key.to_secret_bytes())
+                .and_then(|s| s.non_zero())
+                .unwrap();
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            let url = format!("https://broker.test{}", path);
+            let event = AuthEvent::sign(secret, "GET", &url, b"", now);
+            request = request.header("authorization", event.to_header());
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    // Only for the holder of the key
+    let response = history(String::new(), None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = history(String::new(), Some(&other)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Newest first, in pages
+    let response = history("?limit=2".to_string(), Some(&client)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["x-total-count"], "3");
+    let cursor = response.headers()["x-next-cursor"].to_str().unwrap().to_string();
+    let body = parse_json_response(response.into_body()).await;
+    let amounts: Vec<&Value> = body.as_array().unwrap().iter().map(|q| &q["amount_in"]).collect();
+    assert_eq!(amounts, [&json!(400), &json!(200)]);
+
+    let response = history(format!("?limit=2&cursor={}", cursor), Some(&client))
+        .await
+        .unwrap();
+    assert!(response.headers().get("x-next-cursor").is_none());
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["amount_in"], 100);
+}
+
+#[tokio::test]
+async fn test_cors_headers() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/health")
+                .header("origin", "http://example.com")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // CORS should be configured
+    assert!(response.headers().contains_key("access-control-allow-origin"));
+}
+
+#[tokio::test]
+async fn test_request_quote_same_mint_error() {
+    let (app, _db) = setup_test_app().await;
+
+    let request_body = json!({
+        "source_mint": "http://mint-a.test",
+        "target_mint": "http://mint-a.test",  // Same mint!
+        "amount": 100
+    });
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quote")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // Should return error for same-mint swap
+    assert!(response.status().is_client_error() || response.status().is_server_error());
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["code"], "SAME_MINT");
+    assert_eq!(body["retryable"], false);
+    assert!(body.get("retry_after").is_none());
+}
+
+#[tokio::test]
+async fn test_request_quote_invalid_fields() {
+    let (app, _db) = setup_test_app().await;
+
+    let request_body = json!({
+        "source_mint": "mint-a.test",
+        "target_mint": "http://mint-b.test",
+        "amount": 100,
+        "user_pubkey": "02abcd",
+        "adaptor_point": format!("02{}", "aa".repeat(32)),
+    });
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quote")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["code"], "VALIDATION_FAILED");
+    assert_eq!(body["retryable"], false);
+    let fields: Vec<&str> = body["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, ["source_mint", "user_pubkey"]);
+}
+
+#[tokio::test]
+async fn test_request_body_limit() {
+    let (app, _db) = setup_test_app().await;
+
+    // Far more proofs than any accept needs
+    let request_body = json!({ "source_proofs": "x".repeat(2 * 1024 * 1024) });
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quote/some-quote/accept")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["code"], "PAYLOAD_TOO_LARGE");
+}
+
+#[tokio::test]
+async fn test_request_quote_unsupported_mint() {
+    let (app, _db) = setup_test_app().await;
+
+    let request_body = json!({
+        "source_mint": "http://unknown-mint.test",
+        "target_mint": "http://mint-b.test",
+        "amount": 100
+    });
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/quote")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&request_body).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // Should return error for unsupported mint
+    assert!(response.status().is_client_error() || response.status().is_server_error());
+}
+
+#[tokio::test]
+async fn test_admin_requires_token() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/admin/config")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/admin/config")
+                .header("authorization", "Bearer wrong-token")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_dashboard() {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+    let (app, _db) = setup_test_app().await;
+    let dashboard = |authorization: Option<String>| {
+        let mut req = Request::builder().uri("/dashboard");
+        if let Some(value) = authorization {
+            req = req.header("authorization", value);
+        }
+        app.clone().oneshot(req.body(Body::empty()).unwrap())
+    };
+
+    // Browsers are asked for the token
+    let response = dashboard(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().contains_key("www-authenticate"));
+    let wrong = format!("Basic {}", BASE64.encode("operator:wrong-token"));
+    let response = dashboard(Some(wrong)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let basic = format!("Basic {}", BASE64.encode(format!("operator:{}", TEST_ADMIN_TOKEN)));
+    let response = dashboard(Some(basic)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let html = String::from_utf8(body.to_vec()).unwrap();
+    assert!(html.contains("http://mint-a.test"));
+    assert!(html.contains("Recent quotes"));
+
+    let response = dashboard(Some(format!("Bearer {}", TEST_ADMIN_TOKEN)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_admin_set_fee_rate() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/admin/fee-rate")
+                .method("PUT")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&json!({ "fee_rate": 0.02 })).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["fee_rate"], 0.02);
+
+    // Out-of-range fee rates are rejected
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/admin/fee-rate")
+                .method("PUT")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&json!({ "fee_rate": 1.5 })).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_admin_mint_registry() {
+    let db = Database::new("sqlite::memory:").await.unwrap();
+    db.migrate().await.unwrap();
+    let mint = |url: &str, name: &str| cashu_broker::MintConfig {
+        mint_url: url.to_string(),
+        name: name.to_string(),
+        unit: "sat".to_string(),
+    };
+    let config = cashu_broker::BrokerConfig {
+        mints: vec![
+            mint("http://mint-a.test", "Mint A"),
+            mint("http://mint-b.test", "Mint B"),
+        ],
+        ..Default::default()
+    };
+    let urls = |broker: &Broker| -> Vec<String> {
+        broker
+            .get_config()
+            .mints
+            .into_iter()
+            .map(|m| m.mint_url)
+            .collect()
+    };
+
+    let broker = Arc::new(
+        Broker::builder(config.clone())
+            .database(db.clone())
+            .build()
+            .await
+            .unwrap(),
+    );
+    let app = api::create_router(
+        AppState {
+            broker: broker.clone(),
+            db: db.clone(),
+        },
+        vec!["*".to_string()],
+        Some(TEST_ADMIN_TOKEN.to_string()),
+        None,
+        false,
+        true,
+        RequestLimits::default(),
+    );
+    let admin = |method: &str, uri: &str, body: Option<Value>| {
+        Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+            .header("content-type", "application/json")
+            .body(match body {
+                Some(body) => Body::from(serde_json::to_vec(&body).unwrap()),
+                None => Body::empty(),
+            })
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(admin(
+            "POST",
+            "/admin/mints",
+            Some(json!({ "mint_url": "http://mint-c.test", "name": "Mint C", "unit": "sat" })),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let response = app
+        .clone()
+        .oneshot(admin("DELETE", "/admin/mints/http%3A%2F%2Fmint-a.test", None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(urls(&broker), ["http://mint-b.test", "http://mint-c.test"]);
+
+    let response = app
+        .oneshot(admin(
+            "POST",
+            "/admin/mints",
+            Some(json!({ "mint_url": "not a url", "name": "Broken", "unit": "sat" })),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Neither a reload nor a restart with the original configuration undoes them
+    broker.reload_config(config.clone()).await;
+    assert_eq!(urls(&broker), ["http://mint-b.test", "http://mint-c.test"]);
+
+    let restarted = Broker::builder(config)
+        .database(db.clone())
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(urls(&restarted), ["http://mint-b.test", "http://mint-c.test"]);
+}
+
+#[tokio::test]
+async fn test_admin_audit_log() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/admin/fee-rate")
+                .method("PUT")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .header("content-type", "application/json")
+                .header("x-admin-actor", "alice")
+                .header("x-request-id", "change-42")
+                .body(Body::from(serde_json::to_vec(&json!({ "fee_rate": 0.02 })).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Rejected changes are not recorded
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/admin/fee-rate")
+                .method("PUT")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&json!({ "fee_rate": 1.5 })).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/admin/audit-log?action=set_fee_rate")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = parse_json_response(response.into_body()).await;
+    let entries = body.as_array().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["actor"], "alice");
+    assert_eq!(entries[0]["action"], "set_fee_rate");
+    assert_eq!(entries[0]["before"], json!({ "fee_rate": 0.01 }));
+    assert_eq!(entries[0]["after"], json!({ "fee_rate": 0.02 }));
+    assert_eq!(entries[0]["request_id"], "change-42");
+}
+
+#[tokio::test]
+async fn test_admin_deposit_rejects_invalid_token() {
+    let (app, _db) = setup_test_app().await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/admin/liquidity/http%3A%2F%2Fmint-a.test/deposit")
+                .method("POST")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&json!({ "token": "not-a-token" })).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_admin_accounting_summary() {
+    let (app, db) = setup_test_app().await;
+
+    for (kind, mint, amount) in [
+        (EntryKind::SwapFee, "http://mint-a.test", 10),
+        (EntryKind::MintFee, "http://mint-a.test", 2),
+        (EntryKind::LightningFee, "http://mint-b.test", 3),
+    ] {
+        db.record_ledger_entry(&LedgerEntry::new(kind, mint, "sat", amount, None))
+            .await
+            .expect("Failed to record ledger entry");
+    }
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/admin/accounting/summary")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["by_mint"].as_array().unwrap().len(), 2);
+    assert_eq!(body["by_mint"][0]["mint_url"], "http://mint-a.test");
+    assert_eq!(body["by_mint"][0]["net"], 8);
+    assert_eq!(body["by_unit"][0]["unit"], "sat");
+    assert_eq!(body["by_unit"][0]["swap_fees"], 10);
+    assert_eq!(body["by_unit"][0]["lightning_fees"], 3);
+    assert_eq!(body["by_unit"][0]["net"], 5);
+
+    // Nothing recorded yet in a window starting tomorrow
+    let tomorrow = (chrono::Utc::now() + chrono::Duration::days(1)).format("%Y-%m-%dT%H:%M:%SZ");
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/admin/accounting/summary?from={}", tomorrow))
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = parse_json_response(response.into_body()).await;
+    assert!(body["by_unit"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_admin_export_quotes() {
+    let (app, db) = setup_test_app().await;
+
+    let now = chrono::Utc::now();
+    let quotes = [("quote-1", None), ("quote-2", Some("Mint said \"no\", twice"))];
+    for (id, error_message) in quotes {
+        db.create_quote(&cashu_broker::db::QuoteRecord {
+            id: id.to_string(),
+            source_mint: "http://mint-a.test".to_string(),
+            target_mint: "http://mint-b.test".to_string(),
+            amount_in: 100,
+            amount_out: 99,
+            fee: 1,
+            fee_rate: 0.01,
+            mint_fee: 0,
+            exchange_rate: None,
+            broker_pubkey: "02abcd1234".to_string(),
+            adaptor_point: "03efgh5678".to_string(),
+            tweaked_pubkey: "02ijkl9012".to_string(),
+            status: SwapStatus::Pending,
+            created_at: now.to_rfc3339(),
+            expires_at: (now + chrono::Duration::seconds(300)).to_rfc3339(),
+            accepted_at: None,
+            completed_at: None,
+            user_pubkey: None,
+            error_message: error_message.map(str::to_string),
+            callback_url: None,
+            job_status: None,
+            claim_order: Default::default(),
+            extended_at: None,
+        })
+        .await
+        .unwrap();
+    }
+
+    let export = |query: &str| {
+        Request::builder()
+            .uri(format!("/admin/export/quotes{}", query))
+            .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+            .body(Body::empty())
+            .unwrap()
+    };
+    let read_body = |body: Body| async move {
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    };
+
+    // CSV by default, with a header line
+    let response = app.clone().oneshot(export("")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+    let csv = read_body(response.into_body()).await;
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("id,status,"));
+    assert!(lines[1].starts_with("quote-1,pending,"));
+    assert!(lines[2].ends_with(",\"Mint said \"\"no\"\", twice\""));
+
+    let response = app.clone().oneshot(export("?format=jsonl")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+    let jsonl = read_body(response.into_body()).await;
+    let rows: Vec<Value> = jsonl
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["id"], "quote-1");
+    assert_eq!(rows[0]["fee"], 1);
+
+    // A window with no quotes still gets the header
+    let response = app
+        .clone()
+        .oneshot(export("?to=2020-01-01T00:00:00Z"))
+        .await
+        .unwrap();
+    assert_eq!(read_body(response.into_body()).await.lines().count(), 1);
+
+    let response = app.oneshot(export("?format=xml")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_rate_limit() {
+    let (app, _db) = setup_test_app_with(
+        Some(RateLimitConfig {
+            requests_per_minute: 1,
+            burst: 2,
+            trust_proxy: true,
+        }),
+        false,
+    )
+    .await;
+
+    let get_liquidity = |ip: &str| {
+        Request::builder()
+            .uri("/liquidity")
+            .header("x-forwarded-for", ip)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    for _ in 0..2 {
+        let response = app.clone().oneshot(get_liquidity("10.0.0.1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let response = app.clone().oneshot(get_liquidity("10.0.0.1")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+    let body = parse_json_response(response.into_body()).await;
+    assert_eq!(body["code"], "RATE_LIMITED");
+
+    // Other clients and health checks are unaffected
+    let response = app.clone().oneshot(get_liquidity("10.0.0.2")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/health")
+                .header("x-forwarded-for", "10.0.0.1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_key_required() {
+    let (app, _db) = setup_test_app_with(None, true).await;
+
+    let list_quotes = |key: Option<&str>| {
+        let mut request = Request::builder().uri("/quotes");
+        if let Some(key) = key {
+            request = request.header("x-api-key", key);
+        }
+        request.body(Body::empty()).unwrap()
+    };
+
+    let response = app.clone().oneshot(list_quotes(None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app.clone().oneshot(list_quotes(Some("cbk_wrong"))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Swap routes stay public
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/quote/nonexistent-id")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Issue a key through the admin API
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/admin/api-keys")
+                .method("POST")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&json!({ "name": "dashboard" })).unwrap()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = parse_json_response(response.into_body()).await;
+    let key = body["key"].as_str().unwrap().to_string();
+    let id = body["id"].as_str().unwrap().to_string();
+
+    let response = app.clone().oneshot(list_quotes(Some(&key))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // Revoked keys stop working
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/admin/api-keys/{}", id))
+                .method("DELETE")
+                .header("authorization", format!("Bearer {}", TEST_ADMIN_TOKEN))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = app.oneshot(list_quotes(Some(&key))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
 }